@@ -0,0 +1,34 @@
+//! Fuzzes the AML walker and patcher with whole tables.
+//!
+//! Walks and decodes every object of the table, then patches every operand with its own value, which must neither
+//! panic nor change the table.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use patina_acpi::aml::{AmlPath, find_path, iter_table};
+
+fuzz_target!(|data: &[u8]| {
+    let mut table = data.to_vec();
+    let Ok(handles) = iter_table(&table, 1) else {
+        return;
+    };
+    for handle in handles {
+        let _ = handle.name(&table);
+        if let Ok(operands) = handle.operands(&table) {
+            for (index, operand) in operands.iter().enumerate() {
+                let _ = handle.set_operand(&mut table, index, operand);
+            }
+        }
+    }
+    assert_eq!(table, data);
+
+    let path: AmlPath = "\\_SB.PCI0._CRS".parse().unwrap();
+    let _ = find_path(&table, 1, &path);
+});
@@ -0,0 +1,234 @@
+//! ACPI table types
+//!
+//! Defines the generic [`AcpiTable`] passed through the ACPI service, along with the system description table header
+//! and the signatures of the tables that the ACPI provider links together.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+use zerocopy::FromBytes;
+use zerocopy_derive::*;
+
+use crate::error::AcpiError;
+
+/// Unique key returned when a table is installed, used to uninstall it later.
+pub type TableKey = usize;
+
+/// Bitmask of the ACPI versions a table is exposed for (`EFI_ACPI_TABLE_VERSION`).
+pub type AcpiVersion = u32;
+
+/// ACPI 2.0 table version bit.
+pub const ACPI_TABLE_VERSION_2_0: AcpiVersion = 1 << 2;
+/// ACPI 3.0 table version bit.
+pub const ACPI_TABLE_VERSION_3_0: AcpiVersion = 1 << 3;
+/// ACPI 4.0 table version bit.
+pub const ACPI_TABLE_VERSION_4_0: AcpiVersion = 1 << 4;
+/// ACPI 5.0 (and later) table version bit.
+pub const ACPI_TABLE_VERSION_5_0: AcpiVersion = 1 << 5;
+
+/// Versions every installed table is exposed for. Only XSDT-based (ACPI 2.0+) publication is supported.
+pub const ACPI_EXPOSED_VERSIONS: AcpiVersion =
+    ACPI_TABLE_VERSION_2_0 | ACPI_TABLE_VERSION_3_0 | ACPI_TABLE_VERSION_4_0 | ACPI_TABLE_VERSION_5_0;
+
+/// Fixed ACPI Description Table signature.
+pub const FADT_SIGNATURE: [u8; 4] = *b"FACP";
+/// Firmware ACPI Control Structure signature.
+pub const FACS_SIGNATURE: [u8; 4] = *b"FACS";
+/// Differentiated System Description Table signature.
+pub const DSDT_SIGNATURE: [u8; 4] = *b"DSDT";
+/// Secondary System Description Table signature.
+pub const SSDT_SIGNATURE: [u8; 4] = *b"SSDT";
+/// Root System Description Table signature.
+pub const RSDT_SIGNATURE: [u8; 4] = *b"RSDT";
+/// Extended System Description Table signature.
+pub const XSDT_SIGNATURE: [u8; 4] = *b"XSDT";
+/// Root System Description Pointer signature.
+pub const RSDP_SIGNATURE: [u8; 8] = *b"RSD PTR ";
+
+/// Size of the common FACS prefix (signature and length) that every installed table must contain.
+const MIN_TABLE_SIZE: usize = 8;
+
+/// System description table header
+///
+/// The 36-byte header shared by every ACPI system description table except the FACS.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+pub struct AcpiTableHeader {
+    /// Table signature (e.g. `FACP`)
+    pub signature: [u8; 4],
+    /// Length of the table in bytes, including the header
+    pub length: u32,
+    /// Revision of the table structure
+    pub revision: u8,
+    /// Byte that makes the sum of the whole table zero
+    pub checksum: u8,
+    /// OEM identifier
+    pub oem_id: [u8; 6],
+    /// OEM table identifier
+    pub oem_table_id: [u8; 8],
+    /// OEM revision of the table
+    pub oem_revision: u32,
+    /// Vendor ID of the tool that created the table
+    pub creator_id: u32,
+    /// Revision of the tool that created the table
+    pub creator_revision: u32,
+}
+
+impl AcpiTableHeader {
+    /// Offset of the checksum byte within the header.
+    pub(crate) const CHECKSUM_OFFSET: usize = 9;
+}
+
+/// Returns the checksum byte that makes `bytes` sum to zero, assuming the checksum byte itself is zero.
+pub fn checksum(bytes: &[u8]) -> u8 {
+    0u8.wrapping_sub(bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)))
+}
+
+/// A generic ACPI table
+///
+/// Holds the raw bytes of a table, including its header. Tables returned by the ACPI service also carry the key they
+/// were installed with.
+///
+/// Specific tables should convert to and from `AcpiTable` with `TryFrom`/`From`, using [`AcpiTable::header`] and
+/// [`AcpiTable::data`] to access the common header and the table specific payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcpiTable {
+    bytes: Vec<u8>,
+    table_key: TableKey,
+}
+
+impl AcpiTable {
+    /// Creates a table from a header and the payload that follows it.
+    ///
+    /// The header length and checksum are computed from the payload.
+    pub fn new(mut header: AcpiTableHeader, data: &[u8]) -> Self {
+        header.length = (size_of::<AcpiTableHeader>() + data.len()) as u32;
+        header.checksum = 0;
+
+        let mut bytes = Vec::with_capacity(header.length as usize);
+        bytes.extend_from_slice(zerocopy::IntoBytes::as_bytes(&header));
+        bytes.extend_from_slice(data);
+        bytes[AcpiTableHeader::CHECKSUM_OFFSET] = checksum(&bytes);
+
+        Self { bytes, table_key: 0 }
+    }
+
+    /// Creates a table from its raw bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AcpiError::TableTooSmall`] if the buffer cannot hold the table header, and
+    /// [`AcpiError::LengthMismatch`] if the length in the header does not match the buffer size.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AcpiError> {
+        if bytes.len() < MIN_TABLE_SIZE {
+            return Err(AcpiError::TableTooSmall);
+        }
+
+        let table = Self { bytes: bytes.to_vec(), table_key: 0 };
+        if table.signature() != FACS_SIGNATURE && bytes.len() < size_of::<AcpiTableHeader>() {
+            return Err(AcpiError::TableTooSmall);
+        }
+        if table.length() as usize != bytes.len() {
+            return Err(AcpiError::LengthMismatch);
+        }
+
+        Ok(table)
+    }
+
+    pub(crate) fn with_key(mut self, table_key: TableKey) -> Self {
+        self.table_key = table_key;
+        self
+    }
+
+    /// Returns the table signature.
+    pub fn signature(&self) -> [u8; 4] {
+        [self.bytes[0], self.bytes[1], self.bytes[2], self.bytes[3]]
+    }
+
+    /// Returns the table length recorded in the table.
+    pub fn length(&self) -> u32 {
+        u32::from_le_bytes([self.bytes[4], self.bytes[5], self.bytes[6], self.bytes[7]])
+    }
+
+    /// Returns the system description table header, or `None` for the FACS which has no such header.
+    pub fn header(&self) -> Option<AcpiTableHeader> {
+        if self.signature() == FACS_SIGNATURE {
+            return None;
+        }
+        AcpiTableHeader::read_from_prefix(&self.bytes).ok().map(|(header, _)| header)
+    }
+
+    /// Returns the table payload that follows the system description table header.
+    ///
+    /// For the FACS, this is everything after its signature and length.
+    pub fn data(&self) -> &[u8] {
+        if self.signature() == FACS_SIGNATURE {
+            &self.bytes[MIN_TABLE_SIZE..]
+        } else {
+            &self.bytes[size_of::<AcpiTableHeader>()..]
+        }
+    }
+
+    /// Returns the raw bytes of the whole table.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the key the table was installed with, or 0 for a table that has not been installed.
+    pub fn table_key(&self) -> TableKey {
+        self.table_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::vec;
+
+    #[test]
+    fn test_new_sets_length_and_checksum() {
+        let header = AcpiTableHeader { signature: *b"SSDT", revision: 2, oem_id: *b"PATINA", ..Default::default() };
+        let table = AcpiTable::new(header, &[1, 2, 3, 4]);
+
+        assert_eq!(table.signature(), *b"SSDT");
+        assert_eq!(table.length(), 40);
+        assert_eq!(table.data(), &[1, 2, 3, 4]);
+        assert_eq!(checksum(table.as_bytes()), 0);
+        assert_eq!({ table.header().unwrap().oem_id }, *b"PATINA");
+        assert_eq!(table.table_key(), 0);
+    }
+
+    #[test]
+    fn test_from_bytes_validates_length() {
+        assert_eq!(AcpiTable::from_bytes(&[0; 4]), Err(AcpiError::TableTooSmall));
+
+        // A non-FACS table must hold a full header.
+        let mut bytes = vec![0u8; 16];
+        bytes[..4].copy_from_slice(b"SSDT");
+        bytes[4] = 16;
+        assert_eq!(AcpiTable::from_bytes(&bytes), Err(AcpiError::TableTooSmall));
+
+        let mut bytes =
+            AcpiTable::new(AcpiTableHeader { signature: *b"SSDT", ..Default::default() }, &[0; 4]).as_bytes().to_vec();
+        bytes.push(0);
+        assert_eq!(AcpiTable::from_bytes(&bytes), Err(AcpiError::LengthMismatch));
+    }
+
+    #[test]
+    fn test_facs_has_no_header() {
+        let mut bytes = vec![0u8; 64];
+        bytes[..4].copy_from_slice(&FACS_SIGNATURE);
+        bytes[4] = 64;
+
+        let table = AcpiTable::from_bytes(&bytes).unwrap();
+        assert_eq!(table.header(), None);
+        assert_eq!(table.data().len(), 56);
+    }
+}
@@ -0,0 +1,339 @@
+//! AML (ACPI Machine Language) parsing and patching
+//!
+//! This module walks and patches the AML bytecode of a DSDT or SSDT as described in RFC 0020. It only knows the byte
+//! layout of each opcode and never evaluates AML, which is enough to find named objects and rewrite their values in
+//! place, such as the placeholder in `Name (VAL0, 0x80)` or the bytes of a `_CRS` resource template.
+//!
+//! The functions here operate on a byte buffer holding a whole table, independently of the ACPI provider, so they can
+//! be used on tables before they are installed or in host-side tools. Components patch installed tables through the
+//! [`AmlParser`](crate::service::AmlParser) service instead.
+//!
+//! # AML Tree
+//!
+//! Every AML object is a node: its opcode, an optional package length, its operands, and for objects such as `Scope`,
+//! `Device`, and `Method`, a list of child objects. [`AmlHandle::child`] returns the first child of a node and
+//! [`AmlHandle::sibling`] the next node within the same parent; the top level objects of a table are siblings of the
+//! first one.
+//!
+//! ```text
+//! Scope (\_SB)                     <- handle returned by AmlHandle::open
+//! ├── Device (DEV0)                <- child
+//! │   ├── Name (VAL0, 0x80)        <- child
+//! │   └── Device (CHLD)            <- sibling
+//! └── ...
+//! ```
+//!
+//! Patching never moves AML, so a new operand must encode to the same size as the one it replaces.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+mod encoding;
+mod handle;
+mod name;
+
+pub use encoding::{AmlByteEncoding, AmlOpAttributes, AmlOpcode, AmlOperandKind, lookup};
+pub use handle::{AmlHandle, AmlOperand, find_path, iter_table};
+pub use name::{AmlNameString, AmlPath, NameSeg};
+
+pub(crate) use handle::{OperandSlot, walk};
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        acpi_table::{AcpiTableHeader, checksum},
+        error::AmlError,
+    };
+    extern crate std;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use std::{string::ToString, vec, vec::Vec};
+
+    const RFC0020_EXAMPLE: &[u8] = include_bytes!("../resources/test/aml/rfc0020_example.aml");
+    const PLATFORM_SSDT: &[u8] = include_bytes!("../resources/test/aml/platform_ssdt.aml");
+    const FIRECRACKER_DSDT: &[u8] = include_bytes!("../resources/test/aml/firecracker_dsdt.aml");
+
+    const CORPUS: [&[u8]; 3] = [RFC0020_EXAMPLE, PLATFORM_SSDT, FIRECRACKER_DSDT];
+
+    fn path(path: &str) -> AmlPath {
+        path.parse().unwrap()
+    }
+
+    fn find(table: &[u8], name: &str) -> AmlHandle {
+        find_path(table, 1, &path(name)).unwrap().unwrap_or_else(|| panic!("{name} not found"))
+    }
+
+    #[test]
+    fn test_corpus_tables_are_valid() {
+        for table in CORPUS {
+            assert_eq!(checksum(table), 0);
+            assert_eq!(u32::from_le_bytes(table[4..8].try_into().unwrap()) as usize, table.len());
+        }
+    }
+
+    #[test]
+    fn test_walk_covers_the_whole_stream() {
+        for table in CORPUS {
+            let handles = iter_table(table, 1).unwrap();
+            assert!(!handles.is_empty());
+
+            // The top level objects span the AML stream exactly.
+            let mut top_level = vec![AmlHandle::open(table, 1).unwrap()];
+            while let Some(sibling) = top_level.last().unwrap().sibling(table).unwrap() {
+                top_level.push(sibling);
+            }
+            assert_eq!(top_level[0].offset(), size_of::<AcpiTableHeader>());
+            let last = top_level.last().unwrap();
+            assert_eq!(last.offset() + last.size(), table.len());
+
+            // Every object decodes and lies within the table, and preorder visits offsets in increasing order.
+            for handle in &handles {
+                handle.operands(table).unwrap();
+                assert!(handle.offset() + handle.size() <= table.len());
+            }
+            assert!(handles.windows(2).all(|pair| pair[0].offset() < pair[1].offset()));
+        }
+    }
+
+    #[test]
+    fn test_rfc0020_example_tree() {
+        let table = RFC0020_EXAMPLE;
+        let scope = AmlHandle::open(table, 1).unwrap();
+        assert_eq!(scope.opcode(), AmlOpcode::SCOPE);
+        assert_eq!(scope.name(table).unwrap().unwrap().to_string(), "\\_SB_");
+        assert_eq!(scope.sibling(table).unwrap(), None);
+
+        let dev0 = scope.child(table).unwrap().unwrap();
+        assert_eq!(dev0.opcode(), AmlOpcode::DEVICE);
+        let val0 = dev0.child(table).unwrap().unwrap();
+        assert_eq!(val0.opcode(), AmlOpcode::NAME);
+        let chld = val0.sibling(table).unwrap().unwrap();
+        assert_eq!(chld.opcode(), AmlOpcode::DEVICE);
+        assert_eq!(chld.sibling(table).unwrap(), None);
+        assert_eq!(val0.child(table).unwrap(), None);
+
+        assert_eq!(find(table, "\\_SB.DEV0.CHLD.VAL1"), chld.child(table).unwrap().unwrap());
+        assert_eq!(find_path(table, 1, &path("\\_SB.DEV0.VAL1")).unwrap(), None);
+    }
+
+    #[test]
+    fn test_patch_rfc0020_val0() {
+        let mut table = RFC0020_EXAMPLE.to_vec();
+        let val0 = find(&table, "\\_SB.DEV0.VAL0");
+
+        let operands = val0.operands(&table).unwrap();
+        assert_eq!(operands[0], AmlOperand::Name(AmlNameString::parse(b"VAL0").unwrap().0));
+        assert_eq!(operands[1], AmlOperand::Integer(0x80));
+
+        val0.set_operand(&mut table, 1, &AmlOperand::Integer(0x99)).unwrap();
+        assert_eq!(val0.operands(&table).unwrap()[1], AmlOperand::Integer(0x99));
+
+        // Only the value byte changed.
+        let changed: Vec<usize> = (0..table.len()).filter(|index| table[*index] != RFC0020_EXAMPLE[*index]).collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(table[changed[0] - 1], 0x0A);
+
+        assert_eq!(val0.set_operand(&mut table, 1, &AmlOperand::Integer(0x100)), Err(AmlError::OperandSizeMismatch));
+        assert_eq!(
+            val0.set_operand(&mut table, 1, &AmlOperand::String("X".into())),
+            Err(AmlError::OperandTypeMismatch)
+        );
+        assert_eq!(val0.set_operand(&mut table, 2, &AmlOperand::Integer(0)), Err(AmlError::InvalidOperandIndex));
+    }
+
+    #[test]
+    fn test_platform_ssdt_named_objects() {
+        let table = PLATFORM_SSDT;
+        let integer = |name: &str| find(table, name).operands(table).unwrap()[1].clone();
+
+        assert_eq!(integer("\\_SB.PCI0.MMIO"), AmlOperand::Integer(0xE000_0000));
+        assert_eq!(integer("\\_SB.PCI0.PLAT"), AmlOperand::Integer(0x1_0000_0000));
+        assert_eq!(integer("\\_SB.PCI0.WRD0"), AmlOperand::Integer(0x1234));
+        assert_eq!(integer("\\_SB.UAR0._UID"), AmlOperand::Integer(1));
+        assert_eq!(integer("\\_SB.PCI0.STR0"), AmlOperand::String("Patina".into()));
+        assert_eq!(integer("\\_SB.UAR0._HID"), AmlOperand::String("PNP0501".into()));
+        assert_eq!(integer("\\_SB.PCI0.BUF0"), AmlOperand::Buffer(vec![1, 2, 3, 4]));
+
+        for name in [
+            "\\_SB.UAR0._STA",
+            "\\_SB.PWRB._PRW",
+            "\\_SB.GPIO",
+            "\\_SB.MUT0",
+            "\\_SB.RDG0",
+            "\\_SB.CPU0",
+            "\\_SB.PRS0._OFF",
+            "\\_SB.TZ00._TMP",
+            "\\_SB.PCI0.BF00",
+            "\\_SB.PCI0._INI",
+        ] {
+            find(table, name);
+        }
+
+        // Externals declare nothing, so the PCI0 scope is the only object at that path.
+        assert_eq!(find(table, "\\_SB.PCI0").opcode(), AmlOpcode::SCOPE);
+
+        let prw = find(table, "\\_SB.PWRB._PRW");
+        let AmlOperand::Object(package) = prw.operands(table).unwrap()[1].clone() else { panic!("expected package") };
+        assert_eq!(package.opcode(), AmlOpcode::PACKAGE);
+        let first = package.child(table).unwrap().unwrap();
+        assert_eq!(first.operands(table).unwrap(), [AmlOperand::Integer(0x0D)]);
+        assert_eq!(first.sibling(table).unwrap().unwrap().operands(table).unwrap(), [AmlOperand::Integer(0x04)]);
+    }
+
+    #[test]
+    fn test_patch_crs_resource_template() {
+        let mut table = PLATFORM_SSDT.to_vec();
+        let crs = find(&table, "\\_SB.UAR0._CRS");
+        let AmlOperand::Buffer(mut resources) = crs.operands(&table).unwrap()[1].clone() else {
+            panic!("expected buffer")
+        };
+
+        // IO (Decode16, 0x03F8, 0x03F8, 0x01, 0x08) followed by IRQNoFlags () {4} and the end tag.
+        assert_eq!(&resources[..8], &[0x47, 0x01, 0xF8, 0x03, 0xF8, 0x03, 0x01, 0x08]);
+        assert_eq!(&resources[8..11], &[0x22, 0x10, 0x00]);
+
+        // Move the UART to COM2.
+        resources[2..6].copy_from_slice(&[0xF8, 0x02, 0xF8, 0x02]);
+        resources[9] = 1 << 3;
+        crs.set_operand(&mut table, 1, &AmlOperand::Buffer(resources.clone())).unwrap();
+        assert_eq!(crs.operands(&table).unwrap()[1], AmlOperand::Buffer(resources.clone()));
+
+        resources.push(0);
+        assert_eq!(crs.set_operand(&mut table, 1, &AmlOperand::Buffer(resources)), Err(AmlError::OperandSizeMismatch));
+    }
+
+    #[test]
+    fn test_patch_strings_and_names() {
+        let mut table = PLATFORM_SSDT.to_vec();
+
+        let str0 = find(&table, "\\_SB.PCI0.STR0");
+        str0.set_operand(&mut table, 1, &AmlOperand::String("Patch!".into())).unwrap();
+        assert_eq!(str0.operands(&table).unwrap()[1], AmlOperand::String("Patch!".into()));
+        assert_eq!(
+            str0.set_operand(&mut table, 1, &AmlOperand::String("Short".into())),
+            Err(AmlError::OperandSizeMismatch)
+        );
+
+        // Renaming an object moves it in the namespace.
+        let wrd0 = find(&table, "\\_SB.PCI0.WRD0");
+        let new_name = AmlNameString::parse(b"WRD1").unwrap().0;
+        wrd0.set_operand(&mut table, 0, &AmlOperand::Name(new_name)).unwrap();
+        assert_eq!(find(&table, "\\_SB.PCI0.WRD1"), wrd0);
+        assert_eq!(find_path(&table, 1, &path("\\_SB.PCI0.WRD0")).unwrap(), None);
+
+        let uid = find(&table, "\\_SB.UAR0._UID");
+        uid.set_operand(&mut table, 1, &AmlOperand::Integer(0)).unwrap();
+        assert_eq!(uid.operands(&table).unwrap()[1], AmlOperand::Integer(0));
+        assert_eq!(uid.set_operand(&mut table, 1, &AmlOperand::Integer(2)), Err(AmlError::OperandSizeMismatch));
+    }
+
+    #[test]
+    fn test_firecracker_dsdt_devices() {
+        let table = FIRECRACKER_DSDT;
+        let named: Vec<AmlPath> = walk(table, 1).unwrap().into_iter().filter_map(|entry| entry.path).collect();
+        // Devices are declared with multi-segment names rather than inside a Scope.
+        assert!(named.iter().any(|path| path.to_string() == "\\_SB_.VGEN"));
+
+        // Every declared _HID decodes as an integer (EISA ID) or a string.
+        let hids: Vec<&AmlPath> =
+            named.iter().filter(|path| path.segments().last().map(|seg| seg.as_bytes()) == Some(b"_HID")).collect();
+        assert!(!hids.is_empty());
+        for hid in hids {
+            let handle = find_path(table, 1, hid).unwrap().unwrap();
+            assert!(matches!(handle.operands(table).unwrap()[1], AmlOperand::Integer(_) | AmlOperand::String(_)));
+        }
+    }
+
+    #[test]
+    fn test_truncated_and_empty_tables() {
+        assert_eq!(AmlHandle::open(&RFC0020_EXAMPLE[..36], 1), Err(AmlError::EmptyTable));
+        for len in 37..RFC0020_EXAMPLE.len() {
+            assert!(iter_table(&RFC0020_EXAMPLE[..len], 1).is_err(), "truncated at {len}");
+        }
+
+        // A handle cannot be used with a table it does not fit in.
+        let val0 = find(RFC0020_EXAMPLE, "\\_SB.DEV0.VAL0");
+        assert_eq!(val0.operands(&RFC0020_EXAMPLE[..val0.offset()]), Err(AmlError::InvalidHandle));
+    }
+
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        // Name (DEEP, Increment (Increment (... Local0)))
+        let mut table = RFC0020_EXAMPLE[..36].to_vec();
+        table.extend_from_slice(b"\x08DEEP");
+        table.extend(core::iter::repeat_n(0x75, 1000));
+        table.push(0x60);
+        assert_eq!(iter_table(&table, 1).unwrap_err(), AmlError::NestingTooDeep);
+    }
+
+    /// Walks and decodes a table, then patches every operand with its own value. None of it may panic, and since
+    /// decoding and encoding round trip, the table must come out unchanged.
+    fn patch_in_place(table: &mut [u8]) {
+        let original = table.to_vec();
+        let Ok(handles) = iter_table(table, 1) else {
+            return;
+        };
+        for handle in handles {
+            let _ = handle.name(table);
+            if let Ok(operands) = handle.operands(table) {
+                for (index, operand) in operands.iter().enumerate() {
+                    let _ = handle.set_operand(table, index, operand);
+                }
+            }
+        }
+        assert_eq!(table, &original[..]);
+        let _ = find_path(table, 1, &path("\\_SB.PCI0._CRS"));
+    }
+
+    /// Returns a copy of `seed` with its AML mutated the way a fuzzer would: flipped bits, interesting bytes,
+    /// truncation, or a duplicated slice.
+    fn mutate(rng: &mut StdRng, seed: &[u8]) -> Vec<u8> {
+        let header = size_of::<AcpiTableHeader>();
+        let mut table = seed.to_vec();
+        match rng.gen_range(0..4) {
+            0 => {
+                for _ in 0..=rng.gen_range(0..8) {
+                    let index = rng.gen_range(header..table.len());
+                    table[index] ^= 1 << rng.gen_range(0..8);
+                }
+            }
+            1 => {
+                let index = rng.gen_range(header..table.len());
+                table[index] = [0x00, 0x5B, 0x5C, 0x2F, 0xFF, 0x7F, 0x40, 0xC0][rng.gen_range(0..8)];
+            }
+            2 => table.truncate(rng.gen_range(header..table.len())),
+            _ => {
+                let start = rng.gen_range(header..table.len());
+                let end = rng.gen_range(start..table.len().min(start + 64));
+                let slice = table[start..end].to_vec();
+                table.splice(start..start, slice);
+            }
+        }
+        table
+    }
+
+    #[test]
+    fn test_mutated_corpus_patches_in_place() {
+        let mut rng = StdRng::seed_from_u64(0x5041_5449_4E41_414D);
+        for seed in CORPUS {
+            for _ in 0..300 {
+                patch_in_place(&mut mutate(&mut rng, seed));
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_streams_patch_in_place() {
+        let mut rng = StdRng::seed_from_u64(0x0020_0020_0020_0020);
+        for _ in 0..500 {
+            let mut table = RFC0020_EXAMPLE[..36].to_vec();
+            table.extend((0..rng.gen_range(1..=256)).map(|_| rng.r#gen::<u8>()));
+            patch_in_place(&mut table);
+        }
+    }
+}
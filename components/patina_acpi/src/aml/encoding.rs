@@ -0,0 +1,380 @@
+//! AML opcode encodings
+//!
+//! Describes the byte layout of every AML opcode defined in ACPI 6.5 section 20: the operands that follow the opcode
+//! and whether the object has a package length and child objects. Only the layout is described; the parser never
+//! evaluates AML.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use crate::error::AmlError;
+
+/// Prefix byte of the extended (two byte) opcodes.
+pub(crate) const EXT_OP_PREFIX: u8 = 0x5B;
+
+/// An AML opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmlOpcode {
+    /// A single byte opcode.
+    BaseOp(u8),
+    /// An opcode that follows the `0x5B` extended opcode prefix.
+    ExtOp(u8),
+    /// A name string used as a term: a reference to a named object or the start of a method invocation.
+    ///
+    /// The arguments of a method invocation follow the name as its siblings, since how many arguments a method takes
+    /// is only known once the namespace is loaded.
+    NamePath,
+}
+
+impl AmlOpcode {
+    /// `ZeroOp`
+    pub const ZERO: Self = Self::BaseOp(0x00);
+    /// `OneOp`
+    pub const ONE: Self = Self::BaseOp(0x01);
+    /// `AliasOp`
+    pub const ALIAS: Self = Self::BaseOp(0x06);
+    /// `NameOp`
+    pub const NAME: Self = Self::BaseOp(0x08);
+    /// `BytePrefix`
+    pub const BYTE_PREFIX: Self = Self::BaseOp(0x0A);
+    /// `WordPrefix`
+    pub const WORD_PREFIX: Self = Self::BaseOp(0x0B);
+    /// `DWordPrefix`
+    pub const DWORD_PREFIX: Self = Self::BaseOp(0x0C);
+    /// `StringPrefix`
+    pub const STRING_PREFIX: Self = Self::BaseOp(0x0D);
+    /// `QWordPrefix`
+    pub const QWORD_PREFIX: Self = Self::BaseOp(0x0E);
+    /// `ScopeOp`
+    pub const SCOPE: Self = Self::BaseOp(0x10);
+    /// `BufferOp`
+    pub const BUFFER: Self = Self::BaseOp(0x11);
+    /// `PackageOp`
+    pub const PACKAGE: Self = Self::BaseOp(0x12);
+    /// `VarPackageOp`
+    pub const VAR_PACKAGE: Self = Self::BaseOp(0x13);
+    /// `MethodOp`
+    pub const METHOD: Self = Self::BaseOp(0x14);
+    /// `ExternalOp`
+    pub const EXTERNAL: Self = Self::BaseOp(0x15);
+    /// `IfOp`
+    pub const IF: Self = Self::BaseOp(0xA0);
+    /// `ElseOp`
+    pub const ELSE: Self = Self::BaseOp(0xA1);
+    /// `WhileOp`
+    pub const WHILE: Self = Self::BaseOp(0xA2);
+    /// `OnesOp`
+    pub const ONES: Self = Self::BaseOp(0xFF);
+    /// `MutexOp`
+    pub const MUTEX: Self = Self::ExtOp(0x01);
+    /// `OpRegionOp`
+    pub const OP_REGION: Self = Self::ExtOp(0x80);
+    /// `FieldOp`
+    pub const FIELD: Self = Self::ExtOp(0x81);
+    /// `DeviceOp`
+    pub const DEVICE: Self = Self::ExtOp(0x82);
+    /// `ProcessorOp`
+    pub const PROCESSOR: Self = Self::ExtOp(0x83);
+    /// `PowerResOp`
+    pub const POWER_RES: Self = Self::ExtOp(0x84);
+    /// `ThermalZoneOp`
+    pub const THERMAL_ZONE: Self = Self::ExtOp(0x85);
+
+    /// Returns the number of bytes the opcode itself occupies.
+    pub fn encoded_len(&self) -> usize {
+        match self {
+            Self::BaseOp(_) => 1,
+            Self::ExtOp(_) => 2,
+            Self::NamePath => 0,
+        }
+    }
+}
+
+/// The kind of an operand that follows an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AmlOperandKind {
+    /// An encoded name string.
+    NameString,
+    /// A one byte integer.
+    ByteData,
+    /// A two byte integer.
+    WordData,
+    /// A four byte integer.
+    DWordData,
+    /// An eight byte integer.
+    QWordData,
+    /// A NUL terminated ASCII string.
+    AsciiString,
+    /// A nested AML object, such as a constant, an expression, or a reference.
+    TermArg,
+    /// Raw data that ends an object with a package length and no child objects, such as the contents of a buffer or
+    /// the field list of a field.
+    ByteList,
+}
+
+impl AmlOperandKind {
+    /// Returns the size of a fixed width integer operand.
+    pub(crate) fn integer_width(&self) -> Option<usize> {
+        match self {
+            Self::ByteData => Some(1),
+            Self::WordData => Some(2),
+            Self::DWordData => Some(4),
+            Self::QWordData => Some(8),
+            _ => None,
+        }
+    }
+}
+
+/// Attributes of an AML opcode encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AmlOpAttributes(u32);
+
+impl AmlOpAttributes {
+    /// No attributes.
+    pub const NONE: Self = Self(0);
+    /// The opcode is followed by a package length covering the rest of the object.
+    pub const HAS_PKG_LENGTH: Self = Self(0x0000_0001);
+    /// The object ends with a list of child objects.
+    pub const HAS_CHILD_OBJ: Self = Self(0x0000_0002);
+    /// The object opens a new namespace scope for its children.
+    pub const OPENS_SCOPE: Self = Self(0x0000_0004);
+
+    /// Returns the union of two sets of attributes.
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns true if every attribute in `other` is set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the raw attribute bits.
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+}
+
+/// The byte layout of an AML opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AmlByteEncoding {
+    /// The opcode the layout applies to.
+    pub opcode: AmlOpcode,
+    /// The operands that follow the opcode and its package length, in order.
+    pub operands: &'static [AmlOperandKind],
+    /// Attributes of the opcode.
+    pub attributes: AmlOpAttributes,
+    /// Index of the operand holding the name the object declares, if it declares one.
+    pub name_operand: Option<usize>,
+}
+
+use AmlOperandKind::{
+    AsciiString as S, ByteData as B, DWordData as D, NameString as N, QWordData as Q, TermArg as T, WordData as W,
+};
+
+const NONE: AmlOpAttributes = AmlOpAttributes::NONE;
+const PKG: AmlOpAttributes = AmlOpAttributes::HAS_PKG_LENGTH;
+const PKG_CHILD: AmlOpAttributes = PKG.union(AmlOpAttributes::HAS_CHILD_OBJ);
+const PKG_SCOPE: AmlOpAttributes = PKG_CHILD.union(AmlOpAttributes::OPENS_SCOPE);
+
+const fn base(
+    op: u8,
+    operands: &'static [AmlOperandKind],
+    attributes: AmlOpAttributes,
+    name_operand: Option<usize>,
+) -> AmlByteEncoding {
+    AmlByteEncoding { opcode: AmlOpcode::BaseOp(op), operands, attributes, name_operand }
+}
+
+const fn ext(
+    op: u8,
+    operands: &'static [AmlOperandKind],
+    attributes: AmlOpAttributes,
+    name_operand: Option<usize>,
+) -> AmlByteEncoding {
+    AmlByteEncoding { opcode: AmlOpcode::ExtOp(op), operands, attributes, name_operand }
+}
+
+const NAME_PATH_ENCODING: AmlByteEncoding =
+    AmlByteEncoding { opcode: AmlOpcode::NamePath, operands: &[N], attributes: NONE, name_operand: None };
+
+/// Every opcode defined in ACPI 6.5 section 20.3.
+static ENCODINGS: &[AmlByteEncoding] = &[
+    base(0x00, &[], NONE, None),                  // ZeroOp
+    base(0x01, &[], NONE, None),                  // OneOp
+    base(0x06, &[N, N], NONE, Some(1)),           // AliasOp
+    base(0x08, &[N, T], NONE, Some(0)),           // NameOp
+    base(0x0A, &[B], NONE, None),                 // BytePrefix
+    base(0x0B, &[W], NONE, None),                 // WordPrefix
+    base(0x0C, &[D], NONE, None),                 // DWordPrefix
+    base(0x0D, &[S], NONE, None),                 // StringPrefix
+    base(0x0E, &[Q], NONE, None),                 // QWordPrefix
+    base(0x10, &[N], PKG_SCOPE, Some(0)),         // ScopeOp
+    base(0x11, &[T], PKG, None),                  // BufferOp
+    base(0x12, &[B], PKG_CHILD, None),            // PackageOp
+    base(0x13, &[T], PKG_CHILD, None),            // VarPackageOp
+    base(0x14, &[N, B], PKG_SCOPE, Some(0)),      // MethodOp
+    base(0x15, &[N, B, B], NONE, None),           // ExternalOp
+    base(0x60, &[], NONE, None),                  // Local0Op
+    base(0x61, &[], NONE, None),                  // Local1Op
+    base(0x62, &[], NONE, None),                  // Local2Op
+    base(0x63, &[], NONE, None),                  // Local3Op
+    base(0x64, &[], NONE, None),                  // Local4Op
+    base(0x65, &[], NONE, None),                  // Local5Op
+    base(0x66, &[], NONE, None),                  // Local6Op
+    base(0x67, &[], NONE, None),                  // Local7Op
+    base(0x68, &[], NONE, None),                  // Arg0Op
+    base(0x69, &[], NONE, None),                  // Arg1Op
+    base(0x6A, &[], NONE, None),                  // Arg2Op
+    base(0x6B, &[], NONE, None),                  // Arg3Op
+    base(0x6C, &[], NONE, None),                  // Arg4Op
+    base(0x6D, &[], NONE, None),                  // Arg5Op
+    base(0x6E, &[], NONE, None),                  // Arg6Op
+    base(0x70, &[T, T], NONE, None),              // StoreOp
+    base(0x71, &[T], NONE, None),                 // RefOfOp
+    base(0x72, &[T, T, T], NONE, None),           // AddOp
+    base(0x73, &[T, T, T], NONE, None),           // ConcatOp
+    base(0x74, &[T, T, T], NONE, None),           // SubtractOp
+    base(0x75, &[T], NONE, None),                 // IncrementOp
+    base(0x76, &[T], NONE, None),                 // DecrementOp
+    base(0x77, &[T, T, T], NONE, None),           // MultiplyOp
+    base(0x78, &[T, T, T, T], NONE, None),        // DivideOp
+    base(0x79, &[T, T, T], NONE, None),           // ShiftLeftOp
+    base(0x7A, &[T, T, T], NONE, None),           // ShiftRightOp
+    base(0x7B, &[T, T, T], NONE, None),           // AndOp
+    base(0x7C, &[T, T, T], NONE, None),           // NandOp
+    base(0x7D, &[T, T, T], NONE, None),           // OrOp
+    base(0x7E, &[T, T, T], NONE, None),           // NorOp
+    base(0x7F, &[T, T, T], NONE, None),           // XorOp
+    base(0x80, &[T, T], NONE, None),              // NotOp
+    base(0x81, &[T, T], NONE, None),              // FindSetLeftBitOp
+    base(0x82, &[T, T], NONE, None),              // FindSetRightBitOp
+    base(0x83, &[T], NONE, None),                 // DerefOfOp
+    base(0x84, &[T, T, T], NONE, None),           // ConcatResOp
+    base(0x85, &[T, T, T], NONE, None),           // ModOp
+    base(0x86, &[T, T], NONE, None),              // NotifyOp
+    base(0x87, &[T], NONE, None),                 // SizeOfOp
+    base(0x88, &[T, T, T], NONE, None),           // IndexOp
+    base(0x89, &[T, B, T, B, T, T], NONE, None),  // MatchOp
+    base(0x8A, &[T, T, N], NONE, Some(2)),        // CreateDWordFieldOp
+    base(0x8B, &[T, T, N], NONE, Some(2)),        // CreateWordFieldOp
+    base(0x8C, &[T, T, N], NONE, Some(2)),        // CreateByteFieldOp
+    base(0x8D, &[T, T, N], NONE, Some(2)),        // CreateBitFieldOp
+    base(0x8E, &[T], NONE, None),                 // ObjectTypeOp
+    base(0x8F, &[T, T, N], NONE, Some(2)),        // CreateQWordFieldOp
+    base(0x90, &[T, T], NONE, None),              // LandOp
+    base(0x91, &[T, T], NONE, None),              // LorOp
+    base(0x92, &[T], NONE, None),                 // LnotOp
+    base(0x93, &[T, T], NONE, None),              // LEqualOp
+    base(0x94, &[T, T], NONE, None),              // LGreaterOp
+    base(0x95, &[T, T], NONE, None),              // LLessOp
+    base(0x96, &[T, T], NONE, None),              // ToBufferOp
+    base(0x97, &[T, T], NONE, None),              // ToDecimalStringOp
+    base(0x98, &[T, T], NONE, None),              // ToHexStringOp
+    base(0x99, &[T, T], NONE, None),              // ToIntegerOp
+    base(0x9C, &[T, T, T], NONE, None),           // ToStringOp
+    base(0x9D, &[T, T], NONE, None),              // CopyObjectOp
+    base(0x9E, &[T, T, T, T], NONE, None),        // MidOp
+    base(0x9F, &[], NONE, None),                  // ContinueOp
+    base(0xA0, &[T], PKG_CHILD, None),            // IfOp
+    base(0xA1, &[], PKG_CHILD, None),             // ElseOp
+    base(0xA2, &[T], PKG_CHILD, None),            // WhileOp
+    base(0xA3, &[], NONE, None),                  // NoopOp
+    base(0xA4, &[T], NONE, None),                 // ReturnOp
+    base(0xA5, &[], NONE, None),                  // BreakOp
+    base(0xCC, &[], NONE, None),                  // BreakPointOp
+    base(0xFF, &[], NONE, None),                  // OnesOp
+    ext(0x01, &[N, B], NONE, Some(0)),            // MutexOp
+    ext(0x02, &[N], NONE, Some(0)),               // EventOp
+    ext(0x12, &[T, T], NONE, None),               // CondRefOfOp
+    ext(0x13, &[T, T, T, N], NONE, Some(3)),      // CreateFieldOp
+    ext(0x1F, &[T, T, T, T, T, T], NONE, None),   // LoadTableOp
+    ext(0x20, &[N, T], NONE, None),               // LoadOp
+    ext(0x21, &[T], NONE, None),                  // StallOp
+    ext(0x22, &[T], NONE, None),                  // SleepOp
+    ext(0x23, &[T, W], NONE, None),               // AcquireOp
+    ext(0x24, &[T], NONE, None),                  // SignalOp
+    ext(0x25, &[T, T], NONE, None),               // WaitOp
+    ext(0x26, &[T], NONE, None),                  // ResetOp
+    ext(0x27, &[T], NONE, None),                  // ReleaseOp
+    ext(0x28, &[T, T], NONE, None),               // FromBCDOp
+    ext(0x29, &[T, T], NONE, None),               // ToBCDOp
+    ext(0x30, &[], NONE, None),                   // RevisionOp
+    ext(0x31, &[], NONE, None),                   // DebugOp
+    ext(0x32, &[B, D, T], NONE, None),            // FatalOp
+    ext(0x33, &[], NONE, None),                   // TimerOp
+    ext(0x80, &[N, B, T, T], NONE, Some(0)),      // OpRegionOp
+    ext(0x81, &[N, B], PKG, None),                // FieldOp
+    ext(0x82, &[N], PKG_SCOPE, Some(0)),          // DeviceOp
+    ext(0x83, &[N, B, D, B], PKG_SCOPE, Some(0)), // ProcessorOp
+    ext(0x84, &[N, B, W], PKG_SCOPE, Some(0)),    // PowerResOp
+    ext(0x85, &[N], PKG_SCOPE, Some(0)),          // ThermalZoneOp
+    ext(0x86, &[N, N, B], PKG, None),             // IndexFieldOp
+    ext(0x87, &[N, N, T, B], PKG, None),          // BankFieldOp
+    ext(0x88, &[N, T, T, T], NONE, Some(0)),      // DataRegionOp
+];
+
+/// Returns the byte layout of `opcode`, or `None` if it is not a defined AML opcode.
+pub fn lookup(opcode: AmlOpcode) -> Option<&'static AmlByteEncoding> {
+    if opcode == AmlOpcode::NamePath {
+        return Some(&NAME_PATH_ENCODING);
+    }
+    ENCODINGS.iter().find(|encoding| encoding.opcode == opcode)
+}
+
+/// Decodes the package length at the start of `bytes`.
+///
+/// Returns the package length, which includes the size of its own encoding, and the size of that encoding.
+pub(crate) fn read_pkg_length(bytes: &[u8]) -> Result<(usize, usize), AmlError> {
+    let lead = *bytes.first().ok_or(AmlError::InvalidAml)?;
+    let follow = (lead >> 6) as usize;
+    if follow == 0 {
+        return Ok(((lead & 0x3F) as usize, 1));
+    }
+
+    let follow_bytes = bytes.get(1..=follow).ok_or(AmlError::InvalidAml)?;
+    let length = follow_bytes
+        .iter()
+        .enumerate()
+        .fold((lead & 0x0F) as usize, |length, (index, byte)| length | (*byte as usize) << (4 + 8 * index));
+    if length < follow + 1 {
+        return Err(AmlError::InvalidAml);
+    }
+    Ok((length, follow + 1))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_pkg_length() {
+        assert_eq!(read_pkg_length(&[0x15]), Ok((0x15, 1)));
+        assert_eq!(read_pkg_length(&[0x4A, 0x04]), Ok((0x4A, 2)));
+        assert_eq!(read_pkg_length(&[0x81, 0x23, 0x45]), Ok((0x45231, 3)));
+        assert_eq!(read_pkg_length(&[0x4A]), Err(AmlError::InvalidAml));
+        assert_eq!(read_pkg_length(&[0x40, 0x00]), Err(AmlError::InvalidAml));
+        assert_eq!(read_pkg_length(&[]), Err(AmlError::InvalidAml));
+    }
+
+    #[test]
+    fn test_lookup() {
+        let device = lookup(AmlOpcode::DEVICE).unwrap();
+        assert!(device.attributes.contains(AmlOpAttributes::HAS_PKG_LENGTH));
+        assert!(device.attributes.contains(AmlOpAttributes::OPENS_SCOPE));
+        assert_eq!(device.name_operand, Some(0));
+
+        let buffer = lookup(AmlOpcode::BUFFER).unwrap();
+        assert!(!buffer.attributes.contains(AmlOpAttributes::HAS_CHILD_OBJ));
+
+        assert!(lookup(AmlOpcode::BaseOp(0x02)).is_none());
+        assert!(lookup(AmlOpcode::ExtOp(0x00)).is_none());
+        assert_eq!(lookup(AmlOpcode::NamePath).unwrap().operands, &[AmlOperandKind::NameString]);
+        assert_eq!(AmlOpcode::DEVICE.encoded_len(), 2);
+    }
+}
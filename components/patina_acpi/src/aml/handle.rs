@@ -0,0 +1,456 @@
+//! AML handles
+//!
+//! An [`AmlHandle`] is a cursor at one object in the AML stream of a DSDT or SSDT. Handles only record where the
+//! object is; every operation takes the bytes of the table the handle was opened on, and validates the bytes it reads,
+//! so a malformed or mismatched table produces an [`AmlError`] rather than a panic.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{
+    acpi_table::{AcpiTableHeader, TableKey},
+    error::AmlError,
+};
+
+use super::{
+    encoding::{AmlByteEncoding, AmlOpAttributes, AmlOpcode, AmlOperandKind, EXT_OP_PREFIX, lookup, read_pkg_length},
+    name::{AmlNameString, AmlPath, is_name_string_start},
+};
+
+/// Maximum nesting of expressions within a single object.
+///
+/// Expressions are parsed recursively, so the depth is bounded to keep malformed AML from exhausting the stack.
+const MAX_NESTING: usize = 64;
+
+/// The value of an operand of an AML object
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmlOperand {
+    /// A name string.
+    Name(AmlNameString),
+    /// A fixed width integer operand, or an integer constant object (`Zero`, `One`, `Ones`, and the integer prefixes).
+    Integer(u64),
+    /// A string operand or string constant object, without its NUL terminator.
+    String(String),
+    /// A buffer object, holding the bytes the buffer is initialized with.
+    Buffer(Vec<u8>),
+    /// The raw data that ends an object, such as the contents of the buffer itself or a field list.
+    ByteList(Vec<u8>),
+    /// Any other nested object, such as an expression or a package.
+    Object(AmlHandle),
+}
+
+/// A handle to an object in the AML stream of a DSDT or SSDT
+///
+/// Handles are plain cursors: they hold the key of the table they were opened on and the location of the object, and
+/// are only meaningful together with that table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AmlHandle {
+    table_key: TableKey,
+    offset: usize,
+    size: usize,
+    parent_end: usize,
+    opcode: AmlOpcode,
+}
+
+/// An operand slot within an object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct OperandSlot {
+    pub kind: AmlOperandKind,
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl OperandSlot {
+    fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// The location of the operands and children of an object.
+pub(crate) struct Layout {
+    pub slots: Vec<OperandSlot>,
+    pub children: Option<Range<usize>>,
+}
+
+impl AmlHandle {
+    /// Opens a handle to the first object in the AML stream of `table`, which holds the whole table including its
+    /// header.
+    ///
+    /// The remaining top level objects are the siblings of the returned handle.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AmlError::EmptyTable`] if the table holds no AML, or an error describing why the first object could
+    /// not be parsed.
+    pub fn open(table: &[u8], table_key: TableKey) -> Result<Self, AmlError> {
+        if table.len() <= size_of::<AcpiTableHeader>() {
+            return Err(AmlError::EmptyTable);
+        }
+        Self::parse(table, table_key, size_of::<AcpiTableHeader>(), table.len(), 0)
+    }
+
+    /// Opens a handle to the object at `offset` within `table`.
+    ///
+    /// The handle's siblings are the objects that follow it up to the end of the table.
+    pub fn open_at(table: &[u8], table_key: TableKey, offset: usize) -> Result<Self, AmlError> {
+        if offset < size_of::<AcpiTableHeader>() {
+            return Err(AmlError::InvalidAml);
+        }
+        Self::parse(table, table_key, offset, table.len(), 0)
+    }
+
+    /// Returns the key of the table the handle was opened on.
+    pub fn table_key(&self) -> TableKey {
+        self.table_key
+    }
+
+    /// Returns the offset of the object from the start of the table.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the size of the object, including its operands and children.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the opcode of the object.
+    pub fn opcode(&self) -> AmlOpcode {
+        self.opcode
+    }
+
+    /// Returns the byte layout of the object's opcode.
+    pub fn encoding(&self) -> &'static AmlByteEncoding {
+        lookup(self.opcode).expect("handles are only created for known opcodes")
+    }
+
+    /// Returns the first child of the object, or `None` if it has no children.
+    pub fn child(&self, table: &[u8]) -> Result<Option<Self>, AmlError> {
+        match self.layout(table)?.children {
+            Some(children) if !children.is_empty() => {
+                Self::parse(table, self.table_key, children.start, children.end, 0).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the object that follows this one within its parent, or `None` if it is the last one.
+    pub fn sibling(&self, table: &[u8]) -> Result<Option<Self>, AmlError> {
+        let next = self.offset + self.size;
+        if next >= self.parent_end {
+            return Ok(None);
+        }
+        Self::parse(table, self.table_key, next, self.parent_end, 0).map(Some)
+    }
+
+    /// Decodes the operands of the object.
+    ///
+    /// Objects with a package length and no children, such as buffers and fields, end with an
+    /// [`AmlOperand::ByteList`] operand.
+    pub fn operands(&self, table: &[u8]) -> Result<Vec<AmlOperand>, AmlError> {
+        let layout = self.layout(table)?;
+        layout.slots.iter().map(|slot| self.decode(table, slot)).collect()
+    }
+
+    /// Returns the name the object declares, such as the name of a `Device` or `Name` object.
+    pub fn name(&self, table: &[u8]) -> Result<Option<AmlNameString>, AmlError> {
+        let Some(index) = self.encoding().name_operand else {
+            return Ok(None);
+        };
+        let layout = self.layout(table)?;
+        let slot = layout.slots.get(index).ok_or(AmlError::InvalidAml)?;
+        let bytes = table.get(slot.range()).ok_or(AmlError::InvalidAml)?;
+        AmlNameString::parse(bytes).map(|(name, _)| Some(name))
+    }
+
+    /// Overwrites the operand at `index` with `operand`.
+    ///
+    /// The new value must encode to the same size as the current one, as the AML around it is not moved. Integers
+    /// are written with the width of the existing operand, and integer constant objects keep their prefix. The table
+    /// checksum is not updated.
+    ///
+    /// # Errors
+    ///
+    /// - [`AmlError::InvalidOperandIndex`] if the object has no operand at `index`.
+    /// - [`AmlError::OperandTypeMismatch`] if `operand` is not the type of the existing operand.
+    /// - [`AmlError::OperandSizeMismatch`] if `operand` does not encode to the size of the existing operand.
+    pub fn set_operand(&self, table: &mut [u8], index: usize, operand: &AmlOperand) -> Result<(), AmlError> {
+        let layout = self.layout(table)?;
+        let slot = *layout.slots.get(index).ok_or(AmlError::InvalidOperandIndex)?;
+
+        let (range, bytes) = match (slot.kind, operand) {
+            (AmlOperandKind::NameString, AmlOperand::Name(name)) => (slot.range(), name.encode()),
+            (kind, AmlOperand::Integer(value)) if kind.integer_width().is_some() => {
+                (slot.range(), encode_integer(*value, slot.len)?)
+            }
+            (AmlOperandKind::AsciiString, AmlOperand::String(string)) => (slot.range(), encode_string(string)?),
+            (AmlOperandKind::ByteList, AmlOperand::ByteList(bytes) | AmlOperand::Buffer(bytes)) => {
+                (slot.range(), bytes.clone())
+            }
+            (AmlOperandKind::TermArg, _) => self.encode_object(table, &slot, operand)?,
+            _ => return Err(AmlError::OperandTypeMismatch),
+        };
+
+        if bytes.len() != range.len() {
+            return Err(AmlError::OperandSizeMismatch);
+        }
+        table.get_mut(range).ok_or(AmlError::InvalidHandle)?.copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Parses the object at `offset`, which must end by `parent_end`.
+    fn parse(
+        table: &[u8],
+        table_key: TableKey,
+        offset: usize,
+        parent_end: usize,
+        depth: usize,
+    ) -> Result<Self, AmlError> {
+        if depth > MAX_NESTING {
+            return Err(AmlError::NestingTooDeep);
+        }
+        let bytes = table.get(offset..parent_end).ok_or(AmlError::InvalidAml)?;
+        let opcode = match *bytes.first().ok_or(AmlError::InvalidAml)? {
+            EXT_OP_PREFIX => AmlOpcode::ExtOp(*bytes.get(1).ok_or(AmlError::InvalidAml)?),
+            byte if is_name_string_start(byte) => AmlOpcode::NamePath,
+            byte => AmlOpcode::BaseOp(byte),
+        };
+        let encoding = lookup(opcode).ok_or(AmlError::UnknownOpcode)?;
+
+        let mut pos = offset + opcode.encoded_len();
+        let end = if encoding.attributes.contains(AmlOpAttributes::HAS_PKG_LENGTH) {
+            let (pkg_length, _) = read_pkg_length(table.get(pos..parent_end).ok_or(AmlError::InvalidAml)?)?;
+            let end = pos.checked_add(pkg_length).ok_or(AmlError::InvalidAml)?;
+            if end > parent_end {
+                return Err(AmlError::InvalidAml);
+            }
+            end
+        } else {
+            parent_end
+        };
+
+        let handle = Self { table_key, offset, size: 0, parent_end, opcode };
+        pos = handle.operands_start(table, pos)?;
+        for kind in encoding.operands {
+            pos += operand_len(table, table_key, *kind, pos, end, depth)?;
+        }
+
+        let size = if encoding.attributes.contains(AmlOpAttributes::HAS_PKG_LENGTH) { end } else { pos } - offset;
+        Ok(Self { size, ..handle })
+    }
+
+    /// Returns the offset of the first operand, skipping the package length that follows `after_opcode`.
+    fn operands_start(&self, table: &[u8], after_opcode: usize) -> Result<usize, AmlError> {
+        if !self.encoding().attributes.contains(AmlOpAttributes::HAS_PKG_LENGTH) {
+            return Ok(after_opcode);
+        }
+        let bytes = table.get(after_opcode..).ok_or(AmlError::InvalidAml)?;
+        read_pkg_length(bytes).map(|(_, encoded_len)| after_opcode + encoded_len)
+    }
+
+    /// Locates the operands and children of the object.
+    pub(crate) fn layout(&self, table: &[u8]) -> Result<Layout, AmlError> {
+        let end = self.offset + self.size;
+        if end > table.len() || end > self.parent_end {
+            return Err(AmlError::InvalidHandle);
+        }
+        let encoding = self.encoding();
+
+        let mut pos = self.operands_start(table, self.offset + self.opcode.encoded_len())?;
+        let mut slots = Vec::with_capacity(encoding.operands.len() + 1);
+        for kind in encoding.operands {
+            let len = operand_len(table, self.table_key, *kind, pos, end, 0)?;
+            slots.push(OperandSlot { kind: *kind, offset: pos, len });
+            pos += len;
+        }
+
+        let mut children = None;
+        if encoding.attributes.contains(AmlOpAttributes::HAS_CHILD_OBJ) {
+            children = Some(pos..end);
+        } else if encoding.attributes.contains(AmlOpAttributes::HAS_PKG_LENGTH) {
+            slots.push(OperandSlot { kind: AmlOperandKind::ByteList, offset: pos, len: end - pos });
+        }
+        Ok(Layout { slots, children })
+    }
+
+    fn decode(&self, table: &[u8], slot: &OperandSlot) -> Result<AmlOperand, AmlError> {
+        let bytes = table.get(slot.range()).ok_or(AmlError::InvalidAml)?;
+        Ok(match slot.kind {
+            AmlOperandKind::NameString => AmlOperand::Name(AmlNameString::parse(bytes)?.0),
+            AmlOperandKind::ByteData
+            | AmlOperandKind::WordData
+            | AmlOperandKind::DWordData
+            | AmlOperandKind::QWordData => AmlOperand::Integer(decode_integer(bytes)),
+            AmlOperandKind::AsciiString => AmlOperand::String(decode_string(bytes)),
+            AmlOperandKind::ByteList => AmlOperand::ByteList(bytes.to_vec()),
+            AmlOperandKind::TermArg => self.decode_object(table, slot)?,
+        })
+    }
+
+    /// Decodes a nested object, returning constants by value and anything else as a handle.
+    fn decode_object(&self, table: &[u8], slot: &OperandSlot) -> Result<AmlOperand, AmlError> {
+        let object = Self::parse(table, self.table_key, slot.offset, slot.offset + slot.len, 0)?;
+        let layout = object.layout(table)?;
+        let operand = |index: usize| -> Result<&[u8], AmlError> {
+            let slot = layout.slots.get(index).ok_or(AmlError::InvalidAml)?;
+            table.get(slot.range()).ok_or(AmlError::InvalidAml)
+        };
+
+        Ok(match object.opcode {
+            AmlOpcode::ZERO => AmlOperand::Integer(0),
+            AmlOpcode::ONE => AmlOperand::Integer(1),
+            AmlOpcode::ONES => AmlOperand::Integer(u64::MAX),
+            AmlOpcode::BYTE_PREFIX | AmlOpcode::WORD_PREFIX | AmlOpcode::DWORD_PREFIX | AmlOpcode::QWORD_PREFIX => {
+                AmlOperand::Integer(decode_integer(operand(0)?))
+            }
+            AmlOpcode::STRING_PREFIX => AmlOperand::String(decode_string(operand(0)?)),
+            AmlOpcode::BUFFER => AmlOperand::Buffer(operand(1)?.to_vec()),
+            _ => AmlOperand::Object(object),
+        })
+    }
+
+    /// Encodes `operand` in place of the nested object in `slot`, returning the range to overwrite and its bytes.
+    fn encode_object(
+        &self,
+        table: &[u8],
+        slot: &OperandSlot,
+        operand: &AmlOperand,
+    ) -> Result<(Range<usize>, Vec<u8>), AmlError> {
+        let object = Self::parse(table, self.table_key, slot.offset, slot.offset + slot.len, 0)?;
+        let layout = object.layout(table)?;
+        let operand_slot = |index: usize| layout.slots.get(index).copied().ok_or(AmlError::InvalidAml);
+
+        match (object.opcode, operand) {
+            (AmlOpcode::ZERO | AmlOpcode::ONE | AmlOpcode::ONES, AmlOperand::Integer(value)) => {
+                let opcode = match *value {
+                    0 => 0x00,
+                    1 => 0x01,
+                    u64::MAX => 0xFF,
+                    _ => return Err(AmlError::OperandSizeMismatch),
+                };
+                Ok((slot.range(), vec![opcode]))
+            }
+            (
+                AmlOpcode::BYTE_PREFIX | AmlOpcode::WORD_PREFIX | AmlOpcode::DWORD_PREFIX | AmlOpcode::QWORD_PREFIX,
+                AmlOperand::Integer(value),
+            ) => {
+                let data = operand_slot(0)?;
+                Ok((data.range(), encode_integer(*value, data.len)?))
+            }
+            (AmlOpcode::STRING_PREFIX, AmlOperand::String(string)) => {
+                let data = operand_slot(0)?;
+                Ok((data.range(), encode_string(string)?))
+            }
+            (AmlOpcode::BUFFER, AmlOperand::Buffer(bytes) | AmlOperand::ByteList(bytes)) => {
+                Ok((operand_slot(1)?.range(), bytes.clone()))
+            }
+            (AmlOpcode::NamePath, AmlOperand::Name(name)) => Ok((slot.range(), name.encode())),
+            _ => Err(AmlError::OperandTypeMismatch),
+        }
+    }
+}
+
+/// Returns the encoded size of the operand of `kind` at `pos`, which must end by `end`.
+fn operand_len(
+    table: &[u8],
+    table_key: TableKey,
+    kind: AmlOperandKind,
+    pos: usize,
+    end: usize,
+    depth: usize,
+) -> Result<usize, AmlError> {
+    let bytes = table.get(pos..end).ok_or(AmlError::InvalidAml)?;
+    let len = match kind {
+        AmlOperandKind::NameString => AmlNameString::parse(bytes)?.1,
+        AmlOperandKind::AsciiString => bytes.iter().position(|byte| *byte == 0).ok_or(AmlError::InvalidAml)? + 1,
+        AmlOperandKind::TermArg => AmlHandle::parse(table, table_key, pos, end, depth + 1)?.size,
+        AmlOperandKind::ByteList => bytes.len(),
+        kind => kind.integer_width().unwrap_or_default(),
+    };
+    if len > bytes.len() {
+        return Err(AmlError::InvalidAml);
+    }
+    Ok(len)
+}
+
+fn decode_integer(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+fn encode_integer(value: u64, width: usize) -> Result<Vec<u8>, AmlError> {
+    if width < size_of::<u64>() && value >> (width * 8) != 0 {
+        return Err(AmlError::OperandSizeMismatch);
+    }
+    Ok(value.to_le_bytes()[..width.min(size_of::<u64>())].to_vec())
+}
+
+fn decode_string(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    bytes.iter().map(|byte| *byte as char).collect()
+}
+
+fn encode_string(string: &str) -> Result<Vec<u8>, AmlError> {
+    if !string.bytes().all(|byte| (0x01..=0x7F).contains(&byte)) {
+        return Err(AmlError::OperandTypeMismatch);
+    }
+    let mut bytes = Vec::with_capacity(string.len() + 1);
+    bytes.extend_from_slice(string.as_bytes());
+    bytes.push(0);
+    Ok(bytes)
+}
+
+/// An object visited by [`walk`], along with its place in the namespace.
+pub(crate) struct NamespaceEntry {
+    pub handle: AmlHandle,
+    /// Absolute path of the name the object declares, if it declares one.
+    pub path: Option<AmlPath>,
+    /// Absolute path of the scope the object appears in.
+    pub scope: AmlPath,
+}
+
+/// Visits every object of the table in depth-first preorder, tracking the namespace scope of each.
+pub(crate) fn walk(table: &[u8], table_key: TableKey) -> Result<Vec<NamespaceEntry>, AmlError> {
+    let mut entries = Vec::new();
+    let mut stack = vec![(AmlHandle::open(table, table_key)?, AmlPath::default())];
+
+    while let Some((handle, scope)) = stack.pop() {
+        let path = handle.name(table)?.map(|name| name.resolve(&scope));
+
+        // The sibling is pushed first so the children are visited before it.
+        if let Some(sibling) = handle.sibling(table)? {
+            stack.push((sibling, scope.clone()));
+        }
+        if let Some(child) = handle.child(table)? {
+            let child_scope = match (&path, handle.encoding().attributes.contains(AmlOpAttributes::OPENS_SCOPE)) {
+                (Some(path), true) => path.clone(),
+                _ => scope.clone(),
+            };
+            stack.push((child, child_scope));
+        }
+
+        entries.push(NamespaceEntry { handle, path, scope });
+    }
+    Ok(entries)
+}
+
+/// Returns every object of the table in depth-first preorder.
+///
+/// `table` holds the whole DSDT or SSDT, including its header.
+pub fn iter_table(table: &[u8], table_key: TableKey) -> Result<Vec<AmlHandle>, AmlError> {
+    walk(table, table_key).map(|entries| entries.into_iter().map(|entry| entry.handle).collect())
+}
+
+/// Returns the object that declares the name at `path`, or `None` if the table does not declare it.
+///
+/// `table` holds the whole DSDT or SSDT, including its header.
+pub fn find_path(table: &[u8], table_key: TableKey, path: &AmlPath) -> Result<Option<AmlHandle>, AmlError> {
+    Ok(walk(table, table_key)?.into_iter().find(|entry| entry.path.as_ref() == Some(path)).map(|entry| entry.handle))
+}
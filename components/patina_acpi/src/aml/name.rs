@@ -0,0 +1,322 @@
+//! AML names and paths
+//!
+//! Defines [`NameSeg`], the encoded [`AmlNameString`] found in AML bytecode, and the absolute [`AmlPath`] used to look
+//! up objects in the ACPI namespace.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::Vec;
+use core::{fmt, str::FromStr};
+
+use crate::error::AmlError;
+
+/// Prefix of a name string that starts at the namespace root (`\`).
+pub(crate) const ROOT_CHAR: u8 = b'\\';
+/// Prefix of a name string that starts at the parent scope (`^`).
+pub(crate) const PARENT_PREFIX_CHAR: u8 = b'^';
+/// Prefix of a name path with exactly two segments.
+pub(crate) const DUAL_NAME_PREFIX: u8 = 0x2E;
+/// Prefix of a name path with a segment count byte.
+pub(crate) const MULTI_NAME_PREFIX: u8 = 0x2F;
+/// Name path with no segments.
+pub(crate) const NULL_NAME: u8 = 0x00;
+
+/// Returns true if `byte` may start a name segment.
+pub(crate) fn is_lead_name_char(byte: u8) -> bool {
+    byte.is_ascii_uppercase() || byte == b'_'
+}
+
+/// Returns true if `byte` may start an encoded name string.
+pub(crate) fn is_name_string_start(byte: u8) -> bool {
+    is_lead_name_char(byte) || matches!(byte, ROOT_CHAR | PARENT_PREFIX_CHAR | DUAL_NAME_PREFIX | MULTI_NAME_PREFIX)
+}
+
+fn is_name_char(byte: u8) -> bool {
+    is_lead_name_char(byte) || byte.is_ascii_digit()
+}
+
+/// A single four character AML name segment, such as `_SB_` or `DEV0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NameSeg([u8; 4]);
+
+impl NameSeg {
+    /// Creates a segment from its four encoded characters.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AmlError::InvalidPath`] if the characters are not a valid name segment.
+    pub fn new(bytes: [u8; 4]) -> Result<Self, AmlError> {
+        if !is_lead_name_char(bytes[0]) || !bytes[1..].iter().all(|byte| is_name_char(*byte)) {
+            return Err(AmlError::InvalidPath);
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Returns the four encoded characters of the segment.
+    pub fn as_bytes(&self) -> &[u8; 4] {
+        &self.0
+    }
+}
+
+impl FromStr for NameSeg {
+    type Err = AmlError;
+
+    /// Parses a segment of one to four characters. Shorter segments are padded with `_`, as ASL does.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > 4 {
+            return Err(AmlError::InvalidPath);
+        }
+        let mut bytes = [b'_'; 4];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Self::new(bytes)
+    }
+}
+
+impl fmt::Display for NameSeg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{}", *byte as char))
+    }
+}
+
+/// A name string as encoded in AML
+///
+/// Name strings may be absolute (`\_SB.PCI0`), relative to a parent scope (`^^DEV0`), or relative to the current
+/// scope with the ACPI search rules applied (`DEV0`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AmlNameString {
+    /// Whether the name starts at the namespace root.
+    pub root: bool,
+    /// Number of `^` parent prefixes.
+    pub parent_prefixes: usize,
+    /// Name segments following the prefixes.
+    pub segments: Vec<NameSeg>,
+}
+
+impl AmlNameString {
+    /// Decodes the name string at the start of `bytes`, returning it along with its encoded size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AmlError::InvalidAml`] if `bytes` does not start with a complete, valid name string.
+    pub fn parse(bytes: &[u8]) -> Result<(Self, usize), AmlError> {
+        let mut pos = 0;
+        let root = bytes.first() == Some(&ROOT_CHAR);
+        if root {
+            pos += 1;
+        } else {
+            while bytes.get(pos) == Some(&PARENT_PREFIX_CHAR) {
+                pos += 1;
+            }
+        }
+        let parent_prefixes = if root { 0 } else { pos };
+
+        let count = match bytes.get(pos).copied().ok_or(AmlError::InvalidAml)? {
+            NULL_NAME => {
+                pos += 1;
+                0
+            }
+            DUAL_NAME_PREFIX => {
+                pos += 1;
+                2
+            }
+            MULTI_NAME_PREFIX => {
+                let count = *bytes.get(pos + 1).ok_or(AmlError::InvalidAml)? as usize;
+                pos += 2;
+                count
+            }
+            _ => 1,
+        };
+
+        let mut segments = Vec::with_capacity(count);
+        for _ in 0..count {
+            let segment = bytes.get(pos..pos + 4).ok_or(AmlError::InvalidAml)?;
+            let segment =
+                NameSeg::new([segment[0], segment[1], segment[2], segment[3]]).map_err(|_| AmlError::InvalidAml)?;
+            segments.push(segment);
+            pos += 4;
+        }
+
+        Ok((Self { root, parent_prefixes, segments }, pos))
+    }
+
+    /// Encodes the name string as AML.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + self.parent_prefixes + self.segments.len() * 4);
+        if self.root {
+            bytes.push(ROOT_CHAR);
+        }
+        bytes.extend(core::iter::repeat_n(PARENT_PREFIX_CHAR, self.parent_prefixes));
+        match self.segments.len() {
+            0 => bytes.push(NULL_NAME),
+            1 => {}
+            2 => bytes.push(DUAL_NAME_PREFIX),
+            count => {
+                bytes.push(MULTI_NAME_PREFIX);
+                bytes.push(count as u8);
+            }
+        }
+        self.segments.iter().for_each(|segment| bytes.extend_from_slice(segment.as_bytes()));
+        bytes
+    }
+
+    /// Resolves the name against `scope`, the absolute path of the scope it appears in.
+    ///
+    /// Single segment names are resolved in `scope` itself; the ACPI search rules that look for them in enclosing
+    /// scopes only apply to name references, not to the names that objects are declared with.
+    pub fn resolve(&self, scope: &AmlPath) -> AmlPath {
+        let mut segments = if self.root {
+            Vec::new()
+        } else {
+            let keep = scope.0.len().saturating_sub(self.parent_prefixes);
+            scope.0[..keep].to_vec()
+        };
+        segments.extend_from_slice(&self.segments);
+        AmlPath(segments)
+    }
+}
+
+impl FromStr for AmlNameString {
+    type Err = AmlError;
+
+    /// Parses an ASL name such as `\_SB.PCI0`, `^^DEV0`, or `UAR0._CRS`. Segments shorter than four characters are
+    /// padded with `_`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root, rest) = match s.strip_prefix('\\') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let name = rest.trim_start_matches('^');
+        let parent_prefixes = rest.len() - name.len();
+        if root && parent_prefixes > 0 {
+            return Err(AmlError::InvalidPath);
+        }
+        let segments = if name.is_empty() {
+            Vec::new()
+        } else {
+            name.split('.').map(NameSeg::from_str).collect::<Result<_, _>>()?
+        };
+        Ok(Self { root, parent_prefixes, segments })
+    }
+}
+
+impl fmt::Display for AmlNameString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.root {
+            write!(f, "\\")?;
+        }
+        for _ in 0..self.parent_prefixes {
+            write!(f, "^")?;
+        }
+        for (index, segment) in self.segments.iter().enumerate() {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{segment}")?;
+        }
+        Ok(())
+    }
+}
+
+/// An absolute path in the ACPI namespace, such as `\_SB.PCI0._CRS`.
+///
+/// The root scope is the empty path.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct AmlPath(pub Vec<NameSeg>);
+
+impl AmlPath {
+    /// Returns the segments of the path, starting below the root.
+    pub fn segments(&self) -> &[NameSeg] {
+        &self.0
+    }
+}
+
+impl FromStr for AmlPath {
+    type Err = AmlError;
+
+    /// Parses an ASL style absolute path. The leading `\` is optional, and segments shorter than four characters are
+    /// padded with `_` (`\_SB.PCI0` and `\_SB_.PCI0` are the same path).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.strip_prefix('\\').unwrap_or(s);
+        if s.is_empty() {
+            return Ok(Self::default());
+        }
+        s.split('.').map(NameSeg::from_str).collect::<Result<Vec<_>, _>>().map(Self)
+    }
+}
+
+impl fmt::Display for AmlPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\\")?;
+        for (index, segment) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{segment}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    extern crate std;
+    use std::string::ToString;
+
+    #[test]
+    fn test_name_string_round_trip() {
+        let cases: [&[u8]; 5] = [b"\\_SB_", b"^^DEV0", b"\x2ePCI0UAR0", b"\\\x2f\x03_SB_PCI0UAR0", b"\\\x00"];
+        for encoded in cases {
+            let (name, size) = AmlNameString::parse(encoded).unwrap();
+            assert_eq!(size, encoded.len());
+            assert_eq!(name.encode(), encoded);
+        }
+
+        assert_eq!(AmlNameString::parse(b"\\_SB").unwrap_err(), AmlError::InvalidAml);
+        assert_eq!(AmlNameString::parse(b"dev0").unwrap_err(), AmlError::InvalidAml);
+        assert_eq!(AmlNameString::parse(b"\x2f\x02DEV0").unwrap_err(), AmlError::InvalidAml);
+    }
+
+    #[test]
+    fn test_resolve_against_scope() {
+        let scope: AmlPath = "\\_SB.PCI0".parse().unwrap();
+
+        let (relative, _) = AmlNameString::parse(b"UAR0").unwrap();
+        assert_eq!(relative.resolve(&scope).to_string(), "\\_SB_.PCI0.UAR0");
+
+        let (parent, _) = AmlNameString::parse(b"^PWRB").unwrap();
+        assert_eq!(parent.resolve(&scope).to_string(), "\\_SB_.PWRB");
+
+        let (absolute, _) = AmlNameString::parse(b"\\\x2e_GPE_L01").unwrap();
+        assert_eq!(absolute.resolve(&scope).to_string(), "\\_GPE._L01");
+    }
+
+    #[test]
+    fn test_path_from_str() {
+        let path: AmlPath = "\\_SB.DEV0.VAL0".parse().unwrap();
+        assert_eq!(path, "_SB_.DEV0.VAL0".parse().unwrap());
+        assert_eq!(path.segments().len(), 3);
+        assert_eq!("\\".parse::<AmlPath>().unwrap(), AmlPath::default());
+        assert_eq!("\\_SB.TOOLONG".parse::<AmlPath>(), Err(AmlError::InvalidPath));
+        assert_eq!("\\_SB..DEV0".parse::<AmlPath>(), Err(AmlError::InvalidPath));
+        assert_eq!("\\_sb".parse::<AmlPath>(), Err(AmlError::InvalidPath));
+    }
+
+    #[test]
+    fn test_name_string_from_asl() {
+        let name: AmlNameString = "^^UAR0._CRS".parse().unwrap();
+        assert_eq!((name.root, name.parent_prefixes, name.segments.len()), (false, 2, 2));
+        assert_eq!(name.encode(), b"^^\x2eUAR0_CRS");
+
+        let name: AmlNameString = "\\_SB".parse().unwrap();
+        assert_eq!(name.to_string(), "\\_SB_");
+        assert_eq!("\\^DEV0".parse::<AmlNameString>(), Err(AmlError::InvalidPath));
+    }
+}
@@ -0,0 +1,118 @@
+//! ACPI Service Implementation
+//!
+//! Defines the ACPI provider component for use as a service
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+
+extern crate alloc;
+use crate::{
+    manager::{AcpiManager, OemInfo},
+    service::{AcpiProviderImpl, StandardAmlParser},
+};
+use alloc::boxed::Box;
+use patina::{
+    boot_services::tpl::Tpl,
+    component::{Storage, component, service::memory::MemoryManager},
+    error::Result,
+    tpl_mutex::TplMutex,
+};
+
+/// Creator ID written into the XSDT ("PTNA").
+const CREATOR_ID: u32 = u32::from_le_bytes(*b"PTNA");
+
+/// Creator revision written into the XSDT.
+const CREATOR_REVISION: u32 = 1;
+
+/// Initializes and exposes the ACPI provider service.
+///
+/// This component provides the `Service<dyn AcpiProvider>` which includes:
+/// - Table management: `install_acpi_table()`, `uninstall_acpi_table()`
+/// - Table queries: `get_acpi_table()`, `iter()`
+/// - Installation notifications: `register_notify()`
+///
+/// It also provides the `Service<dyn AmlParser>` for walking and patching the AML of installed DSDTs and SSDTs.
+///
+/// The provider allocates the RSDP and XSDT, publishes the RSDP to the UEFI Configuration Table, and installs the
+/// ACPI table and SDT protocols for C/EDKII driver compatibility.
+///
+/// # Example
+///
+/// ```ignore
+/// commands.add_component(AcpiProviderManager::new(*b"PATINA", *b"PATINAQ3", 1));
+/// ```
+pub struct AcpiProviderManager {
+    oem: OemInfo,
+}
+
+#[component]
+impl AcpiProviderManager {
+    /// Create a new ACPI provider with the OEM information used for the RSDP and XSDT.
+    ///
+    /// # Arguments
+    ///
+    /// * `oem_id` - OEM ID written into the RSDP and XSDT
+    /// * `oem_table_id` - OEM table ID written into the XSDT
+    /// * `oem_revision` - OEM revision written into the XSDT
+    pub fn new(oem_id: [u8; 6], oem_table_id: [u8; 8], oem_revision: u32) -> Self {
+        Self {
+            oem: OemInfo {
+                oem_id,
+                oem_table_id,
+                oem_revision,
+                creator_id: CREATOR_ID,
+                creator_revision: CREATOR_REVISION,
+            },
+        }
+    }
+
+    /// Initialize the ACPI provider and register it as a service
+    #[coverage(off)] // Component integration - tested via integration tests
+    fn entry_point(self, storage: &mut Storage) -> Result<()> {
+        // Get the MemoryManager service for table allocations
+        let memory_manager = storage.get_service::<dyn MemoryManager>().ok_or(patina::error::EfiError::Unsupported)?;
+
+        // Allocate the RSDP and an empty XSDT
+        let manager = AcpiManager::new(memory_manager, self.oem)?;
+
+        // TplMutex and AcpiProviderImpl own their BootServices instances.
+        let boot_services = storage.boot_services();
+
+        // Create TplMutex at TPL_NOTIFY for thread safety against timer interrupts
+        let provider = AcpiProviderImpl {
+            manager: TplMutex::new(boot_services.clone(), Tpl::NOTIFY, manager),
+            boot_services: boot_services.clone(),
+        };
+
+        // Leak the service to get a 'static reference for both Rust service and C protocols
+        let provider: &'static AcpiProviderImpl = Box::leak(Box::new(provider));
+
+        storage.add_service(provider);
+        storage.add_service(StandardAmlParser::new(provider));
+
+        // Install ACPI table and SDT protocols for C/EDKII driver compatibility
+        crate::manager::install_acpi_protocols(provider)?;
+
+        // Publish the RSDP so the (empty) XSDT is reachable before any table is installed
+        provider.publish_rsdp()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acpi_provider_manager_new() {
+        let provider = AcpiProviderManager::new(*b"PATINA", *b"PATINAQ3", 7);
+        assert_eq!(provider.oem.oem_id, *b"PATINA");
+        assert_eq!(provider.oem.oem_table_id, *b"PATINAQ3");
+        assert_eq!(provider.oem.oem_revision, 7);
+        assert_eq!(provider.oem.creator_id, CREATOR_ID);
+    }
+}
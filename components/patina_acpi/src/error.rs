@@ -0,0 +1,168 @@
+//! Error types for ACPI operations
+//!
+//! This module defines the error types returned by ACPI service and AML parser operations.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+/// ACPI operation errors
+///
+/// This enum represents all possible errors that can occur while installing, uninstalling, or querying ACPI tables.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AcpiError {
+    // Table format errors
+    /// Table buffer is too small to contain the table header
+    TableTooSmall,
+    /// Table header length does not match the size of the table buffer
+    LengthMismatch,
+    /// Root tables (RSDP, RSDT, XSDT) are owned by the ACPI provider and cannot be installed
+    RootTableManaged,
+
+    // Table management errors
+    /// A table that may only be installed once (FADT, FACS, DSDT) is already installed
+    TableAlreadyInstalled,
+    /// No installed table matches the table key
+    InvalidTableKey,
+    /// No table is installed at the requested index
+    InvalidIndex,
+
+    // Notification errors
+    /// The notify function being unregistered was never registered
+    NotifyNotRegistered,
+
+    // Resource allocation errors
+    /// Failed to allocate memory for an ACPI table
+    AllocationFailed,
+    /// Failed to install the RSDP into the UEFI configuration table
+    PublishFailed,
+}
+
+impl From<AcpiError> for patina::error::EfiError {
+    fn from(error: AcpiError) -> Self {
+        match error {
+            // Malformed input maps to INVALID_PARAMETER
+            AcpiError::TableTooSmall
+            | AcpiError::LengthMismatch
+            | AcpiError::RootTableManaged
+            | AcpiError::NotifyNotRegistered => patina::error::EfiError::InvalidParameter,
+
+            // Duplicate singleton tables map to ACCESS_DENIED, matching the EDK II ACPI table driver
+            AcpiError::TableAlreadyInstalled => patina::error::EfiError::AccessDenied,
+
+            // Lookups that fail map to NOT_FOUND
+            AcpiError::InvalidTableKey | AcpiError::InvalidIndex => patina::error::EfiError::NotFound,
+
+            // Resource errors map to OUT_OF_RESOURCES
+            AcpiError::AllocationFailed | AcpiError::PublishFailed => patina::error::EfiError::OutOfResources,
+        }
+    }
+}
+
+/// AML parser errors
+///
+/// This enum represents all possible errors that can occur while walking or patching the AML of a DSDT or SSDT.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmlError {
+    // Table errors
+    /// No installed table matches the table key
+    TableNotFound,
+    /// The table is not a DSDT or SSDT
+    NotAmlTable,
+    /// The table holds no AML after its header
+    EmptyTable,
+    /// The table the handle was opened on is no longer installed, or the handle does not fit in the table
+    InvalidHandle,
+
+    // Bytecode errors
+    /// The AML is truncated or malformed
+    InvalidAml,
+    /// The AML contains an opcode that is not defined by the ACPI specification
+    UnknownOpcode,
+    /// Expressions are nested deeper than the parser supports
+    NestingTooDeep,
+
+    // Operand errors
+    /// The object has no operand at the requested index
+    InvalidOperandIndex,
+    /// The new operand value is not the type of the existing operand
+    OperandTypeMismatch,
+    /// The new operand value does not encode to the size of the existing operand
+    OperandSizeMismatch,
+
+    // Path errors
+    /// The path is not a valid ACPI namespace path
+    InvalidPath,
+    /// No object is declared at the path
+    PathNotFound,
+}
+
+impl From<AmlError> for patina::error::EfiError {
+    fn from(error: AmlError) -> Self {
+        match error {
+            // Bad arguments map to INVALID_PARAMETER
+            AmlError::NotAmlTable
+            | AmlError::InvalidHandle
+            | AmlError::InvalidOperandIndex
+            | AmlError::OperandTypeMismatch
+            | AmlError::InvalidPath => patina::error::EfiError::InvalidParameter,
+
+            // Values that do not fit the existing operand map to BAD_BUFFER_SIZE, matching SetOption()
+            AmlError::OperandSizeMismatch => patina::error::EfiError::BadBufferSize,
+
+            // Lookups that fail map to NOT_FOUND
+            AmlError::TableNotFound | AmlError::EmptyTable | AmlError::PathNotFound => {
+                patina::error::EfiError::NotFound
+            }
+
+            // AML that cannot be parsed maps to VOLUME_CORRUPTED
+            AmlError::InvalidAml | AmlError::UnknownOpcode | AmlError::NestingTooDeep => {
+                patina::error::EfiError::VolumeCorrupted
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acpi_error_to_efi_error_conversion() {
+        let efi_err: patina::error::EfiError = AcpiError::TableTooSmall.into();
+        assert_eq!(efi_err, patina::error::EfiError::InvalidParameter);
+
+        let efi_err: patina::error::EfiError = AcpiError::RootTableManaged.into();
+        assert_eq!(efi_err, patina::error::EfiError::InvalidParameter);
+
+        let efi_err: patina::error::EfiError = AcpiError::TableAlreadyInstalled.into();
+        assert_eq!(efi_err, patina::error::EfiError::AccessDenied);
+
+        let efi_err: patina::error::EfiError = AcpiError::InvalidTableKey.into();
+        assert_eq!(efi_err, patina::error::EfiError::NotFound);
+
+        let efi_err: patina::error::EfiError = AcpiError::InvalidIndex.into();
+        assert_eq!(efi_err, patina::error::EfiError::NotFound);
+
+        let efi_err: patina::error::EfiError = AcpiError::AllocationFailed.into();
+        assert_eq!(efi_err, patina::error::EfiError::OutOfResources);
+    }
+
+    #[test]
+    fn test_aml_error_to_efi_error_conversion() {
+        let efi_err: patina::error::EfiError = AmlError::NotAmlTable.into();
+        assert_eq!(efi_err, patina::error::EfiError::InvalidParameter);
+
+        let efi_err: patina::error::EfiError = AmlError::OperandSizeMismatch.into();
+        assert_eq!(efi_err, patina::error::EfiError::BadBufferSize);
+
+        let efi_err: patina::error::EfiError = AmlError::PathNotFound.into();
+        assert_eq!(efi_err, patina::error::EfiError::NotFound);
+
+        let efi_err: patina::error::EfiError = AmlError::InvalidAml.into();
+        assert_eq!(efi_err, patina::error::EfiError::VolumeCorrupted);
+    }
+}
@@ -0,0 +1,115 @@
+//! ACPI (Advanced Configuration and Power Interface) component for Patina
+//!
+//! This crate implements the ACPI provider described in RFC 0005. It owns the root ACPI tables, installs tables on
+//! behalf of Rust components and C drivers, and publishes the RSDP to the UEFI Configuration Table. It also implements
+//! the AML parser described in RFC 0020, which walks and patches the AML of installed DSDTs and SSDTs.
+//!
+//! # Architecture Overview
+//!
+//! ```text
+//! ┌──────────────────────────┐      ┌──────────────────────────────┐
+//! │   Platform Components    │      │          C Drivers           │
+//! │ Service<dyn AcpiProvider>│      │ EFI_ACPI_TABLE_PROTOCOL      │
+//! └────────────┬─────────────┘      │ EFI_ACPI_SDT_PROTOCOL        │
+//!              │                    └──────────────┬───────────────┘
+//!              └──────────────┬────────────────────┘
+//!                             ▼
+//!                  ┌──────────────────────┐
+//!                  │   AcpiProviderImpl   │
+//!                  │   (TPL_NOTIFY lock)  │
+//!                  └──────────┬───────────┘
+//!                             ▼
+//!                  ┌──────────────────────┐
+//!                  │     AcpiManager      │
+//!                  │                      │
+//!                  │ • Table copies       │
+//!                  │ • RSDP / XSDT        │
+//!                  │ • FADT linkage       │
+//!                  └──────────┬───────────┘
+//!                             ▼
+//!                  ┌──────────────────────┐
+//!                  │  UEFI Config Table   │
+//!                  │  (ACPI 2.0 RSDP)     │
+//!                  └──────────────────────┘
+//! ```
+//!
+//! ## Table Linkage
+//!
+//! - Every installed table is copied into its own ACPI reclaim allocation (ACPI NVS for the FACS) and checksummed.
+//! - The XSDT lists every installed table except the FACS and DSDT, which are referenced from the FADT instead.
+//! - The FADT `FIRMWARE_CTRL`/`X_FIRMWARE_CTRL` and `DSDT`/`X_DSDT` fields are updated whenever the FADT, FACS, or
+//!   DSDT is installed or uninstalled, in any order.
+//! - The RSDP is reinstalled in the UEFI Configuration Table after every change.
+//!
+//! # Usage Examples
+//!
+//! ## Basic Setup in Platform DXE
+//!
+//! ```ignore
+//! use patina_acpi::component::AcpiProviderManager;
+//!
+//! commands.add_component(AcpiProviderManager::new(*b"PATINA", *b"PATINAQ3", 1));
+//! ```
+//!
+//! ## Installing a Table
+//!
+//! ```ignore
+//! use patina::component::service::Service;
+//! use patina_acpi::{
+//!     acpi_table::{AcpiTable, AcpiTableHeader},
+//!     service::AcpiProvider,
+//! };
+//!
+//! fn install_ssdt(acpi_provider: Service<dyn AcpiProvider>) -> Result<()> {
+//!     let header = AcpiTableHeader { signature: *b"SSDT", revision: 2, oem_id: *b"PATINA", ..Default::default() };
+//!     let key = acpi_provider.install_acpi_table(&AcpiTable::new(header, &SSDT_AML))?;
+//!     log::info!("Installed SSDT with key {}", key);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## Patching AML
+//!
+//! ```ignore
+//! use patina::component::service::Service;
+//! use patina_acpi::{aml::AmlOperand, service::AmlParser};
+//!
+//! fn enable_feature(aml_parser: Service<dyn AmlParser>) -> Result<()> {
+//!     let val0 = aml_parser.find_path(&"\\_SB.DEV0.VAL0".parse()?)?;
+//!     // Operand 0 is the name, operand 1 the value.
+//!     aml_parser.set_operand(&val0, 1, AmlOperand::Integer(0x99))?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Limitations
+//!
+//! Only ACPI 2.0 and later are supported: the provider produces an XSDT and no RSDT, and publishes the RSDP under
+//! the ACPI 2.0 configuration table GUID. AML is patched in place and never resized, so a patched value must encode to
+//! the same size as the original.
+//!
+//! # Module Organization
+//!
+//! - [`acpi_table`]: Generic ACPI table type, header, and signatures
+//! - [`aml`]: AML handles, opcode encodings, and namespace paths
+//! - [`component`]: Component registration and service providers
+//! - [`error`]: Error types for ACPI operations
+//! - [`service`]: Public service trait definitions and types
+//! - `manager`: Private ACPI manager and C protocol implementation (not public)
+//!
+//! # License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+
+#![cfg_attr(all(not(feature = "std"), not(test), not(feature = "mockall")), no_std)]
+#![feature(coverage_attribute)]
+
+pub mod acpi_table;
+pub mod aml;
+pub mod component;
+pub mod error;
+pub mod service;
+
+mod manager;
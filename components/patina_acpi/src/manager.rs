@@ -0,0 +1,82 @@
+//! ACPI Manager Module
+//!
+//! This module provides the ACPI table manager implementation organized into focused submodules:
+//! - `core`: AcpiManager struct, root table construction, and FADT linkage
+//! - `protocol`: C/EDKII protocol compatibility layer (ACPI table and SDT protocols)
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+
+use patina::uefi_protocol::ProtocolInterface;
+
+mod core;
+mod protocol;
+
+pub use core::AcpiManager;
+pub(crate) use core::{AcpiNotify, OemInfo};
+
+use alloc::boxed::Box;
+
+use patina::boot_services::{BootServices, StandardBootServices};
+use r_efi::efi;
+
+use crate::{error::AcpiError, service::AcpiProviderImpl};
+
+use self::protocol::{AcpiSdtProtocol, AcpiTableProtocol, AcpiTableProtocolInternal};
+
+/// Installs the `EFI_ACPI_TABLE_PROTOCOL` and `EFI_ACPI_SDT_PROTOCOL` for C drivers.
+///
+/// Both protocols are installed on a single new handle and operate on `provider`, so tables installed through
+/// either interface are visible to the other and to the Rust service.
+#[coverage(off)] // Protocol installation - tested via integration tests
+pub fn install_acpi_protocols(
+    provider: &'static AcpiProviderImpl<StandardBootServices>,
+) -> Result<efi::Handle, AcpiError> {
+    let table_interface = Box::into_raw(Box::new(AcpiTableProtocolInternal::new(provider)));
+
+    // SAFETY: With #[repr(C)], the protocol field is at offset 0 of AcpiTableProtocolInternal, so the address of the
+    // internal struct is the same as the address of the protocol field.
+    let handle = unsafe {
+        provider.boot_services.install_protocol_interface_unchecked(
+            None,
+            &AcpiTableProtocol::PROTOCOL_GUID,
+            table_interface as *mut _,
+        )
+    };
+    let handle = match handle {
+        Ok(handle) => handle,
+        Err(status) => {
+            // SAFETY: `table_interface` was created from Box::into_raw() above and was not installed, so it is still
+            // exclusively owned here.
+            unsafe { drop(Box::from_raw(table_interface)) };
+            log::error!("Failed to install ACPI table protocol: {:?}", status);
+            return Err(AcpiError::AllocationFailed);
+        }
+    };
+
+    // The SDT protocol is leaked alongside the table protocol for the lifetime of the system.
+    let sdt_interface = Box::into_raw(Box::new(AcpiSdtProtocol::new(provider)));
+
+    // SAFETY: `sdt_interface` points to an AcpiSdtProtocol, which matches the EFI_ACPI_SDT_PROTOCOL layout.
+    let result = unsafe {
+        provider.boot_services.install_protocol_interface_unchecked(
+            Some(handle),
+            &AcpiSdtProtocol::PROTOCOL_GUID,
+            sdt_interface as *mut _,
+        )
+    };
+    if let Err(status) = result {
+        // SAFETY: `sdt_interface` was created from Box::into_raw() above and was not installed.
+        unsafe { drop(Box::from_raw(sdt_interface)) };
+        log::error!("Failed to install ACPI SDT protocol: {:?}", status);
+        return Err(AcpiError::AllocationFailed);
+    }
+
+    Ok(handle)
+}
@@ -0,0 +1,609 @@
+//! Core ACPI manager implementation
+//!
+//! This module provides the ACPI table manager that copies installed tables into ACPI memory, keeps the XSDT and
+//! RSDP in sync with the installed tables, and links the FADT to the FACS and DSDT.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use patina::{
+    component::service::{
+        Service,
+        memory::{AllocationOptions, MemoryManager},
+    },
+    efi_types::EfiMemoryType,
+    uefi_size_to_pages,
+};
+use zerocopy::IntoBytes;
+use zerocopy_derive::*;
+
+use crate::{
+    acpi_table::{
+        AcpiTable, AcpiTableHeader, DSDT_SIGNATURE, FACS_SIGNATURE, FADT_SIGNATURE, RSDP_SIGNATURE, RSDT_SIGNATURE,
+        TableKey, XSDT_SIGNATURE, checksum,
+    },
+    error::AcpiError,
+    service::AcpiNotifyFn,
+};
+
+use super::protocol::EfiAcpiNotifyFn;
+
+/// Root System Description Pointer (ACPI 2.0+)
+/// Per ACPI specification section 5.2.5.3
+#[repr(C, packed)]
+#[derive(Clone, Copy, IntoBytes, Immutable)]
+pub(crate) struct Rsdp {
+    /// "RSD PTR " (0x00)
+    pub signature: [u8; 8],
+    /// Checksum of the first 20 bytes (0x08)
+    pub checksum: u8,
+    /// OEM identifier (0x09)
+    pub oem_id: [u8; 6],
+    /// Revision - 2 for ACPI 2.0+ (0x0F)
+    pub revision: u8,
+    /// 32-bit RSDT address, unused as no RSDT is produced (0x10)
+    pub rsdt_address: u32,
+    /// Length of the whole structure (0x14)
+    pub length: u32,
+    /// 64-bit XSDT address (0x18)
+    pub xsdt_address: u64,
+    /// Checksum of the whole structure (0x20)
+    pub extended_checksum: u8,
+    /// Reserved (0x21)
+    pub reserved: [u8; 3],
+}
+
+/// Length of the RSDP fields covered by the ACPI 1.0 checksum.
+const RSDP_V1_LENGTH: usize = 20;
+
+/// FADT field offsets used to link the FACS and DSDT.
+/// Per ACPI specification section 5.2.9
+const FADT_FIRMWARE_CTRL_OFFSET: usize = 36;
+const FADT_DSDT_OFFSET: usize = 40;
+const FADT_X_FIRMWARE_CTRL_OFFSET: usize = 132;
+const FADT_X_DSDT_OFFSET: usize = 140;
+
+/// Number of XSDT entries allocated up front. The XSDT is reallocated with twice the capacity when it fills up.
+const INITIAL_XSDT_ENTRIES: usize = 32;
+
+/// OEM information written into the RSDP and XSDT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OemInfo {
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+/// A notify function registered through the service or through the SDT protocol.
+#[derive(Clone, Copy)]
+pub(crate) enum AcpiNotify {
+    Rust(AcpiNotifyFn),
+    Efi(EfiAcpiNotifyFn),
+}
+
+impl AcpiNotify {
+    fn same_as(&self, other: &AcpiNotify) -> bool {
+        match (self, other) {
+            (AcpiNotify::Rust(a), AcpiNotify::Rust(b)) => core::ptr::fn_addr_eq(*a, *b),
+            (AcpiNotify::Efi(a), AcpiNotify::Efi(b)) => core::ptr::fn_addr_eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+/// A table copied into ACPI memory.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InstalledTable {
+    pub key: TableKey,
+    pub signature: [u8; 4],
+    pub address: u64,
+    pub length: usize,
+    pages: usize,
+}
+
+/// ACPI table manager
+///
+/// Owns the RSDP, the XSDT and a copy of every installed table. All memory handed out by the manager stays valid
+/// until the table is uninstalled.
+pub struct AcpiManager {
+    memory_manager: Service<dyn MemoryManager>,
+    oem: OemInfo,
+    rsdp_address: u64,
+    xsdt_address: u64,
+    xsdt_capacity: usize,
+    tables: Vec<InstalledTable>,
+    next_key: TableKey,
+    notify_fns: Vec<AcpiNotify>,
+}
+
+impl AcpiManager {
+    /// Creates a manager and allocates an RSDP pointing at an empty XSDT.
+    pub(crate) fn new(memory_manager: Service<dyn MemoryManager>, oem: OemInfo) -> Result<Self, AcpiError> {
+        let rsdp_address = Self::allocate(&memory_manager, size_of::<Rsdp>(), EfiMemoryType::ACPIReclaimMemory)?;
+        let mut manager = Self {
+            memory_manager,
+            oem,
+            rsdp_address,
+            xsdt_address: 0,
+            xsdt_capacity: 0,
+            tables: Vec::new(),
+            next_key: 1,
+            notify_fns: Vec::new(),
+        };
+        manager.build_xsdt()?;
+        Ok(manager)
+    }
+
+    /// Returns the address of the RSDP to publish in the UEFI configuration table.
+    pub(crate) fn rsdp_address(&self) -> u64 {
+        self.rsdp_address
+    }
+
+    /// Returns the address of the XSDT.
+    #[allow(dead_code)] // Only used in tests
+    pub(crate) fn xsdt_address(&self) -> u64 {
+        self.xsdt_address
+    }
+
+    /// Copies a table into ACPI memory, checksums it, and links it into the XSDT or the FADT.
+    pub(crate) fn install(&mut self, table: &AcpiTable) -> Result<TableKey, AcpiError> {
+        let signature = table.signature();
+        if signature == RSDT_SIGNATURE || signature == XSDT_SIGNATURE || table.as_bytes().starts_with(&RSDP_SIGNATURE) {
+            return Err(AcpiError::RootTableManaged);
+        }
+        if [FADT_SIGNATURE, FACS_SIGNATURE, DSDT_SIGNATURE].contains(&signature) && self.find(signature).is_some() {
+            return Err(AcpiError::TableAlreadyInstalled);
+        }
+
+        // The FACS holds the global lock and waking vector, so the OS requires it in ACPI NVS memory.
+        let memory_type =
+            if signature == FACS_SIGNATURE { EfiMemoryType::ACPIMemoryNVS } else { EfiMemoryType::ACPIReclaimMemory };
+        let length = table.as_bytes().len();
+        let address = Self::allocate(&self.memory_manager, length, memory_type)?;
+
+        // SAFETY: The allocation above is at least `length` bytes and owned by the manager.
+        let bytes = unsafe { table_bytes_mut(address, length) };
+        bytes.copy_from_slice(table.as_bytes());
+        if signature != FACS_SIGNATURE {
+            bytes[AcpiTableHeader::CHECKSUM_OFFSET] = 0;
+            bytes[AcpiTableHeader::CHECKSUM_OFFSET] = checksum(bytes);
+        }
+
+        let key = self.next_key;
+        self.next_key += 1;
+        self.tables.push(InstalledTable { key, signature, address, length, pages: uefi_size_to_pages!(length) });
+
+        self.link_fadt();
+        if let Err(err) = self.build_xsdt() {
+            self.remove(key);
+            self.link_fadt();
+            return Err(err);
+        }
+
+        Ok(key)
+    }
+
+    /// Removes a table from the XSDT or the FADT and frees its memory.
+    pub(crate) fn uninstall(&mut self, key: TableKey) -> Result<(), AcpiError> {
+        if !self.tables.iter().any(|table| table.key == key) {
+            return Err(AcpiError::InvalidTableKey);
+        }
+        self.remove(key);
+        self.link_fadt();
+        self.build_xsdt()
+    }
+
+    /// Returns the installed table at `index`, in installation order.
+    pub(crate) fn get(&self, index: usize) -> Result<AcpiTable, AcpiError> {
+        self.tables.get(index).map(Self::read).ok_or(AcpiError::InvalidIndex)
+    }
+
+    /// Returns the installed table with the given key.
+    pub(crate) fn get_by_key(&self, key: TableKey) -> Option<AcpiTable> {
+        self.tables.iter().find(|table| table.key == key).map(Self::read)
+    }
+
+    /// Returns the memory location of the installed table at `index`.
+    pub(crate) fn installed(&self, index: usize) -> Option<InstalledTable> {
+        self.tables.get(index).copied()
+    }
+
+    /// Returns the memory location of the installed table with the given key.
+    pub(crate) fn installed_by_key(&self, key: TableKey) -> Option<InstalledTable> {
+        self.tables.iter().find(|table| table.key == key).copied()
+    }
+
+    /// Returns a copy of every installed table, in installation order.
+    pub(crate) fn tables(&self) -> Vec<AcpiTable> {
+        self.tables.iter().map(Self::read).collect()
+    }
+
+    /// Calls `f` with the installed copy of the table with the given key.
+    pub(crate) fn with_table<R>(&self, key: TableKey, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let table = self.installed_by_key(key)?;
+        // SAFETY: Installed tables stay allocated and owned by the manager until they are uninstalled, and the
+        // manager is borrowed for the duration of `f`.
+        Some(f(unsafe { core::slice::from_raw_parts(table.address as *const u8, table.length) }))
+    }
+
+    /// Calls `f` with the installed copy of the table with the given key, then recalculates the table checksum.
+    pub(crate) fn with_table_mut<R>(&mut self, key: TableKey, f: impl FnOnce(&mut [u8]) -> R) -> Option<R> {
+        let table = self.installed_by_key(key)?;
+        // SAFETY: Installed tables stay allocated and owned by the manager until they are uninstalled, and the
+        // manager is mutably borrowed for the duration of `f`.
+        let bytes = unsafe { table_bytes_mut(table.address, table.length) };
+        let result = f(bytes);
+        if table.signature != FACS_SIGNATURE {
+            bytes[AcpiTableHeader::CHECKSUM_OFFSET] = 0;
+            bytes[AcpiTableHeader::CHECKSUM_OFFSET] = checksum(bytes);
+        }
+        Some(result)
+    }
+
+    /// Returns the installed table whose memory contains `address`.
+    pub(crate) fn installed_containing(&self, address: u64) -> Option<InstalledTable> {
+        self.tables
+            .iter()
+            .find(|table| (table.address..table.address + table.length as u64).contains(&address))
+            .copied()
+    }
+
+    /// Registers or unregisters a notify function.
+    pub(crate) fn register_notify(&mut self, should_register: bool, notify: AcpiNotify) -> Result<(), AcpiError> {
+        let position = self.notify_fns.iter().position(|registered| registered.same_as(&notify));
+        match (should_register, position) {
+            (true, None) => self.notify_fns.push(notify),
+            (true, Some(_)) => {}
+            (false, Some(position)) => {
+                self.notify_fns.remove(position);
+            }
+            (false, None) => return Err(AcpiError::NotifyNotRegistered),
+        }
+        Ok(())
+    }
+
+    /// Returns the registered notify functions.
+    pub(crate) fn notify_fns(&self) -> Vec<AcpiNotify> {
+        self.notify_fns.clone()
+    }
+
+    fn find(&self, signature: [u8; 4]) -> Option<&InstalledTable> {
+        self.tables.iter().find(|table| table.signature == signature)
+    }
+
+    fn read(table: &InstalledTable) -> AcpiTable {
+        // SAFETY: Installed tables stay allocated and owned by the manager until they are uninstalled.
+        let bytes = unsafe { core::slice::from_raw_parts(table.address as *const u8, table.length) };
+        AcpiTable::from_bytes(bytes).expect("installed tables were validated on install").with_key(table.key)
+    }
+
+    fn remove(&mut self, key: TableKey) {
+        if let Some(position) = self.tables.iter().position(|table| table.key == key) {
+            let table = self.tables.remove(position);
+            // SAFETY: The table memory was allocated by the manager and is no longer referenced by the XSDT or FADT
+            // once it is relinked.
+            if unsafe { self.memory_manager.free_pages(table.address as usize, table.pages) }.is_err() {
+                log::warn!("Failed to free ACPI table memory at {:#x}", table.address);
+            }
+        }
+    }
+
+    /// Points the FADT at the installed FACS and DSDT, or clears the links when they are not installed.
+    fn link_fadt(&mut self) {
+        let Some(fadt) = self.find(FADT_SIGNATURE).copied() else {
+            return;
+        };
+        let facs = self.find(FACS_SIGNATURE).map_or(0, |table| table.address);
+        let dsdt = self.find(DSDT_SIGNATURE).map_or(0, |table| table.address);
+
+        // SAFETY: The FADT is installed, so its memory is allocated and owned by the manager.
+        let bytes = unsafe { table_bytes_mut(fadt.address, fadt.length) };
+
+        // Only one of FIRMWARE_CTRL and X_FIRMWARE_CTRL may be set, so the 32-bit field is preferred when possible.
+        let facs32 = u32::try_from(facs).unwrap_or(0);
+        write_field(bytes, FADT_FIRMWARE_CTRL_OFFSET, &facs32.to_le_bytes());
+        write_field(bytes, FADT_DSDT_OFFSET, &u32::try_from(dsdt).unwrap_or(0).to_le_bytes());
+        write_field(bytes, FADT_X_FIRMWARE_CTRL_OFFSET, &(if facs32 == 0 { facs } else { 0 }).to_le_bytes());
+        write_field(bytes, FADT_X_DSDT_OFFSET, &dsdt.to_le_bytes());
+
+        bytes[AcpiTableHeader::CHECKSUM_OFFSET] = 0;
+        bytes[AcpiTableHeader::CHECKSUM_OFFSET] = checksum(bytes);
+    }
+
+    /// Rewrites the XSDT with every installed table except the FACS and DSDT, which are reached through the FADT.
+    fn build_xsdt(&mut self) -> Result<(), AcpiError> {
+        let entries: Vec<u64> = self
+            .tables
+            .iter()
+            .filter(|table| table.signature != FACS_SIGNATURE && table.signature != DSDT_SIGNATURE)
+            .map(|table| table.address)
+            .collect();
+
+        if entries.len() > self.xsdt_capacity || self.xsdt_address == 0 {
+            let capacity = (self.xsdt_capacity * 2).max(INITIAL_XSDT_ENTRIES).max(entries.len());
+            let size = size_of::<AcpiTableHeader>() + capacity * size_of::<u64>();
+            let address = Self::allocate(&self.memory_manager, size, EfiMemoryType::ACPIReclaimMemory)?;
+            if self.xsdt_address != 0 {
+                let old_size = size_of::<AcpiTableHeader>() + self.xsdt_capacity * size_of::<u64>();
+                // SAFETY: The old XSDT was allocated by the manager and is replaced in the RSDP below.
+                if unsafe { self.memory_manager.free_pages(self.xsdt_address as usize, uefi_size_to_pages!(old_size)) }
+                    .is_err()
+                {
+                    log::warn!("Failed to free previous XSDT at {:#x}", self.xsdt_address);
+                }
+            }
+            self.xsdt_address = address;
+            self.xsdt_capacity = capacity;
+            self.write_rsdp();
+        }
+
+        let length = size_of::<AcpiTableHeader>() + entries.len() * size_of::<u64>();
+        let header = AcpiTableHeader {
+            signature: XSDT_SIGNATURE,
+            length: length as u32,
+            revision: 1,
+            checksum: 0,
+            oem_id: self.oem.oem_id,
+            oem_table_id: self.oem.oem_table_id,
+            oem_revision: self.oem.oem_revision,
+            creator_id: self.oem.creator_id,
+            creator_revision: self.oem.creator_revision,
+        };
+
+        // SAFETY: The XSDT allocation holds the header and `xsdt_capacity` entries, which is at least `entries.len()`.
+        let bytes = unsafe { table_bytes_mut(self.xsdt_address, length) };
+        let (header_bytes, entry_bytes) = bytes.split_at_mut(size_of::<AcpiTableHeader>());
+        header_bytes.copy_from_slice(header.as_bytes());
+        entry_bytes.copy_from_slice(entries.as_bytes());
+        bytes[AcpiTableHeader::CHECKSUM_OFFSET] = checksum(bytes);
+        Ok(())
+    }
+
+    fn write_rsdp(&mut self) {
+        let mut rsdp = Rsdp {
+            signature: RSDP_SIGNATURE,
+            checksum: 0,
+            oem_id: self.oem.oem_id,
+            revision: 2,
+            rsdt_address: 0,
+            length: size_of::<Rsdp>() as u32,
+            xsdt_address: self.xsdt_address,
+            extended_checksum: 0,
+            reserved: [0; 3],
+        };
+        rsdp.checksum = checksum(&rsdp.as_bytes()[..RSDP_V1_LENGTH]);
+        rsdp.extended_checksum = checksum(rsdp.as_bytes());
+
+        // SAFETY: The RSDP allocation is owned by the manager and large enough for the structure.
+        unsafe { table_bytes_mut(self.rsdp_address, size_of::<Rsdp>()) }.copy_from_slice(rsdp.as_bytes());
+    }
+
+    fn allocate(
+        memory_manager: &Service<dyn MemoryManager>,
+        size: usize,
+        memory_type: EfiMemoryType,
+    ) -> Result<u64, AcpiError> {
+        let allocation = memory_manager
+            .allocate_zero_pages(uefi_size_to_pages!(size), AllocationOptions::new().with_memory_type(memory_type))
+            .map_err(|_| AcpiError::AllocationFailed)?;
+        let slice = allocation.into_raw_slice::<u8>();
+        Ok(slice as *mut u8 as u64)
+    }
+}
+
+/// Writes `value` at `offset` if the table is long enough to hold the field.
+fn write_field(bytes: &mut [u8], offset: usize, value: &[u8]) {
+    if let Some(field) = bytes.get_mut(offset..offset + value.len()) {
+        field.copy_from_slice(value);
+    }
+}
+
+/// Returns the ACPI memory at `address` as a mutable byte slice.
+///
+/// # Safety
+///
+/// `address` must point to at least `length` bytes allocated by the manager that are not otherwise borrowed.
+unsafe fn table_bytes_mut<'a>(address: u64, length: usize) -> &'a mut [u8] {
+    // SAFETY: Guaranteed by the caller.
+    unsafe { core::slice::from_raw_parts_mut(address as *mut u8, length) }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::acpi_table::AcpiVersion;
+    use patina::component::service::memory::StdMemoryManager;
+    extern crate std;
+    use std::{boxed::Box, vec, vec::Vec};
+
+    fn oem() -> OemInfo {
+        OemInfo {
+            oem_id: *b"PATINA",
+            oem_table_id: *b"PATINAXS",
+            oem_revision: 1,
+            creator_id: u32::from_le_bytes(*b"PTNA"),
+            creator_revision: 1,
+        }
+    }
+
+    fn manager() -> AcpiManager {
+        AcpiManager::new(Service::mock(Box::new(StdMemoryManager::new())), oem()).expect("failed to create manager")
+    }
+
+    fn table(signature: &[u8; 4], data_len: usize) -> AcpiTable {
+        AcpiTable::new(AcpiTableHeader { signature: *signature, ..Default::default() }, &vec![0xA5; data_len])
+    }
+
+    fn facs() -> AcpiTable {
+        let mut bytes = vec![0u8; 64];
+        bytes[..4].copy_from_slice(&FACS_SIGNATURE);
+        bytes[4] = 64;
+        AcpiTable::from_bytes(&bytes).unwrap()
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn xsdt_entries(manager: &AcpiManager) -> Vec<u64> {
+        // SAFETY: The XSDT is allocated by the manager and at least a header long.
+        let header = unsafe { core::slice::from_raw_parts(manager.xsdt_address() as *const u8, 36) };
+        let length = read_u32(header, 4) as usize;
+        // SAFETY: The XSDT length covers its entries.
+        let bytes = unsafe { core::slice::from_raw_parts(manager.xsdt_address() as *const u8, length) };
+        assert_eq!(checksum(bytes), 0);
+        bytes[36..].chunks(8).map(|entry| u64::from_le_bytes(entry.try_into().unwrap())).collect()
+    }
+
+    #[test]
+    fn test_new_publishes_rsdp_with_empty_xsdt() {
+        let manager = manager();
+        // SAFETY: The RSDP is allocated by the manager.
+        let rsdp = unsafe { core::slice::from_raw_parts(manager.rsdp_address() as *const u8, size_of::<Rsdp>()) };
+
+        assert_eq!(&rsdp[..8], b"RSD PTR ");
+        assert_eq!(checksum(&rsdp[..RSDP_V1_LENGTH]), 0);
+        assert_eq!(checksum(rsdp), 0);
+        assert_eq!(rsdp[15], 2);
+        assert_eq!(read_u64(rsdp, 24), manager.xsdt_address());
+        assert!(xsdt_entries(&manager).is_empty());
+    }
+
+    #[test]
+    fn test_install_checksums_and_adds_to_xsdt() {
+        let mut manager = manager();
+        let key = manager.install(&table(b"SSDT", 16)).unwrap();
+
+        let installed = manager.installed_by_key(key).unwrap();
+        assert_eq!(xsdt_entries(&manager), vec![installed.address]);
+
+        let table = manager.get(0).unwrap();
+        assert_eq!(table.table_key(), key);
+        assert_eq!(checksum(table.as_bytes()), 0);
+        assert_eq!(manager.get(1), Err(AcpiError::InvalidIndex));
+    }
+
+    #[test]
+    fn test_install_fixes_bad_checksum() {
+        let mut manager = manager();
+        let mut bytes = table(b"SSDT", 4).as_bytes().to_vec();
+        bytes[9] = bytes[9].wrapping_add(1);
+
+        manager.install(&AcpiTable::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(checksum(manager.get(0).unwrap().as_bytes()), 0);
+    }
+
+    #[test]
+    fn test_fadt_links_facs_and_dsdt_in_any_order() {
+        let mut manager = manager();
+        let dsdt = manager.install(&table(b"DSDT", 8)).unwrap();
+        let fadt = manager.install(&table(b"FACP", 276 - 36)).unwrap();
+        let facs = manager.install(&facs()).unwrap();
+
+        let facs_address = manager.installed_by_key(facs).unwrap().address;
+        let dsdt_address = manager.installed_by_key(dsdt).unwrap().address;
+        let fadt_table = manager.get_by_key(fadt).unwrap();
+        let bytes = fadt_table.as_bytes();
+
+        assert_eq!(checksum(bytes), 0);
+        assert_eq!(read_u64(bytes, FADT_X_DSDT_OFFSET), dsdt_address);
+        assert_eq!(
+            read_u32(bytes, FADT_DSDT_OFFSET) as u64,
+            if dsdt_address <= u32::MAX as u64 { dsdt_address } else { 0 }
+        );
+        let firmware_ctrl = read_u32(bytes, FADT_FIRMWARE_CTRL_OFFSET) as u64;
+        let x_firmware_ctrl = read_u64(bytes, FADT_X_FIRMWARE_CTRL_OFFSET);
+        assert_eq!(firmware_ctrl.max(x_firmware_ctrl), facs_address);
+        assert_eq!(firmware_ctrl.min(x_firmware_ctrl), 0);
+
+        // Only the FADT is referenced from the XSDT.
+        assert_eq!(xsdt_entries(&manager), vec![manager.installed_by_key(fadt).unwrap().address]);
+
+        manager.uninstall(dsdt).unwrap();
+        let fadt_table = manager.get_by_key(fadt).unwrap();
+        assert_eq!(read_u64(fadt_table.as_bytes(), FADT_X_DSDT_OFFSET), 0);
+        assert_eq!(checksum(fadt_table.as_bytes()), 0);
+    }
+
+    #[test]
+    fn test_singleton_and_root_tables_are_rejected() {
+        let mut manager = manager();
+        manager.install(&table(b"FACP", 244 - 36)).unwrap();
+        assert_eq!(manager.install(&table(b"FACP", 244 - 36)), Err(AcpiError::TableAlreadyInstalled));
+        assert_eq!(manager.install(&table(b"XSDT", 8)), Err(AcpiError::RootTableManaged));
+        assert_eq!(manager.install(&table(b"RSDT", 4)), Err(AcpiError::RootTableManaged));
+    }
+
+    #[test]
+    fn test_uninstall_removes_from_xsdt() {
+        let mut manager = manager();
+        let first = manager.install(&table(b"SSDT", 4)).unwrap();
+        let second = manager.install(&table(b"APIC", 4)).unwrap();
+
+        manager.uninstall(first).unwrap();
+        assert_eq!(xsdt_entries(&manager), vec![manager.installed_by_key(second).unwrap().address]);
+        assert_eq!(manager.uninstall(first), Err(AcpiError::InvalidTableKey));
+        assert_eq!(manager.tables().len(), 1);
+    }
+
+    #[test]
+    fn test_xsdt_grows_and_updates_rsdp() {
+        let mut manager = manager();
+        let first_xsdt = manager.xsdt_address();
+        for _ in 0..INITIAL_XSDT_ENTRIES + 1 {
+            manager.install(&table(b"SSDT", 4)).unwrap();
+        }
+
+        assert_eq!(xsdt_entries(&manager).len(), INITIAL_XSDT_ENTRIES + 1);
+        assert_ne!(manager.xsdt_address(), first_xsdt);
+        // SAFETY: The RSDP is allocated by the manager.
+        let rsdp = unsafe { core::slice::from_raw_parts(manager.rsdp_address() as *const u8, size_of::<Rsdp>()) };
+        assert_eq!(read_u64(rsdp, 24), manager.xsdt_address());
+        assert_eq!(checksum(rsdp), 0);
+    }
+
+    #[test]
+    fn test_register_notify() {
+        fn notify(_table: &AcpiTable, _version: AcpiVersion, _key: TableKey) -> Result<(), AcpiError> {
+            Ok(())
+        }
+
+        let mut manager = manager();
+        assert_eq!(manager.register_notify(false, AcpiNotify::Rust(notify)), Err(AcpiError::NotifyNotRegistered));
+        manager.register_notify(true, AcpiNotify::Rust(notify)).unwrap();
+        manager.register_notify(true, AcpiNotify::Rust(notify)).unwrap();
+        assert_eq!(manager.notify_fns().len(), 1);
+        manager.register_notify(false, AcpiNotify::Rust(notify)).unwrap();
+        assert!(manager.notify_fns().is_empty());
+    }
+
+    #[test]
+    fn test_with_table_mut_updates_checksum() {
+        let mut manager = manager();
+        let key = manager.install(&table(b"SSDT", 4)).unwrap();
+
+        manager.with_table_mut(key, |bytes| bytes[36] = 0x5A).unwrap();
+        let table = manager.get_by_key(key).unwrap();
+        assert_eq!(table.data()[0], 0x5A);
+        assert_eq!(checksum(table.as_bytes()), 0);
+
+        let address = manager.installed_by_key(key).unwrap().address;
+        assert_eq!(manager.installed_containing(address + 10).unwrap().key, key);
+        assert!(manager.installed_containing(address + 40).is_none());
+        assert_eq!(manager.with_table(key + 1, |bytes| bytes.len()), None);
+    }
+}
@@ -0,0 +1,596 @@
+//! C/EDKII protocol compatibility layer
+//!
+//! This module provides the `EFI_ACPI_TABLE_PROTOCOL` and `EFI_ACPI_SDT_PROTOCOL` interfaces for C drivers. Both are
+//! thin wrappers around the [`AcpiProviderImpl`] that also backs the Rust service.
+//!
+//! This module is excluded from coverage as it's FFI code tested via integration.
+//!
+//! ## License
+//!
+//! Copyright (C) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::{ffi::c_void, str::FromStr};
+
+use patina::{boot_services::StandardBootServices, error::EfiError, uefi_protocol::ProtocolInterface};
+use r_efi::efi;
+
+use crate::{
+    acpi_table::{ACPI_EXPOSED_VERSIONS, AcpiTable, AcpiTableHeader, AcpiVersion, DSDT_SIGNATURE, SSDT_SIGNATURE},
+    aml::{self, AmlHandle, AmlNameString, AmlOperandKind, OperandSlot},
+    error::{AcpiError, AmlError},
+    service::{AcpiProvider, AcpiProviderImpl, AmlParser, StandardAmlParser},
+};
+
+use super::core::AcpiNotify;
+
+/// Notify function registered through `EFI_ACPI_SDT_PROTOCOL.RegisterNotify()`.
+pub(crate) type EfiAcpiNotifyFn = extern "efiapi" fn(*mut AcpiTableHeader, AcpiVersion, usize) -> efi::Status;
+
+/// Opaque AML object handle used by the SDT protocol AML functions.
+type AcpiHandle = *mut c_void;
+
+/// AML object data type used by the SDT protocol AML functions.
+type AcpiDataType = u32;
+
+/// `EFI_ACPI_DATA_TYPE` values, per the PI specification.
+const ACPI_DATA_TYPE_NONE: AcpiDataType = 0;
+const ACPI_DATA_TYPE_OPCODE: AcpiDataType = 1;
+const ACPI_DATA_TYPE_NAME_STRING: AcpiDataType = 2;
+const ACPI_DATA_TYPE_OP: AcpiDataType = 3;
+const ACPI_DATA_TYPE_UINT: AcpiDataType = 4;
+const ACPI_DATA_TYPE_STRING: AcpiDataType = 5;
+const ACPI_DATA_TYPE_CHILD: AcpiDataType = 6;
+
+/// Signature of the handles returned by the SDT protocol AML functions ("AMLH").
+const SDT_HANDLE_SIGNATURE: u32 = u32::from_le_bytes(*b"AMLH");
+
+/// Heap allocated state behind an `EFI_ACPI_HANDLE`.
+#[repr(C)]
+struct SdtHandle {
+    signature: u32,
+    handle: AmlHandle,
+}
+
+impl SdtHandle {
+    fn into_raw(handle: AmlHandle) -> AcpiHandle {
+        Box::into_raw(Box::new(Self { signature: SDT_HANDLE_SIGNATURE, handle })) as AcpiHandle
+    }
+
+    /// Returns the state behind `handle`, or `None` if it is not an open handle returned by this protocol.
+    fn from_raw<'a>(handle: AcpiHandle) -> Option<&'a Self> {
+        if handle.is_null() || !(handle as usize).is_multiple_of(core::mem::align_of::<Self>()) {
+            return None;
+        }
+        // SAFETY: The pointer is non-null and aligned. Callers may only pass handles returned by this protocol, and
+        // the signature is checked so that closed handles are rejected.
+        let sdt_handle = unsafe { &*(handle as *const Self) };
+        (sdt_handle.signature == SDT_HANDLE_SIGNATURE).then_some(sdt_handle)
+    }
+}
+
+/// Provider used by the SDT protocol, whose functions do not receive a protocol pointer.
+static SDT_PROVIDER: spin::Once<&'static AcpiProviderImpl<StandardBootServices>> = spin::Once::new();
+
+fn to_status(error: AcpiError) -> efi::Status {
+    EfiError::from(error).into()
+}
+
+fn aml_status(error: AmlError) -> efi::Status {
+    EfiError::from(error).into()
+}
+
+#[repr(C)]
+pub(super) struct AcpiTableProtocol {
+    install_acpi_table: AcpiTableInstall,
+    uninstall_acpi_table: AcpiTableUninstall,
+}
+
+/// Internal protocol struct that packs the provider behind the protocol
+#[repr(C)]
+pub(super) struct AcpiTableProtocolInternal {
+    // The public protocol that external callers will depend on
+    pub(super) protocol: AcpiTableProtocol,
+
+    // Internal component access only! Does not exist in C definition
+    pub(super) provider: &'static AcpiProviderImpl<StandardBootServices>,
+}
+
+// SAFETY: AcpiTableProtocol matches the EFI_ACPI_TABLE_PROTOCOL layout with function pointers in the correct order.
+unsafe impl ProtocolInterface for AcpiTableProtocol {
+    const PROTOCOL_GUID: efi::Guid =
+        efi::Guid::from_fields(0xffe06bdd, 0x6107, 0x46a6, 0x7b, 0xb2, &[0x5a, 0x9c, 0x7e, 0xc5, 0x27, 0x5c]);
+}
+
+type AcpiTableInstall = extern "efiapi" fn(*const AcpiTableProtocol, *const c_void, usize, *mut usize) -> efi::Status;
+
+type AcpiTableUninstall = extern "efiapi" fn(*const AcpiTableProtocol, usize) -> efi::Status;
+
+impl AcpiTableProtocolInternal {
+    /// Creates a new ACPI table protocol internal structure
+    ///
+    /// This constructor is tested via integration as it requires 'static boot services which cannot be mocked in
+    /// unit tests.
+    #[coverage(off)]
+    pub(super) fn new(provider: &'static AcpiProviderImpl<StandardBootServices>) -> Self {
+        Self {
+            protocol: AcpiTableProtocol {
+                install_acpi_table: AcpiTableProtocol::install_acpi_table_ext,
+                uninstall_acpi_table: AcpiTableProtocol::uninstall_acpi_table_ext,
+            },
+            provider,
+        }
+    }
+}
+
+impl AcpiTableProtocol {
+    /// Returns the internal structure containing `protocol`, or `None` if the pointer cannot be one we installed.
+    fn internal<'a>(protocol: *const AcpiTableProtocol) -> Option<&'a AcpiTableProtocolInternal> {
+        if protocol.is_null() || !(protocol as usize).is_multiple_of(core::mem::align_of::<AcpiTableProtocolInternal>())
+        {
+            return None;
+        }
+
+        // SAFETY: The pointer is non-null and aligned. With #[repr(C)], AcpiTableProtocol is the first field of
+        // AcpiTableProtocolInternal, so a pointer to the installed protocol is also a pointer to its container.
+        Some(unsafe { &*(protocol as *const AcpiTableProtocolInternal) })
+    }
+
+    /// C protocol implementation for installing ACPI tables
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn install_acpi_table_ext(
+        protocol: *const AcpiTableProtocol,
+        acpi_table_buffer: *const c_void,
+        acpi_table_buffer_size: usize,
+        table_key: *mut usize,
+    ) -> efi::Status {
+        if acpi_table_buffer.is_null() || table_key.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(internal) = Self::internal(protocol) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        // SAFETY: The caller guarantees that the buffer holds `acpi_table_buffer_size` bytes.
+        let bytes = unsafe { core::slice::from_raw_parts(acpi_table_buffer as *const u8, acpi_table_buffer_size) };
+        let result = AcpiTable::from_bytes(bytes).and_then(|table| internal.provider.install_acpi_table(&table));
+
+        match result {
+            Ok(key) => {
+                // SAFETY: table_key was checked for null and is guaranteed writable by the caller.
+                unsafe { table_key.write_unaligned(key) };
+                efi::Status::SUCCESS
+            }
+            Err(err) => to_status(err),
+        }
+    }
+
+    /// C protocol implementation for uninstalling ACPI tables
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn uninstall_acpi_table_ext(protocol: *const AcpiTableProtocol, table_key: usize) -> efi::Status {
+        let Some(internal) = Self::internal(protocol) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        match internal.provider.uninstall_acpi_table(table_key) {
+            Ok(()) => efi::Status::SUCCESS,
+            Err(err) => to_status(err),
+        }
+    }
+}
+
+#[repr(C)]
+pub(super) struct AcpiSdtProtocol {
+    acpi_version: AcpiVersion,
+    get_acpi_table: AcpiSdtGetAcpiTable,
+    register_notify: AcpiSdtRegisterNotify,
+    open: AcpiSdtOpen,
+    open_sdt: AcpiSdtOpenSdt,
+    close: AcpiSdtClose,
+    get_child: AcpiSdtGetChild,
+    get_option: AcpiSdtGetOption,
+    set_option: AcpiSdtSetOption,
+    find_path: AcpiSdtFindPath,
+}
+
+// SAFETY: AcpiSdtProtocol matches the EFI_ACPI_SDT_PROTOCOL layout with fields in the correct order.
+unsafe impl ProtocolInterface for AcpiSdtProtocol {
+    const PROTOCOL_GUID: efi::Guid =
+        efi::Guid::from_fields(0xeb97088e, 0xcfdf, 0x49c6, 0xbe, 0x4b, &[0xd9, 0x06, 0xa5, 0xb2, 0x0e, 0x86]);
+}
+
+type AcpiSdtGetAcpiTable =
+    extern "efiapi" fn(usize, *mut *mut AcpiTableHeader, *mut AcpiVersion, *mut usize) -> efi::Status;
+
+type AcpiSdtRegisterNotify = extern "efiapi" fn(efi::Boolean, Option<EfiAcpiNotifyFn>) -> efi::Status;
+
+type AcpiSdtOpen = extern "efiapi" fn(*mut c_void, *mut AcpiHandle) -> efi::Status;
+
+type AcpiSdtOpenSdt = extern "efiapi" fn(usize, *mut AcpiHandle) -> efi::Status;
+
+type AcpiSdtClose = extern "efiapi" fn(AcpiHandle) -> efi::Status;
+
+type AcpiSdtGetChild = extern "efiapi" fn(AcpiHandle, *mut AcpiHandle) -> efi::Status;
+
+type AcpiSdtGetOption =
+    extern "efiapi" fn(AcpiHandle, usize, *mut AcpiDataType, *mut *const c_void, *mut usize) -> efi::Status;
+
+type AcpiSdtSetOption = extern "efiapi" fn(AcpiHandle, usize, *const c_void, usize) -> efi::Status;
+
+type AcpiSdtFindPath = extern "efiapi" fn(AcpiHandle, *mut c_void, *mut AcpiHandle) -> efi::Status;
+
+impl AcpiSdtProtocol {
+    /// Creates the SDT protocol and records the provider its functions operate on.
+    #[coverage(off)]
+    pub(super) fn new(provider: &'static AcpiProviderImpl<StandardBootServices>) -> Self {
+        SDT_PROVIDER.call_once(|| provider);
+        Self {
+            acpi_version: ACPI_EXPOSED_VERSIONS,
+            get_acpi_table: Self::get_acpi_table_ext,
+            register_notify: Self::register_notify_ext,
+            open: Self::open_ext,
+            open_sdt: Self::open_sdt_ext,
+            close: Self::close_ext,
+            get_child: Self::get_child_ext,
+            get_option: Self::get_option_ext,
+            set_option: Self::set_option_ext,
+            find_path: Self::find_path_ext,
+        }
+    }
+
+    /// C protocol implementation for retrieving installed tables by index
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn get_acpi_table_ext(
+        index: usize,
+        table: *mut *mut AcpiTableHeader,
+        version: *mut AcpiVersion,
+        table_key: *mut usize,
+    ) -> efi::Status {
+        if table.is_null() || version.is_null() || table_key.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+
+        let Some(installed) = provider.manager.lock().installed(index) else {
+            return efi::Status::NOT_FOUND;
+        };
+
+        // SAFETY: The output pointers were checked for null and are guaranteed writable by the caller. The table
+        // pointer refers to the installed copy, which stays valid until the table is uninstalled.
+        unsafe {
+            table.write_unaligned(installed.address as *mut AcpiTableHeader);
+            version.write_unaligned(ACPI_EXPOSED_VERSIONS);
+            table_key.write_unaligned(installed.key);
+        }
+        efi::Status::SUCCESS
+    }
+
+    /// C protocol implementation for (un)registering table installation notifications
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn register_notify_ext(register: efi::Boolean, notify: Option<EfiAcpiNotifyFn>) -> efi::Status {
+        let Some(notify) = notify else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+
+        match provider.register(register.into(), AcpiNotify::Efi(notify)) {
+            Ok(()) => efi::Status::SUCCESS,
+            Err(err) => to_status(err),
+        }
+    }
+
+    /// C protocol implementation for opening the AML object at `buffer`
+    ///
+    /// `buffer` may point at an object within an installed DSDT or SSDT, or at the table itself to open its first
+    /// object.
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn open_ext(buffer: *mut c_void, handle: *mut AcpiHandle) -> efi::Status {
+        if buffer.is_null() || handle.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+
+        let result = {
+            let manager = provider.manager.lock();
+            let Some(installed) = manager.installed_containing(buffer as u64) else {
+                return efi::Status::NOT_FOUND;
+            };
+            if installed.signature != DSDT_SIGNATURE && installed.signature != SSDT_SIGNATURE {
+                return efi::Status::INVALID_PARAMETER;
+            }
+            let offset = (buffer as u64 - installed.address) as usize;
+            manager
+                .with_table(installed.key, |table| {
+                    if offset < size_of::<AcpiTableHeader>() {
+                        AmlHandle::open(table, installed.key)
+                    } else {
+                        AmlHandle::open_at(table, installed.key, offset)
+                    }
+                })
+                .ok_or(AmlError::TableNotFound)
+                .flatten()
+        };
+
+        match result {
+            Ok(aml_handle) => {
+                // SAFETY: handle was checked for null and is guaranteed writable by the caller.
+                unsafe { handle.write_unaligned(SdtHandle::into_raw(aml_handle)) };
+                efi::Status::SUCCESS
+            }
+            Err(err) => aml_status(err),
+        }
+    }
+
+    /// C protocol implementation for opening the first AML object of an installed DSDT or SSDT
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn open_sdt_ext(table_key: usize, handle: *mut AcpiHandle) -> efi::Status {
+        if handle.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+
+        match StandardAmlParser::new(*provider).open_table(table_key) {
+            Ok(aml_handle) => {
+                // SAFETY: handle was checked for null and is guaranteed writable by the caller.
+                unsafe { handle.write_unaligned(SdtHandle::into_raw(aml_handle)) };
+                efi::Status::SUCCESS
+            }
+            Err(err) => aml_status(err),
+        }
+    }
+
+    /// C protocol implementation for closing a handle returned by the other AML functions
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn close_ext(handle: AcpiHandle) -> efi::Status {
+        if SdtHandle::from_raw(handle).is_none() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+
+        // SAFETY: The handle was validated above, so it was allocated by SdtHandle::into_raw() and not closed yet.
+        let mut sdt_handle = unsafe { Box::from_raw(handle as *mut SdtHandle) };
+        sdt_handle.signature = 0;
+        drop(sdt_handle);
+        efi::Status::SUCCESS
+    }
+
+    /// C protocol implementation for iterating the children of an AML object
+    ///
+    /// When `*handle` is NULL it receives the first child of `parent`, otherwise the sibling that follows `*handle`.
+    /// `*handle` is set to NULL once there are no more children. Every returned handle must be closed.
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn get_child_ext(parent: AcpiHandle, handle: *mut AcpiHandle) -> efi::Status {
+        if handle.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(parent) = SdtHandle::from_raw(parent) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+        let parser = StandardAmlParser::new(*provider);
+
+        // SAFETY: handle was checked for null and is guaranteed readable by the caller.
+        let previous = unsafe { handle.read_unaligned() };
+        let result = if previous.is_null() {
+            parser.get_child(&parent.handle)
+        } else {
+            let Some(previous) = SdtHandle::from_raw(previous) else {
+                return efi::Status::INVALID_PARAMETER;
+            };
+            let parent_range = parent.handle.offset()..parent.handle.offset() + parent.handle.size();
+            if previous.handle.table_key() != parent.handle.table_key()
+                || !parent_range.contains(&previous.handle.offset())
+            {
+                return efi::Status::INVALID_PARAMETER;
+            }
+            parser.get_sibling(&previous.handle)
+        };
+
+        match result {
+            Ok(next) => {
+                let next = next.map_or(core::ptr::null_mut(), SdtHandle::into_raw);
+                // SAFETY: handle was checked for null and is guaranteed writable by the caller.
+                unsafe { handle.write_unaligned(next) };
+                efi::Status::SUCCESS
+            }
+            Err(err) => aml_status(err),
+        }
+    }
+
+    /// C protocol implementation for reading an option of an AML object
+    ///
+    /// Option 0 is the opcode, options 1 and up are the operands, followed by the list of child objects for objects
+    /// that have them. The returned data points into the installed table.
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn get_option_ext(
+        handle: AcpiHandle,
+        index: usize,
+        data_type: *mut AcpiDataType,
+        data: *mut *const c_void,
+        data_size: *mut usize,
+    ) -> efi::Status {
+        if data_type.is_null() || data.is_null() || data_size.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(sdt_handle) = SdtHandle::from_raw(handle) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+        let aml_handle = sdt_handle.handle;
+
+        let option = {
+            let manager = provider.manager.lock();
+            let Some(installed) = manager.installed_by_key(aml_handle.table_key()) else {
+                return efi::Status::INVALID_PARAMETER;
+            };
+            let options = manager
+                .with_table(aml_handle.table_key(), |table| aml_handle.layout(table))
+                .ok_or(AmlError::InvalidHandle)
+                .flatten();
+            let options = match options {
+                Ok(layout) => {
+                    let mut options: Vec<(AcpiDataType, usize, usize)> =
+                        vec![(ACPI_DATA_TYPE_OPCODE, aml_handle.offset(), aml_handle.opcode().encoded_len())];
+                    options.extend(layout.slots.iter().map(|slot| (option_type(slot), slot.offset, slot.len)));
+                    options
+                        .extend(layout.children.map(|children| (ACPI_DATA_TYPE_CHILD, children.start, children.len())));
+                    options
+                }
+                Err(err) => return aml_status(err),
+            };
+            options
+                .get(index)
+                .map(|(option_type, offset, len)| (*option_type, installed.address + *offset as u64, *len))
+        };
+
+        let (option_type, address, len) = option.unwrap_or((ACPI_DATA_TYPE_NONE, 0, 0));
+        // SAFETY: The output pointers were checked for null and are guaranteed writable by the caller. The data
+        // pointer refers to the installed table, which stays valid until the table is uninstalled.
+        unsafe {
+            data_type.write_unaligned(option_type);
+            data.write_unaligned(address as *const c_void);
+            data_size.write_unaligned(len);
+        }
+        efi::Status::SUCCESS
+    }
+
+    /// C protocol implementation for overwriting an option of an AML object
+    ///
+    /// Integers may be smaller than the option, and are zero extended. Names, strings, and raw data must match the
+    /// size of the option, as AML is never moved. The opcode and nested objects cannot be changed.
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn set_option_ext(
+        handle: AcpiHandle,
+        index: usize,
+        data: *const c_void,
+        data_size: usize,
+    ) -> efi::Status {
+        if data.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(sdt_handle) = SdtHandle::from_raw(handle) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+        let aml_handle = sdt_handle.handle;
+        // Option 0 is the opcode.
+        let Some(slot_index) = index.checked_sub(1) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        // SAFETY: The caller guarantees that data holds data_size bytes.
+        let value = unsafe { core::slice::from_raw_parts(data as *const u8, data_size) };
+
+        let result = provider
+            .manager
+            .lock()
+            .with_table_mut(aml_handle.table_key(), |table| {
+                let layout = aml_handle.layout(table).map_err(aml_status)?;
+                let slot = layout.slots.get(slot_index).ok_or(efi::Status::INVALID_PARAMETER)?;
+                let field = table.get_mut(slot.offset..slot.offset + slot.len).ok_or(efi::Status::INVALID_PARAMETER)?;
+                match option_type(slot) {
+                    ACPI_DATA_TYPE_UINT if value.len() <= field.len() => {
+                        field.fill(0);
+                        field[..value.len()].copy_from_slice(value);
+                        Ok(())
+                    }
+                    ACPI_DATA_TYPE_OP => Err(efi::Status::INVALID_PARAMETER),
+                    _ if value.len() == field.len() => {
+                        field.copy_from_slice(value);
+                        Ok(())
+                    }
+                    _ => Err(efi::Status::BAD_BUFFER_SIZE),
+                }
+            })
+            .unwrap_or(Err(efi::Status::INVALID_PARAMETER));
+
+        match result {
+            Ok(()) => efi::Status::SUCCESS,
+            Err(status) => status,
+        }
+    }
+
+    /// C protocol implementation for finding an AML object by its ASL path
+    ///
+    /// Absolute paths are searched for in the table `handle_in` belongs to, and relative paths are resolved against
+    /// the scope of `handle_in`.
+    #[coverage(off)] // FFI function - tested via integration tests
+    extern "efiapi" fn find_path_ext(
+        handle_in: AcpiHandle,
+        path: *mut c_void,
+        handle_out: *mut AcpiHandle,
+    ) -> efi::Status {
+        if path.is_null() || handle_out.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        let Some(sdt_handle) = SdtHandle::from_raw(handle_in) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+        let Some(provider) = SDT_PROVIDER.get() else {
+            return efi::Status::NOT_READY;
+        };
+        let aml_handle = sdt_handle.handle;
+
+        // SAFETY: The caller guarantees that path is a NUL terminated ASCII string.
+        let path = unsafe { core::ffi::CStr::from_ptr(path as *const core::ffi::c_char) };
+        let Ok(name) = path.to_str().map_err(|_| AmlError::InvalidPath).and_then(AmlNameString::from_str) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        let result = provider
+            .manager
+            .lock()
+            .with_table(aml_handle.table_key(), |table| {
+                let entries = aml::walk(table, aml_handle.table_key())?;
+                let entry = entries.iter().find(|entry| entry.handle == aml_handle).ok_or(AmlError::InvalidHandle)?;
+                let scope = entry.path.as_ref().unwrap_or(&entry.scope);
+                let target = name.resolve(scope);
+                entries
+                    .iter()
+                    .find(|entry| entry.path.as_ref() == Some(&target))
+                    .map(|entry| entry.handle)
+                    .ok_or(AmlError::PathNotFound)
+            })
+            .ok_or(AmlError::InvalidHandle)
+            .flatten();
+
+        match result {
+            Ok(found) => {
+                // SAFETY: handle_out was checked for null and is guaranteed writable by the caller.
+                unsafe { handle_out.write_unaligned(SdtHandle::into_raw(found)) };
+                efi::Status::SUCCESS
+            }
+            Err(err) => aml_status(err),
+        }
+    }
+}
+
+/// Returns the `EFI_ACPI_DATA_TYPE` an operand is reported as.
+fn option_type(slot: &OperandSlot) -> AcpiDataType {
+    match slot.kind {
+        AmlOperandKind::NameString => ACPI_DATA_TYPE_NAME_STRING,
+        AmlOperandKind::ByteData | AmlOperandKind::WordData | AmlOperandKind::DWordData | AmlOperandKind::QWordData => {
+            ACPI_DATA_TYPE_UINT
+        }
+        AmlOperandKind::AsciiString => ACPI_DATA_TYPE_STRING,
+        AmlOperandKind::TermArg => ACPI_DATA_TYPE_OP,
+        AmlOperandKind::ByteList => ACPI_DATA_TYPE_CHILD,
+    }
+}
@@ -0,0 +1,443 @@
+//! ACPI service interfaces
+//!
+//! This module defines the public service types for ACPI table operations.
+//! Platform components install and query ACPI tables through [`AcpiProvider`] instead of touching raw table memory,
+//! and walk or patch the AML of installed DSDTs and SSDTs through [`AmlParser`].
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+extern crate alloc;
+use alloc::vec::{IntoIter, Vec};
+use patina::{
+    boot_services::{BootServices, StandardBootServices},
+    tpl_mutex::TplMutex,
+};
+use r_efi::efi;
+
+use crate::{
+    acpi_table::{
+        ACPI_EXPOSED_VERSIONS, AcpiTable, AcpiTableHeader, AcpiVersion, DSDT_SIGNATURE, SSDT_SIGNATURE, TableKey,
+    },
+    aml::{self, AmlHandle, AmlOperand, AmlPath},
+    error::{AcpiError, AmlError},
+    manager::{AcpiManager, AcpiNotify},
+};
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// Function called after a table is installed, with the installed table, the versions it is exposed for, and its key.
+pub type AcpiNotifyFn = fn(&AcpiTable, AcpiVersion, TableKey) -> Result<(), AcpiError>;
+
+/// Object-safe trait for ACPI table operations
+///
+/// This trait defines the operations available through `Service<dyn AcpiProvider>`. Installed tables are copied into
+/// ACPI memory, so callers keep ownership of the tables they pass in and receive copies back.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait AcpiProvider {
+    /// Installs an ACPI table into the XSDT.
+    ///
+    /// The table checksum is recalculated. The FADT, FACS, and DSDT are linked together rather than added to the
+    /// XSDT directly, and may only be installed once.
+    ///
+    /// # Returns
+    ///
+    /// The key to pass to [`uninstall_acpi_table`](AcpiProvider::uninstall_acpi_table).
+    fn install_acpi_table(&self, acpi_table: &AcpiTable) -> Result<TableKey, AcpiError>;
+
+    /// Uninstalls an ACPI table from the XSDT.
+    fn uninstall_acpi_table(&self, table_key: TableKey) -> Result<(), AcpiError>;
+
+    /// Returns a copy of the installed table at `index`, in installation order.
+    ///
+    /// Use [`iter`](AcpiProvider::iter) to search for a table; this is meant for random access to a known index.
+    fn get_acpi_table(&self, index: usize) -> Result<AcpiTable, AcpiError>;
+
+    /// Registers a function to call after each table installation.
+    ///
+    /// # Arguments
+    ///
+    /// * `should_register` - If true, registers the function. If false, unregisters the function.
+    /// * `notify_fn` - The function to (un)register
+    fn register_notify(&self, should_register: bool, notify_fn: AcpiNotifyFn) -> Result<(), AcpiError>;
+
+    /// Returns an iterator over copies of the installed tables, as installed at the time of the call.
+    fn iter(&self) -> IntoIter<AcpiTable>;
+}
+
+/// ACPI service implementation
+///
+/// This struct implements `AcpiProvider` and is registered as `Service<dyn AcpiProvider>`. The C protocols share the
+/// same instance, so tables installed by C drivers and Rust components land in the same XSDT.
+///
+/// # Example
+///
+/// ```ignore
+/// fn entry_point(acpi_provider: Service<dyn AcpiProvider>) -> Result<()> {
+///     let key = acpi_provider.install_acpi_table(&ssdt)?;
+///     for table in acpi_provider.iter().filter(|table| table.signature() == *b"SSDT") {
+///         // ...
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(patina::component::service::IntoService)]
+#[service(dyn AcpiProvider)]
+pub struct AcpiProviderImpl<B: BootServices + 'static = StandardBootServices> {
+    pub(crate) manager: TplMutex<AcpiManager, B>,
+    pub(crate) boot_services: B,
+}
+
+impl<B: BootServices> AcpiProviderImpl<B> {
+    /// Installs the RSDP into the UEFI Configuration Table.
+    ///
+    /// Reinstalling the same RSDP after every change signals the ACPI table event group, which is how C drivers
+    /// learn that the tables changed.
+    pub(crate) fn publish_rsdp(&self) -> Result<(), AcpiError> {
+        let rsdp = self.manager.lock().rsdp_address();
+
+        // Lock is not held during install_configuration_table, as event handlers signaled by it may use the service.
+        //
+        // SAFETY: The RSDP is in ACPI reclaim memory that the manager never frees.
+        unsafe {
+            self.boot_services
+                .install_configuration_table(&efi::ACPI_20_TABLE_GUID, rsdp as *mut core::ffi::c_void)
+                .map_err(|_| AcpiError::PublishFailed)
+        }
+    }
+
+    /// Calls every registered notify function for a newly installed table.
+    fn notify(&self, table_key: TableKey) {
+        let (installed, table, notify_fns) = {
+            let manager = self.manager.lock();
+            (manager.installed_by_key(table_key), manager.get_by_key(table_key), manager.notify_fns())
+        };
+        let (Some(installed), Some(table)) = (installed, table) else {
+            return;
+        };
+
+        // Notify functions run without the lock so they can install or uninstall tables themselves.
+        for notify in notify_fns {
+            match notify {
+                AcpiNotify::Rust(notify_fn) => {
+                    if let Err(err) = notify_fn(&table, ACPI_EXPOSED_VERSIONS, table_key) {
+                        log::warn!("ACPI notify function failed for table {table_key}: {err:?}");
+                    }
+                }
+                AcpiNotify::Efi(notify_fn) => {
+                    let status = notify_fn(installed.address as *mut AcpiTableHeader, ACPI_EXPOSED_VERSIONS, table_key);
+                    if status.is_error() {
+                        log::warn!("ACPI notify function failed for table {table_key}: {status:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn register(&self, should_register: bool, notify: AcpiNotify) -> Result<(), AcpiError> {
+        self.manager.lock().register_notify(should_register, notify)
+    }
+}
+
+impl<B: BootServices> AcpiProvider for AcpiProviderImpl<B> {
+    fn install_acpi_table(&self, acpi_table: &AcpiTable) -> Result<TableKey, AcpiError> {
+        let table_key = self.manager.lock().install(acpi_table)?;
+        self.publish_rsdp()?;
+        self.notify(table_key);
+        Ok(table_key)
+    }
+
+    fn uninstall_acpi_table(&self, table_key: TableKey) -> Result<(), AcpiError> {
+        self.manager.lock().uninstall(table_key)?;
+        self.publish_rsdp()
+    }
+
+    fn get_acpi_table(&self, index: usize) -> Result<AcpiTable, AcpiError> {
+        self.manager.lock().get(index)
+    }
+
+    fn register_notify(&self, should_register: bool, notify_fn: AcpiNotifyFn) -> Result<(), AcpiError> {
+        self.register(should_register, AcpiNotify::Rust(notify_fn))
+    }
+
+    fn iter(&self) -> IntoIter<AcpiTable> {
+        self.manager.lock().tables().into_iter()
+    }
+}
+
+/// Object-safe trait for AML operations
+///
+/// This trait defines the operations available through `Service<dyn AmlParser>`. It walks and patches the AML of
+/// installed DSDTs and SSDTs in place, so patches are visible to the OS without reinstalling the table. Handles refer
+/// to the table they were opened on and stop working once it is uninstalled.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait AmlParser {
+    /// Opens the AML stream of an installed DSDT or SSDT.
+    ///
+    /// # Returns
+    ///
+    /// A handle to the first object in the table. The remaining top level objects are its siblings.
+    fn open_table(&self, table_key: TableKey) -> Result<AmlHandle, AmlError>;
+
+    /// Returns the first child of an object, or `None` if it has no children.
+    fn get_child(&self, handle: &AmlHandle) -> Result<Option<AmlHandle>, AmlError>;
+
+    /// Returns the next object within the same parent, or `None` if `handle` is the last one.
+    fn get_sibling(&self, handle: &AmlHandle) -> Result<Option<AmlHandle>, AmlError>;
+
+    /// Decodes the operands of an object.
+    fn iter_operands(&self, handle: &AmlHandle) -> Result<Vec<AmlOperand>, AmlError>;
+
+    /// Overwrites the operand at `index` and recalculates the table checksum.
+    ///
+    /// The new value must encode to the same size as the existing operand. See [`AmlHandle::set_operand`].
+    fn set_operand(&self, handle: &AmlHandle, index: usize, operand: AmlOperand) -> Result<(), AmlError>;
+
+    /// Finds the object declaring the absolute `path`, searching the DSDT and then each SSDT in installation order.
+    fn find_path(&self, path: &AmlPath) -> Result<AmlHandle, AmlError>;
+
+    /// Returns every object in the table in depth-first preorder.
+    fn iter(&self, table_key: TableKey) -> Result<IntoIter<AmlHandle>, AmlError>;
+}
+
+/// AML parser service implementation
+///
+/// This struct implements `AmlParser` on the tables owned by an [`AcpiProviderImpl`], and is registered as
+/// `Service<dyn AmlParser>` alongside it.
+///
+/// # Example
+///
+/// ```ignore
+/// fn entry_point(aml_parser: Service<dyn AmlParser>) -> Result<()> {
+///     let val0 = aml_parser.find_path(&"\\_SB.DEV0.VAL0".parse()?)?;
+///     aml_parser.set_operand(&val0, 1, AmlOperand::Integer(0x99))?;
+///     Ok(())
+/// }
+/// ```
+#[derive(patina::component::service::IntoService)]
+#[service(dyn AmlParser)]
+pub struct StandardAmlParser<B: BootServices + 'static = StandardBootServices> {
+    provider: &'static AcpiProviderImpl<B>,
+}
+
+impl<B: BootServices> StandardAmlParser<B> {
+    /// Creates a parser over the tables installed through `provider`.
+    pub fn new(provider: &'static AcpiProviderImpl<B>) -> Self {
+        Self { provider }
+    }
+
+    /// Calls `f` with the bytes of the table a handle was opened on.
+    fn with_aml<R>(&self, table_key: TableKey, f: impl FnOnce(&[u8]) -> Result<R, AmlError>) -> Result<R, AmlError> {
+        self.provider.manager.lock().with_table(table_key, f).ok_or(AmlError::InvalidHandle)?
+    }
+
+    /// Returns the keys of the installed DSDT and SSDTs, DSDT first.
+    fn aml_tables(&self) -> Vec<TableKey> {
+        let manager = self.provider.manager.lock();
+        let installed: Vec<_> = (0..).map_while(|index| manager.installed(index)).collect();
+        let dsdt = installed.iter().filter(|table| table.signature == DSDT_SIGNATURE);
+        let ssdts = installed.iter().filter(|table| table.signature == SSDT_SIGNATURE);
+        dsdt.chain(ssdts).map(|table| table.key).collect()
+    }
+}
+
+impl<B: BootServices> AmlParser for StandardAmlParser<B> {
+    fn open_table(&self, table_key: TableKey) -> Result<AmlHandle, AmlError> {
+        let manager = self.provider.manager.lock();
+        let installed = manager.installed_by_key(table_key).ok_or(AmlError::TableNotFound)?;
+        if installed.signature != DSDT_SIGNATURE && installed.signature != SSDT_SIGNATURE {
+            return Err(AmlError::NotAmlTable);
+        }
+        manager.with_table(table_key, |table| AmlHandle::open(table, table_key)).ok_or(AmlError::TableNotFound)?
+    }
+
+    fn get_child(&self, handle: &AmlHandle) -> Result<Option<AmlHandle>, AmlError> {
+        self.with_aml(handle.table_key(), |table| handle.child(table))
+    }
+
+    fn get_sibling(&self, handle: &AmlHandle) -> Result<Option<AmlHandle>, AmlError> {
+        self.with_aml(handle.table_key(), |table| handle.sibling(table))
+    }
+
+    fn iter_operands(&self, handle: &AmlHandle) -> Result<Vec<AmlOperand>, AmlError> {
+        self.with_aml(handle.table_key(), |table| handle.operands(table))
+    }
+
+    fn set_operand(&self, handle: &AmlHandle, index: usize, operand: AmlOperand) -> Result<(), AmlError> {
+        self.provider
+            .manager
+            .lock()
+            .with_table_mut(handle.table_key(), |table| handle.set_operand(table, index, &operand))
+            .ok_or(AmlError::InvalidHandle)?
+    }
+
+    fn find_path(&self, path: &AmlPath) -> Result<AmlHandle, AmlError> {
+        for table_key in self.aml_tables() {
+            if let Some(handle) = self.with_aml(table_key, |table| aml::find_path(table, table_key, path))? {
+                return Ok(handle);
+            }
+        }
+        Err(AmlError::PathNotFound)
+    }
+
+    fn iter(&self, table_key: TableKey) -> Result<IntoIter<AmlHandle>, AmlError> {
+        let root = self.open_table(table_key)?;
+        self.with_aml(root.table_key(), |table| aml::iter_table(table, table_key)).map(Vec::into_iter)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::manager::OemInfo;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use mockall::predicate::eq;
+    use patina::{
+        boot_services::{MockBootServices, tpl::Tpl},
+        component::service::{Service, memory::StdMemoryManager},
+    };
+    extern crate std;
+    use std::{boxed::Box, vec::Vec};
+
+    fn mock_boot_services() -> MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_raise_tpl().with(eq(Tpl::NOTIFY)).return_const(Tpl::APPLICATION);
+        boot_services.expect_restore_tpl().with(eq(Tpl::APPLICATION)).return_const(());
+        boot_services
+            .expect_install_configuration_table::<*mut core::ffi::c_void>()
+            .withf(|guid, _| *guid == efi::ACPI_20_TABLE_GUID)
+            .returning(|_, _| Ok(()));
+        boot_services
+    }
+
+    fn create_test_acpi_provider(boot_services: MockBootServices) -> AcpiProviderImpl<MockBootServices> {
+        let oem = OemInfo {
+            oem_id: *b"PATINA",
+            oem_table_id: *b"PATINAXS",
+            oem_revision: 1,
+            creator_id: 0,
+            creator_revision: 0,
+        };
+        let manager = AcpiManager::new(Service::mock(Box::new(StdMemoryManager::new())), oem).unwrap();
+        AcpiProviderImpl { manager: TplMutex::new(boot_services.clone(), Tpl::NOTIFY, manager), boot_services }
+    }
+
+    fn ssdt() -> AcpiTable {
+        AcpiTable::new(AcpiTableHeader { signature: *b"SSDT", ..Default::default() }, &[0x10, 0x20])
+    }
+
+    #[test]
+    fn test_install_get_iter_uninstall() {
+        let provider = create_test_acpi_provider(mock_boot_services());
+
+        let first = provider.install_acpi_table(&ssdt()).unwrap();
+        let second = provider.install_acpi_table(&ssdt()).unwrap();
+        assert_ne!(first, second);
+
+        assert_eq!(provider.get_acpi_table(1).unwrap().table_key(), second);
+        assert_eq!(provider.iter().map(|table| table.table_key()).collect::<Vec<_>>(), [first, second]);
+
+        provider.uninstall_acpi_table(first).unwrap();
+        assert_eq!(provider.iter().map(|table| table.table_key()).collect::<Vec<_>>(), [second]);
+        assert_eq!(provider.get_acpi_table(1), Err(AcpiError::InvalidIndex));
+        assert_eq!(provider.uninstall_acpi_table(first), Err(AcpiError::InvalidTableKey));
+    }
+
+    #[test]
+    fn test_notify_called_on_install() {
+        static NOTIFIED: AtomicUsize = AtomicUsize::new(0);
+        fn notify(table: &AcpiTable, version: AcpiVersion, key: TableKey) -> Result<(), AcpiError> {
+            assert_eq!(table.signature(), *b"SSDT");
+            assert_eq!(table.table_key(), key);
+            assert_eq!(version, ACPI_EXPOSED_VERSIONS);
+            NOTIFIED.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        let provider = create_test_acpi_provider(mock_boot_services());
+        provider.register_notify(true, notify).unwrap();
+        provider.install_acpi_table(&ssdt()).unwrap();
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+
+        provider.register_notify(false, notify).unwrap();
+        provider.install_acpi_table(&ssdt()).unwrap();
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_publish_failure_is_reported() {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_raise_tpl().return_const(Tpl::APPLICATION);
+        boot_services.expect_restore_tpl().return_const(());
+        boot_services
+            .expect_install_configuration_table::<*mut core::ffi::c_void>()
+            .returning(|_, _| Err(efi::Status::OUT_OF_RESOURCES));
+        let provider = create_test_acpi_provider(boot_services);
+
+        assert_eq!(provider.install_acpi_table(&ssdt()), Err(AcpiError::PublishFailed));
+    }
+
+    fn create_test_aml_parser() -> (&'static AcpiProviderImpl<MockBootServices>, StandardAmlParser<MockBootServices>) {
+        let provider: &'static _ = Box::leak(Box::new(create_test_acpi_provider(mock_boot_services())));
+        (provider, StandardAmlParser::new(provider))
+    }
+
+    const RFC0020_EXAMPLE: &[u8] = include_bytes!("../resources/test/aml/rfc0020_example.aml");
+    const PLATFORM_SSDT: &[u8] = include_bytes!("../resources/test/aml/platform_ssdt.aml");
+
+    #[test]
+    fn test_aml_parser_patches_installed_table() {
+        let (provider, parser) = create_test_aml_parser();
+        let dsdt = provider.install_acpi_table(&AcpiTable::from_bytes(RFC0020_EXAMPLE).unwrap()).unwrap();
+        let ssdt = provider.install_acpi_table(&AcpiTable::from_bytes(PLATFORM_SSDT).unwrap()).unwrap();
+
+        let val0 = parser.find_path(&"\\_SB.DEV0.VAL0".parse().unwrap()).unwrap();
+        assert_eq!(val0.table_key(), dsdt);
+        parser.set_operand(&val0, 1, AmlOperand::Integer(0x99)).unwrap();
+        assert_eq!(parser.iter_operands(&val0).unwrap()[1], AmlOperand::Integer(0x99));
+
+        let table = provider.iter().find(|table| table.table_key() == dsdt).unwrap();
+        assert_eq!(crate::acpi_table::checksum(table.as_bytes()), 0);
+        assert_ne!(table.as_bytes(), RFC0020_EXAMPLE);
+
+        // Objects that only the SSDT declares are found there.
+        assert_eq!(parser.find_path(&"\\_SB.UAR0._CRS".parse().unwrap()).unwrap().table_key(), ssdt);
+        assert_eq!(parser.find_path(&"\\_SB.NONE".parse().unwrap()), Err(AmlError::PathNotFound));
+    }
+
+    #[test]
+    fn test_aml_parser_traversal() {
+        let (provider, parser) = create_test_aml_parser();
+        let dsdt = provider.install_acpi_table(&AcpiTable::from_bytes(RFC0020_EXAMPLE).unwrap()).unwrap();
+
+        let scope = parser.open_table(dsdt).unwrap();
+        assert_eq!(parser.get_sibling(&scope).unwrap(), None);
+        let dev0 = parser.get_child(&scope).unwrap().unwrap();
+        let chld = parser.get_sibling(&parser.get_child(&dev0).unwrap().unwrap()).unwrap().unwrap();
+        assert_eq!(parser.iter(dsdt).unwrap().collect::<Vec<_>>().len(), 5);
+        assert_eq!(parser.iter(dsdt).unwrap().nth(3), Some(chld));
+    }
+
+    #[test]
+    fn test_aml_parser_rejects_stale_and_non_aml_tables() {
+        let (provider, parser) = create_test_aml_parser();
+        assert_eq!(parser.open_table(1), Err(AmlError::TableNotFound));
+
+        let ssdt = provider.install_acpi_table(&AcpiTable::from_bytes(PLATFORM_SSDT).unwrap()).unwrap();
+        let apic = provider
+            .install_acpi_table(&AcpiTable::new(AcpiTableHeader { signature: *b"APIC", ..Default::default() }, &[0; 8]))
+            .unwrap();
+        assert_eq!(parser.open_table(apic), Err(AmlError::NotAmlTable));
+
+        let root = parser.open_table(ssdt).unwrap();
+        provider.uninstall_acpi_table(ssdt).unwrap();
+        assert_eq!(parser.get_child(&root), Err(AmlError::InvalidHandle));
+        assert_eq!(parser.set_operand(&root, 0, AmlOperand::Integer(0)), Err(AmlError::InvalidHandle));
+    }
+}
@@ -0,0 +1,611 @@
+//! Boot Manager
+//!
+//! Implements the boot manager of the UEFI specification on top of the boot and runtime services: signals EndOfDxe,
+//! loads the `Driver####` options, connects the controllers, and tries the `Boot####` options, the default boot
+//! options of the platform and the removable media boot file until one of them takes over.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+use core::{
+    ffi::c_void,
+    iter,
+    ptr::{self, NonNull},
+};
+
+use patina::{
+    boot_services::{
+        BootServices, StandardBootServices, event::EventType, protocol_handler::HandleSearchType, tpl::Tpl,
+    },
+    guids::EVENT_GROUP_END_OF_DXE,
+    runtime_services::{RuntimeServices, StandardRuntimeServices, authenticated_variables::GLOBAL_VARIABLE_GUID},
+    uefi_protocol::device_path::{DevicePath, DevicePathBuf, nodes::DevicePathType},
+};
+use r_efi::efi;
+
+use crate::{
+    load_option::{LOAD_OPTION_ACTIVE, LOAD_OPTION_FORCE_RECONNECT, LoadOption, LoadOptionType, parse_order},
+    service::BootPolicy,
+    short_form::{self, ShortForm},
+};
+
+/// The watchdog timeout armed before starting a boot option, as required by the UEFI specification.
+const BOOT_WATCHDOG_SECONDS: usize = 5 * 60;
+
+/// The delay between two rounds of boot attempts, in microseconds.
+const BOOT_RETRY_DELAY_US: usize = 5_000_000;
+
+/// A `Boot####` option to try.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BootCandidate {
+    /// The number of the option.
+    pub(crate) number: u16,
+    /// Whether the option was selected for this boot, through a hot key or `BootNext`, rather than being next in
+    /// `BootOrder`. Selected options are booted even if inactive or in the application category.
+    pub(crate) selected: bool,
+}
+
+/// Returns the `Boot####` options to try, in order: the one selected with a hot key, `BootNext`, then `BootOrder`.
+///
+/// Each option is tried at most once.
+pub(crate) fn boot_candidates(hotkey: Option<u16>, boot_next: Option<u16>, boot_order: &[u16]) -> Vec<BootCandidate> {
+    let selected = hotkey.into_iter().chain(boot_next).map(|number| BootCandidate { number, selected: true });
+    let ordered = boot_order.iter().map(|&number| BootCandidate { number, selected: false });
+
+    let mut candidates: Vec<BootCandidate> = Vec::new();
+    for candidate in selected.chain(ordered) {
+        if !candidates.iter().any(|tried| tried.number == candidate.number) {
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+/// Returns the null-terminated name of a variable of the global variable namespace.
+fn variable_name(name: &str) -> Vec<u16> {
+    name.encode_utf16().chain(iter::once(0)).collect()
+}
+
+/// Returns `device_path` as a pointer to the `EFI_DEVICE_PATH_PROTOCOL` it starts with.
+fn device_path_protocol(device_path: &DevicePath) -> *mut efi::protocols::device_path::Protocol {
+    device_path.as_bytes().as_ptr() as *mut efi::protocols::device_path::Protocol
+}
+
+/// Returns the handle of the image this code is part of, which owns the images loaded by the boot manager.
+///
+/// Components are linked into the DXE core, so this is the image handle of the core.
+pub(crate) fn own_image_handle<B: BootServices>(boot_services: &B) -> Option<efi::Handle> {
+    let address = own_image_handle::<B> as *const () as usize;
+    let handles = boot_services
+        .locate_handle_buffer(HandleSearchType::ByProtocol(&efi::protocols::loaded_image::PROTOCOL_GUID))
+        .ok()?;
+    handles.iter().copied().find(|&handle| {
+        // SAFETY: The loaded image protocol is only read, and handle was returned for that protocol.
+        match unsafe { boot_services.handle_protocol::<efi::protocols::loaded_image::Protocol>(handle) } {
+            Ok(loaded_image) => {
+                let base = loaded_image.image_base as usize;
+                (base..base + loaded_image.image_size as usize).contains(&address)
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+/// The boot manager, run from the BDS architectural protocol entry.
+pub(crate) struct BootManager<
+    B: BootServices + 'static = StandardBootServices,
+    R: RuntimeServices + 'static = StandardRuntimeServices,
+> {
+    pub(crate) boot_services: B,
+    pub(crate) runtime_services: R,
+    pub(crate) policy: &'static dyn BootPolicy,
+}
+
+impl<B: BootServices, R: RuntimeServices> BootManager<B, R> {
+    /// Starts the driver options, then tries to boot until an option takes over. Never returns.
+    #[coverage(off)] // Never returns.
+    pub(crate) fn run(&self) -> ! {
+        let image_handle = own_image_handle(&self.boot_services);
+        if image_handle.is_none() {
+            log::error!(target: "bds", "The loaded image of the boot manager was not found, nothing can be loaded.");
+        }
+
+        self.start_driver_options(image_handle);
+
+        loop {
+            if self.policy.connect_all() {
+                self.connect_all();
+            }
+            if let Some(image_handle) = image_handle {
+                self.boot(image_handle);
+            }
+
+            log::error!(target: "bds", "No boot option could be booted.");
+            self.policy.boot_failed();
+            let _ = self.boot_services.stall(BOOT_RETRY_DELAY_US);
+        }
+    }
+
+    /// Tries every boot option once, and returns if none of them took over.
+    fn boot(&self, image_handle: efi::Handle) {
+        let hotkey = self.policy.hotkey_boot_option();
+        let boot_next = self.take_boot_next();
+        let boot_order = self.load_option_order(LoadOptionType::Boot);
+
+        for candidate in boot_candidates(hotkey, boot_next, &boot_order) {
+            let Some(option) = self.read_load_option(LoadOptionType::Boot, candidate.number) else {
+                continue;
+            };
+            if candidate.selected || option.is_auto_boot() {
+                self.boot_load_option(image_handle, Some(candidate.number), &option);
+            }
+        }
+
+        for option in self.policy.default_boot_options() {
+            self.boot_load_option(image_handle, None, &option);
+        }
+
+        if let Some(file) = self.policy.removable_media_boot_file() {
+            let option = LoadOption::new(LOAD_OPTION_ACTIVE, "Removable media", short_form::file_path(file));
+            self.boot_load_option(image_handle, None, &option);
+        }
+    }
+
+    /// Boots `option`, and returns if it could not be loaded or the image it started returned.
+    fn boot_load_option(&self, image_handle: efi::Handle, number: Option<u16>, option: &LoadOption) {
+        log::info!(target: "bds", "Booting \"{}\" from {}", option.description, option.file_path);
+        if !self.policy.connect_all() && ShortForm::of(&option.file_path) == ShortForm::Full {
+            self.connect_device_path(&option.file_path);
+        }
+
+        if let Some(number) = number {
+            let attributes = efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+            if let Err(status) = self.runtime_services.set_variable(
+                &variable_name("BootCurrent"),
+                &GLOBAL_VARIABLE_GUID,
+                attributes,
+                &number.to_le_bytes(),
+            ) {
+                log::warn!(target: "bds", "Failed to set BootCurrent: {status:#x?}");
+            }
+        }
+        self.signal_event_group(&efi::EVENT_GROUP_READY_TO_BOOT);
+        self.signal_event_group(&efi::EVENT_GROUP_AFTER_READY_TO_BOOT);
+
+        let Some(handle) = self.load(image_handle, &option.file_path, &option.optional_data) else {
+            return;
+        };
+
+        let _ = self.boot_services.set_watchdog_timer(BOOT_WATCHDOG_SECONDS);
+        let result = self.boot_services.start_image(handle).map_err(|(status, _)| status);
+        let _ = self.boot_services.set_watchdog_timer(0);
+        match result {
+            Ok(()) => log::info!(target: "bds", "\"{}\" returned.", option.description),
+            Err(status) => log::warn!(target: "bds", "\"{}\" failed: {status:#x?}", option.description),
+        }
+    }
+
+    /// Signals EndOfDxe, then loads and starts the driver options, and reconnects the controllers if one of them asked
+    /// for it.
+    ///
+    /// `Driver####` and `Boot####` options are not part of the platform firmware, so EndOfDxe is always signalled
+    /// first for the platform to lock itself down before any of them runs. The platform must not signal it itself.
+    fn start_driver_options(&self, image_handle: Option<efi::Handle>) {
+        self.signal_event_group(&EVENT_GROUP_END_OF_DXE);
+
+        if let Some(image_handle) = image_handle
+            && self.load_driver_options(image_handle)
+        {
+            self.disconnect_all();
+            self.connect_all();
+        }
+    }
+
+    /// Loads and starts the active `Driver####` options of `DriverOrder`, and returns whether one of those that
+    /// started asked for the controllers to be reconnected.
+    fn load_driver_options(&self, image_handle: efi::Handle) -> bool {
+        let mut reconnect = false;
+        for number in self.load_option_order(LoadOptionType::Driver) {
+            let Some(option) = self.read_load_option(LoadOptionType::Driver, number) else {
+                continue;
+            };
+            if !option.is_active() {
+                continue;
+            }
+
+            log::info!(target: "bds", "Loading driver \"{}\" from {}", option.description, option.file_path);
+            let Some(handle) = self.load(image_handle, &option.file_path, &option.optional_data) else {
+                continue;
+            };
+            match self.boot_services.start_image(handle) {
+                Ok(()) => reconnect |= option.attributes & LOAD_OPTION_FORCE_RECONNECT != 0,
+                Err((status, _)) => log::warn!(target: "bds", "\"{}\" failed: {status:#x?}", option.description),
+            }
+        }
+        reconnect
+    }
+
+    /// Loads the image at the first expansion of `device_path` that can be loaded, and hands it `optional_data` as
+    /// its load options.
+    fn load(&self, image_handle: efi::Handle, device_path: &DevicePath, optional_data: &[u8]) -> Option<efi::Handle> {
+        let device_paths = self.expand(device_path);
+        if device_paths.is_empty() {
+            log::warn!(target: "bds", "No device matches {device_path}.");
+        }
+
+        for device_path in &device_paths {
+            let file_path = NonNull::new(device_path_protocol(device_path))?;
+            let handle = match self.boot_services.load_image_from_file(image_handle, file_path) {
+                Ok(handle) => handle,
+                Err(status) => {
+                    log::warn!(target: "bds", "Failed to load {device_path}: {status:#x?}");
+                    continue;
+                }
+            };
+
+            if !optional_data.is_empty() {
+                // SAFETY: handle is the image that was just loaded, and optional_data outlives its execution.
+                let loaded_image =
+                    unsafe { self.boot_services.handle_protocol::<efi::protocols::loaded_image::Protocol>(handle) };
+                if let Ok(loaded_image) = loaded_image {
+                    loaded_image.load_options = optional_data.as_ptr() as *mut c_void;
+                    loaded_image.load_options_size = optional_data.len() as u32;
+                }
+            }
+            return Some(handle);
+        }
+        None
+    }
+
+    /// Returns the full device paths that `device_path` designates among the devices present.
+    fn expand(&self, device_path: &DevicePath) -> Vec<DevicePathBuf> {
+        match ShortForm::of(device_path) {
+            ShortForm::Full => vec![DevicePathBuf::from(device_path)],
+            ShortForm::HardDrive => {
+                short_form::expand_hard_drive(device_path, &self.device_paths(&efi::protocols::block_io::PROTOCOL_GUID))
+            }
+            ShortForm::FilePath => short_form::expand_file_path(
+                device_path,
+                &self.device_paths(&efi::protocols::simple_file_system::PROTOCOL_GUID),
+            ),
+            ShortForm::Unsupported => {
+                log::warn!(target: "bds", "Unsupported short-form device path {device_path}.");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the device paths of the handles that carry `protocol`.
+    fn device_paths(&self, protocol: &'static efi::Guid) -> Vec<DevicePathBuf> {
+        let Ok(handles) = self.boot_services.locate_handle_buffer(HandleSearchType::ByProtocol(protocol)) else {
+            return Vec::new();
+        };
+        handles
+            .iter()
+            .filter_map(|&handle| {
+                // SAFETY: The device path protocol is only read.
+                let device_path =
+                    unsafe { self.boot_services.handle_protocol::<efi::protocols::device_path::Protocol>(handle) }
+                        .ok()?;
+                // SAFETY: A device path protocol interface starts a device path that ends with an end node.
+                let device_path = unsafe { DevicePath::try_from_ptr(device_path as *const _ as *const u8) }.ok()?;
+                Some(DevicePathBuf::from(device_path))
+            })
+            .collect()
+    }
+
+    /// Connects every driver to every controller, recursively.
+    fn connect_all(&self) {
+        let Ok(handles) = self.boot_services.locate_handle_buffer(HandleSearchType::AllHandle) else {
+            return;
+        };
+        for &handle in handles.iter() {
+            // SAFETY: No driver image handles or remaining device path are passed.
+            let _ = unsafe { self.boot_services.connect_controller(handle, Vec::new(), ptr::null_mut(), true) };
+        }
+    }
+
+    /// Disconnects every driver from every controller.
+    fn disconnect_all(&self) {
+        let Ok(handles) = self.boot_services.locate_handle_buffer(HandleSearchType::AllHandle) else {
+            return;
+        };
+        for &handle in handles.iter() {
+            let _ = self.boot_services.disconnect_controller(handle, None, None);
+        }
+    }
+
+    /// Connects the controllers along `device_path`, from the closest existing device to the end of the path.
+    fn connect_device_path(&self, device_path: &DevicePath) {
+        let mut previous = None;
+        loop {
+            let mut remaining = device_path_protocol(device_path);
+            // SAFETY: remaining points to device_path, which ends with an end node and is only read.
+            let Ok(handle) = (unsafe {
+                self.boot_services.locate_device_path(&efi::protocols::device_path::PROTOCOL_GUID, &mut remaining)
+            }) else {
+                return;
+            };
+            if previous == Some(handle) {
+                return;
+            }
+            previous = Some(handle);
+
+            // SAFETY: remaining points to the nodes of device_path that the handle does not cover.
+            if unsafe { self.boot_services.connect_controller(handle, Vec::new(), remaining, false) }.is_err() {
+                return;
+            }
+            // SAFETY: As above, remaining points to a node of device_path.
+            if unsafe { (*remaining).r#type } == DevicePathType::End as u8 {
+                return;
+            }
+        }
+    }
+
+    /// Signals the event group `group`.
+    fn signal_event_group(&self, group: &'static efi::Guid) {
+        extern "efiapi" fn noop(_event: efi::Event, _context: *mut c_void) {}
+
+        if let Ok(event) = self.boot_services.create_event_ex(
+            EventType::NOTIFY_SIGNAL,
+            Tpl::CALLBACK,
+            Some(noop),
+            ptr::null_mut(),
+            group,
+        ) {
+            let _ = self.boot_services.signal_event(event);
+            let _ = self.boot_services.close_event(event);
+        }
+    }
+
+    /// Returns the option number in `BootNext`, and deletes it so that it is only booted once.
+    fn take_boot_next(&self) -> Option<u16> {
+        let name = variable_name("BootNext");
+        let (data, _) = self.runtime_services.get_variable::<Vec<u8>>(&name, &GLOBAL_VARIABLE_GUID, None).ok()?;
+        if let Err(status) = self.runtime_services.set_variable(&name, &GLOBAL_VARIABLE_GUID, 0, &Vec::<u8>::new()) {
+            log::warn!(target: "bds", "Failed to delete BootNext: {status:#x?}");
+        }
+        parse_order(&data).first().copied()
+    }
+
+    /// Returns the option numbers of `BootOrder` or `DriverOrder`, or those of every option of `kind` if the order
+    /// variable does not exist.
+    fn load_option_order(&self, kind: LoadOptionType) -> Vec<u16> {
+        match self.runtime_services.get_variable::<Vec<u8>>(&kind.order_variable_name(), &GLOBAL_VARIABLE_GUID, None) {
+            Ok((data, _)) => parse_order(&data),
+            Err(efi::Status::NOT_FOUND) => self.enumerate_load_options(kind),
+            Err(status) => {
+                log::warn!(target: "bds", "Failed to read the {kind:?} order: {status:#x?}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Returns the numbers of the `Boot####` or `Driver####` variables, in increasing order.
+    fn enumerate_load_options(&self, kind: LoadOptionType) -> Vec<u16> {
+        let mut numbers = Vec::new();
+        let (mut name, mut namespace) = (vec![0u16], GLOBAL_VARIABLE_GUID);
+        while let Ok((next_name, next_namespace)) = self.runtime_services.get_next_variable_name(&name, &namespace) {
+            if next_namespace == GLOBAL_VARIABLE_GUID
+                && let Some(number) = kind.parse_variable_name(&next_name)
+            {
+                numbers.push(number);
+            }
+            (name, namespace) = (next_name, next_namespace);
+        }
+        numbers.sort_unstable();
+        numbers
+    }
+
+    /// Reads and parses the `Boot####` or `Driver####` variable of option `number`.
+    fn read_load_option(&self, kind: LoadOptionType, number: u16) -> Option<LoadOption> {
+        let name = kind.variable_name(number);
+        let (data, _) = match self.runtime_services.get_variable::<Vec<u8>>(&name, &GLOBAL_VARIABLE_GUID, None) {
+            Ok(variable) => variable,
+            Err(status) => {
+                log::warn!(target: "bds", "Failed to read {kind:?}{number:04X}: {status:#x?}");
+                return None;
+            }
+        };
+        LoadOption::parse(&data)
+            .inspect_err(|_| log::warn!(target: "bds", "{kind:?}{number:04X} is not a valid load option."))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::load_option::{LOAD_OPTION_FORCE_RECONNECT, order_to_bytes};
+    use patina::{
+        boot_services::MockBootServices, runtime_services::MockRuntimeServices, uefi_protocol::device_path::nodes::Acpi,
+    };
+    use std::{
+        string::String,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicBool, Ordering},
+        },
+    };
+
+    struct NoPolicy;
+
+    impl BootPolicy for NoPolicy {}
+
+    fn boot_manager(
+        boot_services: MockBootServices,
+        runtime_services: MockRuntimeServices,
+    ) -> BootManager<MockBootServices, MockRuntimeServices> {
+        BootManager { boot_services, runtime_services, policy: &NoPolicy }
+    }
+
+    fn name(name: &[u16]) -> String {
+        String::from_utf16_lossy(name.split(|&c| c == 0).next().unwrap())
+    }
+
+    /// Returns runtime services serving `variables`, by name, from the global variable namespace.
+    fn runtime_services(variables: Vec<(&'static str, Vec<u8>)>) -> MockRuntimeServices {
+        let mut runtime_services = MockRuntimeServices::new();
+        runtime_services.expect_get_variable::<Vec<u8>>().returning(move |variable, namespace, _| {
+            assert_eq!(*namespace, GLOBAL_VARIABLE_GUID);
+            variables
+                .iter()
+                .find(|(n, _)| *n == name(variable))
+                .map(|(_, data)| (data.clone(), 0))
+                .ok_or(efi::Status::NOT_FOUND)
+        });
+        runtime_services
+    }
+
+    /// A `Driver####` option that loads `PciRoot(n)`.
+    fn driver_option(attributes: u32, n: u32) -> Vec<u8> {
+        let file_path = DevicePathBuf::from_device_path_node_iter(iter::once(Acpi::new_pci_root(n)));
+        LoadOption::new(attributes, "Driver", file_path).to_bytes()
+    }
+
+    fn numbers(candidates: &[BootCandidate]) -> Vec<(u16, bool)> {
+        candidates.iter().map(|candidate| (candidate.number, candidate.selected)).collect()
+    }
+
+    #[test]
+    fn selected_options_come_first() {
+        let candidates = boot_candidates(Some(7), Some(3), &[1, 3, 2]);
+        assert_eq!(numbers(&candidates), [(7, true), (3, true), (1, false), (2, false)]);
+    }
+
+    #[test]
+    fn each_option_is_tried_once() {
+        assert_eq!(numbers(&boot_candidates(Some(2), Some(2), &[2, 1, 1])), [(2, true), (1, false)]);
+        assert!(boot_candidates(None, None, &[]).is_empty());
+    }
+
+    #[test]
+    fn boot_next_is_booted_once() {
+        let mut runtime_services = runtime_services(vec![("BootNext", vec![3, 0])]);
+        runtime_services
+            .expect_set_variable::<Vec<u8>>()
+            .withf(|variable, namespace, attributes, data| {
+                name(variable) == "BootNext"
+                    && *namespace == GLOBAL_VARIABLE_GUID
+                    && *attributes == 0
+                    && data.is_empty()
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        assert_eq!(boot_manager(MockBootServices::new(), runtime_services).take_boot_next(), Some(3));
+
+        let mut runtime_services = self::runtime_services(Vec::new());
+        runtime_services.expect_set_variable::<Vec<u8>>().never();
+        assert_eq!(boot_manager(MockBootServices::new(), runtime_services).take_boot_next(), None);
+    }
+
+    #[test]
+    fn boot_order_falls_back_to_the_boot_options_present() {
+        let runtime_services = self::runtime_services(vec![("BootOrder", order_to_bytes(&[2, 0, 1]))]);
+        let manager = boot_manager(MockBootServices::new(), runtime_services);
+        assert_eq!(manager.load_option_order(LoadOptionType::Boot), [2, 0, 1]);
+
+        let mut runtime_services = self::runtime_services(Vec::new());
+        let other_namespace = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+        let present = [
+            ("Boot0002", GLOBAL_VARIABLE_GUID),
+            ("Driver0001", GLOBAL_VARIABLE_GUID),
+            ("Boot0005", other_namespace),
+            ("BootOrderX", GLOBAL_VARIABLE_GUID),
+            ("Boot0001", GLOBAL_VARIABLE_GUID),
+        ];
+        runtime_services.expect_get_next_variable_name().returning(move |previous, _| {
+            let next = match name(previous).as_str() {
+                "" => 0,
+                previous => present.iter().position(|(n, _)| *n == previous).unwrap() + 1,
+            };
+            present.get(next).map(|&(n, namespace)| (variable_name(n), namespace)).ok_or(efi::Status::NOT_FOUND)
+        });
+        let manager = boot_manager(MockBootServices::new(), runtime_services);
+        assert_eq!(manager.load_option_order(LoadOptionType::Boot), [1, 2]);
+        assert_eq!(manager.load_option_order(LoadOptionType::Driver), [1]);
+    }
+
+    #[test]
+    fn active_driver_options_are_loaded_in_order() {
+        let runtime_services = runtime_services(vec![
+            ("DriverOrder", order_to_bytes(&[3, 1, 2, 4])),
+            ("Driver0001", driver_option(LOAD_OPTION_ACTIVE, 1)),
+            ("Driver0002", driver_option(0, 2)),
+            ("Driver0003", driver_option(LOAD_OPTION_ACTIVE | LOAD_OPTION_FORCE_RECONNECT, 3)),
+        ]);
+
+        let loaded = Arc::new(Mutex::new(Vec::new()));
+        let mut boot_services = MockBootServices::new();
+        let paths = loaded.clone();
+        boot_services.expect_load_image_from_file().returning(move |parent, file_path| {
+            assert_eq!(parent, 0x10 as efi::Handle);
+            // SAFETY: The boot manager passes the device path of a load option, which ends with an end node.
+            let device_path = unsafe { DevicePath::try_from_ptr(file_path.as_ptr() as *const u8) }.unwrap();
+            let mut paths = paths.lock().unwrap();
+            paths.push(DevicePathBuf::from(device_path));
+            Ok(paths.len() as efi::Handle)
+        });
+        // The first driver loaded, Driver0003, fails to start and so cannot ask for a reconnection.
+        boot_services.expect_start_image().times(2).returning(|handle| match handle as usize {
+            1 => Err((efi::Status::LOAD_ERROR, None)),
+            _ => Ok(()),
+        });
+
+        let boot_manager = boot_manager(boot_services, runtime_services);
+        assert!(!boot_manager.load_driver_options(0x10 as efi::Handle));
+        let expected: Vec<_> = [3, 1]
+            .into_iter()
+            .map(|n| DevicePathBuf::from_device_path_node_iter(iter::once(Acpi::new_pci_root(n))))
+            .collect();
+        assert_eq!(*loaded.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn started_driver_options_can_ask_for_a_reconnection() {
+        let runtime_services = runtime_services(vec![
+            ("DriverOrder", order_to_bytes(&[1])),
+            ("Driver0001", driver_option(LOAD_OPTION_ACTIVE | LOAD_OPTION_FORCE_RECONNECT, 1)),
+        ]);
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_load_image_from_file().times(1).returning(|_, _| Ok(1 as efi::Handle));
+        boot_services.expect_start_image().times(1).returning(|_| Ok(()));
+
+        assert!(boot_manager(boot_services, runtime_services).load_driver_options(0x10 as efi::Handle));
+    }
+
+    #[test]
+    fn end_of_dxe_is_signalled_before_the_driver_options() {
+        let runtime_services = runtime_services(vec![
+            ("DriverOrder", order_to_bytes(&[1])),
+            ("Driver0001", driver_option(LOAD_OPTION_ACTIVE, 1)),
+        ]);
+
+        let signalled = Arc::new(AtomicBool::new(false));
+        let mut boot_services = MockBootServices::new();
+        boot_services
+            .expect_create_event_ex::<*mut c_void>()
+            .withf(|_, _, _, _, group| *group == EVENT_GROUP_END_OF_DXE)
+            .times(1)
+            .returning(|_, _, _, _, _| Ok(0x20 as efi::Event));
+        let end_of_dxe = signalled.clone();
+        boot_services.expect_signal_event().times(1).returning(move |event| {
+            assert_eq!(event, 0x20 as efi::Event);
+            end_of_dxe.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        boot_services.expect_close_event().times(1).returning(|_| Ok(()));
+        boot_services.expect_load_image_from_file().times(1).returning(move |_, _| {
+            assert!(signalled.load(Ordering::SeqCst), "A driver option was loaded before EndOfDxe.");
+            Ok(1 as efi::Handle)
+        });
+        boot_services.expect_start_image().times(1).returning(|_| Ok(()));
+
+        boot_manager(boot_services, runtime_services).start_driver_options(Some(0x10 as efi::Handle));
+    }
+}
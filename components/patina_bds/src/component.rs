@@ -0,0 +1,88 @@
+//! BDS Component
+//!
+//! Installs the Boot Device Selection (BDS) architectural protocol, whose entry runs the boot manager once the DXE
+//! core has dispatched every driver.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::ffi::c_void;
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices},
+    component::{component, service::Service},
+    error::{EfiError, Result},
+    pi::protocols::bds,
+    runtime_services::StandardRuntimeServices,
+};
+
+use crate::{boot_manager::BootManager, service::BootPolicy};
+
+static PROVIDER: spin::Once<BootManager> = spin::Once::new();
+
+/// The protocol interface, which the DXE core locates after dispatch.
+static PROTOCOL: bds::Protocol = bds::Protocol { entry: bds_entry };
+
+/// The [BootPolicy] used when the platform does not produce one.
+struct DefaultBootPolicy;
+
+impl BootPolicy for DefaultBootPolicy {}
+
+/// Produces the BDS architectural protocol.
+///
+/// Optionally consumes the platform's [BootPolicy] service, without which the defaults of its methods apply: every
+/// controller is connected, and `BootNext`, `BootOrder` and the removable media boot file are tried in turn.
+///
+/// ```rust,ignore
+/// commands.add_service(MyBootPolicy);
+/// commands.add_component(BdsProvider);
+/// ```
+#[derive(Default)]
+pub struct BdsProvider;
+
+#[component]
+impl BdsProvider {
+    #[coverage(off)] // Component integration - the boot manager logic it wires up is tested directly.
+    fn entry_point(
+        self,
+        policy: Option<Service<dyn BootPolicy>>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+    ) -> Result<()> {
+        if PROVIDER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        let policy: &'static dyn BootPolicy = match policy {
+            Some(policy) => *policy,
+            None => &DefaultBootPolicy,
+        };
+        PROVIDER.call_once(|| BootManager { boot_services: boot_services.clone(), runtime_services, policy });
+
+        // SAFETY: PROTOCOL is a static BDS architectural protocol interface, so it is valid for the life of the
+        // firmware and matches the GUID it is installed with.
+        unsafe {
+            boot_services.install_protocol_interface_unchecked(
+                None,
+                &bds::PROTOCOL_GUID,
+                &PROTOCOL as *const bds::Protocol as *mut c_void,
+            )
+        }
+        .map_err(EfiError::from)?;
+
+        log::info!(target: "bds", "BDS architectural protocol installed.");
+        Ok(())
+    }
+}
+
+/// The BDS entry, called by the DXE core once dispatch is complete. Never returns.
+#[coverage(off)] // Requires boot services.
+extern "efiapi" fn bds_entry(_this: *mut bds::Protocol) {
+    if let Some(boot_manager) = PROVIDER.get() {
+        boot_manager.run();
+    }
+    log::error!(target: "bds", "The BDS entry was called before the component ran.");
+}
@@ -0,0 +1,16 @@
+#![doc = include_str!("../README.md")]
+#![doc = concat!(
+    "## License\n\n",
+    " Copyright (c) Microsoft Corporation.\n\n",
+)]
+#![cfg_attr(all(not(feature = "std"), not(test), not(feature = "mockall")), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+pub mod load_option;
+pub mod service;
+
+mod boot_manager;
+mod short_form;
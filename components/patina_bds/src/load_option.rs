@@ -0,0 +1,325 @@
+//! Load Options
+//!
+//! Parses and serializes the `EFI_LOAD_OPTION` structure stored in the `Boot####` and `Driver####` variables, and
+//! the names and order variables that refer to them.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{string::String, vec::Vec};
+use core::{char, iter};
+
+use patina::{
+    error::EfiError,
+    uefi_protocol::device_path::{
+        DevicePathBuf,
+        device_path_node::{Header, UnknownDevicePathNode},
+        nodes::{DevicePathType, EndSubType},
+    },
+};
+
+/// The option is booted or loaded by the boot manager.
+pub const LOAD_OPTION_ACTIVE: u32 = 0x0000_0001;
+/// All controllers are reconnected after the driver of a `Driver####` option is loaded.
+pub const LOAD_OPTION_FORCE_RECONNECT: u32 = 0x0000_0002;
+/// The option is not shown in menus of boot options.
+pub const LOAD_OPTION_HIDDEN: u32 = 0x0000_0008;
+/// The bits of the attributes that hold the category of the option.
+pub const LOAD_OPTION_CATEGORY: u32 = 0x0000_1F00;
+/// The option is part of the normal boot processing.
+pub const LOAD_OPTION_CATEGORY_BOOT: u32 = 0x0000_0000;
+/// The option is an application, only started when explicitly selected.
+pub const LOAD_OPTION_CATEGORY_APP: u32 = 0x0000_0100;
+
+/// The kind of load option, which decides the names of its variables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOptionType {
+    /// A `Boot####` option, ordered by `BootOrder`.
+    Boot,
+    /// A `Driver####` option, ordered by `DriverOrder`.
+    Driver,
+}
+
+impl LoadOptionType {
+    fn prefix(self) -> &'static str {
+        match self {
+            LoadOptionType::Boot => "Boot",
+            LoadOptionType::Driver => "Driver",
+        }
+    }
+
+    /// Returns the null-terminated name of the `Boot####` or `Driver####` variable of option `number`.
+    pub fn variable_name(self, number: u16) -> Vec<u16> {
+        let digits = (0..4).rev().map(|shift| {
+            let digit = (number >> (shift * 4)) & 0xF;
+            char::from_digit(digit as u32, 16).unwrap_or('0').to_ascii_uppercase()
+        });
+        self.prefix().chars().chain(digits).map(|c| c as u16).chain(iter::once(0)).collect()
+    }
+
+    /// Returns the null-terminated name of the `BootOrder` or `DriverOrder` variable.
+    pub fn order_variable_name(self) -> Vec<u16> {
+        self.prefix().encode_utf16().chain("Order".encode_utf16()).chain(iter::once(0)).collect()
+    }
+
+    /// Returns the option number of a `Boot####` or `Driver####` variable name, which may be null-terminated.
+    ///
+    /// The spec requires the four digits to be uppercase hexadecimal, so `Boot000a` is not a load option.
+    pub fn parse_variable_name(self, name: &[u16]) -> Option<u16> {
+        let name = name.split(|&c| c == 0).next().unwrap_or_default();
+        let prefix_length = self.prefix().len();
+        if name.len() != prefix_length + 4
+            || !name.iter().copied().zip(self.prefix().encode_utf16()).all(|(a, b)| a == b)
+        {
+            return None;
+        }
+        name[prefix_length..].iter().try_fold(0u16, |number, &c| match c {
+            0x30..=0x39 => Some((number << 4) | (c - 0x30)),
+            0x41..=0x46 => Some((number << 4) | (c - 0x41 + 10)),
+            _ => None,
+        })
+    }
+}
+
+/// Parses the content of a `BootOrder` or `DriverOrder` variable, an array of option numbers.
+///
+/// A trailing odd byte is ignored.
+pub fn parse_order(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect()
+}
+
+/// Serializes option numbers into the content of a `BootOrder` or `DriverOrder` variable.
+pub fn order_to_bytes(order: &[u16]) -> Vec<u8> {
+    order.iter().flat_map(|number| number.to_le_bytes()).collect()
+}
+
+/// An `EFI_LOAD_OPTION`, the content of a `Boot####` or `Driver####` variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOption {
+    /// The `LOAD_OPTION_*` attributes.
+    pub attributes: u32,
+    /// The description shown to the user.
+    pub description: String,
+    /// The device path of the image to load, the first entry of the `FilePathList`.
+    pub file_path: DevicePathBuf,
+    /// The other entries of the `FilePathList`, whose meaning is defined by the image.
+    pub additional_file_paths: Vec<DevicePathBuf>,
+    /// The data passed to the image in the `LoadOptions` of its loaded image protocol.
+    pub optional_data: Vec<u8>,
+}
+
+impl LoadOption {
+    /// Creates an option with a single file path and no optional data.
+    pub fn new(attributes: u32, description: &str, file_path: DevicePathBuf) -> Self {
+        Self {
+            attributes,
+            description: String::from(description),
+            file_path,
+            additional_file_paths: Vec::new(),
+            optional_data: Vec::new(),
+        }
+    }
+
+    /// Parses an `EFI_LOAD_OPTION`.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::InvalidParameter] if the structure is truncated, the description is not null-terminated, or the
+    ///   `FilePathList` is empty, has a node shorter than its header, or does not end with an end of entire device
+    ///   path node.
+    pub fn parse(bytes: &[u8]) -> Result<Self, EfiError> {
+        let attributes = u32::from_le_bytes(read_array(bytes, 0)?);
+        let file_path_list_length = u16::from_le_bytes(read_array(bytes, 4)?) as usize;
+
+        let mut description = Vec::new();
+        let mut offset = 6;
+        loop {
+            let c = u16::from_le_bytes(read_array(bytes, offset)?);
+            offset += 2;
+            if c == 0 {
+                break;
+            }
+            description.push(c);
+        }
+        let description = char::decode_utf16(description).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect();
+
+        let file_path_list = bytes.get(offset..offset + file_path_list_length).ok_or(EfiError::InvalidParameter)?;
+        let mut file_paths = parse_file_path_list(file_path_list)?.into_iter();
+        let file_path = file_paths.next().ok_or(EfiError::InvalidParameter)?;
+
+        Ok(Self {
+            attributes,
+            description,
+            file_path,
+            additional_file_paths: file_paths.collect(),
+            optional_data: bytes[offset + file_path_list_length..].to_vec(),
+        })
+    }
+
+    /// Serializes the option into an `EFI_LOAD_OPTION`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let file_path_list_length = iter::once(&self.file_path)
+            .chain(&self.additional_file_paths)
+            .map(|file_path| file_path.size())
+            .sum::<usize>();
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.attributes.to_le_bytes());
+        bytes.extend_from_slice(&(file_path_list_length as u16).to_le_bytes());
+        bytes.extend(self.description.encode_utf16().chain(iter::once(0)).flat_map(u16::to_le_bytes));
+        for file_path in iter::once(&self.file_path).chain(&self.additional_file_paths) {
+            bytes.extend_from_slice(file_path.as_bytes());
+        }
+        bytes.extend_from_slice(&self.optional_data);
+        bytes
+    }
+
+    /// Returns whether [LOAD_OPTION_ACTIVE] is set.
+    pub fn is_active(&self) -> bool {
+        self.attributes & LOAD_OPTION_ACTIVE != 0
+    }
+
+    /// Returns the `LOAD_OPTION_CATEGORY_*` of the option.
+    pub fn category(&self) -> u32 {
+        self.attributes & LOAD_OPTION_CATEGORY
+    }
+
+    /// Returns whether the option is booted without being selected: it is active and in the boot category.
+    pub fn is_auto_boot(&self) -> bool {
+        self.is_active() && self.category() == LOAD_OPTION_CATEGORY_BOOT
+    }
+}
+
+fn read_array<const N: usize>(bytes: &[u8], offset: usize) -> Result<[u8; N], EfiError> {
+    bytes.get(offset..offset + N).and_then(|slice| slice.try_into().ok()).ok_or(EfiError::InvalidParameter)
+}
+
+/// Splits a `FilePathList` into its device paths, each ending with an end of entire device path node.
+fn parse_file_path_list(bytes: &[u8]) -> Result<Vec<DevicePathBuf>, EfiError> {
+    let mut file_paths = Vec::new();
+    let mut nodes = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let header: [u8; 4] = read_array(bytes, offset)?;
+        let length = u16::from_le_bytes([header[2], header[3]]) as usize;
+        if length < Header::size_of_header() {
+            return Err(EfiError::InvalidParameter);
+        }
+        let data = bytes.get(offset + Header::size_of_header()..offset + length).ok_or(EfiError::InvalidParameter)?;
+        nodes.push(UnknownDevicePathNode { header: Header::new(header[0], header[1], length), data });
+        offset += length;
+
+        if header[0] == DevicePathType::End as u8 && header[1] == EndSubType::Entire as u8 {
+            file_paths.push(DevicePathBuf::from_device_path_node_iter(nodes.drain(..)));
+        }
+    }
+    if !nodes.is_empty() {
+        return Err(EfiError::InvalidParameter);
+    }
+    Ok(file_paths)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// `PciRoot(0x0)/Pci(0x0,0x2)` followed by an end of entire device path node.
+    const PCI_PATH: [u8; 22] = [2, 1, 12, 0, 0xD0, 0x41, 0x03, 0x0A, 0, 0, 0, 0, 1, 1, 6, 0, 0, 2, 0x7F, 0xFF, 4, 0];
+    const END: [u8; 4] = [0x7F, 0xFF, 4, 0];
+
+    fn ucs2(s: &str) -> Vec<u8> {
+        s.encode_utf16().chain(iter::once(0)).flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn load_option_bytes(attributes: u32, description: &str, file_path_list: &[u8], optional_data: &[u8]) -> Vec<u8> {
+        let mut bytes = attributes.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&(file_path_list.len() as u16).to_le_bytes());
+        bytes.extend(ucs2(description));
+        bytes.extend_from_slice(file_path_list);
+        bytes.extend_from_slice(optional_data);
+        bytes
+    }
+
+    #[test]
+    fn parse_reads_every_field() {
+        let file_path_list = [&PCI_PATH[..], &END[..]].concat();
+        let bytes = load_option_bytes(
+            LOAD_OPTION_ACTIVE | LOAD_OPTION_CATEGORY_APP,
+            "UEFI Shell",
+            &file_path_list,
+            b"-nostartup",
+        );
+
+        let option = LoadOption::parse(&bytes).unwrap();
+        assert_eq!(option.attributes, LOAD_OPTION_ACTIVE | LOAD_OPTION_CATEGORY_APP);
+        assert_eq!(option.description, "UEFI Shell");
+        assert_eq!(option.file_path.as_bytes(), PCI_PATH);
+        assert_eq!(option.additional_file_paths.len(), 1);
+        assert_eq!(option.additional_file_paths[0].as_bytes(), END);
+        assert_eq!(option.optional_data, b"-nostartup");
+        assert!(option.is_active());
+        assert!(!option.is_auto_boot());
+
+        assert_eq!(option.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_options() {
+        let valid = load_option_bytes(LOAD_OPTION_ACTIVE, "Disk", &PCI_PATH, &[]);
+        assert!(LoadOption::parse(&valid).is_ok());
+
+        // Truncated anywhere before the end of the file path list.
+        for length in 0..valid.len() {
+            assert_eq!(LoadOption::parse(&valid[..length]), Err(EfiError::InvalidParameter), "length {length}");
+        }
+        // An empty file path list.
+        assert_eq!(LoadOption::parse(&load_option_bytes(0, "Disk", &[], &[])), Err(EfiError::InvalidParameter));
+        // A file path that does not end with an end of entire device path node.
+        assert_eq!(
+            LoadOption::parse(&load_option_bytes(0, "Disk", &PCI_PATH[..18], &[])),
+            Err(EfiError::InvalidParameter)
+        );
+        // A node shorter than its header.
+        assert_eq!(
+            LoadOption::parse(&load_option_bytes(0, "Disk", &[1, 1, 2, 0, 0x7F, 0xFF, 4, 0], &[])),
+            Err(EfiError::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn new_option_round_trips() {
+        let file_path = parse_file_path_list(&PCI_PATH).unwrap().pop().unwrap();
+        let option = LoadOption::new(LOAD_OPTION_ACTIVE, "Disk", file_path);
+        assert!(option.is_auto_boot());
+        assert_eq!(LoadOption::parse(&option.to_bytes()).unwrap(), option);
+        assert_eq!(parse_file_path_list(&[PCI_PATH, PCI_PATH].concat()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn variable_names_use_four_uppercase_hex_digits() {
+        let name = LoadOptionType::Boot.variable_name(0x00AF);
+        assert_eq!(String::from_utf16(&name).unwrap(), "Boot00AF\0");
+        assert_eq!(LoadOptionType::Boot.parse_variable_name(&name), Some(0x00AF));
+        assert_eq!(
+            LoadOptionType::Driver.parse_variable_name(&LoadOptionType::Driver.variable_name(0xFFFF)),
+            Some(0xFFFF)
+        );
+        assert_eq!(String::from_utf16(&LoadOptionType::Driver.order_variable_name()).unwrap(), "DriverOrder\0");
+
+        for name in ["Boot00af", "Boot0001X", "Boot001", "Driver0001", "BootOrder", "Boot000G"] {
+            let name: Vec<u16> = name.encode_utf16().collect();
+            assert_eq!(LoadOptionType::Boot.parse_variable_name(&name), None);
+        }
+    }
+
+    #[test]
+    fn order_round_trips() {
+        assert_eq!(parse_order(&[1, 0, 0x34, 0x12, 9]), vec![1, 0x1234]);
+        assert_eq!(order_to_bytes(&[1, 0x1234]), vec![1, 0, 0x34, 0x12]);
+    }
+}
@@ -0,0 +1,70 @@
+//! BDS Service Definitions
+//!
+//! Defines the [BootPolicy] service through which a platform customizes the boot manager of the [BdsProvider]
+//! component.
+//!
+//! [BdsProvider]: crate::component::BdsProvider
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+
+use crate::load_option::LoadOption;
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// The file booted from removable media when no boot option succeeds, for the architecture of the firmware.
+#[cfg(target_arch = "aarch64")]
+pub const DEFAULT_REMOVABLE_MEDIA_FILE: &str = "\\EFI\\BOOT\\BOOTAA64.EFI";
+/// The file booted from removable media when no boot option succeeds, for the architecture of the firmware.
+#[cfg(not(target_arch = "aarch64"))]
+pub const DEFAULT_REMOVABLE_MEDIA_FILE: &str = "\\EFI\\BOOT\\BOOTX64.EFI";
+
+/// Platform policy of the boot manager.
+///
+/// Produced by the platform and optionally consumed by the [BdsProvider] component. Every method has a default, which
+/// is also the behavior without the service. The methods are called from the BDS phase at `TPL_APPLICATION`, so
+/// they may use boot services, wait for input or draw on the console.
+///
+/// [BdsProvider]: crate::component::BdsProvider
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait BootPolicy: Send + Sync {
+    /// Returns whether every driver is connected to every controller before booting. True by default.
+    ///
+    /// Without it, only the devices on the device paths of the options being booted are connected, which is faster
+    /// but cannot expand short-form device paths to devices that were never connected.
+    fn connect_all(&self) -> bool {
+        true
+    }
+
+    /// Returns the `Boot####` option the user selected with a hot key, if any, which is booted before `BootNext`
+    /// and `BootOrder`. None by default.
+    ///
+    /// Called once per boot attempt, after the consoles are connected.
+    fn hotkey_boot_option(&self) -> Option<u16> {
+        None
+    }
+
+    /// Returns the options tried after those of `BootOrder`, for example a network boot or the UEFI shell on
+    /// platforms that do not create `Boot####` variables. None by default.
+    fn default_boot_options(&self) -> Vec<LoadOption> {
+        Vec::new()
+    }
+
+    /// Returns the file tried on every file system after the default boot options, or None to skip it.
+    /// [DEFAULT_REMOVABLE_MEDIA_FILE] by default.
+    fn removable_media_boot_file(&self) -> Option<&'static str> {
+        Some(DEFAULT_REMOVABLE_MEDIA_FILE)
+    }
+
+    /// Called when no option could be booted. Does nothing by default.
+    ///
+    /// The boot manager tries every option again when this returns, so an implementation may wait for new media,
+    /// show a menu, or reset the system instead.
+    fn boot_failed(&self) {}
+}
@@ -0,0 +1,196 @@
+//! Short-Form Device Path Expansion
+//!
+//! Load options may hold a short-form device path, which starts in the middle of the full device path of the
+//! device. The boot manager matches them against the device paths of the devices present, as described in the
+//! "Boot Option Variables Default Boot Behavior" section of the UEFI specification:
+//!
+//! - A hard drive media device path (`HD(...)`) first is matched against the partitions that carry the same node.
+//! - A file path media device path first is tried on every file system.
+//!
+//! USB WWID, USB class and URI short forms are not supported.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::iter;
+
+use patina::uefi_protocol::device_path::{
+    DevicePath, DevicePathBuf,
+    device_path_node::{Header, UnknownDevicePathNode},
+    nodes::{DevicePathType, MediaSubType, MessagingSubType},
+};
+
+/// The kind of device path at the start of a load option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShortForm {
+    /// A full device path, loaded as is.
+    Full,
+    /// Starts with a hard drive media device path.
+    HardDrive,
+    /// Starts with a file path media device path.
+    FilePath,
+    /// A short form that the boot manager cannot expand.
+    Unsupported,
+}
+
+impl ShortForm {
+    /// Classifies `device_path` by its first node.
+    pub(crate) fn of(device_path: &DevicePath) -> Self {
+        let Some(first) = device_path.iter().next() else {
+            return ShortForm::Unsupported;
+        };
+        let (r#type, sub_type) = (first.header.r#type, first.header.sub_type);
+        if r#type == DevicePathType::Media as u8 && sub_type == MediaSubType::HardDrive as u8 {
+            ShortForm::HardDrive
+        } else if r#type == DevicePathType::Media as u8 && sub_type == MediaSubType::FilePath as u8 {
+            ShortForm::FilePath
+        } else if r#type == DevicePathType::Messaging as u8
+            && [MessagingSubType::UsbWwid, MessagingSubType::UsbClass, MessagingSubType::Uri]
+                .iter()
+                .any(|&short_form| sub_type == short_form as u8)
+        {
+            ShortForm::Unsupported
+        } else {
+            ShortForm::Full
+        }
+    }
+}
+
+/// Expands a device path that starts with a hard drive node against the device paths of the block devices.
+///
+/// A block device matches if its device path contains the same hard drive node, which identifies the partition by
+/// its signature. The expansion is the device path of the block device up to that node, followed by the rest of the
+/// short form.
+pub(crate) fn expand_hard_drive(short_form: &DevicePath, block_devices: &[DevicePathBuf]) -> Vec<DevicePathBuf> {
+    let Some(hard_drive) = short_form.iter().next() else {
+        return Vec::new();
+    };
+    block_devices
+        .iter()
+        .filter_map(|block_device| {
+            let position = block_device.iter().position(|node| node == hard_drive)?;
+            Some(DevicePathBuf::from_device_path_node_iter(block_device.iter().take(position).chain(short_form.iter())))
+        })
+        .collect()
+}
+
+/// Expands a device path that starts with a file path node by prefixing it with the device path of each file system.
+pub(crate) fn expand_file_path(short_form: &DevicePath, file_systems: &[DevicePathBuf]) -> Vec<DevicePathBuf> {
+    file_systems
+        .iter()
+        .map(|file_system| {
+            DevicePathBuf::from_device_path_node_iter(
+                file_system
+                    .iter()
+                    .take_while(|node| node.header.r#type != DevicePathType::End as u8)
+                    .chain(short_form.iter()),
+            )
+        })
+        .collect()
+}
+
+/// Returns a short-form device path made of a single file path media node, such as `\EFI\BOOT\BOOTX64.EFI`.
+pub(crate) fn file_path(path: &str) -> DevicePathBuf {
+    let data: Vec<u8> = path.encode_utf16().chain(iter::once(0)).flat_map(u16::to_le_bytes).collect();
+    let node = UnknownDevicePathNode {
+        header: Header::new(
+            DevicePathType::Media as u8,
+            MediaSubType::FilePath as u8,
+            Header::size_of_header() + data.len(),
+        ),
+        data: &data,
+    };
+    DevicePathBuf::from_device_path_node_iter(iter::once(node))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use patina::uefi_protocol::device_path::nodes::{Acpi, Pci};
+
+    /// A hard drive media node for partition `number` of the disk with the given GPT signature byte.
+    fn hard_drive_node(number: u8, signature: u8) -> Vec<u8> {
+        let mut node = alloc::vec![4, 1, 42, 0, number, 0, 0, 0];
+        node.extend_from_slice(&[0; 16]);
+        node.extend_from_slice(&[signature; 16]);
+        node.extend_from_slice(&[2, 2]);
+        node
+    }
+
+    fn node(bytes: &[u8]) -> UnknownDevicePathNode<'_> {
+        UnknownDevicePathNode {
+            header: Header::new(bytes[0], bytes[1], bytes.len()),
+            data: &bytes[Header::size_of_header()..],
+        }
+    }
+
+    fn controller(device: u8) -> DevicePathBuf {
+        let mut device_path = DevicePathBuf::from_device_path_node_iter(iter::once(Acpi::new_pci_root(0)));
+        device_path
+            .append_device_path(&DevicePathBuf::from_device_path_node_iter(iter::once(Pci { function: 0, device })));
+        device_path
+    }
+
+    fn partition(device: u8, number: u8, signature: u8) -> DevicePathBuf {
+        let hard_drive = hard_drive_node(number, signature);
+        let mut device_path = controller(device);
+        device_path.append_device_path(&DevicePathBuf::from_device_path_node_iter(iter::once(node(&hard_drive))));
+        device_path
+    }
+
+    #[test]
+    fn short_forms_are_classified_by_their_first_node() {
+        let hard_drive = hard_drive_node(1, 0xAA);
+        assert_eq!(
+            ShortForm::of(&DevicePathBuf::from_device_path_node_iter(iter::once(node(&hard_drive)))),
+            ShortForm::HardDrive
+        );
+        assert_eq!(ShortForm::of(&file_path("\\EFI\\BOOT\\BOOTX64.EFI")), ShortForm::FilePath);
+        assert_eq!(ShortForm::of(&controller(2)), ShortForm::Full);
+        let usb_class = [3, 15, 11, 0, 0xFF, 0xFF, 0xFF, 0xFF, 8, 6, 0x50];
+        assert_eq!(
+            ShortForm::of(&DevicePathBuf::from_device_path_node_iter(iter::once(node(&usb_class)))),
+            ShortForm::Unsupported
+        );
+    }
+
+    #[test]
+    fn hard_drive_short_form_matches_the_partition_signature() {
+        let hard_drive = hard_drive_node(2, 0xBB);
+        let mut short_form = DevicePathBuf::from_device_path_node_iter(iter::once(node(&hard_drive)));
+        short_form.append_device_path(&file_path("\\EFI\\os\\loader.efi"));
+
+        let block_devices = [controller(1), partition(1, 1, 0xAA), partition(2, 2, 0xBB), partition(3, 2, 0xCC)];
+        let expanded = expand_hard_drive(&short_form, &block_devices);
+
+        let mut expected = partition(2, 2, 0xBB);
+        expected.append_device_path(&file_path("\\EFI\\os\\loader.efi"));
+        assert_eq!(expanded, [expected]);
+        assert!(expand_hard_drive(&short_form, &block_devices[..2]).is_empty());
+    }
+
+    #[test]
+    fn file_path_short_form_is_tried_on_every_file_system() {
+        let short_form = file_path("\\EFI\\BOOT\\BOOTX64.EFI");
+        let file_systems = [partition(1, 1, 0xAA), partition(2, 1, 0xBB)];
+        let expanded = expand_file_path(&short_form, &file_systems);
+
+        assert_eq!(expanded.len(), 2);
+        for (expanded, file_system) in expanded.iter().zip(&file_systems) {
+            assert!(expanded.starts_with(file_system));
+            assert_eq!(expanded.node_count(), file_system.node_count() + 1);
+            assert_eq!(expanded.slice_end(2), &*short_form);
+        }
+    }
+
+    #[test]
+    fn file_path_node_holds_the_null_terminated_path() {
+        let device_path = file_path("\\A");
+        assert_eq!(device_path.as_bytes(), [4, 4, 10, 0, b'\\', 0, b'A', 0, 0, 0, 0x7F, 0xFF, 4, 0]);
+    }
+}
@@ -0,0 +1,625 @@
+//! Capsule Provider Component
+//!
+//! Installs the `UpdateCapsule()` and `QueryCapsuleCapabilities()` entries of the runtime services table and the
+//! Capsule architectural protocol, routes the capsules handed over in capsule HOBs, and produces the
+//! [CapsuleRouter] service.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate alloc;
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{ffi::c_void, mem::offset_of, ptr, slice};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        component,
+        hob::Hob,
+        params::Commands,
+        service::{
+            IntoService, Service,
+            memory::MemoryManager,
+            runtime_table::{CapsuleEntries, RuntimeServiceEntries, RuntimeServicesTable},
+        },
+    },
+    efi_types::EfiMemoryType,
+    error::{EfiError, Result},
+    pi::{hob, protocols::capsule},
+};
+use r_efi::efi;
+
+use crate::{
+    fmp::{FMP_CAPSULE_GUID, FirmwareManagementImages, FmpCapsuleProcessor},
+    router::Router,
+    scatter_gather::{coalesce, split},
+    service::{CapsuleImage, CapsulePersistence, CapsuleProcessor, CapsuleRouter},
+};
+
+/// State shared with the runtime services table entries, which receive no context pointer.
+struct Provider {
+    router: &'static Router,
+    persistence: Option<&'static dyn CapsulePersistence>,
+    boot_services: StandardBootServices,
+}
+
+static PROVIDER: spin::Once<Provider> = spin::Once::new();
+
+/// `EFI_CAPSULE_TABLE`, followed by `capsule_array_number` pointers in total.
+#[repr(C)]
+struct CapsuleTable {
+    capsule_array_number: u32,
+    capsule_ptr: [*mut c_void; 1],
+}
+
+/// Produces the UEFI capsule runtime services.
+///
+/// Optionally consumes the platform's [CapsulePersistence] service, without which capsules that persist across a
+/// reset are not supported, and produces the [CapsuleRouter] service. Capsules from capsule HOBs are processed once
+/// a processor for their GUID is registered, except for those with `CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE`, which are
+/// published in an `EFI_CAPSULE_TABLE` configuration table named by their GUID instead.
+///
+/// Both runtime services run from the DXE core image, which the OS reclaims, so they are boot-time only.
+///
+/// ```rust,ignore
+/// commands.add_service(MyCapsulePersistence);
+/// commands.add_component(CapsuleProvider);
+/// ```
+#[derive(Default)]
+pub struct CapsuleProvider;
+
+#[component]
+impl CapsuleProvider {
+    #[coverage(off)] // Component integration - the capsule services it wires up are tested directly.
+    fn entry_point(
+        self,
+        persistence: Option<Service<dyn CapsulePersistence>>,
+        capsule_hobs: Option<Hob<hob::Capsule>>,
+        rt_table: Service<dyn RuntimeServicesTable>,
+        memory_manager: Service<dyn MemoryManager>,
+        boot_services: StandardBootServices,
+        mut commands: Commands,
+    ) -> Result<()> {
+        if PROVIDER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        // The OS updates capsules through the router at runtime, after boot services memory is reclaimed.
+        let router: &'static Router = memory_manager.leak_in_runtime_memory(Router::new())?;
+        let persistence: Option<&'static dyn CapsulePersistence> = persistence.map(|service| *service);
+        PROVIDER.call_once(|| Provider { router, persistence, boot_services: boot_services.clone() });
+
+        if let Some(capsule_hobs) = capsule_hobs {
+            // SAFETY: Capsule HOBs describe the memory that the pre-DXE phase coalesced the capsules into.
+            let tables = unsafe { route_hob_capsules(router, &capsule_hobs) };
+            install_capsule_tables(&boot_services, &tables)?;
+        }
+
+        boot_services
+            .create_event(EventType::SIGNAL_EXIT_BOOT_SERVICES, Tpl::NOTIFY, Some(exit_boot_services), router)
+            .map_err(EfiError::from)?;
+
+        rt_table
+            .install(RuntimeServiceEntries::Capsule(CapsuleEntries { update_capsule, query_capsule_capabilities }))?;
+
+        // SAFETY: The architectural protocol carries no interface; a null pointer is what consumers expect.
+        unsafe { boot_services.install_protocol_interface_unchecked(None, &capsule::PROTOCOL_GUID, ptr::null_mut()) }
+            .map_err(EfiError::from)?;
+
+        commands.add_service(CapsuleRouterService { router });
+
+        log::info!(target: "capsule", "Capsule services installed.");
+        Ok(())
+    }
+}
+
+/// Processes firmware management (FMP) capsules.
+///
+/// Registers an [FmpCapsuleProcessor] that writes the payloads of FMP capsules through the Firmware Management
+/// Protocol instances of the platform. It runs when `EFI_END_OF_DXE_EVENT_GROUP_GUID` is signaled, once the drivers
+/// that install those instances have been dispatched; FMP capsules from capsule HOBs are processed then.
+///
+/// ```rust,ignore
+/// commands.add_component(CapsuleProvider);
+/// commands.add_component(FmpCapsuleProvider);
+/// ```
+#[derive(Default)]
+pub struct FmpCapsuleProvider;
+
+#[component(event = patina::guids::EVENT_GROUP_END_OF_DXE)]
+impl FmpCapsuleProvider {
+    #[coverage(off)] // Component integration - the processor it registers is tested directly.
+    fn entry_point(self, router: Service<dyn CapsuleRouter>, boot_services: StandardBootServices) -> Result<()> {
+        let processor = Box::leak(Box::new(FmpCapsuleProcessor::new(FirmwareManagementImages::new(boot_services))));
+        router.register_processor(FMP_CAPSULE_GUID, processor)
+    }
+}
+
+/// The [CapsuleRouter] service produced by [CapsuleProvider].
+#[derive(IntoService)]
+#[service(dyn CapsuleRouter)]
+struct CapsuleRouterService {
+    router: &'static Router,
+}
+
+impl CapsuleRouter for CapsuleRouterService {
+    fn register_processor(
+        &self,
+        guid: efi::Guid,
+        processor: &'static dyn CapsuleProcessor,
+    ) -> core::result::Result<(), EfiError> {
+        self.router.register_processor(guid, processor)
+    }
+}
+
+/// Queues the capsules found in `capsule_hobs`, and returns the ones that belong in a configuration table instead.
+///
+/// # Safety
+///
+/// The memory described by each HOB must be readable and remain allocated.
+unsafe fn route_hob_capsules(router: &Router, capsule_hobs: &Hob<hob::Capsule>) -> Vec<CapsuleImage<'static>> {
+    let mut tables = Vec::new();
+    for capsule_hob in capsule_hobs {
+        if capsule_hob.base_address == 0 || capsule_hob.length == 0 {
+            continue;
+        }
+        // SAFETY: The caller guarantees the memory described by the HOB is readable and remains allocated.
+        let bytes =
+            unsafe { slice::from_raw_parts(capsule_hob.base_address as *const u8, capsule_hob.length as usize) };
+        let capsules = match split(bytes) {
+            Ok(capsules) => capsules,
+            Err(err) => {
+                log::error!(target: "capsule", "Malformed capsule at {:#x}: {err:?}", capsule_hob.base_address);
+                continue;
+            }
+        };
+        for capsule in capsules {
+            if capsule.flags() & efi::CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE != 0 {
+                tables.push(capsule);
+            } else {
+                router.queue(capsule);
+            }
+        }
+    }
+    tables
+}
+
+/// Installs one `EFI_CAPSULE_TABLE` per GUID, pointing at the capsules with that GUID.
+#[coverage(off)] // Requires boot services.
+fn install_capsule_tables(boot_services: &StandardBootServices, capsules: &[CapsuleImage<'static>]) -> Result<()> {
+    let mut guids: Vec<efi::Guid> = Vec::new();
+    for capsule in capsules {
+        if !guids.contains(&capsule.guid()) {
+            guids.push(capsule.guid());
+        }
+    }
+
+    for guid in guids {
+        let pointers: Vec<*mut c_void> = capsules
+            .iter()
+            .filter(|capsule| capsule.guid() == guid)
+            .map(|capsule| capsule.image().as_ptr() as *mut c_void)
+            .collect();
+        let size = offset_of!(CapsuleTable, capsule_ptr) + pointers.len() * size_of::<*mut c_void>();
+        let table = boot_services
+            .allocate_pool(EfiMemoryType::RuntimeServicesData, size)
+            .map_err(EfiError::from)?
+            .cast::<CapsuleTable>();
+
+        // SAFETY: The pool holds the count and every pointer, and pool memory is aligned for both.
+        unsafe {
+            (&raw mut (*table).capsule_array_number).write(pointers.len() as u32);
+            ptr::copy_nonoverlapping(
+                pointers.as_ptr(),
+                (&raw mut (*table).capsule_ptr).cast::<*mut c_void>(),
+                pointers.len(),
+            );
+        }
+        // SAFETY: The table lives in runtime services data and is never freed.
+        unsafe { boot_services.install_configuration_table_unchecked(&guid, table.cast()) }.map_err(EfiError::from)?;
+        log::info!(target: "capsule", "Published {} capsule(s) with GUID {guid:?}.", pointers.len());
+    }
+    Ok(())
+}
+
+extern "efiapi" fn exit_boot_services(_event: efi::Event, router: &'static Router) {
+    router.exit_boot_services();
+}
+
+/// Runs `f` against the installed provider, at `TPL_NOTIFY` while boot services are available.
+fn with_provider(f: impl FnOnce(&Router, Option<&dyn CapsulePersistence>) -> efi::Status) -> efi::Status {
+    let Some(provider) = PROVIDER.get() else {
+        return efi::Status::NOT_READY;
+    };
+    if provider.router.at_runtime() {
+        return f(provider.router, provider.persistence);
+    }
+    let _tpl = provider.boot_services.raise_tpl_guarded(Tpl::NOTIFY);
+    f(provider.router, provider.persistence)
+}
+
+extern "efiapi" fn update_capsule(
+    capsule_header_array: *mut *mut efi::CapsuleHeader,
+    capsule_count: usize,
+    scatter_gather_list: efi::PhysicalAddress,
+) -> efi::Status {
+    with_provider(|router, persistence| {
+        // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+        unsafe { update_capsule_with(router, persistence, capsule_header_array, capsule_count, scatter_gather_list) }
+    })
+}
+
+extern "efiapi" fn query_capsule_capabilities(
+    capsule_header_array: *mut *mut efi::CapsuleHeader,
+    capsule_count: usize,
+    maximum_capsule_size: *mut u64,
+    reset_type: *mut efi::ResetType,
+) -> efi::Status {
+    with_provider(|router, persistence| {
+        // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+        unsafe {
+            query_capsule_capabilities_with(
+                router,
+                persistence,
+                capsule_header_array,
+                capsule_count,
+                maximum_capsule_size,
+                reset_type,
+            )
+        }
+    })
+}
+
+/// The capsules passed to `UpdateCapsule()` or `QueryCapsuleCapabilities()`.
+///
+/// The capsules are parsed again on every pass instead of being collected, since nothing can be allocated at runtime.
+struct Request<'a> {
+    headers: &'a [*mut efi::CapsuleHeader],
+    persistence: Option<&'a dyn CapsulePersistence>,
+}
+
+impl<'a> Request<'a> {
+    fn capsules(&self) -> impl Iterator<Item = CapsuleImage<'a>> {
+        // SAFETY: request() checked that every entry points at a well-formed capsule.
+        self.headers.iter().filter_map(|header| unsafe { capsule(*header) }.ok())
+    }
+
+    fn total_size(&self) -> u64 {
+        self.capsules().map(|capsule| capsule.image().len() as u64).sum()
+    }
+
+    fn initiate_reset(&self) -> bool {
+        self.capsules().any(|capsule| capsule.flags() & efi::CAPSULE_FLAGS_INITIATE_RESET != 0)
+    }
+}
+
+/// Parses the capsule that `header` points at.
+///
+/// # Safety
+///
+/// `header` must be null or point at a capsule of `CapsuleImageSize` bytes.
+unsafe fn capsule<'a>(header: *mut efi::CapsuleHeader) -> core::result::Result<CapsuleImage<'a>, EfiError> {
+    if header.is_null() {
+        return Err(EfiError::InvalidParameter);
+    }
+    // SAFETY: The caller guarantees the header points at a capsule of CapsuleImageSize bytes.
+    let image_size = unsafe { ptr::read_unaligned(&raw const (*header).capsule_image_size) };
+    // SAFETY: As above.
+    let bytes = unsafe { slice::from_raw_parts(header as *const u8, image_size as usize) };
+    CapsuleImage::parse(bytes)
+}
+
+/// Checks the capsules against the flags they carry and what the platform supports.
+///
+/// `persistence` is only part of the request if one of the capsules persists across a reset.
+///
+/// # Safety
+///
+/// `capsule_header_array` must be null or valid as described for `UpdateCapsule()` in the UEFI specification.
+unsafe fn request<'a>(
+    router: &Router,
+    persistence: Option<&'a dyn CapsulePersistence>,
+    capsule_header_array: *const *mut efi::CapsuleHeader,
+    capsule_count: usize,
+) -> core::result::Result<Request<'a>, EfiError> {
+    if capsule_header_array.is_null() || capsule_count == 0 {
+        return Err(EfiError::InvalidParameter);
+    }
+
+    // SAFETY: The caller guarantees the array holds capsule_count entries.
+    let headers = unsafe { slice::from_raw_parts(capsule_header_array, capsule_count) };
+    let mut request = Request { headers, persistence: None };
+    for header in headers {
+        // SAFETY: The caller guarantees every entry points at a capsule of CapsuleImageSize bytes.
+        let capsule = unsafe { capsule(*header) }?;
+
+        let persist = capsule.flags() & efi::CAPSULE_FLAGS_PERSIST_ACROSS_RESET != 0;
+        let needs_persist = efi::CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE | efi::CAPSULE_FLAGS_INITIATE_RESET;
+        if capsule.flags() & needs_persist != 0 && !persist {
+            return Err(EfiError::InvalidParameter);
+        }
+        if persist {
+            request.persistence = Some(persistence.ok_or(EfiError::Unsupported)?);
+        } else if !router.supports(&capsule.guid()) {
+            return Err(EfiError::Unsupported);
+        } else if router.at_runtime() {
+            // Processors need boot services.
+            return Err(EfiError::OutOfResources);
+        }
+    }
+    Ok(request)
+}
+
+/// # Safety
+///
+/// `capsule_header_array` must be null or valid, and `scatter_gather_list` must be 0 or valid, as described for
+/// `UpdateCapsule()` in the UEFI specification.
+unsafe fn update_capsule_with(
+    router: &Router,
+    persistence: Option<&dyn CapsulePersistence>,
+    capsule_header_array: *const *mut efi::CapsuleHeader,
+    capsule_count: usize,
+    scatter_gather_list: efi::PhysicalAddress,
+) -> efi::Status {
+    // SAFETY: The caller guarantees the array is null or valid.
+    let request = match unsafe { request(router, persistence, capsule_header_array, capsule_count) } {
+        Ok(request) => request,
+        Err(err) => return err.into(),
+    };
+
+    if let Some(persistence) = request.persistence {
+        if scatter_gather_list == 0 {
+            return efi::Status::INVALID_PARAMETER;
+        }
+        if request.total_size() > persistence.max_capsule_size() {
+            return efi::Status::OUT_OF_RESOURCES;
+        }
+        // At runtime the list may not be mapped, so it is only checked while boot services are available.
+        if !router.at_runtime() {
+            // SAFETY: The caller guarantees the list is valid.
+            if let Err(err) = unsafe { check_scatter_gather_list(&request, scatter_gather_list) } {
+                return err.into();
+            }
+        }
+    }
+
+    let mut status = efi::Status::SUCCESS;
+    for capsule in request.capsules() {
+        if capsule.flags() & efi::CAPSULE_FLAGS_PERSIST_ACROSS_RESET == 0
+            && let Err(err) = router.process(&capsule)
+        {
+            log::error!(target: "capsule", "Failed to process capsule {:?}: {err:?}", capsule.guid());
+            status = err.into();
+        }
+    }
+    if status.is_error() {
+        return status;
+    }
+
+    match request.persistence {
+        Some(persistence) => match persistence.persist(scatter_gather_list, request.initiate_reset()) {
+            Ok(()) => efi::Status::SUCCESS,
+            Err(err) => err.into(),
+        },
+        None => efi::Status::SUCCESS,
+    }
+}
+
+/// Checks that the scatter-gather list describes exactly the capsules of the request.
+///
+/// # Safety
+///
+/// The list must be valid as described for `UpdateCapsule()` in the UEFI specification.
+unsafe fn check_scatter_gather_list(
+    request: &Request<'_>,
+    scatter_gather_list: efi::PhysicalAddress,
+) -> core::result::Result<(), EfiError> {
+    let expected = usize::try_from(request.total_size()).map_err(|_| EfiError::OutOfResources)?;
+    // SAFETY: The caller guarantees the list is valid.
+    let data = unsafe { coalesce(scatter_gather_list, expected) }?;
+    let described = split(&data)?;
+    let matches = described.len() == request.capsules().count()
+        && described.iter().zip(request.capsules()).all(|(described, capsule)| described.image() == capsule.image());
+    if !matches {
+        return Err(EfiError::InvalidParameter);
+    }
+    Ok(())
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `QueryCapsuleCapabilities()` in the UEFI specification.
+unsafe fn query_capsule_capabilities_with(
+    router: &Router,
+    persistence: Option<&dyn CapsulePersistence>,
+    capsule_header_array: *const *mut efi::CapsuleHeader,
+    capsule_count: usize,
+    maximum_capsule_size: *mut u64,
+    reset_type: *mut efi::ResetType,
+) -> efi::Status {
+    if maximum_capsule_size.is_null() || reset_type.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: The caller guarantees the array is null or valid.
+    let request = match unsafe { request(router, persistence, capsule_header_array, capsule_count) } {
+        Ok(request) => request,
+        Err(err) => return err.into(),
+    };
+
+    let (size, reset) = match request.persistence {
+        Some(persistence) => (persistence.max_capsule_size(), persistence.reset_type()),
+        // Capsules processed in memory are only limited by the size field of their header.
+        None => (u32::MAX as u64, efi::RESET_COLD),
+    };
+    // SAFETY: Both pointers were checked for null above and are otherwise valid per the caller contract.
+    unsafe {
+        maximum_capsule_size.write(size);
+        reset_type.write(reset);
+    }
+    efi::Status::SUCCESS
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        scatter_gather::{test_capsule, test_descriptor},
+        service::{MockCapsulePersistence, MockCapsuleProcessor},
+    };
+    use alloc::boxed::Box;
+
+    const FMP: efi::Guid =
+        efi::Guid::from_fields(0x6dcbd5ed, 0xe82d, 0x4c44, 0xbd, 0xa1, &[0x71, 0x94, 0x19, 0x9a, 0xd9, 0x2a]);
+    const TABLE: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+    const PERSIST: u32 = efi::CAPSULE_FLAGS_PERSIST_ACROSS_RESET;
+
+    fn leak<T>(value: T) -> &'static mut T {
+        Box::leak(Box::new(value))
+    }
+
+    fn capsule_hob(bytes: &[u8]) -> hob::Capsule {
+        let header =
+            hob::header::Hob { r#type: hob::UEFI_CAPSULE, length: size_of::<hob::Capsule>() as u16, reserved: 0 };
+        hob::Capsule { header, base_address: bytes.as_ptr() as u64, length: bytes.len() as u64 }
+    }
+
+    fn processor(body: &'static [u8]) -> &'static MockCapsuleProcessor {
+        let mut processor = MockCapsuleProcessor::new();
+        processor.expect_process().withf(move |capsule| capsule.body() == body).times(1).returning(|_| Ok(()));
+        leak(processor)
+    }
+
+    fn persistence(max_capsule_size: u64) -> MockCapsulePersistence {
+        let mut persistence = MockCapsulePersistence::new();
+        persistence.expect_max_capsule_size().return_const(max_capsule_size);
+        persistence
+    }
+
+    fn header(bytes: &mut [u8]) -> *mut efi::CapsuleHeader {
+        bytes.as_mut_ptr() as *mut efi::CapsuleHeader
+    }
+
+    #[test]
+    fn hob_capsules_are_routed_by_guid() {
+        let fmp = leak(test_capsule(FMP, 0, &[1, 2, 3]));
+        let table = leak(test_capsule(TABLE, PERSIST | efi::CAPSULE_FLAGS_POPULATE_SYSTEM_TABLE, &[4]));
+        let truncated = leak(test_capsule(FMP, 0, &[5, 6]));
+        let hobs = Hob::mock(vec![capsule_hob(fmp), capsule_hob(table), capsule_hob(&truncated[..29])]);
+
+        let router = Router::new();
+        // SAFETY: The HOBs describe leaked buffers.
+        let tables = unsafe { route_hob_capsules(&router, &hobs) };
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].guid(), TABLE);
+
+        // Only the well-formed FMP capsule reaches the processor, once it is registered.
+        router.register_processor(FMP, processor(&[1, 2, 3])).unwrap();
+    }
+
+    #[test]
+    fn update_capsule_checks_its_arguments() {
+        let router = Router::new();
+        router.register_processor(FMP, leak(MockCapsuleProcessor::new())).unwrap();
+        let mut reset_only = test_capsule(FMP, efi::CAPSULE_FLAGS_INITIATE_RESET, &[]);
+        let mut unknown = test_capsule(TABLE, 0, &[]);
+        let mut persisted = test_capsule(FMP, PERSIST, &[]);
+
+        let update = |headers: &[*mut efi::CapsuleHeader], count: usize| {
+            // SAFETY: The array references live locals.
+            unsafe { update_capsule_with(&router, None, headers.as_ptr(), count, 0) }
+        };
+        assert_eq!(update(&[header(&mut unknown)], 0), efi::Status::INVALID_PARAMETER);
+        assert_eq!(update(&[ptr::null_mut()], 1), efi::Status::INVALID_PARAMETER);
+        assert_eq!(update(&[header(&mut reset_only)], 1), efi::Status::INVALID_PARAMETER);
+        assert_eq!(update(&[header(&mut unknown)], 1), efi::Status::UNSUPPORTED);
+        assert_eq!(update(&[header(&mut persisted)], 1), efi::Status::UNSUPPORTED);
+        // SAFETY: A null array is rejected before it is read.
+        let status = unsafe { update_capsule_with(&router, None, ptr::null(), 1, 0) };
+        assert_eq!(status, efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn in_memory_capsules_are_processed_at_boot_time_only() {
+        let router = Router::new();
+        router.register_processor(FMP, processor(&[7, 7])).unwrap();
+        let mut capsule = test_capsule(FMP, 0, &[7, 7]);
+        let headers = [header(&mut capsule)];
+
+        // SAFETY: The array references a live local.
+        unsafe {
+            assert_eq!(update_capsule_with(&router, None, headers.as_ptr(), 1, 0), efi::Status::SUCCESS);
+            router.exit_boot_services();
+            assert_eq!(update_capsule_with(&router, None, headers.as_ptr(), 1, 0), efi::Status::OUT_OF_RESOURCES);
+        }
+    }
+
+    #[test]
+    fn persisted_capsules_need_a_matching_scatter_gather_list() {
+        let router = Router::new();
+        let mut capsule = test_capsule(FMP, PERSIST | efi::CAPSULE_FLAGS_INITIATE_RESET, &[1, 2, 3, 4]);
+        let other = test_capsule(FMP, PERSIST | efi::CAPSULE_FLAGS_INITIATE_RESET, &[9, 9, 9, 9]);
+        let len = capsule.len() as u64;
+        let list = [test_descriptor(len, capsule.as_ptr() as u64), test_descriptor(0, 0)];
+        let wrong_list = [test_descriptor(len, other.as_ptr() as u64), test_descriptor(0, 0)];
+        let headers = [header(&mut capsule)];
+        let list_address = list.as_ptr() as u64;
+
+        let mut accepting = persistence(len);
+        accepting
+            .expect_persist()
+            .withf(move |list, reset| *list == list_address && *reset)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let small = persistence(len - 1);
+
+        // SAFETY: The array, the lists and the capsules are live locals.
+        unsafe {
+            let update = |persistence: &dyn CapsulePersistence, list| {
+                update_capsule_with(&router, Some(persistence), headers.as_ptr(), 1, list)
+            };
+            assert_eq!(update(&accepting, 0), efi::Status::INVALID_PARAMETER);
+            assert_eq!(update(&accepting, wrong_list.as_ptr() as u64), efi::Status::INVALID_PARAMETER);
+            assert_eq!(update(&small, list_address), efi::Status::OUT_OF_RESOURCES);
+            assert_eq!(update(&accepting, list_address), efi::Status::SUCCESS);
+        }
+    }
+
+    #[test]
+    fn query_reports_the_persistence_limits() {
+        let router = Router::new();
+        router.register_processor(FMP, leak(MockCapsuleProcessor::new())).unwrap();
+        let mut in_memory = test_capsule(FMP, 0, &[]);
+        let mut persisted = test_capsule(TABLE, PERSIST, &[]);
+        let mut persistence = persistence(0x10_0000);
+        persistence.expect_reset_type().return_const(efi::RESET_WARM);
+
+        let mut size = 0;
+        let mut reset = efi::RESET_SHUTDOWN;
+        // SAFETY: All pointers are null or reference locals.
+        unsafe {
+            let query = |headers: &[*mut efi::CapsuleHeader], size: *mut u64, reset: *mut efi::ResetType| {
+                query_capsule_capabilities_with(
+                    &router,
+                    Some(&persistence),
+                    headers.as_ptr(),
+                    headers.len(),
+                    size,
+                    reset,
+                )
+            };
+            assert_eq!(query(&[header(&mut in_memory)], &mut size, ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+
+            assert_eq!(query(&[header(&mut in_memory)], &mut size, &mut reset), efi::Status::SUCCESS);
+            assert_eq!((size, reset), (u32::MAX as u64, efi::RESET_COLD));
+
+            let headers = [header(&mut in_memory), header(&mut persisted)];
+            assert_eq!(query(&headers, &mut size, &mut reset), efi::Status::SUCCESS);
+            assert_eq!((size, reset), (0x10_0000, efi::RESET_WARM));
+        }
+    }
+}
@@ -0,0 +1,615 @@
+//! Firmware Management Capsules
+//!
+//! Parses firmware management (FMP) capsules, and applies their payloads through the Firmware Management Protocol
+//! instances that the drivers of updatable devices install.
+//!
+//! An FMP capsule body starts with an `EFI_FIRMWARE_MANAGEMENT_CAPSULE_HEADER` and a list of item offsets: first the
+//! embedded drivers, then the payloads. Each payload is an `EFI_FIRMWARE_MANAGEMENT_CAPSULE_IMAGE_HEADER` followed by
+//! the new image and the optional vendor code.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::{ffi::c_void, ptr};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, protocol_handler::HandleSearchType},
+    error::EfiError,
+    uefi_protocol::firmware_management::{self, ImageDescriptor},
+};
+use r_efi::efi;
+
+use crate::service::{CapsuleImage, CapsuleProcessor};
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// The capsule GUID of FMP capsules (`EFI_FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID`).
+pub const FMP_CAPSULE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x6dcbd5ed, 0xe82d, 0x4c44, 0xbd, 0xa1, &[0x71, 0x94, 0x19, 0x9a, 0xd9, 0x2a]);
+
+/// The only version of the capsule header.
+const CAPSULE_HEADER_VERSION: u32 = 1;
+/// The size of the capsule header, without the item offsets that follow it.
+const CAPSULE_HEADER_SIZE: usize = 8;
+/// The latest version of the payload header.
+const IMAGE_HEADER_VERSION: u32 = 3;
+
+/// A payload of an FMP capsule: a new firmware image and the device it is meant for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FmpPayload<'a> {
+    /// The type of firmware image the payload updates.
+    pub image_type_id: efi::Guid,
+    /// The index of the image on the device, from 1.
+    pub image_index: u8,
+    /// The instance of the device to update, or 0 for any device with a matching image.
+    pub hardware_instance: u64,
+    /// The new firmware image.
+    pub image: &'a [u8],
+    /// Data for the device driver, if the capsule carries any.
+    pub vendor_code: Option<&'a [u8]>,
+}
+
+/// The contents of an FMP capsule.
+#[derive(Debug)]
+pub struct FmpCapsule<'a> {
+    embedded_drivers: Vec<&'a [u8]>,
+    payloads: Vec<FmpPayload<'a>>,
+}
+
+impl<'a> FmpCapsule<'a> {
+    /// Parses the body of an FMP capsule.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::InvalidParameter] if a header version is unknown, the capsule holds no item, or an item does not
+    ///   end exactly where the next one, or the capsule, does.
+    pub fn parse(body: &'a [u8]) -> Result<Self, EfiError> {
+        let version = read_u32(body, 0)?;
+        let driver_count = read_u16(body, 4)? as usize;
+        let payload_count = read_u16(body, 6)? as usize;
+        let item_count = driver_count + payload_count;
+        if version != CAPSULE_HEADER_VERSION || item_count == 0 {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let offsets_end = CAPSULE_HEADER_SIZE + item_count * size_of::<u64>();
+        let mut items = Vec::with_capacity(item_count);
+        let mut start = offsets_end;
+        for index in 0..item_count {
+            let offset = usize::try_from(read_u64(body, CAPSULE_HEADER_SIZE + index * size_of::<u64>())?)
+                .map_err(|_| EfiError::InvalidParameter)?;
+            if offset < start {
+                return Err(EfiError::InvalidParameter);
+            }
+            if index > 0 {
+                items.push(body.get(start..offset).ok_or(EfiError::InvalidParameter)?);
+            }
+            start = offset;
+        }
+        items.push(body.get(start..).ok_or(EfiError::InvalidParameter)?);
+
+        let payloads = items.split_off(driver_count);
+        Ok(Self {
+            embedded_drivers: items,
+            payloads: payloads.into_iter().map(parse_payload).collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Returns the drivers that the capsule carries to update its payloads with.
+    pub fn embedded_drivers(&self) -> &[&'a [u8]] {
+        &self.embedded_drivers
+    }
+
+    /// Returns the payloads of the capsule, in order.
+    pub fn payloads(&self) -> &[FmpPayload<'a>] {
+        &self.payloads
+    }
+}
+
+/// Parses a payload, which must take up all of `item`.
+fn parse_payload(item: &[u8]) -> Result<FmpPayload<'_>, EfiError> {
+    let (header_size, hardware_instance) = match read_u32(item, 0)? {
+        1 => (32, 0),
+        2 => (40, read_u64(item, 32)?),
+        IMAGE_HEADER_VERSION => (48, read_u64(item, 32)?),
+        _ => return Err(EfiError::InvalidParameter),
+    };
+    let type_id = item.get(4..20).ok_or(EfiError::InvalidParameter)?;
+    let image_index = read_u8(item, 20)?;
+    let image_size = read_u32(item, 24)? as usize;
+    let vendor_code_size = read_u32(item, 28)? as usize;
+    if header_size + image_size + vendor_code_size != item.len() {
+        return Err(EfiError::InvalidParameter);
+    }
+
+    let (image, vendor_code) = item[header_size..].split_at(image_size);
+    Ok(FmpPayload {
+        image_type_id: efi::Guid::from_bytes(type_id.try_into().unwrap()),
+        image_index,
+        hardware_instance,
+        image,
+        vendor_code: (!vendor_code.is_empty()).then_some(vendor_code),
+    })
+}
+
+fn read_u8(bytes: &[u8], offset: usize) -> Result<u8, EfiError> {
+    bytes.get(offset).copied().ok_or(EfiError::InvalidParameter)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, EfiError> {
+    let bytes = bytes.get(offset..offset + 2).ok_or(EfiError::InvalidParameter)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, EfiError> {
+    let bytes = bytes.get(offset..offset + 4).ok_or(EfiError::InvalidParameter)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, EfiError> {
+    let bytes = bytes.get(offset..offset + 8).ok_or(EfiError::InvalidParameter)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// The firmware images that FMP capsules update.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+#[allow(clippy::needless_lifetimes)] //https://github.com/rust-lang/rust-clippy/issues/6622
+pub trait FirmwareImages: Send + Sync {
+    /// Writes `payload` to the firmware image it is meant for.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::NotFound] if no device has a matching image.
+    /// - The error of the device if it rejected the image.
+    fn set_image<'a>(&self, payload: &FmpPayload<'a>) -> Result<(), EfiError>;
+}
+
+/// Processes FMP capsules by writing each of their payloads through [FirmwareImages].
+pub struct FmpCapsuleProcessor<F: FirmwareImages> {
+    images: F,
+}
+
+impl<F: FirmwareImages> FmpCapsuleProcessor<F> {
+    /// Creates a processor that updates `images`.
+    pub const fn new(images: F) -> Self {
+        Self { images }
+    }
+}
+
+impl<F: FirmwareImages> CapsuleProcessor for FmpCapsuleProcessor<F> {
+    /// Writes every payload of `capsule`, even after one of them failed.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::InvalidParameter] if the capsule is malformed. No payload is written then.
+    /// - [EfiError::Unsupported] if the capsule carries embedded drivers, which are not loaded.
+    /// - The error of the first payload that could not be written.
+    fn process<'a>(&self, capsule: &CapsuleImage<'a>) -> Result<(), EfiError> {
+        let fmp = FmpCapsule::parse(capsule.body())?;
+        if !fmp.embedded_drivers().is_empty() {
+            return Err(EfiError::Unsupported);
+        }
+
+        let mut result = Ok(());
+        for payload in fmp.payloads() {
+            if let Err(err) = self.images.set_image(payload) {
+                log::error!(
+                    target: "capsule",
+                    "Failed to update image {} of type {:?}: {err:?}",
+                    payload.image_index,
+                    payload.image_type_id
+                );
+                result = result.and(Err(err));
+            }
+        }
+        result
+    }
+}
+
+/// [FirmwareImages] backed by the Firmware Management Protocol instances installed in the handle database.
+pub struct FirmwareManagementImages {
+    boot_services: StandardBootServices,
+}
+
+impl FirmwareManagementImages {
+    /// Creates the firmware images of the devices found through `boot_services`.
+    pub const fn new(boot_services: StandardBootServices) -> Self {
+        Self { boot_services }
+    }
+}
+
+impl FirmwareImages for FirmwareManagementImages {
+    #[coverage(off)] // Requires boot services; the descriptor matching is tested directly.
+    fn set_image<'a>(&self, payload: &FmpPayload<'a>) -> Result<(), EfiError> {
+        let handles = self
+            .boot_services
+            .locate_handle_buffer(HandleSearchType::ByProtocol(&firmware_management::PROTOCOL_GUID))
+            .map_err(|_| EfiError::NotFound)?;
+        for &handle in handles.iter() {
+            // SAFETY: handle was returned for the protocol, and the interface is only used for the calls below.
+            let Ok(fmp) = (unsafe { self.boot_services.handle_protocol::<firmware_management::Protocol>(handle) })
+            else {
+                continue;
+            };
+            let Ok(descriptors) = image_descriptors(fmp) else {
+                continue;
+            };
+            if !descriptors.iter().any(|(descriptor, version)| targets(descriptor, *version, payload)) {
+                continue;
+            }
+
+            let vendor_code = payload.vendor_code.map_or(ptr::null(), |code| code.as_ptr() as *const c_void);
+            let mut abort_reason = ptr::null_mut();
+            let status = (fmp.set_image)(
+                fmp,
+                payload.image_index,
+                payload.image.as_ptr() as *const c_void,
+                payload.image.len(),
+                vendor_code,
+                None,
+                &mut abort_reason,
+            );
+            if !abort_reason.is_null() {
+                let _ = self.boot_services.free_pool(abort_reason as *mut u8);
+            }
+            return EfiError::status_to_result(status);
+        }
+        Err(EfiError::NotFound)
+    }
+}
+
+/// Returns true if `descriptor`, of `version`, describes the image `payload` is meant for.
+fn targets(descriptor: &ImageDescriptor, version: u32, payload: &FmpPayload<'_>) -> bool {
+    // Descriptors before version 3 carry no hardware instance, so they only match payloads meant for any device.
+    let hardware_instance = if version >= 3 { descriptor.hardware_instance } else { 0 };
+    descriptor.image_type_id == payload.image_type_id
+        && (payload.hardware_instance == 0 || payload.hardware_instance == hardware_instance)
+}
+
+/// Returns the image descriptors of `fmp`, with the descriptor version they were reported with.
+fn image_descriptors(fmp: &mut firmware_management::Protocol) -> Result<Vec<(ImageDescriptor, u32)>, EfiError> {
+    let mut size = 0;
+    let mut version = 0;
+    let mut count = 0;
+    let mut descriptor_size = 0;
+    let mut package_version = 0;
+    let mut package_version_name = ptr::null_mut();
+    let status = (fmp.get_image_info)(
+        fmp,
+        &mut size,
+        ptr::null_mut(),
+        &mut version,
+        &mut count,
+        &mut descriptor_size,
+        &mut package_version,
+        &mut package_version_name,
+    );
+    if status != efi::Status::BUFFER_TOO_SMALL {
+        EfiError::status_to_result(status)?;
+        return Ok(Vec::new());
+    }
+
+    // Backed by u64 so that the descriptors are aligned.
+    let mut buffer = alloc::vec![0u64; size.div_ceil(size_of::<u64>())];
+    let status = (fmp.get_image_info)(
+        fmp,
+        &mut size,
+        buffer.as_mut_ptr() as *mut ImageDescriptor,
+        &mut version,
+        &mut count,
+        &mut descriptor_size,
+        &mut package_version,
+        &mut package_version_name,
+    );
+    EfiError::status_to_result(status)?;
+
+    // Only the fields of version 1 are always present, and the descriptors are descriptor_size bytes apart.
+    let minimum_size = core::mem::offset_of!(ImageDescriptor, lowest_supported_image_version);
+    if descriptor_size < minimum_size || (count as usize) * descriptor_size > size {
+        return Err(EfiError::DeviceError);
+    }
+    let bytes = buffer.as_ptr() as *const u8;
+    Ok((0..count as usize)
+        .map(|index| {
+            // SAFETY: Every field is an integer, a GUID or a raw pointer, for which zero is a valid value.
+            let mut descriptor: ImageDescriptor = unsafe { core::mem::zeroed() };
+            // SAFETY: The descriptor lies within the buffer, which get_image_info filled with count descriptors of
+            // descriptor_size bytes each; only as many bytes as both the descriptor and the structure hold are copied.
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    bytes.add(index * descriptor_size),
+                    ptr::addr_of_mut!(descriptor) as *mut u8,
+                    descriptor_size.min(size_of::<ImageDescriptor>()),
+                )
+            };
+            (descriptor, version)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::scatter_gather::test_capsule;
+
+    const IMAGE_TYPE: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+
+    fn payload_item(
+        version: u32,
+        image_index: u8,
+        hardware_instance: u64,
+        image: &[u8],
+        vendor_code: &[u8],
+    ) -> Vec<u8> {
+        let mut item = Vec::new();
+        item.extend_from_slice(&version.to_le_bytes());
+        item.extend_from_slice(IMAGE_TYPE.as_bytes());
+        item.extend_from_slice(&[image_index, 0, 0, 0]);
+        item.extend_from_slice(&(image.len() as u32).to_le_bytes());
+        item.extend_from_slice(&(vendor_code.len() as u32).to_le_bytes());
+        if version >= 2 {
+            item.extend_from_slice(&hardware_instance.to_le_bytes());
+        }
+        if version >= 3 {
+            item.extend_from_slice(&0u64.to_le_bytes());
+        }
+        item.extend_from_slice(image);
+        item.extend_from_slice(vendor_code);
+        item
+    }
+
+    fn fmp_body(driver_count: u16, items: &[Vec<u8>]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&CAPSULE_HEADER_VERSION.to_le_bytes());
+        body.extend_from_slice(&driver_count.to_le_bytes());
+        body.extend_from_slice(&(items.len() as u16 - driver_count).to_le_bytes());
+        let mut offset = CAPSULE_HEADER_SIZE + items.len() * size_of::<u64>();
+        for item in items {
+            body.extend_from_slice(&(offset as u64).to_le_bytes());
+            offset += item.len();
+        }
+        for item in items {
+            body.extend_from_slice(item);
+        }
+        body
+    }
+
+    fn descriptor(image_type_id: efi::Guid, hardware_instance: u64) -> ImageDescriptor {
+        // SAFETY: Every field is an integer, a GUID or a raw pointer, for which zero is a valid value.
+        ImageDescriptor { image_index: 1, image_type_id, hardware_instance, ..unsafe { core::mem::zeroed() } }
+    }
+
+    #[test]
+    fn parse_reads_every_payload_version() {
+        let body = fmp_body(
+            0,
+            &[
+                payload_item(1, 1, 0, &[1, 2, 3], &[]),
+                payload_item(3, 2, 7, &[4], &[5, 6]),
+                payload_item(2, 3, 9, &[], &[]),
+            ],
+        );
+        let fmp = FmpCapsule::parse(&body).unwrap();
+        assert!(fmp.embedded_drivers().is_empty());
+        assert_eq!(
+            fmp.payloads(),
+            [
+                FmpPayload {
+                    image_type_id: IMAGE_TYPE,
+                    image_index: 1,
+                    hardware_instance: 0,
+                    image: &[1, 2, 3],
+                    vendor_code: None
+                },
+                FmpPayload {
+                    image_type_id: IMAGE_TYPE,
+                    image_index: 2,
+                    hardware_instance: 7,
+                    image: &[4],
+                    vendor_code: Some(&[5, 6]),
+                },
+                FmpPayload {
+                    image_type_id: IMAGE_TYPE,
+                    image_index: 3,
+                    hardware_instance: 9,
+                    image: &[],
+                    vendor_code: None
+                },
+            ]
+        );
+
+        let body = fmp_body(1, &[alloc::vec![0xAA; 5], payload_item(1, 1, 0, &[1], &[])]);
+        let fmp = FmpCapsule::parse(&body).unwrap();
+        assert_eq!(fmp.embedded_drivers(), [&[0xAA; 5][..]]);
+        assert_eq!(fmp.payloads().len(), 1);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_capsules() {
+        let valid = fmp_body(0, &[payload_item(3, 1, 0, &[1, 2], &[3])]);
+        assert!(FmpCapsule::parse(&valid).is_ok());
+
+        // Unknown capsule header version, and no items.
+        let mut body = valid.clone();
+        body[0] = 2;
+        assert_eq!(FmpCapsule::parse(&body).unwrap_err(), EfiError::InvalidParameter);
+        assert_eq!(FmpCapsule::parse(&fmp_body(0, &[])).unwrap_err(), EfiError::InvalidParameter);
+
+        // Truncated header, offset list, or payload.
+        for len in [6, 12, valid.len() - 1] {
+            assert_eq!(FmpCapsule::parse(&valid[..len]).unwrap_err(), EfiError::InvalidParameter);
+        }
+        // Trailing bytes after the last payload.
+        let mut body = valid.clone();
+        body.push(0);
+        assert_eq!(FmpCapsule::parse(&body).unwrap_err(), EfiError::InvalidParameter);
+
+        // Unknown payload header version.
+        assert_eq!(
+            FmpCapsule::parse(&fmp_body(0, &[payload_item(4, 1, 0, &[], &[])])).unwrap_err(),
+            EfiError::InvalidParameter
+        );
+
+        // An offset that points into the offset list, or before the previous item.
+        let mut body = valid.clone();
+        body[8] = 4;
+        assert_eq!(FmpCapsule::parse(&body).unwrap_err(), EfiError::InvalidParameter);
+        let mut body = fmp_body(0, &[payload_item(1, 1, 0, &[], &[]), payload_item(1, 2, 0, &[], &[])]);
+        body[16..24].copy_from_slice(&16u64.to_le_bytes());
+        assert_eq!(FmpCapsule::parse(&body).unwrap_err(), EfiError::InvalidParameter);
+    }
+
+    #[test]
+    fn processor_writes_every_payload() {
+        let body = fmp_body(0, &[payload_item(1, 1, 0, &[1], &[]), payload_item(1, 2, 0, &[2], &[])]);
+        let capsule_bytes = test_capsule(FMP_CAPSULE_GUID, 0, &body);
+        let capsule = CapsuleImage::parse(&capsule_bytes).unwrap();
+
+        let mut images = MockFirmwareImages::new();
+        images
+            .expect_set_image()
+            .withf(|payload| payload.image_index == 1)
+            .times(1)
+            .returning(|_| Err(EfiError::NotFound));
+        images.expect_set_image().withf(|payload| payload.image_index == 2).times(1).returning(|_| Ok(()));
+        // The second payload is still written after the first one failed.
+        assert_eq!(FmpCapsuleProcessor::new(images).process(&capsule), Err(EfiError::NotFound));
+
+        let mut images = MockFirmwareImages::new();
+        images.expect_set_image().times(2).returning(|_| Ok(()));
+        assert_eq!(FmpCapsuleProcessor::new(images).process(&capsule), Ok(()));
+    }
+
+    #[test]
+    fn processor_rejects_embedded_drivers() {
+        let body = fmp_body(1, &[alloc::vec![0; 4], payload_item(1, 1, 0, &[1], &[])]);
+        let capsule_bytes = test_capsule(FMP_CAPSULE_GUID, 0, &body);
+        let capsule = CapsuleImage::parse(&capsule_bytes).unwrap();
+
+        let mut images = MockFirmwareImages::new();
+        images.expect_set_image().never();
+        let processor = FmpCapsuleProcessor::new(images);
+        assert_eq!(processor.process(&capsule), Err(EfiError::Unsupported));
+
+        let capsule_bytes = test_capsule(FMP_CAPSULE_GUID, 0, &[1, 0, 0]);
+        assert_eq!(processor.process(&CapsuleImage::parse(&capsule_bytes).unwrap()), Err(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn descriptors_match_by_type_and_hardware_instance() {
+        let other = efi::Guid::from_fields(9, 9, 9, 9, 9, &[9; 6]);
+        let payload = |hardware_instance| FmpPayload {
+            image_type_id: IMAGE_TYPE,
+            image_index: 1,
+            hardware_instance,
+            image: &[],
+            vendor_code: None,
+        };
+
+        assert!(targets(&descriptor(IMAGE_TYPE, 5), 3, &payload(0)));
+        assert!(targets(&descriptor(IMAGE_TYPE, 5), 3, &payload(5)));
+        assert!(!targets(&descriptor(IMAGE_TYPE, 5), 3, &payload(6)));
+        assert!(!targets(&descriptor(other, 5), 3, &payload(0)));
+        // Older descriptors have no hardware instance.
+        assert!(targets(&descriptor(IMAGE_TYPE, 5), 2, &payload(0)));
+        assert!(!targets(&descriptor(IMAGE_TYPE, 5), 2, &payload(5)));
+    }
+
+    #[test]
+    fn image_descriptors_follow_the_reported_size() {
+        extern "efiapi" fn get_image_info(
+            _this: *mut firmware_management::Protocol,
+            image_info_size: *mut usize,
+            image_info: *mut ImageDescriptor,
+            descriptor_version: *mut u32,
+            descriptor_count: *mut u8,
+            descriptor_size: *mut usize,
+            _package_version: *mut u32,
+            _package_version_name: *mut *mut efi::Char16,
+        ) -> efi::Status {
+            // Two descriptors, padded to 128 bytes each as a newer version might be.
+            const STRIDE: usize = 128;
+            // SAFETY: Test code - the caller passes valid pointers, and a buffer of image_info_size bytes.
+            unsafe {
+                *descriptor_version = firmware_management::IMAGE_DESCRIPTOR_VERSION;
+                *descriptor_count = 2;
+                *descriptor_size = STRIDE;
+                if *image_info_size < 2 * STRIDE {
+                    *image_info_size = 2 * STRIDE;
+                    return efi::Status::BUFFER_TOO_SMALL;
+                }
+                image_info.write(descriptor(IMAGE_TYPE, 1));
+                (image_info as *mut u8).add(STRIDE).cast::<ImageDescriptor>().write(descriptor(IMAGE_TYPE, 2));
+            }
+            efi::Status::SUCCESS
+        }
+        extern "efiapi" fn get_image(
+            _: *mut firmware_management::Protocol,
+            _: u8,
+            _: *mut c_void,
+            _: *mut usize,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn set_image(
+            _: *mut firmware_management::Protocol,
+            _: u8,
+            _: *const c_void,
+            _: usize,
+            _: *const c_void,
+            _: Option<firmware_management::ProgressFn>,
+            _: *mut *mut efi::Char16,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn check_image(
+            _: *mut firmware_management::Protocol,
+            _: u8,
+            _: *const c_void,
+            _: usize,
+            _: *mut u32,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn get_package_info(
+            _: *mut firmware_management::Protocol,
+            _: *mut u32,
+            _: *mut *mut efi::Char16,
+            _: *mut u32,
+            _: *mut u64,
+            _: *mut u64,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn set_package_info(
+            _: *mut firmware_management::Protocol,
+            _: *const c_void,
+            _: usize,
+            _: *const c_void,
+            _: u32,
+            _: *const efi::Char16,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+
+        let mut fmp = firmware_management::Protocol {
+            get_image_info,
+            get_image,
+            set_image,
+            check_image,
+            get_package_info,
+            set_package_info,
+        };
+        let descriptors = image_descriptors(&mut fmp).unwrap();
+        assert_eq!(descriptors.len(), 2);
+        assert_eq!(descriptors[1].0.hardware_instance, 2);
+        assert_eq!(descriptors[1].1, firmware_management::IMAGE_DESCRIPTOR_VERSION);
+        assert!(descriptors.iter().all(|(descriptor, _)| descriptor.image_type_id == IMAGE_TYPE));
+    }
+}
@@ -0,0 +1,191 @@
+//! Capsule Routing
+//!
+//! Hands capsules to the [CapsuleProcessor] registered for their GUID, and holds on to capsules from the pre-DXE
+//! phase until a processor for them is registered.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use patina::{error::EfiError, runtime_services::fixed_vec::FixedVec};
+use r_efi::efi;
+use spin::Mutex;
+
+use crate::service::{CapsuleImage, CapsuleProcessor};
+
+/// The most processors that can be registered.
+pub(crate) const MAX_PROCESSORS: usize = 16;
+
+/// The registered processors, by capsule GUID.
+type Processors = FixedVec<(efi::Guid, &'static dyn CapsuleProcessor), MAX_PROCESSORS>;
+
+fn find(processors: &Processors, guid: &efi::Guid) -> Option<&'static dyn CapsuleProcessor> {
+    processors.iter().find(|(g, _)| g == guid).map(|(_, processor)| *processor)
+}
+
+/// Owns the registered processors and the capsules waiting for one.
+///
+/// `UpdateCapsule()` and `QueryCapsuleCapabilities()` read the router at runtime, so it is allocated as
+/// `EfiRuntimeServicesData` and holds the processors in a fixed-capacity list. The waiting capsules are only touched
+/// while boot services are available.
+pub(crate) struct Router {
+    processors: Mutex<Processors>,
+    pending: Mutex<Vec<CapsuleImage<'static>>>,
+    at_runtime: AtomicBool,
+}
+
+impl Router {
+    pub(crate) fn new() -> Self {
+        Self {
+            processors: Mutex::new(Processors::new()),
+            pending: Mutex::new(Vec::new()),
+            at_runtime: AtomicBool::new(false),
+        }
+    }
+
+    /// Stops accepting registrations; processors only run while boot services are available.
+    pub(crate) fn exit_boot_services(&self) {
+        self.at_runtime.store(true, Ordering::SeqCst);
+        for capsule in self.pending.lock().iter() {
+            log::warn!(target: "capsule", "No processor for capsule {:?}; it was not processed.", capsule.guid());
+        }
+    }
+
+    pub(crate) fn at_runtime(&self) -> bool {
+        self.at_runtime.load(Ordering::SeqCst)
+    }
+
+    fn processor(&self, guid: &efi::Guid) -> Option<&'static dyn CapsuleProcessor> {
+        find(&self.processors.lock(), guid)
+    }
+
+    /// Returns true if a processor is registered for `guid`.
+    pub(crate) fn supports(&self, guid: &efi::Guid) -> bool {
+        self.processor(guid).is_some()
+    }
+
+    /// Processes `capsule` with the processor registered for its GUID.
+    ///
+    /// Returns [EfiError::Unsupported] if there is none.
+    pub(crate) fn process(&self, capsule: &CapsuleImage<'_>) -> Result<(), EfiError> {
+        // The lock is released before the processor runs, so it may register processors itself.
+        let processor = self.processor(&capsule.guid()).ok_or(EfiError::Unsupported)?;
+        processor.process(capsule)
+    }
+
+    /// Processes `capsule` now if a processor is registered for it, or once one is.
+    pub(crate) fn queue(&self, capsule: CapsuleImage<'static>) {
+        if self.supports(&capsule.guid()) {
+            self.process_logged(&capsule);
+        } else {
+            self.pending.lock().push(capsule);
+        }
+    }
+
+    pub(crate) fn register_processor(
+        &self,
+        guid: efi::Guid,
+        processor: &'static dyn CapsuleProcessor,
+    ) -> Result<(), EfiError> {
+        if self.at_runtime() {
+            return Err(EfiError::Unsupported);
+        }
+        {
+            let mut processors = self.processors.lock();
+            if find(&processors, &guid).is_some() {
+                return Err(EfiError::AlreadyStarted);
+            }
+            processors.push((guid, processor))?;
+        }
+
+        let mut waiting = Vec::new();
+        self.pending.lock().retain(|capsule| {
+            let matches = capsule.guid() == guid;
+            if matches {
+                waiting.push(*capsule);
+            }
+            !matches
+        });
+        for capsule in &waiting {
+            self.process_logged(capsule);
+        }
+        Ok(())
+    }
+
+    fn process_logged(&self, capsule: &CapsuleImage<'_>) {
+        match self.process(capsule) {
+            Ok(()) => log::info!(target: "capsule", "Processed capsule {:?}.", capsule.guid()),
+            Err(err) => log::error!(target: "capsule", "Failed to process capsule {:?}: {err:?}", capsule.guid()),
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{scatter_gather::test_capsule, service::MockCapsuleProcessor};
+    use alloc::boxed::Box;
+
+    const FMP: efi::Guid =
+        efi::Guid::from_fields(0x6dcbd5ed, 0xe82d, 0x4c44, 0xbd, 0xa1, &[0x71, 0x94, 0x19, 0x9a, 0xd9, 0x2a]);
+    const OTHER: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+
+    fn capsule(guid: efi::Guid, body: &[u8]) -> CapsuleImage<'static> {
+        CapsuleImage::parse(Box::leak(test_capsule(guid, 0, body).into_boxed_slice())).unwrap()
+    }
+
+    fn processor(guid: efi::Guid, times: usize) -> &'static MockCapsuleProcessor {
+        let mut processor = MockCapsuleProcessor::new();
+        processor.expect_process().withf(move |capsule| capsule.guid() == guid).times(times).returning(|_| Ok(()));
+        Box::leak(Box::new(processor))
+    }
+
+    #[test]
+    fn pending_capsules_run_when_their_processor_registers() {
+        let router = Router::new();
+        router.queue(capsule(FMP, &[1]));
+        router.queue(capsule(OTHER, &[2]));
+        router.queue(capsule(FMP, &[3]));
+
+        router.register_processor(FMP, processor(FMP, 2)).unwrap();
+        // Capsules queued after the registration run immediately.
+        router.register_processor(OTHER, processor(OTHER, 2)).unwrap();
+        router.queue(capsule(OTHER, &[4]));
+        assert!(router.pending.lock().is_empty());
+    }
+
+    #[test]
+    fn process_needs_a_processor() {
+        let router = Router::new();
+        assert!(!router.supports(&FMP));
+        assert_eq!(router.process(&capsule(FMP, &[])), Err(EfiError::Unsupported));
+
+        let mut failing = MockCapsuleProcessor::new();
+        failing.expect_process().returning(|_| Err(EfiError::VolumeCorrupted));
+        router.register_processor(FMP, Box::leak(Box::new(failing))).unwrap();
+        assert!(router.supports(&FMP));
+        assert_eq!(router.process(&capsule(FMP, &[])), Err(EfiError::VolumeCorrupted));
+    }
+
+    #[test]
+    fn registration_rules() {
+        let router = Router::new();
+        assert_eq!(router.register_processor(FMP, processor(FMP, 0)), Ok(()));
+        assert_eq!(router.register_processor(FMP, processor(FMP, 0)), Err(EfiError::AlreadyStarted));
+        for index in 1..MAX_PROCESSORS as u32 {
+            let guid = efi::Guid::from_fields(index, 0, 0, 0, 0, &[0; 6]);
+            assert_eq!(router.register_processor(guid, processor(guid, 0)), Ok(()));
+        }
+        assert_eq!(router.register_processor(OTHER, processor(OTHER, 0)), Err(EfiError::OutOfResources));
+        assert!(!router.supports(&OTHER));
+
+        router.exit_boot_services();
+        assert_eq!(router.register_processor(OTHER, processor(OTHER, 0)), Err(EfiError::Unsupported));
+    }
+}
@@ -0,0 +1,184 @@
+//! Scatter-Gather Lists
+//!
+//! `UpdateCapsule()` describes the memory of capsules that persist across a reset with a list of
+//! `EFI_CAPSULE_BLOCK_DESCRIPTOR` arrays. The pre-DXE phase of the next boot walks the same list to coalesce the
+//! capsules, so it is checked here, while the caller can still be told about a malformed list.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::{ptr, slice};
+
+use patina::error::EfiError;
+use r_efi::efi;
+
+use crate::service::CapsuleImage;
+
+/// Continuation pointers allowed in a row before the list is considered to loop.
+const MAX_CONSECUTIVE_CONTINUATIONS: usize = 16;
+
+/// Walks the scatter-gather list at `list` and gathers the data blocks it describes into one buffer.
+///
+/// # Errors
+///
+/// - [EfiError::InvalidParameter] if a descriptor is null or misaligned, a data block is null, the list loops
+///   without describing data, or the data blocks do not add up to exactly `expected` bytes.
+/// - [EfiError::OutOfResources] if the buffer cannot be allocated.
+///
+/// # Safety
+///
+/// Every descriptor and data block reachable from `list` must be readable at its physical address.
+pub(crate) unsafe fn coalesce(list: efi::PhysicalAddress, expected: usize) -> Result<Vec<u8>, EfiError> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(expected).map_err(|_| EfiError::OutOfResources)?;
+
+    let mut descriptor = list;
+    let mut continuations = 0;
+    loop {
+        if descriptor == 0 || !descriptor.is_multiple_of(align_of::<efi::CapsuleBlockDescriptor>() as u64) {
+            return Err(EfiError::InvalidParameter);
+        }
+        // SAFETY: The descriptor is non-null and aligned, and readable per the caller contract.
+        let block = unsafe { ptr::read(descriptor as *const efi::CapsuleBlockDescriptor) };
+        // SAFETY: Both members of the union are physical addresses.
+        let address = unsafe { block.data.data_block };
+
+        match (block.length, address) {
+            (0, 0) => break,
+            (0, next) => {
+                continuations += 1;
+                if continuations > MAX_CONSECUTIVE_CONTINUATIONS {
+                    return Err(EfiError::InvalidParameter);
+                }
+                descriptor = next;
+            }
+            (length, address) => {
+                if address == 0 || length > (expected - data.len()) as u64 {
+                    return Err(EfiError::InvalidParameter);
+                }
+                // SAFETY: The data block is non-null and readable per the caller contract.
+                data.extend_from_slice(unsafe { slice::from_raw_parts(address as *const u8, length as usize) });
+                continuations = 0;
+                descriptor += size_of::<efi::CapsuleBlockDescriptor>() as u64;
+            }
+        }
+    }
+
+    if data.len() != expected {
+        return Err(EfiError::InvalidParameter);
+    }
+    Ok(data)
+}
+
+/// Splits back-to-back capsules, as found in coalesced capsule memory.
+///
+/// # Errors
+///
+/// - [EfiError::InvalidParameter] if a capsule is malformed or truncated.
+pub(crate) fn split(mut bytes: &[u8]) -> Result<Vec<CapsuleImage<'_>>, EfiError> {
+    let mut capsules = Vec::new();
+    while !bytes.is_empty() {
+        let capsule = CapsuleImage::parse(bytes)?;
+        bytes = &bytes[capsule.image().len()..];
+        capsules.push(capsule);
+    }
+    Ok(capsules)
+}
+
+/// Builds the bytes of a capsule with `guid`, `flags` and `body`.
+#[cfg(test)]
+pub(crate) fn test_capsule(guid: efi::Guid, flags: u32, body: &[u8]) -> Vec<u8> {
+    let header_size = size_of::<efi::CapsuleHeader>() as u32;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(guid.as_bytes());
+    bytes.extend_from_slice(&header_size.to_le_bytes());
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    bytes.extend_from_slice(&(header_size + body.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+/// Returns a descriptor for `length` bytes at `address`, or a continuation or terminator if `length` is 0.
+#[cfg(test)]
+pub(crate) fn test_descriptor(length: u64, address: u64) -> efi::CapsuleBlockDescriptor {
+    efi::CapsuleBlockDescriptor { length, data: efi::CapsuleBlockDescriptorUnion { data_block: address } }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const GUID: efi::Guid = efi::Guid::from_fields(0x1234, 0x5678, 0x9abc, 0xde, 0xf0, &[1, 2, 3, 4, 5, 6]);
+
+    fn address<T>(value: &[T]) -> u64 {
+        value.as_ptr() as u64
+    }
+
+    #[test]
+    fn coalesce_follows_continuations() {
+        let mut capsules = test_capsule(GUID, 0, &[0xAA; 20]);
+        capsules.extend(test_capsule(GUID, 0, &[0xBB; 3]));
+        let (first, rest) = capsules.split_at(10);
+        let (second, third) = rest.split_at(30);
+
+        let tail = [test_descriptor(third.len() as u64, address(third)), test_descriptor(0, 0)];
+        let head = [
+            test_descriptor(first.len() as u64, address(first)),
+            test_descriptor(second.len() as u64, address(second)),
+            test_descriptor(0, address(&tail)),
+        ];
+
+        // SAFETY: The descriptors and data blocks are live locals.
+        let data = unsafe { coalesce(address(&head), capsules.len()) }.unwrap();
+        assert_eq!(data, capsules);
+
+        let split = split(&data).unwrap();
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].body(), [0xAA; 20]);
+        assert_eq!(split[1].body(), [0xBB; 3]);
+    }
+
+    #[test]
+    fn malformed_lists_are_rejected() {
+        let capsule = test_capsule(GUID, 0, &[0; 4]);
+        let len = capsule.len() as u64;
+
+        let check = |list: &[efi::CapsuleBlockDescriptor], expected: usize| {
+            // SAFETY: The descriptors and data blocks are live locals.
+            unsafe { coalesce(address(list), expected) }.unwrap_err()
+        };
+
+        // More or less data than the capsules need.
+        assert_eq!(
+            check(&[test_descriptor(len, address(&capsule)), test_descriptor(0, 0)], 16),
+            EfiError::InvalidParameter
+        );
+        assert_eq!(
+            check(&[test_descriptor(16, address(&capsule)), test_descriptor(0, 0)], 32),
+            EfiError::InvalidParameter
+        );
+        // A null data block and a misaligned continuation.
+        assert_eq!(check(&[test_descriptor(len, 0), test_descriptor(0, 0)], len as usize), EfiError::InvalidParameter);
+        assert_eq!(check(&[test_descriptor(0, address(&capsule) + 1)], len as usize), EfiError::InvalidParameter);
+
+        // A continuation that points back at itself.
+        let mut looping = [test_descriptor(0, 0)];
+        looping[0] = test_descriptor(0, address(&looping));
+        assert_eq!(check(&looping, len as usize), EfiError::InvalidParameter);
+
+        // SAFETY: A null list is rejected before it is read.
+        assert_eq!(unsafe { coalesce(0, 0) }.unwrap_err(), EfiError::InvalidParameter);
+    }
+
+    #[test]
+    fn split_rejects_truncated_capsules() {
+        let capsule = test_capsule(GUID, 0, &[0; 8]);
+        assert_eq!(split(&capsule[..capsule.len() - 1]).unwrap_err(), EfiError::InvalidParameter);
+        assert!(split(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Capsule Service Definitions
+//!
+//! Defines the [CapsuleProcessor] trait implemented by components that consume capsules of a given GUID, the
+//! [CapsuleRouter] service produced by the [CapsuleProvider] component to register them, and the
+//! [CapsulePersistence] service through which a platform keeps capsules across a reset.
+//!
+//! [CapsuleProvider]: crate::component::CapsuleProvider
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::ptr;
+
+use patina::error::EfiError;
+use r_efi::efi;
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// A capsule: an `EFI_CAPSULE_HEADER` followed by its payload.
+#[derive(Debug, Clone, Copy)]
+pub struct CapsuleImage<'a> {
+    header: efi::CapsuleHeader,
+    image: &'a [u8],
+}
+
+impl<'a> CapsuleImage<'a> {
+    /// Parses the capsule at the start of `bytes`. Anything after `CapsuleImageSize` bytes is not part of it.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::InvalidParameter] if the header is truncated, its `HeaderSize` is smaller than the header
+    ///   structure or larger than the capsule, or `bytes` is shorter than `CapsuleImageSize`.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, EfiError> {
+        let header_bytes = bytes.get(..size_of::<efi::CapsuleHeader>()).ok_or(EfiError::InvalidParameter)?;
+        // SAFETY: header_bytes holds a full header, and every bit pattern is a valid header.
+        let header = unsafe { ptr::read_unaligned(header_bytes.as_ptr() as *const efi::CapsuleHeader) };
+        if (header.header_size as usize) < size_of::<efi::CapsuleHeader>()
+            || header.header_size > header.capsule_image_size
+        {
+            return Err(EfiError::InvalidParameter);
+        }
+        let image = bytes.get(..header.capsule_image_size as usize).ok_or(EfiError::InvalidParameter)?;
+        Ok(Self { header, image })
+    }
+
+    /// Returns the capsule header.
+    pub fn header(&self) -> &efi::CapsuleHeader {
+        &self.header
+    }
+
+    /// Returns the GUID that identifies the format of the capsule.
+    pub fn guid(&self) -> efi::Guid {
+        self.header.capsule_guid
+    }
+
+    /// Returns the `CAPSULE_FLAGS_*` of the capsule.
+    pub fn flags(&self) -> u32 {
+        self.header.flags
+    }
+
+    /// Returns the whole capsule, including its header.
+    pub fn image(&self) -> &'a [u8] {
+        self.image
+    }
+
+    /// Returns the payload that follows the header.
+    pub fn body(&self) -> &'a [u8] {
+        &self.image[self.header.header_size as usize..]
+    }
+}
+
+/// Consumes the capsules of one GUID, for example firmware management (FMP) capsules.
+///
+/// Registered through the [CapsuleRouter] service. Processors run while boot services are available, either from
+/// `UpdateCapsule()` or for the capsules that the pre-DXE phase handed over in capsule HOBs.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+#[allow(clippy::needless_lifetimes)] //https://github.com/rust-lang/rust-clippy/issues/6622
+pub trait CapsuleProcessor: Send + Sync {
+    /// Processes `capsule`, whose GUID is the one the processor was registered for.
+    fn process<'a>(&self, capsule: &CapsuleImage<'a>) -> Result<(), EfiError>;
+}
+
+/// Registration of capsule processors.
+///
+/// Produced by the [CapsuleProvider] component.
+///
+/// [CapsuleProvider]: crate::component::CapsuleProvider
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait CapsuleRouter {
+    /// Routes capsules with `guid` to `processor`.
+    ///
+    /// Capsules with `guid` that were handed over in capsule HOBs and have not been processed yet are processed
+    /// before this returns.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::AlreadyStarted] if a processor is already registered for `guid`.
+    /// - [EfiError::OutOfResources] if 16 processors are already registered.
+    /// - [EfiError::Unsupported] if called after `ExitBootServices()`.
+    fn register_processor(&self, guid: efi::Guid, processor: &'static dyn CapsuleProcessor) -> Result<(), EfiError>;
+}
+
+/// Keeps capsules in memory across a reset, so that the pre-DXE phase of the next boot can coalesce them.
+///
+/// Produced by the platform and optionally consumed by the [CapsuleProvider] component. Without it, capsules with
+/// `CAPSULE_FLAGS_PERSIST_ACROSS_RESET` are not supported. The implementation must remain callable after
+/// `ExitBootServices()`, so it must not use boot services or memory that is reclaimed by the OS.
+///
+/// [CapsuleProvider]: crate::component::CapsuleProvider
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait CapsulePersistence: Send + Sync {
+    /// Returns the largest total size, in bytes, of the capsules passed to one `UpdateCapsule()` call.
+    fn max_capsule_size(&self) -> u64;
+
+    /// Returns the reset the caller must request for the capsules to be processed. Warm by default.
+    fn reset_type(&self) -> efi::ResetType {
+        efi::RESET_WARM
+    }
+
+    /// Records the scatter-gather list that describes the capsules, for example in the `CapsuleUpdateData` variable.
+    ///
+    /// If `initiate_reset` is set, one of the capsules asked for the reset to be performed by the firmware, and the
+    /// implementation must not return after recording the list successfully.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::OutOfResources] or [EfiError::DeviceError] if the list could not be recorded.
+    fn persist(&self, scatter_gather_list: efi::PhysicalAddress, initiate_reset: bool) -> Result<(), EfiError>;
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn parse_checks_the_header_sizes() {
+        let guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+        let header = |header_size: u32, image_size: u32| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(guid.as_bytes());
+            bytes.extend_from_slice(&header_size.to_le_bytes());
+            bytes.extend_from_slice(&0u32.to_le_bytes());
+            bytes.extend_from_slice(&image_size.to_le_bytes());
+            bytes
+        };
+
+        let mut bytes = header(28, 32);
+        bytes.extend_from_slice(&[1, 2, 3, 4, 5, 6]);
+        let capsule = CapsuleImage::parse(&bytes).unwrap();
+        assert_eq!(capsule.guid(), guid);
+        assert_eq!(capsule.image().len(), 32);
+        assert_eq!(capsule.body(), [1, 2, 3, 4]);
+
+        assert_eq!(CapsuleImage::parse(&bytes[..27]).unwrap_err(), EfiError::InvalidParameter);
+        assert_eq!(CapsuleImage::parse(&bytes[..31]).unwrap_err(), EfiError::InvalidParameter);
+        assert_eq!(CapsuleImage::parse(&header(20, 32)).unwrap_err(), EfiError::InvalidParameter);
+        assert_eq!(CapsuleImage::parse(&header(40, 32)).unwrap_err(), EfiError::InvalidParameter);
+    }
+}
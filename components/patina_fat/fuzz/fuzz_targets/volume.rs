@@ -0,0 +1,16 @@
+//! Fuzzes the FAT volume with whole disk images.
+//!
+//! Mounts the image, reads every file and directory it can reach, then creates, changes and deletes files, which must
+//! report every inconsistency of the image as an error rather than panic or hang.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| patina_fat::fuzz::exercise(data));
@@ -0,0 +1,277 @@
+//! BIOS Parameter Block
+//!
+//! Parses the BIOS parameter block (BPB) in the boot sector of a FAT volume into the layout of the volume. As in the
+//! EDK II FAT driver, a volume is FAT32 when its BPB has no 16-bit FAT size, and otherwise FAT12 or FAT16 depending on
+//! its number of clusters, as the "Microsoft Extensible Firmware Initiative FAT32 File System Specification" requires.
+//!
+//! Boot sectors that do not start with a jump instruction or have an unusual sector size are not FAT volumes. Boot
+//! sectors that look like FAT but describe a layout that does not fit together, or does not fit on the device, are
+//! corrupt.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::error::{EfiError, Result};
+
+/// The size of the boot sector read to find the BPB.
+pub(crate) const BOOT_SECTOR_SIZE: usize = 512;
+
+/// FAT12 and FAT16 volumes have fewer clusters than these.
+const MAX_FAT12_CLUSTERS: u64 = 4085;
+const MAX_FAT16_CLUSTERS: u64 = 65525;
+/// Cluster numbers from 0x0FFFFFF7 on are reserved in a FAT32.
+const MAX_FAT32_CLUSTERS: u64 = 0x0FFF_FFF7 - 2;
+/// The largest cluster supported, as in most implementations.
+const MAX_CLUSTER_SIZE: u32 = 64 * 1024;
+
+/// The width of the entries of the FAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+/// Where the entries of the root directory are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RootDirectory {
+    /// The fixed region of FAT12 and FAT16 volumes, at a byte offset, with room for a number of entries.
+    Fixed { offset: u64, entries: u32 },
+    /// The cluster chain starting at this cluster, on FAT32 volumes.
+    Cluster(u32),
+}
+
+/// The layout of a FAT volume, with every offset and size in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Layout {
+    pub(crate) fat_type: FatType,
+    pub(crate) cluster_size: u32,
+    /// The offset of the first FAT.
+    pub(crate) fat_offset: u64,
+    /// The size of each FAT.
+    pub(crate) fat_size: u64,
+    pub(crate) fat_count: u32,
+    /// The only FAT in use on FAT32 volumes that do not mirror their FATs.
+    pub(crate) active_fat: Option<u32>,
+    pub(crate) root: RootDirectory,
+    /// The offset of cluster 2, the first cluster.
+    pub(crate) data_offset: u64,
+    pub(crate) cluster_count: u32,
+    /// The offset of the FSInfo sector of FAT32 volumes.
+    pub(crate) fs_info_offset: Option<u64>,
+}
+
+fn u16_at(boot_sector: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([boot_sector[offset], boot_sector[offset + 1]])
+}
+
+fn u32_at(boot_sector: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([boot_sector[offset], boot_sector[offset + 1], boot_sector[offset + 2], boot_sector[offset + 3]])
+}
+
+impl Layout {
+    /// Parses the BPB of `boot_sector`, for a volume on a device of `device_size` bytes.
+    ///
+    /// Fails with [EfiError::Unsupported] if the boot sector is not the one of a FAT volume, and with
+    /// [EfiError::VolumeCorrupted] if its BPB is inconsistent.
+    pub(crate) fn parse(boot_sector: &[u8; BOOT_SECTOR_SIZE], device_size: u64) -> Result<Self> {
+        let sector_size = u16_at(boot_sector, 11) as u32;
+        if !matches!(boot_sector[0], 0xEB | 0xE9) || !matches!(sector_size, 512 | 1024 | 2048 | 4096) {
+            return Err(EfiError::Unsupported);
+        }
+
+        let sectors_per_cluster = boot_sector[13] as u32;
+        let reserved_sectors = u16_at(boot_sector, 14) as u64;
+        let fat_count = boot_sector[16] as u32;
+        let root_entries = u16_at(boot_sector, 17) as u32;
+        let total_sectors = match u16_at(boot_sector, 19) {
+            0 => u32_at(boot_sector, 32) as u64,
+            sectors => sectors as u64,
+        };
+        let fat_sectors_16 = u16_at(boot_sector, 22) as u64;
+
+        let cluster_size = sector_size * sectors_per_cluster;
+        if !sectors_per_cluster.is_power_of_two() || cluster_size > MAX_CLUSTER_SIZE {
+            return Err(EfiError::VolumeCorrupted);
+        }
+        if reserved_sectors == 0 || fat_count == 0 || total_sectors == 0 {
+            return Err(EfiError::VolumeCorrupted);
+        }
+
+        let fat32 = fat_sectors_16 == 0;
+        let (fat_sectors, root_sectors) = if fat32 {
+            // The FAT32 BPB has no fixed root directory and must be of version 0.0.
+            if root_entries != 0 || u16_at(boot_sector, 42) != 0 {
+                return Err(EfiError::VolumeCorrupted);
+            }
+            (u32_at(boot_sector, 36) as u64, 0)
+        } else {
+            if root_entries == 0 {
+                return Err(EfiError::VolumeCorrupted);
+            }
+            (fat_sectors_16, (root_entries as u64 * 32).div_ceil(sector_size as u64))
+        };
+        if fat_sectors == 0 {
+            return Err(EfiError::VolumeCorrupted);
+        }
+
+        let metadata_sectors = reserved_sectors + fat_count as u64 * fat_sectors + root_sectors;
+        let cluster_count = total_sectors.saturating_sub(metadata_sectors) / sectors_per_cluster as u64;
+        let fat_type = match cluster_count {
+            0 => return Err(EfiError::VolumeCorrupted),
+            _ if fat32 => FatType::Fat32,
+            count if count < MAX_FAT12_CLUSTERS => FatType::Fat12,
+            count if count < MAX_FAT16_CLUSTERS => FatType::Fat16,
+            _ => return Err(EfiError::VolumeCorrupted),
+        };
+        if cluster_count > MAX_FAT32_CLUSTERS {
+            return Err(EfiError::VolumeCorrupted);
+        }
+
+        // The FATs must have an entry for each cluster, and the volume must fit on the device.
+        let fat_size = fat_sectors * sector_size as u64;
+        let entries = cluster_count + 2;
+        let needed_fat_size = match fat_type {
+            FatType::Fat12 => (entries * 3).div_ceil(2),
+            FatType::Fat16 => entries * 2,
+            FatType::Fat32 => entries * 4,
+        };
+        if fat_size < needed_fat_size || total_sectors * sector_size as u64 > device_size {
+            return Err(EfiError::VolumeCorrupted);
+        }
+
+        let fat_offset = reserved_sectors * sector_size as u64;
+        let root_offset = fat_offset + fat_count as u64 * fat_size;
+        let mut layout = Self {
+            fat_type,
+            cluster_size,
+            fat_offset,
+            fat_size,
+            fat_count,
+            active_fat: None,
+            root: RootDirectory::Fixed { offset: root_offset, entries: root_entries },
+            data_offset: root_offset + root_sectors * sector_size as u64,
+            cluster_count: cluster_count as u32,
+            fs_info_offset: None,
+        };
+
+        if fat_type == FatType::Fat32 {
+            let root_cluster = u32_at(boot_sector, 44);
+            if !layout.is_cluster(root_cluster) {
+                return Err(EfiError::VolumeCorrupted);
+            }
+            layout.root = RootDirectory::Cluster(root_cluster);
+
+            // Bit 7 of the extended flags turns mirroring off, leaving only the FAT its low bits select in use.
+            let extended_flags = u16_at(boot_sector, 40);
+            if extended_flags & 0x80 != 0 {
+                let active_fat = (extended_flags & 0x0F) as u32;
+                if active_fat >= fat_count {
+                    return Err(EfiError::VolumeCorrupted);
+                }
+                layout.active_fat = Some(active_fat);
+            }
+
+            let fs_info_sector = u16_at(boot_sector, 48) as u64;
+            if fs_info_sector != 0 && fs_info_sector < reserved_sectors {
+                layout.fs_info_offset = Some(fs_info_sector * sector_size as u64);
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Returns whether `cluster` is a cluster of the data region.
+    pub(crate) fn is_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < self.cluster_count
+    }
+
+    /// Returns the byte offset of `cluster`, which must be a cluster of the data region.
+    pub(crate) fn cluster_offset(&self, cluster: u32) -> u64 {
+        self.data_offset + (cluster - 2) as u64 * self.cluster_size as u64
+    }
+
+    /// Returns the size of the data region, the space available to files and directories.
+    pub(crate) fn data_size(&self) -> u64 {
+        self.cluster_count as u64 * self.cluster_size as u64
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE};
+
+    fn parse(image: &[u8]) -> Result<Layout> {
+        Layout::parse(image[..BOOT_SECTOR_SIZE].try_into().unwrap(), image.len() as u64)
+    }
+
+    #[test]
+    fn layouts_of_the_test_volumes_are_parsed() {
+        let fat12 = parse(FAT12_IMAGE).unwrap();
+        assert_eq!(fat12.fat_type, FatType::Fat12);
+        assert_eq!((fat12.fat_offset, fat12.fat_size, fat12.fat_count), (512, 1024, 2));
+        assert_eq!(fat12.root, RootDirectory::Fixed { offset: 5 * 512, entries: 64 });
+        assert_eq!((fat12.data_offset, fat12.cluster_count, fat12.cluster_size), (9 * 512, 503, 512));
+
+        let fat16 = parse(FAT16_IMAGE).unwrap();
+        assert_eq!(fat16.fat_type, FatType::Fat16);
+        assert_eq!((fat16.data_offset, fat16.cluster_count), (72 * 512, 4328));
+
+        let fat32 = parse(FAT32_IMAGE).unwrap();
+        assert_eq!(fat32.fat_type, FatType::Fat32);
+        assert_eq!(fat32.root, RootDirectory::Cluster(2));
+        assert_eq!((fat32.data_offset, fat32.cluster_count), (64 * 512, 1984));
+        assert_eq!(fat32.fs_info_offset, Some(512));
+        assert_eq!(fat32.active_fat, None);
+        assert_eq!(fat32.cluster_offset(3), 65 * 512);
+    }
+
+    #[test]
+    fn other_boot_sectors_are_not_fat() {
+        assert_eq!(parse(&[0; 4096]).unwrap_err(), EfiError::Unsupported);
+
+        let mut sector_size = FAT12_IMAGE.to_vec();
+        sector_size[11..13].copy_from_slice(&300u16.to_le_bytes());
+        assert_eq!(parse(&sector_size).unwrap_err(), EfiError::Unsupported);
+    }
+
+    #[test]
+    fn inconsistent_bpbs_are_corrupt() {
+        // More sectors than the device has.
+        assert_eq!(parse(&FAT16_IMAGE[..FAT16_IMAGE.len() / 2]).unwrap_err(), EfiError::VolumeCorrupted);
+
+        // A FAT too small for the clusters.
+        let mut small_fat = FAT16_IMAGE.to_vec();
+        small_fat[22..24].copy_from_slice(&8u16.to_le_bytes());
+        assert_eq!(parse(&small_fat).unwrap_err(), EfiError::VolumeCorrupted);
+
+        // A root directory cluster outside the volume.
+        let mut root_cluster = FAT32_IMAGE.to_vec();
+        root_cluster[44..48].copy_from_slice(&5000u32.to_le_bytes());
+        assert_eq!(parse(&root_cluster).unwrap_err(), EfiError::VolumeCorrupted);
+
+        // No sectors per cluster.
+        let mut no_cluster = FAT12_IMAGE.to_vec();
+        no_cluster[13] = 0;
+        assert_eq!(parse(&no_cluster).unwrap_err(), EfiError::VolumeCorrupted);
+
+        // Metadata larger than the volume.
+        let mut no_data = FAT12_IMAGE.to_vec();
+        no_data[14..16].copy_from_slice(&600u16.to_le_bytes());
+        assert_eq!(parse(&no_data).unwrap_err(), EfiError::VolumeCorrupted);
+    }
+
+    #[test]
+    fn fat32_without_mirroring_uses_its_active_fat() {
+        let mut image = FAT32_IMAGE.to_vec();
+        image[40..42].copy_from_slice(&0x81u16.to_le_bytes());
+        assert_eq!(parse(&image).unwrap().active_fat, Some(1));
+
+        image[40..42].copy_from_slice(&0x82u16.to_le_bytes());
+        assert_eq!(parse(&image).unwrap_err(), EfiError::VolumeCorrupted);
+    }
+}
@@ -0,0 +1,62 @@
+//! FAT Driver Component
+//!
+//! Installs the driver binding protocol of the FAT driver, through which the DXE core connects it to disks and
+//! partitions.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::{ptr, sync::atomic::Ordering};
+
+use patina::{
+    boot_services::StandardBootServices,
+    component::component,
+    driver_binding::UefiDriverBinding,
+    error::{EfiError, Result},
+    runtime_services::{RuntimeServices, StandardRuntimeServices},
+};
+use r_efi::efi;
+
+use crate::driver::{DRIVER_BINDING_HANDLE, FatDriverBinding};
+
+/// The runtime services the clock of the volumes reads the time from.
+static RUNTIME_SERVICES: spin::Once<StandardRuntimeServices> = spin::Once::new();
+
+fn clock() -> Option<efi::Time> {
+    RUNTIME_SERVICES.get()?.get_time().ok()
+}
+
+/// Produces the Simple File System protocol of the FAT12, FAT16 and FAT32 volumes connected to it.
+///
+/// The driver binding is installed on a handle of its own. File systems appear once the controllers of the disks
+/// and partitions are connected, for example when the boot manager connects every controller. Times recorded in
+/// directory entries come from the `GetTime()` runtime service.
+///
+/// ```rust,ignore
+/// commands.add_component(FatDriver);
+/// ```
+#[derive(Default)]
+pub struct FatDriver;
+
+#[component]
+impl FatDriver {
+    #[coverage(off)] // Component integration - the driver binding it installs is tested directly.
+    fn entry_point(self, boot_services: StandardBootServices, runtime_services: StandardRuntimeServices) -> Result<()> {
+        if !DRIVER_BINDING_HANDLE.load(Ordering::Acquire).is_null() {
+            return Err(EfiError::AlreadyStarted);
+        }
+        RUNTIME_SERVICES.call_once(|| runtime_services);
+
+        let boot_services: &'static StandardBootServices = Box::leak(Box::new(boot_services));
+        let mut driver_binding = UefiDriverBinding::new(FatDriverBinding { clock }, ptr::null_mut(), boot_services);
+        driver_binding.install().map_err(EfiError::from)?;
+        DRIVER_BINDING_HANDLE.store(driver_binding.driver_binding_handle(), Ordering::Release);
+
+        log::info!(target: "fat", "FAT driver installed.");
+        Ok(())
+    }
+}
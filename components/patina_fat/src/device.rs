@@ -0,0 +1,320 @@
+//! Volume Storage
+//!
+//! The storage a FAT volume lives on, addressed in bytes. On a platform it is a disk or partition, accessed through
+//! its Disk I/O protocol, which takes care of the block alignment of the Block I/O protocol that describes the media.
+//! Unit tests use disk images in memory instead.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{ffi::c_void, ptr::NonNull};
+
+use patina::error::{EfiError, Result};
+use r_efi::protocols::{block_io, disk_io};
+
+/// Byte addressed storage holding a volume.
+pub(crate) trait Device {
+    /// Reads `buffer.len()` bytes at byte `offset`.
+    fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<()>;
+
+    /// Writes `data` at byte `offset`.
+    fn write(&self, offset: u64, data: &[u8]) -> Result<()>;
+
+    /// Flushes the data written to the storage.
+    fn flush(&self) -> Result<()>;
+
+    /// Returns the size of the storage in bytes.
+    fn size(&self) -> u64;
+
+    /// Returns whether the storage can only be read.
+    fn is_read_only(&self) -> bool;
+}
+
+/// The Block I/O and Disk I/O protocols of a disk or partition.
+pub(crate) struct Disk {
+    block_io: NonNull<block_io::Protocol>,
+    disk_io: NonNull<disk_io::Protocol>,
+    /// The media the volume was found on. Accesses fail once it is replaced.
+    media_id: u32,
+}
+
+impl Disk {
+    /// Wraps the protocols of a disk, for the media currently in it.
+    ///
+    /// # Safety
+    ///
+    /// Both protocols must belong to the same device and stay valid, along with the media of the Block I/O protocol,
+    /// while the disk is used.
+    pub(crate) unsafe fn new(block_io: NonNull<block_io::Protocol>, disk_io: NonNull<disk_io::Protocol>) -> Self {
+        // SAFETY: The Block I/O protocol and its media are valid, per the caller.
+        let media_id = unsafe { (*block_io.as_ref().media).media_id };
+        Self { block_io, disk_io, media_id }
+    }
+
+    fn media(&self) -> block_io::Media {
+        // SAFETY: new requires the Block I/O protocol and its media to be valid.
+        unsafe { *self.block_io.as_ref().media }
+    }
+}
+
+impl Device for Disk {
+    fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<()> {
+        let disk_io = self.disk_io.as_ptr();
+        // SAFETY: new requires the Disk I/O protocol to be valid, and the buffer is valid for its length.
+        let status = unsafe {
+            ((*disk_io).read_disk)(disk_io, self.media_id, offset, buffer.len(), buffer.as_mut_ptr() as *mut c_void)
+        };
+        EfiError::status_to_result(status)
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let disk_io = self.disk_io.as_ptr();
+        // SAFETY: new requires the Disk I/O protocol to be valid, and the protocol only reads from the buffer.
+        let status = unsafe {
+            ((*disk_io).write_disk)(disk_io, self.media_id, offset, data.len(), data.as_ptr() as *mut c_void)
+        };
+        EfiError::status_to_result(status)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let block_io = self.block_io.as_ptr();
+        // SAFETY: new requires the Block I/O protocol to be valid.
+        EfiError::status_to_result(unsafe { ((*block_io).flush_blocks)(block_io) })
+    }
+
+    fn size(&self) -> u64 {
+        let media = self.media();
+        media.last_block.saturating_add(1).saturating_mul(media.block_size as u64)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.media().read_only
+    }
+}
+
+/// Disk images in memory, as storage and as Block I/O and Disk I/O protocols.
+#[cfg(test)]
+#[coverage(off)]
+pub(crate) mod mock {
+    use alloc::{boxed::Box, rc::Rc, vec::Vec};
+    use core::{cell::RefCell, ffi::c_void, mem, slice};
+
+    use patina::error::{EfiError, Result};
+    use r_efi::{
+        efi,
+        protocols::{block_io, disk_io},
+    };
+
+    use super::Device;
+
+    /// The FAT12 test image, described in `resources/test/volume/README.md`.
+    pub(crate) const FAT12_IMAGE: &[u8] = include_bytes!("../resources/test/volume/fat12.img");
+    /// The FAT16 test image, described in `resources/test/volume/README.md`.
+    pub(crate) const FAT16_IMAGE: &[u8] = include_bytes!("../resources/test/volume/fat16.img");
+    /// The FAT32 test image, described in `resources/test/volume/README.md`.
+    pub(crate) const FAT32_IMAGE: &[u8] = include_bytes!("../resources/test/volume/fat32.img");
+
+    /// The media ID of mock disks.
+    pub(crate) const MEDIA_ID: u32 = 3;
+
+    /// An image in memory, shared with the test that created it.
+    pub(crate) struct MemoryDisk {
+        pub(crate) data: Rc<RefCell<Vec<u8>>>,
+        pub(crate) read_only: bool,
+    }
+
+    impl MemoryDisk {
+        /// Returns storage holding `image`, and the content of the storage.
+        pub(crate) fn shared(image: &[u8]) -> (Box<dyn Device>, Rc<RefCell<Vec<u8>>>) {
+            let data = Rc::new(RefCell::new(image.to_vec()));
+            (Box::new(Self { data: data.clone(), read_only: false }), data)
+        }
+
+        /// Returns read only storage holding `image`.
+        pub(crate) fn read_only(image: &[u8]) -> Box<dyn Device> {
+            Box::new(Self { data: Rc::new(RefCell::new(image.to_vec())), read_only: true })
+        }
+
+        fn range(&self, offset: u64, size: usize) -> Result<core::ops::Range<usize>> {
+            let start = usize::try_from(offset).map_err(|_| EfiError::InvalidParameter)?;
+            match start.checked_add(size) {
+                Some(end) if end <= self.data.borrow().len() => Ok(start..end),
+                _ => Err(EfiError::InvalidParameter),
+            }
+        }
+    }
+
+    impl Device for MemoryDisk {
+        fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<()> {
+            let range = self.range(offset, buffer.len())?;
+            buffer.copy_from_slice(&self.data.borrow()[range]);
+            Ok(())
+        }
+
+        fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
+            if self.read_only {
+                return Err(EfiError::WriteProtected);
+            }
+            let range = self.range(offset, data.len())?;
+            self.data.borrow_mut()[range].copy_from_slice(data);
+            Ok(())
+        }
+
+        fn flush(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn size(&self) -> u64 {
+            self.data.borrow().len() as u64
+        }
+
+        fn is_read_only(&self) -> bool {
+            self.read_only
+        }
+    }
+
+    /// An image in memory served through Block I/O and Disk I/O protocols, with blocks of 512 bytes.
+    #[repr(C)]
+    pub(crate) struct MockDisk {
+        block_io: block_io::Protocol,
+        disk_io: disk_io::Protocol,
+        media: block_io::Media,
+        pub(crate) data: Vec<u8>,
+    }
+
+    impl MockDisk {
+        pub(crate) fn new(image: &[u8]) -> Box<Self> {
+            let mut disk = Box::new(Self {
+                block_io: block_io::Protocol {
+                    revision: block_io::REVISION,
+                    media: core::ptr::null(),
+                    reset,
+                    read_blocks,
+                    write_blocks,
+                    flush_blocks,
+                },
+                disk_io: disk_io::Protocol { revision: disk_io::REVISION, read_disk, write_disk },
+                media: block_io::Media {
+                    media_id: MEDIA_ID,
+                    removable_media: false,
+                    media_present: true,
+                    logical_partition: true,
+                    read_only: false,
+                    write_caching: false,
+                    block_size: 512,
+                    io_align: 0,
+                    last_block: (image.len() / 512 - 1) as u64,
+                    lowest_aligned_lba: 0,
+                    logical_blocks_per_physical_block: 0,
+                    optimal_transfer_length_granularity: 0,
+                },
+                data: image.to_vec(),
+            });
+            disk.block_io.media = &disk.media;
+            disk
+        }
+
+        pub(crate) fn media_mut(&mut self) -> &mut block_io::Media {
+            &mut self.media
+        }
+
+        pub(crate) fn block_io_ptr(&mut self) -> *mut block_io::Protocol {
+            &mut self.block_io
+        }
+
+        pub(crate) fn disk_io_ptr(&mut self) -> *mut disk_io::Protocol {
+            &mut self.disk_io
+        }
+
+        fn from_disk_io<'a>(this: *mut disk_io::Protocol) -> &'a mut Self {
+            // SAFETY: The Disk I/O protocol is a field of the disk.
+            unsafe { &mut *((this as *mut u8).sub(mem::offset_of!(Self, disk_io)) as *mut Self) }
+        }
+
+        fn range(
+            &self,
+            media_id: u32,
+            offset: u64,
+            size: usize,
+        ) -> core::result::Result<core::ops::Range<usize>, efi::Status> {
+            if media_id != self.media.media_id {
+                return Err(efi::Status::MEDIA_CHANGED);
+            }
+            let start = offset as usize;
+            match start.checked_add(size) {
+                Some(end) if end <= self.data.len() => Ok(start..end),
+                _ => Err(efi::Status::INVALID_PARAMETER),
+            }
+        }
+    }
+
+    extern "efiapi" fn reset(_this: *mut block_io::Protocol, _extended_verification: efi::Boolean) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_blocks(
+        _this: *mut block_io::Protocol,
+        _media_id: u32,
+        _lba: efi::Lba,
+        _size: usize,
+        _buffer: *mut c_void,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn write_blocks(
+        _this: *mut block_io::Protocol,
+        _media_id: u32,
+        _lba: efi::Lba,
+        _size: usize,
+        _buffer: *mut c_void,
+    ) -> efi::Status {
+        efi::Status::UNSUPPORTED
+    }
+
+    extern "efiapi" fn flush_blocks(_this: *mut block_io::Protocol) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_disk(
+        this: *mut disk_io::Protocol,
+        media_id: u32,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = MockDisk::from_disk_io(this);
+        match disk.range(media_id, offset, size) {
+            Ok(range) => {
+                // SAFETY: The caller provides a buffer of `size` bytes.
+                unsafe { slice::from_raw_parts_mut(buffer as *mut u8, size) }.copy_from_slice(&disk.data[range]);
+                efi::Status::SUCCESS
+            }
+            Err(status) => status,
+        }
+    }
+
+    extern "efiapi" fn write_disk(
+        this: *mut disk_io::Protocol,
+        media_id: u32,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = MockDisk::from_disk_io(this);
+        if disk.media.read_only {
+            return efi::Status::WRITE_PROTECTED;
+        }
+        match disk.range(media_id, offset, size) {
+            Ok(range) => {
+                // SAFETY: The caller provides a buffer of `size` bytes.
+                disk.data[range].copy_from_slice(unsafe { slice::from_raw_parts(buffer as *const u8, size) });
+                efi::Status::SUCCESS
+            }
+            Err(status) => status,
+        }
+    }
+}
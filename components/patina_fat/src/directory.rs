@@ -0,0 +1,427 @@
+//! Directory Entries
+//!
+//! The 32-byte entries directories are made of: short entries, which describe a file or directory, the long name
+//! entries that may precede them, and the volume label of the root directory. Long name entries are only used when
+//! their sequence is complete and their checksum matches the short entry that follows, as the specification requires;
+//! otherwise the file is known by its short name.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{string::String, vec, vec::Vec};
+
+use r_efi::efi;
+
+use crate::name::{self, checksum};
+
+/// The size of a directory entry.
+pub(crate) const ENTRY_SIZE: usize = 32;
+
+/// Attributes of short entries.
+pub(crate) const ATTRIBUTE_READ_ONLY: u8 = 0x01;
+pub(crate) const ATTRIBUTE_HIDDEN: u8 = 0x02;
+pub(crate) const ATTRIBUTE_SYSTEM: u8 = 0x04;
+pub(crate) const ATTRIBUTE_VOLUME_ID: u8 = 0x08;
+pub(crate) const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+pub(crate) const ATTRIBUTE_ARCHIVE: u8 = 0x20;
+/// The attributes that mark long name entries.
+const ATTRIBUTE_LONG_NAME: u8 = ATTRIBUTE_READ_ONLY | ATTRIBUTE_HIDDEN | ATTRIBUTE_SYSTEM | ATTRIBUTE_VOLUME_ID;
+
+/// The first byte of the entry that ends a directory.
+pub(crate) const END_OF_DIRECTORY: u8 = 0x00;
+/// The first byte of deleted entries.
+pub(crate) const DELETED: u8 = 0xE5;
+
+/// Set in the sequence number of the first long name entry, the one holding the end of the name.
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// The UTF-16 code units each long name entry holds, and where.
+const LONG_ENTRY_UNITS: usize = 13;
+const LONG_ENTRY_UNIT_OFFSETS: [usize; LONG_ENTRY_UNITS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+/// The most long name entries a name needs.
+const MAX_LONG_ENTRIES: usize = name::MAX_LONG_NAME.div_ceil(LONG_ENTRY_UNITS);
+
+/// A short entry, which describes a file or a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ShortEntry {
+    pub(crate) name: [u8; 11],
+    pub(crate) attributes: u8,
+    /// The case of the name, see [name::LOWER_CASE_BASE].
+    pub(crate) case: u8,
+    pub(crate) create_time_tenths: u8,
+    pub(crate) create_time: u16,
+    pub(crate) create_date: u16,
+    pub(crate) access_date: u16,
+    pub(crate) cluster_high: u16,
+    pub(crate) write_time: u16,
+    pub(crate) write_date: u16,
+    pub(crate) cluster_low: u16,
+    pub(crate) size: u32,
+}
+
+impl ShortEntry {
+    pub(crate) fn parse(raw: &[u8; ENTRY_SIZE]) -> Self {
+        let u16_at = |offset: usize| u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+        Self {
+            name: raw[..11].try_into().unwrap_or_default(),
+            attributes: raw[11],
+            case: raw[12],
+            create_time_tenths: raw[13],
+            create_time: u16_at(14),
+            create_date: u16_at(16),
+            access_date: u16_at(18),
+            cluster_high: u16_at(20),
+            write_time: u16_at(22),
+            write_date: u16_at(24),
+            cluster_low: u16_at(26),
+            size: u32::from_le_bytes([raw[28], raw[29], raw[30], raw[31]]),
+        }
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; ENTRY_SIZE] {
+        let mut raw = [0; ENTRY_SIZE];
+        raw[..11].copy_from_slice(&self.name);
+        raw[11] = self.attributes;
+        raw[12] = self.case;
+        raw[13] = self.create_time_tenths;
+        raw[14..16].copy_from_slice(&self.create_time.to_le_bytes());
+        raw[16..18].copy_from_slice(&self.create_date.to_le_bytes());
+        raw[18..20].copy_from_slice(&self.access_date.to_le_bytes());
+        raw[20..22].copy_from_slice(&self.cluster_high.to_le_bytes());
+        raw[22..24].copy_from_slice(&self.write_time.to_le_bytes());
+        raw[24..26].copy_from_slice(&self.write_date.to_le_bytes());
+        raw[26..28].copy_from_slice(&self.cluster_low.to_le_bytes());
+        raw[28..32].copy_from_slice(&self.size.to_le_bytes());
+        raw
+    }
+
+    /// Returns the first cluster of the file, or 0 if it has none. Only FAT32 volumes use the high 16 bits.
+    pub(crate) fn first_cluster(&self, fat32: bool) -> u32 {
+        let high = if fat32 { (self.cluster_high as u32) << 16 } else { 0 };
+        high | self.cluster_low as u32
+    }
+
+    pub(crate) fn set_first_cluster(&mut self, cluster: u32) {
+        self.cluster_high = (cluster >> 16) as u16;
+        self.cluster_low = cluster as u16;
+    }
+
+    pub(crate) fn is_directory(&self) -> bool {
+        self.attributes & ATTRIBUTE_DIRECTORY != 0
+    }
+
+    /// Returns whether this is the "." or ".." entry of a directory.
+    pub(crate) fn is_dot(&self) -> bool {
+        self.name[0] == b'.'
+    }
+}
+
+/// A file or directory found in a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Found {
+    /// The index of the first entry of the file, its first long name entry if it has a long name.
+    pub(crate) first: u32,
+    /// The index of the short entry.
+    pub(crate) index: u32,
+    /// The long name of the file, or its short name.
+    pub(crate) name: String,
+    pub(crate) entry: ShortEntry,
+}
+
+/// Returns the entry at `index` of the entries of a directory in `raw`.
+pub(crate) fn entry_at(raw: &[u8], index: u32) -> Option<&[u8; ENTRY_SIZE]> {
+    let start = (index as usize).checked_mul(ENTRY_SIZE)?;
+    raw.get(start..start.checked_add(ENTRY_SIZE)?)?.try_into().ok()
+}
+
+fn is_long_entry(entry: &[u8; ENTRY_SIZE]) -> bool {
+    entry[11] & 0x3F == ATTRIBUTE_LONG_NAME
+}
+
+/// A long name being collected from its entries, the last part of the name first.
+struct LongName {
+    first: u32,
+    checksum: u8,
+    /// The sequence number of the next entry expected.
+    next: u8,
+    units: Vec<u16>,
+}
+
+impl LongName {
+    fn start(index: u32, entry: &[u8; ENTRY_SIZE]) -> Option<Self> {
+        let count = (entry[0] & !LAST_LONG_ENTRY) as usize;
+        if entry[0] & LAST_LONG_ENTRY == 0 || count == 0 || count > MAX_LONG_ENTRIES {
+            return None;
+        }
+        let mut long_name =
+            Self { first: index, checksum: entry[13], next: count as u8, units: vec![0; count * LONG_ENTRY_UNITS] };
+        long_name.add(entry);
+        Some(long_name)
+    }
+
+    /// Adds the part of the name in `entry`, which must be the next entry expected.
+    fn add(&mut self, entry: &[u8; ENTRY_SIZE]) {
+        let start = (self.next as usize - 1) * LONG_ENTRY_UNITS;
+        for (unit, &offset) in self.units[start..start + LONG_ENTRY_UNITS].iter_mut().zip(&LONG_ENTRY_UNIT_OFFSETS) {
+            *unit = u16::from_le_bytes([entry[offset], entry[offset + 1]]);
+        }
+        self.next -= 1;
+    }
+
+    fn continues_with(&self, entry: &[u8; ENTRY_SIZE]) -> bool {
+        self.next > 0 && entry[0] == self.next && entry[13] == self.checksum
+    }
+
+    /// Returns the name if it is complete and belongs to `short_name`.
+    fn finish(self, short_name: &[u8; 11]) -> Option<String> {
+        if self.next != 0 || self.checksum != checksum(short_name) {
+            return None;
+        }
+        let length = self.units.iter().position(|&unit| unit == 0).unwrap_or(self.units.len());
+        let name = char::decode_utf16(self.units[..length].iter().copied())
+            .map(|character| character.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect::<String>();
+        (length > 0 && length <= name::MAX_LONG_NAME).then_some(name)
+    }
+}
+
+/// Returns the first file or directory described at or after entry `start` of the entries in `raw`, including the
+/// "." and ".." entries of subdirectories.
+pub(crate) fn next_file(raw: &[u8], start: u32) -> Option<Found> {
+    let mut long_name: Option<LongName> = None;
+    let mut index = start;
+    while let Some(entry) = entry_at(raw, index) {
+        match entry[0] {
+            END_OF_DIRECTORY => return None,
+            DELETED => long_name = None,
+            _ if is_long_entry(entry) => match long_name.as_mut() {
+                Some(name) if name.continues_with(entry) => name.add(entry),
+                _ => long_name = LongName::start(index, entry),
+            },
+            _ if entry[11] & ATTRIBUTE_VOLUME_ID != 0 => long_name = None,
+            _ => {
+                let entry = ShortEntry::parse(entry);
+                let (first, name) =
+                    match long_name.take().and_then(|long| Some((long.first, long.finish(&entry.name)?))) {
+                        Some((first, name)) => (first, name),
+                        None => (index, name::short_name_to_string(&entry.name, entry.case)),
+                    };
+                return Some(Found { first, index, name, entry });
+            }
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Returns the index and name of the volume label entry in the entries of a root directory.
+pub(crate) fn volume_label(raw: &[u8]) -> Option<(u32, [u8; 11])> {
+    let mut index = 0;
+    while let Some(entry) = entry_at(raw, index) {
+        match entry[0] {
+            END_OF_DIRECTORY => return None,
+            DELETED => {}
+            _ if !is_long_entry(entry) && entry[11] & ATTRIBUTE_VOLUME_ID != 0 => {
+                return Some((index, entry[..11].try_into().unwrap_or_default()));
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Returns the index of the first run of `count` unused entries in `raw`, which may extend past its end.
+///
+/// Entries are unused once deleted, and from the entry that ends the directory on.
+pub(crate) fn free_entries(raw: &[u8], count: u32) -> u32 {
+    let mut run_start = 0;
+    let mut index = 0;
+    while let Some(entry) = entry_at(raw, index) {
+        match entry[0] {
+            END_OF_DIRECTORY => return run_start,
+            DELETED if index + 1 - run_start == count => return run_start,
+            DELETED => {}
+            _ => run_start = index + 1,
+        }
+        index += 1;
+    }
+    run_start
+}
+
+/// Returns the long name entries of `name`, in the order they are stored in, for the short name `short_name`.
+pub(crate) fn long_entries(name: &str, short_name: &[u8; 11]) -> Vec<[u8; ENTRY_SIZE]> {
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    let count = units.len().div_ceil(LONG_ENTRY_UNITS);
+    // The name ends with a null unless it fills its entries, and is padded with 0xFFFF.
+    if !units.len().is_multiple_of(LONG_ENTRY_UNITS) {
+        units.push(0);
+    }
+    units.resize(count * LONG_ENTRY_UNITS, 0xFFFF);
+
+    let checksum = checksum(short_name);
+    (1..=count)
+        .rev()
+        .map(|sequence| {
+            let mut entry = [0; ENTRY_SIZE];
+            entry[0] = sequence as u8 | if sequence == count { LAST_LONG_ENTRY } else { 0 };
+            entry[11] = ATTRIBUTE_LONG_NAME;
+            entry[13] = checksum;
+            let part = &units[(sequence - 1) * LONG_ENTRY_UNITS..sequence * LONG_ENTRY_UNITS];
+            for (unit, &offset) in part.iter().zip(&LONG_ENTRY_UNIT_OFFSETS) {
+                entry[offset..offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Returns the time of a FAT date and time, with the hundredths of a second creation times have.
+pub(crate) fn to_efi_time(date: u16, time: u16, hundredths: u8) -> efi::Time {
+    efi::Time {
+        year: 1980 + (date >> 9),
+        month: ((date >> 5) & 0x0F) as u8,
+        day: (date & 0x1F) as u8,
+        hour: (time >> 11) as u8,
+        minute: ((time >> 5) & 0x3F) as u8,
+        second: ((time & 0x1F) * 2) as u8 + hundredths / 100,
+        nanosecond: (hundredths % 100) as u32 * 10_000_000,
+        timezone: efi::UNSPECIFIED_TIMEZONE,
+        ..Default::default()
+    }
+}
+
+/// Returns the FAT date, time and hundredths of a second of `time`, which must be a valid time.
+pub(crate) fn from_efi_time(time: &efi::Time) -> (u16, u16, u8) {
+    let year = time.year.clamp(1980, 2107) - 1980;
+    let date = (year << 9) | ((time.month as u16) << 5) | time.day as u16;
+    let fat_time = ((time.hour as u16) << 11) | ((time.minute as u16) << 5) | (time.second as u16 / 2);
+    let hundredths = (time.second % 2) * 100 + (time.nanosecond / 10_000_000).min(99) as u8;
+    (date, fat_time, hundredths)
+}
+
+/// Returns whether `time` is a valid time a FAT entry can hold.
+pub(crate) fn is_valid_time(time: &efi::Time) -> bool {
+    (1980..=2107).contains(&time.year)
+        && (1..=12).contains(&time.month)
+        && (1..=31).contains(&time.day)
+        && time.hour < 24
+        && time.minute < 60
+        && time.second < 60
+        && time.nanosecond < 1_000_000_000
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{FAT12_IMAGE, FAT32_IMAGE};
+
+    /// The entries of the root directory of the FAT12 test image.
+    fn fat12_root() -> &'static [u8] {
+        &FAT12_IMAGE[5 * 512..9 * 512]
+    }
+
+    fn files(raw: &[u8]) -> Vec<Found> {
+        let mut files = Vec::new();
+        let mut index = 0;
+        while let Some(found) = next_file(raw, index) {
+            index = found.index + 1;
+            files.push(found);
+        }
+        files
+    }
+
+    #[test]
+    fn long_and_short_names_are_found() {
+        let files = files(fat12_root());
+        let names: Vec<&str> = files.iter().map(|found| found.name.as_str()).collect();
+        assert_eq!(names, ["README.TXT", "notes.txt", "Long File Name.txt", "EFI", "Documents"]);
+
+        let long = &files[2];
+        assert_eq!((long.first, long.index), (3, 5));
+        assert_eq!(&long.entry.name, b"LONGFI~1TXT");
+        assert_eq!((long.entry.first_cluster(false), long.entry.size), (40, 1500));
+        assert!(files[3].entry.is_directory());
+        assert_eq!(volume_label(fat12_root()), Some((0, *b"PATINA12   ")));
+    }
+
+    #[test]
+    fn broken_long_names_fall_back_to_the_short_name() {
+        // A wrong checksum in the second long name entry.
+        let mut checksum = fat12_root().to_vec();
+        checksum[4 * 32 + 13] ^= 1;
+        assert_eq!(files(&checksum)[2].name, "LONGFI~1.TXT");
+        assert_eq!(files(&checksum)[2].first, 5);
+
+        // A missing long name entry.
+        let mut missing = fat12_root().to_vec();
+        missing[4 * 32] = DELETED;
+        assert_eq!(files(&missing)[2].name, "LONGFI~1.TXT");
+
+        // Long name entries out of sequence.
+        let mut sequence = fat12_root().to_vec();
+        sequence[4 * 32] = 3;
+        assert_eq!(files(&sequence)[2].name, "LONGFI~1.TXT");
+
+        // A long name entry claiming more entries than any name needs.
+        let mut count = fat12_root().to_vec();
+        count[3 * 32] = LAST_LONG_ENTRY | 30;
+        assert_eq!(files(&count)[2].name, "LONGFI~1.TXT");
+    }
+
+    #[test]
+    fn long_entries_round_trip() {
+        for name in ["Long File Name.txt", "a very long file name that needs several directory entries.txt", "x"] {
+            let mut raw: Vec<u8> = long_entries(name, b"SHORT   TXT").concat();
+            let short = ShortEntry { name: *b"SHORT   TXT", ..Default::default() };
+            raw.extend(short.to_bytes());
+            let found = next_file(&raw, 0).unwrap();
+            assert_eq!((found.name.as_str(), found.first), (name, 0));
+        }
+        // Names filling their entries have no null.
+        let exact = long_entries("thirteen char", b"THIRTE~1   ");
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0][30..32], *b"r\0");
+    }
+
+    #[test]
+    fn free_entries_are_found() {
+        let mut raw = fat12_root().to_vec();
+        // The deleted entry of the test image, which the end of the directory follows.
+        assert_eq!(free_entries(&raw, 1), 9);
+        assert_eq!(free_entries(&raw, 3), 9);
+        raw[32] = DELETED;
+        assert_eq!(free_entries(&raw, 1), 1);
+        assert_eq!(free_entries(&raw, 2), 9);
+        // A full directory needs to grow.
+        let full = vec![b'A'; 4 * ENTRY_SIZE];
+        assert_eq!(free_entries(&full, 1), 4);
+    }
+
+    #[test]
+    fn fat32_entries_use_the_high_cluster_bits() {
+        let root = &FAT32_IMAGE[64 * 512..65 * 512];
+        let efi = next_file(root, 0).unwrap();
+        assert_eq!(efi.name, "EFI");
+        let mut entry = efi.entry;
+        entry.set_first_cluster(0x12345);
+        assert_eq!(entry.first_cluster(true), 0x12345);
+        assert_eq!(entry.first_cluster(false), 0x2345);
+        assert_eq!(ShortEntry::parse(&entry.to_bytes()), entry);
+    }
+
+    #[test]
+    fn times_are_converted() {
+        let time = to_efi_time(0x58CF, 0x6458, 155);
+        assert_eq!((time.year, time.month, time.day), (2024, 6, 15));
+        assert_eq!((time.hour, time.minute, time.second, time.nanosecond), (12, 34, 49, 550_000_000));
+
+        let (date, fat_time, hundredths) = from_efi_time(&time);
+        assert_eq!((date, fat_time, hundredths), (0x58CF, 0x6458, 155));
+        assert!(is_valid_time(&time));
+        assert!(!is_valid_time(&efi::Time::default()));
+    }
+}
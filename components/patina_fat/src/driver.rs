@@ -0,0 +1,280 @@
+//! FAT Driver Binding
+//!
+//! The UEFI driver model side of the FAT driver. It manages controllers that have a Block I/O and a Disk I/O
+//! protocol, opening the Disk I/O protocol `BY_DRIVER`, and installs a Simple File System protocol on the controller
+//! when it holds a FAT volume.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use patina::{boot_services::BootServices, driver_binding::DriverBinding};
+use r_efi::{
+    efi,
+    protocols::{block_io, device_path, disk_io, simple_file_system},
+};
+
+use crate::{
+    device::Disk,
+    file::FileSystem,
+    volume::{Clock, Volume},
+};
+
+/// The handle the driver binding protocol is installed on, the agent that opens the protocols of the controllers.
+pub(crate) static DRIVER_BINDING_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Produces the file systems of FAT volumes.
+pub(crate) struct FatDriverBinding {
+    /// Tells the time recorded in directory entries.
+    pub(crate) clock: Clock,
+}
+
+fn agent() -> efi::Handle {
+    DRIVER_BINDING_HANDLE.load(Ordering::Acquire)
+}
+
+/// Opens `protocol` on `controller` with the driver as the agent.
+fn open<T: BootServices>(
+    boot_services: &T,
+    controller: efi::Handle,
+    protocol: &efi::Guid,
+    attributes: u32,
+) -> Result<*mut c_void, efi::Status> {
+    // SAFETY: The interface is returned as a raw pointer, which the caller casts to the protocol it opened.
+    unsafe { boot_services.open_protocol_unchecked(controller, protocol, agent(), controller, attributes) }
+}
+
+impl DriverBinding for FatDriverBinding {
+    fn driver_binding_supported<T: BootServices + 'static>(
+        &self,
+        boot_services: &'static T,
+        controller: efi::Handle,
+        _remaining_device_path: Option<NonNull<device_path::Protocol>>,
+    ) -> Result<bool, efi::Status> {
+        open(boot_services, controller, &block_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?;
+        // Opening the Disk I/O protocol by driver fails if another driver, or this one, manages the controller.
+        open(boot_services, controller, &disk_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_BY_DRIVER)?;
+        boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), controller)?;
+        Ok(true)
+    }
+
+    fn driver_binding_start<T: BootServices + 'static>(
+        &mut self,
+        boot_services: &'static T,
+        controller: efi::Handle,
+        _remaining_device_path: Option<NonNull<device_path::Protocol>>,
+    ) -> Result<(), efi::Status> {
+        let block_io = open(boot_services, controller, &block_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?;
+        let disk_io = open(boot_services, controller, &disk_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_BY_DRIVER)?;
+        let close_disk_io = |status: efi::Status| {
+            let _ = boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), controller);
+            status
+        };
+
+        let (Some(block_io), Some(disk_io)) = (NonNull::new(block_io as _), NonNull::new(disk_io as _)) else {
+            return Err(close_disk_io(efi::Status::DEVICE_ERROR));
+        };
+        // SAFETY: Both protocols are installed on the controller, and stay valid while the Disk I/O protocol is
+        // opened by the driver, which is until the volume is abandoned.
+        let disk = unsafe { Disk::new(block_io, disk_io) };
+        let volume = Volume::mount(Box::new(disk), self.clock).map_err(|err| close_disk_io(err.into()))?;
+
+        let file_system = Box::into_raw(FileSystem::new(boot_services, volume));
+        // SAFETY: The interface is the protocol of the file system, which lives until the protocol is uninstalled.
+        let installed = unsafe {
+            boot_services.install_protocol_interface_unchecked(
+                Some(controller),
+                &simple_file_system::PROTOCOL_GUID,
+                (*file_system).protocol_ptr(),
+            )
+        };
+        if let Err(status) = installed {
+            // SAFETY: The file system was leaked above, and its protocol is not installed.
+            drop(unsafe { Box::from_raw(file_system) });
+            return Err(close_disk_io(status));
+        }
+        Ok(())
+    }
+
+    fn driver_binding_stop<T: BootServices + 'static>(
+        &mut self,
+        boot_services: &'static T,
+        controller: efi::Handle,
+        _number_of_children: usize,
+        _child_handle_buffer: Option<NonNull<efi::Handle>>,
+    ) -> Result<(), efi::Status> {
+        let protocol =
+            open(boot_services, controller, &simple_file_system::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?;
+        // SAFETY: The protocol was installed with this interface when the driver started.
+        unsafe {
+            boot_services.uninstall_protocol_interface_unchecked(
+                controller,
+                &simple_file_system::PROTOCOL_GUID,
+                protocol,
+            )
+        }?;
+        // SAFETY: The driver installed the protocol as part of a leaked file system, and just uninstalled it.
+        unsafe { FileSystem::<T>::abandon(protocol as *mut FileSystem<T>) }?;
+        boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), controller)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{FAT12_IMAGE, FAT32_IMAGE, MEDIA_ID, MockDisk};
+    use alloc::vec::Vec;
+    use patina::boot_services::{MockBootServices, tpl::Tpl};
+    use std::sync::{Arc, Mutex};
+
+    const CONTROLLER: efi::Handle = 0x1000 as efi::Handle;
+
+    /// The protocols installed on the controller, and whether its Disk I/O protocol is opened by the driver.
+    #[derive(Default)]
+    struct State {
+        installed: Vec<(efi::Guid, usize)>,
+        disk_io_opened: bool,
+    }
+
+    type Shared = Arc<Mutex<State>>;
+
+    fn clock() -> Option<efi::Time> {
+        None
+    }
+
+    /// Returns boot services serving `disk` on CONTROLLER, and recording the protocols installed.
+    fn boot_services(disk: &mut MockDisk, state: &Shared) -> &'static MockBootServices {
+        DRIVER_BINDING_HANDLE.store(0x10 as efi::Handle, Ordering::Release);
+        let block_io = disk.block_io_ptr() as usize;
+        let disk_io = disk.disk_io_ptr() as usize;
+
+        let mut boot_services = MockBootServices::new();
+        let shared = state.clone();
+        boot_services.expect_open_protocol_unchecked().returning(move |handle, protocol, _, _, attributes| {
+            assert_eq!(handle, CONTROLLER);
+            let mut state = shared.lock().unwrap();
+            let interface = match *protocol {
+                block_io::PROTOCOL_GUID => block_io,
+                disk_io::PROTOCOL_GUID if attributes == efi::OPEN_PROTOCOL_BY_DRIVER => {
+                    if state.disk_io_opened {
+                        return Err(efi::Status::ACCESS_DENIED);
+                    }
+                    state.disk_io_opened = true;
+                    disk_io
+                }
+                _ => state
+                    .installed
+                    .iter()
+                    .find(|(guid, _)| guid == protocol)
+                    .map(|(_, interface)| *interface)
+                    .ok_or(efi::Status::UNSUPPORTED)?,
+            };
+            Ok(interface as *mut c_void)
+        });
+        let shared = state.clone();
+        boot_services.expect_close_protocol().returning(move |_, protocol, _, _| {
+            assert_eq!(*protocol, disk_io::PROTOCOL_GUID);
+            shared.lock().unwrap().disk_io_opened = false;
+            Ok(())
+        });
+        let shared = state.clone();
+        boot_services.expect_install_protocol_interface_unchecked().returning(move |handle, protocol, interface| {
+            assert_eq!(handle, Some(CONTROLLER));
+            shared.lock().unwrap().installed.push((*protocol, interface as usize));
+            Ok(CONTROLLER)
+        });
+        let shared = state.clone();
+        boot_services.expect_uninstall_protocol_interface_unchecked().returning(move |_, protocol, _| {
+            shared.lock().unwrap().installed.retain(|(guid, _)| guid != protocol);
+            Ok(())
+        });
+        boot_services.expect_raise_tpl().returning(|_| Tpl::APPLICATION);
+        boot_services.expect_restore_tpl().returning(|_| ());
+        Box::leak(Box::new(boot_services))
+    }
+
+    #[test]
+    fn controllers_are_supported_once() {
+        let mut disk = MockDisk::new(FAT12_IMAGE);
+        let state = Shared::default();
+        let boot_services = boot_services(&mut disk, &state);
+        let mut driver = FatDriverBinding { clock };
+
+        assert_eq!(driver.driver_binding_supported(boot_services, CONTROLLER, None), Ok(true));
+        driver.driver_binding_start(boot_services, CONTROLLER, None).unwrap();
+        assert_eq!(driver.driver_binding_supported(boot_services, CONTROLLER, None), Err(efi::Status::ACCESS_DENIED));
+    }
+
+    #[test]
+    fn file_systems_are_installed_and_stopped() {
+        let mut disk = MockDisk::new(FAT32_IMAGE);
+        let state = Shared::default();
+        let boot_services = boot_services(&mut disk, &state);
+        let mut driver = FatDriverBinding { clock };
+
+        driver.driver_binding_start(boot_services, CONTROLLER, None).unwrap();
+        let (guid, interface) = state.lock().unwrap().installed[0];
+        assert_eq!(guid, simple_file_system::PROTOCOL_GUID);
+
+        // The file system serves the volume, and a file open when the driver stops fails until it is closed.
+        let protocol = interface as *mut simple_file_system::Protocol;
+        let mut root = ptr::null_mut();
+        // SAFETY: Test code - the installed protocol, with a place for the root directory.
+        assert_eq!(unsafe { ((*protocol).open_volume)(protocol, &mut root) }, efi::Status::SUCCESS);
+
+        driver.driver_binding_stop(boot_services, CONTROLLER, 0, None).unwrap();
+        assert!(state.lock().unwrap().installed.is_empty());
+        assert!(!state.lock().unwrap().disk_io_opened);
+        let mut position = 0;
+        // SAFETY: Test code - the root directory opened above.
+        unsafe {
+            assert_eq!(((*root).set_position)(root, 0), efi::Status::SUCCESS);
+            let mut size = 0;
+            assert_eq!(((*root).read)(root, &mut size, ptr::null_mut()), efi::Status::DEVICE_ERROR);
+            assert_eq!(((*root).get_position)(root, &mut position), efi::Status::UNSUPPORTED);
+            assert_eq!(((*root).close)(root), efi::Status::SUCCESS);
+        }
+    }
+
+    #[test]
+    fn other_controllers_are_released() {
+        let mut disk = MockDisk::new(&[0; 64 * 1024]);
+        let state = Shared::default();
+        let boot_services = boot_services(&mut disk, &state);
+
+        let result = FatDriverBinding { clock }.driver_binding_start(boot_services, CONTROLLER, None);
+        assert_eq!(result, Err(efi::Status::UNSUPPORTED));
+        assert!(state.lock().unwrap().installed.is_empty());
+        assert!(!state.lock().unwrap().disk_io_opened);
+    }
+
+    #[test]
+    fn changed_media_fails_the_volume() {
+        let mut disk = MockDisk::new(FAT12_IMAGE);
+        let state = Shared::default();
+        let boot_services = boot_services(&mut disk, &state);
+
+        FatDriverBinding { clock }.driver_binding_start(boot_services, CONTROLLER, None).unwrap();
+        disk.media_mut().media_id = MEDIA_ID + 1;
+        let interface = state.lock().unwrap().installed[0].1;
+        let protocol = interface as *mut simple_file_system::Protocol;
+        let mut root = ptr::null_mut();
+        // SAFETY: Test code - the installed protocol, with a place for the root directory.
+        unsafe {
+            assert_eq!(((*protocol).open_volume)(protocol, &mut root), efi::Status::SUCCESS);
+            let mut size = 0;
+            assert_eq!(((*root).read)(root, &mut size, ptr::null_mut()), efi::Status::MEDIA_CHANGED);
+            assert_eq!(((*root).close)(root), efi::Status::SUCCESS);
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! File Allocation Table
+//!
+//! Reads and updates the FAT of a volume through a small write-back cache, follows the cluster chains of files and
+//! directories, and allocates and frees clusters. Updates are written to every FAT, or to the only FAT in use on FAT32
+//! volumes that do not mirror their FATs.
+//!
+//! A chain is corrupt when it runs into a free, bad or reserved entry, or into a cluster outside the volume, and when
+//! it is longer than the volume has clusters, which is how loops are caught without remembering the clusters seen.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+
+use patina::error::{EfiError, Result};
+
+use crate::{
+    bpb::{FatType, Layout},
+    device::Device,
+};
+
+/// The size of the part of the FAT kept in memory.
+const WINDOW_SIZE: u64 = 4096;
+
+/// The meaning of an entry of the FAT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Entry {
+    /// The cluster is free.
+    Free,
+    /// The chain continues at this cluster, which is a cluster of the volume.
+    Next(u32),
+    /// The chain ends with the cluster.
+    End,
+    /// The cluster is bad, or the entry holds a reserved value or a cluster outside the volume.
+    Bad,
+}
+
+/// The FAT of a volume.
+pub(crate) struct Fat {
+    fat_type: FatType,
+    cluster_count: u32,
+    /// The byte offsets of the FATs updates are written to, the first of which is the one read.
+    copies: Vec<u64>,
+    size: u64,
+    /// The cached part of the FAT, and its offset within the FAT.
+    window: Vec<u8>,
+    window_offset: Option<u64>,
+    dirty: bool,
+    /// Where the search for a free cluster starts.
+    next_free: u32,
+    /// The number of free clusters, once counted.
+    free_count: Option<u32>,
+}
+
+impl Fat {
+    pub(crate) fn new(layout: &Layout) -> Self {
+        let copies = match layout.active_fat {
+            Some(active) => vec![layout.fat_offset + active as u64 * layout.fat_size],
+            None => (0..layout.fat_count as u64).map(|index| layout.fat_offset + index * layout.fat_size).collect(),
+        };
+        Self {
+            fat_type: layout.fat_type,
+            cluster_count: layout.cluster_count,
+            copies,
+            size: layout.fat_size,
+            window: Vec::new(),
+            window_offset: None,
+            dirty: false,
+            next_free: 2,
+            free_count: None,
+        }
+    }
+
+    /// Returns whether `cluster` is a cluster of the volume.
+    pub(crate) fn is_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < self.cluster_count
+    }
+
+    /// Returns the cluster the search for free clusters starts at.
+    pub(crate) fn next_free(&self) -> u32 {
+        self.next_free
+    }
+
+    /// Sets the cluster the search for free clusters starts at, ignoring clusters outside the volume.
+    pub(crate) fn set_next_free(&mut self, cluster: u32) {
+        if self.is_cluster(cluster) {
+            self.next_free = cluster;
+        }
+    }
+
+    /// Makes the byte at `offset` of the FAT part of the window, and returns its index in the window.
+    fn load(&mut self, device: &dyn Device, offset: u64) -> Result<usize> {
+        let window_offset = offset - offset % WINDOW_SIZE;
+        if self.window_offset != Some(window_offset) {
+            self.flush(device)?;
+            let length = WINDOW_SIZE.min(self.size - window_offset) as usize;
+            let mut window = vec![0; length];
+            device.read(self.copies[0] + window_offset, &mut window)?;
+            self.window = window;
+            self.window_offset = Some(window_offset);
+        }
+        Ok((offset - window_offset) as usize)
+    }
+
+    fn read_byte(&mut self, device: &dyn Device, offset: u64) -> Result<u8> {
+        let index = self.load(device, offset)?;
+        Ok(self.window[index])
+    }
+
+    fn write_byte(&mut self, device: &dyn Device, offset: u64, value: u8) -> Result<()> {
+        let index = self.load(device, offset)?;
+        self.window[index] = value;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Returns the offset in the FAT of the entry of `cluster`, and its width in bytes.
+    fn entry_location(&self, cluster: u32) -> (u64, u64) {
+        match self.fat_type {
+            FatType::Fat12 => (cluster as u64 + cluster as u64 / 2, 2),
+            FatType::Fat16 => (cluster as u64 * 2, 2),
+            FatType::Fat32 => (cluster as u64 * 4, 4),
+        }
+    }
+
+    fn read_raw(&mut self, device: &dyn Device, cluster: u32) -> Result<u32> {
+        let (offset, width) = self.entry_location(cluster);
+        let mut value = 0;
+        for index in 0..width {
+            value |= (self.read_byte(device, offset + index)? as u32) << (index * 8);
+        }
+        Ok(match self.fat_type {
+            FatType::Fat12 if cluster & 1 == 1 => value >> 4,
+            FatType::Fat12 => value & 0xFFF,
+            FatType::Fat16 => value,
+            // The high 4 bits of FAT32 entries are reserved.
+            FatType::Fat32 => value & 0x0FFF_FFFF,
+        })
+    }
+
+    fn write_raw(&mut self, device: &dyn Device, cluster: u32, value: u32) -> Result<()> {
+        let (offset, width) = self.entry_location(cluster);
+        let mut current = 0;
+        for index in 0..width {
+            current |= (self.read_byte(device, offset + index)? as u32) << (index * 8);
+        }
+        let value = match self.fat_type {
+            FatType::Fat12 if cluster & 1 == 1 => (current & 0x000F) | (value << 4),
+            FatType::Fat12 => (current & 0xF000) | (value & 0xFFF),
+            FatType::Fat16 => value,
+            FatType::Fat32 => (current & 0xF000_0000) | (value & 0x0FFF_FFFF),
+        };
+        for index in 0..width {
+            self.write_byte(device, offset + index, (value >> (index * 8)) as u8)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the entry of `cluster`, a cluster of the volume.
+    pub(crate) fn get(&mut self, device: &dyn Device, cluster: u32) -> Result<Entry> {
+        let (bad, end) = match self.fat_type {
+            FatType::Fat12 => (0xFF7, 0xFF8),
+            FatType::Fat16 => (0xFFF7, 0xFFF8),
+            FatType::Fat32 => (0x0FFF_FFF7, 0x0FFF_FFF8),
+        };
+        Ok(match self.read_raw(device, cluster)? {
+            0 => Entry::Free,
+            value if value >= end => Entry::End,
+            value if value != bad && self.is_cluster(value) => Entry::Next(value),
+            _ => Entry::Bad,
+        })
+    }
+
+    /// Sets the entry of `cluster`, a cluster of the volume. Bad clusters cannot be marked.
+    pub(crate) fn set(&mut self, device: &dyn Device, cluster: u32, entry: Entry) -> Result<()> {
+        let value = match entry {
+            Entry::Free => 0,
+            Entry::Next(next) => next,
+            Entry::End => 0x0FFF_FFFF,
+            Entry::Bad => return Err(EfiError::InvalidParameter),
+        };
+        let value = match self.fat_type {
+            FatType::Fat12 => value & 0xFFF,
+            FatType::Fat16 => value & 0xFFFF,
+            FatType::Fat32 => value,
+        };
+        self.write_raw(device, cluster, value)
+    }
+
+    /// Returns up to `limit` clusters of the chain starting at `first`, or all of it if shorter.
+    ///
+    /// Fails with [EfiError::VolumeCorrupted] if the clusters read are not a valid chain.
+    pub(crate) fn chain(&mut self, device: &dyn Device, first: u32, limit: usize) -> Result<Vec<u32>> {
+        if !self.is_cluster(first) {
+            return Err(EfiError::VolumeCorrupted);
+        }
+        let mut chain = vec![first];
+        let mut cluster = first;
+        while chain.len() < limit {
+            match self.get(device, cluster)? {
+                Entry::End => break,
+                Entry::Next(_) if chain.len() >= self.cluster_count as usize => {
+                    log::error!(target: "fat", "Cluster chain at {first:#x} loops.");
+                    return Err(EfiError::VolumeCorrupted);
+                }
+                Entry::Next(next) => {
+                    chain.push(next);
+                    cluster = next;
+                }
+                entry => {
+                    log::error!(target: "fat", "Cluster chain at {first:#x} runs into {entry:?} cluster {cluster:#x}.");
+                    return Err(EfiError::VolumeCorrupted);
+                }
+            }
+        }
+        Ok(chain)
+    }
+
+    /// Allocates `count` clusters as a chain, appended to the chain ending with `last` if any.
+    ///
+    /// Fails with [EfiError::VolumeFull] if the volume has fewer free clusters, in which case nothing is allocated.
+    pub(crate) fn allocate(&mut self, device: &dyn Device, count: usize, last: Option<u32>) -> Result<Vec<u32>> {
+        let mut clusters = Vec::with_capacity(count);
+        let mut cluster = self.next_free;
+        for _ in 0..self.cluster_count {
+            if clusters.len() == count {
+                break;
+            }
+            if self.get(device, cluster)? == Entry::Free {
+                clusters.push(cluster);
+            }
+            cluster = if cluster - 2 + 1 >= self.cluster_count { 2 } else { cluster + 1 };
+        }
+        if clusters.len() < count {
+            return Err(EfiError::VolumeFull);
+        }
+
+        // Link the new clusters from the last one, so that the chain is only reachable once complete.
+        let mut next = Entry::End;
+        for &cluster in clusters.iter().rev() {
+            self.set(device, cluster, next)?;
+            next = Entry::Next(cluster);
+        }
+        if let (Some(last), Some(&first)) = (last, clusters.first()) {
+            self.set(device, last, Entry::Next(first))?;
+        }
+        self.next_free = cluster;
+        if let Some(free_count) = self.free_count.as_mut() {
+            *free_count = free_count.saturating_sub(count as u32);
+        }
+        Ok(clusters)
+    }
+
+    /// Frees `clusters`, which are clusters of the volume.
+    pub(crate) fn free(&mut self, device: &dyn Device, clusters: &[u32]) -> Result<()> {
+        for &cluster in clusters {
+            self.set(device, cluster, Entry::Free)?;
+            if let Some(free_count) = self.free_count.as_mut() {
+                *free_count += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of free clusters, counting them the first time.
+    pub(crate) fn free_count(&mut self, device: &dyn Device) -> Result<u32> {
+        if let Some(free_count) = self.free_count {
+            return Ok(free_count);
+        }
+        let mut free_count = 0;
+        for cluster in 2..self.cluster_count + 2 {
+            if self.get(device, cluster)? == Entry::Free {
+                free_count += 1;
+            }
+        }
+        self.free_count = Some(free_count);
+        Ok(free_count)
+    }
+
+    /// Returns the number of free clusters if they have been counted.
+    pub(crate) fn known_free_count(&self) -> Option<u32> {
+        self.free_count
+    }
+
+    /// Writes the cached part of the FAT back to the FATs if it changed.
+    pub(crate) fn flush(&mut self, device: &dyn Device) -> Result<()> {
+        if let (true, Some(window_offset)) = (self.dirty, self.window_offset) {
+            for &copy in &self.copies {
+                device.write(copy + window_offset, &self.window)?;
+            }
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        bpb::BOOT_SECTOR_SIZE,
+        device::mock::{FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE, MemoryDisk},
+    };
+    use alloc::boxed::Box;
+
+    fn open(image: &[u8]) -> (Fat, Box<dyn Device>, Layout) {
+        let layout = Layout::parse(image[..BOOT_SECTOR_SIZE].try_into().unwrap(), image.len() as u64).unwrap();
+        let (device, _) = MemoryDisk::shared(image);
+        (Fat::new(&layout), device, layout)
+    }
+
+    #[test]
+    fn chains_are_followed() {
+        // "Long File Name.txt" of the FAT12 volume is fragmented, and spans an odd and an even entry.
+        let (mut fat, device, _) = open(FAT12_IMAGE);
+        assert_eq!(fat.chain(device.as_ref(), 40, usize::MAX).unwrap(), [40, 41, 60]);
+        assert_eq!(fat.chain(device.as_ref(), 40, 2).unwrap(), [40, 41]);
+
+        let (mut fat, device, _) = open(FAT32_IMAGE);
+        let chain = fat.chain(device.as_ref(), 100, usize::MAX).unwrap();
+        assert_eq!(chain.len(), 16);
+        assert_eq!((chain[5], chain[6], chain[11], chain[15]), (105, 200, 150, 154));
+        assert_eq!(fat.get(device.as_ref(), 0x500).unwrap(), Entry::Free);
+    }
+
+    #[test]
+    fn corrupt_chains_are_rejected() {
+        let (mut fat, device, _) = open(FAT16_IMAGE);
+        let device = device.as_ref();
+
+        // A loop back to the start of the chain.
+        fat.set(device, 5, Entry::Next(3)).unwrap();
+        fat.set(device, 3, Entry::Next(4)).unwrap();
+        fat.set(device, 4, Entry::Next(5)).unwrap();
+        assert_eq!(fat.chain(device, 3, usize::MAX).unwrap_err(), EfiError::VolumeCorrupted);
+        // A free cluster in the chain.
+        fat.set(device, 4, Entry::Next(0x1000)).unwrap();
+        assert_eq!(fat.chain(device, 3, usize::MAX).unwrap_err(), EfiError::VolumeCorrupted);
+        // A cluster outside the volume, and a reserved value.
+        fat.write_raw(device, 4, 0xFF00).unwrap();
+        assert_eq!(fat.get(device, 4).unwrap(), Entry::Bad);
+        assert_eq!(fat.chain(device, 3, usize::MAX).unwrap_err(), EfiError::VolumeCorrupted);
+        fat.write_raw(device, 4, 1).unwrap();
+        assert_eq!(fat.chain(device, 3, usize::MAX).unwrap_err(), EfiError::VolumeCorrupted);
+        // A chain starting outside the volume.
+        assert_eq!(fat.chain(device, 0, usize::MAX).unwrap_err(), EfiError::VolumeCorrupted);
+    }
+
+    #[test]
+    fn clusters_are_allocated_and_freed_in_every_fat() {
+        for image in [FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE] {
+            let (mut fat, device, layout) = open(image);
+            let device = device.as_ref();
+            let free = fat.free_count(device).unwrap();
+
+            let first = fat.allocate(device, 3, None).unwrap();
+            let more = fat.allocate(device, 2, Some(first[2])).unwrap();
+            assert_eq!(fat.free_count(device).unwrap(), free - 5);
+            fat.flush(device).unwrap();
+
+            let chain = fat.chain(device, first[0], usize::MAX).unwrap();
+            assert_eq!(chain, [first.as_slice(), &more].concat());
+            // The second FAT holds the same chain.
+            let mut second = Fat { copies: vec![layout.fat_offset + layout.fat_size], ..Fat::new(&layout) };
+            assert_eq!(second.chain(device, first[0], usize::MAX).unwrap(), chain);
+
+            fat.free(device, &chain).unwrap();
+            fat.flush(device).unwrap();
+            let mut fresh = Fat::new(&layout);
+            assert_eq!(fresh.free_count(device).unwrap(), free);
+        }
+    }
+
+    #[test]
+    fn full_volumes_allocate_nothing() {
+        let (mut fat, device, _) = open(FAT12_IMAGE);
+        let device = device.as_ref();
+        let free = fat.free_count(device).unwrap() as usize;
+
+        assert_eq!(fat.allocate(device, free + 1, None).unwrap_err(), EfiError::VolumeFull);
+        assert_eq!(fat.free_count(device).unwrap() as usize, free);
+        let all = fat.allocate(device, free, None).unwrap();
+        assert_eq!(all.len(), free);
+        assert_eq!(fat.allocate(device, 1, None).unwrap_err(), EfiError::VolumeFull);
+    }
+
+    #[test]
+    fn fat32_entries_keep_their_reserved_bits() {
+        let (mut fat, device, _) = open(FAT32_IMAGE);
+        let device = device.as_ref();
+        fat.write_raw(device, 0x300, 0x0FFF_FFFF).unwrap();
+        fat.set(device, 0x300, Entry::Free).unwrap();
+        fat.flush(device).unwrap();
+
+        let (offset, _) = fat.entry_location(0x300);
+        let mut raw = [0; 4];
+        device.read(fat.copies[0] + offset, &mut raw).unwrap();
+        assert_eq!(u32::from_le_bytes(raw), 0);
+
+        // Set the reserved bits on the device, they survive a change of the entry.
+        device.write(fat.copies[0] + offset, &0xF000_0000u32.to_le_bytes()).unwrap();
+        let mut fat = Fat { window_offset: None, ..fat };
+        assert_eq!(fat.get(device, 0x300).unwrap(), Entry::Free);
+        fat.set(device, 0x300, Entry::End).unwrap();
+        fat.flush(device).unwrap();
+        device.read(fat.copies[0] + offset, &mut raw).unwrap();
+        assert_eq!(u32::from_le_bytes(raw), 0xFFFF_FFFF);
+    }
+}
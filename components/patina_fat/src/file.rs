@@ -0,0 +1,1051 @@
+//! Simple File System and File Protocols
+//!
+//! The `EFI_SIMPLE_FILE_SYSTEM_PROTOCOL` installed on each volume, and the `EFI_FILE_PROTOCOL` of every file and
+//! directory opened on it. Each protocol call runs at `TPL_CALLBACK` with the volume borrowed; a call made while the
+//! volume is in use, from a callback, fails with `EFI_ACCESS_DENIED`.
+//!
+//! The file system outlives the driver managing the volume: once stopped, the volume is flushed and dropped, the open
+//! files fail with `EFI_DEVICE_ERROR`, and the file system is freed when the last of them is closed.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    cell::{Cell, RefCell, RefMut},
+    ffi::c_void,
+    mem, ptr,
+    ptr::NonNull,
+    slice,
+};
+
+use patina::boot_services::{BootServices, tpl::Tpl};
+use r_efi::{
+    efi,
+    protocols::{file, simple_file_system},
+};
+
+use crate::{
+    directory::ATTRIBUTE_DIRECTORY,
+    volume::{Info, Node, Volume},
+};
+
+/// The open modes files can be opened with.
+const OPEN_MODES: [u64; 3] =
+    [file::MODE_READ, file::MODE_READ | file::MODE_WRITE, file::MODE_READ | file::MODE_WRITE | file::MODE_CREATE];
+
+/// The file system of a volume, installed as its Simple File System protocol.
+#[repr(C)]
+pub(crate) struct FileSystem<T: BootServices + 'static> {
+    // The protocol must be first, so that the protocol interface is also the file system.
+    protocol: simple_file_system::Protocol,
+    boot_services: &'static T,
+    /// The volume, until the driver stops managing it.
+    volume: RefCell<Option<Volume>>,
+    /// The number of open files, which keep the file system alive.
+    open_files: Cell<usize>,
+}
+
+/// Restores the TPL raised to lock a file system when dropped.
+struct RestoreTpl<'a, T: BootServices + 'static> {
+    boot_services: &'a T,
+    tpl: Tpl,
+}
+
+impl<T: BootServices + 'static> RestoreTpl<'_, T> {
+    fn raise(boot_services: &T) -> RestoreTpl<'_, T> {
+        RestoreTpl { boot_services, tpl: boot_services.raise_tpl(Tpl::CALLBACK) }
+    }
+}
+
+impl<T: BootServices + 'static> Drop for RestoreTpl<'_, T> {
+    fn drop(&mut self) {
+        self.boot_services.restore_tpl(self.tpl);
+    }
+}
+
+/// The volume of a file system, borrowed at `TPL_CALLBACK`.
+struct Locked<'a, T: BootServices + 'static> {
+    // Declared first, so that the volume is released before the TPL is restored.
+    volume: RefMut<'a, Volume>,
+    _tpl: RestoreTpl<'a, T>,
+}
+
+impl<T: BootServices + 'static> FileSystem<T> {
+    pub(crate) fn new(boot_services: &'static T, volume: Volume) -> Box<Self> {
+        Box::new(Self {
+            protocol: simple_file_system::Protocol {
+                revision: simple_file_system::REVISION,
+                open_volume: open_volume::<T>,
+            },
+            boot_services,
+            volume: RefCell::new(Some(volume)),
+            open_files: Cell::new(0),
+        })
+    }
+
+    /// Returns the file system whose protocol is `protocol`.
+    ///
+    /// # Safety
+    ///
+    /// `protocol` must be the Simple File System protocol of a live file system.
+    pub(crate) unsafe fn from_protocol<'a>(protocol: *mut simple_file_system::Protocol) -> &'a Self {
+        // SAFETY: The protocol is the first field of the file system, per the caller.
+        unsafe { &*(protocol as *const Self) }
+    }
+
+    pub(crate) fn protocol_ptr(&mut self) -> *mut c_void {
+        &mut self.protocol as *mut simple_file_system::Protocol as *mut c_void
+    }
+
+    /// Borrows the volume, failing with `EFI_ACCESS_DENIED` if it is in use and `EFI_DEVICE_ERROR` once the driver
+    /// stopped managing it.
+    fn lock(&self) -> Result<Locked<'_, T>, efi::Status> {
+        let tpl = RestoreTpl::raise(self.boot_services);
+        let volume = self.volume.try_borrow_mut().map_err(|_| efi::Status::ACCESS_DENIED)?;
+        let volume = RefMut::filter_map(volume, Option::as_mut).map_err(|_| efi::Status::DEVICE_ERROR)?;
+        Ok(Locked { volume, _tpl: tpl })
+    }
+
+    /// Stops using the volume of a file system whose protocol was uninstalled: the volume is flushed and dropped, and
+    /// the file system is freed now if no file is open, or when the last one is closed.
+    ///
+    /// # Safety
+    ///
+    /// `file_system` must be a leaked file system, whose protocol is not installed anymore.
+    pub(crate) unsafe fn abandon(file_system: *mut Self) -> Result<(), efi::Status> {
+        // SAFETY: The file system is live, per the caller.
+        let this = unsafe { &*file_system };
+        {
+            let _tpl = RestoreTpl::raise(this.boot_services);
+            let mut volume = this.volume.try_borrow_mut().map_err(|_| efi::Status::ACCESS_DENIED)?;
+            if let Some(Err(err)) = volume.as_mut().map(Volume::flush) {
+                log::warn!(target: "fat", "Failed to flush a volume no longer managed: {err:?}");
+            }
+            *volume = None;
+        }
+        if this.open_files.get() == 0 {
+            // SAFETY: The file system was leaked and nothing refers to it anymore.
+            drop(unsafe { Box::from_raw(file_system) });
+        }
+        Ok(())
+    }
+
+    /// Returns the protocol of a new open file of the file system.
+    fn open_file(&self, node: Node, directory: bool, writable: bool) -> *mut file::Protocol {
+        self.open_files.set(self.open_files.get() + 1);
+        let file = Box::new(File {
+            protocol: file_protocol::<T>(),
+            file_system: NonNull::from(self),
+            node,
+            position: 0,
+            directory,
+            writable,
+        });
+        Box::into_raw(file) as *mut file::Protocol
+    }
+
+    /// Records that a file of `file_system` was closed, freeing the file system if it was the last one and the volume
+    /// was abandoned.
+    ///
+    /// # Safety
+    ///
+    /// `file_system` must be the live, leaked file system of the file, which must not be used anymore.
+    unsafe fn close_file(file_system: NonNull<Self>) {
+        // SAFETY: The file system is live, per the caller.
+        let this = unsafe { file_system.as_ref() };
+        this.open_files.set(this.open_files.get() - 1);
+        if this.open_files.get() == 0 && this.volume.try_borrow().is_ok_and(|volume| volume.is_none()) {
+            // SAFETY: The volume was abandoned, so the protocol is uninstalled, and this was the last file.
+            drop(unsafe { Box::from_raw(file_system.as_ptr()) });
+        }
+    }
+}
+
+/// An open file or directory.
+#[repr(C)]
+struct File<T: BootServices + 'static> {
+    // The protocol must be first, so that the protocol interface is also the file.
+    protocol: file::Protocol,
+    file_system: NonNull<FileSystem<T>>,
+    node: Node,
+    /// The byte position in a file, or the entry position in a directory.
+    position: u64,
+    directory: bool,
+    writable: bool,
+}
+
+impl<T: BootServices + 'static> File<T> {
+    /// Returns the file whose protocol is `protocol`, failing with `EFI_INVALID_PARAMETER` if it is null.
+    ///
+    /// # Safety
+    ///
+    /// `protocol` must be null or the protocol of an open file.
+    unsafe fn from_protocol<'a>(protocol: *mut file::Protocol) -> Result<&'a mut Self, efi::Status> {
+        // SAFETY: The protocol is the first field of the file, per the caller.
+        unsafe { (protocol as *mut Self).as_mut() }.ok_or(efi::Status::INVALID_PARAMETER)
+    }
+
+    /// Returns the file system of the file, which is not part of it.
+    fn file_system<'a>(&self) -> &'a FileSystem<T> {
+        // SAFETY: The file system lives at least as long as its open files.
+        unsafe { self.file_system.as_ref() }
+    }
+}
+
+fn file_protocol<T: BootServices + 'static>() -> file::Protocol {
+    file::Protocol {
+        revision: file::REVISION,
+        open: open::<T>,
+        close: close::<T>,
+        delete: delete::<T>,
+        read: read::<T>,
+        write: write::<T>,
+        get_position: get_position::<T>,
+        set_position: set_position::<T>,
+        get_info: get_info::<T>,
+        set_info: set_info::<T>,
+        flush: flush::<T>,
+        open_ex,
+        read_ex,
+        write_ex,
+        flush_ex,
+    }
+}
+
+fn status(result: Result<(), efi::Status>) -> efi::Status {
+    result.err().unwrap_or(efi::Status::SUCCESS)
+}
+
+/// Returns the null-terminated UTF-16 string at `string`.
+///
+/// # Safety
+///
+/// `string` must point to a null-terminated UTF-16 string.
+unsafe fn string_from_ptr(string: *const efi::Char16) -> Result<String, efi::Status> {
+    // SAFETY: Every character up to the terminator is readable, per the caller.
+    let length = (0..).find(|&index| unsafe { *string.add(index) } == 0).unwrap_or_default();
+    // SAFETY: The first `length` characters were just read.
+    let units = unsafe { slice::from_raw_parts(string, length) };
+    char::decode_utf16(units.iter().copied()).collect::<Result<_, _>>().map_err(|_| efi::Status::INVALID_PARAMETER)
+}
+
+/// Returns the string held in `units`, up to its terminator.
+fn string_from_units(units: &[u16]) -> Result<String, efi::Status> {
+    let length = units.iter().position(|&unit| unit == 0).ok_or(efi::Status::INVALID_PARAMETER)?;
+    char::decode_utf16(units[..length].iter().copied())
+        .collect::<Result<_, _>>()
+        .map_err(|_| efi::Status::INVALID_PARAMETER)
+}
+
+/// Writes to `buffer` a structure ending with a null-terminated string: the first `string_offset` bytes of the header
+/// `header` returns for the total size, then `string`. Fails with `EFI_BUFFER_TOO_SMALL`, and the size needed in
+/// `buffer_size`, if the buffer is too small.
+///
+/// # Safety
+///
+/// `buffer_size` must be valid, and `buffer` valid for writes of `*buffer_size` bytes. `string_offset` must not be
+/// larger than the header.
+unsafe fn write_with_string<H>(
+    header: impl FnOnce(u64) -> H,
+    string_offset: usize,
+    string: &str,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> Result<(), efi::Status> {
+    let units: Vec<u16> = string.encode_utf16().chain([0]).collect();
+    let size = string_offset + units.len() * mem::size_of::<u16>();
+    // SAFETY: The buffer size is valid, per the caller.
+    let available = unsafe { mem::replace(&mut *buffer_size, size) };
+    if available < size {
+        return Err(efi::Status::BUFFER_TOO_SMALL);
+    }
+    if buffer.is_null() {
+        return Err(efi::Status::INVALID_PARAMETER);
+    }
+    let header = header(size as u64);
+    // SAFETY: The buffer holds `size` bytes, per the caller, and the header holds `string_offset` bytes. The buffer
+    // may be unaligned, so the string is copied as bytes.
+    unsafe {
+        ptr::copy_nonoverlapping(&header as *const H as *const u8, buffer as *mut u8, string_offset);
+        ptr::copy_nonoverlapping(
+            units.as_ptr() as *const u8,
+            (buffer as *mut u8).add(string_offset),
+            units.len() * mem::size_of::<u16>(),
+        );
+    }
+    Ok(())
+}
+
+/// Writes `info` to `buffer` as an `EFI_FILE_INFO`.
+///
+/// # Safety
+///
+/// As for [write_with_string].
+unsafe fn write_file_info(info: &Info, buffer_size: *mut usize, buffer: *mut c_void) -> Result<(), efi::Status> {
+    let header = |size| file::Info::<0> {
+        size,
+        file_size: info.size,
+        physical_size: info.physical_size,
+        create_time: info.create_time,
+        last_access_time: info.last_access_time,
+        modification_time: info.modification_time,
+        attribute: info.attributes as u64,
+        file_name: [],
+    };
+    // SAFETY: Per the caller, and the name follows the header.
+    unsafe { write_with_string(header, mem::offset_of!(file::Info<0>, file_name), &info.name, buffer_size, buffer) }
+}
+
+extern "efiapi" fn open_volume<T: BootServices + 'static>(
+    this: *mut simple_file_system::Protocol,
+    root: *mut *mut file::Protocol,
+) -> efi::Status {
+    if this.is_null() || root.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: The protocol is only installed as part of a file system.
+    let file_system = unsafe { FileSystem::<T>::from_protocol(this) };
+    let writable = match file_system.lock() {
+        Ok(locked) => !locked.volume.is_read_only(),
+        Err(status) => return status,
+    };
+    // SAFETY: The caller provides a place for the root directory.
+    unsafe { root.write(file_system.open_file(Node::Root, true, writable)) };
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn open<T: BootServices + 'static>(
+    this: *mut file::Protocol,
+    new_handle: *mut *mut file::Protocol,
+    file_name: *mut efi::Char16,
+    open_mode: u64,
+    attributes: u64,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if new_handle.is_null() || file_name.is_null() || !OPEN_MODES.contains(&open_mode) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let create = open_mode & file::MODE_CREATE != 0;
+    if create && attributes & !file::VALID_ATTR != 0 {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: The caller provides a null-terminated name.
+    let path = match unsafe { string_from_ptr(file_name) } {
+        Ok(path) => path,
+        Err(status) => return status,
+    };
+
+    let writable = open_mode & file::MODE_WRITE != 0;
+    let file_system = file.file_system();
+    let mut locked = match file_system.lock() {
+        Ok(locked) => locked,
+        Err(status) => return status,
+    };
+    let volume = &mut *locked.volume;
+    if writable && volume.is_read_only() {
+        return efi::Status::WRITE_PROTECTED;
+    }
+
+    let node = match volume.open(file.node, &path) {
+        Ok(node) => node,
+        Err(patina::error::EfiError::NotFound) if create => match volume.create(file.node, &path, attributes as u8) {
+            Ok(node) => node,
+            Err(err) => return err.into(),
+        },
+        Err(err) => return err.into(),
+    };
+    let info = match volume.info(node) {
+        Ok(info) => info,
+        Err(err) => return err.into(),
+    };
+    if writable && info.attributes & file::READ_ONLY as u8 != 0 && !create {
+        return efi::Status::ACCESS_DENIED;
+    }
+    let directory = info.attributes & ATTRIBUTE_DIRECTORY != 0;
+    drop(locked);
+
+    // SAFETY: The caller provides a place for the new file.
+    unsafe { new_handle.write(file_system.open_file(node, directory, writable)) };
+    efi::Status::SUCCESS
+}
+
+/// Frees a file after it is closed or deleted.
+fn release<T: BootServices + 'static>(file: &mut File<T>) {
+    let file_system = file.file_system;
+    // SAFETY: The file was leaked when it was opened and its caller does not use it anymore.
+    drop(unsafe { Box::from_raw(file as *mut File<T>) });
+    // SAFETY: The file system outlives its files, and this one was just freed.
+    unsafe { FileSystem::close_file(file_system) };
+}
+
+extern "efiapi" fn close<T: BootServices + 'static>(this: *mut file::Protocol) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if file.writable
+        && let Ok(mut locked) = file.file_system().lock()
+        && let Err(err) = locked.volume.flush()
+    {
+        log::warn!(target: "fat", "Failed to flush the volume of a closed file: {err:?}");
+    }
+    release(file);
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn delete<T: BootServices + 'static>(this: *mut file::Protocol) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    let deleted = file.writable
+        && file.file_system().lock().is_ok_and(|mut locked| {
+            let volume = &mut *locked.volume;
+            volume.delete(file.node).and_then(|()| volume.flush()).is_ok()
+        });
+    release(file);
+    if deleted { efi::Status::SUCCESS } else { efi::Status::WARN_DELETE_FAILURE }
+}
+
+extern "efiapi" fn read<T: BootServices + 'static>(
+    this: *mut file::Protocol,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if buffer_size.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: The caller provides the size of the buffer.
+    let size = unsafe { *buffer_size };
+    if buffer.is_null() && (size != 0 || !file.directory) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let mut locked = match file.file_system().lock() {
+        Ok(locked) => locked,
+        Err(status) => return status,
+    };
+
+    if file.directory {
+        let position = u32::try_from(file.position).unwrap_or(u32::MAX);
+        return match locked.volume.read_directory(file.node, position) {
+            Ok(Some((info, next))) => {
+                // SAFETY: The caller provides a buffer of `size` bytes.
+                let result = unsafe { write_file_info(&info, buffer_size, buffer) };
+                if result.is_ok() {
+                    file.position = next as u64;
+                }
+                status(result)
+            }
+            Ok(None) => {
+                // SAFETY: The caller provides the size of the buffer.
+                unsafe { buffer_size.write(0) };
+                efi::Status::SUCCESS
+            }
+            Err(err) => err.into(),
+        };
+    }
+
+    let data = match size {
+        0 => &mut [][..],
+        // SAFETY: The caller provides a buffer of `size` bytes.
+        _ => unsafe { slice::from_raw_parts_mut(buffer as *mut u8, size) },
+    };
+    match locked.volume.read(file.node, file.position, data) {
+        Ok(read) => {
+            file.position += read as u64;
+            // SAFETY: The caller provides the size of the buffer.
+            unsafe { buffer_size.write(read) };
+            efi::Status::SUCCESS
+        }
+        Err(err) => err.into(),
+    }
+}
+
+extern "efiapi" fn write<T: BootServices + 'static>(
+    this: *mut file::Protocol,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if buffer_size.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: The caller provides the size of the buffer.
+    let size = unsafe { *buffer_size };
+    if buffer.is_null() && size != 0 {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    if file.directory {
+        return efi::Status::UNSUPPORTED;
+    }
+    if !file.writable {
+        return efi::Status::ACCESS_DENIED;
+    }
+    let mut locked = match file.file_system().lock() {
+        Ok(locked) => locked,
+        Err(status) => return status,
+    };
+
+    let data = match size {
+        0 => &[][..],
+        // SAFETY: The caller provides a buffer of `size` bytes.
+        _ => unsafe { slice::from_raw_parts(buffer as *const u8, size) },
+    };
+    match locked.volume.write(file.node, file.position, data) {
+        Ok(()) => {
+            file.position += size as u64;
+            efi::Status::SUCCESS
+        }
+        Err(err) => {
+            // SAFETY: The caller provides the size of the buffer.
+            unsafe { buffer_size.write(0) };
+            err.into()
+        }
+    }
+}
+
+extern "efiapi" fn get_position<T: BootServices + 'static>(
+    this: *mut file::Protocol,
+    position: *mut u64,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if position.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    if file.directory {
+        return efi::Status::UNSUPPORTED;
+    }
+    // SAFETY: The caller provides a place for the position.
+    unsafe { position.write(file.position) };
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn set_position<T: BootServices + 'static>(this: *mut file::Protocol, position: u64) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    // Directories can only be read again from their start.
+    if file.directory {
+        if position != 0 {
+            return efi::Status::UNSUPPORTED;
+        }
+        file.position = 0;
+        return efi::Status::SUCCESS;
+    }
+    if position == u64::MAX {
+        let mut locked = match file.file_system().lock() {
+            Ok(locked) => locked,
+            Err(status) => return status,
+        };
+        match locked.volume.file_size(file.node) {
+            Ok(size) => file.position = size,
+            Err(err) => return err.into(),
+        }
+    } else {
+        file.position = position;
+    }
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn get_info<T: BootServices + 'static>(
+    this: *mut file::Protocol,
+    information_type: *mut efi::Guid,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if information_type.is_null() || buffer_size.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let mut locked = match file.file_system().lock() {
+        Ok(locked) => locked,
+        Err(status) => return status,
+    };
+    // SAFETY: The caller provides the information type, the size of the buffer and a buffer of that size.
+    status(unsafe { write_info(&mut locked.volume, file.node, *information_type, buffer_size, buffer) })
+}
+
+/// Writes the information of type `information_type` about `node` to `buffer`.
+///
+/// # Safety
+///
+/// As for [write_with_string].
+unsafe fn write_info(
+    volume: &mut Volume,
+    node: Node,
+    information_type: efi::Guid,
+    buffer_size: *mut usize,
+    buffer: *mut c_void,
+) -> Result<(), efi::Status> {
+    match information_type {
+        file::INFO_ID => {
+            let info = volume.info(node)?;
+            // SAFETY: Per the caller.
+            unsafe { write_file_info(&info, buffer_size, buffer) }
+        }
+        file::SYSTEM_INFO_ID => {
+            let label = volume.label()?;
+            let header = file::SystemInfo::<0> {
+                size: 0,
+                read_only: volume.is_read_only().into(),
+                volume_size: volume.size(),
+                free_space: volume.free_space()?,
+                block_size: volume.cluster_size(),
+                volume_label: [],
+            };
+            let offset = mem::offset_of!(file::SystemInfo<0>, volume_label);
+            // SAFETY: Per the caller, and the label follows the header.
+            unsafe {
+                write_with_string(|size| file::SystemInfo { size, ..header }, offset, &label, buffer_size, buffer)
+            }
+        }
+        file::SYSTEM_VOLUME_LABEL_ID => {
+            let label = volume.label()?;
+            let header = |_| file::SystemVolumeLabel::<0> { volume_label: [] };
+            // SAFETY: Per the caller, and the label is all there is.
+            unsafe { write_with_string(header, 0, &label, buffer_size, buffer) }
+        }
+        _ => Err(efi::Status::UNSUPPORTED),
+    }
+}
+
+extern "efiapi" fn set_info<T: BootServices + 'static>(
+    this: *mut file::Protocol,
+    information_type: *mut efi::Guid,
+    buffer_size: usize,
+    buffer: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if information_type.is_null() || buffer.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: The caller provides a buffer of `buffer_size` bytes, read as bytes as it may be unaligned.
+    let bytes = unsafe { slice::from_raw_parts(buffer as *const u8, buffer_size) };
+    // SAFETY: The caller provides the information type.
+    status(apply_info(file, unsafe { *information_type }, bytes))
+}
+
+/// Returns the string at byte `offset` of `bytes`, up to its terminator.
+fn string_at(bytes: &[u8], offset: usize) -> Result<String, efi::Status> {
+    let units: Vec<u16> = bytes
+        .get(offset..)
+        .ok_or(efi::Status::BAD_BUFFER_SIZE)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    string_from_units(&units)
+}
+
+/// Applies the information of type `information_type` in `bytes` to `file`.
+fn apply_info<T: BootServices + 'static>(
+    file: &mut File<T>,
+    information_type: efi::Guid,
+    bytes: &[u8],
+) -> Result<(), efi::Status> {
+    match information_type {
+        file::INFO_ID => {
+            let name_offset = mem::offset_of!(file::Info<0>, file_name);
+            if bytes.len() < name_offset {
+                return Err(efi::Status::BAD_BUFFER_SIZE);
+            }
+            // SAFETY: The buffer holds the header, checked above.
+            let header = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const file::Info<0>) };
+            if header.size > bytes.len() as u64 || header.size < name_offset as u64 {
+                return Err(efi::Status::BAD_BUFFER_SIZE);
+            }
+            if header.attribute & !file::VALID_ATTR != 0 {
+                return Err(efi::Status::INVALID_PARAMETER);
+            }
+            let name = string_at(&bytes[..header.size as usize], name_offset)?;
+            if !file.writable {
+                return Err(efi::Status::ACCESS_DENIED);
+            }
+            let info = Info {
+                name,
+                size: header.file_size,
+                physical_size: header.physical_size,
+                attributes: header.attribute as u8,
+                create_time: header.create_time,
+                last_access_time: header.last_access_time,
+                modification_time: header.modification_time,
+            };
+            let mut locked = file.file_system().lock()?;
+            file.node = locked.volume.set_info(file.node, &info)?;
+            Ok(())
+        }
+        file::SYSTEM_INFO_ID => {
+            let label = string_at(bytes, mem::offset_of!(file::SystemInfo<0>, volume_label))?;
+            Ok(file.file_system().lock()?.volume.set_label(&label)?)
+        }
+        file::SYSTEM_VOLUME_LABEL_ID => {
+            let label = string_at(bytes, 0)?;
+            Ok(file.file_system().lock()?.volume.set_label(&label)?)
+        }
+        _ => Err(efi::Status::UNSUPPORTED),
+    }
+}
+
+extern "efiapi" fn flush<T: BootServices + 'static>(this: *mut file::Protocol) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a file.
+    let file = match unsafe { File::<T>::from_protocol(this) } {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    if !file.writable {
+        return efi::Status::ACCESS_DENIED;
+    }
+    match file.file_system().lock() {
+        Ok(mut locked) => locked.volume.flush().map_or_else(Into::into, |()| efi::Status::SUCCESS),
+        Err(status) => status,
+    }
+}
+
+extern "efiapi" fn open_ex(
+    _this: *mut file::Protocol,
+    _new_handle: *mut *mut file::Protocol,
+    _file_name: *mut efi::Char16,
+    _open_mode: u64,
+    _attributes: u64,
+    _token: *mut file::IoToken,
+) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn read_ex(_this: *mut file::Protocol, _token: *mut file::IoToken) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn write_ex(_this: *mut file::Protocol, _token: *mut file::IoToken) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+extern "efiapi" fn flush_ex(_this: *mut file::Protocol, _token: *mut file::IoToken) -> efi::Status {
+    efi::Status::UNSUPPORTED
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE, MemoryDisk};
+    use patina::boot_services::MockBootServices;
+
+    fn clock() -> Option<efi::Time> {
+        None
+    }
+
+    fn boot_services() -> &'static MockBootServices {
+        let mut boot_services = MockBootServices::new();
+        boot_services.expect_raise_tpl().returning(|_| Tpl::APPLICATION);
+        boot_services.expect_restore_tpl().returning(|_| ());
+        Box::leak(Box::new(boot_services))
+    }
+
+    /// Opens the root directory of a file system serving `device`.
+    fn open_volume(device: Box<dyn crate::device::Device>) -> *mut file::Protocol {
+        let file_system = Box::leak(FileSystem::new(boot_services(), Volume::mount(device, clock).unwrap()));
+        let protocol = file_system.protocol_ptr() as *mut simple_file_system::Protocol;
+        let mut root = ptr::null_mut();
+        // SAFETY: Test code - the protocol of the file system, with a place for the root directory.
+        assert_eq!(unsafe { ((*protocol).open_volume)(protocol, &mut root) }, efi::Status::SUCCESS);
+        root
+    }
+
+    fn name(path: &str) -> Vec<u16> {
+        path.encode_utf16().chain([0]).collect()
+    }
+
+    fn open_file(
+        directory: *mut file::Protocol,
+        path: &str,
+        mode: u64,
+        attributes: u64,
+    ) -> Result<*mut file::Protocol, efi::Status> {
+        let mut file = ptr::null_mut();
+        let mut name = name(path);
+        // SAFETY: Test code - an open file, with a name and a place for the new file.
+        let status = unsafe { ((*directory).open)(directory, &mut file, name.as_mut_ptr(), mode, attributes) };
+        if status == efi::Status::SUCCESS { Ok(file) } else { Err(status) }
+    }
+
+    fn read(file: *mut file::Protocol, size: usize) -> Result<Vec<u8>, efi::Status> {
+        let mut buffer = vec![0u8; size];
+        let mut size = size;
+        // SAFETY: Test code - an open file, with a buffer of `size` bytes.
+        match unsafe { ((*file).read)(file, &mut size, buffer.as_mut_ptr() as *mut c_void) } {
+            efi::Status::SUCCESS => {
+                buffer.truncate(size);
+                Ok(buffer)
+            }
+            status => Err(status),
+        }
+    }
+
+    fn write(file: *mut file::Protocol, data: &[u8]) -> efi::Status {
+        let mut size = data.len();
+        // SAFETY: Test code - an open file, with a buffer of `size` bytes.
+        unsafe { ((*file).write)(file, &mut size, data.as_ptr() as *mut c_void) }
+    }
+
+    /// Returns the information of type `guid` of `file`, after checking the size it asks for.
+    fn get_info(file: *mut file::Protocol, guid: efi::Guid) -> Vec<u8> {
+        let mut guid = guid;
+        let mut size = 0;
+        // SAFETY: Test code - an open file, asked for the size of the information.
+        let status = unsafe { ((*file).get_info)(file, &mut guid, &mut size, ptr::null_mut()) };
+        assert_eq!(status, efi::Status::BUFFER_TOO_SMALL);
+        let mut buffer = vec![0u8; size];
+        // SAFETY: Test code - an open file, with a buffer of `size` bytes.
+        let status = unsafe { ((*file).get_info)(file, &mut guid, &mut size, buffer.as_mut_ptr() as *mut c_void) };
+        assert_eq!(status, efi::Status::SUCCESS);
+        assert_eq!(size, buffer.len());
+        buffer
+    }
+
+    fn set_info(file: *mut file::Protocol, guid: efi::Guid, mut buffer: Vec<u8>) -> efi::Status {
+        let mut guid = guid;
+        // SAFETY: Test code - an open file, with a buffer of information.
+        unsafe { ((*file).set_info)(file, &mut guid, buffer.len(), buffer.as_mut_ptr() as *mut c_void) }
+    }
+
+    /// Splits an `EFI_FILE_INFO` into its header and its name.
+    fn file_info(buffer: &[u8]) -> (file::Info<0>, String) {
+        // SAFETY: Test code - the buffer holds a file information header.
+        let header = unsafe { ptr::read_unaligned(buffer.as_ptr() as *const file::Info<0>) };
+        let name = string_from_units(
+            &buffer[mem::offset_of!(file::Info<0>, file_name)..]
+                .chunks_exact(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert_eq!(header.size as usize, buffer.len());
+        (header, name)
+    }
+
+    fn with_name(header: file::Info<0>, name: &str) -> Vec<u8> {
+        let offset = mem::offset_of!(file::Info<0>, file_name);
+        let units = self::name(name);
+        let size = offset + units.len() * 2;
+        let header = file::Info::<0> { size: size as u64, ..header };
+        // SAFETY: Test code - the header is plain data of at least `offset` bytes.
+        let mut buffer = unsafe { slice::from_raw_parts(&header as *const _ as *const u8, offset) }.to_vec();
+        buffer.extend(units.iter().flat_map(|unit| unit.to_le_bytes()));
+        buffer
+    }
+
+    fn close(file: *mut file::Protocol) {
+        // SAFETY: Test code - an open file.
+        assert_eq!(unsafe { ((*file).close)(file) }, efi::Status::SUCCESS);
+    }
+
+    #[test]
+    fn files_are_read_and_positioned() {
+        let root = open_volume(MemoryDisk::read_only(FAT12_IMAGE));
+        let file = open_file(root, "\\EFI\\BOOT\\BOOTX64.EFI", file::MODE_READ, 0).unwrap();
+        assert_eq!(&read(file, 100).unwrap()[..2], b"MZ");
+
+        let mut position = 0;
+        // SAFETY: Test code - an open file.
+        unsafe {
+            assert_eq!(((*file).get_position)(file, &mut position), efi::Status::SUCCESS);
+            assert_eq!(position, 100);
+            assert_eq!(((*file).set_position)(file, u64::MAX), efi::Status::SUCCESS);
+            assert_eq!(((*file).get_position)(file, &mut position), efi::Status::SUCCESS);
+            assert_eq!(position, 3000);
+            assert_eq!(read(file, 10).unwrap(), b"");
+            assert_eq!(((*file).set_position)(file, 3001), efi::Status::SUCCESS);
+            assert_eq!(read(file, 10).unwrap_err(), efi::Status::DEVICE_ERROR);
+            assert_eq!(((*file).flush)(file), efi::Status::ACCESS_DENIED);
+        }
+        assert_eq!(write(file, b"data"), efi::Status::ACCESS_DENIED);
+        close(file);
+
+        let readme = open_file(root, "README.TXT", file::MODE_READ, 0).unwrap();
+        assert_eq!(read(readme, 512).unwrap(), b"Patina FAT12 test volume.\r\n");
+        close(readme);
+        close(root);
+    }
+
+    #[test]
+    fn directories_are_read_as_file_info() {
+        let root = open_volume(MemoryDisk::read_only(FAT16_IMAGE));
+        let directory = open_file(root, "Many Files", file::MODE_READ, 0).unwrap();
+
+        let mut size = 10;
+        let mut buffer = [0u8; 10];
+        // SAFETY: Test code - an open directory, with a buffer too small for an entry.
+        let status = unsafe { ((*directory).read)(directory, &mut size, buffer.as_mut_ptr() as *mut c_void) };
+        assert_eq!(status, efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(size, mem::offset_of!(file::Info<0>, file_name) + 2 * 2);
+
+        let mut names = Vec::new();
+        loop {
+            let entry = read(directory, 512).unwrap();
+            if entry.is_empty() {
+                break;
+            }
+            let (header, name) = file_info(&entry);
+            assert_eq!(header.attribute & file::DIRECTORY != 0, name.starts_with('.'));
+            names.push(name);
+        }
+        assert_eq!(names.len(), 42);
+        assert_eq!((names[0].as_str(), names[41].as_str()), (".", "file39.txt"));
+
+        let mut position = 0;
+        // SAFETY: Test code - an open directory.
+        unsafe {
+            assert_eq!(((*directory).set_position)(directory, 1), efi::Status::UNSUPPORTED);
+            assert_eq!(((*directory).get_position)(directory, &mut position), efi::Status::UNSUPPORTED);
+            assert_eq!(((*directory).set_position)(directory, 0), efi::Status::SUCCESS);
+        }
+        assert_eq!(file_info(&read(directory, 512).unwrap()).1, ".");
+        assert_eq!(write(directory, b"data"), efi::Status::UNSUPPORTED);
+        close(directory);
+        close(root);
+    }
+
+    #[test]
+    fn open_modes_are_checked() {
+        let root = open_volume(MemoryDisk::shared(FAT12_IMAGE).0);
+        let create = file::MODE_READ | file::MODE_WRITE | file::MODE_CREATE;
+        assert_eq!(open_file(root, "README.TXT", file::MODE_WRITE, 0).unwrap_err(), efi::Status::INVALID_PARAMETER);
+        assert_eq!(
+            open_file(root, "new.txt", file::MODE_READ | file::MODE_CREATE, 0).unwrap_err(),
+            efi::Status::INVALID_PARAMETER
+        );
+        assert_eq!(open_file(root, "new.txt", create, 0x100).unwrap_err(), efi::Status::INVALID_PARAMETER);
+        assert_eq!(open_file(root, "new.txt", file::MODE_READ, 0).unwrap_err(), efi::Status::NOT_FOUND);
+
+        let file = open_file(root, "new.txt", create, file::READ_ONLY).unwrap();
+        assert_eq!(write(file, b"created read only"), efi::Status::SUCCESS);
+        close(file);
+        assert_eq!(
+            open_file(root, "new.txt", file::MODE_READ | file::MODE_WRITE, 0).unwrap_err(),
+            efi::Status::ACCESS_DENIED
+        );
+        let file = open_file(root, "new.txt", file::MODE_READ, 0).unwrap();
+        assert_eq!(read(file, 100).unwrap(), b"created read only");
+        close(file);
+        close(root);
+
+        let root = open_volume(MemoryDisk::read_only(FAT12_IMAGE));
+        assert_eq!(open_file(root, "README.TXT", create, 0).unwrap_err(), efi::Status::WRITE_PROTECTED);
+        close(root);
+    }
+
+    #[test]
+    fn files_are_written_renamed_and_deleted() {
+        let (device, data) = MemoryDisk::shared(FAT32_IMAGE);
+        let root = open_volume(device);
+        let create = file::MODE_READ | file::MODE_WRITE | file::MODE_CREATE;
+
+        let directory = open_file(root, "\\Logs", create, file::DIRECTORY).unwrap();
+        let file = open_file(directory, "boot log.txt", create, 0).unwrap();
+        assert_eq!(write(file, b"first line\r\n"), efi::Status::SUCCESS);
+        assert_eq!(write(file, b"second line\r\n"), efi::Status::SUCCESS);
+
+        let (header, name) = file_info(&get_info(file, file::INFO_ID));
+        assert_eq!((header.file_size, name.as_str()), (25, "boot log.txt"));
+        assert_eq!(header.attribute, file::ARCHIVE);
+        assert_eq!(set_info(file, file::INFO_ID, with_name(header, "\\Logs\\Renamed.log")), efi::Status::SUCCESS);
+        assert_eq!(file_info(&get_info(file, file::INFO_ID)).1, "Renamed.log");
+        assert_eq!(set_info(file, file::INFO_ID, with_name(header, "bad:name")), efi::Status::INVALID_PARAMETER);
+        let directory_attribute = file::Info::<0> { attribute: file::DIRECTORY, ..header };
+        assert_eq!(set_info(file, file::INFO_ID, with_name(directory_attribute, "x")), efi::Status::ACCESS_DENIED);
+        close(file);
+
+        // The content reached the device.
+        let mut volume = Volume::mount(MemoryDisk::shared(&data.borrow()).0, clock).unwrap();
+        let node = volume.open(Node::Root, "\\Logs\\Renamed.log").unwrap();
+        let mut buffer = [0; 64];
+        assert_eq!(volume.read(node, 0, &mut buffer).unwrap(), 25);
+
+        // SAFETY: Test code - an open directory, which is not empty.
+        assert_eq!(unsafe { ((*directory).delete)(directory) }, efi::Status::WARN_DELETE_FAILURE);
+        let file = open_file(root, "Logs\\Renamed.log", file::MODE_READ | file::MODE_WRITE, 0).unwrap();
+        // SAFETY: Test code - an open file.
+        assert_eq!(unsafe { ((*file).delete)(file) }, efi::Status::SUCCESS);
+        let directory = open_file(root, "Logs", file::MODE_READ | file::MODE_WRITE, 0).unwrap();
+        // SAFETY: Test code - an open directory, now empty.
+        assert_eq!(unsafe { ((*directory).delete)(directory) }, efi::Status::SUCCESS);
+        assert_eq!(open_file(root, "Logs", file::MODE_READ, 0).unwrap_err(), efi::Status::NOT_FOUND);
+
+        // SAFETY: Test code - the root directory, opened for writing.
+        assert_eq!(unsafe { ((*root).delete)(root) }, efi::Status::WARN_DELETE_FAILURE);
+    }
+
+    #[test]
+    fn system_info_and_volume_labels_are_reported_and_set() {
+        let root = open_volume(MemoryDisk::shared(FAT32_IMAGE).0);
+        let buffer = get_info(root, file::SYSTEM_INFO_ID);
+        let offset = mem::offset_of!(file::SystemInfo<0>, volume_label);
+        assert_eq!(buffer.len(), offset + 9 * 2);
+        // SAFETY: Test code - the buffer holds a file system information header.
+        let info = unsafe { ptr::read_unaligned(buffer.as_ptr() as *const file::SystemInfo<0>) };
+        assert_eq!((info.size as usize, bool::from(info.read_only), info.block_size), (buffer.len(), false, 512));
+        assert_eq!((info.volume_size, info.free_space), (1984 * 512, 1836 * 512));
+        assert_eq!(buffer[offset..offset + 4], [b'P', 0, b'A', 0]);
+
+        let label = get_info(root, file::SYSTEM_VOLUME_LABEL_ID);
+        assert_eq!(label, name("PATINA32").iter().flat_map(|unit| unit.to_le_bytes()).collect::<Vec<_>>());
+        let new_label = name("Boot").iter().flat_map(|unit| unit.to_le_bytes()).collect::<Vec<_>>();
+        assert_eq!(set_info(root, file::SYSTEM_VOLUME_LABEL_ID, new_label), efi::Status::SUCCESS);
+        assert_eq!(get_info(root, file::SYSTEM_VOLUME_LABEL_ID)[..8], [b'B', 0, b'O', 0, b'O', 0, b'T', 0]);
+
+        let mut system_info = buffer[..offset].to_vec();
+        system_info.extend(name("System").iter().flat_map(|unit| unit.to_le_bytes()));
+        assert_eq!(set_info(root, file::SYSTEM_INFO_ID, system_info), efi::Status::SUCCESS);
+        assert_eq!(get_info(root, file::SYSTEM_VOLUME_LABEL_ID)[..4], [b'S', 0, b'Y', 0]);
+
+        let unknown = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+        let mut guid = unknown;
+        let mut size = 0;
+        // SAFETY: Test code - the root directory, asked for an unknown information type.
+        let status = unsafe { ((*root).get_info)(root, &mut guid, &mut size, ptr::null_mut()) };
+        assert_eq!(status, efi::Status::UNSUPPORTED);
+        close(root);
+    }
+
+    #[test]
+    fn busy_volumes_deny_access() {
+        let root = open_volume(MemoryDisk::shared(FAT12_IMAGE).0);
+        // SAFETY: Test code - the root directory is open.
+        let file_system = unsafe { File::<MockBootServices>::from_protocol(root) }.unwrap().file_system();
+        let _locked = file_system.lock().unwrap();
+        assert_eq!(read(root, 512).unwrap_err(), efi::Status::ACCESS_DENIED);
+    }
+}
@@ -0,0 +1,122 @@
+//! Fuzzing Entry Point
+//!
+//! Drives a volume mounted from an arbitrary image through the operations of the File protocol. It is shared by the
+//! cargo-fuzz target in `fuzz/` and the property tests of the volume, and is only built for them.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::cell::RefCell;
+
+use patina::error::{EfiError, Result};
+use r_efi::efi;
+
+use crate::{
+    device::Device,
+    directory::ATTRIBUTE_DIRECTORY,
+    volume::{Info, Node, Volume},
+};
+
+/// An image in memory, as the storage of the volume.
+struct Image(RefCell<Vec<u8>>);
+
+impl Image {
+    fn range(&self, offset: u64, size: usize) -> Result<core::ops::Range<usize>> {
+        let start = usize::try_from(offset).map_err(|_| EfiError::InvalidParameter)?;
+        match start.checked_add(size) {
+            Some(end) if end <= self.0.borrow().len() => Ok(start..end),
+            _ => Err(EfiError::InvalidParameter),
+        }
+    }
+}
+
+impl Device for Image {
+    fn read(&self, offset: u64, buffer: &mut [u8]) -> Result<()> {
+        let range = self.range(offset, buffer.len())?;
+        buffer.copy_from_slice(&self.0.borrow()[range]);
+        Ok(())
+    }
+
+    fn write(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let range = self.range(offset, data.len())?;
+        self.0.borrow_mut()[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.0.borrow().len() as u64
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// A fixed time, so that runs are reproducible.
+fn clock() -> Option<efi::Time> {
+    Some(efi::Time { year: 2025, month: 3, day: 14, hour: 15, minute: 9, second: 26, ..Default::default() })
+}
+
+/// Mounts `image`, walks its tree reading every file, then creates, changes and deletes files.
+///
+/// None of it may panic or hang: every inconsistency of the image must be reported as an error.
+pub fn exercise(image: &[u8]) {
+    let device = Box::new(Image(RefCell::new(image.to_vec())));
+    let Ok(mut volume) = Volume::mount(device, clock) else {
+        return;
+    };
+    let _ = volume.label();
+    let _ = volume.free_space();
+
+    let mut buffer = vec![0; 16384];
+    let mut pending = vec![(Node::Root, 0)];
+    for _ in 0..64 {
+        let Some((node, depth)) = pending.pop() else {
+            break;
+        };
+        match volume.info(node).map(|info| info.attributes & ATTRIBUTE_DIRECTORY != 0) {
+            Ok(true) if depth < 4 => {
+                let mut position = 0;
+                for _ in 0..128 {
+                    let Ok(Some((info, next))) = volume.read_directory(node, position) else {
+                        break;
+                    };
+                    position = next;
+                    if let Ok(child) = volume.open(node, &info.name)
+                        && info.name != "."
+                        && info.name != ".."
+                    {
+                        pending.push((child, depth + 1));
+                    }
+                }
+            }
+            Ok(false) => {
+                let _ = volume.read(node, 0, &mut buffer);
+                let _ = volume.read(node, 100_000, &mut buffer[..10]);
+            }
+            _ => {}
+        }
+    }
+
+    if let Ok(node) = volume.create(Node::Root, "Fuzz File.txt", 0) {
+        let _ = volume.write(node, 0, &buffer[..3000]);
+        let _ = volume.set_size(node, 700);
+        let renamed =
+            volume.info(node).and_then(|info| volume.set_info(node, &Info { name: "Renamed.txt".into(), ..info }));
+        let _ = volume.delete(renamed.unwrap_or(node));
+    }
+    if let Ok(node) = volume.create(Node::Root, "FUZZDIR", ATTRIBUTE_DIRECTORY) {
+        let _ = volume.create(node, "inner", 0);
+        let _ = volume.delete(node);
+    }
+    let _ = volume.set_label("FUZZ");
+    let _ = volume.flush();
+}
@@ -0,0 +1,23 @@
+#![doc = include_str!("../README.md")]
+#![doc = concat!(
+    "## License\n\n",
+    " Copyright (c) Microsoft Corporation.\n\n",
+)]
+#![cfg_attr(all(not(feature = "std"), not(test), not(feature = "mockall")), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+
+mod bpb;
+mod device;
+mod directory;
+mod driver;
+mod fat;
+mod file;
+#[cfg(any(test, feature = "fuzzing"))]
+#[doc(hidden)]
+pub mod fuzz;
+mod name;
+mod volume;
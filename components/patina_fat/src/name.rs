@@ -0,0 +1,261 @@
+//! File Names
+//!
+//! Short (8.3) names, as stored in directory entries, and the rules that tie long names to them: which long names
+//! can be stored as a short name alone, and the short name generated for the others, following the "Short Names"
+//! and "Long Names" sections of the "Microsoft Extensible Firmware Initiative FAT32 File System Specification".
+//!
+//! Names are compared without regard to case. Short names are read and written as Latin-1, which matches the OEM code
+//! page for the ASCII names firmware deals with.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// The most UTF-16 code units in a long name.
+pub(crate) const MAX_LONG_NAME: usize = 255;
+
+/// The case flags of a short entry, set when the base name or the extension is shown in lower case.
+pub(crate) const LOWER_CASE_BASE: u8 = 0x08;
+pub(crate) const LOWER_CASE_EXTENSION: u8 = 0x10;
+
+/// The short name of the entries of a new file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShortName {
+    /// The name is a valid short name, shown with the case flags, and needs no long name.
+    Exact([u8; 11], u8),
+    /// The name needs a long name. The basis can be used as the short name if the name fits it without loss and no
+    /// other entry has it; otherwise a numeric tail is needed.
+    Basis { basis: [u8; 11], lossy: bool },
+}
+
+/// Returns the checksum of a short name, which long name entries carry.
+pub(crate) fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name.iter().fold(0u8, |sum, &byte| sum.rotate_right(1).wrapping_add(byte))
+}
+
+/// Returns the name shown for the short name of an entry with `case` flags.
+pub(crate) fn short_name_to_string(short_name: &[u8; 11], case: u8) -> String {
+    let mut raw = *short_name;
+    // A leading 0x05 stands for 0xE5, which marks deleted entries.
+    if raw[0] == 0x05 {
+        raw[0] = 0xE5;
+    }
+    let part = |bytes: &[u8], lower: bool| -> String {
+        let length = bytes.iter().rposition(|&byte| byte != b' ').map_or(0, |index| index + 1);
+        bytes[..length].iter().map(|&byte| if lower { byte.to_ascii_lowercase() } else { byte } as char).collect()
+    };
+    let base = part(&raw[..8], case & LOWER_CASE_BASE != 0);
+    let extension = part(&raw[8..], case & LOWER_CASE_EXTENSION != 0);
+    if extension.is_empty() { base } else { base + "." + &extension }
+}
+
+/// Returns whether `byte` is allowed in short names, in addition to letters and digits.
+fn is_short_name_symbol(byte: u8) -> bool {
+    b"$%'-_@~`!(){}^#&".contains(&byte)
+}
+
+/// Returns whether `character` is allowed in long names.
+fn is_long_name_character(character: char) -> bool {
+    character >= ' ' && !"\"*/:<>?\\|".contains(character)
+}
+
+/// Returns whether `name` can be the name of a file, which excludes "." and "..".
+pub(crate) fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name.encode_utf16().count() <= MAX_LONG_NAME
+        && name.chars().all(is_long_name_character)
+        && name.chars().any(|character| character != '.')
+}
+
+/// Returns `name` without the trailing periods and spaces that file names cannot end with, keeping "." and "..".
+pub(crate) fn trim(name: &str) -> &str {
+    match name {
+        "." | ".." => name,
+        _ => name.trim_end_matches(['.', ' ']),
+    }
+}
+
+/// Returns whether two names are the same without regard to case.
+pub(crate) fn matches(name: &str, other: &str) -> bool {
+    name.chars().flat_map(char::to_uppercase).eq(other.chars().flat_map(char::to_uppercase))
+}
+
+/// Returns how `name`, a valid name, is stored as a short name.
+pub(crate) fn short_name(name: &str) -> ShortName {
+    if let Some(exact) = exact_short_name(name) {
+        return exact;
+    }
+
+    // The basis name: upper case OEM characters without spaces or leading periods, and without the periods before
+    // the extension. Anything dropped or replaced makes the conversion lossy.
+    let trimmed = name.trim_start_matches('.');
+    let mut lossy = trimmed.len() != name.len() || name.contains(' ');
+    let name = trimmed;
+    let (base, extension) = match name.rfind('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => (name, ""),
+    };
+    let mut convert = |part: &str, length: usize| -> ([u8; 11], bool) {
+        let mut bytes = [b' '; 11];
+        let mut truncated = false;
+        for (count, character) in part.chars().filter(|&character| character != ' ' && character != '.').enumerate() {
+            if count == length {
+                truncated = true;
+                break;
+            }
+            let upper = character.to_ascii_uppercase();
+            bytes[count] = if upper.is_ascii_alphanumeric() || (upper.is_ascii() && is_short_name_symbol(upper as u8)) {
+                upper as u8
+            } else {
+                lossy = true;
+                b'_'
+            };
+        }
+        (bytes, truncated)
+    };
+    let (base_bytes, base_truncated) = convert(base, 8);
+    let (extension_bytes, extension_truncated) = convert(extension, 3);
+    let embedded_periods = base.contains('.');
+
+    let mut basis = [b' '; 11];
+    basis[..8].copy_from_slice(&base_bytes[..8]);
+    basis[8..].copy_from_slice(&extension_bytes[..3]);
+    if basis[0] == b' ' {
+        basis[0] = b'_';
+        lossy = true;
+    }
+    ShortName::Basis { basis, lossy: lossy || base_truncated || extension_truncated || embedded_periods }
+}
+
+/// Returns the short name of `name` if it is one already, in one case per part.
+fn exact_short_name(name: &str) -> Option<ShortName> {
+    let (base, extension) = match name.split_once('.') {
+        Some((base, extension)) => (base, extension),
+        None => (name, ""),
+    };
+    let valid = |part: &str, length: usize| {
+        part.len() <= length && part.bytes().all(|byte| byte.is_ascii_alphanumeric() || is_short_name_symbol(byte))
+    };
+    if base.is_empty() || !valid(base, 8) || !valid(extension, 3) {
+        return None;
+    }
+
+    let case = |part: &str, flag: u8| -> Option<u8> {
+        let upper = part.bytes().any(|byte| byte.is_ascii_uppercase());
+        let lower = part.bytes().any(|byte| byte.is_ascii_lowercase());
+        match (upper, lower) {
+            (true, true) => None,
+            (_, true) => Some(flag),
+            _ => Some(0),
+        }
+    };
+    let flags = case(base, LOWER_CASE_BASE)? | case(extension, LOWER_CASE_EXTENSION)?;
+
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.to_ascii_uppercase().as_bytes());
+    short_name[8..8 + extension.len()].copy_from_slice(extension.to_ascii_uppercase().as_bytes());
+    Some(ShortName::Exact(short_name, flags))
+}
+
+/// Returns `basis` with the numeric tail `~number`, which replaces the end of the base name if needed.
+pub(crate) fn with_numeric_tail(basis: &[u8; 11], number: u32) -> [u8; 11] {
+    let mut tail = Vec::from(*b"~");
+    tail.extend(number.to_string().bytes());
+    let base_length = basis[..8].iter().position(|&byte| byte == b' ').unwrap_or(8).min(8 - tail.len());
+    let mut short_name = *basis;
+    short_name[base_length..base_length + tail.len()].copy_from_slice(&tail);
+    short_name[base_length + tail.len()..8].fill(b' ');
+    short_name
+}
+
+/// Returns the short name a volume label is stored as, or None if `label` cannot be one.
+pub(crate) fn volume_label(label: &str) -> Option<[u8; 11]> {
+    let mut raw = [b' '; 11];
+    if label.chars().count() > raw.len() {
+        return None;
+    }
+    for (byte, character) in raw.iter_mut().zip(label.chars()) {
+        let upper = character.to_ascii_uppercase();
+        if !(upper.is_ascii_alphanumeric() || upper == ' ' || (upper.is_ascii() && is_short_name_symbol(upper as u8))) {
+            return None;
+        }
+        *byte = upper as u8;
+    }
+    Some(raw)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_names_are_shown_with_their_case() {
+        assert_eq!(short_name_to_string(b"README  TXT", 0), "README.TXT");
+        assert_eq!(short_name_to_string(b"NOTES   TXT", LOWER_CASE_BASE | LOWER_CASE_EXTENSION), "notes.txt");
+        assert_eq!(short_name_to_string(b"MAKEFILE   ", LOWER_CASE_EXTENSION), "MAKEFILE");
+        assert_eq!(short_name_to_string(b"..         ", 0), "..");
+        assert_eq!(short_name_to_string(b"\x05BC     TXT", 0), "\u{e5}BC.TXT");
+    }
+
+    #[test]
+    fn checksums_match_the_specification() {
+        // The checksum of the short name of "Long File Name.txt" in the FAT12 test image.
+        assert_eq!(checksum(b"LONGFI~1TXT"), crate::device::mock::FAT12_IMAGE[5 * 512 + 4 * 32 + 13]);
+    }
+
+    #[test]
+    fn names_are_validated_and_compared() {
+        assert!(is_valid("Long File Name.txt"));
+        assert!(is_valid(".hidden"));
+        assert!(!is_valid(""));
+        assert!(!is_valid(".."));
+        assert!(!is_valid("a:b"));
+        assert!(!is_valid("tab\t"));
+        assert!(!is_valid(&"x".repeat(256)));
+
+        assert_eq!(trim("name. . "), "name");
+        assert_eq!(trim(".."), "..");
+        assert!(matches("BootX64.efi", "BOOTX64.EFI"));
+        assert!(matches("\u{e9}t\u{e9}", "\u{c9}T\u{c9}"));
+        assert!(!matches("boot", "boot1"));
+    }
+
+    #[test]
+    fn short_names_are_derived_from_names() {
+        assert_eq!(short_name("README.TXT"), ShortName::Exact(*b"README  TXT", 0));
+        assert_eq!(
+            short_name("startup.nsh"),
+            ShortName::Exact(*b"STARTUP NSH", LOWER_CASE_BASE | LOWER_CASE_EXTENSION)
+        );
+        assert_eq!(short_name("EFI"), ShortName::Exact(*b"EFI        ", 0));
+        assert_eq!(short_name("Readme.md"), ShortName::Basis { basis: *b"README  MD ", lossy: false });
+        assert_eq!(short_name("Long File Name.txt"), ShortName::Basis { basis: *b"LONGFILETXT", lossy: true });
+        assert_eq!(short_name("archive.tar.gz"), ShortName::Basis { basis: *b"ARCHIVETGZ ", lossy: true });
+        assert_eq!(short_name(".config"), ShortName::Basis { basis: *b"CONFIG     ", lossy: true });
+        assert_eq!(short_name("na+me.jpeg"), ShortName::Basis { basis: *b"NA_ME   JPE", lossy: true });
+        assert_eq!(short_name("\u{e9}"), ShortName::Basis { basis: *b"_          ", lossy: true });
+    }
+
+    #[test]
+    fn numeric_tails_replace_the_end_of_the_base() {
+        assert_eq!(&with_numeric_tail(b"LONGFILETXT", 1), b"LONGFI~1TXT");
+        assert_eq!(&with_numeric_tail(b"AB      TXT", 2), b"AB~2    TXT");
+        assert_eq!(&with_numeric_tail(b"LONGFILETXT", 12345), b"LO~12345TXT");
+    }
+
+    #[test]
+    fn volume_labels_are_upper_case_short_names() {
+        assert_eq!(volume_label("Patina Fat"), Some(*b"PATINA FAT "));
+        assert_eq!(volume_label(""), Some(*b"           "));
+        assert_eq!(volume_label("far too long label"), None);
+        assert_eq!(volume_label("a.b"), None);
+    }
+}
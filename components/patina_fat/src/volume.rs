@@ -0,0 +1,1377 @@
+//! FAT Volume
+//!
+//! The files and directories of a FAT12, FAT16 or FAT32 volume: finding them by path, reading and writing files,
+//! creating, renaming and deleting files and directories, and the information the file protocol reports about them.
+//! It works on any [Device], so that it can be tested, and fuzzed, against disk images on the host.
+//!
+//! Files and directories are designated by [Node]s, the location of their short entry, and every operation reads the
+//! entry again, so that all the file handles open on a file see the changes made through any of them. Directories are
+//! loaded whole, up to the 65536 entries they can hold. Corrupt metadata, such as a broken cluster chain or a file
+//! larger than its chain, fails the operation with `EFI_VOLUME_CORRUPTED` instead of being trusted.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+
+use patina::error::{EfiError, Result};
+use r_efi::efi;
+
+use crate::{
+    bpb::{BOOT_SECTOR_SIZE, FatType, Layout, RootDirectory},
+    device::Device,
+    directory::{
+        ATTRIBUTE_ARCHIVE, ATTRIBUTE_DIRECTORY, ATTRIBUTE_HIDDEN, ATTRIBUTE_READ_ONLY, ATTRIBUTE_SYSTEM,
+        ATTRIBUTE_VOLUME_ID, DELETED, END_OF_DIRECTORY, ENTRY_SIZE, Found, ShortEntry, entry_at, free_entries,
+        from_efi_time, is_valid_time, long_entries, next_file, to_efi_time, volume_label,
+    },
+    fat::{Entry, Fat},
+    name::{self, ShortName},
+};
+
+/// Returns the current time, if known.
+pub(crate) type Clock = fn() -> Option<efi::Time>;
+
+/// The attributes of files that can be changed, which match the EFI file attributes.
+pub(crate) const FILE_ATTRIBUTES: u8 = ATTRIBUTE_READ_ONLY | ATTRIBUTE_HIDDEN | ATTRIBUTE_SYSTEM | ATTRIBUTE_ARCHIVE;
+
+/// Directories hold at most 65536 entries.
+const MAX_DIRECTORY_SIZE: usize = 65536 * ENTRY_SIZE;
+/// Files hold less than 4 GiB.
+const MAX_FILE_SIZE: u64 = u32::MAX as u64;
+/// Short names get numeric tails up to this one.
+const MAX_NUMERIC_TAIL: u32 = 999_999;
+
+/// The FSInfo sector of FAT32 volumes, which hints at the free clusters.
+const FS_INFO_SIZE: usize = 512;
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCTURE_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+const FS_INFO_FREE_COUNT: usize = 488;
+const FS_INFO_NEXT_FREE: usize = 492;
+/// The free cluster count of FSInfo sectors when it is unknown.
+const UNKNOWN_FREE_COUNT: u32 = u32::MAX;
+
+/// The names of the "." and ".." entries of directories.
+const DOT: [u8; 11] = *b".          ";
+const DOT_DOT: [u8; 11] = *b"..         ";
+
+/// A directory, by where its entries are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Directory {
+    Root,
+    /// A subdirectory, by its first cluster.
+    Cluster(u32),
+}
+
+/// A file or directory of the volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Node {
+    /// The root directory, which has no entry.
+    Root,
+    /// A file or subdirectory, by the indices of its entries in its parent directory: the first one, which is the
+    /// first long name entry if it has a long name, and its short entry.
+    Entry { parent: Directory, first: u32, index: u32 },
+}
+
+/// What the file protocol reports about a file or directory, as in an `EFI_FILE_INFO`.
+#[derive(Debug, Clone)]
+pub(crate) struct Info {
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    pub(crate) physical_size: u64,
+    /// The attributes of the entry, which are also the EFI file attributes.
+    pub(crate) attributes: u8,
+    pub(crate) create_time: efi::Time,
+    pub(crate) last_access_time: efi::Time,
+    pub(crate) modification_time: efi::Time,
+}
+
+/// Where the entries of a loaded directory are on the device.
+enum Location {
+    /// The fixed root directory of FAT12 and FAT16 volumes, at this offset.
+    Fixed(u64),
+    /// The clusters of the directory.
+    Clusters(Vec<u32>),
+}
+
+/// The entries of a directory, loaded in memory.
+struct Entries {
+    raw: Vec<u8>,
+    location: Location,
+}
+
+/// Returns the file or directory named `name` among `raw` entries, by its long or short name.
+fn find(raw: &[u8], name: &str) -> Option<Found> {
+    let mut index = 0;
+    while let Some(found) = next_file(raw, index) {
+        if !found.entry.is_dot()
+            && (name::matches(&found.name, name)
+                || name::matches(&name::short_name_to_string(&found.entry.name, 0), name))
+        {
+            return Some(found);
+        }
+        index = found.index + 1;
+    }
+    None
+}
+
+/// Returns whether an entry of `raw` has the short name `short_name`.
+fn short_name_taken(raw: &[u8], short_name: &[u8; 11]) -> bool {
+    let mut index = 0;
+    while let Some(found) = next_file(raw, index) {
+        if found.entry.name == *short_name {
+            return true;
+        }
+        index = found.index + 1;
+    }
+    false
+}
+
+/// Returns whether a time was given, rather than left zero.
+fn is_set(time: &efi::Time) -> bool {
+    time.year != 0 || time.month != 0 || time.day != 0
+}
+
+/// A mounted FAT volume.
+pub(crate) struct Volume {
+    device: Box<dyn Device>,
+    layout: Layout,
+    fat: Fat,
+    clock: Clock,
+    /// The clusters of the file read last, by its first cluster, so that reading a file in pieces follows its chain
+    /// once. Any change to the volume drops it.
+    last_chain: Option<(u32, Vec<u32>)>,
+}
+
+impl Volume {
+    /// Mounts the volume on `device`, which tells the time with `clock`.
+    ///
+    /// Fails with [EfiError::Unsupported] if the device does not hold a FAT volume.
+    pub(crate) fn mount(device: Box<dyn Device>, clock: Clock) -> Result<Self> {
+        let mut boot_sector = [0; BOOT_SECTOR_SIZE];
+        device.read(0, &mut boot_sector)?;
+        let layout = Layout::parse(&boot_sector, device.size())?;
+
+        let mut fat = Fat::new(&layout);
+        if let Some(fs_info) = Self::read_fs_info(device.as_ref(), &layout)? {
+            fat.set_next_free(u32::from_le_bytes(
+                fs_info[FS_INFO_NEXT_FREE..FS_INFO_NEXT_FREE + 4].try_into().unwrap(),
+            ));
+        }
+        log::info!(target: "fat", "Mounted a {:?} volume of {} clusters.", layout.fat_type, layout.cluster_count);
+        Ok(Self { device, layout, fat, clock, last_chain: None })
+    }
+
+    /// Returns the FSInfo sector of a FAT32 volume, if it has a valid one.
+    fn read_fs_info(device: &dyn Device, layout: &Layout) -> Result<Option<[u8; FS_INFO_SIZE]>> {
+        let Some(offset) = layout.fs_info_offset else {
+            return Ok(None);
+        };
+        let mut fs_info = [0; FS_INFO_SIZE];
+        device.read(offset, &mut fs_info)?;
+        let signature = |offset: usize| u32::from_le_bytes(fs_info[offset..offset + 4].try_into().unwrap());
+        let valid = signature(0) == FS_INFO_LEAD_SIGNATURE
+            && signature(484) == FS_INFO_STRUCTURE_SIGNATURE
+            && signature(508) == FS_INFO_TRAIL_SIGNATURE;
+        Ok(valid.then_some(fs_info))
+    }
+
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.device.is_read_only()
+    }
+
+    /// Returns the size of the space available to files and directories.
+    pub(crate) fn size(&self) -> u64 {
+        self.layout.data_size()
+    }
+
+    pub(crate) fn cluster_size(&self) -> u32 {
+        self.layout.cluster_size
+    }
+
+    /// Returns the free space of the volume.
+    pub(crate) fn free_space(&mut self) -> Result<u64> {
+        Ok(self.fat.free_count(self.device.as_ref())? as u64 * self.layout.cluster_size as u64)
+    }
+
+    fn is_fat32(&self) -> bool {
+        self.layout.fat_type == FatType::Fat32
+    }
+
+    /// Starts a change of the volume, which fails with [EfiError::WriteProtected] on read only devices.
+    fn begin_change(&mut self) -> Result<()> {
+        if self.is_read_only() {
+            return Err(EfiError::WriteProtected);
+        }
+        self.last_chain = None;
+        Ok(())
+    }
+
+    /// Returns the current time, or the FAT epoch if the clock does not know it.
+    fn now(&self) -> efi::Time {
+        (self.clock)().filter(is_valid_time).unwrap_or(efi::Time { year: 1980, month: 1, day: 1, ..Default::default() })
+    }
+
+    /// Returns the directory starting at `cluster`, as recorded in an entry, where 0 stands for the root directory.
+    fn directory_at(&self, cluster: u32) -> Directory {
+        match self.layout.root {
+            _ if cluster == 0 => Directory::Root,
+            RootDirectory::Cluster(root) if root == cluster => Directory::Root,
+            _ => Directory::Cluster(cluster),
+        }
+    }
+
+    /// Returns the cluster recorded for `directory` in entries.
+    fn cluster_of(directory: Directory) -> u32 {
+        match directory {
+            Directory::Root => 0,
+            Directory::Cluster(cluster) => cluster,
+        }
+    }
+
+    /// Returns the device ranges holding `length` bytes from byte `position` of the clusters in `chain`, merging
+    /// contiguous clusters.
+    fn runs(&self, chain: &[u32], position: u64, length: usize) -> Result<Vec<(u64, usize)>> {
+        let cluster_size = self.layout.cluster_size as u64;
+        let mut runs = Vec::new();
+        let mut position = position;
+        let mut remaining = length as u64;
+        while remaining > 0 {
+            let index = (position / cluster_size) as usize;
+            let within = position % cluster_size;
+            let first = *chain.get(index).ok_or(EfiError::VolumeCorrupted)?;
+            let mut count = 1;
+            while count * cluster_size < within + remaining
+                && chain.get(index + count as usize) == Some(&(first + count as u32))
+            {
+                count += 1;
+            }
+            let size = (count * cluster_size - within).min(remaining);
+            runs.push((self.layout.cluster_offset(first) + within, size as usize));
+            position += size;
+            remaining -= size;
+        }
+        Ok(runs)
+    }
+
+    /// Reads `buffer.len()` bytes from byte `position` of the clusters in `chain`.
+    fn read_chain(&self, chain: &[u32], position: u64, buffer: &mut [u8]) -> Result<()> {
+        let mut done = 0;
+        for (offset, size) in self.runs(chain, position, buffer.len())? {
+            self.device.read(offset, &mut buffer[done..done + size])?;
+            done += size;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` from byte `position` of the clusters in `chain`.
+    fn write_chain(&self, chain: &[u32], position: u64, data: &[u8]) -> Result<()> {
+        let mut done = 0;
+        for (offset, size) in self.runs(chain, position, data.len())? {
+            self.device.write(offset, &data[done..done + size])?;
+            done += size;
+        }
+        Ok(())
+    }
+
+    /// Writes zeros from byte `start` to byte `end` of the clusters in `chain`.
+    fn zero_chain(&self, chain: &[u32], start: u64, end: u64) -> Result<()> {
+        let zeros = vec![0; self.layout.cluster_size as usize];
+        let mut position = start;
+        while position < end {
+            let size = (end - position).min(zeros.len() as u64);
+            self.write_chain(chain, position, &zeros[..size as usize])?;
+            position += size;
+        }
+        Ok(())
+    }
+
+    /// Loads the entries of `directory`.
+    fn load(&mut self, directory: Directory) -> Result<Entries> {
+        let first = match (directory, self.layout.root) {
+            (Directory::Root, RootDirectory::Fixed { offset, entries }) => {
+                let mut raw = vec![0; entries as usize * ENTRY_SIZE];
+                self.device.read(offset, &mut raw)?;
+                return Ok(Entries { raw, location: Location::Fixed(offset) });
+            }
+            (Directory::Root, RootDirectory::Cluster(cluster)) | (Directory::Cluster(cluster), _) => cluster,
+        };
+
+        let cluster_size = self.layout.cluster_size as usize;
+        let clusters = self.fat.chain(self.device.as_ref(), first, MAX_DIRECTORY_SIZE / cluster_size + 1)?;
+        if clusters.len() * cluster_size > MAX_DIRECTORY_SIZE {
+            log::error!(target: "fat", "Directory at cluster {first:#x} is too large.");
+            return Err(EfiError::VolumeCorrupted);
+        }
+        let mut raw = vec![0; clusters.len() * cluster_size];
+        self.read_chain(&clusters, 0, &mut raw)?;
+        Ok(Entries { raw, location: Location::Clusters(clusters) })
+    }
+
+    /// Returns the byte offset of entry `index` of the loaded `entries`.
+    fn offset_in(&self, entries: &Entries, index: u32) -> Result<u64> {
+        let byte = index as u64 * ENTRY_SIZE as u64;
+        match &entries.location {
+            Location::Fixed(offset) => Ok(offset + byte),
+            Location::Clusters(clusters) => {
+                let cluster_size = self.layout.cluster_size as u64;
+                let cluster = clusters.get((byte / cluster_size) as usize).ok_or(EfiError::VolumeCorrupted)?;
+                Ok(self.layout.cluster_offset(*cluster) + byte % cluster_size)
+            }
+        }
+    }
+
+    /// Writes `new` entries to the loaded `entries`, from entry `index`.
+    fn write_entries(&mut self, entries: &mut Entries, index: u32, new: &[[u8; ENTRY_SIZE]]) -> Result<()> {
+        for (entry_index, entry) in (index..).zip(new) {
+            let offset = self.offset_in(entries, entry_index)?;
+            self.device.write(offset, entry)?;
+            let start = entry_index as usize * ENTRY_SIZE;
+            entries.raw[start..start + ENTRY_SIZE].copy_from_slice(entry);
+        }
+        Ok(())
+    }
+
+    /// Returns the byte offset of entry `index` of `directory`, without loading the directory.
+    fn entry_offset(&mut self, directory: Directory, index: u32) -> Result<u64> {
+        let byte = index as u64 * ENTRY_SIZE as u64;
+        let first = match (directory, self.layout.root) {
+            (Directory::Root, RootDirectory::Fixed { offset, entries }) if index < entries => return Ok(offset + byte),
+            (Directory::Root, RootDirectory::Fixed { .. }) => return Err(EfiError::NotFound),
+            (Directory::Root, RootDirectory::Cluster(cluster)) | (Directory::Cluster(cluster), _) => cluster,
+        };
+        let cluster_size = self.layout.cluster_size as u64;
+        let position = (byte / cluster_size) as usize;
+        let chain = self.fat.chain(self.device.as_ref(), first, position + 1)?;
+        let cluster = chain.get(position).ok_or(EfiError::NotFound)?;
+        Ok(self.layout.cluster_offset(*cluster) + byte % cluster_size)
+    }
+
+    /// Reads the short entry of `node`, which must be a file or subdirectory.
+    ///
+    /// Fails with [EfiError::NotFound] if the entry no longer describes a file or directory, which happens once the
+    /// file is deleted.
+    fn read_entry(&mut self, node: Node) -> Result<ShortEntry> {
+        let Node::Entry { parent, index, .. } = node else {
+            return Err(EfiError::InvalidParameter);
+        };
+        let offset = self.entry_offset(parent, index)?;
+        let mut raw = [0; ENTRY_SIZE];
+        self.device.read(offset, &mut raw)?;
+        let entry = ShortEntry::parse(&raw);
+        if matches!(raw[0], END_OF_DIRECTORY | DELETED) || entry.attributes & ATTRIBUTE_VOLUME_ID != 0 {
+            return Err(EfiError::NotFound);
+        }
+        Ok(entry)
+    }
+
+    fn write_entry(&mut self, node: Node, entry: &ShortEntry) -> Result<()> {
+        let Node::Entry { parent, index, .. } = node else {
+            return Err(EfiError::InvalidParameter);
+        };
+        let offset = self.entry_offset(parent, index)?;
+        self.device.write(offset, &entry.to_bytes())
+    }
+
+    /// Marks the entries of a file, from `first` to `index`, as deleted.
+    fn remove_entries(&mut self, parent: Directory, first: u32, index: u32) -> Result<()> {
+        for entry_index in first..=index {
+            let offset = self.entry_offset(parent, entry_index)?;
+            self.device.write(offset, &[DELETED])?;
+        }
+        Ok(())
+    }
+
+    /// Records a change of the content of a file in its entry.
+    fn touch(&self, entry: &mut ShortEntry) {
+        let (date, time, _) = from_efi_time(&self.now());
+        entry.write_date = date;
+        entry.write_time = time;
+        entry.access_date = date;
+        entry.attributes |= ATTRIBUTE_ARCHIVE;
+    }
+
+    /// Returns the directory `node` is, or fails with [EfiError::NotFound] if it is a file.
+    fn directory_of(&mut self, node: Node) -> Result<Directory> {
+        if node == Node::Root {
+            return Ok(Directory::Root);
+        }
+        let entry = self.read_entry(node)?;
+        if !entry.is_directory() {
+            return Err(EfiError::NotFound);
+        }
+        match entry.first_cluster(self.is_fat32()) {
+            cluster if self.layout.is_cluster(cluster) => Ok(self.directory_at(cluster)),
+            _ => Err(EfiError::VolumeCorrupted),
+        }
+    }
+
+    /// Returns the parent of `directory`, as recorded in its ".." entry.
+    fn parent_directory(&mut self, directory: Directory) -> Result<Directory> {
+        if directory == Directory::Root {
+            return Err(EfiError::NotFound);
+        }
+        let entries = self.load(directory)?;
+        let mut index = 0;
+        while let Some(found) = next_file(&entries.raw, index) {
+            if found.entry.name == DOT_DOT {
+                return Ok(self.directory_at(found.entry.first_cluster(self.is_fat32())));
+            }
+            index = found.index + 1;
+        }
+        Err(EfiError::VolumeCorrupted)
+    }
+
+    /// Returns the node of `directory`, found in its parent directory.
+    fn node_of(&mut self, directory: Directory) -> Result<Node> {
+        let Directory::Cluster(cluster) = directory else {
+            return Ok(Node::Root);
+        };
+        let parent = self.parent_directory(directory)?;
+        let entries = self.load(parent)?;
+        let mut index = 0;
+        while let Some(found) = next_file(&entries.raw, index) {
+            if !found.entry.is_dot()
+                && found.entry.is_directory()
+                && found.entry.first_cluster(self.is_fat32()) == cluster
+            {
+                return Ok(Node::Entry { parent, first: found.first, index: found.index });
+            }
+            index = found.index + 1;
+        }
+        log::error!(target: "fat", "Directory at cluster {cluster:#x} is not in its parent directory.");
+        Err(EfiError::VolumeCorrupted)
+    }
+
+    /// Returns the file or directory at `path`, relative to `from` unless it starts with a backslash.
+    ///
+    /// Fails with [EfiError::NotFound] if the path does not lead to a file or directory.
+    pub(crate) fn open(&mut self, from: Node, path: &str) -> Result<Node> {
+        let (mut node, path) = match path.strip_prefix('\\') {
+            Some(path) => (Node::Root, path),
+            None => (from, path),
+        };
+        for component in path.split('\\').filter(|component| !component.is_empty()) {
+            let directory = self.directory_of(node)?;
+            node = match name::trim(component) {
+                "." => node,
+                ".." => {
+                    let parent = self.parent_directory(directory)?;
+                    self.node_of(parent)?
+                }
+                name => {
+                    let found = find(&self.load(directory)?.raw, name).ok_or(EfiError::NotFound)?;
+                    Node::Entry { parent: directory, first: found.first, index: found.index }
+                }
+            };
+        }
+        Ok(node)
+    }
+
+    /// Splits `path` into the directory it is in, relative to `from`, and its valid name.
+    fn split<'a>(&mut self, from: Node, path: &'a str) -> Result<(Directory, &'a str)> {
+        let path = path.trim_end_matches('\\');
+        let (parent, name) = match path.rfind('\\') {
+            Some(index) => (self.open(from, &path[..index + 1])?, &path[index + 1..]),
+            None => (from, path),
+        };
+        let name = name::trim(name);
+        if !name::is_valid(name) {
+            return Err(EfiError::InvalidParameter);
+        }
+        Ok((self.directory_of(parent)?, name))
+    }
+
+    /// Returns the short name, case flags and long name entries of a new file named `name` among `raw` entries.
+    fn new_names(raw: &[u8], name: &str) -> Result<([u8; 11], u8, Vec<[u8; ENTRY_SIZE]>)> {
+        let short_name = match name::short_name(name) {
+            ShortName::Exact(short_name, case) => return Ok((short_name, case, Vec::new())),
+            ShortName::Basis { basis, lossy: false } if !short_name_taken(raw, &basis) => basis,
+            ShortName::Basis { basis, .. } => (1..=MAX_NUMERIC_TAIL)
+                .map(|number| name::with_numeric_tail(&basis, number))
+                .find(|short_name| !short_name_taken(raw, short_name))
+                .ok_or(EfiError::AccessDenied)?,
+        };
+        Ok((short_name, 0, long_entries(name, &short_name)))
+    }
+
+    /// Adds `new` entries to the loaded `entries`, growing the directory if needed, and returns the index of the
+    /// first one.
+    fn insert(&mut self, entries: &mut Entries, new: &[[u8; ENTRY_SIZE]]) -> Result<u32> {
+        let start = free_entries(&entries.raw, new.len() as u32);
+        let end = start + new.len() as u32;
+        let end_of_directory = (0..)
+            .find(|&index| entry_at(&entries.raw, index).is_none_or(|entry| entry[0] == END_OF_DIRECTORY))
+            .unwrap_or(u32::MAX);
+
+        if end as usize * ENTRY_SIZE > entries.raw.len() {
+            self.grow(entries, end as usize * ENTRY_SIZE)?;
+        }
+        self.write_entries(entries, start, new)?;
+        // Entries taken from past the end of the directory move its end, which must be marked if anything follows.
+        if end > end_of_directory && entry_at(&entries.raw, end).is_some_and(|entry| entry[0] != END_OF_DIRECTORY) {
+            self.write_entries(entries, end, &[[0; ENTRY_SIZE]])?;
+        }
+        Ok(start)
+    }
+
+    /// Grows the loaded `entries` to at least `size` bytes with cleared clusters.
+    fn grow(&mut self, entries: &mut Entries, size: usize) -> Result<()> {
+        let cluster_size = self.layout.cluster_size as usize;
+        let Location::Clusters(clusters) = &mut entries.location else {
+            return Err(EfiError::VolumeFull);
+        };
+        if size > MAX_DIRECTORY_SIZE {
+            return Err(EfiError::VolumeFull);
+        }
+        let count = size.div_ceil(cluster_size) - clusters.len();
+        let new = self.fat.allocate(self.device.as_ref(), count, clusters.last().copied())?;
+        let zeros = vec![0; cluster_size];
+        for &cluster in &new {
+            self.device.write(self.layout.cluster_offset(cluster), &zeros)?;
+        }
+        clusters.extend(new);
+        entries.raw.resize(clusters.len() * cluster_size, 0);
+        Ok(())
+    }
+
+    /// Creates the file or directory at `path`, relative to `from`, with `attributes`.
+    ///
+    /// Fails with [EfiError::AccessDenied] if it exists already.
+    pub(crate) fn create(&mut self, from: Node, path: &str, attributes: u8) -> Result<Node> {
+        self.begin_change()?;
+        let (directory, name) = self.split(from, path)?;
+        let mut entries = self.load(directory)?;
+        if find(&entries.raw, name).is_some() {
+            return Err(EfiError::AccessDenied);
+        }
+        let (short_name, case, mut new) = Self::new_names(&entries.raw, name)?;
+
+        let (date, time, hundredths) = from_efi_time(&self.now());
+        let mut entry = ShortEntry {
+            name: short_name,
+            attributes: attributes & (FILE_ATTRIBUTES | ATTRIBUTE_DIRECTORY),
+            case,
+            create_time_tenths: hundredths,
+            create_time: time,
+            create_date: date,
+            access_date: date,
+            write_time: time,
+            write_date: date,
+            ..Default::default()
+        };
+
+        // A directory starts with its "." and ".." entries.
+        let mut allocated = None;
+        if entry.is_directory() {
+            let cluster = self.fat.allocate(self.device.as_ref(), 1, None)?[0];
+            allocated = Some(cluster);
+            entry.set_first_cluster(cluster);
+            let dot = ShortEntry { name: DOT, ..entry };
+            let mut dot_dot = ShortEntry { name: DOT_DOT, ..entry };
+            dot_dot.set_first_cluster(Self::cluster_of(directory));
+            let mut content = vec![0; self.layout.cluster_size as usize];
+            content[..ENTRY_SIZE].copy_from_slice(&dot.to_bytes());
+            content[ENTRY_SIZE..2 * ENTRY_SIZE].copy_from_slice(&dot_dot.to_bytes());
+            self.device.write(self.layout.cluster_offset(cluster), &content)?;
+        } else {
+            entry.attributes |= ATTRIBUTE_ARCHIVE;
+        }
+
+        let long_entry_count = new.len() as u32;
+        new.push(entry.to_bytes());
+        match self.insert(&mut entries, &new) {
+            Ok(first) => Ok(Node::Entry { parent: directory, first, index: first + long_entry_count }),
+            Err(err) => {
+                if let Some(cluster) = allocated {
+                    self.fat.free(self.device.as_ref(), &[cluster])?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns the clusters of the file of `entry`, allocating clusters for it to hold `size` bytes.
+    fn allocate(&mut self, entry: &mut ShortEntry, size: u64) -> Result<Vec<u32>> {
+        let needed = size.div_ceil(self.layout.cluster_size as u64) as usize;
+        let mut chain = match entry.first_cluster(self.is_fat32()) {
+            0 => Vec::new(),
+            first => self.fat.chain(self.device.as_ref(), first, usize::MAX)?,
+        };
+        if chain.len() < needed {
+            let new = self.fat.allocate(self.device.as_ref(), needed - chain.len(), chain.last().copied())?;
+            if chain.is_empty() {
+                entry.set_first_cluster(new[0]);
+            }
+            chain.extend(new);
+        }
+        Ok(chain)
+    }
+
+    /// Returns the size of the file `node`.
+    pub(crate) fn file_size(&mut self, node: Node) -> Result<u64> {
+        Ok(self.read_entry(node)?.size as u64)
+    }
+
+    /// Reads from byte `position` of the file `node` into `buffer`, and returns the number of bytes read, which is
+    /// less than the size of the buffer at the end of the file.
+    ///
+    /// Fails with [EfiError::DeviceError] if the position is past the end of the file.
+    pub(crate) fn read(&mut self, node: Node, position: u64, buffer: &mut [u8]) -> Result<usize> {
+        let entry = self.read_entry(node)?;
+        let size = entry.size as u64;
+        if position > size {
+            return Err(EfiError::DeviceError);
+        }
+        if position == size || buffer.is_empty() {
+            return Ok(0);
+        }
+        let length = (size - position).min(buffer.len() as u64) as usize;
+
+        // The chain must end with the file, which also rules out loops.
+        let first = entry.first_cluster(self.is_fat32());
+        let count = size.div_ceil(self.layout.cluster_size as u64) as usize;
+        let chain = match self.last_chain.take() {
+            Some((cached, chain)) if cached == first && chain.len() == count => chain,
+            _ => {
+                let chain = self.fat.chain(self.device.as_ref(), first, count + 1)?;
+                if chain.len() != count {
+                    log::error!(target: "fat", "File of {size} bytes has a chain of {} clusters.", chain.len());
+                    return Err(EfiError::VolumeCorrupted);
+                }
+                chain
+            }
+        };
+        let result = self.read_chain(&chain, position, &mut buffer[..length]);
+        self.last_chain = Some((first, chain));
+        result.map(|_| length)
+    }
+
+    /// Writes `data` at byte `position` of the file `node`, which grows as needed. A gap between the end of the file
+    /// and the position is filled with zeros.
+    pub(crate) fn write(&mut self, node: Node, position: u64, data: &[u8]) -> Result<()> {
+        self.begin_change()?;
+        let mut entry = self.read_entry(node)?;
+        let end =
+            position.checked_add(data.len() as u64).filter(|&end| end <= MAX_FILE_SIZE).ok_or(EfiError::VolumeFull)?;
+        let size = entry.size as u64;
+        let chain = self.allocate(&mut entry, end.max(size))?;
+        if position > size {
+            self.zero_chain(&chain, size, position)?;
+        }
+        self.write_chain(&chain, position, data)?;
+        entry.size = end.max(size) as u32;
+        self.touch(&mut entry);
+        self.write_entry(node, &entry)
+    }
+
+    /// Sets the size of the file `node`, freeing the clusters past its end or clearing the bytes added.
+    pub(crate) fn set_size(&mut self, node: Node, size: u64) -> Result<()> {
+        self.begin_change()?;
+        let mut entry = self.read_entry(node)?;
+        if entry.is_directory() {
+            return Err(EfiError::AccessDenied);
+        }
+        if size > MAX_FILE_SIZE {
+            return Err(EfiError::VolumeFull);
+        }
+        let current = entry.size as u64;
+        if size > current {
+            let chain = self.allocate(&mut entry, size)?;
+            self.zero_chain(&chain, current, size)?;
+        } else if size < current {
+            let keep = size.div_ceil(self.layout.cluster_size as u64) as usize;
+            let chain = self.fat.chain(self.device.as_ref(), entry.first_cluster(self.is_fat32()), usize::MAX)?;
+            if keep == 0 {
+                entry.set_first_cluster(0);
+            } else if let Some(&last) = chain.get(keep - 1) {
+                self.fat.set(self.device.as_ref(), last, Entry::End)?;
+            }
+            self.fat.free(self.device.as_ref(), chain.get(keep..).unwrap_or_default())?;
+        }
+        entry.size = size as u32;
+        self.touch(&mut entry);
+        self.write_entry(node, &entry)
+    }
+
+    /// Deletes the file or empty directory `node`.
+    pub(crate) fn delete(&mut self, node: Node) -> Result<()> {
+        self.begin_change()?;
+        let Node::Entry { parent, first, index } = node else {
+            return Err(EfiError::AccessDenied);
+        };
+        let entry = self.read_entry(node)?;
+        if entry.is_directory() {
+            let directory = self.directory_of(node)?;
+            let entries = self.load(directory)?;
+            let mut entry_index = 0;
+            while let Some(found) = next_file(&entries.raw, entry_index) {
+                if !found.entry.is_dot() {
+                    return Err(EfiError::AccessDenied);
+                }
+                entry_index = found.index + 1;
+            }
+        }
+
+        self.remove_entries(parent, first, index)?;
+        let cluster = entry.first_cluster(self.is_fat32());
+        if cluster != 0 {
+            // The file is gone either way, at worst leaving its clusters lost.
+            match self.fat.chain(self.device.as_ref(), cluster, usize::MAX) {
+                Ok(chain) => self.fat.free(self.device.as_ref(), &chain)?,
+                Err(err) => log::warn!(target: "fat", "Clusters of a deleted file are lost: {err:?}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the size of `directory`, the size of its clusters.
+    fn directory_size(&mut self, directory: Directory) -> Result<u64> {
+        let cluster_size = self.layout.cluster_size as u64;
+        let first = match (directory, self.layout.root) {
+            (Directory::Root, RootDirectory::Fixed { entries, .. }) => return Ok(entries as u64 * ENTRY_SIZE as u64),
+            (Directory::Root, RootDirectory::Cluster(cluster)) | (Directory::Cluster(cluster), _) => cluster,
+        };
+        Ok(self.fat.chain(self.device.as_ref(), first, usize::MAX)?.len() as u64 * cluster_size)
+    }
+
+    /// Returns the information of the file or directory found in a directory.
+    fn info_of(&mut self, found: Found) -> Info {
+        let entry = &found.entry;
+        let cluster_size = self.layout.cluster_size as u64;
+        let (size, physical_size) = if entry.is_directory() {
+            // A directory with a broken chain is still listed, so that it can be deleted.
+            let size = match entry.first_cluster(self.is_fat32()) {
+                0 => self.directory_size(Directory::Root),
+                cluster => self.directory_size(Directory::Cluster(cluster)),
+            };
+            let size = size.unwrap_or_default();
+            (size, size)
+        } else {
+            (entry.size as u64, (entry.size as u64).div_ceil(cluster_size) * cluster_size)
+        };
+        Info {
+            name: found.name,
+            size,
+            physical_size,
+            attributes: entry.attributes & (FILE_ATTRIBUTES | ATTRIBUTE_DIRECTORY),
+            create_time: to_efi_time(entry.create_date, entry.create_time, entry.create_time_tenths),
+            last_access_time: to_efi_time(entry.access_date, 0, 0),
+            modification_time: to_efi_time(entry.write_date, entry.write_time, 0),
+        }
+    }
+
+    /// Returns the information of the file or directory `node`.
+    pub(crate) fn info(&mut self, node: Node) -> Result<Info> {
+        let Node::Entry { parent, first, index } = node else {
+            let size = self.directory_size(Directory::Root)?;
+            return Ok(Info {
+                name: String::new(),
+                size,
+                physical_size: size,
+                attributes: ATTRIBUTE_DIRECTORY,
+                create_time: efi::Time::default(),
+                last_access_time: efi::Time::default(),
+                modification_time: efi::Time::default(),
+            });
+        };
+        let entries = self.load(parent)?;
+        let found = next_file(&entries.raw, first).filter(|found| found.index == index).ok_or(EfiError::NotFound)?;
+        Ok(self.info_of(found))
+    }
+
+    /// Returns the information of the first file or subdirectory at or after entry `position` of the directory
+    /// `node`, and the position of the entry after it.
+    pub(crate) fn read_directory(&mut self, node: Node, position: u32) -> Result<Option<(Info, u32)>> {
+        let directory = self.directory_of(node)?;
+        let entries = self.load(directory)?;
+        Ok(next_file(&entries.raw, position).map(|found| {
+            let next = found.index + 1;
+            (self.info_of(found), next)
+        }))
+    }
+
+    /// Applies `info` to the file or directory `node`: its name, which may be a path, its size, its attributes and
+    /// the times given. Returns the node of the file, which changes when it is renamed.
+    pub(crate) fn set_info(&mut self, node: Node, info: &Info) -> Result<Node> {
+        self.begin_change()?;
+        if node == Node::Root {
+            return Err(EfiError::AccessDenied);
+        }
+        let current = self.info(node)?;
+        let directory = current.attributes & ATTRIBUTE_DIRECTORY != 0;
+        if (info.attributes ^ current.attributes) & ATTRIBUTE_DIRECTORY != 0 || (directory && info.size != current.size)
+        {
+            return Err(EfiError::AccessDenied);
+        }
+        let times = [&info.create_time, &info.last_access_time, &info.modification_time];
+        if info.attributes & !(FILE_ATTRIBUTES | ATTRIBUTE_DIRECTORY) != 0
+            || times.iter().any(|time| is_set(time) && !is_valid_time(time))
+        {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let node = if info.name != current.name { self.rename(node, &info.name)? } else { node };
+        if !directory && info.size != current.size {
+            self.set_size(node, info.size)?;
+        }
+
+        let mut entry = self.read_entry(node)?;
+        entry.attributes = (entry.attributes & !FILE_ATTRIBUTES) | (info.attributes & FILE_ATTRIBUTES);
+        if is_set(&info.create_time) {
+            (entry.create_date, entry.create_time, entry.create_time_tenths) = from_efi_time(&info.create_time);
+        }
+        if is_set(&info.last_access_time) {
+            entry.access_date = from_efi_time(&info.last_access_time).0;
+        }
+        if is_set(&info.modification_time) {
+            (entry.write_date, entry.write_time, _) = from_efi_time(&info.modification_time);
+        }
+        self.write_entry(node, &entry)?;
+        Ok(node)
+    }
+
+    /// Moves the file or directory `node` to `path`, relative to the directory it is in, and returns its new node.
+    fn rename(&mut self, node: Node, path: &str) -> Result<Node> {
+        let Node::Entry { parent, first, index } = node else {
+            return Err(EfiError::AccessDenied);
+        };
+        let parent_node = self.node_of(parent)?;
+        let (directory, name) = self.split(parent_node, path)?;
+        let entry = self.read_entry(node)?;
+        let fat32 = self.is_fat32();
+
+        // A directory cannot move into itself or one of its subdirectories, which are found by walking up to the root
+        // directory. The walk cannot take more steps than there are clusters, unless the volume is corrupt.
+        if entry.is_directory() {
+            let moved = self.directory_at(entry.first_cluster(fat32));
+            let mut ancestor = directory;
+            for _ in 0..=self.layout.cluster_count {
+                if ancestor == moved {
+                    return Err(EfiError::AccessDenied);
+                }
+                if ancestor == Directory::Root {
+                    break;
+                }
+                ancestor = self.parent_directory(ancestor)?;
+            }
+            if ancestor != Directory::Root {
+                return Err(EfiError::VolumeCorrupted);
+            }
+        }
+
+        let mut entries = self.load(directory)?;
+        if let Some(existing) = find(&entries.raw, name)
+            && (directory != parent || existing.index != index)
+        {
+            return Err(EfiError::AccessDenied);
+        }
+        let (short_name, case, mut new) = Self::new_names(&entries.raw, name)?;
+        let long_entry_count = new.len() as u32;
+        new.push(ShortEntry { name: short_name, case, ..entry }.to_bytes());
+        let new_first = self.insert(&mut entries, &new)?;
+        self.remove_entries(parent, first, index)?;
+
+        // A directory moved to another directory records its new parent in its ".." entry.
+        if entry.is_directory() && directory != parent {
+            let moved = self.directory_at(entry.first_cluster(fat32));
+            let mut moved_entries = self.load(moved)?;
+            let mut entry_index = 0;
+            while let Some(found) = next_file(&moved_entries.raw, entry_index) {
+                if found.entry.name == DOT_DOT {
+                    let mut dot_dot = found.entry;
+                    dot_dot.set_first_cluster(Self::cluster_of(directory));
+                    self.write_entries(&mut moved_entries, found.index, &[dot_dot.to_bytes()])?;
+                    break;
+                }
+                entry_index = found.index + 1;
+            }
+        }
+        Ok(Node::Entry { parent: directory, first: new_first, index: new_first + long_entry_count })
+    }
+
+    /// Returns the volume label, from the volume label entry of the root directory.
+    pub(crate) fn label(&mut self) -> Result<String> {
+        let entries = self.load(Directory::Root)?;
+        let label = volume_label(&entries.raw).map(|(_, raw)| raw.iter().map(|&byte| byte as char).collect::<String>());
+        Ok(label.unwrap_or_default().trim_end().into())
+    }
+
+    /// Sets the volume label, or removes it if `label` is empty.
+    pub(crate) fn set_label(&mut self, label: &str) -> Result<()> {
+        self.begin_change()?;
+        let raw = name::volume_label(label).ok_or(EfiError::InvalidParameter)?;
+        let blank = raw.iter().all(|&byte| byte == b' ');
+        let mut entries = self.load(Directory::Root)?;
+        match volume_label(&entries.raw) {
+            Some((index, _)) if blank => {
+                let offset = self.offset_in(&entries, index)?;
+                self.device.write(offset, &[DELETED])
+            }
+            Some((index, _)) => {
+                let mut entry = *entry_at(&entries.raw, index).ok_or(EfiError::VolumeCorrupted)?;
+                entry[..11].copy_from_slice(&raw);
+                self.write_entries(&mut entries, index, &[entry])
+            }
+            None if blank => Ok(()),
+            None => {
+                let (date, time, _) = from_efi_time(&self.now());
+                let entry = ShortEntry {
+                    name: raw,
+                    attributes: ATTRIBUTE_VOLUME_ID,
+                    write_date: date,
+                    write_time: time,
+                    ..Default::default()
+                };
+                self.insert(&mut entries, &[entry.to_bytes()]).map(|_| ())
+            }
+        }
+    }
+
+    /// Writes the cached FAT and the FSInfo sector to the device, and flushes it.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        if self.is_read_only() {
+            return Ok(());
+        }
+        self.fat.flush(self.device.as_ref())?;
+        if let (Some(mut fs_info), Some(offset)) =
+            (Self::read_fs_info(self.device.as_ref(), &self.layout)?, self.layout.fs_info_offset)
+        {
+            let free_count = self.fat.known_free_count().unwrap_or(UNKNOWN_FREE_COUNT);
+            let mut updated = fs_info;
+            updated[FS_INFO_FREE_COUNT..FS_INFO_FREE_COUNT + 4].copy_from_slice(&free_count.to_le_bytes());
+            updated[FS_INFO_NEXT_FREE..FS_INFO_NEXT_FREE + 4].copy_from_slice(&self.fat.next_free().to_le_bytes());
+            if updated != fs_info {
+                fs_info = updated;
+                self.device.write(offset, &fs_info)?;
+            }
+        }
+        self.device.flush()
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::device::mock::{FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE, MemoryDisk};
+    use alloc::{rc::Rc, string::ToString};
+    use core::cell::RefCell;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn clock() -> Option<efi::Time> {
+        Some(efi::Time { year: 2025, month: 3, day: 14, hour: 15, minute: 9, second: 26, ..Default::default() })
+    }
+
+    fn mount(image: &[u8]) -> (Volume, Rc<RefCell<Vec<u8>>>) {
+        let (device, data) = MemoryDisk::shared(image);
+        (Volume::mount(device, clock).unwrap(), data)
+    }
+
+    /// The content of the test files, as generated for the test images.
+    fn pattern(length: usize, seed: usize) -> Vec<u8> {
+        (0..length).map(|index| (index * 7 + seed + (index >> 8)) as u8).collect()
+    }
+
+    fn boot_image(length: usize, seed: usize) -> Vec<u8> {
+        [b"MZ".as_slice(), &pattern(length - 2, seed)].concat()
+    }
+
+    fn read_file(volume: &mut Volume, path: &str) -> Vec<u8> {
+        let node = volume.open(Node::Root, path).unwrap();
+        let mut buffer = vec![0; volume.info(node).unwrap().size as usize + 16];
+        let size = volume.read(node, 0, &mut buffer).unwrap();
+        buffer.truncate(size);
+        buffer
+    }
+
+    fn names(volume: &mut Volume, node: Node) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut position = 0;
+        while let Some((info, next)) = volume.read_directory(node, position).unwrap() {
+            names.push(info.name);
+            position = next;
+        }
+        names
+    }
+
+    #[test]
+    fn files_are_read_from_every_fat_type() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        assert_eq!(read_file(&mut volume, "\\README.TXT"), b"Patina FAT12 test volume.\r\n");
+        assert_eq!(read_file(&mut volume, "\\Long File Name.txt"), pattern(1500, 3));
+        assert_eq!(read_file(&mut volume, "\\EFI\\BOOT\\BOOTX64.EFI"), boot_image(3000, 1));
+        assert_eq!(read_file(&mut volume, "\\Documents\\empty.bin"), b"");
+
+        let (mut volume, _) = mount(FAT16_IMAGE);
+        let expected = boot_image(20000, 2);
+        assert_eq!(read_file(&mut volume, "\\EFI\\BOOT\\BOOTX64.EFI"), expected);
+        let node = volume.open(Node::Root, "\\EFI\\BOOT\\BOOTX64.EFI").unwrap();
+        let mut buffer = [0; 700];
+        assert_eq!(volume.read(node, 10000, &mut buffer).unwrap(), 700);
+        assert_eq!(buffer, expected[10000..10700]);
+        assert_eq!(volume.read(node, 19900, &mut buffer).unwrap(), 100);
+        assert_eq!(volume.read(node, 20000, &mut buffer).unwrap(), 0);
+        assert_eq!(volume.read(node, 20001, &mut buffer).unwrap_err(), EfiError::DeviceError);
+
+        let (mut volume, _) = mount(FAT32_IMAGE);
+        assert_eq!(read_file(&mut volume, "\\EFI\\BOOT\\BOOTX64.EFI"), boot_image(8000, 3));
+        assert_eq!(read_file(&mut volume, "\\Big Data.bin"), pattern(65536, 5));
+        assert_eq!(read_file(&mut volume, "\\readme.md"), b"# Patina FAT32 test volume\n");
+    }
+
+    #[test]
+    fn paths_are_resolved() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        let boot_file = volume.open(Node::Root, "\\EFI\\BOOT\\BOOTX64.EFI").unwrap();
+        assert_eq!(volume.open(Node::Root, "EFI\\BOOT\\..\\.\\boot\\bootx64.efi").unwrap(), boot_file);
+        let boot = volume.open(Node::Root, "\\EFI\\BOOT\\").unwrap();
+        assert_eq!(volume.open(boot, "BOOTX64.EFI").unwrap(), boot_file);
+        assert_eq!(volume.open(boot, "..\\..\\README.TXT").unwrap(), volume.open(boot, "\\README.TXT").unwrap());
+        assert_eq!(volume.open(boot, "..\\..").unwrap(), Node::Root);
+        assert_eq!(volume.open(boot, "").unwrap(), boot);
+
+        // Short names, case and trailing periods.
+        let long = volume.open(Node::Root, "Long File Name.txt").unwrap();
+        assert_eq!(volume.open(Node::Root, "LONGFI~1.TXT").unwrap(), long);
+        assert_eq!(volume.open(Node::Root, "long file name.TXT.").unwrap(), long);
+        assert_eq!(volume.open(Node::Root, "NOTES.TXT").unwrap(), volume.open(Node::Root, "notes.txt").unwrap());
+
+        assert_eq!(volume.open(Node::Root, "\\missing").unwrap_err(), EfiError::NotFound);
+        assert_eq!(volume.open(Node::Root, "\\OLD.TXT").unwrap_err(), EfiError::NotFound);
+        assert_eq!(volume.open(Node::Root, "\\README.TXT\\file").unwrap_err(), EfiError::NotFound);
+        assert_eq!(volume.open(boot_file, "file").unwrap_err(), EfiError::NotFound);
+        assert_eq!(volume.open(Node::Root, "..").unwrap_err(), EfiError::NotFound);
+
+        // The ".." entries of FAT32 subdirectories of the root directory hold 0, not the root cluster.
+        let (mut volume, _) = mount(FAT32_IMAGE);
+        let boot = volume.open(Node::Root, "\\EFI\\BOOT").unwrap();
+        assert_eq!(volume.open(boot, "..\\..").unwrap(), Node::Root);
+    }
+
+    #[test]
+    fn directories_are_enumerated() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        assert_eq!(
+            names(&mut volume, Node::Root),
+            ["README.TXT", "notes.txt", "Long File Name.txt", "EFI", "Documents"]
+        );
+        let documents = volume.open(Node::Root, "Documents").unwrap();
+        assert_eq!(
+            names(&mut volume, documents),
+            [".", "..", "a very long file name that needs several directory entries.txt", "empty.bin"]
+        );
+
+        let readme = volume.open(Node::Root, "README.TXT").unwrap();
+        let info = volume.info(readme).unwrap();
+        assert_eq!((info.name.as_str(), info.size, info.physical_size), ("README.TXT", 27, 512));
+        assert_eq!(info.attributes & ATTRIBUTE_DIRECTORY, 0);
+        let time = info.modification_time;
+        assert_eq!((time.year, time.month, time.day, time.hour, time.minute, time.second), (2024, 6, 15, 12, 34, 56));
+        let info = volume.info(documents).unwrap();
+        assert_eq!((info.name.as_str(), info.size), ("Documents", 512));
+        assert_eq!(info.attributes & ATTRIBUTE_DIRECTORY, ATTRIBUTE_DIRECTORY);
+        let info = volume.info(Node::Root).unwrap();
+        assert_eq!((info.name.as_str(), info.size), ("", 64 * 32));
+
+        // The entries of this directory span three clusters.
+        let (mut volume, _) = mount(FAT16_IMAGE);
+        let many = volume.open(Node::Root, "many files").unwrap();
+        let expected = [".".to_string(), "..".to_string()]
+            .into_iter()
+            .chain((0..40).map(|index| format!("file{index:02}.txt")))
+            .collect::<Vec<_>>();
+        assert_eq!(names(&mut volume, many), expected);
+    }
+
+    #[test]
+    fn files_are_created_written_and_read_back() {
+        for image in [FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE] {
+            let (mut volume, data) = mount(image);
+            let free = volume.free_space().unwrap();
+            let node = volume.create(Node::Root, "\\EFI\\New File.txt", ATTRIBUTE_HIDDEN).unwrap();
+            assert_eq!(volume.create(Node::Root, "\\EFI\\NEW FILE.TXT", 0).unwrap_err(), EfiError::AccessDenied);
+            volume.write(node, 0, &pattern(5000, 9)).unwrap();
+            volume.write(node, 7000, b"tail").unwrap();
+            volume.flush().unwrap();
+            assert!(volume.free_space().unwrap() < free);
+
+            let (mut volume, _) = mount(&data.borrow());
+            let content = read_file(&mut volume, "\\efi\\new file.txt");
+            assert_eq!(content.len(), 7004);
+            assert_eq!(content[..5000], pattern(5000, 9));
+            assert!(content[5000..7000].iter().all(|&byte| byte == 0));
+            assert_eq!(&content[7000..], b"tail");
+
+            let node = volume.open(Node::Root, "\\EFI\\New File.txt").unwrap();
+            let info = volume.info(node).unwrap();
+            assert_eq!(info.attributes, ATTRIBUTE_HIDDEN | ATTRIBUTE_ARCHIVE);
+            assert_eq!((info.modification_time.year, info.create_time.minute), (2025, 9));
+        }
+    }
+
+    #[test]
+    fn short_names_get_numeric_tails() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        let second = volume.create(Node::Root, "long file name 2.txt", 0).unwrap();
+        assert_eq!(volume.open(Node::Root, "LONGFI~2.TXT").unwrap(), second);
+        let exact = volume.create(Node::Root, "data.bin", 0).unwrap();
+        assert_eq!(names(&mut volume, Node::Root).last().unwrap(), "data.bin");
+        assert_eq!(volume.open(Node::Root, "DATA.BIN").unwrap(), exact);
+        let Node::Entry { first, index, .. } = exact else { panic!("not an entry") };
+        assert_eq!(first, index);
+    }
+
+    #[test]
+    fn directories_are_created_and_deleted() {
+        let (mut volume, data) = mount(FAT32_IMAGE);
+        let free = volume.free_space().unwrap();
+        let directory = volume.create(Node::Root, "\\Sub Dir", ATTRIBUTE_DIRECTORY).unwrap();
+        assert_eq!(names(&mut volume, directory), [".", ".."]);
+        assert_eq!(volume.open(directory, "..").unwrap(), Node::Root);
+        let inner = volume.create(directory, "inner.txt", 0).unwrap();
+        volume.write(inner, 0, &[0x5A; 100]).unwrap();
+        assert_eq!(volume.open(Node::Root, "\\Sub Dir\\..\\Sub Dir\\inner.txt").unwrap(), inner);
+
+        assert_eq!(volume.delete(directory).unwrap_err(), EfiError::AccessDenied);
+        assert_eq!(volume.delete(Node::Root).unwrap_err(), EfiError::AccessDenied);
+        volume.delete(inner).unwrap();
+        assert_eq!(volume.read(inner, 0, &mut [0; 4]).unwrap_err(), EfiError::NotFound);
+        volume.delete(directory).unwrap();
+        assert_eq!(volume.open(Node::Root, "Sub Dir").unwrap_err(), EfiError::NotFound);
+        assert_eq!(volume.free_space().unwrap(), free);
+
+        // FSInfo records the free clusters.
+        volume.flush().unwrap();
+        let fs_info = &data.borrow()[512..1024];
+        let free_count = u32::from_le_bytes(fs_info[FS_INFO_FREE_COUNT..FS_INFO_FREE_COUNT + 4].try_into().unwrap());
+        assert_eq!(free_count as u64 * volume.cluster_size() as u64, free);
+    }
+
+    #[test]
+    fn directories_grow_until_full() {
+        // Subdirectories take more clusters.
+        let (mut volume, _) = mount(FAT16_IMAGE);
+        let many = volume.open(Node::Root, "Many Files").unwrap();
+        let size = volume.info(many).unwrap().size;
+        for index in 0..100 {
+            volume.create(many, &format!("another file with a long name {index}.txt"), 0).unwrap();
+        }
+        assert!(volume.info(many).unwrap().size > size);
+        assert_eq!(names(&mut volume, many).len(), 142);
+        assert!(volume.open(many, "another file with a long name 99.txt").is_ok());
+        assert!(volume.open(many, "file39.txt").is_ok());
+
+        // The root directory of FAT12 and FAT16 volumes cannot.
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        let mut created = 0;
+        let err = loop {
+            match volume.create(Node::Root, &format!("F{created}"), 0) {
+                Ok(_) => created += 1,
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(err, EfiError::VolumeFull);
+        // Ten entries are taken, one of them deleted.
+        assert_eq!(created, 64 - 9);
+    }
+
+    #[test]
+    fn files_are_truncated_and_extended() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        let node = volume.open(Node::Root, "Long File Name.txt").unwrap();
+        let free = volume.free_space().unwrap();
+
+        volume.set_size(node, 600).unwrap();
+        assert_eq!(read_file(&mut volume, "Long File Name.txt"), pattern(600, 3));
+        assert_eq!(volume.free_space().unwrap(), free + 512);
+        volume.set_size(node, 0).unwrap();
+        assert_eq!(volume.free_space().unwrap(), free + 3 * 512);
+        assert_eq!(volume.read_entry(node).unwrap().first_cluster(false), 0);
+        volume.set_size(node, 1000).unwrap();
+        assert_eq!(read_file(&mut volume, "Long File Name.txt"), [0; 1000]);
+
+        let documents = volume.open(Node::Root, "Documents").unwrap();
+        assert_eq!(volume.set_size(documents, 0).unwrap_err(), EfiError::AccessDenied);
+    }
+
+    #[test]
+    fn files_are_renamed_and_moved() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        let readme = volume.open(Node::Root, "README.TXT").unwrap();
+        let info = volume.info(readme).unwrap();
+
+        let renamed = volume.set_info(readme, &Info { name: "Read Me First.txt".into(), ..info.clone() }).unwrap();
+        assert_eq!(volume.open(Node::Root, "read me first.txt").unwrap(), renamed);
+        assert_eq!(volume.open(Node::Root, "README.TXT").unwrap_err(), EfiError::NotFound);
+        assert_eq!(read_file(&mut volume, "Read Me First.txt"), b"Patina FAT12 test volume.\r\n");
+
+        // A directory moves with its content, and its ".." entry follows.
+        let documents = volume.open(Node::Root, "Documents").unwrap();
+        let info = volume.info(documents).unwrap();
+        let moved = volume.set_info(documents, &Info { name: "\\EFI\\Docs".into(), ..info.clone() }).unwrap();
+        assert_eq!(volume.open(Node::Root, "\\EFI\\docs").unwrap(), moved);
+        assert!(volume.open(Node::Root, "\\EFI\\Docs\\empty.bin").is_ok());
+        assert!(volume.open(moved, "..\\BOOT\\BOOTX64.EFI").is_ok());
+
+        let efi = volume.open(Node::Root, "EFI").unwrap();
+        let info = volume.info(efi).unwrap();
+        let into_itself = Info { name: "\\EFI\\Docs\\EFI".into(), ..info.clone() };
+        assert_eq!(volume.set_info(efi, &into_itself).unwrap_err(), EfiError::AccessDenied);
+        let existing = Info { name: "notes.txt".into(), ..info.clone() };
+        assert_eq!(volume.set_info(efi, &existing).unwrap_err(), EfiError::AccessDenied);
+        let file = Info { attributes: 0, ..info.clone() };
+        assert_eq!(volume.set_info(efi, &file).unwrap_err(), EfiError::AccessDenied);
+        let invalid_time = Info { modification_time: efi::Time { month: 13, ..clock().unwrap() }, ..info };
+        assert_eq!(volume.set_info(efi, &invalid_time).unwrap_err(), EfiError::InvalidParameter);
+    }
+
+    #[test]
+    fn sizes_attributes_and_times_are_set() {
+        let (mut volume, _) = mount(FAT16_IMAGE);
+        let node = volume.open(Node::Root, "startup.nsh").unwrap();
+        let info = volume.info(node).unwrap();
+        let time = efi::Time { year: 2030, month: 1, day: 2, hour: 3, minute: 4, second: 6, ..Default::default() };
+        let update = Info {
+            size: 4,
+            attributes: ATTRIBUTE_READ_ONLY,
+            modification_time: time,
+            create_time: efi::Time::default(),
+            ..info.clone()
+        };
+        assert_eq!(volume.set_info(node, &update).unwrap(), node);
+
+        let updated = volume.info(node).unwrap();
+        assert_eq!((updated.size, updated.attributes), (4, ATTRIBUTE_READ_ONLY));
+        assert_eq!((updated.modification_time.year, updated.modification_time.second), (2030, 6));
+        assert_eq!(updated.create_time.year, info.create_time.year);
+        assert_eq!(read_file(&mut volume, "startup.nsh"), b"fs0:");
+        assert_eq!(volume.set_info(Node::Root, &info).unwrap_err(), EfiError::AccessDenied);
+    }
+
+    #[test]
+    fn volume_labels_are_read_and_written() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        assert_eq!(volume.label().unwrap(), "PATINA12");
+        volume.set_label("New Label").unwrap();
+        assert_eq!(volume.label().unwrap(), "NEW LABEL");
+        volume.set_label("").unwrap();
+        assert_eq!(volume.label().unwrap(), "");
+        volume.set_label("Again").unwrap();
+        assert_eq!(volume.label().unwrap(), "AGAIN");
+        assert_eq!(volume.set_label("a.b").unwrap_err(), EfiError::InvalidParameter);
+        // The label is not a file.
+        assert_eq!(volume.open(Node::Root, "AGAIN").unwrap_err(), EfiError::NotFound);
+    }
+
+    #[test]
+    fn read_only_volumes_are_not_changed() {
+        let mut volume = Volume::mount(MemoryDisk::read_only(FAT32_IMAGE), clock).unwrap();
+        assert!(volume.is_read_only());
+        let node = volume.open(Node::Root, "Readme.md").unwrap();
+        let info = volume.info(node).unwrap();
+
+        assert_eq!(volume.create(Node::Root, "new", 0).unwrap_err(), EfiError::WriteProtected);
+        assert_eq!(volume.write(node, 0, b"x").unwrap_err(), EfiError::WriteProtected);
+        assert_eq!(volume.set_size(node, 0).unwrap_err(), EfiError::WriteProtected);
+        assert_eq!(volume.set_info(node, &info).unwrap_err(), EfiError::WriteProtected);
+        assert_eq!(volume.delete(node).unwrap_err(), EfiError::WriteProtected);
+        assert_eq!(volume.set_label("X").unwrap_err(), EfiError::WriteProtected);
+        volume.flush().unwrap();
+        assert_eq!(read_file(&mut volume, "Readme.md"), b"# Patina FAT32 test volume\n");
+    }
+
+    #[test]
+    fn corrupt_metadata_fails_cleanly() {
+        let (mut volume, _) = mount(FAT12_IMAGE);
+        let node = volume.open(Node::Root, "Long File Name.txt").unwrap();
+
+        // A chain shorter than the file.
+        volume.fat.set(volume.device.as_ref(), 41, Entry::End).unwrap();
+        let mut buffer = [0; 1500];
+        assert_eq!(volume.read(node, 0, &mut buffer).unwrap_err(), EfiError::VolumeCorrupted);
+        // A chain longer than the file.
+        volume.fat.set(volume.device.as_ref(), 41, Entry::Next(60)).unwrap();
+        volume.fat.set(volume.device.as_ref(), 60, Entry::Next(61)).unwrap();
+        volume.fat.set(volume.device.as_ref(), 61, Entry::End).unwrap();
+        assert_eq!(volume.read(node, 0, &mut buffer).unwrap_err(), EfiError::VolumeCorrupted);
+        // A loop.
+        volume.fat.set(volume.device.as_ref(), 41, Entry::Next(40)).unwrap();
+        assert_eq!(volume.read(node, 0, &mut buffer).unwrap_err(), EfiError::VolumeCorrupted);
+        // Files with broken chains can still be deleted.
+        volume.delete(node).unwrap();
+
+        // A directory looping on itself.
+        let efi = volume.open(Node::Root, "EFI").unwrap();
+        let cluster = volume.read_entry(efi).unwrap().first_cluster(false);
+        volume.fat.set(volume.device.as_ref(), cluster, Entry::Next(cluster)).unwrap();
+        assert_eq!(volume.open(Node::Root, "\\EFI\\BOOT").unwrap_err(), EfiError::VolumeCorrupted);
+        assert_eq!(volume.delete(efi).unwrap_err(), EfiError::VolumeCorrupted);
+        // It is still listed.
+        assert!(names(&mut volume, Node::Root).contains(&"EFI".to_string()));
+    }
+
+    #[test]
+    fn other_devices_are_not_mounted() {
+        let (device, _) = MemoryDisk::shared(&[0; 4096]);
+        assert_eq!(Volume::mount(device, clock).err(), Some(EfiError::Unsupported));
+    }
+
+    /// Returns a copy of `image` with its metadata mutated the way a fuzzer would: flipped bits, interesting bytes,
+    /// truncation, or a slice copied over another place.
+    fn mutate(rng: &mut StdRng, image: &[u8]) -> Vec<u8> {
+        // The boot sector, the FATs, the root directory and the first clusters.
+        let metadata = image.len().min(96 * 1024);
+        let mut image = image.to_vec();
+        match rng.gen_range(0..4) {
+            0 => {
+                for _ in 0..=rng.gen_range(0..8) {
+                    let index = rng.gen_range(0..metadata);
+                    image[index] ^= 1 << rng.gen_range(0..8);
+                }
+            }
+            1 => {
+                for _ in 0..=rng.gen_range(0..4) {
+                    let index = rng.gen_range(0..metadata);
+                    image[index] = [0x00, 0x01, 0x05, 0x0F, 0x10, 0x2E, 0xE5, 0xFF][rng.gen_range(0..8)];
+                }
+            }
+            2 => image.truncate(rng.gen_range(0..image.len())),
+            _ => {
+                let start = rng.gen_range(0..metadata);
+                let end = rng.gen_range(start..metadata.min(start + 64));
+                let slice = image[start..end].to_vec();
+                let destination = rng.gen_range(0..=metadata - slice.len());
+                image[destination..destination + slice.len()].copy_from_slice(&slice);
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn mutated_images_only_fail_with_errors() {
+        let mut rng = StdRng::seed_from_u64(0x4641_5431_3231_3633);
+        for image in [FAT12_IMAGE, FAT16_IMAGE, FAT32_IMAGE] {
+            for _ in 0..200 {
+                crate::fuzz::exercise(&mutate(&mut rng, image));
+            }
+        }
+    }
+
+    #[test]
+    fn random_images_only_fail_with_errors() {
+        let mut rng = StdRng::seed_from_u64(0x0FA7_0FA7_0FA7_0FA7);
+        for _ in 0..200 {
+            // A valid boot sector, then random FATs and directories.
+            let base = [FAT12_IMAGE, FAT32_IMAGE][rng.gen_range(0..2)];
+            let mut image = base[..512].to_vec();
+            image.extend((512..64 * 1024).map(|_| rng.r#gen::<u8>()));
+            image.resize(base.len(), 0);
+            crate::fuzz::exercise(&image);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Partition Block I/O
+//!
+//! The Block I/O protocol produced for each partition. It presents the partition as a disk of its own: block 0 is
+//! the first block of the partition, and accesses past its last block are rejected. Accesses go to the Disk I/O
+//! protocol of the disk, which takes care of the buffer alignment the disk requires.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::{ffi::c_void, slice};
+
+use patina::uefi_protocol::{device_path::DevicePath, partition_info};
+use r_efi::{efi, protocols::block_io};
+
+use crate::{disk::Disk, partition::Partition};
+
+/// The protocols of a partition, installed on its child handle.
+#[repr(C)]
+pub(crate) struct Child {
+    // The Block I/O protocol must be first, so that the protocol interface is also the child.
+    block_io: block_io::Protocol,
+    media: block_io::Media,
+    disk: Disk,
+    /// The byte offset of the partition on the disk.
+    start: u64,
+    partition_info: partition_info::Protocol,
+    device_path: Box<DevicePath>,
+    system: bool,
+}
+
+impl Child {
+    /// Creates the protocols of `partition` of `disk`, whose device path is `disk_device_path`.
+    pub(crate) fn new(disk: Disk, partition: &Partition, disk_device_path: &DevicePath) -> Box<Self> {
+        let disk_media = disk.media();
+        let mut child = Box::new(Self {
+            block_io: block_io::Protocol {
+                revision: block_io::REVISION,
+                media: core::ptr::null(),
+                reset,
+                read_blocks,
+                write_blocks,
+                flush_blocks,
+            },
+            media: block_io::Media {
+                logical_partition: true,
+                last_block: partition.size - 1,
+                lowest_aligned_lba: 0,
+                ..disk_media
+            },
+            disk,
+            start: partition.start * disk_media.block_size as u64,
+            partition_info: partition.info,
+            device_path: partition.device_path(disk_device_path).into_box_device_path(),
+            system: partition.is_system(),
+        });
+        child.block_io.media = &child.media;
+        child
+    }
+
+    /// Returns the child whose Block I/O protocol is `block_io`.
+    ///
+    /// # Safety
+    ///
+    /// `block_io` must be the Block I/O protocol of a child.
+    pub(crate) unsafe fn from_block_io<'a>(block_io: *mut block_io::Protocol) -> &'a mut Self {
+        // SAFETY: The Block I/O protocol is the first field of the child, per the caller.
+        unsafe { &mut *(block_io as *mut Self) }
+    }
+
+    pub(crate) fn block_io_ptr(&mut self) -> *mut c_void {
+        &mut self.block_io as *mut block_io::Protocol as *mut c_void
+    }
+
+    pub(crate) fn partition_info_ptr(&mut self) -> *mut c_void {
+        &mut self.partition_info as *mut partition_info::Protocol as *mut c_void
+    }
+
+    pub(crate) fn device_path_ptr(&self) -> *mut c_void {
+        self.device_path.as_bytes().as_ptr() as *mut c_void
+    }
+
+    /// Returns whether the partition is an EFI system partition.
+    pub(crate) fn is_system(&self) -> bool {
+        self.system
+    }
+
+    /// Returns the byte offset on the disk of an access of `size` bytes at `lba` of the partition.
+    fn offset(&self, lba: efi::Lba, size: usize, buffer: *mut c_void) -> Result<u64, efi::Status> {
+        let block_size = self.media.block_size as u64;
+        if buffer.is_null() {
+            return Err(efi::Status::INVALID_PARAMETER);
+        }
+        if !(size as u64).is_multiple_of(block_size) {
+            return Err(efi::Status::BAD_BUFFER_SIZE);
+        }
+        let blocks = size as u64 / block_size;
+        match lba.checked_add(blocks) {
+            Some(end) if end <= self.media.last_block + 1 => Ok(self.start + lba * block_size),
+            _ => Err(efi::Status::INVALID_PARAMETER),
+        }
+    }
+}
+
+extern "efiapi" fn reset(this: *mut block_io::Protocol, extended_verification: efi::Boolean) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a child.
+    let child = unsafe { Child::from_block_io(this) };
+    let disk = child.disk.block_io().as_ptr();
+    // SAFETY: The Block I/O protocol of the disk outlives its children.
+    unsafe { ((*disk).reset)(disk, extended_verification) }
+}
+
+extern "efiapi" fn read_blocks(
+    this: *mut block_io::Protocol,
+    media_id: u32,
+    lba: efi::Lba,
+    size: usize,
+    buffer: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a child.
+    let child = unsafe { Child::from_block_io(this) };
+    match child.offset(lba, size, buffer) {
+        Ok(offset) => {
+            // SAFETY: The caller provides a buffer of `size` bytes.
+            let buffer = unsafe { slice::from_raw_parts_mut(buffer as *mut u8, size) };
+            child.disk.read(media_id, offset, buffer).map_or_else(Into::into, |()| efi::Status::SUCCESS)
+        }
+        Err(status) => status,
+    }
+}
+
+extern "efiapi" fn write_blocks(
+    this: *mut block_io::Protocol,
+    media_id: u32,
+    lba: efi::Lba,
+    size: usize,
+    buffer: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a child.
+    let child = unsafe { Child::from_block_io(this) };
+    if child.media.read_only {
+        return efi::Status::WRITE_PROTECTED;
+    }
+    match child.offset(lba, size, buffer) {
+        Ok(offset) => {
+            // SAFETY: The caller provides a buffer of `size` bytes.
+            let data = unsafe { slice::from_raw_parts(buffer as *const u8, size) };
+            child.disk.write(media_id, offset, data).map_or_else(Into::into, |()| efi::Status::SUCCESS)
+        }
+        Err(status) => status,
+    }
+}
+
+extern "efiapi" fn flush_blocks(this: *mut block_io::Protocol) -> efi::Status {
+    // SAFETY: The protocol is only installed as part of a child.
+    let child = unsafe { Child::from_block_io(this) };
+    let disk = child.disk.block_io().as_ptr();
+    // SAFETY: The Block I/O protocol of the disk outlives its children.
+    unsafe { ((*disk).flush_blocks)(disk) }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        disk::mock::{GPT_IMAGE, MEDIA_ID, MockDisk},
+        partition,
+    };
+    use alloc::vec;
+    use core::iter;
+    use patina::uefi_protocol::device_path::{DevicePathBuf, nodes::Acpi};
+
+    #[allow(clippy::vec_box)] // Children point to their own media, so they must stay boxed.
+    fn children(disk: &mut MockDisk) -> alloc::vec::Vec<Box<Child>> {
+        let disk_device_path = DevicePathBuf::from_device_path_node_iter(iter::once(Acpi::new_pci_root(0)));
+        let disk = disk.disk();
+        partition::find(&disk).unwrap().iter().map(|p| Child::new(disk, p, &disk_device_path)).collect()
+    }
+
+    fn read(child: &mut Child, lba: u64, size: usize) -> Result<alloc::vec::Vec<u8>, efi::Status> {
+        let mut buffer = vec![0u8; size];
+        let block_io = child.block_io_ptr() as *mut block_io::Protocol;
+        // SAFETY: Test code - the protocol belongs to the child and the buffer is `size` bytes.
+        let status = unsafe { ((*block_io).read_blocks)(block_io, MEDIA_ID, lba, size, buffer.as_mut_ptr() as _) };
+        if status == efi::Status::SUCCESS { Ok(buffer) } else { Err(status) }
+    }
+
+    #[test]
+    fn partition_media_covers_the_partition() {
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let children = children(&mut disk);
+
+        assert_eq!(children.len(), 2);
+        // SAFETY: Test code - the media pointer refers to the boxed child.
+        let media = unsafe { *children[1].block_io.media };
+        assert!(media.logical_partition);
+        assert_eq!(media.last_block, 19);
+        assert_eq!(media.block_size, 512);
+        assert_eq!(media.media_id, MEDIA_ID);
+        assert!(children[0].is_system());
+        assert!(!children[1].is_system());
+    }
+
+    #[test]
+    fn partition_blocks_are_read_from_the_partition() {
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let mut children = children(&mut disk);
+
+        assert!(read(&mut children[0], 0, 512).unwrap().starts_with(b"GPT PARTITION 1"));
+        assert!(read(&mut children[1], 0, 1024).unwrap().starts_with(b"GPT PARTITION 3"));
+        assert_eq!(read(&mut children[1], 19, 512).unwrap(), &GPT_IMAGE[79 * 512..80 * 512]);
+    }
+
+    #[test]
+    fn partition_accesses_are_checked() {
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let mut children = children(&mut disk);
+
+        assert_eq!(read(&mut children[1], 19, 1024), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(read(&mut children[1], u64::MAX, 512), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(read(&mut children[1], 0, 100), Err(efi::Status::BAD_BUFFER_SIZE));
+        assert_eq!(read(&mut children[1], 0, 0), Ok(vec![]));
+
+        let block_io = children[1].block_io_ptr() as *mut block_io::Protocol;
+        // SAFETY: Test code - the buffer is null on purpose.
+        let status = unsafe { ((*block_io).read_blocks)(block_io, MEDIA_ID, 0, 512, core::ptr::null_mut()) };
+        assert_eq!(status, efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn partition_blocks_are_written_to_the_partition() {
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let mut children = children(&mut disk);
+
+        let mut data = vec![0x5A_u8; 1024];
+        let block_io = children[0].block_io_ptr() as *mut block_io::Protocol;
+        // SAFETY: Test code - the protocol belongs to the child and the buffer is 1024 bytes.
+        let status = unsafe { ((*block_io).write_blocks)(block_io, MEDIA_ID, 2, 1024, data.as_mut_ptr() as _) };
+        assert_eq!(status, efi::Status::SUCCESS);
+        drop(children);
+        assert!(disk.data[36 * 512..38 * 512].iter().all(|&byte| byte == 0x5A));
+        assert_eq!(disk.data[38 * 512..], GPT_IMAGE[38 * 512..]);
+
+        disk.media_mut().read_only = true;
+        let mut children = self::children(&mut disk);
+        let block_io = children[0].block_io_ptr() as *mut block_io::Protocol;
+        // SAFETY: Test code - the protocol belongs to the child and the buffer is 1024 bytes.
+        let status = unsafe { ((*block_io).write_blocks)(block_io, MEDIA_ID, 2, 1024, data.as_mut_ptr() as _) };
+        assert_eq!(status, efi::Status::WRITE_PROTECTED);
+    }
+}
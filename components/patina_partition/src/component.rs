@@ -0,0 +1,50 @@
+//! Partition Driver Component
+//!
+//! Installs the driver binding protocol of the partition driver, through which the DXE core connects it to disks.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::{ptr, sync::atomic::Ordering};
+
+use patina::{
+    boot_services::StandardBootServices,
+    component::component,
+    driver_binding::UefiDriverBinding,
+    error::{EfiError, Result},
+};
+
+use crate::driver::{DRIVER_BINDING_HANDLE, PartitionDriverBinding};
+
+/// Produces the GPT and MBR partitions of the disks connected to it.
+///
+/// The driver binding is installed on a handle of its own. Partitions appear once the controllers of the disks are
+/// connected, for example when the boot manager connects every controller.
+///
+/// ```rust,ignore
+/// commands.add_component(PartitionDriver);
+/// ```
+#[derive(Default)]
+pub struct PartitionDriver;
+
+#[component]
+impl PartitionDriver {
+    #[coverage(off)] // Component integration - the driver binding it installs is tested directly.
+    fn entry_point(self, boot_services: StandardBootServices) -> Result<()> {
+        if !DRIVER_BINDING_HANDLE.load(Ordering::Acquire).is_null() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        let boot_services: &'static StandardBootServices = Box::leak(Box::new(boot_services));
+        let mut driver_binding = UefiDriverBinding::new(PartitionDriverBinding, ptr::null_mut(), boot_services);
+        driver_binding.install().map_err(EfiError::from)?;
+        DRIVER_BINDING_HANDLE.store(driver_binding.driver_binding_handle(), Ordering::Release);
+
+        log::info!(target: "partition", "Partition driver installed.");
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+//! Disk Access
+//!
+//! Wraps the Block I/O and Disk I/O protocols of a disk. Partition tables are read and repaired through Disk I/O,
+//! which takes care of the buffer alignment the Block I/O protocol of the device may require, and the Block I/O
+//! protocol describes the media.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+use core::{ffi::c_void, ptr::NonNull};
+
+use patina::error::{EfiError, Result};
+use r_efi::protocols::{block_io, disk_io};
+
+/// The Block I/O and Disk I/O protocols of a disk.
+#[derive(Clone, Copy)]
+pub(crate) struct Disk {
+    block_io: NonNull<block_io::Protocol>,
+    disk_io: NonNull<disk_io::Protocol>,
+}
+
+impl Disk {
+    /// Wraps the protocols of a disk.
+    ///
+    /// # Safety
+    ///
+    /// Both protocols must belong to the same device and stay valid, along with the media of the Block I/O protocol,
+    /// while the disk is used.
+    pub(crate) unsafe fn new(block_io: NonNull<block_io::Protocol>, disk_io: NonNull<disk_io::Protocol>) -> Self {
+        Self { block_io, disk_io }
+    }
+
+    /// Returns the Block I/O protocol of the disk.
+    pub(crate) fn block_io(&self) -> NonNull<block_io::Protocol> {
+        self.block_io
+    }
+
+    /// Returns the current media of the disk.
+    pub(crate) fn media(&self) -> block_io::Media {
+        // SAFETY: new requires the Block I/O protocol and its media to be valid.
+        unsafe { *self.block_io.as_ref().media }
+    }
+
+    /// Reads `count` blocks from `lba`.
+    pub(crate) fn read_blocks(&self, lba: u64, count: usize) -> Result<Vec<u8>> {
+        let media = self.media();
+        let offset = lba.checked_mul(media.block_size as u64).ok_or(EfiError::InvalidParameter)?;
+        let mut buffer = vec![0; count.checked_mul(media.block_size as usize).ok_or(EfiError::InvalidParameter)?];
+        self.read(media.media_id, offset, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Writes `data`, a whole number of blocks, to `lba`.
+    pub(crate) fn write_blocks(&self, lba: u64, data: &[u8]) -> Result<()> {
+        let media = self.media();
+        let offset = lba.checked_mul(media.block_size as u64).ok_or(EfiError::InvalidParameter)?;
+        self.write(media.media_id, offset, data)
+    }
+
+    /// Reads `buffer.len()` bytes at byte `offset` of the media `media_id`.
+    pub(crate) fn read(&self, media_id: u32, offset: u64, buffer: &mut [u8]) -> Result<()> {
+        let disk_io = self.disk_io.as_ptr();
+        // SAFETY: new requires the Disk I/O protocol to be valid, and the buffer is valid for its length.
+        let status = unsafe {
+            ((*disk_io).read_disk)(disk_io, media_id, offset, buffer.len(), buffer.as_mut_ptr() as *mut c_void)
+        };
+        EfiError::status_to_result(status)
+    }
+
+    /// Writes `data` at byte `offset` of the media `media_id`.
+    pub(crate) fn write(&self, media_id: u32, offset: u64, data: &[u8]) -> Result<()> {
+        let disk_io = self.disk_io.as_ptr();
+        // SAFETY: new requires the Disk I/O protocol to be valid, and the protocol only reads from the buffer.
+        let status =
+            unsafe { ((*disk_io).write_disk)(disk_io, media_id, offset, data.len(), data.as_ptr() as *mut c_void) };
+        EfiError::status_to_result(status)
+    }
+}
+
+/// A disk image in memory, served through Block I/O and Disk I/O protocols.
+#[cfg(test)]
+#[coverage(off)]
+pub(crate) mod mock {
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{ffi::c_void, mem, ptr::NonNull, slice};
+
+    use r_efi::{
+        efi,
+        protocols::{block_io, disk_io},
+    };
+
+    use super::Disk;
+
+    /// The GPT test image, described in `resources/test/disk/README.md`.
+    pub(crate) const GPT_IMAGE: &[u8] = include_bytes!("../resources/test/disk/gpt.img");
+    /// The MBR test image, described in `resources/test/disk/README.md`.
+    pub(crate) const MBR_IMAGE: &[u8] = include_bytes!("../resources/test/disk/mbr.img");
+
+    /// The media ID of mock disks.
+    pub(crate) const MEDIA_ID: u32 = 7;
+
+    #[repr(C)]
+    pub(crate) struct MockDisk {
+        block_io: block_io::Protocol,
+        disk_io: disk_io::Protocol,
+        media: block_io::Media,
+        /// The content of the disk.
+        pub(crate) data: Vec<u8>,
+        /// The number of successful Disk I/O writes.
+        pub(crate) writes: usize,
+    }
+
+    impl MockDisk {
+        /// Creates a disk holding `image`, with blocks of 512 bytes.
+        pub(crate) fn new(image: &[u8]) -> Box<Self> {
+            let mut disk = Box::new(Self {
+                block_io: block_io::Protocol {
+                    revision: block_io::REVISION,
+                    media: core::ptr::null(),
+                    reset,
+                    read_blocks,
+                    write_blocks,
+                    flush_blocks,
+                },
+                disk_io: disk_io::Protocol { revision: disk_io::REVISION, read_disk, write_disk },
+                media: block_io::Media {
+                    media_id: MEDIA_ID,
+                    removable_media: false,
+                    media_present: true,
+                    logical_partition: false,
+                    read_only: false,
+                    write_caching: false,
+                    block_size: 512,
+                    io_align: 0,
+                    last_block: (image.len() / 512 - 1) as u64,
+                    lowest_aligned_lba: 0,
+                    logical_blocks_per_physical_block: 0,
+                    optimal_transfer_length_granularity: 0,
+                },
+                data: image.to_vec(),
+                writes: 0,
+            });
+            disk.block_io.media = &disk.media;
+            disk
+        }
+
+        pub(crate) fn media_mut(&mut self) -> &mut block_io::Media {
+            &mut self.media
+        }
+
+        pub(crate) fn block_io_ptr(&mut self) -> *mut block_io::Protocol {
+            &mut self.block_io
+        }
+
+        pub(crate) fn disk_io_ptr(&mut self) -> *mut disk_io::Protocol {
+            &mut self.disk_io
+        }
+
+        pub(crate) fn disk(&mut self) -> Disk {
+            // SAFETY: Both protocols belong to this boxed disk, which outlives the wrapper in the tests.
+            unsafe { Disk::new(NonNull::from(&mut self.block_io), NonNull::from(&mut self.disk_io)) }
+        }
+
+        fn from_block_io<'a>(this: *mut block_io::Protocol) -> &'a mut Self {
+            // SAFETY: The Block I/O protocol is the first field of the disk.
+            unsafe { &mut *(this as *mut Self) }
+        }
+
+        fn from_disk_io<'a>(this: *mut disk_io::Protocol) -> &'a mut Self {
+            // SAFETY: The Disk I/O protocol is a field of the disk.
+            unsafe { &mut *((this as *mut u8).sub(mem::offset_of!(Self, disk_io)) as *mut Self) }
+        }
+
+        fn access(&self, media_id: u32, offset: u64, size: usize) -> Result<core::ops::Range<usize>, efi::Status> {
+            if media_id != self.media.media_id {
+                return Err(efi::Status::MEDIA_CHANGED);
+            }
+            let start = offset as usize;
+            match start.checked_add(size) {
+                Some(end) if end <= self.data.len() => Ok(start..end),
+                _ => Err(efi::Status::INVALID_PARAMETER),
+            }
+        }
+
+        fn read(&mut self, media_id: u32, offset: u64, size: usize, buffer: *mut c_void) -> efi::Status {
+            match self.access(media_id, offset, size) {
+                Ok(range) => {
+                    // SAFETY: The caller provides a buffer of `size` bytes.
+                    unsafe { slice::from_raw_parts_mut(buffer as *mut u8, size) }.copy_from_slice(&self.data[range]);
+                    efi::Status::SUCCESS
+                }
+                Err(status) => status,
+            }
+        }
+
+        fn write(&mut self, media_id: u32, offset: u64, size: usize, buffer: *mut c_void) -> efi::Status {
+            if self.media.read_only {
+                return efi::Status::WRITE_PROTECTED;
+            }
+            match self.access(media_id, offset, size) {
+                Ok(range) => {
+                    // SAFETY: The caller provides a buffer of `size` bytes.
+                    self.data[range].copy_from_slice(unsafe { slice::from_raw_parts(buffer as *const u8, size) });
+                    self.writes += 1;
+                    efi::Status::SUCCESS
+                }
+                Err(status) => status,
+            }
+        }
+    }
+
+    extern "efiapi" fn reset(_this: *mut block_io::Protocol, _extended_verification: efi::Boolean) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_blocks(
+        this: *mut block_io::Protocol,
+        media_id: u32,
+        lba: efi::Lba,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = MockDisk::from_block_io(this);
+        disk.read(media_id, lba * disk.media.block_size as u64, size, buffer)
+    }
+
+    extern "efiapi" fn write_blocks(
+        this: *mut block_io::Protocol,
+        media_id: u32,
+        lba: efi::Lba,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        let disk = MockDisk::from_block_io(this);
+        disk.write(media_id, lba * disk.media.block_size as u64, size, buffer)
+    }
+
+    extern "efiapi" fn flush_blocks(_this: *mut block_io::Protocol) -> efi::Status {
+        efi::Status::SUCCESS
+    }
+
+    extern "efiapi" fn read_disk(
+        this: *mut disk_io::Protocol,
+        media_id: u32,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        MockDisk::from_disk_io(this).read(media_id, offset, size, buffer)
+    }
+
+    extern "efiapi" fn write_disk(
+        this: *mut disk_io::Protocol,
+        media_id: u32,
+        offset: u64,
+        size: usize,
+        buffer: *mut c_void,
+    ) -> efi::Status {
+        MockDisk::from_disk_io(this).write(media_id, offset, size, buffer)
+    }
+}
@@ -0,0 +1,469 @@
+//! Partition Driver Binding
+//!
+//! The UEFI driver model side of the partition driver. It manages disks that have a Block I/O and a Disk I/O
+//! protocol, opening the Disk I/O protocol `BY_DRIVER`, and produces a child handle for each partition with a
+//! device path, a Block I/O protocol and a Partition Information protocol, plus the EFI system partition tag
+//! protocol on EFI system partitions. Each child opens the Disk I/O protocol of its disk `BY_CHILD_CONTROLLER`.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use patina::{
+    boot_services::BootServices,
+    driver_binding::DriverBinding,
+    uefi_protocol::{device_path::DevicePath, partition_info},
+};
+use r_efi::{
+    efi,
+    protocols::{block_io, device_path, disk_io},
+};
+
+use crate::{
+    child::Child,
+    disk::Disk,
+    gpt::EFI_SYSTEM_PARTITION_GUID,
+    partition::{self, Partition},
+};
+
+/// The handle the driver binding protocol is installed on, the agent that opens the protocols of the disks.
+pub(crate) static DRIVER_BINDING_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+/// Produces the partitions of disks.
+pub(crate) struct PartitionDriverBinding;
+
+fn agent() -> efi::Handle {
+    DRIVER_BINDING_HANDLE.load(Ordering::Acquire)
+}
+
+/// Opens `protocol` on `controller` with the driver as the agent.
+fn open<T: BootServices>(
+    boot_services: &T,
+    controller: efi::Handle,
+    protocol: &efi::Guid,
+    attributes: u32,
+) -> Result<*mut c_void, efi::Status> {
+    // SAFETY: The interface is returned as a raw pointer, which the caller casts to the protocol it opened.
+    unsafe { boot_services.open_protocol_unchecked(controller, protocol, agent(), controller, attributes) }
+}
+
+impl DriverBinding for PartitionDriverBinding {
+    fn driver_binding_supported<T: BootServices + 'static>(
+        &self,
+        boot_services: &'static T,
+        controller: efi::Handle,
+        remaining_device_path: Option<NonNull<device_path::Protocol>>,
+    ) -> Result<bool, efi::Status> {
+        // Only a partition, or no partition at all, can be asked for.
+        if let Some(remaining) = remaining_device_path {
+            // SAFETY: A remaining device path points to at least one node header.
+            let node = unsafe { remaining.as_ref() };
+            let partition =
+                node.r#type == device_path::TYPE_MEDIA && node.sub_type == device_path::Media::SUBTYPE_HARDDRIVE;
+            if !partition && node.r#type != device_path::TYPE_END {
+                return Ok(false);
+            }
+        }
+
+        // Partitions are not partitioned further.
+        let block_io = open(boot_services, controller, &block_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?
+            as *const block_io::Protocol;
+        // SAFETY: The Block I/O protocol and its media are valid while the protocol is installed.
+        if unsafe { (*(*block_io).media).logical_partition } {
+            return Ok(false);
+        }
+        open(boot_services, controller, &device_path::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?;
+
+        // Opening the Disk I/O protocol by driver fails if another driver, or this one, manages the disk.
+        open(boot_services, controller, &disk_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_BY_DRIVER)?;
+        boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), controller)?;
+        Ok(true)
+    }
+
+    fn driver_binding_start<T: BootServices + 'static>(
+        &mut self,
+        boot_services: &'static T,
+        controller: efi::Handle,
+        remaining_device_path: Option<NonNull<device_path::Protocol>>,
+    ) -> Result<(), efi::Status> {
+        let block_io = open(boot_services, controller, &block_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?;
+        let device_path =
+            open(boot_services, controller, &device_path::PROTOCOL_GUID, efi::OPEN_PROTOCOL_GET_PROTOCOL)?;
+        let disk_io = open(boot_services, controller, &disk_io::PROTOCOL_GUID, efi::OPEN_PROTOCOL_BY_DRIVER)?;
+        let close_disk_io = |status: efi::Status| {
+            let _ = boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), controller);
+            status
+        };
+
+        let (Some(block_io), Some(disk_io)) = (NonNull::new(block_io as _), NonNull::new(disk_io as _)) else {
+            return Err(close_disk_io(efi::Status::DEVICE_ERROR));
+        };
+        // SAFETY: Both protocols are installed on the disk, and stay valid while the Disk I/O protocol is opened by
+        // the driver, which is until the children are stopped.
+        let disk = unsafe { Disk::new(block_io, disk_io) };
+        // SAFETY: The device path protocol of the disk points to a device path.
+        let disk_device_path = unsafe { DevicePath::try_from_ptr(device_path as *const u8) }
+            .map_err(|_| close_disk_io(efi::Status::DEVICE_ERROR))?;
+
+        // Keep managing a disk without media, so that the partitions are found once media is inserted and the Block
+        // I/O protocol is reinstalled.
+        if !disk.media().media_present {
+            return Ok(());
+        }
+        // SAFETY: A remaining device path points to at least one node header.
+        if remaining_device_path.is_some_and(|remaining| unsafe { remaining.as_ref() }.r#type == device_path::TYPE_END)
+        {
+            return Ok(());
+        }
+
+        let partitions = partition::find(&disk).map_err(|err| close_disk_io(err.into()))?;
+        for partition in &partitions {
+            if let Err(status) = start_child(boot_services, controller, disk, partition, disk_device_path) {
+                log::error!(target: "partition", "Failed to produce partition {}: {status:?}", partition.number);
+            }
+        }
+        log::info!(target: "partition", "Found {} partitions on {}.", partitions.len(), disk_device_path);
+        Ok(())
+    }
+
+    fn driver_binding_stop<T: BootServices + 'static>(
+        &mut self,
+        boot_services: &'static T,
+        controller: efi::Handle,
+        number_of_children: usize,
+        child_handle_buffer: Option<NonNull<efi::Handle>>,
+    ) -> Result<(), efi::Status> {
+        if number_of_children == 0 {
+            return boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), controller);
+        }
+
+        let children = child_handle_buffer.ok_or(efi::Status::INVALID_PARAMETER)?;
+        // SAFETY: The caller provides a buffer of number_of_children handles.
+        let children = unsafe { slice::from_raw_parts(children.as_ptr(), number_of_children) };
+        let mut result = Ok(());
+        for &child in children {
+            if let Err(status) = stop_child(boot_services, controller, child) {
+                result = Err(status);
+            }
+        }
+        result
+    }
+}
+
+/// Installs the protocols of `partition` on a new child handle of `controller`.
+fn start_child<T: BootServices>(
+    boot_services: &T,
+    controller: efi::Handle,
+    disk: Disk,
+    partition: &Partition,
+    disk_device_path: &DevicePath,
+) -> Result<(), efi::Status> {
+    let child = Box::leak(Child::new(disk, partition, disk_device_path));
+
+    let mut installed: [Option<&'static efi::Guid>; 4] = [None; 4];
+    let mut handle = None;
+    let mut result = Ok(());
+    let protocols = [
+        (Some(&device_path::PROTOCOL_GUID), child.device_path_ptr()),
+        (Some(&partition_info::PROTOCOL_GUID), child.partition_info_ptr()),
+        (child.is_system().then_some(&EFI_SYSTEM_PARTITION_GUID), ptr::null_mut()),
+        // Installed last, as it is what other drivers bind to.
+        (Some(&block_io::PROTOCOL_GUID), child.block_io_ptr()),
+    ];
+    for (slot, (protocol, interface)) in installed.iter_mut().zip(protocols) {
+        let Some(protocol) = protocol else { continue };
+        // SAFETY: Each interface is the one of its protocol, and lives in the leaked child until it is stopped.
+        match unsafe { boot_services.install_protocol_interface_unchecked(handle, protocol, interface) } {
+            Ok(new_handle) => {
+                handle = Some(new_handle);
+                *slot = Some(protocol);
+            }
+            Err(status) => {
+                result = Err(status);
+                break;
+            }
+        }
+    }
+
+    if let (Ok(()), Some(handle)) = (result, handle) {
+        result = open_by_child(boot_services, controller, handle);
+    }
+    if result.is_err() {
+        for (protocol, interface) in installed.iter().zip(protocols).filter_map(|(i, (_, p))| Some(((*i)?, p))) {
+            // SAFETY: The protocol was installed above with this interface.
+            let _ =
+                unsafe { boot_services.uninstall_protocol_interface_unchecked(handle.unwrap(), protocol, interface) };
+        }
+        // SAFETY: The child was leaked above and none of its protocols are installed.
+        drop(unsafe { Box::from_raw(child) });
+    }
+    result
+}
+
+/// Opens the Disk I/O protocol of `controller` by `child`, which ties the child to the disk.
+fn open_by_child<T: BootServices>(
+    boot_services: &T,
+    controller: efi::Handle,
+    child: efi::Handle,
+) -> Result<(), efi::Status> {
+    // SAFETY: The interface is not used.
+    unsafe {
+        boot_services.open_protocol_unchecked(
+            controller,
+            &disk_io::PROTOCOL_GUID,
+            agent(),
+            child,
+            efi::OPEN_PROTOCOL_BY_CHILD_CONTROLLER,
+        )
+    }
+    .map(|_| ())
+}
+
+/// Uninstalls the protocols of the child handle `handle` of `controller` and frees the child.
+fn stop_child<T: BootServices>(
+    boot_services: &T,
+    controller: efi::Handle,
+    handle: efi::Handle,
+) -> Result<(), efi::Status> {
+    // SAFETY: The interface is the Block I/O protocol of a child, as only children are passed to stop.
+    let block_io = unsafe {
+        boot_services.open_protocol_unchecked(
+            handle,
+            &block_io::PROTOCOL_GUID,
+            agent(),
+            controller,
+            efi::OPEN_PROTOCOL_GET_PROTOCOL,
+        )
+    }?;
+    // SAFETY: Children are the handles this driver installed a child's Block I/O protocol on.
+    let child = unsafe { Child::from_block_io(block_io as *mut block_io::Protocol) };
+
+    boot_services.close_protocol(controller, &disk_io::PROTOCOL_GUID, agent(), handle)?;
+    // The Block I/O protocol goes first, as the drivers that use the partition must release it.
+    // SAFETY: The protocol was installed with this interface when the child was started.
+    let uninstalled =
+        unsafe { boot_services.uninstall_protocol_interface_unchecked(handle, &block_io::PROTOCOL_GUID, block_io) };
+    if let Err(status) = uninstalled {
+        let _ = open_by_child(boot_services, controller, handle);
+        return Err(status);
+    }
+
+    let mut protocols = [
+        (&device_path::PROTOCOL_GUID, child.device_path_ptr()),
+        (&partition_info::PROTOCOL_GUID, child.partition_info_ptr()),
+    ]
+    .to_vec();
+    if child.is_system() {
+        protocols.push((&EFI_SYSTEM_PARTITION_GUID, ptr::null_mut()));
+    }
+    for (protocol, interface) in protocols {
+        // SAFETY: The protocol was installed with this interface when the child was started.
+        unsafe { boot_services.uninstall_protocol_interface_unchecked(handle, protocol, interface) }?;
+    }
+
+    // SAFETY: The child was leaked when it was started, and none of its protocols are installed anymore.
+    drop(unsafe { Box::from_raw(child as *mut Child) });
+    Ok(())
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::disk::mock::{GPT_IMAGE, MBR_IMAGE, MockDisk};
+    use alloc::vec::Vec;
+    use core::iter;
+    use patina::{
+        boot_services::MockBootServices,
+        uefi_protocol::device_path::{DevicePathBuf, nodes::Acpi},
+    };
+    use std::sync::{Arc, Mutex};
+
+    const CONTROLLER: efi::Handle = 0x1000 as efi::Handle;
+    const FIRST_CHILD: usize = 0x2000;
+
+    /// The protocols installed on each child handle, by handle.
+    type Installed = Arc<Mutex<Vec<(usize, efi::Guid, usize)>>>;
+
+    struct Fixture {
+        disk: Box<MockDisk>,
+        device_path: DevicePathBuf,
+    }
+
+    impl Fixture {
+        fn new(image: &[u8]) -> Self {
+            DRIVER_BINDING_HANDLE.store(0x10 as efi::Handle, Ordering::Release);
+            Self {
+                disk: MockDisk::new(image),
+                device_path: DevicePathBuf::from_device_path_node_iter(iter::once(Acpi::new_pci_root(0))),
+            }
+        }
+
+        /// Returns boot services serving the disk on CONTROLLER, and recording the protocols installed.
+        fn boot_services(&mut self, installed: &Installed) -> &'static MockBootServices {
+            let block_io = self.disk.block_io_ptr() as usize;
+            let disk_io = self.disk.disk_io_ptr() as usize;
+            let device_path = self.device_path.as_bytes().as_ptr() as usize;
+
+            let mut boot_services = MockBootServices::new();
+            let children = installed.clone();
+            boot_services.expect_open_protocol_unchecked().returning(move |handle, protocol, _, _, _| {
+                let interface = match *protocol {
+                    _ if handle != CONTROLLER => children
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .find(|(h, p, _)| *h == handle as usize && p == protocol)
+                        .map(|(_, _, interface)| *interface)
+                        .ok_or(efi::Status::UNSUPPORTED)?,
+                    block_io::PROTOCOL_GUID => block_io,
+                    disk_io::PROTOCOL_GUID => disk_io,
+                    device_path::PROTOCOL_GUID => device_path,
+                    _ => return Err(efi::Status::UNSUPPORTED),
+                };
+                Ok(interface as *mut c_void)
+            });
+            boot_services.expect_close_protocol().returning(|_, _, _, _| Ok(()));
+            let children = installed.clone();
+            boot_services.expect_install_protocol_interface_unchecked().returning(
+                move |handle, protocol, interface| {
+                    let mut children = children.lock().unwrap();
+                    let handle = handle.map_or(FIRST_CHILD + children.len() * 0x10, |handle| handle as usize);
+                    children.push((handle, *protocol, interface as usize));
+                    Ok(handle as efi::Handle)
+                },
+            );
+            let children = installed.clone();
+            boot_services.expect_uninstall_protocol_interface_unchecked().returning(move |handle, protocol, _| {
+                children.lock().unwrap().retain(|(h, p, _)| *h != handle as usize || p != protocol);
+                Ok(())
+            });
+            Box::leak(Box::new(boot_services))
+        }
+    }
+
+    fn protocols_of(installed: &Installed, handle: usize) -> Vec<(efi::Guid, usize)> {
+        installed.lock().unwrap().iter().filter(|(h, _, _)| *h == handle).map(|(_, p, i)| (*p, *i)).collect()
+    }
+
+    #[test]
+    fn disks_are_supported_but_not_partitions() {
+        let mut fixture = Fixture::new(GPT_IMAGE);
+        let boot_services = fixture.boot_services(&Installed::default());
+        let driver = PartitionDriverBinding;
+        assert_eq!(driver.driver_binding_supported(boot_services, CONTROLLER, None), Ok(true));
+
+        let mut end = device_path::Protocol { r#type: device_path::TYPE_END, sub_type: 0xFF, length: [4, 0] };
+        assert_eq!(driver.driver_binding_supported(boot_services, CONTROLLER, Some(NonNull::from(&mut end))), Ok(true));
+        let mut pci = device_path::Protocol { r#type: device_path::TYPE_HARDWARE, sub_type: 1, length: [6, 0] };
+        assert_eq!(
+            driver.driver_binding_supported(boot_services, CONTROLLER, Some(NonNull::from(&mut pci))),
+            Ok(false)
+        );
+
+        fixture.disk.media_mut().logical_partition = true;
+        assert_eq!(driver.driver_binding_supported(boot_services, CONTROLLER, None), Ok(false));
+    }
+
+    #[test]
+    fn gpt_partitions_are_produced_and_stopped() {
+        let mut fixture = Fixture::new(GPT_IMAGE);
+        let installed = Installed::default();
+        let boot_services = fixture.boot_services(&installed);
+        let mut driver = PartitionDriverBinding;
+
+        driver.driver_binding_start(boot_services, CONTROLLER, None).unwrap();
+
+        let system = protocols_of(&installed, FIRST_CHILD);
+        let guids: Vec<efi::Guid> = system.iter().map(|(guid, _)| *guid).collect();
+        assert_eq!(
+            guids,
+            [
+                device_path::PROTOCOL_GUID,
+                partition_info::PROTOCOL_GUID,
+                EFI_SYSTEM_PARTITION_GUID,
+                block_io::PROTOCOL_GUID
+            ]
+        );
+        let data = protocols_of(&installed, FIRST_CHILD + 0x40);
+        assert_eq!(data.len(), 3);
+
+        // SAFETY: Test code - the device path interface of the child is a device path.
+        let device_path = unsafe { DevicePath::try_from_ptr(data[0].1 as *const u8) }.unwrap();
+        assert!(device_path.starts_with(&fixture.device_path));
+        let hard_drive = device_path.iter().nth(1).unwrap();
+        assert_eq!(hard_drive.data[..4], 3u32.to_le_bytes());
+        // SAFETY: Test code - the partition information interface of the child.
+        let info = unsafe { *(data[1].1 as *const partition_info::Protocol) };
+        assert_eq!(info.r#type, partition_info::TYPE_GPT);
+        assert_eq!(info.system, 0);
+
+        let mut handles = [FIRST_CHILD as efi::Handle, (FIRST_CHILD + 0x40) as efi::Handle];
+        driver.driver_binding_stop(boot_services, CONTROLLER, 2, NonNull::new(handles.as_mut_ptr())).unwrap();
+        assert!(installed.lock().unwrap().is_empty());
+        driver.driver_binding_stop(boot_services, CONTROLLER, 0, None).unwrap();
+    }
+
+    #[test]
+    fn mbr_partitions_are_produced() {
+        let mut fixture = Fixture::new(MBR_IMAGE);
+        let installed = Installed::default();
+        let boot_services = fixture.boot_services(&installed);
+
+        PartitionDriverBinding.driver_binding_start(boot_services, CONTROLLER, None).unwrap();
+
+        let block_ios: Vec<usize> = installed
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, protocol, _)| *protocol == block_io::PROTOCOL_GUID)
+            .map(|(_, _, interface)| *interface)
+            .collect();
+        assert_eq!(block_ios.len(), 3);
+        for (block_io, first_lba) in block_ios.into_iter().zip([8, 40, 80]) {
+            let block_io = block_io as *mut block_io::Protocol;
+            let mut buffer = [0u8; 512];
+            // SAFETY: Test code - the Block I/O interface of a child, read into a buffer of one block.
+            let status = unsafe {
+                ((*block_io).read_blocks)(block_io, crate::disk::mock::MEDIA_ID, 0, 512, buffer.as_mut_ptr() as _)
+            };
+            assert_eq!(status, efi::Status::SUCCESS);
+            assert_eq!(buffer[..], MBR_IMAGE[first_lba * 512..(first_lba + 1) * 512]);
+        }
+    }
+
+    #[test]
+    fn disks_without_partition_table_are_released() {
+        let mut fixture = Fixture::new(&[0; 64 * 1024]);
+        let installed = Installed::default();
+        let boot_services = fixture.boot_services(&installed);
+
+        let result = PartitionDriverBinding.driver_binding_start(boot_services, CONTROLLER, None);
+        assert_eq!(result, Err(efi::Status::NOT_FOUND));
+        assert!(installed.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn no_partitions_are_produced_without_media_or_when_none_is_asked_for() {
+        let mut fixture = Fixture::new(GPT_IMAGE);
+        let installed = Installed::default();
+        let boot_services = fixture.boot_services(&installed);
+        let mut driver = PartitionDriverBinding;
+
+        let mut end = device_path::Protocol { r#type: device_path::TYPE_END, sub_type: 0xFF, length: [4, 0] };
+        driver.driver_binding_start(boot_services, CONTROLLER, Some(NonNull::from(&mut end))).unwrap();
+        fixture.disk.media_mut().media_present = false;
+        driver.driver_binding_start(boot_services, CONTROLLER, None).unwrap();
+        assert!(installed.lock().unwrap().is_empty());
+    }
+}
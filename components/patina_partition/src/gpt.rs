@@ -0,0 +1,384 @@
+//! GUID Partition Table
+//!
+//! Parses the GPT of a disk, as described in the "GUID Partition Table (GPT) Disk Layout" chapter of the UEFI
+//! specification. The primary table is read from LBA 1 and the backup table from the alternate LBA of the primary
+//! header, or from the last block of the disk. A table is valid if the CRC32 of its header and of its partition
+//! entry array match. When only one of the tables is valid, the other one is rewritten from it, as long as the disk
+//! is writable; the valid table is used either way.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{vec, vec::Vec};
+use core::{mem, ptr};
+
+use patina::{
+    error::{EfiError, Result},
+    uefi_protocol::partition_info::{GptPartitionEntry, Protocol as PartitionInfo},
+};
+use r_efi::efi;
+
+use crate::{
+    disk::Disk,
+    partition::{Partition, SIGNATURE_TYPE_GUID},
+};
+
+/// The signature of a GPT header, "EFI PART".
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+/// The size of the GPT header defined by the UEFI specification.
+const HEADER_SIZE: u32 = 92;
+/// The LBA of the primary GPT header.
+const PRIMARY_HEADER_LBA: u64 = 1;
+/// The largest partition entry array accepted, which bounds the memory a corrupt header can make the driver use.
+const MAX_ENTRY_ARRAY_SIZE: usize = 1024 * 1024;
+
+/// The partition type GUID of an EFI system partition.
+pub(crate) const EFI_SYSTEM_PARTITION_GUID: efi::Guid =
+    efi::Guid::from_fields(0xc12a7328, 0xf81f, 0x11d2, 0xba, 0x4b, &[0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b]);
+/// The partition type GUID of an unused entry.
+const UNUSED_ENTRY_GUID: efi::Guid = efi::Guid::from_bytes(&[0; 16]);
+
+/// Partition attribute: firmware must not produce a Block I/O protocol for the partition.
+const ATTRIBUTE_NO_BLOCK_IO: u64 = 1 << 1;
+
+/// The fields of a GPT header, `EFI_PARTITION_TABLE_HEADER`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Header {
+    revision: u32,
+    header_size: u32,
+    my_lba: u64,
+    alternate_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    disk_guid: [u8; 16],
+    partition_entry_lba: u64,
+    number_of_partition_entries: u32,
+    size_of_partition_entry: u32,
+    partition_entry_array_crc32: u32,
+}
+
+impl Header {
+    /// Parses the header at the start of `block`, checking its signature, size and CRC32.
+    fn parse(block: &[u8]) -> Option<Self> {
+        let u32_at = |offset: usize| u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap());
+        let u64_at = |offset: usize| u64::from_le_bytes(block[offset..offset + 8].try_into().unwrap());
+
+        if block.len() < HEADER_SIZE as usize || &block[..8] != SIGNATURE {
+            return None;
+        }
+        let header_size = u32_at(12);
+        if header_size < HEADER_SIZE || header_size as usize > block.len() {
+            return None;
+        }
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(&block[..16]);
+        crc.update(&[0; 4]);
+        crc.update(&block[20..header_size as usize]);
+        if crc.finalize() != u32_at(16) {
+            return None;
+        }
+
+        Some(Self {
+            revision: u32_at(8),
+            header_size,
+            my_lba: u64_at(24),
+            alternate_lba: u64_at(32),
+            first_usable_lba: u64_at(40),
+            last_usable_lba: u64_at(48),
+            disk_guid: block[56..72].try_into().unwrap(),
+            partition_entry_lba: u64_at(72),
+            number_of_partition_entries: u32_at(80),
+            size_of_partition_entry: u32_at(84),
+            partition_entry_array_crc32: u32_at(88),
+        })
+    }
+
+    /// Returns a block of `block_size` bytes holding the header, with its CRC32.
+    ///
+    /// Only the fields defined by the UEFI specification are kept, so the header size is always [HEADER_SIZE].
+    fn to_block(&self, block_size: usize) -> Vec<u8> {
+        let mut block = vec![0; block_size];
+        block[..8].copy_from_slice(SIGNATURE);
+        block[8..12].copy_from_slice(&self.revision.to_le_bytes());
+        block[12..16].copy_from_slice(&HEADER_SIZE.to_le_bytes());
+        block[24..32].copy_from_slice(&self.my_lba.to_le_bytes());
+        block[32..40].copy_from_slice(&self.alternate_lba.to_le_bytes());
+        block[40..48].copy_from_slice(&self.first_usable_lba.to_le_bytes());
+        block[48..56].copy_from_slice(&self.last_usable_lba.to_le_bytes());
+        block[56..72].copy_from_slice(&self.disk_guid);
+        block[72..80].copy_from_slice(&self.partition_entry_lba.to_le_bytes());
+        block[80..84].copy_from_slice(&self.number_of_partition_entries.to_le_bytes());
+        block[84..88].copy_from_slice(&self.size_of_partition_entry.to_le_bytes());
+        block[88..92].copy_from_slice(&self.partition_entry_array_crc32.to_le_bytes());
+        let crc = crc32fast::hash(&block[..HEADER_SIZE as usize]);
+        block[16..20].copy_from_slice(&crc.to_le_bytes());
+        block
+    }
+
+    /// The size of the partition entry array in bytes, if it is acceptable.
+    fn entry_array_size(&self) -> Option<usize> {
+        let entry_size = self.size_of_partition_entry as usize;
+        // Entries are 128 bytes multiplied by a power of two.
+        if entry_size < mem::size_of::<GptPartitionEntry>() || !entry_size.is_power_of_two() {
+            return None;
+        }
+        (self.number_of_partition_entries as usize).checked_mul(entry_size).filter(|&size| size <= MAX_ENTRY_ARRAY_SIZE)
+    }
+}
+
+/// A GPT header and its partition entry array, validated.
+struct Table {
+    header: Header,
+    /// The partition entry array, padded to whole blocks.
+    entries: Vec<u8>,
+}
+
+impl Table {
+    /// Reads and validates the table whose header is at `lba`.
+    fn read(disk: &Disk, lba: u64) -> Option<Self> {
+        let media = disk.media();
+        let header = Header::parse(&disk.read_blocks(lba, 1).ok()?)?;
+        if header.my_lba != lba
+            || header.first_usable_lba > header.last_usable_lba
+            || header.last_usable_lba > media.last_block
+        {
+            return None;
+        }
+
+        let size = header.entry_array_size()?;
+        let blocks = size.div_ceil(media.block_size as usize);
+        if header.partition_entry_lba.checked_add(blocks as u64)? > media.last_block + 1 {
+            return None;
+        }
+        let entries = disk.read_blocks(header.partition_entry_lba, blocks).ok()?;
+        if crc32fast::hash(&entries[..size]) != header.partition_entry_array_crc32 {
+            return None;
+        }
+        Some(Self { header, entries })
+    }
+
+    /// Rewrites the other table of the disk from this one.
+    fn restore(&self, disk: &Disk, to_primary: bool) -> Result<()> {
+        let media = disk.media();
+        let entry_blocks = (self.entries.len() / media.block_size as usize) as u64;
+
+        let mut header = self.header.clone();
+        if to_primary {
+            header.my_lba = PRIMARY_HEADER_LBA;
+            header.partition_entry_lba = PRIMARY_HEADER_LBA + 1;
+            if header.partition_entry_lba + entry_blocks > header.first_usable_lba {
+                return Err(EfiError::VolumeCorrupted);
+            }
+        } else {
+            // The backup header goes to the last block, where the primary header expects it.
+            if self.header.alternate_lba != media.last_block {
+                return Err(EfiError::VolumeCorrupted);
+            }
+            header.my_lba = media.last_block;
+            header.partition_entry_lba = header.last_usable_lba + 1;
+            if header.partition_entry_lba + entry_blocks > header.my_lba {
+                return Err(EfiError::VolumeCorrupted);
+            }
+        }
+        header.alternate_lba = self.header.my_lba;
+
+        disk.write_blocks(header.partition_entry_lba, &self.entries)?;
+        disk.write_blocks(header.my_lba, &header.to_block(media.block_size as usize))
+    }
+
+    /// Returns the partitions of the valid, used entries of the table.
+    ///
+    /// An entry is valid if it lies within the usable blocks of the disk and does not overlap another entry. Entries
+    /// with the attribute that forbids a Block I/O protocol are left out.
+    fn partitions(&self) -> Vec<Partition> {
+        let header = &self.header;
+        let entries: Vec<(u32, GptPartitionEntry)> = self
+            .entries
+            .chunks_exact(header.size_of_partition_entry as usize)
+            .take(header.number_of_partition_entries as usize)
+            // SAFETY: Each chunk is at least the size of an entry, and every bit pattern is a valid entry.
+            .map(|chunk| unsafe { ptr::read_unaligned(chunk.as_ptr() as *const GptPartitionEntry) })
+            .zip(1..)
+            .filter(|(entry, _)| entry.partition_type_guid != UNUSED_ENTRY_GUID)
+            .map(|(entry, number)| (number, entry))
+            .collect();
+
+        let overlaps = |a: &GptPartitionEntry, b: &GptPartitionEntry| {
+            a.starting_lba <= b.ending_lba && b.starting_lba <= a.ending_lba
+        };
+        entries
+            .iter()
+            .filter(|(number, entry)| {
+                let valid = entry.starting_lba <= entry.ending_lba
+                    && entry.starting_lba >= header.first_usable_lba
+                    && entry.ending_lba <= header.last_usable_lba
+                    && !entries.iter().any(|(other_number, other)| other_number != number && overlaps(entry, other));
+                if !valid {
+                    log::warn!(target: "partition", "Ignoring invalid GPT partition entry {number}.");
+                }
+                valid && entry.attributes & ATTRIBUTE_NO_BLOCK_IO == 0
+            })
+            .map(|&(number, entry)| Partition {
+                number,
+                start: entry.starting_lba,
+                size: entry.ending_lba - entry.starting_lba + 1,
+                signature: *entry.unique_partition_guid.as_bytes(),
+                signature_type: SIGNATURE_TYPE_GUID,
+                info: PartitionInfo::new_gpt(entry, entry.partition_type_guid == EFI_SYSTEM_PARTITION_GUID),
+            })
+            .collect()
+    }
+}
+
+/// Returns the partitions of the GPT of the disk, restoring the primary or backup table if it is damaged.
+///
+/// Fails with [EfiError::NotFound] if neither table is valid.
+pub(crate) fn partitions(disk: &Disk) -> Result<Vec<Partition>> {
+    let last_block = disk.media().last_block;
+    let primary = Table::read(disk, PRIMARY_HEADER_LBA);
+    let backup_lba = primary.as_ref().map_or(last_block, |primary| primary.header.alternate_lba);
+    let backup = Table::read(disk, backup_lba);
+
+    let table = match (primary, backup) {
+        (Some(primary), Some(_)) => primary,
+        (Some(primary), None) => {
+            log::warn!(target: "partition", "The backup GPT is damaged, restoring it from the primary GPT.");
+            if let Err(err) = primary.restore(disk, false) {
+                log::error!(target: "partition", "Failed to restore the backup GPT: {err:?}");
+            }
+            primary
+        }
+        (None, Some(backup)) => {
+            log::warn!(target: "partition", "The primary GPT is damaged, restoring it from the backup GPT.");
+            if let Err(err) = backup.restore(disk, true) {
+                log::error!(target: "partition", "Failed to restore the primary GPT: {err:?}");
+            }
+            backup
+        }
+        (None, None) => {
+            log::error!(target: "partition", "Both the primary and backup GPT are damaged.");
+            return Err(EfiError::NotFound);
+        }
+    };
+    Ok(table.partitions())
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::disk::mock::{GPT_IMAGE, MockDisk};
+
+    const BLOCK: usize = 512;
+    const BACKUP_HEADER: usize = 127 * BLOCK;
+    const BACKUP_ENTRIES: usize = 95 * BLOCK;
+
+    fn layout(partitions: &[Partition]) -> Vec<(u32, u64, u64)> {
+        partitions.iter().map(|p| (p.number, p.start, p.size)).collect()
+    }
+
+    #[test]
+    fn gpt_partitions_are_numbered_by_entry() {
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let partitions = partitions(&disk.disk()).unwrap();
+
+        // Entry 1 is unused and entry 3 forbids a Block I/O protocol.
+        assert_eq!(layout(&partitions), [(1, 34, 26), (3, 60, 20)]);
+        assert!(partitions[0].is_system());
+        assert!(!partitions[1].is_system());
+        // SAFETY: GPT partitions carry the gpt member of the union.
+        let entry = unsafe { partitions[1].info.info.gpt };
+        assert_eq!(&entry.partition_name[..5], "Data\0".encode_utf16().collect::<Vec<_>>());
+        assert_eq!(disk.writes, 0);
+    }
+
+    #[test]
+    fn damaged_primary_header_is_restored_from_the_backup() {
+        let mut image = GPT_IMAGE.to_vec();
+        image[BLOCK + 40] ^= 0xFF;
+        let mut disk = MockDisk::new(&image);
+
+        let partitions = partitions(&disk.disk()).unwrap();
+        assert_eq!(layout(&partitions), [(1, 34, 26), (3, 60, 20)]);
+        assert_eq!(disk.data, GPT_IMAGE);
+    }
+
+    #[test]
+    fn damaged_primary_entries_are_restored_from_the_backup() {
+        let mut image = GPT_IMAGE.to_vec();
+        image[2 * BLOCK + 32] ^= 0xFF;
+        let mut disk = MockDisk::new(&image);
+
+        assert_eq!(layout(&partitions(&disk.disk()).unwrap()), [(1, 34, 26), (3, 60, 20)]);
+        assert_eq!(disk.data, GPT_IMAGE);
+    }
+
+    #[test]
+    fn damaged_backup_is_restored_from_the_primary() {
+        let mut image = GPT_IMAGE.to_vec();
+        image[BACKUP_HEADER..BACKUP_HEADER + BLOCK].fill(0);
+        image[BACKUP_ENTRIES + 200] ^= 0xFF;
+        let mut disk = MockDisk::new(&image);
+
+        assert_eq!(layout(&partitions(&disk.disk()).unwrap()), [(1, 34, 26), (3, 60, 20)]);
+        assert_eq!(disk.data, GPT_IMAGE);
+    }
+
+    #[test]
+    fn damaged_table_is_used_without_restore_on_read_only_media() {
+        let mut image = GPT_IMAGE.to_vec();
+        image[BLOCK..2 * BLOCK].fill(0);
+        let mut disk = MockDisk::new(&image);
+        disk.media_mut().read_only = true;
+
+        assert_eq!(layout(&partitions(&disk.disk()).unwrap()), [(1, 34, 26), (3, 60, 20)]);
+        assert_eq!(disk.writes, 0);
+        assert_eq!(disk.data, image);
+    }
+
+    #[test]
+    fn gpt_without_valid_table_is_not_found() {
+        let mut image = GPT_IMAGE.to_vec();
+        image[BLOCK + 40] ^= 0xFF;
+        image[BACKUP_HEADER + 40] ^= 0xFF;
+        let mut disk = MockDisk::new(&image);
+        assert_eq!(partitions(&disk.disk()).err(), Some(EfiError::NotFound));
+    }
+
+    #[test]
+    fn header_with_invalid_fields_is_rejected() {
+        let header = Header::parse(&GPT_IMAGE[BLOCK..2 * BLOCK]).unwrap();
+        assert_eq!(header.to_block(BLOCK), GPT_IMAGE[BLOCK..2 * BLOCK]);
+
+        let entry_size = |size| Header { size_of_partition_entry: size, ..header.clone() }.entry_array_size();
+        assert_eq!(entry_size(128), Some(128 * 128));
+        assert_eq!(entry_size(256), Some(128 * 256));
+        assert_eq!(entry_size(64), None);
+        assert_eq!(entry_size(192), None);
+        let too_many = Header { number_of_partition_entries: u32::MAX, ..header.clone() };
+        assert_eq!(too_many.entry_array_size(), None);
+
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let mut moved = header.clone();
+        moved.last_usable_lba = 128;
+        disk.data[BLOCK..2 * BLOCK].copy_from_slice(&moved.to_block(BLOCK));
+        assert!(Table::read(&disk.disk(), PRIMARY_HEADER_LBA).is_none());
+    }
+
+    #[test]
+    fn overlapping_entries_are_ignored() {
+        let mut disk = MockDisk::new(GPT_IMAGE);
+        let mut table = Table::read(&disk.disk(), PRIMARY_HEADER_LBA).unwrap();
+        // Make entry 1 cover blocks 50 to 70, across entries 0 and 2, and entry 3 end past the usable blocks.
+        table.entries[128..144].copy_from_slice(&[0xAA; 16]);
+        table.entries[160..168].copy_from_slice(&50u64.to_le_bytes());
+        table.entries[168..176].copy_from_slice(&70u64.to_le_bytes());
+        table.entries[3 * 128 + 48..3 * 128 + 56].copy_from_slice(&0u64.to_le_bytes());
+        table.entries[3 * 128 + 40..3 * 128 + 48].copy_from_slice(&95u64.to_le_bytes());
+
+        assert!(table.partitions().is_empty());
+    }
+}
@@ -0,0 +1,18 @@
+#![doc = include_str!("../README.md")]
+#![doc = concat!(
+    "## License\n\n",
+    " Copyright (c) Microsoft Corporation.\n\n",
+)]
+#![cfg_attr(all(not(feature = "std"), not(test), not(feature = "mockall")), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod component;
+
+mod child;
+mod disk;
+mod driver;
+mod gpt;
+mod mbr;
+mod partition;
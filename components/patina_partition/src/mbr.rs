@@ -0,0 +1,243 @@
+//! Legacy Master Boot Record
+//!
+//! Parses the legacy MBR of a disk, as described in the "Legacy Master Boot Record (MBR)" section of the UEFI
+//! specification, including the logical partitions chained from extended partitions by Extended Boot Records (EBR).
+//! The MBR is rejected as a whole if a partition extends past the end of the disk or overlaps another, which is
+//! what tells a partitioned disk from a file system that starts at block 0 with a boot signature.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::{mem, ptr};
+
+use patina::{
+    error::{EfiError, Result},
+    uefi_protocol::partition_info::{MbrPartitionRecord, Protocol as PartitionInfo},
+};
+
+use crate::{
+    disk::Disk,
+    partition::{Partition, SIGNATURE_TYPE_MBR},
+};
+
+/// The offset of the 32-bit unique disk signature.
+const DISK_SIGNATURE_OFFSET: usize = 440;
+/// The offset of the four partition records.
+const RECORDS_OFFSET: usize = 446;
+/// The offset of the boot signature, 0x55 0xAA.
+const BOOT_SIGNATURE_OFFSET: usize = 510;
+
+/// The partition type of the protective MBR of a GPT disk.
+const TYPE_PROTECTIVE: u8 = 0xEE;
+/// The partition type of an EFI system partition.
+const TYPE_EFI_SYSTEM: u8 = 0xEF;
+/// The partition types of extended partitions.
+const TYPE_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+
+/// The number of the first logical partition.
+const FIRST_LOGICAL_PARTITION: u32 = 5;
+/// The most logical partitions followed in an EBR chain.
+const MAX_LOGICAL_PARTITIONS: u32 = 128;
+
+/// Returns the partition records of a block holding an MBR or an EBR, or None without a boot signature.
+fn records(block: &[u8]) -> Option<[MbrPartitionRecord; 4]> {
+    if block.get(BOOT_SIGNATURE_OFFSET..BOOT_SIGNATURE_OFFSET + 2)? != [0x55, 0xAA] {
+        return None;
+    }
+    Some(core::array::from_fn(|index| {
+        let offset = RECORDS_OFFSET + index * mem::size_of::<MbrPartitionRecord>();
+        // SAFETY: The record lies before the boot signature, which is in the block, and every bit pattern is a valid
+        // record.
+        unsafe { ptr::read_unaligned(block[offset..].as_ptr() as *const MbrPartitionRecord) }
+    }))
+}
+
+/// The first block of the partition of a record, relative to the block its offsets are relative to.
+fn starting_lba(record: &MbrPartitionRecord) -> u64 {
+    u32::from_le_bytes(record.starting_lba) as u64
+}
+
+/// The number of blocks of the partition of a record.
+fn size_in_lba(record: &MbrPartitionRecord) -> u64 {
+    u32::from_le_bytes(record.size_in_lba) as u64
+}
+
+/// Returns whether the record describes a partition.
+fn is_used(record: &MbrPartitionRecord) -> bool {
+    record.os_indicator != 0 && size_in_lba(record) != 0
+}
+
+/// Returns whether the first block of a disk is a protective MBR, which means the disk is partitioned by a GPT.
+pub(crate) fn is_protective(block: &[u8]) -> bool {
+    records(block).is_some_and(|records| records.iter().any(|record| record.os_indicator == TYPE_PROTECTIVE))
+}
+
+/// Returns the partitions described by `first_block`, the MBR of the disk.
+///
+/// Fails with [EfiError::NotFound] if the block is not a valid MBR.
+pub(crate) fn partitions(disk: &Disk, first_block: &[u8]) -> Result<Vec<Partition>> {
+    let records = records(first_block).ok_or(EfiError::NotFound)?;
+    let last_block = disk.media().last_block;
+
+    let used: Vec<(usize, &MbrPartitionRecord)> = records.iter().enumerate().filter(|(_, r)| is_used(r)).collect();
+    let valid = used.iter().all(|(index, record)| {
+        let end = starting_lba(record) + size_in_lba(record) - 1;
+        end <= last_block
+            && used.iter().all(|(other_index, other)| {
+                other_index == index
+                    || starting_lba(other) > end
+                    || starting_lba(other) + size_in_lba(other) - 1 < starting_lba(record)
+            })
+    });
+    if used.is_empty() || !valid {
+        return Err(EfiError::NotFound);
+    }
+
+    let mut signature = [0; 16];
+    signature[..4].copy_from_slice(&first_block[DISK_SIGNATURE_OFFSET..DISK_SIGNATURE_OFFSET + 4]);
+
+    let mut partitions = Vec::new();
+    for (index, record) in used {
+        if TYPE_EXTENDED.contains(&record.os_indicator) {
+            logical_partitions(disk, record, signature, &mut partitions);
+        } else {
+            partitions.push(partition(index as u32 + 1, starting_lba(record), record, signature));
+        }
+    }
+    Ok(partitions)
+}
+
+/// Follows the EBR chain of an extended partition and adds its logical partitions to `partitions`.
+///
+/// The chain ends at the first EBR that cannot be read or is invalid, and at links that do not move forward within
+/// the extended partition, so that a corrupt chain cannot loop.
+fn logical_partitions(
+    disk: &Disk,
+    extended: &MbrPartitionRecord,
+    signature: [u8; 16],
+    partitions: &mut Vec<Partition>,
+) {
+    let extended_start = starting_lba(extended);
+    let extended_end = extended_start + size_in_lba(extended) - 1;
+
+    let mut ebr_lba = extended_start;
+    for number in FIRST_LOGICAL_PARTITION..FIRST_LOGICAL_PARTITION + MAX_LOGICAL_PARTITIONS {
+        let Some(records) = disk.read_blocks(ebr_lba, 1).ok().as_deref().and_then(records) else {
+            log::warn!(target: "partition", "Invalid EBR at LBA {ebr_lba:#x}, ignoring the rest of the chain.");
+            break;
+        };
+
+        let logical = &records[0];
+        if is_used(logical) {
+            let start = ebr_lba + starting_lba(logical);
+            if starting_lba(logical) != 0 && start + size_in_lba(logical) - 1 <= extended_end {
+                partitions.push(partition(number, start, logical, signature));
+            } else {
+                log::warn!(target: "partition", "Logical partition {number} lies outside its extended partition.");
+            }
+        }
+
+        let next = &records[1];
+        if !TYPE_EXTENDED.contains(&next.os_indicator) || !is_used(next) {
+            break;
+        }
+        let next_lba = extended_start + starting_lba(next);
+        if next_lba <= ebr_lba || next_lba > extended_end {
+            log::warn!(target: "partition", "Invalid EBR link to LBA {next_lba:#x}, ignoring the rest of the chain.");
+            break;
+        }
+        ebr_lba = next_lba;
+    }
+}
+
+fn partition(number: u32, start: u64, record: &MbrPartitionRecord, signature: [u8; 16]) -> Partition {
+    Partition {
+        number,
+        start,
+        size: size_in_lba(record),
+        signature,
+        signature_type: SIGNATURE_TYPE_MBR,
+        info: PartitionInfo::new_mbr(*record, record.os_indicator == TYPE_EFI_SYSTEM),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::disk::mock::{GPT_IMAGE, MBR_IMAGE, MockDisk};
+
+    fn set_record(image: &mut [u8], block: usize, index: usize, os_indicator: u8, start: u32, size: u32) {
+        let offset = block * 512 + RECORDS_OFFSET + index * 16;
+        image[offset + 4] = os_indicator;
+        image[offset + 8..offset + 12].copy_from_slice(&start.to_le_bytes());
+        image[offset + 12..offset + 16].copy_from_slice(&size.to_le_bytes());
+    }
+
+    fn find(image: &[u8]) -> Result<Vec<Partition>> {
+        let mut disk = MockDisk::new(image);
+        partitions(&disk.disk(), &image[..512])
+    }
+
+    #[test]
+    fn primary_and_logical_partitions_are_found() {
+        let partitions = find(MBR_IMAGE).unwrap();
+
+        let layout: Vec<(u32, u64, u64)> = partitions.iter().map(|p| (p.number, p.start, p.size)).collect();
+        assert_eq!(layout, [(1, 8, 24), (5, 40, 32), (6, 80, 48)]);
+        assert!(partitions[0].is_system());
+        assert!(!partitions[1].is_system());
+        assert!(partitions.iter().all(|p| p.signature[..4] == [0x78, 0x56, 0x34, 0x12]));
+        // SAFETY: MBR partitions carry the mbr member of the union.
+        let record = unsafe { partitions[2].info.info.mbr };
+        assert_eq!(record.os_indicator, 0x07);
+        assert_eq!(record.starting_lba, 8u32.to_le_bytes());
+    }
+
+    #[test]
+    fn protective_mbr_is_recognized() {
+        assert!(is_protective(&GPT_IMAGE[..512]));
+        assert!(!is_protective(&MBR_IMAGE[..512]));
+        assert!(!is_protective(&[0; 512]));
+    }
+
+    #[test]
+    fn mbr_with_invalid_records_is_rejected() {
+        let mut overlapping = MBR_IMAGE.to_vec();
+        set_record(&mut overlapping, 0, 2, 0x83, 16, 8);
+        assert_eq!(find(&overlapping).err(), Some(EfiError::NotFound));
+
+        let mut past_the_end = MBR_IMAGE.to_vec();
+        set_record(&mut past_the_end, 0, 1, 0x83, 32, 97);
+        assert_eq!(find(&past_the_end).err(), Some(EfiError::NotFound));
+
+        let mut unsigned = MBR_IMAGE.to_vec();
+        unsigned[BOOT_SIGNATURE_OFFSET] = 0;
+        assert_eq!(find(&unsigned).err(), Some(EfiError::NotFound));
+    }
+
+    #[test]
+    fn corrupt_ebr_chain_ends_the_logical_partitions() {
+        // The second EBR links back to the first one.
+        let mut looping = MBR_IMAGE.to_vec();
+        set_record(&mut looping, 72, 1, 0x05, 0, 8);
+        let numbers: Vec<u32> = find(&looping).unwrap().iter().map(|p| p.number).collect();
+        assert_eq!(numbers, [1, 5, 6]);
+
+        // The second logical partition extends past its extended partition.
+        let mut outside = MBR_IMAGE.to_vec();
+        set_record(&mut outside, 72, 0, 0x07, 8, 49);
+        let numbers: Vec<u32> = find(&outside).unwrap().iter().map(|p| p.number).collect();
+        assert_eq!(numbers, [1, 5]);
+
+        // The second EBR has no boot signature.
+        let mut unsigned = MBR_IMAGE.to_vec();
+        unsigned[72 * 512 + BOOT_SIGNATURE_OFFSET] = 0;
+        let numbers: Vec<u32> = find(&unsigned).unwrap().iter().map(|p| p.number).collect();
+        assert_eq!(numbers, [1, 5]);
+    }
+}
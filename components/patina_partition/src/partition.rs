@@ -0,0 +1,157 @@
+//! Partitions
+//!
+//! The partitions found on a disk, whichever table describes them, and the hard drive media device path node that
+//! identifies each of them.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::iter;
+
+use patina::{
+    error::{EfiError, Result},
+    uefi_protocol::{
+        device_path::{
+            DevicePath, DevicePathBuf,
+            device_path_node::{Header, UnknownDevicePathNode},
+            nodes::{DevicePathType, MediaSubType},
+        },
+        partition_info,
+    },
+};
+
+use crate::{disk::Disk, gpt, mbr};
+
+/// The smallest block size a partition table can be read from.
+const MIN_BLOCK_SIZE: u32 = 512;
+
+/// The hard drive node signature is the 32-bit MBR disk signature.
+pub(crate) const SIGNATURE_TYPE_MBR: u8 = 0x01;
+/// The hard drive node signature is the unique GUID of the GPT partition.
+pub(crate) const SIGNATURE_TYPE_GUID: u8 = 0x02;
+
+/// The hard drive node describes a legacy MBR partition.
+const FORMAT_MBR: u8 = 0x01;
+/// The hard drive node describes a GPT partition.
+const FORMAT_GPT: u8 = 0x02;
+
+/// A partition of a disk.
+#[derive(Clone, Copy)]
+pub(crate) struct Partition {
+    /// The number of the partition: the entry index plus one for a GPT, 1 to 4 for MBR primary partitions and 5
+    /// onward for logical partitions.
+    pub(crate) number: u32,
+    /// The first block of the partition on the disk.
+    pub(crate) start: u64,
+    /// The number of blocks of the partition.
+    pub(crate) size: u64,
+    /// The signature of the hard drive node, of type `signature_type`.
+    pub(crate) signature: [u8; 16],
+    /// [SIGNATURE_TYPE_MBR] or [SIGNATURE_TYPE_GUID].
+    pub(crate) signature_type: u8,
+    /// The Partition Information protocol of the partition.
+    pub(crate) info: partition_info::Protocol,
+}
+
+impl Partition {
+    /// Returns whether the partition is an EFI system partition.
+    pub(crate) fn is_system(&self) -> bool {
+        self.info.system != 0
+    }
+
+    /// Returns the hard drive media device path node of the partition.
+    pub(crate) fn hard_drive_node(&self) -> Vec<u8> {
+        let format = if self.info.r#type == partition_info::TYPE_GPT { FORMAT_GPT } else { FORMAT_MBR };
+        let mut node = Vec::with_capacity(42);
+        node.extend_from_slice(&[DevicePathType::Media as u8, MediaSubType::HardDrive as u8, 42, 0]);
+        node.extend_from_slice(&self.number.to_le_bytes());
+        node.extend_from_slice(&self.start.to_le_bytes());
+        node.extend_from_slice(&self.size.to_le_bytes());
+        node.extend_from_slice(&self.signature);
+        node.extend_from_slice(&[format, self.signature_type]);
+        node
+    }
+
+    /// Returns the device path of the partition: the device path of the disk followed by the hard drive node.
+    pub(crate) fn device_path(&self, disk: &DevicePath) -> DevicePathBuf {
+        let hard_drive = self.hard_drive_node();
+        let node = UnknownDevicePathNode {
+            header: Header::new(hard_drive[0], hard_drive[1], hard_drive.len()),
+            data: &hard_drive[Header::size_of_header()..],
+        };
+        let mut device_path = DevicePathBuf::from(disk);
+        device_path.append_device_path(&DevicePathBuf::from_device_path_node_iter(iter::once(node)));
+        device_path
+    }
+}
+
+/// Returns the partitions of the disk, from its GPT if the MBR is a protective MBR, or from its legacy MBR.
+///
+/// Fails with [EfiError::NotFound] if the disk holds no valid partition table.
+pub(crate) fn find(disk: &Disk) -> Result<Vec<Partition>> {
+    if disk.media().block_size < MIN_BLOCK_SIZE {
+        return Err(EfiError::Unsupported);
+    }
+    let first_block = disk.read_blocks(0, 1)?;
+    if mbr::is_protective(&first_block) { gpt::partitions(disk) } else { mbr::partitions(disk, &first_block) }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::disk::mock::{GPT_IMAGE, MBR_IMAGE, MockDisk};
+    use patina::uefi_protocol::device_path::nodes::{Acpi, Pci};
+
+    #[test]
+    fn gpt_partition_is_identified_by_its_unique_guid() {
+        let partitions = find(&MockDisk::new(GPT_IMAGE).disk()).unwrap();
+        let node = partitions[0].hard_drive_node();
+
+        let mut expected = alloc::vec![4, 1, 42, 0, 1, 0, 0, 0];
+        expected.extend_from_slice(&34u64.to_le_bytes());
+        expected.extend_from_slice(&26u64.to_le_bytes());
+        expected.extend_from_slice(&[0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x33, 0x33]);
+        expected.extend_from_slice(&[0x44, 0x44, 0x55, 0x55, 0x55, 0x55, 0x55, 0x01]);
+        expected.extend_from_slice(&[FORMAT_GPT, SIGNATURE_TYPE_GUID]);
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn mbr_partition_is_identified_by_the_disk_signature() {
+        let partitions = find(&MockDisk::new(MBR_IMAGE).disk()).unwrap();
+        let node = partitions[1].hard_drive_node();
+
+        let mut expected = alloc::vec![4, 1, 42, 0, 5, 0, 0, 0];
+        expected.extend_from_slice(&40u64.to_le_bytes());
+        expected.extend_from_slice(&32u64.to_le_bytes());
+        expected.extend_from_slice(&[0x78, 0x56, 0x34, 0x12]);
+        expected.extend_from_slice(&[0; 12]);
+        expected.extend_from_slice(&[FORMAT_MBR, SIGNATURE_TYPE_MBR]);
+        assert_eq!(node, expected);
+    }
+
+    #[test]
+    fn partition_device_path_extends_the_disk_device_path() {
+        let partitions = find(&MockDisk::new(GPT_IMAGE).disk()).unwrap();
+        let mut disk = DevicePathBuf::from_device_path_node_iter(iter::once(Acpi::new_pci_root(0)));
+        disk.append_device_path(&DevicePathBuf::from_device_path_node_iter(iter::once(Pci { function: 0, device: 3 })));
+
+        let device_path = partitions[1].device_path(&disk);
+        assert!(device_path.starts_with(&disk));
+        assert_eq!(device_path.node_count(), disk.node_count() + 1);
+        let hard_drive = device_path.iter().nth(2).unwrap();
+        assert_eq!(hard_drive.header.r#type, DevicePathType::Media as u8);
+        assert_eq!(hard_drive.header.sub_type, MediaSubType::HardDrive as u8);
+        assert_eq!(hard_drive.data, &partitions[1].hard_drive_node()[4..]);
+    }
+
+    #[test]
+    fn disk_without_partition_table_is_not_partitioned() {
+        assert_eq!(find(&MockDisk::new(&[0; 64 * 1024]).disk()).err(), Some(EfiError::NotFound));
+    }
+}
@@ -0,0 +1,233 @@
+//! Reset System Provider Component
+//!
+//! Routes the `ResetSystem()` entry of the runtime services table through the registered reset filters and
+//! notifications to the platform's [ResetSystem] implementation, produces the [ResetNotification] service and installs the
+//! Reset architectural protocol.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate alloc;
+
+use core::{ffi::c_void, ptr, slice};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        component,
+        params::Commands,
+        service::{
+            IntoService, Service,
+            memory::MemoryManager,
+            runtime_table::{ResetEntries, RuntimeServiceEntries, RuntimeServicesTable},
+        },
+    },
+    error::{EfiError, Result},
+    pi::protocols::reset,
+    runtime_services::{RuntimeServices, StandardRuntimeServices},
+};
+use r_efi::efi;
+
+use crate::{
+    dispatcher::ResetDispatcher,
+    service::{ResetFilter, ResetNotification, ResetNotify, ResetRequest, ResetSystem, ResetType},
+};
+
+/// State shared with the runtime services table entry, which receives no context pointer.
+struct Provider {
+    dispatcher: &'static ResetDispatcher,
+    boot_services: StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+}
+
+static PROVIDER: spin::Once<Provider> = spin::Once::new();
+
+/// Produces the UEFI `ResetSystem()` runtime service.
+///
+/// Takes the platform's [ResetSystem] implementation, which it moves to runtime services memory with the rest of the
+/// dispatcher state, and produces the [ResetNotification] service. Before the platform reset, the
+/// `EFI_EVENT_GROUP_RESET_SYSTEM` event group is signaled if boot services are still available.
+///
+/// `ResetSystem()` is not available to the OS: it is part of the DXE core image, which is boot services code.
+///
+/// ```rust,ignore
+/// commands.add_component(ResetSystemProvider::new(MyPlatformReset));
+/// ```
+pub struct ResetSystemProvider<R: ResetSystem + 'static> {
+    platform: R,
+}
+
+#[component]
+impl<R: ResetSystem + 'static> ResetSystemProvider<R> {
+    /// Creates the component for `platform`.
+    pub const fn new(platform: R) -> Self {
+        Self { platform }
+    }
+
+    #[coverage(off)] // Component integration - the dispatch logic it wires up is tested directly.
+    fn entry_point(
+        self,
+        rt_table: Service<dyn RuntimeServicesTable>,
+        memory_manager: Service<dyn MemoryManager>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+        mut commands: Commands,
+    ) -> Result<()> {
+        if PROVIDER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        // The OS resets through the dispatcher at runtime, after boot services memory is reclaimed.
+        let platform: &'static dyn ResetSystem = memory_manager.leak_in_runtime_memory(self.platform)?;
+        let dispatcher: &'static ResetDispatcher =
+            memory_manager.leak_in_runtime_memory(ResetDispatcher::new(platform))?;
+        dispatcher.register_notify(signal_reset_event_group)?;
+        PROVIDER.call_once(|| Provider { dispatcher, boot_services: boot_services.clone(), runtime_services });
+
+        boot_services
+            .create_event(EventType::SIGNAL_EXIT_BOOT_SERVICES, Tpl::NOTIFY, Some(exit_boot_services), dispatcher)
+            .map_err(EfiError::from)?;
+        boot_services
+            .create_event(
+                EventType::SIGNAL_VIRTUAL_ADDRESS_CHANGE,
+                Tpl::NOTIFY,
+                Some(virtual_address_change),
+                dispatcher,
+            )
+            .map_err(EfiError::from)?;
+
+        rt_table.install(RuntimeServiceEntries::Reset(ResetEntries { reset_system }))?;
+
+        // SAFETY: The architectural protocol carries no interface; a null pointer is what consumers expect.
+        unsafe { boot_services.install_protocol_interface_unchecked(None, &reset::PROTOCOL_GUID, ptr::null_mut()) }
+            .map_err(EfiError::from)?;
+
+        commands.add_service(ResetNotifier { dispatcher });
+
+        log::info!(target: "reset", "Reset services installed.");
+        Ok(())
+    }
+}
+
+/// The [ResetNotification] service produced by [ResetSystemProvider].
+#[derive(IntoService)]
+#[service(dyn ResetNotification)]
+struct ResetNotifier {
+    dispatcher: &'static ResetDispatcher,
+}
+
+impl ResetNotification for ResetNotifier {
+    fn register_filter(&self, filter: ResetFilter) -> core::result::Result<(), EfiError> {
+        self.dispatcher.register_filter(filter)
+    }
+
+    fn register_notify(&self, notify: ResetNotify) -> core::result::Result<(), EfiError> {
+        self.dispatcher.register_notify(notify)
+    }
+
+    fn unregister_notify(&self, notify: ResetNotify) -> core::result::Result<(), EfiError> {
+        self.dispatcher.unregister_notify(notify)
+    }
+}
+
+extern "efiapi" fn exit_boot_services(_event: efi::Event, dispatcher: &'static ResetDispatcher) {
+    dispatcher.exit_boot_services();
+}
+
+extern "efiapi" fn virtual_address_change(_event: efi::Event, dispatcher: &'static ResetDispatcher) {
+    if let Some(provider) = PROVIDER.get() {
+        dispatcher.convert_pointers(&|address| provider.runtime_services.convert_pointer(address).ok());
+    }
+}
+
+extern "efiapi" fn noop(_event: efi::Event, _context: *mut c_void) {}
+
+/// Signals `EFI_EVENT_GROUP_RESET_SYSTEM`, which only exists while boot services are available.
+///
+/// Registered as the first notification so that it only runs for requests that passed the filters.
+#[coverage(off)] // Requires boot services.
+fn signal_reset_event_group(_request: &ResetRequest<'_>) {
+    let Some(provider) = PROVIDER.get() else {
+        return;
+    };
+    if provider.dispatcher.at_runtime() {
+        return;
+    }
+
+    let boot_services = &provider.boot_services;
+    if let Ok(event) = boot_services.create_event_ex(
+        EventType::NOTIFY_SIGNAL,
+        Tpl::CALLBACK,
+        Some(noop),
+        ptr::null_mut(),
+        &efi::EVENT_GROUP_RESET_SYSTEM,
+    ) {
+        let _ = boot_services.signal_event(event);
+        let _ = boot_services.close_event(event);
+    }
+}
+
+extern "efiapi" fn reset_system(reset_type: efi::ResetType, status: efi::Status, data_size: usize, data: *mut c_void) {
+    let Some(provider) = PROVIDER.get() else {
+        return;
+    };
+    dispatch(provider.dispatcher, reset_type, status, data_size, data);
+}
+
+/// Converts the raw `ResetSystem()` arguments into a [ResetRequest] and dispatches it.
+fn dispatch(
+    dispatcher: &ResetDispatcher,
+    reset_type: efi::ResetType,
+    status: efi::Status,
+    data_size: usize,
+    data: *mut c_void,
+) {
+    // The specification defines no error return; an unknown reset type is ignored.
+    let Ok(reset_type) = ResetType::try_from(reset_type) else {
+        return;
+    };
+    let data = if data.is_null() {
+        &[][..]
+    } else {
+        // SAFETY: The caller provides data_size bytes of reset data at data.
+        unsafe { slice::from_raw_parts(data as *const u8, data_size) }
+    };
+    dispatcher.reset(ResetRequest { reset_type, status, data });
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::service::MockResetSystem;
+    use alloc::boxed::Box;
+
+    #[test]
+    fn dispatch_converts_the_raw_arguments() {
+        let mut data = [b'H', 0, b'I', 0, 0, 0, 0xAA];
+        let mut platform = MockResetSystem::new();
+        platform
+            .expect_reset_system()
+            .withf(|request| {
+                request.reset_type == ResetType::Shutdown
+                    && request.status == efi::Status::ABORTED
+                    && request.data == [b'H', 0, b'I', 0, 0, 0, 0xAA]
+            })
+            .times(1)
+            .return_const(());
+        platform
+            .expect_reset_system()
+            .withf(|request| request.reset_type == ResetType::Cold && request.data.is_empty())
+            .times(1)
+            .return_const(());
+        let dispatcher = ResetDispatcher::new(Box::leak(Box::new(platform)));
+
+        dispatch(&dispatcher, efi::RESET_SHUTDOWN, efi::Status::ABORTED, data.len(), data.as_mut_ptr() as *mut c_void);
+        dispatch(&dispatcher, efi::RESET_COLD, efi::Status::SUCCESS, 4, ptr::null_mut());
+        // Unknown reset types never reach the platform.
+        dispatch(&dispatcher, 0x10, efi::Status::SUCCESS, 0, ptr::null_mut());
+    }
+}
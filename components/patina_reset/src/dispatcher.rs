@@ -0,0 +1,274 @@
+//! Reset Request Dispatch
+//!
+//! Runs reset requests through the registered filters and notification callbacks before handing them to the
+//! platform's [ResetSystem] implementation.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use patina::{
+    error::EfiError,
+    runtime_services::{VirtualAddressMap, fixed_vec::FixedVec},
+};
+use spin::Mutex;
+
+use crate::service::{FilterAction, ResetFilter, ResetNotify, ResetRequest, ResetSystem};
+
+/// The most filters, and the most notification callbacks, that can be registered.
+pub(crate) const MAX_CALLBACKS: usize = 16;
+
+/// Owns the registered filters and callbacks, and serializes them in front of the platform reset.
+///
+/// Resets requested by the OS read the dispatcher at runtime, so it is allocated as `EfiRuntimeServicesData` and
+/// holds its callbacks in fixed-capacity lists.
+pub(crate) struct ResetDispatcher {
+    platform: &'static dyn ResetSystem,
+    filters: Mutex<FixedVec<ResetFilter, MAX_CALLBACKS>>,
+    notifies: Mutex<FixedVec<ResetNotify, MAX_CALLBACKS>>,
+    at_runtime: AtomicBool,
+    depth: AtomicUsize,
+}
+
+impl ResetDispatcher {
+    pub(crate) fn new(platform: &'static dyn ResetSystem) -> Self {
+        Self {
+            platform,
+            filters: Mutex::new(FixedVec::new()),
+            notifies: Mutex::new(FixedVec::new()),
+            at_runtime: AtomicBool::new(false),
+            depth: AtomicUsize::new(0),
+        }
+    }
+
+    /// Moves the platform reset to the virtual addresses of `map`, while the OS calls `SetVirtualAddressMap()`.
+    pub(crate) fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        self.platform.convert_pointers(map);
+    }
+
+    /// Stops accepting registrations, which only boot services callers may make.
+    pub(crate) fn exit_boot_services(&self) {
+        self.at_runtime.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn at_runtime(&self) -> bool {
+        self.at_runtime.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn register_filter(&self, filter: ResetFilter) -> Result<(), EfiError> {
+        if self.at_runtime() {
+            return Err(EfiError::Unsupported);
+        }
+        let mut filters = self.filters.lock();
+        if filters.iter().any(|&f| core::ptr::fn_addr_eq(f, filter)) {
+            return Err(EfiError::AlreadyStarted);
+        }
+        filters.push(filter)
+    }
+
+    pub(crate) fn register_notify(&self, notify: ResetNotify) -> Result<(), EfiError> {
+        if self.at_runtime() {
+            return Err(EfiError::Unsupported);
+        }
+        let mut notifies = self.notifies.lock();
+        if notifies.iter().any(|&n| core::ptr::fn_addr_eq(n, notify)) {
+            return Err(EfiError::AlreadyStarted);
+        }
+        notifies.push(notify)
+    }
+
+    pub(crate) fn unregister_notify(&self, notify: ResetNotify) -> Result<(), EfiError> {
+        if self.at_runtime() {
+            return Err(EfiError::Unsupported);
+        }
+        let mut notifies = self.notifies.lock();
+        let index =
+            notifies.iter().position(|&n| core::ptr::fn_addr_eq(n, notify)).ok_or(EfiError::InvalidParameter)?;
+        notifies.remove(index);
+        Ok(())
+    }
+
+    /// Filters `request`, runs the notification callbacks and resets the platform.
+    ///
+    /// Returns only if a filter vetoed the request or the platform failed to reset. A reset requested from within a
+    /// filter or callback goes straight to the platform.
+    pub(crate) fn reset(&self, mut request: ResetRequest<'_>) {
+        if self.depth.fetch_add(1, Ordering::SeqCst) == 0 {
+            // The locks are only held while reading an entry, so callbacks may request a reset themselves.
+            let filter_at = |index: usize| self.filters.lock().get(index).copied();
+            let mut index = 0;
+            while let Some(filter) = filter_at(index) {
+                if filter(&mut request) == FilterAction::Veto {
+                    if !self.at_runtime() {
+                        log::info!(target: "reset", "Reset request {request:?} vetoed by a filter.");
+                    }
+                    self.depth.fetch_sub(1, Ordering::SeqCst);
+                    return;
+                }
+                index += 1;
+            }
+
+            let notify_at = |index: usize| self.notifies.lock().get(index).copied();
+            let mut index = 0;
+            while let Some(notify) = notify_at(index) {
+                notify(&request);
+                index += 1;
+            }
+        }
+
+        self.platform.reset_system(&request);
+        self.depth.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::service::{MockResetSystem, ResetType};
+    use alloc::boxed::Box;
+    use core::sync::atomic::AtomicU32;
+    use r_efi::efi;
+
+    fn dispatcher(platform: MockResetSystem) -> &'static ResetDispatcher {
+        Box::leak(Box::new(ResetDispatcher::new(Box::leak(Box::new(platform)))))
+    }
+
+    fn request(reset_type: ResetType) -> ResetRequest<'static> {
+        ResetRequest { reset_type, status: efi::Status::SUCCESS, data: &[] }
+    }
+
+    #[test]
+    fn reset_reaches_the_platform_after_notifications() {
+        static NOTIFIED: AtomicU32 = AtomicU32::new(0);
+        fn notify(request: &ResetRequest<'_>) {
+            assert_eq!(request.reset_type, ResetType::Warm);
+            NOTIFIED.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let mut platform = MockResetSystem::new();
+        platform
+            .expect_reset_system()
+            .withf(|request| request.reset_type == ResetType::Warm && NOTIFIED.load(Ordering::SeqCst) == 1)
+            .times(1)
+            .return_const(());
+        let dispatcher = dispatcher(platform);
+
+        dispatcher.register_notify(notify).unwrap();
+        dispatcher.reset(request(ResetType::Warm));
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn filters_can_rewrite_or_veto_requests() {
+        static NOTIFIED: AtomicU32 = AtomicU32::new(0);
+        fn warm_to_cold(request: &mut ResetRequest<'_>) -> FilterAction {
+            if request.reset_type == ResetType::Warm {
+                request.reset_type = ResetType::Cold;
+            }
+            FilterAction::Continue
+        }
+        fn veto_shutdown(request: &mut ResetRequest<'_>) -> FilterAction {
+            match request.reset_type {
+                ResetType::Shutdown => FilterAction::Veto,
+                _ => FilterAction::Continue,
+            }
+        }
+        fn notify(_: &ResetRequest<'_>) {
+            NOTIFIED.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let mut platform = MockResetSystem::new();
+        platform.expect_reset_system().withf(|request| request.reset_type == ResetType::Cold).times(1).return_const(());
+        let dispatcher = dispatcher(platform);
+        dispatcher.register_filter(warm_to_cold).unwrap();
+        dispatcher.register_filter(veto_shutdown).unwrap();
+        dispatcher.register_notify(notify).unwrap();
+
+        dispatcher.reset(request(ResetType::Warm));
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+
+        // A vetoed request neither notifies nor reaches the platform.
+        dispatcher.reset(request(ResetType::Shutdown));
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn nested_reset_skips_the_callbacks() {
+        static DISPATCHER: spin::Once<&'static ResetDispatcher> = spin::Once::new();
+        static NOTIFIED: AtomicU32 = AtomicU32::new(0);
+        fn notify(_: &ResetRequest<'_>) {
+            NOTIFIED.fetch_add(1, Ordering::SeqCst);
+            DISPATCHER.get().unwrap().reset(request(ResetType::Shutdown));
+        }
+
+        let mut platform = MockResetSystem::new();
+        let mut sequence = mockall::Sequence::new();
+        platform
+            .expect_reset_system()
+            .withf(|request| request.reset_type == ResetType::Shutdown)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_const(());
+        platform
+            .expect_reset_system()
+            .withf(|request| request.reset_type == ResetType::Cold)
+            .times(1)
+            .in_sequence(&mut sequence)
+            .return_const(());
+        let dispatcher = DISPATCHER.call_once(|| dispatcher(platform));
+
+        dispatcher.register_notify(notify).unwrap();
+        dispatcher.reset(request(ResetType::Cold));
+        assert_eq!(NOTIFIED.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn callback_lists_are_bounded_and_keep_their_order() {
+        static ORDER: Mutex<alloc::vec::Vec<usize>> = Mutex::new(alloc::vec::Vec::new());
+        fn first(_: &ResetRequest<'_>) {
+            ORDER.lock().push(1);
+        }
+        fn second(_: &ResetRequest<'_>) {
+            ORDER.lock().push(2);
+        }
+        fn third(_: &ResetRequest<'_>) {
+            ORDER.lock().push(3);
+        }
+
+        let mut platform = MockResetSystem::new();
+        platform.expect_reset_system().return_const(());
+        let dispatcher = dispatcher(platform);
+        for notify in [first, second, third] {
+            dispatcher.register_notify(notify).unwrap();
+        }
+        dispatcher.unregister_notify(second).unwrap();
+        dispatcher.reset(request(ResetType::Cold));
+        assert_eq!(*ORDER.lock(), [1, 3]);
+    }
+
+    #[test]
+    fn registration_rules() {
+        fn filter(_: &mut ResetRequest<'_>) -> FilterAction {
+            FilterAction::Continue
+        }
+        fn notify(_: &ResetRequest<'_>) {}
+
+        let dispatcher = dispatcher(MockResetSystem::new());
+        assert_eq!(dispatcher.register_filter(filter), Ok(()));
+        assert_eq!(dispatcher.register_filter(filter), Err(EfiError::AlreadyStarted));
+        assert_eq!(dispatcher.register_notify(notify), Ok(()));
+        assert_eq!(dispatcher.register_notify(notify), Err(EfiError::AlreadyStarted));
+        assert_eq!(dispatcher.unregister_notify(notify), Ok(()));
+        assert_eq!(dispatcher.unregister_notify(notify), Err(EfiError::InvalidParameter));
+
+        dispatcher.exit_boot_services();
+        assert_eq!(dispatcher.register_notify(notify), Err(EfiError::Unsupported));
+        assert_eq!(dispatcher.register_filter(filter), Err(EfiError::Unsupported));
+        assert_eq!(dispatcher.unregister_notify(notify), Err(EfiError::Unsupported));
+    }
+}
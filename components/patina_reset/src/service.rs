@@ -0,0 +1,187 @@
+//! Reset Service Definitions
+//!
+//! Defines the [ResetSystem] trait that a platform implements to perform the actual reset, and the
+//! [ResetNotification] service produced by the [ResetSystemProvider] component so that other components can observe
+//! or filter reset requests before they reach the platform.
+//!
+//! [ResetSystemProvider]: crate::component::ResetSystemProvider
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::{error::EfiError, runtime_services::VirtualAddressMap};
+use r_efi::efi;
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// The kind of reset requested through `ResetSystem()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetType {
+    /// System-wide reset; every circuit is set to its initial state.
+    Cold,
+    /// System-wide initialization where the processors are reset but memory may be preserved.
+    Warm,
+    /// The system is placed in the ACPI G2/S5 or G3 state.
+    Shutdown,
+    /// A platform defined reset, identified by the GUID that follows the string in the reset data.
+    PlatformSpecific,
+}
+
+impl TryFrom<efi::ResetType> for ResetType {
+    type Error = EfiError;
+
+    fn try_from(value: efi::ResetType) -> Result<Self, Self::Error> {
+        match value {
+            efi::RESET_COLD => Ok(ResetType::Cold),
+            efi::RESET_WARM => Ok(ResetType::Warm),
+            efi::RESET_SHUTDOWN => Ok(ResetType::Shutdown),
+            efi::RESET_PLATFORM_SPECIFIC => Ok(ResetType::PlatformSpecific),
+            _ => Err(EfiError::InvalidParameter),
+        }
+    }
+}
+
+impl From<ResetType> for efi::ResetType {
+    fn from(value: ResetType) -> Self {
+        match value {
+            ResetType::Cold => efi::RESET_COLD,
+            ResetType::Warm => efi::RESET_WARM,
+            ResetType::Shutdown => efi::RESET_SHUTDOWN,
+            ResetType::PlatformSpecific => efi::RESET_PLATFORM_SPECIFIC,
+        }
+    }
+}
+
+/// A reset request, as passed to `ResetSystem()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetRequest<'a> {
+    /// The kind of reset to perform.
+    pub reset_type: ResetType,
+    /// The status code of the reset. Anything other than success indicates an abnormal reset.
+    pub status: efi::Status,
+    /// The reset data: a null-terminated UCS-2 string, optionally followed by binary data.
+    pub data: &'a [u8],
+}
+
+impl ResetRequest<'_> {
+    /// Returns the reset subtype GUID that follows the null-terminated string in the reset data.
+    ///
+    /// The subtype is only meaningful for [ResetType::PlatformSpecific] requests. `None` is returned if the data is
+    /// not terminated or too short to hold a GUID after the string.
+    pub fn reset_subtype(&self) -> Option<efi::Guid> {
+        let terminator = self.data.chunks_exact(2).position(|c| c == [0, 0])?;
+        let start = (terminator + 1) * 2;
+        let bytes = self.data.get(start..start + size_of::<efi::Guid>())?;
+        Some(efi::Guid::from_bytes(bytes.try_into().ok()?))
+    }
+}
+
+/// The outcome of a [ResetFilter].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Continue with the (possibly rewritten) request.
+    Continue,
+    /// Drop the request. `ResetSystem()` returns to its caller without resetting the platform.
+    Veto,
+}
+
+/// Inspects a reset request before the notifications run, and may rewrite or veto it.
+///
+/// Filters run in registration order, each one seeing the request as rewritten by the previous filters.
+pub type ResetFilter = fn(&mut ResetRequest<'_>) -> FilterAction;
+
+/// Called with the final reset request, just before the platform is reset.
+pub type ResetNotify = fn(&ResetRequest<'_>);
+
+/// Performs the platform reset.
+///
+/// Implemented by the platform and passed to [ResetSystemProvider::new], which moves it to runtime services memory.
+/// The implementation must remain callable after `ExitBootServices()`, so it must not use boot services or memory
+/// that is reclaimed by the OS.
+///
+/// [ResetSystemProvider]: crate::component::ResetSystemProvider
+/// [ResetSystemProvider::new]: crate::component::ResetSystemProvider::new
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+#[allow(clippy::needless_lifetimes)] //https://github.com/rust-lang/rust-clippy/issues/6622
+pub trait ResetSystem: Send + Sync {
+    /// Resets the platform as described by `request`. Does not return if the reset succeeds.
+    fn reset_system<'a>(&self, request: &ResetRequest<'a>);
+
+    /// Moves the addresses the implementation holds, such as those of reset registers, to the virtual addresses of
+    /// `map` when the OS calls `SetVirtualAddressMap()`.
+    ///
+    /// Implementations that hold no address do not need to implement this.
+    fn convert_pointers(&self, _map: &dyn VirtualAddressMap) {}
+}
+
+/// Registration of reset filters and notification callbacks.
+///
+/// Produced by the [ResetSystemProvider] component. Registrations are only accepted while boot services are
+/// available, but the callbacks also run for resets requested at runtime, so they are subject to the same
+/// restrictions as [ResetSystem] implementations.
+///
+/// [ResetSystemProvider]: crate::component::ResetSystemProvider
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait ResetNotification {
+    /// Registers a filter that can rewrite or veto reset requests.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::AlreadyStarted] if the filter is already registered.
+    /// - [EfiError::OutOfResources] if 16 filters are already registered.
+    /// - [EfiError::Unsupported] if called after `ExitBootServices()`.
+    fn register_filter(&self, filter: ResetFilter) -> Result<(), EfiError>;
+
+    /// Registers a callback that runs before the platform is reset.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::AlreadyStarted] if the callback is already registered.
+    /// - [EfiError::OutOfResources] if 16 callbacks are already registered.
+    /// - [EfiError::Unsupported] if called after `ExitBootServices()`.
+    fn register_notify(&self, notify: ResetNotify) -> Result<(), EfiError>;
+
+    /// Removes a callback registered with [ResetNotification::register_notify].
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::InvalidParameter] if the callback is not registered.
+    /// - [EfiError::Unsupported] if called after `ExitBootServices()`.
+    fn unregister_notify(&self, notify: ResetNotify) -> Result<(), EfiError>;
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const SUBTYPE: efi::Guid =
+        efi::Guid::from_fields(0x8b2a9e31, 0x4d4c, 0x4a8f, 0x9f, 0x2e, &[0x63, 0x1b, 0x0c, 0x5d, 0x7e, 0x11]);
+
+    #[test]
+    fn reset_type_round_trips() {
+        for reset_type in [ResetType::Cold, ResetType::Warm, ResetType::Shutdown, ResetType::PlatformSpecific] {
+            assert_eq!(ResetType::try_from(efi::ResetType::from(reset_type)), Ok(reset_type));
+        }
+        assert_eq!(ResetType::try_from(4), Err(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn reset_subtype_follows_the_string() {
+        let mut data = [b'O', 0, b'K', 0, 0, 0].to_vec();
+        data.extend_from_slice(SUBTYPE.as_bytes());
+        let request =
+            ResetRequest { reset_type: ResetType::PlatformSpecific, status: efi::Status::SUCCESS, data: &data };
+        assert_eq!(request.reset_subtype(), Some(SUBTYPE));
+
+        // Missing terminator, or a GUID that is cut short.
+        let request = ResetRequest { data: &data[..4], ..request };
+        assert_eq!(request.reset_subtype(), None);
+        let request = ResetRequest { data: &data[..data.len() - 1], ..request };
+        assert_eq!(request.reset_subtype(), None);
+    }
+}
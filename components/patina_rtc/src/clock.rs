@@ -0,0 +1,184 @@
+//! Validated Clock Access
+//!
+//! Wraps the platform's [RealTimeClock] service with the parameter checks of the UEFI time services, and keeps the
+//! time zone and daylight settings that the hardware does not store.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use patina::{error::EfiError, runtime_services::VirtualAddressMap};
+use r_efi::efi;
+use spin::Mutex;
+
+use crate::{
+    service::{RealTimeClock, WakeupTime},
+    time::validate,
+};
+
+/// The settings from the last successful `SetTime()`.
+#[derive(Debug, Clone, Copy)]
+struct Zone {
+    timezone: i16,
+    daylight: u8,
+}
+
+impl Zone {
+    fn apply(self, time: &mut efi::Time) {
+        time.timezone = self.timezone;
+        time.daylight = self.daylight;
+    }
+}
+
+/// Serializes access to the platform clock and validates the times passed through it.
+pub(crate) struct Clock {
+    rtc: &'static dyn RealTimeClock,
+    zone: Mutex<Zone>,
+    at_runtime: AtomicBool,
+}
+
+impl Clock {
+    pub(crate) fn new(rtc: &'static dyn RealTimeClock) -> Self {
+        Self {
+            rtc,
+            zone: Mutex::new(Zone { timezone: efi::UNSPECIFIED_TIMEZONE, daylight: 0 }),
+            at_runtime: AtomicBool::new(false),
+        }
+    }
+
+    pub(crate) fn exit_boot_services(&self) {
+        self.at_runtime.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn at_runtime(&self) -> bool {
+        self.at_runtime.load(Ordering::SeqCst)
+    }
+
+    /// Moves the clock to the virtual addresses of `map`, while the OS calls `SetVirtualAddressMap()`.
+    pub(crate) fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        self.rtc.convert_pointers(map);
+    }
+
+    /// Returns the current time and the capabilities of the clock.
+    ///
+    /// A time that the clock reports but that is not valid is a [EfiError::DeviceError].
+    pub(crate) fn get_time(&self) -> Result<(efi::Time, efi::TimeCapabilities), EfiError> {
+        let zone = self.zone.lock();
+        let mut time = self.rtc.get_time()?;
+        zone.apply(&mut time);
+        validate(&time).map_err(|_| EfiError::DeviceError)?;
+        Ok((time, self.rtc.capabilities()))
+    }
+
+    /// Sets the current time, and keeps its time zone and daylight settings.
+    pub(crate) fn set_time(&self, time: &efi::Time) -> Result<(), EfiError> {
+        validate(time)?;
+        let mut zone = self.zone.lock();
+        self.rtc.set_time(time)?;
+        *zone = Zone { timezone: time.timezone, daylight: time.daylight };
+        Ok(())
+    }
+
+    pub(crate) fn get_wakeup_time(&self) -> Result<WakeupTime, EfiError> {
+        let zone = self.zone.lock();
+        let mut wakeup = self.rtc.get_wakeup_time()?;
+        zone.apply(&mut wakeup.time);
+        validate(&wakeup.time).map_err(|_| EfiError::DeviceError)?;
+        Ok(wakeup)
+    }
+
+    /// Arms the wakeup alarm for `time` if `enable` is set, or disarms it. `time` is only needed to arm the alarm.
+    pub(crate) fn set_wakeup_time(&self, enable: bool, time: Option<&efi::Time>) -> Result<(), EfiError> {
+        let time = match (enable, time) {
+            (true, Some(time)) => {
+                validate(time)?;
+                Some(time)
+            }
+            (true, None) => return Err(EfiError::InvalidParameter),
+            (false, _) => None,
+        };
+        let _zone = self.zone.lock();
+        self.rtc.set_wakeup_time(time)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::MockRealTimeClock,
+        time::{fields, new_time},
+    };
+
+    fn clock(rtc: MockRealTimeClock) -> Clock {
+        Clock::new(Box::leak(Box::new(rtc)))
+    }
+
+    fn capabilities() -> efi::TimeCapabilities {
+        efi::TimeCapabilities { resolution: 1, accuracy: 0, sets_to_zero: efi::Boolean::FALSE }
+    }
+
+    #[test]
+    fn time_zone_and_daylight_are_kept_by_the_clock() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_set_time().times(1).returning(|_| Ok(()));
+        rtc.expect_get_time().returning(|| Ok(new_time(2024, 5, 1, 8, 0, 0)));
+        rtc.expect_capabilities().returning(capabilities);
+        let clock = clock(rtc);
+
+        let (time, _) = clock.get_time().unwrap();
+        assert_eq!(time.timezone, efi::UNSPECIFIED_TIMEZONE);
+        assert_eq!(time.daylight, 0);
+
+        let set = efi::Time { timezone: 120, daylight: efi::TIME_ADJUST_DAYLIGHT, ..new_time(2024, 5, 1, 8, 0, 0) };
+        clock.set_time(&set).unwrap();
+        let (time, capabilities) = clock.get_time().unwrap();
+        assert_eq!(fields(&time), fields(&set));
+        assert_eq!(capabilities.resolution, 1);
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_set_time().never();
+        rtc.expect_set_wakeup_time().never();
+        rtc.expect_get_time().returning(|| Ok(new_time(2023, 2, 29, 8, 0, 0)));
+        rtc.expect_capabilities().returning(capabilities);
+        let clock = clock(rtc);
+
+        assert_eq!(clock.set_time(&new_time(2024, 4, 31, 0, 0, 0)), Err(EfiError::InvalidParameter));
+        let bad_zone = efi::Time { timezone: 2000, ..new_time(2024, 4, 30, 0, 0, 0) };
+        assert_eq!(clock.set_time(&bad_zone), Err(EfiError::InvalidParameter));
+        assert_eq!(clock.set_wakeup_time(true, Some(&bad_zone)), Err(EfiError::InvalidParameter));
+        assert_eq!(clock.set_wakeup_time(true, None), Err(EfiError::InvalidParameter));
+
+        // The hardware reporting a date that does not exist.
+        assert_eq!(clock.get_time().unwrap_err(), EfiError::DeviceError);
+    }
+
+    #[test]
+    fn wakeup_time_is_passed_to_the_clock() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_set_wakeup_time()
+            .withf(|time| time.is_some_and(|time| time.hour == 6))
+            .times(1)
+            .returning(|_| Ok(()));
+        rtc.expect_set_wakeup_time().withf(|time| time.is_none()).times(1).returning(|_| Ok(()));
+        rtc.expect_get_wakeup_time()
+            .returning(|| Ok(WakeupTime { enabled: true, pending: false, time: new_time(2024, 5, 2, 6, 0, 0) }));
+        let clock = clock(rtc);
+
+        clock.set_wakeup_time(true, Some(&new_time(2024, 5, 2, 6, 0, 0))).unwrap();
+        // Disabling ignores the time, which may be missing or invalid.
+        clock.set_wakeup_time(false, Some(&new_time(2024, 0, 0, 0, 0, 0))).unwrap();
+
+        let wakeup = clock.get_wakeup_time().unwrap();
+        assert!(wakeup.enabled);
+        assert_eq!(wakeup.time.timezone, efi::UNSPECIFIED_TIMEZONE);
+    }
+}
@@ -0,0 +1,422 @@
+//! PC CMOS Real-Time Clock
+//!
+//! [RealTimeClock] implementation for the MC146818 compatible clock found in the CMOS of PC platforms, reached
+//! through the index and data ports at 0x70 and 0x71. The clock registers may hold BCD or binary values and 12 or
+//! 24 hour time, as selected by status register B; the format programmed by earlier firmware is kept.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::error::EfiError;
+use r_efi::efi;
+use spin::Mutex;
+
+use crate::{
+    io::PortIo,
+    service::{RealTimeClock, WakeupTime},
+    time::to_unix_seconds,
+};
+
+/// The CMOS index port.
+pub const INDEX_PORT: u16 = 0x70;
+/// The CMOS data port.
+pub const DATA_PORT: u16 = 0x71;
+/// The register that holds the century on most platforms, as reported by the `CENTURY` field of the ACPI FADT.
+pub const DEFAULT_CENTURY_REGISTER: u8 = 0x32;
+
+const SECONDS: u8 = 0x00;
+const SECONDS_ALARM: u8 = 0x01;
+const MINUTES: u8 = 0x02;
+const MINUTES_ALARM: u8 = 0x03;
+const HOURS: u8 = 0x04;
+const HOURS_ALARM: u8 = 0x05;
+const DAY_OF_MONTH: u8 = 0x07;
+const MONTH: u8 = 0x08;
+const YEAR: u8 = 0x09;
+const STATUS_A: u8 = 0x0A;
+const STATUS_B: u8 = 0x0B;
+const STATUS_C: u8 = 0x0C;
+const STATUS_D: u8 = 0x0D;
+
+const STATUS_A_UPDATE_IN_PROGRESS: u8 = 0x80;
+const STATUS_A_DIVIDER_MASK: u8 = 0x70;
+/// 32.768 kHz time base.
+const STATUS_A_DIVIDER_32KHZ: u8 = 0x20;
+/// 32.768 kHz time base and a 1024 Hz periodic rate, the power-on default.
+const STATUS_A_DEFAULT: u8 = 0x26;
+const STATUS_B_SET: u8 = 0x80;
+const STATUS_B_ALARM_INTERRUPT_ENABLE: u8 = 0x20;
+const STATUS_B_BINARY: u8 = 0x04;
+const STATUS_B_24_HOUR: u8 = 0x02;
+const STATUS_C_ALARM_FLAG: u8 = 0x20;
+const STATUS_D_VALID_RAM_AND_TIME: u8 = 0x80;
+const HOUR_PM: u8 = 0x80;
+
+/// Status register A reads before an update in progress is considered stuck, at least 100ms of port I/O.
+const UPDATE_POLL_LIMIT: usize = 100_000;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// The encoding of the clock registers, selected by status register B.
+#[derive(Debug, Clone, Copy)]
+struct Format {
+    binary: bool,
+    hour_24: bool,
+}
+
+impl Format {
+    fn from_status_b(status_b: u8) -> Self {
+        Self { binary: status_b & STATUS_B_BINARY != 0, hour_24: status_b & STATUS_B_24_HOUR != 0 }
+    }
+
+    fn decode(self, value: u8) -> Result<u8, EfiError> {
+        if self.binary {
+            return Ok(value);
+        }
+        let (tens, ones) = (value >> 4, value & 0x0F);
+        if tens > 9 || ones > 9 {
+            return Err(EfiError::DeviceError);
+        }
+        Ok(tens * 10 + ones)
+    }
+
+    fn encode(self, value: u8) -> u8 {
+        if self.binary { value } else { ((value / 10) << 4) | (value % 10) }
+    }
+
+    fn decode_hour(self, value: u8) -> Result<u8, EfiError> {
+        if self.hour_24 {
+            return self.decode(value);
+        }
+        let hour = self.decode(value & !HOUR_PM)?;
+        if !(1..=12).contains(&hour) {
+            return Err(EfiError::DeviceError);
+        }
+        Ok(hour % 12 + if value & HOUR_PM != 0 { 12 } else { 0 })
+    }
+
+    fn encode_hour(self, hour: u8) -> u8 {
+        if self.hour_24 {
+            return self.encode(hour);
+        }
+        let pm = if hour >= 12 { HOUR_PM } else { 0 };
+        match hour % 12 {
+            0 => self.encode(12) | pm,
+            hour => self.encode(hour) | pm,
+        }
+    }
+}
+
+/// The CMOS real-time clock.
+///
+/// ```rust,ignore
+/// commands.add_component(RealTimeClockProvider::new(CmosRtc::new(IoPorts)));
+/// ```
+pub struct CmosRtc<P: PortIo + 'static> {
+    io: P,
+    century_register: Option<u8>,
+    /// The date of the armed alarm; the alarm registers only hold the time of day.
+    wakeup_date: Mutex<Option<(u16, u8, u8)>>,
+}
+
+impl<P: PortIo + 'static> CmosRtc<P> {
+    /// Creates the clock, using [DEFAULT_CENTURY_REGISTER] for the century.
+    pub const fn new(io: P) -> Self {
+        Self { io, century_register: Some(DEFAULT_CENTURY_REGISTER), wakeup_date: Mutex::new(None) }
+    }
+
+    /// Uses `register` for the century.
+    ///
+    /// With `None`, two-digit years below 70 are in the 2000s and the others in the 1900s, so only the years 1970
+    /// to 2069 can be set.
+    pub const fn with_century_register(mut self, register: Option<u8>) -> Self {
+        self.century_register = register;
+        self
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        self.io.write_u8(INDEX_PORT, register);
+        self.io.read_u8(DATA_PORT)
+    }
+
+    fn write(&self, register: u8, value: u8) {
+        self.io.write_u8(INDEX_PORT, register);
+        self.io.write_u8(DATA_PORT, value);
+    }
+
+    /// Waits until the clock is not updating, after which its registers are stable for at least 244us.
+    fn wait_for_update(&self) -> Result<(), EfiError> {
+        for _ in 0..UPDATE_POLL_LIMIT {
+            if self.read(STATUS_A) & STATUS_A_UPDATE_IN_PROGRESS == 0 {
+                return Ok(());
+            }
+        }
+        Err(EfiError::DeviceError)
+    }
+
+    fn format(&self) -> Format {
+        Format::from_status_b(self.read(STATUS_B))
+    }
+}
+
+impl<P: PortIo + 'static> RealTimeClock for CmosRtc<P> {
+    fn initialize(&self) -> Result<(), EfiError> {
+        if self.read(STATUS_D) & STATUS_D_VALID_RAM_AND_TIME == 0 {
+            return Err(EfiError::DeviceError);
+        }
+        if self.read(STATUS_A) & STATUS_A_DIVIDER_MASK != STATUS_A_DIVIDER_32KHZ {
+            self.write(STATUS_A, STATUS_A_DEFAULT);
+        }
+        // An interrupted update may have left the clock halted.
+        let status_b = self.read(STATUS_B);
+        if status_b & STATUS_B_SET != 0 {
+            self.write(STATUS_B, status_b & !STATUS_B_SET);
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> efi::TimeCapabilities {
+        efi::TimeCapabilities { resolution: 1, accuracy: 50_000_000, sets_to_zero: efi::Boolean::FALSE }
+    }
+
+    fn get_time(&self) -> Result<efi::Time, EfiError> {
+        self.wait_for_update()?;
+        let format = self.format();
+        let year = format.decode(self.read(YEAR))? as u16;
+        let year = match self.century_register {
+            Some(register) => format.decode(self.read(register))? as u16 * 100 + year,
+            None if year < 70 => 2000 + year,
+            None => 1900 + year,
+        };
+        Ok(efi::Time {
+            year,
+            month: format.decode(self.read(MONTH))?,
+            day: format.decode(self.read(DAY_OF_MONTH))?,
+            hour: format.decode_hour(self.read(HOURS))?,
+            minute: format.decode(self.read(MINUTES))?,
+            second: format.decode(self.read(SECONDS))?,
+            timezone: efi::UNSPECIFIED_TIMEZONE,
+            ..Default::default()
+        })
+    }
+
+    fn set_time(&self, time: &efi::Time) -> Result<(), EfiError> {
+        if self.century_register.is_none() && !(1970..=2069).contains(&time.year) {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let status_b = self.read(STATUS_B);
+        let format = Format::from_status_b(status_b);
+        // Halt the updates so the registers are not changed halfway through.
+        self.write(STATUS_B, status_b | STATUS_B_SET);
+        self.write(SECONDS, format.encode(time.second));
+        self.write(MINUTES, format.encode(time.minute));
+        self.write(HOURS, format.encode_hour(time.hour));
+        self.write(DAY_OF_MONTH, format.encode(time.day));
+        self.write(MONTH, format.encode(time.month));
+        self.write(YEAR, format.encode((time.year % 100) as u8));
+        if let Some(register) = self.century_register {
+            self.write(register, format.encode((time.year / 100) as u8));
+        }
+        self.write(STATUS_B, status_b & !STATUS_B_SET);
+        Ok(())
+    }
+
+    fn get_wakeup_time(&self) -> Result<WakeupTime, EfiError> {
+        let now = self.get_time()?;
+        let status_b = self.read(STATUS_B);
+        let format = Format::from_status_b(status_b);
+        let (year, month, day) = self.wakeup_date.lock().unwrap_or((now.year, now.month, now.day));
+        Ok(WakeupTime {
+            enabled: status_b & STATUS_B_ALARM_INTERRUPT_ENABLE != 0,
+            pending: self.read(STATUS_C) & STATUS_C_ALARM_FLAG != 0,
+            time: efi::Time {
+                year,
+                month,
+                day,
+                hour: format.decode_hour(self.read(HOURS_ALARM))?,
+                minute: format.decode(self.read(MINUTES_ALARM))?,
+                second: format.decode(self.read(SECONDS_ALARM))?,
+                timezone: efi::UNSPECIFIED_TIMEZONE,
+                ..Default::default()
+            },
+        })
+    }
+
+    fn set_wakeup_time(&self, time: Option<&efi::Time>) -> Result<(), EfiError> {
+        let mut wakeup_date = self.wakeup_date.lock();
+        let Some(time) = time else {
+            let status_b = self.read(STATUS_B);
+            self.write(STATUS_B, status_b & !STATUS_B_ALARM_INTERRUPT_ENABLE);
+            *wakeup_date = None;
+            return Ok(());
+        };
+
+        // The alarm fires on the next match of the time of day, so it cannot be more than a day ahead.
+        let now = self.get_time()?;
+        if !(0..=SECONDS_PER_DAY).contains(&(to_unix_seconds(time) - to_unix_seconds(&now))) {
+            return Err(EfiError::Unsupported);
+        }
+
+        let status_b = self.read(STATUS_B);
+        let format = Format::from_status_b(status_b);
+        self.write(STATUS_B, status_b & !STATUS_B_ALARM_INTERRUPT_ENABLE);
+        self.write(SECONDS_ALARM, format.encode(time.second));
+        self.write(MINUTES_ALARM, format.encode(time.minute));
+        self.write(HOURS_ALARM, format.encode_hour(time.hour));
+        // Reading status register C acknowledges an alarm that fired earlier.
+        self.read(STATUS_C);
+        self.write(STATUS_B, status_b | STATUS_B_ALARM_INTERRUPT_ENABLE);
+        *wakeup_date = Some((time.year, time.month, time.day));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::time::{fields, new_time};
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    /// A CMOS that holds whatever is written to it, with no clock running.
+    struct SimulatedCmos {
+        index: AtomicU8,
+        registers: Mutex<[u8; 128]>,
+    }
+
+    impl SimulatedCmos {
+        fn new(status_b: u8) -> &'static Self {
+            let mut registers = [0; 128];
+            registers[STATUS_A as usize] = STATUS_A_DEFAULT;
+            registers[STATUS_B as usize] = status_b;
+            registers[STATUS_D as usize] = STATUS_D_VALID_RAM_AND_TIME;
+            Box::leak(Box::new(Self { index: AtomicU8::new(0), registers: Mutex::new(registers) }))
+        }
+
+        fn register(&self, register: u8) -> u8 {
+            self.registers.lock()[register as usize]
+        }
+
+        fn set_register(&self, register: u8, value: u8) {
+            self.registers.lock()[register as usize] = value;
+        }
+    }
+
+    impl PortIo for &'static SimulatedCmos {
+        fn read_u8(&self, port: u16) -> u8 {
+            assert_eq!(port, DATA_PORT);
+            let index = self.index.load(Ordering::SeqCst);
+            let value = self.register(index);
+            if index == STATUS_C {
+                self.set_register(STATUS_C, 0);
+            }
+            value
+        }
+
+        fn write_u8(&self, port: u16, value: u8) {
+            match port {
+                INDEX_PORT => self.index.store(value & 0x7F, Ordering::SeqCst),
+                DATA_PORT => self.set_register(self.index.load(Ordering::SeqCst), value),
+                _ => panic!("Unexpected port {port:#x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn bcd_and_12_hour_registers_are_converted() {
+        let cmos = SimulatedCmos::new(0);
+        let rtc = CmosRtc::new(cmos);
+
+        rtc.set_time(&new_time(2024, 12, 31, 23, 59, 58)).unwrap();
+        assert_eq!(cmos.register(SECONDS), 0x58);
+        assert_eq!(cmos.register(HOURS), 0x11 | HOUR_PM);
+        assert_eq!(cmos.register(DAY_OF_MONTH), 0x31);
+        assert_eq!(cmos.register(YEAR), 0x24);
+        assert_eq!(cmos.register(DEFAULT_CENTURY_REGISTER), 0x20);
+        assert_eq!(cmos.register(STATUS_B) & STATUS_B_SET, 0);
+        assert_eq!(fields(&rtc.get_time().unwrap()), fields(&new_time(2024, 12, 31, 23, 59, 58)));
+
+        // Midnight and noon are 12 AM and 12 PM.
+        rtc.set_time(&new_time(2024, 1, 1, 0, 0, 0)).unwrap();
+        assert_eq!(cmos.register(HOURS), 0x12);
+        rtc.set_time(&new_time(2024, 1, 1, 12, 0, 0)).unwrap();
+        assert_eq!(cmos.register(HOURS), 0x12 | HOUR_PM);
+        assert_eq!(rtc.get_time().unwrap().hour, 12);
+    }
+
+    #[test]
+    fn binary_and_24_hour_registers_are_used_as_is() {
+        let cmos = SimulatedCmos::new(STATUS_B_BINARY | STATUS_B_24_HOUR);
+        let rtc = CmosRtc::new(cmos).with_century_register(None);
+
+        rtc.set_time(&new_time(2069, 6, 15, 18, 30, 5)).unwrap();
+        assert_eq!(cmos.register(HOURS), 18);
+        assert_eq!(cmos.register(YEAR), 69);
+        assert_eq!(fields(&rtc.get_time().unwrap()), fields(&new_time(2069, 6, 15, 18, 30, 5)));
+
+        // Two-digit years cannot describe years outside 1970 to 2069.
+        assert_eq!(rtc.set_time(&new_time(2070, 1, 1, 0, 0, 0)), Err(EfiError::InvalidParameter));
+        cmos.set_register(YEAR, 70);
+        assert_eq!(rtc.get_time().unwrap().year, 1970);
+    }
+
+    #[test]
+    fn invalid_registers_are_device_errors() {
+        let cmos = SimulatedCmos::new(0);
+        let rtc = CmosRtc::new(cmos);
+
+        cmos.set_register(MINUTES, 0x5A);
+        assert_eq!(rtc.get_time().unwrap_err(), EfiError::DeviceError);
+        cmos.set_register(MINUTES, 0);
+        cmos.set_register(HOURS, 0x13);
+        assert_eq!(rtc.get_time().unwrap_err(), EfiError::DeviceError);
+
+        // An update that never completes.
+        cmos.set_register(STATUS_A, STATUS_A_DEFAULT | STATUS_A_UPDATE_IN_PROGRESS);
+        assert_eq!(rtc.get_time().unwrap_err(), EfiError::DeviceError);
+    }
+
+    #[test]
+    fn initialize_restarts_a_halted_clock() {
+        let cmos = SimulatedCmos::new(STATUS_B_SET | STATUS_B_24_HOUR);
+        cmos.set_register(STATUS_A, 0);
+        let rtc = CmosRtc::new(cmos);
+
+        assert_eq!(rtc.initialize(), Ok(()));
+        assert_eq!(cmos.register(STATUS_A), STATUS_A_DEFAULT);
+        assert_eq!(cmos.register(STATUS_B), STATUS_B_24_HOUR);
+
+        cmos.set_register(STATUS_D, 0);
+        assert_eq!(rtc.initialize(), Err(EfiError::DeviceError));
+    }
+
+    #[test]
+    fn wakeup_alarm_is_limited_to_one_day() {
+        let cmos = SimulatedCmos::new(STATUS_B_24_HOUR);
+        let rtc = CmosRtc::new(cmos);
+        rtc.set_time(&new_time(2024, 2, 28, 22, 0, 0)).unwrap();
+
+        rtc.set_wakeup_time(Some(&new_time(2024, 2, 29, 6, 45, 0))).unwrap();
+        assert_eq!(cmos.register(HOURS_ALARM), 0x06);
+        assert_eq!(cmos.register(MINUTES_ALARM), 0x45);
+        let wakeup = rtc.get_wakeup_time().unwrap();
+        assert!(wakeup.enabled);
+        assert!(!wakeup.pending);
+        assert_eq!(fields(&wakeup.time), fields(&new_time(2024, 2, 29, 6, 45, 0)));
+
+        cmos.set_register(STATUS_C, STATUS_C_ALARM_FLAG);
+        assert!(rtc.get_wakeup_time().unwrap().pending);
+
+        assert_eq!(rtc.set_wakeup_time(Some(&new_time(2024, 3, 1, 6, 45, 0))), Err(EfiError::Unsupported));
+        assert_eq!(rtc.set_wakeup_time(Some(&new_time(2024, 2, 28, 21, 0, 0))), Err(EfiError::Unsupported));
+
+        rtc.set_wakeup_time(None).unwrap();
+        let wakeup = rtc.get_wakeup_time().unwrap();
+        assert!(!wakeup.enabled);
+        assert_eq!(fields(&wakeup.time), fields(&new_time(2024, 2, 28, 6, 45, 0)));
+    }
+}
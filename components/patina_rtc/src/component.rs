@@ -0,0 +1,343 @@
+//! Real-Time Clock Provider Component
+//!
+//! Installs the `GetTime()`, `SetTime()`, `GetWakeupTime()` and `SetWakeupTime()` entries of the runtime services
+//! table on top of the platform's [RealTimeClock], and the Real Time Clock architectural protocol.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate alloc;
+
+use core::ptr;
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        component,
+        service::{
+            Service,
+            memory::MemoryManager,
+            runtime_table::{RuntimeServiceEntries, RuntimeServicesTable, TimeEntries},
+        },
+    },
+    error::{EfiError, Result},
+    pi::protocols::real_time_clock,
+    runtime_services::{RuntimeServices, StandardRuntimeServices},
+};
+use r_efi::efi;
+
+use crate::{clock::Clock, service::RealTimeClock};
+
+/// State shared with the runtime services table entries, which receive no context pointer.
+struct Provider {
+    clock: &'static Clock,
+    boot_services: StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+}
+
+static PROVIDER: spin::Once<Provider> = spin::Once::new();
+
+/// Produces the UEFI time runtime services.
+///
+/// Takes the platform's [RealTimeClock] and moves it to runtime services memory. Times passed to `SetTime()` and
+/// `SetWakeupTime()` are validated before they reach the clock, and times read from the clock are validated before
+/// they are returned.
+///
+/// The code of the services is part of the DXE core image, so the OS cannot call them after `ExitBootServices()`.
+///
+/// ```rust,ignore
+/// commands.add_component(RealTimeClockProvider::new(CmosRtc::new(IoPorts)));
+/// ```
+pub struct RealTimeClockProvider<R: RealTimeClock + 'static> {
+    rtc: R,
+}
+
+#[component]
+impl<R: RealTimeClock + 'static> RealTimeClockProvider<R> {
+    /// Creates the component for `rtc`.
+    pub const fn new(rtc: R) -> Self {
+        Self { rtc }
+    }
+
+    #[coverage(off)] // Component integration - the time services it wires up are tested directly.
+    fn entry_point(
+        self,
+        rt_table: Service<dyn RuntimeServicesTable>,
+        memory_manager: Service<dyn MemoryManager>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+    ) -> Result<()> {
+        if PROVIDER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        // The OS reads the clock at runtime, after boot services memory is reclaimed.
+        let rtc: &'static dyn RealTimeClock = memory_manager.leak_in_runtime_memory(self.rtc)?;
+        rtc.initialize().inspect_err(|err| log::error!(target: "rtc", "Failed to initialize the clock: {err:?}"))?;
+
+        let clock: &'static Clock = memory_manager.leak_in_runtime_memory(Clock::new(rtc))?;
+        PROVIDER.call_once(|| Provider { clock, boot_services: boot_services.clone(), runtime_services });
+
+        boot_services
+            .create_event(EventType::SIGNAL_EXIT_BOOT_SERVICES, Tpl::NOTIFY, Some(exit_boot_services), clock)
+            .map_err(EfiError::from)?;
+        boot_services
+            .create_event(EventType::SIGNAL_VIRTUAL_ADDRESS_CHANGE, Tpl::NOTIFY, Some(virtual_address_change), clock)
+            .map_err(EfiError::from)?;
+
+        rt_table.install(RuntimeServiceEntries::Time(TimeEntries {
+            get_time,
+            set_time,
+            get_wakeup_time,
+            set_wakeup_time,
+        }))?;
+
+        // SAFETY: The architectural protocol carries no interface; a null pointer is what consumers expect.
+        unsafe {
+            boot_services.install_protocol_interface_unchecked(None, &real_time_clock::PROTOCOL_GUID, ptr::null_mut())
+        }
+        .map_err(EfiError::from)?;
+
+        log::info!(target: "rtc", "Time services installed.");
+        Ok(())
+    }
+}
+
+extern "efiapi" fn exit_boot_services(_event: efi::Event, clock: &'static Clock) {
+    clock.exit_boot_services();
+}
+
+extern "efiapi" fn virtual_address_change(_event: efi::Event, clock: &'static Clock) {
+    if let Some(provider) = PROVIDER.get() {
+        convert_clock(clock, &provider.runtime_services);
+    }
+}
+
+/// Moves `clock` to the virtual addresses the OS has mapped its registers at.
+fn convert_clock(clock: &Clock, runtime_services: &impl RuntimeServices) {
+    clock.convert_pointers(&|address| runtime_services.convert_pointer(address).ok());
+}
+
+/// Runs `f` against the installed clock, at `TPL_NOTIFY` while boot services are available.
+fn with_clock(f: impl FnOnce(&Clock) -> efi::Status) -> efi::Status {
+    let Some(provider) = PROVIDER.get() else {
+        return efi::Status::NOT_READY;
+    };
+    if provider.clock.at_runtime() {
+        return f(provider.clock);
+    }
+    let _tpl = provider.boot_services.raise_tpl_guarded(Tpl::NOTIFY);
+    f(provider.clock)
+}
+
+fn status(result: core::result::Result<(), EfiError>) -> efi::Status {
+    match result {
+        Ok(()) => efi::Status::SUCCESS,
+        Err(err) => err.into(),
+    }
+}
+
+extern "efiapi" fn get_time(time: *mut efi::Time, capabilities: *mut efi::TimeCapabilities) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_clock(|clock| unsafe { get_time_with(clock, time, capabilities) })
+}
+
+extern "efiapi" fn set_time(time: *mut efi::Time) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_clock(|clock| unsafe { set_time_with(clock, time) })
+}
+
+extern "efiapi" fn get_wakeup_time(
+    enabled: *mut efi::Boolean,
+    pending: *mut efi::Boolean,
+    time: *mut efi::Time,
+) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_clock(|clock| unsafe { get_wakeup_time_with(clock, enabled, pending, time) })
+}
+
+extern "efiapi" fn set_wakeup_time(enable: efi::Boolean, time: *mut efi::Time) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_clock(|clock| unsafe { set_wakeup_time_with(clock, enable, time) })
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `GetTime()` in the UEFI specification.
+unsafe fn get_time_with(clock: &Clock, time: *mut efi::Time, capabilities: *mut efi::TimeCapabilities) -> efi::Status {
+    if time.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    match clock.get_time() {
+        Ok((now, caps)) => {
+            // SAFETY: time was checked for null above, and capabilities is written only if it is not null.
+            unsafe {
+                time.write(now);
+                if !capabilities.is_null() {
+                    capabilities.write(caps);
+                }
+            }
+            efi::Status::SUCCESS
+        }
+        Err(err) => err.into(),
+    }
+}
+
+/// # Safety
+///
+/// `time` must be null or valid as described for `SetTime()` in the UEFI specification.
+unsafe fn set_time_with(clock: &Clock, time: *const efi::Time) -> efi::Status {
+    // SAFETY: The caller guarantees the pointer is null or valid.
+    let Some(time) = (unsafe { time.as_ref() }) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    status(clock.set_time(time))
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `GetWakeupTime()` in the UEFI specification.
+unsafe fn get_wakeup_time_with(
+    clock: &Clock,
+    enabled: *mut efi::Boolean,
+    pending: *mut efi::Boolean,
+    time: *mut efi::Time,
+) -> efi::Status {
+    if enabled.is_null() || pending.is_null() || time.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    match clock.get_wakeup_time() {
+        Ok(wakeup) => {
+            // SAFETY: All pointers were checked for null above and are otherwise valid per the caller contract.
+            unsafe {
+                enabled.write(wakeup.enabled.into());
+                pending.write(wakeup.pending.into());
+                time.write(wakeup.time);
+            }
+            efi::Status::SUCCESS
+        }
+        Err(err) => err.into(),
+    }
+}
+
+/// # Safety
+///
+/// `time` must be null or valid as described for `SetWakeupTime()` in the UEFI specification.
+unsafe fn set_wakeup_time_with(clock: &Clock, enable: efi::Boolean, time: *const efi::Time) -> efi::Status {
+    // SAFETY: The caller guarantees the pointer is null or valid.
+    let time = unsafe { time.as_ref() };
+    status(clock.set_wakeup_time(enable.into(), time))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::MmioRegion,
+        pl031::Pl031Rtc,
+        service::{MockRealTimeClock, WakeupTime},
+        time::{fields, new_time},
+    };
+    use alloc::boxed::Box;
+    use patina::runtime_services::MockRuntimeServices;
+
+    fn clock(rtc: MockRealTimeClock) -> Clock {
+        Clock::new(Box::leak(Box::new(rtc)))
+    }
+
+    #[test]
+    fn get_time_fills_the_optional_capabilities() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_get_time().returning(|| Ok(new_time(2024, 5, 1, 8, 0, 0)));
+        rtc.expect_capabilities().returning(|| efi::TimeCapabilities {
+            resolution: 1,
+            accuracy: 50_000_000,
+            sets_to_zero: efi::Boolean::FALSE,
+        });
+        let clock = clock(rtc);
+
+        let mut time = efi::Time::default();
+        let mut capabilities = efi::TimeCapabilities { resolution: 0, accuracy: 0, sets_to_zero: efi::Boolean::TRUE };
+        // SAFETY: All pointers are null or reference locals.
+        unsafe {
+            assert_eq!(get_time_with(&clock, ptr::null_mut(), ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+            assert_eq!(get_time_with(&clock, &mut time, ptr::null_mut()), efi::Status::SUCCESS);
+            assert_eq!(get_time_with(&clock, &mut time, &mut capabilities), efi::Status::SUCCESS);
+        }
+        assert_eq!(fields(&time), fields(&new_time(2024, 5, 1, 8, 0, 0)));
+        assert_eq!(capabilities.accuracy, 50_000_000);
+    }
+
+    #[test]
+    fn set_time_requires_a_valid_time() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_set_time().times(1).returning(|_| Ok(()));
+        let clock = clock(rtc);
+
+        let mut time = new_time(2024, 2, 30, 0, 0, 0);
+        // SAFETY: All pointers are null or reference locals.
+        unsafe {
+            assert_eq!(set_time_with(&clock, ptr::null()), efi::Status::INVALID_PARAMETER);
+            assert_eq!(set_time_with(&clock, &time), efi::Status::INVALID_PARAMETER);
+            time.day = 29;
+            assert_eq!(set_time_with(&clock, &time), efi::Status::SUCCESS);
+        }
+    }
+
+    #[test]
+    fn wakeup_time_arguments_are_checked() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_get_wakeup_time()
+            .returning(|| Ok(WakeupTime { enabled: true, pending: true, time: new_time(2024, 5, 2, 6, 0, 0) }));
+        rtc.expect_set_wakeup_time().withf(|time| time.is_none()).times(1).returning(|_| Ok(()));
+        let clock = clock(rtc);
+
+        let mut enabled = efi::Boolean::FALSE;
+        let mut pending = efi::Boolean::FALSE;
+        let mut time = efi::Time::default();
+        // SAFETY: All pointers are null or reference locals.
+        unsafe {
+            assert_eq!(
+                get_wakeup_time_with(&clock, &mut enabled, ptr::null_mut(), &mut time),
+                efi::Status::INVALID_PARAMETER
+            );
+            assert_eq!(get_wakeup_time_with(&clock, &mut enabled, &mut pending, &mut time), efi::Status::SUCCESS);
+            assert_eq!(set_wakeup_time_with(&clock, efi::Boolean::TRUE, ptr::null()), efi::Status::INVALID_PARAMETER);
+            assert_eq!(set_wakeup_time_with(&clock, efi::Boolean::FALSE, ptr::null()), efi::Status::SUCCESS);
+        }
+        assert!(bool::from(enabled) && bool::from(pending));
+        assert_eq!(time.hour, 6);
+    }
+
+    #[test]
+    fn unsupported_wakeup_is_reported() {
+        let mut rtc = MockRealTimeClock::new();
+        rtc.expect_get_wakeup_time().returning(|| Err(EfiError::Unsupported));
+        let clock = clock(rtc);
+
+        let (mut enabled, mut pending, mut time) = (efi::Boolean::FALSE, efi::Boolean::FALSE, efi::Time::default());
+        // SAFETY: All pointers reference locals.
+        let status = unsafe { get_wakeup_time_with(&clock, &mut enabled, &mut pending, &mut time) };
+        assert_eq!(status, efi::Status::UNSUPPORTED);
+    }
+
+    #[test]
+    fn clock_registers_follow_the_virtual_address_map() {
+        // The PL031 data register holds the seconds since 1970.
+        let registers = [951_827_415u32, 0, 0, 0, 0, 0, 0, 0];
+        let physical = registers.as_ptr() as usize;
+        // SAFETY: The register block is a local array, only read once the region has been moved to it.
+        let rtc = Pl031Rtc::new(unsafe { MmioRegion::new(physical - 0x1000) });
+        let clock = Clock::new(Box::leak(Box::new(rtc)));
+
+        let mut runtime_services = MockRuntimeServices::new();
+        runtime_services.expect_convert_pointer().returning(|address| Ok(address + 0x1000));
+        convert_clock(&clock, &runtime_services);
+
+        assert_eq!(fields(&clock.get_time().unwrap().0), fields(&new_time(2000, 2, 29, 12, 30, 15)));
+    }
+}
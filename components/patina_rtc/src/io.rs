@@ -0,0 +1,128 @@
+//! Register Access
+//!
+//! The clock backends reach their hardware only through [PortIo] or [Mmio], so that they can be driven by a simulated
+//! device in host-based tests.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use patina::runtime_services::VirtualAddressMap;
+
+/// Byte-wide access to x86 I/O ports.
+pub trait PortIo: Send + Sync {
+    /// Reads a byte from `port`.
+    fn read_u8(&self, port: u16) -> u8;
+
+    /// Writes `value` to `port`.
+    fn write_u8(&self, port: u16, value: u8);
+}
+
+/// 32-bit access to the memory mapped registers of a device.
+pub trait Mmio: Send + Sync {
+    /// Reads the register at `offset` bytes from the base of the device.
+    fn read_u32(&self, offset: usize) -> u32;
+
+    /// Writes `value` to the register at `offset` bytes from the base of the device.
+    fn write_u32(&self, offset: usize, value: u32);
+
+    /// Moves the accessor to the virtual address of the register block when the OS calls `SetVirtualAddressMap()`.
+    ///
+    /// Accessors that hold no address do not need to implement this.
+    fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        let _ = map;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(any(feature = "doc", all(target_os = "uefi", target_arch = "x86_64")))] {
+        use x86_64::instructions::port::Port;
+
+        /// [PortIo] through the `in` and `out` instructions.
+        #[derive(Debug, Default, Clone, Copy)]
+        pub struct IoPorts;
+
+        impl PortIo for IoPorts {
+            fn read_u8(&self, port: u16) -> u8 {
+                // SAFETY: Port I/O has no memory safety implications; the backend only accesses its own device.
+                unsafe { Port::<u8>::new(port).read() }
+            }
+
+            fn write_u8(&self, port: u16, value: u8) {
+                // SAFETY: Port I/O has no memory safety implications; the backend only accesses its own device.
+                unsafe { Port::<u8>::new(port).write(value) }
+            }
+        }
+    }
+}
+
+/// [Mmio] through volatile accesses to a register block.
+///
+/// The address of the block is converted to its virtual address when the OS calls `SetVirtualAddressMap()`.
+#[derive(Debug)]
+pub struct MmioRegion {
+    base: AtomicUsize,
+}
+
+impl MmioRegion {
+    /// Creates an accessor for the register block at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the address of the device's register block, mapped as device memory for as long as the
+    /// accessor is used, including at runtime if the accessor backs a runtime service.
+    pub const unsafe fn new(base: usize) -> Self {
+        Self { base: AtomicUsize::new(base) }
+    }
+
+    fn register(&self, offset: usize) -> usize {
+        self.base.load(Ordering::Relaxed) + offset
+    }
+}
+
+impl Mmio for MmioRegion {
+    fn read_u32(&self, offset: usize) -> u32 {
+        // SAFETY: The register block is mapped per the contract of MmioRegion::new.
+        unsafe { ptr::read_volatile(self.register(offset) as *const u32) }
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) {
+        // SAFETY: The register block is mapped per the contract of MmioRegion::new.
+        unsafe { ptr::write_volatile(self.register(offset) as *mut u32, value) }
+    }
+
+    fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        // A block the OS did not map stays where it was; it cannot be reached either way.
+        if let Some(base) = map.convert(self.base.load(Ordering::Relaxed)) {
+            self.base.store(base, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mmio_region_follows_the_virtual_address_map() {
+        let mut registers = [0u32; 4];
+        let physical = registers.as_mut_ptr() as usize;
+        // SAFETY: The register block is a local array, used while it is in scope.
+        let region = unsafe { MmioRegion::new(physical - 0x1000) };
+
+        region.convert_pointers(&|address| (address == physical - 0x1000).then_some(physical));
+        region.write_u32(4, 0x1234_5678);
+        assert_eq!(registers[1], 0x1234_5678);
+
+        region.convert_pointers(&|_| None);
+        assert_eq!(region.read_u32(4), 0x1234_5678);
+    }
+}
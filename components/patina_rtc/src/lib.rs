@@ -0,0 +1,18 @@
+#![doc = include_str!("../README.md")]
+#![doc = concat!(
+    "## License\n\n",
+    " Copyright (c) Microsoft Corporation.\n\n",
+)]
+#![cfg_attr(all(not(feature = "std"), not(test), not(feature = "mockall")), no_std)]
+#![feature(coverage_attribute)]
+
+extern crate alloc;
+
+pub mod cmos;
+pub mod component;
+pub mod io;
+pub mod pl031;
+pub mod service;
+pub mod time;
+
+mod clock;
@@ -0,0 +1,187 @@
+//! Arm PL031 Real-Time Clock
+//!
+//! [RealTimeClock] implementation for the Arm PrimeCell PL031, a 32-bit seconds counter with a match register that
+//! serves as the wakeup alarm. The counter holds the seconds since 1970-01-01 00:00:00 of the local time, which
+//! limits the clock to the years 1970 to 2106.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::{error::EfiError, runtime_services::VirtualAddressMap};
+use r_efi::efi;
+
+use crate::{
+    io::Mmio,
+    service::{RealTimeClock, WakeupTime},
+    time::{from_unix_seconds, to_unix_seconds},
+};
+
+const DATA: usize = 0x00;
+const MATCH: usize = 0x04;
+const LOAD: usize = 0x08;
+const CONTROL: usize = 0x0C;
+const INTERRUPT_MASK: usize = 0x10;
+const RAW_INTERRUPT_STATUS: usize = 0x14;
+const INTERRUPT_CLEAR: usize = 0x1C;
+
+const CONTROL_START: u32 = 0x1;
+const INTERRUPT: u32 = 0x1;
+
+/// The PL031 real-time clock.
+///
+/// The register block must be described as runtime MMIO in the memory map for the clock to be usable by the OS.
+///
+/// ```rust,ignore
+/// // SAFETY: The PL031 registers are mapped at 0x0901_0000 on this platform.
+/// commands.add_component(RealTimeClockProvider::new(Pl031Rtc::new(unsafe { MmioRegion::new(0x0901_0000) })));
+/// ```
+pub struct Pl031Rtc<M: Mmio + 'static> {
+    io: M,
+}
+
+impl<M: Mmio + 'static> Pl031Rtc<M> {
+    /// Creates the clock.
+    pub const fn new(io: M) -> Self {
+        Self { io }
+    }
+
+    fn seconds(time: &efi::Time) -> Option<u32> {
+        u32::try_from(to_unix_seconds(time)).ok()
+    }
+}
+
+impl<M: Mmio + 'static> RealTimeClock for Pl031Rtc<M> {
+    fn initialize(&self) -> Result<(), EfiError> {
+        // The counter does not run until started, and cannot be stopped afterwards.
+        if self.io.read_u32(CONTROL) & CONTROL_START == 0 {
+            self.io.write_u32(CONTROL, CONTROL_START);
+        }
+        Ok(())
+    }
+
+    fn capabilities(&self) -> efi::TimeCapabilities {
+        // The accuracy depends on the clock source of the platform, and is not known.
+        efi::TimeCapabilities { resolution: 1, accuracy: 0, sets_to_zero: efi::Boolean::FALSE }
+    }
+
+    fn get_time(&self) -> Result<efi::Time, EfiError> {
+        Ok(from_unix_seconds(self.io.read_u32(DATA) as i64))
+    }
+
+    fn set_time(&self, time: &efi::Time) -> Result<(), EfiError> {
+        let seconds = Self::seconds(time).ok_or(EfiError::InvalidParameter)?;
+        self.io.write_u32(LOAD, seconds);
+        Ok(())
+    }
+
+    fn get_wakeup_time(&self) -> Result<WakeupTime, EfiError> {
+        Ok(WakeupTime {
+            enabled: self.io.read_u32(INTERRUPT_MASK) & INTERRUPT != 0,
+            pending: self.io.read_u32(RAW_INTERRUPT_STATUS) & INTERRUPT != 0,
+            time: from_unix_seconds(self.io.read_u32(MATCH) as i64),
+        })
+    }
+
+    fn set_wakeup_time(&self, time: Option<&efi::Time>) -> Result<(), EfiError> {
+        match time {
+            Some(time) => {
+                let seconds = Self::seconds(time).ok_or(EfiError::Unsupported)?;
+                self.io.write_u32(INTERRUPT_MASK, 0);
+                self.io.write_u32(MATCH, seconds);
+                self.io.write_u32(INTERRUPT_CLEAR, INTERRUPT);
+                self.io.write_u32(INTERRUPT_MASK, INTERRUPT);
+            }
+            None => {
+                self.io.write_u32(INTERRUPT_MASK, 0);
+                self.io.write_u32(INTERRUPT_CLEAR, INTERRUPT);
+            }
+        }
+        Ok(())
+    }
+
+    fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        self.io.convert_pointers(map);
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::time::{fields, new_time};
+    use spin::Mutex;
+
+    /// A PL031 whose counter only changes when loaded.
+    struct SimulatedPl031 {
+        registers: Mutex<[u32; 8]>,
+    }
+
+    impl SimulatedPl031 {
+        fn new() -> &'static Self {
+            Box::leak(Box::new(Self { registers: Mutex::new([0; 8]) }))
+        }
+
+        fn register(&self, offset: usize) -> u32 {
+            self.registers.lock()[offset / 4]
+        }
+    }
+
+    impl Mmio for &'static SimulatedPl031 {
+        fn read_u32(&self, offset: usize) -> u32 {
+            self.register(offset)
+        }
+
+        fn write_u32(&self, offset: usize, value: u32) {
+            let mut registers = self.registers.lock();
+            match offset {
+                LOAD => registers[DATA / 4] = value,
+                CONTROL => registers[CONTROL / 4] |= value & CONTROL_START,
+                INTERRUPT_CLEAR => registers[RAW_INTERRUPT_STATUS / 4] &= !value,
+                MATCH | INTERRUPT_MASK => registers[offset / 4] = value,
+                _ => panic!("Write to read-only register {offset:#x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn counter_holds_seconds_since_1970() {
+        let pl031 = SimulatedPl031::new();
+        let rtc = Pl031Rtc::new(pl031);
+
+        assert_eq!(rtc.initialize(), Ok(()));
+        assert_eq!(pl031.register(CONTROL), CONTROL_START);
+
+        rtc.set_time(&new_time(2000, 2, 29, 12, 30, 15)).unwrap();
+        assert_eq!(pl031.register(DATA), 951_827_415);
+        assert_eq!(fields(&rtc.get_time().unwrap()), fields(&new_time(2000, 2, 29, 12, 30, 15)));
+
+        assert_eq!(rtc.set_time(&new_time(1969, 12, 31, 23, 59, 59)), Err(EfiError::InvalidParameter));
+        assert_eq!(rtc.set_time(&new_time(2106, 2, 7, 6, 28, 16)), Err(EfiError::InvalidParameter));
+    }
+
+    #[test]
+    fn match_register_is_the_wakeup_alarm() {
+        let pl031 = SimulatedPl031::new();
+        let rtc = Pl031Rtc::new(pl031);
+        pl031.registers.lock()[RAW_INTERRUPT_STATUS / 4] = INTERRUPT;
+
+        rtc.set_wakeup_time(Some(&new_time(2030, 1, 1, 7, 0, 0))).unwrap();
+        let wakeup = rtc.get_wakeup_time().unwrap();
+        assert!(wakeup.enabled);
+        assert!(!wakeup.pending);
+        assert_eq!(fields(&wakeup.time), fields(&new_time(2030, 1, 1, 7, 0, 0)));
+
+        pl031.registers.lock()[RAW_INTERRUPT_STATUS / 4] = INTERRUPT;
+        assert!(rtc.get_wakeup_time().unwrap().pending);
+
+        rtc.set_wakeup_time(None).unwrap();
+        let wakeup = rtc.get_wakeup_time().unwrap();
+        assert!(!wakeup.enabled);
+        assert!(!wakeup.pending);
+
+        assert_eq!(rtc.set_wakeup_time(Some(&new_time(2200, 1, 1, 0, 0, 0))), Err(EfiError::Unsupported));
+    }
+}
@@ -0,0 +1,93 @@
+//! Real-Time Clock Definitions
+//!
+//! Defines the [RealTimeClock] trait that gives the [RealTimeClockProvider] component access to the platform's
+//! clock hardware. The [cmos](crate::cmos) and [pl031](crate::pl031) modules provide implementations for common
+//! devices.
+//!
+//! [RealTimeClockProvider]: crate::component::RealTimeClockProvider
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::{error::EfiError, runtime_services::VirtualAddressMap};
+use r_efi::efi;
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// The state of the wakeup alarm, as returned by `GetWakeupTime()`.
+#[derive(Debug, Clone, Copy)]
+pub struct WakeupTime {
+    /// Whether the alarm is enabled.
+    pub enabled: bool,
+    /// Whether the alarm has fired and is waiting to be serviced.
+    pub pending: bool,
+    /// The time the alarm is set to.
+    pub time: efi::Time,
+}
+
+/// Access to the platform's real-time clock.
+///
+/// Implemented by the platform's clock, which is handed to the [RealTimeClockProvider] component. The component
+/// validates every time it passes in or out and serializes the calls. The clock only keeps the date and time fields: the time zone and
+/// daylight fields are maintained by the component, and are ignored on input and overwritten on output.
+///
+/// The implementation must remain callable after `ExitBootServices()`, so it must not use boot services or memory
+/// that is reclaimed by the OS. The component moves the clock itself to runtime services memory.
+///
+/// [RealTimeClockProvider]: crate::component::RealTimeClockProvider
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+#[allow(clippy::needless_lifetimes)] //https://github.com/rust-lang/rust-clippy/issues/6622
+pub trait RealTimeClock: Send + Sync {
+    /// Prepares the hardware, for example by starting a stopped clock. Called once by the component.
+    fn initialize(&self) -> Result<(), EfiError> {
+        Ok(())
+    }
+
+    /// Returns the resolution, accuracy and reset behavior of the clock.
+    fn capabilities(&self) -> efi::TimeCapabilities;
+
+    /// Reads the current date and time.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::DeviceError] if the time could not be read.
+    fn get_time(&self) -> Result<efi::Time, EfiError>;
+
+    /// Sets the current date and time. `time` has been validated.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::InvalidParameter] if the clock cannot represent `time`.
+    /// - [EfiError::DeviceError] if the time could not be set.
+    fn set_time<'a>(&self, time: &'a efi::Time) -> Result<(), EfiError>;
+
+    /// Reads the wakeup alarm.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::Unsupported] if the clock has no wakeup alarm, which is the default.
+    /// - [EfiError::DeviceError] if the alarm could not be read.
+    fn get_wakeup_time(&self) -> Result<WakeupTime, EfiError> {
+        Err(EfiError::Unsupported)
+    }
+
+    /// Arms the wakeup alarm for `time`, or disarms it if `time` is `None`. `time` has been validated.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::Unsupported] if the clock has no wakeup alarm, which is the default, or cannot wake the system at
+    ///   `time`.
+    /// - [EfiError::DeviceError] if the alarm could not be set.
+    fn set_wakeup_time<'a>(&self, _time: Option<&'a efi::Time>) -> Result<(), EfiError> {
+        Err(EfiError::Unsupported)
+    }
+
+    /// Converts the addresses the clock uses to their virtual addresses when the OS calls `SetVirtualAddressMap()`.
+    ///
+    /// Clocks that hold no address, such as clocks behind I/O ports, do not need to implement this.
+    fn convert_pointers<'a>(&self, _map: &'a dyn VirtualAddressMap) {}
+}
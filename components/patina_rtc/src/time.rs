@@ -0,0 +1,191 @@
+//! Time Validation and Conversion
+//!
+//! Checks [efi::Time] values against the ranges defined for `SetTime()` in the UEFI specification, and converts the
+//! date and time fields to and from a count of seconds for counter based clocks.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::error::EfiError;
+use r_efi::efi;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Days between 0000-03-01 and 1970-01-01 in the proleptic Gregorian calendar.
+const UNIX_EPOCH_DAYS: i64 = 719_468;
+
+/// Days in a 400 year Gregorian cycle.
+const DAYS_PER_ERA: i64 = 146_097;
+
+/// The time zone range, in minutes from UTC, accepted by `SetTime()`.
+pub const TIMEZONE_RANGE: core::ops::RangeInclusive<i16> = -1440..=1440;
+
+/// The year range accepted by `SetTime()`.
+pub const YEAR_RANGE: core::ops::RangeInclusive<u16> = 1900..=9999;
+
+/// Returns true if `year` is a leap year in the Gregorian calendar.
+pub fn is_leap_year(year: u16) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+/// Returns the number of days in `month` of `year`, or 0 if `month` is not in `1..=12`.
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Checks every field of `time`, including the time zone and daylight flags.
+///
+/// # Errors
+///
+/// - [EfiError::InvalidParameter] if a field is out of range, the day does not exist in the month, or the daylight
+///   field has bits other than `EFI_TIME_ADJUST_DAYLIGHT` and `EFI_TIME_IN_DAYLIGHT` set.
+pub fn validate(time: &efi::Time) -> Result<(), EfiError> {
+    let valid = YEAR_RANGE.contains(&time.year)
+        && (1..=days_in_month(time.year, time.month)).contains(&time.day)
+        && time.hour < 24
+        && time.minute < 60
+        && time.second < 60
+        && time.nanosecond < 1_000_000_000
+        && (time.timezone == efi::UNSPECIFIED_TIMEZONE || TIMEZONE_RANGE.contains(&time.timezone))
+        && time.daylight & !(efi::TIME_ADJUST_DAYLIGHT | efi::TIME_IN_DAYLIGHT) == 0;
+    if valid { Ok(()) } else { Err(EfiError::InvalidParameter) }
+}
+
+/// Returns the seconds between 1970-01-01 00:00:00 and the date and time fields of `time`.
+///
+/// The nanosecond, time zone and daylight fields are ignored. The date must be valid.
+pub(crate) fn to_unix_seconds(time: &efi::Time) -> i64 {
+    let (year, month) = match time.month {
+        1 | 2 => (time.year as i64 - 1, time.month as i64 + 9),
+        _ => (time.year as i64, time.month as i64 - 3),
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * month + 2) / 5 + time.day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * DAYS_PER_ERA + day_of_era - UNIX_EPOCH_DAYS;
+
+    days * SECONDS_PER_DAY + time.hour as i64 * 3600 + time.minute as i64 * 60 + time.second as i64
+}
+
+/// Returns the date and time `seconds` after 1970-01-01 00:00:00, with an unspecified time zone.
+///
+/// `seconds` must describe a date within [YEAR_RANGE].
+pub(crate) fn from_unix_seconds(seconds: i64) -> efi::Time {
+    let days = seconds.div_euclid(SECONDS_PER_DAY) + UNIX_EPOCH_DAYS;
+    let second_of_day = seconds.rem_euclid(SECONDS_PER_DAY);
+
+    let era = days.div_euclid(DAYS_PER_ERA);
+    let day_of_era = days.rem_euclid(DAYS_PER_ERA);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + (month <= 2) as i64;
+
+    efi::Time {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (second_of_day / 3600) as u8,
+        minute: (second_of_day / 60 % 60) as u8,
+        second: (second_of_day % 60) as u8,
+        timezone: efi::UNSPECIFIED_TIMEZONE,
+        ..Default::default()
+    }
+}
+
+/// Builds a time with an unspecified time zone from its date and time fields.
+#[cfg(test)]
+pub(crate) fn new_time(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> efi::Time {
+    efi::Time { year, month, day, hour, minute, second, timezone: efi::UNSPECIFIED_TIMEZONE, ..Default::default() }
+}
+
+/// The fields of a time that carry a value; [efi::Time] does not implement `PartialEq` because of its padding.
+#[cfg(test)]
+pub(crate) fn fields(time: &efi::Time) -> (u16, u8, u8, u8, u8, u8, u32, i16, u8) {
+    (
+        time.year,
+        time.month,
+        time.day,
+        time.hour,
+        time.minute,
+        time.second,
+        time.nanosecond,
+        time.timezone,
+        time.daylight,
+    )
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leap_years_follow_the_gregorian_rules() {
+        assert!(is_leap_year(2024));
+        assert!(is_leap_year(2000));
+        assert!(!is_leap_year(1900));
+        assert!(!is_leap_year(2023));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2100, 2), 28);
+        assert_eq!(days_in_month(2023, 4), 30);
+        assert_eq!(days_in_month(2023, 13), 0);
+    }
+
+    #[test]
+    fn validate_checks_every_field() {
+        let valid =
+            efi::Time { nanosecond: 999_999_999, timezone: -480, daylight: 3, ..new_time(2024, 2, 29, 23, 59, 59) };
+        assert_eq!(validate(&valid), Ok(()));
+        assert_eq!(validate(&efi::Time { timezone: efi::UNSPECIFIED_TIMEZONE, ..valid }), Ok(()));
+
+        let invalid = [
+            efi::Time { year: 1899, ..valid },
+            efi::Time { year: 10000, ..valid },
+            efi::Time { month: 0, ..valid },
+            efi::Time { month: 13, ..valid },
+            efi::Time { year: 2023, ..valid },
+            efi::Time { day: 0, ..valid },
+            efi::Time { hour: 24, ..valid },
+            efi::Time { minute: 60, ..valid },
+            efi::Time { second: 60, ..valid },
+            efi::Time { nanosecond: 1_000_000_000, ..valid },
+            efi::Time { timezone: 1441, ..valid },
+            efi::Time { timezone: -1441, ..valid },
+            efi::Time { daylight: 4, ..valid },
+        ];
+        for time in invalid {
+            assert_eq!(validate(&time), Err(EfiError::InvalidParameter), "{time:?}");
+        }
+    }
+
+    #[test]
+    fn unix_seconds_round_trip() {
+        let cases = [
+            (new_time(1970, 1, 1, 0, 0, 0), 0),
+            (new_time(2000, 2, 29, 12, 30, 15), 951_827_415),
+            (new_time(2106, 2, 7, 6, 28, 15), u32::MAX as i64),
+            (new_time(1900, 1, 1, 0, 0, 0), -2_208_988_800),
+        ];
+        for (time, seconds) in cases {
+            assert_eq!(to_unix_seconds(&time), seconds);
+            assert_eq!(fields(&from_unix_seconds(seconds)), fields(&time));
+        }
+        assert_eq!(
+            fields(&from_unix_seconds(to_unix_seconds(&new_time(9999, 12, 31, 23, 59, 59)))),
+            fields(&new_time(9999, 12, 31, 23, 59, 59))
+        );
+    }
+}
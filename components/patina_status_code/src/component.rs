@@ -0,0 +1,256 @@
+//! Status Code Router Component
+//!
+//! Installs the Status Code Runtime Protocol, whose `ReportStatusCode()` routes status codes to the listeners
+//! registered through the [StatusCodeRouter] service, and registers the built-in [listeners].
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate alloc;
+
+use core::{ffi::c_void, slice};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        component,
+        params::Commands,
+        service::{IntoService, Service, memory::MemoryManager},
+    },
+    error::{EfiError, Result},
+    pi::protocols::status_code::{self, EfiStatusCodeData, EfiStatusCodeType, EfiStatusCodeValue},
+};
+use r_efi::efi;
+
+use crate::{
+    listeners,
+    router::{DeferredListener, Router},
+    service::{StatusCodeData, StatusCodeListener, StatusCodeRecord, StatusCodeRouter},
+};
+
+/// State shared with `ReportStatusCode()`, which receives no context pointer.
+struct Provider {
+    router: &'static Router,
+    boot_services: StandardBootServices,
+}
+
+static PROVIDER: spin::Once<Provider> = spin::Once::new();
+
+/// The protocol interface. Like the code behind it, it lives in the DXE core image, so it is only valid until
+/// `ExitBootServices()`.
+static PROTOCOL: status_code::Protocol = status_code::Protocol { report_status_code };
+
+/// Produces the Status Code Runtime Protocol and the [StatusCodeRouter] service.
+///
+/// By default, [listeners::record_progress_code] is registered at [Tpl::HIGH_LEVEL] and [listeners::log_status_code]
+/// at [Tpl::NOTIFY]. The protocol is part of the DXE core image, so runtime drivers cannot report status codes through
+/// it after `ExitBootServices()`.
+///
+/// ```rust,ignore
+/// commands.add_component(StatusCodeRouterProvider::default());
+/// ```
+pub struct StatusCodeRouterProvider {
+    log_listener: bool,
+    progress_ring: bool,
+}
+
+impl Default for StatusCodeRouterProvider {
+    fn default() -> Self {
+        Self { log_listener: true, progress_ring: true }
+    }
+}
+
+#[component]
+impl StatusCodeRouterProvider {
+    /// Sets whether status codes are forwarded to the `log` crate.
+    pub fn with_log_listener(mut self, enabled: bool) -> Self {
+        self.log_listener = enabled;
+        self
+    }
+
+    /// Sets whether progress codes are recorded into the progress code ring.
+    pub fn with_progress_ring(mut self, enabled: bool) -> Self {
+        self.progress_ring = enabled;
+        self
+    }
+
+    #[coverage(off)] // Component integration - the routing logic it wires up is tested directly.
+    fn entry_point(
+        self,
+        memory_manager: Service<dyn MemoryManager>,
+        boot_services: StandardBootServices,
+        mut commands: Commands,
+    ) -> Result<()> {
+        if PROVIDER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        // Status codes reported at runtime are routed after boot services memory is reclaimed.
+        let router: &'static Router = memory_manager.leak_in_runtime_memory(Router::new(signal_event))?;
+        PROVIDER.call_once(|| Provider { router, boot_services: boot_services.clone() });
+
+        boot_services
+            .create_event(EventType::SIGNAL_EXIT_BOOT_SERVICES, Tpl::NOTIFY, Some(exit_boot_services), router)
+            .map_err(EfiError::from)?;
+
+        let service = StatusCodeRouterService { router, boot_services: boot_services.clone() };
+        if self.progress_ring {
+            service.register_listener(listeners::record_progress_code, Tpl::HIGH_LEVEL)?;
+        }
+        if self.log_listener {
+            service.register_listener(listeners::log_status_code, Tpl::NOTIFY)?;
+        }
+
+        // SAFETY: PROTOCOL is a static Status Code Runtime Protocol interface, so it is valid for the life of the
+        // firmware and matches the GUID it is installed with.
+        unsafe {
+            boot_services.install_protocol_interface_unchecked(
+                None,
+                &status_code::PROTOCOL_GUID,
+                &PROTOCOL as *const status_code::Protocol as *mut c_void,
+            )
+        }
+        .map_err(EfiError::from)?;
+
+        commands.add_service(service);
+
+        log::info!(target: "status_code", "Status code router installed.");
+        Ok(())
+    }
+}
+
+/// The [StatusCodeRouter] service produced by [StatusCodeRouterProvider].
+#[derive(IntoService)]
+#[service(dyn StatusCodeRouter)]
+struct StatusCodeRouterService {
+    router: &'static Router,
+    boot_services: StandardBootServices,
+}
+
+impl StatusCodeRouter for StatusCodeRouterService {
+    #[coverage(off)] // Requires boot services.
+    fn register_listener(&self, listener: StatusCodeListener, tpl: Tpl) -> core::result::Result<(), EfiError> {
+        self.router.register(listener, tpl, |deferred| {
+            self.boot_services
+                .create_event(EventType::NOTIFY_SIGNAL, deferred.tpl(), Some(deliver_status_codes), deferred)
+                .map_err(|_| EfiError::OutOfResources)
+        })
+    }
+
+    #[coverage(off)] // Requires boot services.
+    fn unregister_listener(&self, listener: StatusCodeListener) -> core::result::Result<(), EfiError> {
+        if let Some(event) = self.router.unregister(listener)? {
+            let _ = self.boot_services.close_event(event);
+        }
+        Ok(())
+    }
+}
+
+extern "efiapi" fn exit_boot_services(_event: efi::Event, router: &'static Router) {
+    router.exit_boot_services();
+}
+
+extern "efiapi" fn deliver_status_codes(_event: efi::Event, deferred: &'static DeferredListener) {
+    deferred.drain();
+}
+
+/// Signals the event of a deferred listener that has status codes queued.
+#[coverage(off)] // Requires boot services.
+fn signal_event(event: efi::Event) {
+    if let Some(provider) = PROVIDER.get() {
+        let _ = provider.boot_services.signal_event(event);
+    }
+}
+
+extern "efiapi" fn report_status_code(
+    code_type: EfiStatusCodeType,
+    value: EfiStatusCodeValue,
+    instance: u32,
+    caller_id: *const efi::Guid,
+    data: *const EfiStatusCodeData,
+) -> efi::Status {
+    let Some(provider) = PROVIDER.get() else {
+        return efi::Status::NOT_READY;
+    };
+    dispatch(provider.router, code_type, value, instance, caller_id, data)
+}
+
+/// Converts the raw `ReportStatusCode()` arguments into a [StatusCodeRecord] and routes it.
+fn dispatch(
+    router: &Router,
+    code_type: EfiStatusCodeType,
+    value: EfiStatusCodeValue,
+    instance: u32,
+    caller_id: *const efi::Guid,
+    data: *const EfiStatusCodeData,
+) -> efi::Status {
+    // SAFETY: A non-null caller_id points to the GUID of the caller.
+    let caller_id = unsafe { caller_id.as_ref() }.copied();
+    let data = if data.is_null() {
+        None
+    } else {
+        // SAFETY: A non-null data points to an EFI_STATUS_CODE_DATA header followed by `size` bytes of data, with
+        // no alignment guarantee.
+        let header = unsafe { data.read_unaligned() };
+        // SAFETY: As above, the data starts header_size bytes after the start of the header.
+        let bytes = unsafe {
+            slice::from_raw_parts((data as *const u8).add(header.header_size as usize), header.size as usize)
+        };
+        Some(StatusCodeData { data_type: header.r#type, data: bytes })
+    };
+
+    match router.report(&StatusCodeRecord { code_type, value, instance, caller_id, data }) {
+        Ok(()) => efi::Status::SUCCESS,
+        Err(error) => error.into(),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::{mem, ptr};
+    use patina::pi::status_code::{EFI_ERROR_CODE, EFI_SOFTWARE_DXE_CORE, EFI_SW_EC_NON_SPECIFIC};
+    use spin::Mutex;
+
+    const CALLER: efi::Guid =
+        efi::Guid::from_fields(0x2f4a6e0c, 0x1b3d, 0x4c5e, 0x8f, 0x90, &[0xa1, 0xb2, 0xc3, 0xd4, 0xe5, 0xf6]);
+
+    /// The caller ID, and data type and data, of each status code received by the listener.
+    type Received = (Option<efi::Guid>, Option<(efi::Guid, Vec<u8>)>);
+
+    static RECORDS: Mutex<Vec<Received>> = Mutex::new(Vec::new());
+
+    fn listener(record: &StatusCodeRecord<'_>) {
+        assert_eq!(record.code_type, EFI_ERROR_CODE);
+        assert_eq!(record.value, EFI_SOFTWARE_DXE_CORE | EFI_SW_EC_NON_SPECIFIC);
+        assert_eq!(record.instance, 3);
+        RECORDS.lock().push((record.caller_id, record.data.map(|data| (data.data_type, data.data.to_vec()))));
+    }
+
+    fn noop_signal(_event: efi::Event) {}
+
+    #[test]
+    fn dispatch_converts_the_raw_arguments() {
+        let router = Router::new(noop_signal);
+        router.register(listener, Tpl::HIGH_LEVEL, |_| Err(EfiError::Unsupported)).unwrap();
+        let value = EFI_SOFTWARE_DXE_CORE | EFI_SW_EC_NON_SPECIFIC;
+
+        // An EFI_STATUS_CODE_DATA header followed by the data, at an unaligned address.
+        let header =
+            EfiStatusCodeData { header_size: mem::size_of::<EfiStatusCodeData>() as u16, size: 3, r#type: CALLER };
+        let mut buffer = alloc::vec![0u8; 1 + mem::size_of::<EfiStatusCodeData>() + 3];
+        // SAFETY: The buffer holds the header at offset 1, followed by the data.
+        unsafe { (buffer.as_mut_ptr().add(1) as *mut EfiStatusCodeData).write_unaligned(header) };
+        buffer[1 + mem::size_of::<EfiStatusCodeData>()..].copy_from_slice(&[7, 8, 9]);
+        let data = buffer[1..].as_ptr() as *const EfiStatusCodeData;
+
+        assert_eq!(dispatch(&router, EFI_ERROR_CODE, value, 3, &CALLER, data), efi::Status::SUCCESS);
+        assert_eq!(dispatch(&router, EFI_ERROR_CODE, value, 3, ptr::null(), ptr::null()), efi::Status::SUCCESS);
+        assert_eq!(*RECORDS.lock(), [(Some(CALLER), Some((CALLER, alloc::vec![7, 8, 9]))), (None, None)]);
+    }
+}
@@ -0,0 +1,587 @@
+//! Status Code Decoding
+//!
+//! Maps status code values to the names of their class, subclass and operation, as defined in
+//! [patina::pi::status_code]. Names are the constant names without their prefix, for example
+//! `SOFTWARE.DXE_CORE.HANDOFF_TO_NEXT` for `EFI_SOFTWARE_DXE_CORE | EFI_SW_DXE_CORE_PC_HANDOFF_TO_NEXT`.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::fmt;
+
+use patina::pi::{
+    protocols::status_code::{EfiStatusCodeType, EfiStatusCodeValue},
+    status_code::*,
+};
+
+/// Returns the name of the class of `value`.
+pub fn class_name(value: EfiStatusCodeValue) -> Option<&'static str> {
+    let class = value & EFI_STATUS_CODE_CLASS_MASK;
+    CLASSES.iter().find(|(c, _)| *c == class).map(|(_, name)| *name)
+}
+
+/// Returns the name of the subclass of `value`.
+pub fn subclass_name(value: EfiStatusCodeValue) -> Option<&'static str> {
+    let subclass = value & (EFI_STATUS_CODE_CLASS_MASK | EFI_STATUS_CODE_SUBCLASS_MASK);
+    SUBCLASSES.iter().find(|(s, _)| *s == subclass).map(|(_, name)| *name)
+}
+
+/// Returns the name of the operation of `value`, for progress and error codes.
+///
+/// Subclass specific operations take precedence over the operations shared by all subclasses of a class, as the
+/// exception subclasses of the software class reuse the shared operation range.
+pub fn operation_name(code_type: EfiStatusCodeType, value: EfiStatusCodeValue) -> Option<&'static str> {
+    let operations = match code_type & EFI_STATUS_CODE_TYPE_MASK {
+        EFI_PROGRESS_CODE => PROGRESS_OPERATIONS,
+        EFI_ERROR_CODE => ERROR_OPERATIONS,
+        _ => return None,
+    };
+    let operation = value & EFI_STATUS_CODE_OPERATION_MASK;
+    let subclass = value & (EFI_STATUS_CODE_CLASS_MASK | EFI_STATUS_CODE_SUBCLASS_MASK);
+    let class = value & EFI_STATUS_CODE_CLASS_MASK;
+
+    operations
+        .iter()
+        .find(|(scope, op, _)| *scope == subclass && *op == operation)
+        .or_else(|| {
+            operations.iter().find(|(scope, op, _)| *scope == class && *op == operation && *op < EFI_SUBCLASS_SPECIFIC)
+        })
+        .map(|(_, _, name)| *name)
+}
+
+/// Returns a displayable `CLASS.SUBCLASS.OPERATION` name for a status code.
+///
+/// Parts without a name are displayed as hexadecimal numbers.
+pub fn describe(code_type: EfiStatusCodeType, value: EfiStatusCodeValue) -> StatusCodeName {
+    StatusCodeName { code_type, value }
+}
+
+/// The name of a status code, see [describe].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusCodeName {
+    code_type: EfiStatusCodeType,
+    value: EfiStatusCodeValue,
+}
+
+impl fmt::Display for StatusCodeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match class_name(self.value) {
+            Some(name) => write!(f, "{name}.")?,
+            None => write!(f, "{:#04x}.", (self.value & EFI_STATUS_CODE_CLASS_MASK) >> 24)?,
+        }
+        match subclass_name(self.value) {
+            Some(name) => write!(f, "{name}.")?,
+            None => write!(f, "{:#04x}.", (self.value & EFI_STATUS_CODE_SUBCLASS_MASK) >> 16)?,
+        }
+        match operation_name(self.code_type, self.value) {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{:#06x}", self.value & EFI_STATUS_CODE_OPERATION_MASK),
+        }
+    }
+}
+
+/// The class names, keyed by class.
+const CLASSES: &[(EfiStatusCodeValue, &str)] = &[
+    (EFI_COMPUTING_UNIT, "COMPUTING_UNIT"),
+    (EFI_PERIPHERAL, "PERIPHERAL"),
+    (EFI_IO_BUS, "IO_BUS"),
+    (EFI_SOFTWARE, "SOFTWARE"),
+];
+
+/// The subclass names, keyed by class and subclass.
+const SUBCLASSES: &[(EfiStatusCodeValue, &str)] = &[
+    (EFI_COMPUTING_UNIT_UNSPECIFIED, "UNSPECIFIED"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, "HOST_PROCESSOR"),
+    (EFI_COMPUTING_UNIT_FIRMWARE_PROCESSOR, "FIRMWARE_PROCESSOR"),
+    (EFI_COMPUTING_UNIT_IO_PROCESSOR, "IO_PROCESSOR"),
+    (EFI_COMPUTING_UNIT_CACHE, "CACHE"),
+    (EFI_COMPUTING_UNIT_MEMORY, "MEMORY"),
+    (EFI_COMPUTING_UNIT_CHIPSET, "CHIPSET"),
+    (EFI_PERIPHERAL_UNSPECIFIED, "UNSPECIFIED"),
+    (EFI_PERIPHERAL_KEYBOARD, "KEYBOARD"),
+    (EFI_PERIPHERAL_MOUSE, "MOUSE"),
+    (EFI_PERIPHERAL_LOCAL_CONSOLE, "LOCAL_CONSOLE"),
+    (EFI_PERIPHERAL_REMOTE_CONSOLE, "REMOTE_CONSOLE"),
+    (EFI_PERIPHERAL_SERIAL_PORT, "SERIAL_PORT"),
+    (EFI_PERIPHERAL_PARALLEL_PORT, "PARALLEL_PORT"),
+    (EFI_PERIPHERAL_FIXED_MEDIA, "FIXED_MEDIA"),
+    (EFI_PERIPHERAL_REMOVABLE_MEDIA, "REMOVABLE_MEDIA"),
+    (EFI_PERIPHERAL_AUDIO_INPUT, "AUDIO_INPUT"),
+    (EFI_PERIPHERAL_AUDIO_OUTPUT, "AUDIO_OUTPUT"),
+    (EFI_PERIPHERAL_LCD_DEVICE, "LCD_DEVICE"),
+    (EFI_PERIPHERAL_NETWORK, "NETWORK"),
+    (EFI_PERIPHERAL_DOCKING, "DOCKING"),
+    (EFI_PERIPHERAL_TPM, "TPM"),
+    (EFI_IO_BUS_UNSPECIFIED, "UNSPECIFIED"),
+    (EFI_IO_BUS_PCI, "PCI"),
+    (EFI_IO_BUS_USB, "USB"),
+    (EFI_IO_BUS_IBA, "IBA"),
+    (EFI_IO_BUS_AGP, "AGP"),
+    (EFI_IO_BUS_PC_CARD, "PC_CARD"),
+    (EFI_IO_BUS_LPC, "LPC"),
+    (EFI_IO_BUS_SCSI, "SCSI"),
+    (EFI_IO_BUS_ATA_ATAPI, "ATA_ATAPI"),
+    (EFI_IO_BUS_FC, "FC"),
+    (EFI_IO_BUS_IP_NETWORK, "IP_NETWORK"),
+    (EFI_IO_BUS_SMBUS, "SMBUS"),
+    (EFI_IO_BUS_I2C, "I2C"),
+    (EFI_SOFTWARE_UNSPECIFIED, "UNSPECIFIED"),
+    (EFI_SOFTWARE_SEC, "SEC"),
+    (EFI_SOFTWARE_PEI_CORE, "PEI_CORE"),
+    (EFI_SOFTWARE_PEI_MODULE, "PEI_MODULE"),
+    (EFI_SOFTWARE_DXE_CORE, "DXE_CORE"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, "DXE_BS_DRIVER"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, "DXE_RT_DRIVER"),
+    (EFI_SOFTWARE_SMM_DRIVER, "SMM_DRIVER"),
+    (EFI_SOFTWARE_EFI_APPLICATION, "EFI_APPLICATION"),
+    (EFI_SOFTWARE_EFI_OS_LOADER, "EFI_OS_LOADER"),
+    (EFI_SOFTWARE_RT, "RT"),
+    (EFI_SOFTWARE_AL, "AL"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, "EBC_EXCEPTION"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, "IA32_EXCEPTION"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, "IPF_EXCEPTION"),
+    (EFI_SOFTWARE_PEI_SERVICE, "PEI_SERVICE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, "EFI_BOOT_SERVICE"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, "EFI_RUNTIME_SERVICE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, "EFI_DXE_SERVICE"),
+    (EFI_SOFTWARE_X64_EXCEPTION, "X64_EXCEPTION"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, "ARM_EXCEPTION"),
+];
+
+/// The progress code operation names, keyed by the class, or the class and subclass for subclass specific operations.
+const PROGRESS_OPERATIONS: &[(EfiStatusCodeValue, EfiStatusCodeValue, &str)] = &[
+    (EFI_COMPUTING_UNIT, EFI_CU_PC_INIT_BEGIN, "INIT_BEGIN"),
+    (EFI_COMPUTING_UNIT, EFI_CU_PC_INIT_END, "INIT_END"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_POWER_ON_INIT, "POWER_ON_INIT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_CACHE_INIT, "CACHE_INIT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_RAM_INIT, "RAM_INIT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_MEMORY_CONTROLLER_INIT, "MEMORY_CONTROLLER_INIT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_IO_INIT, "IO_INIT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_BSP_SELECT, "BSP_SELECT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_BSP_RESELECT, "BSP_RESELECT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_AP_INIT, "AP_INIT"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_PC_SMM_INIT, "SMM_INIT"),
+    (EFI_COMPUTING_UNIT_CACHE, EFI_CU_CACHE_PC_PRESENCE_DETECT, "PRESENCE_DETECT"),
+    (EFI_COMPUTING_UNIT_CACHE, EFI_CU_CACHE_PC_CONFIGURATION, "CONFIGURATION"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_SPD_READ, "SPD_READ"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_PRESENCE_DETECT, "PRESENCE_DETECT"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_TIMING, "TIMING"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_CONFIGURING, "CONFIGURING"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_OPTIMIZING, "OPTIMIZING"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_INIT, "INIT"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_PC_TEST, "TEST"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_PEI_CAR_SB_INIT, "PEI_CAR_SB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_PEI_CAR_NB_INIT, "PEI_CAR_NB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_PEI_MEM_SB_INIT, "PEI_MEM_SB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_PEI_MEM_NB_INIT, "PEI_MEM_NB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_HB_INIT, "DXE_HB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_NB_INIT, "DXE_NB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_NB_SMM_INIT, "DXE_NB_SMM_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_SB_RT_INIT, "DXE_SB_RT_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_SB_INIT, "DXE_SB_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_SB_SMM_INIT, "DXE_SB_SMM_INIT"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_PC_DXE_SB_DEVICES_INIT, "DXE_SB_DEVICES_INIT"),
+    (EFI_PERIPHERAL, EFI_P_PC_INIT, "INIT"),
+    (EFI_PERIPHERAL, EFI_P_PC_RESET, "RESET"),
+    (EFI_PERIPHERAL, EFI_P_PC_DISABLE, "DISABLE"),
+    (EFI_PERIPHERAL, EFI_P_PC_PRESENCE_DETECT, "PRESENCE_DETECT"),
+    (EFI_PERIPHERAL, EFI_P_PC_ENABLE, "ENABLE"),
+    (EFI_PERIPHERAL, EFI_P_PC_RECONFIG, "RECONFIG"),
+    (EFI_PERIPHERAL, EFI_P_PC_DETECTED, "DETECTED"),
+    (EFI_PERIPHERAL, EFI_P_PC_REMOVED, "REMOVED"),
+    (EFI_PERIPHERAL_KEYBOARD, EFI_P_KEYBOARD_PC_CLEAR_BUFFER, "CLEAR_BUFFER"),
+    (EFI_PERIPHERAL_KEYBOARD, EFI_P_KEYBOARD_PC_SELF_TEST, "SELF_TEST"),
+    (EFI_PERIPHERAL_MOUSE, EFI_P_MOUSE_PC_SELF_TEST, "SELF_TEST"),
+    (EFI_PERIPHERAL_SERIAL_PORT, EFI_P_SERIAL_PORT_PC_CLEAR_BUFFER, "CLEAR_BUFFER"),
+    (EFI_IO_BUS, EFI_IOB_PC_INIT, "INIT"),
+    (EFI_IO_BUS, EFI_IOB_PC_RESET, "RESET"),
+    (EFI_IO_BUS, EFI_IOB_PC_DISABLE, "DISABLE"),
+    (EFI_IO_BUS, EFI_IOB_PC_DETECT, "DETECT"),
+    (EFI_IO_BUS, EFI_IOB_PC_ENABLE, "ENABLE"),
+    (EFI_IO_BUS, EFI_IOB_PC_RECONFIG, "RECONFIG"),
+    (EFI_IO_BUS, EFI_IOB_PC_HOTPLUG, "HOTPLUG"),
+    (EFI_IO_BUS_PCI, EFI_IOB_PCI_BUS_ENUM, "BUS_ENUM"),
+    (EFI_IO_BUS_PCI, EFI_IOB_PCI_RES_ALLOC, "RES_ALLOC"),
+    (EFI_IO_BUS_PCI, EFI_IOB_PCI_HPC_INIT, "HPC_INIT"),
+    (EFI_IO_BUS_ATA_ATAPI, EFI_IOB_ATA_BUS_SMART_ENABLE, "SMART_ENABLE"),
+    (EFI_IO_BUS_ATA_ATAPI, EFI_IOB_ATA_BUS_SMART_DISABLE, "SMART_DISABLE"),
+    (EFI_IO_BUS_ATA_ATAPI, EFI_IOB_ATA_BUS_SMART_OVERTHRESHOLD, "SMART_OVERTHRESHOLD"),
+    (EFI_IO_BUS_ATA_ATAPI, EFI_IOB_ATA_BUS_SMART_UNDERTHRESHOLD, "SMART_UNDERTHRESHOLD"),
+    (EFI_SOFTWARE, EFI_SW_PC_INIT, "INIT"),
+    (EFI_SOFTWARE, EFI_SW_PC_LOAD, "LOAD"),
+    (EFI_SOFTWARE, EFI_SW_PC_INIT_BEGIN, "INIT_BEGIN"),
+    (EFI_SOFTWARE, EFI_SW_PC_INIT_END, "INIT_END"),
+    (EFI_SOFTWARE, EFI_SW_PC_AUTHENTICATE_BEGIN, "AUTHENTICATE_BEGIN"),
+    (EFI_SOFTWARE, EFI_SW_PC_AUTHENTICATE_END, "AUTHENTICATE_END"),
+    (EFI_SOFTWARE, EFI_SW_PC_INPUT_WAIT, "INPUT_WAIT"),
+    (EFI_SOFTWARE, EFI_SW_PC_USER_SETUP, "USER_SETUP"),
+    (EFI_SOFTWARE_SEC, EFI_SW_SEC_PC_ENTRY_POINT, "ENTRY_POINT"),
+    (EFI_SOFTWARE_SEC, EFI_SW_SEC_PC_HANDOFF_TO_NEXT, "HANDOFF_TO_NEXT"),
+    (EFI_SOFTWARE_PEI_CORE, EFI_SW_PEI_CORE_PC_ENTRY_POINT, "ENTRY_POINT"),
+    (EFI_SOFTWARE_PEI_CORE, EFI_SW_PEI_CORE_PC_HANDOFF_TO_NEXT, "HANDOFF_TO_NEXT"),
+    (EFI_SOFTWARE_PEI_CORE, EFI_SW_PEI_CORE_PC_RETURN_TO_LAST, "RETURN_TO_LAST"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_RECOVERY_BEGIN, "RECOVERY_BEGIN"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_CAPSULE_LOAD, "CAPSULE_LOAD"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_CAPSULE_START, "CAPSULE_START"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_RECOVERY_USER, "RECOVERY_USER"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_RECOVERY_AUTO, "RECOVERY_AUTO"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_S3_BOOT_SCRIPT, "S3_BOOT_SCRIPT"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_OS_WAKE, "OS_WAKE"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_PC_S3_STARTED, "S3_STARTED"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_ENTRY_POINT, "ENTRY_POINT"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_HANDOFF_TO_NEXT, "HANDOFF_TO_NEXT"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_RETURN_TO_LAST, "RETURN_TO_LAST"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_START_DRIVER, "START_DRIVER"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_ARCH_READY, "ARCH_READY"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_LEGACY_OPROM_INIT, "LEGACY_OPROM_INIT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_READY_TO_BOOT_EVENT, "READY_TO_BOOT_EVENT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_LEGACY_BOOT_EVENT, "LEGACY_BOOT_EVENT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_EXIT_BOOT_SERVICES_EVENT, "EXIT_BOOT_SERVICES_EVENT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_VIRTUAL_ADDRESS_CHANGE_EVENT, "VIRTUAL_ADDRESS_CHANGE_EVENT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_VARIABLE_SERVICES_INIT, "VARIABLE_SERVICES_INIT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_VARIABLE_RECLAIM, "VARIABLE_RECLAIM"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_ATTEMPT_BOOT_ORDER_EVENT, "ATTEMPT_BOOT_ORDER_EVENT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_CONFIG_RESET, "CONFIG_RESET"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_CSM_INIT, "CSM_INIT"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_BOOT_OPTION_COMPLETE, "BOOT_OPTION_COMPLETE"),
+    (EFI_SOFTWARE_RT, EFI_SW_RT_PC_ENTRY_POINT, "ENTRY_POINT"),
+    (EFI_SOFTWARE_RT, EFI_SW_RT_PC_HANDOFF_TO_NEXT, "HANDOFF_TO_NEXT"),
+    (EFI_SOFTWARE_RT, EFI_SW_RT_PC_RETURN_TO_LAST, "RETURN_TO_LAST"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_INSTALL_PPI, "INSTALL_PPI"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_REINSTALL_PPI, "REINSTALL_PPI"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_LOCATE_PPI, "LOCATE_PPI"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_NOTIFY_PPI, "NOTIFY_PPI"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_GET_BOOT_MODE, "GET_BOOT_MODE"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_SET_BOOT_MODE, "SET_BOOT_MODE"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_GET_HOB_LIST, "GET_HOB_LIST"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_CREATE_HOB, "CREATE_HOB"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_FIND_NEXT_VOLUME, "FFS_FIND_NEXT_VOLUME"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_FIND_NEXT_FILE, "FFS_FIND_NEXT_FILE"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_FIND_SECTION_DATA, "FFS_FIND_SECTION_DATA"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_INSTALL_PEI_MEMORY, "INSTALL_PEI_MEMORY"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_ALLOCATE_PAGES, "ALLOCATE_PAGES"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_ALLOCATE_POOL, "ALLOCATE_POOL"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_COPY_MEM, "COPY_MEM"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_SET_MEM, "SET_MEM"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_RESET_SYSTEM, "RESET_SYSTEM"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_FIND_FILE_BY_NAME, "FFS_FIND_FILE_BY_NAME"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_GET_FILE_INFO, "FFS_GET_FILE_INFO"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_GET_VOLUME_INFO, "FFS_GET_VOLUME_INFO"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_PC_FFS_REGISTER_FOR_SHADOW, "FFS_REGISTER_FOR_SHADOW"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_RAISE_TPL, "RAISE_TPL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_RESTORE_TPL, "RESTORE_TPL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_ALLOCATE_PAGES, "ALLOCATE_PAGES"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_FREE_PAGES, "FREE_PAGES"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_GET_MEMORY_MAP, "GET_MEMORY_MAP"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_ALLOCATE_POOL, "ALLOCATE_POOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_FREE_POOL, "FREE_POOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CREATE_EVENT, "CREATE_EVENT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_SET_TIMER, "SET_TIMER"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_WAIT_FOR_EVENT, "WAIT_FOR_EVENT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_SIGNAL_EVENT, "SIGNAL_EVENT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CLOSE_EVENT, "CLOSE_EVENT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CHECK_EVENT, "CHECK_EVENT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_INSTALL_PROTOCOL_INTERFACE, "INSTALL_PROTOCOL_INTERFACE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_REINSTALL_PROTOCOL_INTERFACE, "REINSTALL_PROTOCOL_INTERFACE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_UNINSTALL_PROTOCOL_INTERFACE, "UNINSTALL_PROTOCOL_INTERFACE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_HANDLE_PROTOCOL, "HANDLE_PROTOCOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_PC_HANDLE_PROTOCOL, "PC_HANDLE_PROTOCOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_REGISTER_PROTOCOL_NOTIFY, "REGISTER_PROTOCOL_NOTIFY"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_LOCATE_HANDLE, "LOCATE_HANDLE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_INSTALL_CONFIGURATION_TABLE, "INSTALL_CONFIGURATION_TABLE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_LOAD_IMAGE, "LOAD_IMAGE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_START_IMAGE, "START_IMAGE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_EXIT, "EXIT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_UNLOAD_IMAGE, "UNLOAD_IMAGE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_EXIT_BOOT_SERVICES, "EXIT_BOOT_SERVICES"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_GET_NEXT_MONOTONIC_COUNT, "GET_NEXT_MONOTONIC_COUNT"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_STALL, "STALL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_SET_WATCHDOG_TIMER, "SET_WATCHDOG_TIMER"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CONNECT_CONTROLLER, "CONNECT_CONTROLLER"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_DISCONNECT_CONTROLLER, "DISCONNECT_CONTROLLER"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_OPEN_PROTOCOL, "OPEN_PROTOCOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CLOSE_PROTOCOL, "CLOSE_PROTOCOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_OPEN_PROTOCOL_INFORMATION, "OPEN_PROTOCOL_INFORMATION"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_PROTOCOLS_PER_HANDLE, "PROTOCOLS_PER_HANDLE"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_LOCATE_HANDLE_BUFFER, "LOCATE_HANDLE_BUFFER"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_LOCATE_PROTOCOL, "LOCATE_PROTOCOL"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_INSTALL_MULTIPLE_INTERFACES, "INSTALL_MULTIPLE_INTERFACES"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_UNINSTALL_MULTIPLE_INTERFACES, "UNINSTALL_MULTIPLE_INTERFACES"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CALCULATE_CRC_32, "CALCULATE_CRC_32"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_COPY_MEM, "COPY_MEM"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_SET_MEM, "SET_MEM"),
+    (EFI_SOFTWARE_EFI_BOOT_SERVICE, EFI_SW_BS_PC_CREATE_EVENT_EX, "CREATE_EVENT_EX"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_GET_TIME, "GET_TIME"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_SET_TIME, "SET_TIME"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_GET_WAKEUP_TIME, "GET_WAKEUP_TIME"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_SET_WAKEUP_TIME, "SET_WAKEUP_TIME"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_SET_VIRTUAL_ADDRESS_MAP, "SET_VIRTUAL_ADDRESS_MAP"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_CONVERT_POINTER, "CONVERT_POINTER"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_GET_VARIABLE, "GET_VARIABLE"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_GET_NEXT_VARIABLE_NAME, "GET_NEXT_VARIABLE_NAME"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_SET_VARIABLE, "SET_VARIABLE"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_GET_NEXT_HIGH_MONOTONIC_COUNT, "GET_NEXT_HIGH_MONOTONIC_COUNT"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_RESET_SYSTEM, "RESET_SYSTEM"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_UPDATE_CAPSULE, "UPDATE_CAPSULE"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_QUERY_CAPSULE_CAPABILITIES, "QUERY_CAPSULE_CAPABILITIES"),
+    (EFI_SOFTWARE_EFI_RUNTIME_SERVICE, EFI_SW_RS_PC_QUERY_VARIABLE_INFO, "QUERY_VARIABLE_INFO"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_ADD_MEMORY_SPACE, "ADD_MEMORY_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_ALLOCATE_MEMORY_SPACE, "ALLOCATE_MEMORY_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_FREE_MEMORY_SPACE, "FREE_MEMORY_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_REMOVE_MEMORY_SPACE, "REMOVE_MEMORY_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_GET_MEMORY_SPACE_DESCRIPTOR, "GET_MEMORY_SPACE_DESCRIPTOR"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_SET_MEMORY_SPACE_ATTRIBUTES, "SET_MEMORY_SPACE_ATTRIBUTES"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_GET_MEMORY_SPACE_MAP, "GET_MEMORY_SPACE_MAP"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_ADD_IO_SPACE, "ADD_IO_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_ALLOCATE_IO_SPACE, "ALLOCATE_IO_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_FREE_IO_SPACE, "FREE_IO_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_REMOVE_IO_SPACE, "REMOVE_IO_SPACE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_GET_IO_SPACE_DESCRIPTOR, "GET_IO_SPACE_DESCRIPTOR"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_GET_IO_SPACE_MAP, "GET_IO_SPACE_MAP"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_DISPATCH, "DISPATCH"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_SCHEDULE, "SCHEDULE"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_TRUST, "TRUST"),
+    (EFI_SOFTWARE_EFI_DXE_SERVICE, EFI_SW_DS_PC_PROCESS_FIRMWARE_VOLUME, "PROCESS_FIRMWARE_VOLUME"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_BEGIN_CONNECTING_DRIVERS, "BEGIN_CONNECTING_DRIVERS"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_PC_VERIFYING_PASSWORD, "VERIFYING_PASSWORD"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, EFI_SW_DXE_RT_PC_S0, "S0"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, EFI_SW_DXE_RT_PC_S1, "S1"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, EFI_SW_DXE_RT_PC_S2, "S2"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, EFI_SW_DXE_RT_PC_S3, "S3"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, EFI_SW_DXE_RT_PC_S4, "S4"),
+    (EFI_SOFTWARE_DXE_RT_DRIVER, EFI_SW_DXE_RT_PC_S5, "S5"),
+];
+
+/// The error code operation names, keyed by the class, or the class and subclass for subclass specific operations.
+const ERROR_OPERATIONS: &[(EfiStatusCodeValue, EfiStatusCodeValue, &str)] = &[
+    (EFI_COMPUTING_UNIT, EFI_CU_EC_NON_SPECIFIC, "NON_SPECIFIC"),
+    (EFI_COMPUTING_UNIT, EFI_CU_EC_DISABLED, "DISABLED"),
+    (EFI_COMPUTING_UNIT, EFI_CU_EC_NOT_SUPPORTED, "NOT_SUPPORTED"),
+    (EFI_COMPUTING_UNIT, EFI_CU_EC_NOT_DETECTED, "NOT_DETECTED"),
+    (EFI_COMPUTING_UNIT, EFI_CU_EC_NOT_CONFIGURED, "NOT_CONFIGURED"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_INVALID_TYPE, "INVALID_TYPE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_INVALID_SPEED, "INVALID_SPEED"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_MISMATCH, "MISMATCH"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_TIMER_EXPIRED, "TIMER_EXPIRED"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_SELF_TEST, "SELF_TEST"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_INTERNAL, "INTERNAL"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_THERMAL, "THERMAL"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_LOW_VOLTAGE, "LOW_VOLTAGE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_HIGH_VOLTAGE, "HIGH_VOLTAGE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_CACHE, "CACHE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_MICROCODE_UPDATE, "MICROCODE_UPDATE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_CORRECTABLE, "CORRECTABLE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_UNCORRECTABLE, "UNCORRECTABLE"),
+    (EFI_COMPUTING_UNIT_HOST_PROCESSOR, EFI_CU_HP_EC_NO_MICROCODE_UPDATE, "NO_MICROCODE_UPDATE"),
+    (EFI_COMPUTING_UNIT_FIRMWARE_PROCESSOR, EFI_CU_FP_EC_HARD_FAIL, "HARD_FAIL"),
+    (EFI_COMPUTING_UNIT_FIRMWARE_PROCESSOR, EFI_CU_FP_EC_SOFT_FAIL, "SOFT_FAIL"),
+    (EFI_COMPUTING_UNIT_FIRMWARE_PROCESSOR, EFI_CU_FP_EC_COMM_ERROR, "COMM_ERROR"),
+    (EFI_COMPUTING_UNIT_CACHE, EFI_CU_CACHE_EC_INVALID_TYPE, "INVALID_TYPE"),
+    (EFI_COMPUTING_UNIT_CACHE, EFI_CU_CACHE_EC_INVALID_SPEED, "INVALID_SPEED"),
+    (EFI_COMPUTING_UNIT_CACHE, EFI_CU_CACHE_EC_INVALID_SIZE, "INVALID_SIZE"),
+    (EFI_COMPUTING_UNIT_CACHE, EFI_CU_CACHE_EC_MISMATCH, "MISMATCH"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_INVALID_TYPE, "INVALID_TYPE"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_INVALID_SPEED, "INVALID_SPEED"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_CORRECTABLE, "CORRECTABLE"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_UNCORRECTABLE, "UNCORRECTABLE"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_SPD_FAIL, "SPD_FAIL"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_INVALID_SIZE, "INVALID_SIZE"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_MISMATCH, "MISMATCH"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_S3_RESUME_FAIL, "S3_RESUME_FAIL"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_UPDATE_FAIL, "UPDATE_FAIL"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_NONE_DETECTED, "NONE_DETECTED"),
+    (EFI_COMPUTING_UNIT_MEMORY, EFI_CU_MEMORY_EC_NONE_USEFUL, "NONE_USEFUL"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_EC_BAD_BATTERY, "BAD_BATTERY"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_EC_DXE_NB_ERROR, "DXE_NB_ERROR"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_EC_DXE_SB_ERROR, "DXE_SB_ERROR"),
+    (EFI_COMPUTING_UNIT_CHIPSET, EFI_CHIPSET_EC_INTRUDER_DETECT, "INTRUDER_DETECT"),
+    (EFI_PERIPHERAL, EFI_P_EC_NON_SPECIFIC, "NON_SPECIFIC"),
+    (EFI_PERIPHERAL, EFI_P_EC_DISABLED, "DISABLED"),
+    (EFI_PERIPHERAL, EFI_P_EC_NOT_SUPPORTED, "NOT_SUPPORTED"),
+    (EFI_PERIPHERAL, EFI_P_EC_NOT_DETECTED, "NOT_DETECTED"),
+    (EFI_PERIPHERAL, EFI_P_EC_NOT_CONFIGURED, "NOT_CONFIGURED"),
+    (EFI_PERIPHERAL, EFI_P_EC_INTERFACE_ERROR, "INTERFACE_ERROR"),
+    (EFI_PERIPHERAL, EFI_P_EC_CONTROLLER_ERROR, "CONTROLLER_ERROR"),
+    (EFI_PERIPHERAL, EFI_P_EC_INPUT_ERROR, "INPUT_ERROR"),
+    (EFI_PERIPHERAL, EFI_P_EC_OUTPUT_ERROR, "OUTPUT_ERROR"),
+    (EFI_PERIPHERAL, EFI_P_EC_RESOURCE_CONFLICT, "RESOURCE_CONFLICT"),
+    (EFI_PERIPHERAL_KEYBOARD, EFI_P_KEYBOARD_EC_LOCKED, "LOCKED"),
+    (EFI_PERIPHERAL_KEYBOARD, EFI_P_KEYBOARD_EC_STUCK_KEY, "STUCK_KEY"),
+    (EFI_PERIPHERAL_KEYBOARD, EFI_P_KEYBOARD_EC_BUFFER_FULL, "BUFFER_FULL"),
+    (EFI_PERIPHERAL_MOUSE, EFI_P_MOUSE_EC_LOCKED, "LOCKED"),
+    (EFI_IO_BUS, EFI_IOB_EC_NON_SPECIFIC, "NON_SPECIFIC"),
+    (EFI_IO_BUS, EFI_IOB_EC_DISABLED, "DISABLED"),
+    (EFI_IO_BUS, EFI_IOB_EC_NOT_SUPPORTED, "NOT_SUPPORTED"),
+    (EFI_IO_BUS, EFI_IOB_EC_NOT_DETECTED, "NOT_DETECTED"),
+    (EFI_IO_BUS, EFI_IOB_EC_NOT_CONFIGURED, "NOT_CONFIGURED"),
+    (EFI_IO_BUS, EFI_IOB_EC_INTERFACE_ERROR, "INTERFACE_ERROR"),
+    (EFI_IO_BUS, EFI_IOB_EC_CONTROLLER_ERROR, "CONTROLLER_ERROR"),
+    (EFI_IO_BUS, EFI_IOB_EC_READ_ERROR, "READ_ERROR"),
+    (EFI_IO_BUS, EFI_IOB_EC_WRITE_ERROR, "WRITE_ERROR"),
+    (EFI_IO_BUS, EFI_IOB_EC_RESOURCE_CONFLICT, "RESOURCE_CONFLICT"),
+    (EFI_IO_BUS_PCI, EFI_IOB_PCI_EC_PERR, "PERR"),
+    (EFI_IO_BUS_PCI, EFI_IOB_PCI_EC_SERR, "SERR"),
+    (EFI_IO_BUS_ATA_ATAPI, EFI_IOB_ATA_BUS_SMART_NOTSUPPORTED, "SMART_NOTSUPPORTED"),
+    (EFI_IO_BUS_ATA_ATAPI, EFI_IOB_ATA_BUS_SMART_DISABLED, "SMART_DISABLED"),
+    (EFI_SOFTWARE, EFI_SW_EC_NON_SPECIFIC, "NON_SPECIFIC"),
+    (EFI_SOFTWARE, EFI_SW_EC_LOAD_ERROR, "LOAD_ERROR"),
+    (EFI_SOFTWARE, EFI_SW_EC_INVALID_PARAMETER, "INVALID_PARAMETER"),
+    (EFI_SOFTWARE, EFI_SW_EC_UNSUPPORTED, "UNSUPPORTED"),
+    (EFI_SOFTWARE, EFI_SW_EC_INVALID_BUFFER, "INVALID_BUFFER"),
+    (EFI_SOFTWARE, EFI_SW_EC_OUT_OF_RESOURCES, "OUT_OF_RESOURCES"),
+    (EFI_SOFTWARE, EFI_SW_EC_ABORTED, "ABORTED"),
+    (EFI_SOFTWARE, EFI_SW_EC_ILLEGAL_SOFTWARE_STATE, "ILLEGAL_SOFTWARE_STATE"),
+    (EFI_SOFTWARE, EFI_SW_EC_ILLEGAL_HARDWARE_STATE, "ILLEGAL_HARDWARE_STATE"),
+    (EFI_SOFTWARE, EFI_SW_EC_START_ERROR, "START_ERROR"),
+    (EFI_SOFTWARE, EFI_SW_EC_BAD_DATE_TIME, "BAD_DATE_TIME"),
+    (EFI_SOFTWARE, EFI_SW_EC_CFG_INVALID, "CFG_INVALID"),
+    (EFI_SOFTWARE, EFI_SW_EC_CFG_CLR_REQUEST, "CFG_CLR_REQUEST"),
+    (EFI_SOFTWARE, EFI_SW_EC_CFG_DEFAULT, "CFG_DEFAULT"),
+    (EFI_SOFTWARE, EFI_SW_EC_PWD_INVALID, "PWD_INVALID"),
+    (EFI_SOFTWARE, EFI_SW_EC_PWD_CLR_REQUEST, "PWD_CLR_REQUEST"),
+    (EFI_SOFTWARE, EFI_SW_EC_PWD_CLEARED, "PWD_CLEARED"),
+    (EFI_SOFTWARE, EFI_SW_EC_EVENT_LOG_FULL, "EVENT_LOG_FULL"),
+    (EFI_SOFTWARE, EFI_SW_EC_WRITE_PROTECTED, "WRITE_PROTECTED"),
+    (EFI_SOFTWARE, EFI_SW_EC_FV_CORRUPTED, "FV_CORRUPTED"),
+    (EFI_SOFTWARE, EFI_SW_EC_INCONSISTENT_MEMORY_MAP, "INCONSISTENT_MEMORY_MAP"),
+    (EFI_SOFTWARE_PEI_CORE, EFI_SW_PEI_CORE_EC_DXE_CORRUPT, "DXE_CORRUPT"),
+    (EFI_SOFTWARE_PEI_CORE, EFI_SW_PEI_CORE_EC_DXEIPL_NOT_FOUND, "DXEIPL_NOT_FOUND"),
+    (EFI_SOFTWARE_PEI_CORE, EFI_SW_PEI_CORE_EC_MEMORY_NOT_INSTALLED, "MEMORY_NOT_INSTALLED"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_NO_RECOVERY_CAPSULE, "NO_RECOVERY_CAPSULE"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_INVALID_CAPSULE_DESCRIPTOR, "INVALID_CAPSULE_DESCRIPTOR"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_S3_RESUME_PPI_NOT_FOUND, "S3_RESUME_PPI_NOT_FOUND"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_S3_BOOT_SCRIPT_ERROR, "S3_BOOT_SCRIPT_ERROR"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_S3_OS_WAKE_ERROR, "S3_OS_WAKE_ERROR"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_S3_RESUME_FAILED, "S3_RESUME_FAILED"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_RECOVERY_PPI_NOT_FOUND, "RECOVERY_PPI_NOT_FOUND"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_RECOVERY_FAILED, "RECOVERY_FAILED"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_S3_RESUME_ERROR, "S3_RESUME_ERROR"),
+    (EFI_SOFTWARE_PEI_MODULE, EFI_SW_PEI_EC_INVALID_CAPSULE, "INVALID_CAPSULE"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_EC_NO_ARCH, "NO_ARCH"),
+    (EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_EC_IMAGE_LOAD_FAILURE, "IMAGE_LOAD_FAILURE"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_EC_LEGACY_OPROM_NO_SPACE, "LEGACY_OPROM_NO_SPACE"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_EC_INVALID_PASSWORD, "INVALID_PASSWORD"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_EC_BOOT_OPTION_LOAD_ERROR, "BOOT_OPTION_LOAD_ERROR"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_EC_BOOT_OPTION_FAILED, "BOOT_OPTION_FAILED"),
+    (EFI_SOFTWARE_DXE_BS_DRIVER, EFI_SW_DXE_BS_EC_INVALID_IDE_PASSWORD, "INVALID_IDE_PASSWORD"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_UNDEFINED, "UNDEFINED"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_DIVIDE_ERROR, "DIVIDE_ERROR"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_DEBUG, "DEBUG"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_BREAKPOINT, "BREAKPOINT"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_OVERFLOW, "OVERFLOW"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_INVALID_OPCODE, "INVALID_OPCODE"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_STACK_FAULT, "STACK_FAULT"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_ALIGNMENT_CHECK, "ALIGNMENT_CHECK"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_INSTRUCTION_ENCODING, "INSTRUCTION_ENCODING"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_BAD_BREAK, "BAD_BREAK"),
+    (EFI_SOFTWARE_EBC_EXCEPTION, EFI_SW_EC_EBC_STEP, "STEP"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_DIVIDE_ERROR, "DIVIDE_ERROR"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_DEBUG, "DEBUG"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_NMI, "NMI"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_BREAKPOINT, "BREAKPOINT"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_OVERFLOW, "OVERFLOW"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_BOUND, "BOUND"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_INVALID_OPCODE, "INVALID_OPCODE"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_DOUBLE_FAULT, "DOUBLE_FAULT"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_INVALID_TSS, "INVALID_TSS"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_SEG_NOT_PRESENT, "SEG_NOT_PRESENT"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_STACK_FAULT, "STACK_FAULT"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_GP_FAULT, "GP_FAULT"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_PAGE_FAULT, "PAGE_FAULT"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_FP_ERROR, "FP_ERROR"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_ALIGNMENT_CHECK, "ALIGNMENT_CHECK"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_MACHINE_CHECK, "MACHINE_CHECK"),
+    (EFI_SOFTWARE_IA32_EXCEPTION, EFI_SW_EC_IA32_SIMD, "SIMD"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_ALT_DTLB, "ALT_DTLB"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_DNESTED_TLB, "DNESTED_TLB"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_BREAKPOINT, "BREAKPOINT"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_EXTERNAL_INTERRUPT, "EXTERNAL_INTERRUPT"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_GEN_EXCEPT, "GEN_EXCEPT"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_NAT_CONSUMPTION, "NAT_CONSUMPTION"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_DEBUG_EXCEPT, "DEBUG_EXCEPT"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_UNALIGNED_ACCESS, "UNALIGNED_ACCESS"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_FP_FAULT, "FP_FAULT"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_FP_TRAP, "FP_TRAP"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_TAKEN_BRANCH, "TAKEN_BRANCH"),
+    (EFI_SOFTWARE_IPF_EXCEPTION, EFI_SW_EC_IPF_SINGLE_STEP, "SINGLE_STEP"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_EC_RESET_NOT_AVAILABLE, "RESET_NOT_AVAILABLE"),
+    (EFI_SOFTWARE_PEI_SERVICE, EFI_SW_PS_EC_MEMORY_INSTALLED_TWICE, "MEMORY_INSTALLED_TWICE"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_DIVIDE_ERROR, "DIVIDE_ERROR"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_DEBUG, "DEBUG"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_NMI, "NMI"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_BREAKPOINT, "BREAKPOINT"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_OVERFLOW, "OVERFLOW"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_BOUND, "BOUND"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_INVALID_OPCODE, "INVALID_OPCODE"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_DOUBLE_FAULT, "DOUBLE_FAULT"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_INVALID_TSS, "INVALID_TSS"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_SEG_NOT_PRESENT, "SEG_NOT_PRESENT"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_STACK_FAULT, "STACK_FAULT"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_GP_FAULT, "GP_FAULT"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_PAGE_FAULT, "PAGE_FAULT"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_FP_ERROR, "FP_ERROR"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_ALIGNMENT_CHECK, "ALIGNMENT_CHECK"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_MACHINE_CHECK, "MACHINE_CHECK"),
+    (EFI_SOFTWARE_X64_EXCEPTION, EFI_SW_EC_X64_SIMD, "SIMD"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_RESET, "RESET"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_UNDEFINED_INSTRUCTION, "UNDEFINED_INSTRUCTION"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_SOFTWARE_INTERRUPT, "SOFTWARE_INTERRUPT"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_PREFETCH_ABORT, "PREFETCH_ABORT"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_DATA_ABORT, "DATA_ABORT"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_RESERVED, "RESERVED"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_IRQ, "IRQ"),
+    (EFI_SOFTWARE_ARM_EXCEPTION, EFI_SW_EC_ARM_FIQ, "FIQ"),
+];
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn describe_names_known_codes() {
+        let name = describe(EFI_PROGRESS_CODE, EFI_SOFTWARE_DXE_CORE | EFI_SW_DXE_CORE_PC_HANDOFF_TO_NEXT);
+        assert_eq!(name.to_string(), "SOFTWARE.DXE_CORE.HANDOFF_TO_NEXT");
+
+        let name = describe(EFI_ERROR_CODE | EFI_ERROR_MAJOR, EFI_PERIPHERAL_KEYBOARD | EFI_P_EC_NOT_DETECTED);
+        assert_eq!(name.to_string(), "PERIPHERAL.KEYBOARD.NOT_DETECTED");
+    }
+
+    #[test]
+    fn subclass_operations_take_precedence() {
+        assert_eq!(
+            operation_name(EFI_ERROR_CODE, EFI_SOFTWARE_X64_EXCEPTION | EFI_SW_EC_X64_DIVIDE_ERROR),
+            Some("DIVIDE_ERROR")
+        );
+        assert_eq!(
+            operation_name(EFI_ERROR_CODE, EFI_SOFTWARE_DXE_CORE | EFI_SW_EC_NON_SPECIFIC),
+            Some("NON_SPECIFIC")
+        );
+        // Progress and error codes share the operation numbers.
+        assert_eq!(operation_name(EFI_PROGRESS_CODE, EFI_SOFTWARE_DXE_CORE | EFI_SW_PC_INIT), Some("INIT"));
+        assert_eq!(operation_name(EFI_DEBUG_CODE, EFI_SOFTWARE_DXE_CORE | EFI_SW_PC_INIT), None);
+    }
+
+    #[test]
+    fn describe_falls_back_to_numbers() {
+        let name = describe(EFI_PROGRESS_CODE, EFI_SOFTWARE_DXE_CORE | EFI_OEM_SPECIFIC | 0x12);
+        assert_eq!(name.to_string(), "SOFTWARE.DXE_CORE.0x8012");
+        let name = describe(EFI_PROGRESS_CODE, 0x7F23_0001);
+        assert_eq!(name.to_string(), "0x7f.0x23.0x0001");
+    }
+
+    #[test]
+    fn tables_are_consistent() {
+        for (subclass, _) in SUBCLASSES {
+            assert!(class_name(*subclass).is_some());
+        }
+        for (scope, operation, _) in PROGRESS_OPERATIONS.iter().chain(ERROR_OPERATIONS) {
+            assert!(subclass_name(*scope).is_some());
+            assert!(*operation < EFI_OEM_SPECIFIC);
+        }
+    }
+}
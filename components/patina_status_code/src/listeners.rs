@@ -0,0 +1,171 @@
+//! Built-in Status Code Listeners
+//!
+//! - [log_status_code] forwards status codes to the `log` crate, with the names decoded by [crate::decode].
+//! - [record_progress_code] records progress codes into a memory ring, which can be read back with
+//!   [progress_codes], for example to find the last progress code reached before a hang or crash.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::{
+    Guid,
+    pi::{
+        protocols::status_code::EfiStatusCodeValue,
+        status_code::{EFI_ERROR_CODE, EFI_ERROR_MINOR, EFI_PROGRESS_CODE},
+    },
+};
+use spin::Mutex;
+
+use crate::{decode, service::StatusCodeRecord};
+
+/// The number of progress codes kept by [record_progress_code].
+pub const PROGRESS_RING_CAPACITY: usize = 64;
+
+/// Logs `record` to the `status_code` target: progress codes at info level, minor errors at warn level, other
+/// errors at error level, and debug codes at debug level.
+///
+/// The logger is not expected to be usable at runtime, so this listener should be registered below
+/// [Tpl::HIGH_LEVEL](patina::boot_services::tpl::Tpl::HIGH_LEVEL).
+pub fn log_status_code(record: &StatusCodeRecord<'_>) {
+    let name = decode::describe(record.code_type, record.value);
+    let level = match record.kind() {
+        EFI_PROGRESS_CODE => log::Level::Info,
+        EFI_ERROR_CODE if record.severity() == EFI_ERROR_MINOR => log::Level::Warn,
+        EFI_ERROR_CODE => log::Level::Error,
+        _ => log::Level::Debug,
+    };
+    let kind = match record.kind() {
+        EFI_PROGRESS_CODE => "Progress",
+        EFI_ERROR_CODE => "Error",
+        _ => "Debug",
+    };
+
+    match record.caller_id {
+        Some(caller_id) => log::log!(
+            target: "status_code",
+            level,
+            "{kind} code {name} ({:#010x}), instance {}, from {}",
+            record.value,
+            record.instance,
+            Guid::from_ref(&caller_id)
+        ),
+        None => log::log!(
+            target: "status_code",
+            level,
+            "{kind} code {name} ({:#010x}), instance {}",
+            record.value,
+            record.instance
+        ),
+    }
+}
+
+/// A progress code recorded by [record_progress_code].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressCode {
+    /// The number of progress codes recorded before this one, including those that were overwritten since.
+    pub sequence: u64,
+    /// The class, subclass and operation of the progress code.
+    pub value: EfiStatusCodeValue,
+    /// The instance the progress code was reported for.
+    pub instance: u32,
+}
+
+/// A fixed size ring of the most recent progress codes.
+struct ProgressRing {
+    entries: [ProgressCode; PROGRESS_RING_CAPACITY],
+    recorded: u64,
+}
+
+impl ProgressRing {
+    const fn new() -> Self {
+        Self { entries: [ProgressCode { sequence: 0, value: 0, instance: 0 }; PROGRESS_RING_CAPACITY], recorded: 0 }
+    }
+
+    fn record(&mut self, value: EfiStatusCodeValue, instance: u32) {
+        let sequence = self.recorded;
+        self.entries[(sequence % PROGRESS_RING_CAPACITY as u64) as usize] = ProgressCode { sequence, value, instance };
+        self.recorded += 1;
+    }
+
+    fn copy_to(&self, buffer: &mut [ProgressCode]) -> usize {
+        let count = buffer.len().min(PROGRESS_RING_CAPACITY).min(self.recorded as usize);
+        let first = self.recorded - count as u64;
+        for (slot, sequence) in buffer.iter_mut().zip(first..self.recorded) {
+            *slot = self.entries[(sequence % PROGRESS_RING_CAPACITY as u64) as usize];
+        }
+        count
+    }
+}
+
+static PROGRESS_RING: Mutex<ProgressRing> = Mutex::new(ProgressRing::new());
+
+/// Records progress codes into the progress code ring, ignoring other status codes.
+///
+/// The listener neither allocates nor uses boot services, so it can be registered at
+/// [Tpl::HIGH_LEVEL](patina::boot_services::tpl::Tpl::HIGH_LEVEL) and keep recording at runtime. A progress code
+/// reported while the ring is being read or written, for example from an interrupt, is not recorded.
+pub fn record_progress_code(record: &StatusCodeRecord<'_>) {
+    if record.kind() != EFI_PROGRESS_CODE {
+        return;
+    }
+    if let Some(mut ring) = PROGRESS_RING.try_lock() {
+        ring.record(record.value, record.instance);
+    }
+}
+
+/// Copies the most recent progress codes recorded by [record_progress_code] into `buffer`, oldest first, and
+/// returns the number of entries copied.
+///
+/// At most [PROGRESS_RING_CAPACITY] entries are available. Gaps in [ProgressCode::sequence] show that older entries
+/// were overwritten.
+pub fn progress_codes(buffer: &mut [ProgressCode]) -> usize {
+    PROGRESS_RING.lock().copy_to(buffer)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use patina::pi::status_code::{
+        EFI_DEBUG_CODE, EFI_SOFTWARE_DXE_CORE, EFI_SW_DXE_CORE_PC_HANDOFF_TO_NEXT, EFI_SW_EC_NON_SPECIFIC,
+    };
+
+    fn record(code_type: u32, value: EfiStatusCodeValue) -> StatusCodeRecord<'static> {
+        StatusCodeRecord { code_type, value, instance: 0, caller_id: None, data: None }
+    }
+
+    #[test]
+    fn progress_ring_keeps_the_most_recent_codes() {
+        let mut ring = ProgressRing::new();
+        let mut buffer = [ProgressCode::default(); 4];
+        assert_eq!(ring.copy_to(&mut buffer), 0);
+
+        for value in 0..PROGRESS_RING_CAPACITY as u32 + 2 {
+            ring.record(value, value * 2);
+        }
+        assert_eq!(ring.copy_to(&mut buffer), 4);
+        let last = PROGRESS_RING_CAPACITY as u32 + 1;
+        assert_eq!(buffer[3], ProgressCode { sequence: last as u64, value: last, instance: last * 2 });
+        assert_eq!(buffer[0].value, last - 3);
+
+        let mut buffer = [ProgressCode::default(); PROGRESS_RING_CAPACITY + 1];
+        assert_eq!(ring.copy_to(&mut buffer), PROGRESS_RING_CAPACITY);
+        assert_eq!(buffer[0].sequence, 2);
+    }
+
+    #[test]
+    fn record_progress_code_ignores_other_codes() {
+        let value = EFI_SOFTWARE_DXE_CORE | EFI_SW_DXE_CORE_PC_HANDOFF_TO_NEXT;
+        record_progress_code(&record(EFI_ERROR_CODE, EFI_SOFTWARE_DXE_CORE | EFI_SW_EC_NON_SPECIFIC));
+        record_progress_code(&record(EFI_DEBUG_CODE, EFI_SOFTWARE_DXE_CORE));
+        record_progress_code(&record(EFI_PROGRESS_CODE, value));
+
+        let mut buffer = [ProgressCode::default(); PROGRESS_RING_CAPACITY];
+        let count = progress_codes(&mut buffer);
+        assert!(buffer[..count].iter().all(|code| code.value == value));
+        assert_ne!(count, 0);
+    }
+}
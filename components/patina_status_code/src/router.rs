@@ -0,0 +1,453 @@
+//! Status Code Routing
+//!
+//! Hands reported status codes to the registered listeners: directly for listeners registered at
+//! [Tpl::HIGH_LEVEL], and through a per-listener queue drained from an event notification for the others.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering},
+};
+
+use patina::{
+    boot_services::tpl::Tpl,
+    error::EfiError,
+    pi::protocols::status_code::{EfiStatusCodeType, EfiStatusCodeValue},
+    runtime_services::fixed_vec::FixedVec,
+};
+use r_efi::efi;
+use spin::{Mutex, RwLock};
+
+use crate::service::{StatusCodeData, StatusCodeListener, StatusCodeRecord};
+
+/// The number of status codes that can be queued for a listener below [Tpl::HIGH_LEVEL].
+pub(crate) const QUEUE_DEPTH: usize = 16;
+
+/// The largest extended data, without its header, that can be queued with a status code.
+pub(crate) const MAX_QUEUED_DATA: usize = 256;
+
+/// The most listeners that can be registered at [Tpl::HIGH_LEVEL].
+pub(crate) const MAX_RUNTIME_LISTENERS: usize = 16;
+
+/// The listeners at [Tpl::HIGH_LEVEL].
+type RuntimeListeners = FixedVec<StatusCodeListener, MAX_RUNTIME_LISTENERS>;
+
+fn position(listeners: &RuntimeListeners, listener: StatusCodeListener) -> Option<usize> {
+    listeners.iter().position(|&l| core::ptr::fn_addr_eq(l, listener))
+}
+
+/// A copy of a [StatusCodeRecord], owned by a listener queue.
+#[derive(Clone, Copy)]
+struct QueuedRecord {
+    code_type: EfiStatusCodeType,
+    value: EfiStatusCodeValue,
+    instance: u32,
+    caller_id: Option<efi::Guid>,
+    data_type: Option<efi::Guid>,
+    data_len: usize,
+    data: [u8; MAX_QUEUED_DATA],
+}
+
+impl QueuedRecord {
+    fn new(record: &StatusCodeRecord<'_>) -> Option<Self> {
+        let mut queued = Self {
+            code_type: record.code_type,
+            value: record.value,
+            instance: record.instance,
+            caller_id: record.caller_id,
+            data_type: None,
+            data_len: 0,
+            data: [0; MAX_QUEUED_DATA],
+        };
+        if let Some(data) = record.data {
+            queued.data.get_mut(..data.data.len())?.copy_from_slice(data.data);
+            queued.data_type = Some(data.data_type);
+            queued.data_len = data.data.len();
+        }
+        Some(queued)
+    }
+
+    fn record(&self) -> StatusCodeRecord<'_> {
+        StatusCodeRecord {
+            code_type: self.code_type,
+            value: self.value,
+            instance: self.instance,
+            caller_id: self.caller_id,
+            data: self.data_type.map(|data_type| StatusCodeData { data_type, data: &self.data[..self.data_len] }),
+        }
+    }
+}
+
+/// A listener below [Tpl::HIGH_LEVEL], with the queue of status codes waiting to be delivered to it.
+pub(crate) struct DeferredListener {
+    listener: StatusCodeListener,
+    tpl: Tpl,
+    queue: Mutex<VecDeque<QueuedRecord>>,
+    event: AtomicPtr<c_void>,
+    dropped: AtomicUsize,
+}
+
+impl DeferredListener {
+    fn new(listener: StatusCodeListener, tpl: Tpl) -> Self {
+        Self {
+            listener,
+            tpl,
+            queue: Mutex::new(VecDeque::with_capacity(QUEUE_DEPTH)),
+            event: AtomicPtr::new(ptr::null_mut()),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// The TPL the listener is called at.
+    pub(crate) fn tpl(&self) -> Tpl {
+        self.tpl
+    }
+
+    fn event(&self) -> efi::Event {
+        self.event.load(Ordering::SeqCst)
+    }
+
+    /// Queues a copy of `record` without allocating, or counts it as dropped if it does not fit.
+    fn push(&self, record: &StatusCodeRecord<'_>) {
+        let queued = QueuedRecord::new(record);
+        match (queued, self.queue.try_lock()) {
+            (Some(queued), Some(mut queue)) if queue.len() < QUEUE_DEPTH => queue.push_back(queued),
+            _ => {
+                self.dropped.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Delivers the queued status codes to the listener. Called from the event notification.
+    pub(crate) fn drain(&self) {
+        // The lock is only held while popping an entry, so that the listener may report status codes itself.
+        let pop = || self.queue.lock().pop_front();
+        while let Some(queued) = pop() {
+            (self.listener)(&queued.record());
+        }
+
+        let dropped = self.dropped.swap(0, Ordering::SeqCst);
+        if dropped != 0 {
+            log::warn!(target: "status_code", "{dropped} status code(s) dropped for a listener at TPL {:?}.", self.tpl);
+        }
+    }
+}
+
+/// Owns the registered listeners, and routes reported status codes to them.
+///
+/// Status codes reported at runtime read the router, so it is allocated as `EfiRuntimeServicesData` and holds the
+/// listeners at [Tpl::HIGH_LEVEL] in a fixed-capacity list. The deferred listeners are only touched while boot
+/// services are available.
+pub(crate) struct Router {
+    signal: fn(efi::Event),
+    runtime: RwLock<RuntimeListeners>,
+    deferred: RwLock<Vec<&'static DeferredListener>>,
+    at_runtime: AtomicBool,
+    reporting: AtomicBool,
+}
+
+impl Router {
+    /// Creates a router that signals queued status codes to deferred listeners with `signal`.
+    pub(crate) fn new(signal: fn(efi::Event)) -> Self {
+        Self {
+            signal,
+            runtime: RwLock::new(RuntimeListeners::new()),
+            deferred: RwLock::new(Vec::new()),
+            at_runtime: AtomicBool::new(false),
+            reporting: AtomicBool::new(false),
+        }
+    }
+
+    /// Drops the deferred listeners and stops accepting registrations, which need boot services.
+    pub(crate) fn exit_boot_services(&self) {
+        self.at_runtime.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn at_runtime(&self) -> bool {
+        self.at_runtime.load(Ordering::SeqCst)
+    }
+
+    fn is_registered(&self, listener: StatusCodeListener) -> bool {
+        position(&self.runtime.read(), listener).is_some()
+            || self.deferred.read().iter().any(|d| core::ptr::fn_addr_eq(d.listener, listener))
+    }
+
+    /// Registers `listener` at `tpl`.
+    ///
+    /// For listeners below [Tpl::HIGH_LEVEL], `create_event` is called to create the event that drains the queue of
+    /// the listener, by calling [DeferredListener::drain] from its notification.
+    pub(crate) fn register(
+        &self,
+        listener: StatusCodeListener,
+        tpl: Tpl,
+        create_event: impl FnOnce(&'static DeferredListener) -> Result<efi::Event, EfiError>,
+    ) -> Result<(), EfiError> {
+        if self.at_runtime() {
+            return Err(EfiError::Unsupported);
+        }
+        if tpl > Tpl::HIGH_LEVEL {
+            return Err(EfiError::InvalidParameter);
+        }
+        if self.is_registered(listener) {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        if tpl == Tpl::HIGH_LEVEL {
+            return self.runtime.write().push(listener);
+        }
+
+        // The entry is never freed: its event may still be pending when the listener is unregistered.
+        let deferred: &'static DeferredListener = Box::leak(Box::new(DeferredListener::new(listener, tpl)));
+        let event = create_event(deferred)?;
+        deferred.event.store(event, Ordering::SeqCst);
+        self.deferred.write().push(deferred);
+        Ok(())
+    }
+
+    /// Unregisters `listener`, returning the event of a deferred listener so that it can be closed.
+    pub(crate) fn unregister(&self, listener: StatusCodeListener) -> Result<Option<efi::Event>, EfiError> {
+        if self.at_runtime() {
+            return Err(EfiError::Unsupported);
+        }
+
+        let mut runtime = self.runtime.write();
+        if let Some(index) = position(&runtime, listener) {
+            runtime.remove(index);
+            return Ok(None);
+        }
+        drop(runtime);
+
+        let mut deferred = self.deferred.write();
+        let index =
+            deferred.iter().position(|d| core::ptr::fn_addr_eq(d.listener, listener)).ok_or(EfiError::NotFound)?;
+        Ok(Some(deferred.remove(index).event()))
+    }
+
+    /// Routes `record` to the registered listeners.
+    ///
+    /// Fails with [EfiError::DeviceError] if the status code is reported while another one is being routed, for
+    /// example by a listener at [Tpl::HIGH_LEVEL] or from an interrupt, or while a listener is being registered.
+    pub(crate) fn report(&self, record: &StatusCodeRecord<'_>) -> Result<(), EfiError> {
+        if self.reporting.swap(true, Ordering::SeqCst) {
+            return Err(EfiError::DeviceError);
+        }
+
+        let result = self.route(record);
+        self.reporting.store(false, Ordering::SeqCst);
+
+        // Signal outside of the routing guard: the notifications run right away if the caller's TPL is low enough.
+        if result.is_ok() && !self.at_runtime() {
+            let Some(deferred) = self.deferred.try_read() else {
+                return result;
+            };
+            for listener in deferred.iter().filter(|d| !d.queue.lock().is_empty()) {
+                (self.signal)(listener.event());
+            }
+        }
+        result
+    }
+
+    fn route(&self, record: &StatusCodeRecord<'_>) -> Result<(), EfiError> {
+        let runtime = self.runtime.try_read().ok_or(EfiError::DeviceError)?;
+        for listener in runtime.iter().copied() {
+            listener(record);
+        }
+        drop(runtime);
+
+        if !self.at_runtime() {
+            let deferred = self.deferred.try_read().ok_or(EfiError::DeviceError)?;
+            for listener in deferred.iter() {
+                listener.push(record);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicU32;
+    use patina::pi::status_code::{EFI_PROGRESS_CODE, EFI_SOFTWARE_DXE_CORE, EFI_SW_PC_INIT};
+
+    fn noop_signal(_event: efi::Event) {}
+
+    fn router() -> &'static Router {
+        Box::leak(Box::new(Router::new(noop_signal)))
+    }
+
+    fn record(instance: u32) -> StatusCodeRecord<'static> {
+        StatusCodeRecord {
+            code_type: EFI_PROGRESS_CODE,
+            value: EFI_SOFTWARE_DXE_CORE | EFI_SW_PC_INIT,
+            instance,
+            caller_id: None,
+            data: None,
+        }
+    }
+
+    /// Registers `listener` at `tpl` with a fake event, returning the deferred listener entry if there is one.
+    fn register(router: &Router, listener: StatusCodeListener, tpl: Tpl) -> Option<&'static DeferredListener> {
+        let mut entry = None;
+        router
+            .register(listener, tpl, |deferred| {
+                entry = Some(deferred);
+                Ok(ptr::dangling_mut())
+            })
+            .unwrap();
+        entry
+    }
+
+    #[test]
+    fn high_level_listeners_are_called_directly() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        fn listener(record: &StatusCodeRecord<'_>) {
+            assert_eq!(record.instance, 7);
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let router = router();
+        assert!(register(router, listener, Tpl::HIGH_LEVEL).is_none());
+        router.report(&record(7)).unwrap();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 1);
+
+        // High level listeners keep receiving status codes at runtime.
+        router.exit_boot_services();
+        router.report(&record(7)).unwrap();
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn deferred_listeners_receive_queued_copies() {
+        static INSTANCES: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+        fn listener(record: &StatusCodeRecord<'_>) {
+            if let Some(data) = record.data {
+                assert_eq!(data.data, [1, 2, 3]);
+            }
+            INSTANCES.lock().push(record.instance);
+        }
+
+        let router = router();
+        let deferred = register(router, listener, Tpl::CALLBACK).unwrap();
+        assert_eq!(deferred.tpl(), Tpl::CALLBACK);
+
+        router.report(&record(1)).unwrap();
+        let data = [1, 2, 3];
+        let with_data = StatusCodeRecord {
+            data: Some(StatusCodeData { data_type: efi::Guid::from_bytes(&[0xA5; 16]), data: &data }),
+            ..record(2)
+        };
+        router.report(&with_data).unwrap();
+        assert!(INSTANCES.lock().is_empty());
+
+        deferred.drain();
+        assert_eq!(*INSTANCES.lock(), [1, 2]);
+
+        // Deferred listeners are dropped at runtime.
+        router.exit_boot_services();
+        router.report(&record(3)).unwrap();
+        deferred.drain();
+        assert_eq!(*INSTANCES.lock(), [1, 2]);
+    }
+
+    #[test]
+    fn deferred_queue_drops_what_does_not_fit() {
+        static CALLS: AtomicU32 = AtomicU32::new(0);
+        fn listener(_record: &StatusCodeRecord<'_>) {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let router = router();
+        let deferred = register(router, listener, Tpl::NOTIFY).unwrap();
+        let data = [0; MAX_QUEUED_DATA + 1];
+        let too_large = StatusCodeRecord {
+            data: Some(StatusCodeData { data_type: efi::Guid::from_bytes(&[0; 16]), data: &data }),
+            ..record(0)
+        };
+        router.report(&too_large).unwrap();
+        for instance in 0..QUEUE_DEPTH as u32 + 1 {
+            router.report(&record(instance)).unwrap();
+        }
+
+        assert_eq!(deferred.dropped.load(Ordering::SeqCst), 2);
+
+        deferred.drain();
+        assert_eq!(CALLS.load(Ordering::SeqCst), QUEUE_DEPTH as u32);
+        assert_eq!(deferred.dropped.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn nested_reports_are_rejected() {
+        static ROUTER: spin::Once<&'static Router> = spin::Once::new();
+        static NESTED: Mutex<Option<Result<(), EfiError>>> = Mutex::new(None);
+        fn listener(_record: &StatusCodeRecord<'_>) {
+            *NESTED.lock() = Some(ROUTER.get().unwrap().report(&record(1)));
+        }
+
+        let router = ROUTER.call_once(router);
+        register(router, listener, Tpl::HIGH_LEVEL);
+        assert_eq!(router.report(&record(0)), Ok(()));
+        assert_eq!(*NESTED.lock(), Some(Err(EfiError::DeviceError)));
+    }
+
+    #[test]
+    fn runtime_listeners_keep_their_order() {
+        static ORDER: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+        fn first(_record: &StatusCodeRecord<'_>) {
+            ORDER.lock().push(1);
+        }
+        fn second(_record: &StatusCodeRecord<'_>) {
+            ORDER.lock().push(2);
+        }
+        fn third(_record: &StatusCodeRecord<'_>) {
+            ORDER.lock().push(3);
+        }
+
+        let router = router();
+        for listener in [first, second, third] {
+            register(router, listener, Tpl::HIGH_LEVEL);
+        }
+        assert_eq!(router.unregister(second), Ok(None));
+        router.report(&record(0)).unwrap();
+        assert_eq!(*ORDER.lock(), [1, 3]);
+    }
+
+    #[test]
+    fn registration_rules() {
+        fn listener(_record: &StatusCodeRecord<'_>) {}
+        fn other(_record: &StatusCodeRecord<'_>) {}
+
+        let router = router();
+        register(router, listener, Tpl::HIGH_LEVEL);
+        register(router, other, Tpl::CALLBACK);
+        for tpl in [Tpl::HIGH_LEVEL, Tpl::NOTIFY] {
+            assert_eq!(router.register(listener, tpl, |_| Ok(ptr::null_mut())), Err(EfiError::AlreadyStarted));
+            assert_eq!(router.register(other, tpl, |_| Ok(ptr::null_mut())), Err(EfiError::AlreadyStarted));
+        }
+        assert_eq!(router.unregister(listener), Ok(None));
+        assert_eq!(router.unregister(other), Ok(Some(ptr::dangling_mut())));
+        assert_eq!(router.unregister(other), Err(EfiError::NotFound));
+
+        assert_eq!(
+            router.register(listener, Tpl(efi::TPL_HIGH_LEVEL + 1), |_| Ok(ptr::null_mut())),
+            Err(EfiError::InvalidParameter)
+        );
+        assert_eq!(
+            router.register(listener, Tpl::CALLBACK, |_| Err(EfiError::OutOfResources)),
+            Err(EfiError::OutOfResources)
+        );
+        assert_eq!(router.unregister(listener), Err(EfiError::NotFound));
+
+        router.exit_boot_services();
+        assert_eq!(router.register(listener, Tpl::HIGH_LEVEL, |_| Ok(ptr::null_mut())), Err(EfiError::Unsupported));
+        assert_eq!(router.unregister(other), Err(EfiError::Unsupported));
+    }
+}
@@ -0,0 +1,119 @@
+//! Status Code Service Definitions
+//!
+//! Defines the [StatusCodeRecord] handed to listeners and the [StatusCodeRouter] service produced by the
+//! [StatusCodeRouterProvider] component, which follows the semantics of the `EFI_RSC_HANDLER_PROTOCOL` of the PI
+//! specification.
+//!
+//! [StatusCodeRouterProvider]: crate::component::StatusCodeRouterProvider
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::{
+    boot_services::tpl::Tpl,
+    error::EfiError,
+    pi::{
+        protocols::status_code::{EfiStatusCodeType, EfiStatusCodeValue},
+        status_code::{EFI_STATUS_CODE_SEVERITY_MASK, EFI_STATUS_CODE_TYPE_MASK},
+    },
+};
+use r_efi::efi;
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// The extended data reported with a status code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusCodeData<'a> {
+    /// The GUID identifying the format of the data.
+    pub data_type: efi::Guid,
+    /// The data that follows the `EFI_STATUS_CODE_DATA` header.
+    pub data: &'a [u8],
+}
+
+/// A status code, as passed to `ReportStatusCode()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatusCodeRecord<'a> {
+    /// The code type and severity.
+    pub code_type: EfiStatusCodeType,
+    /// The class, subclass and operation.
+    pub value: EfiStatusCodeValue,
+    /// The instance of the reporting hardware or software entity, 0 if there is only one.
+    pub instance: u32,
+    /// The GUID of the module that reported the status code, if provided.
+    pub caller_id: Option<efi::Guid>,
+    /// The extended data, if provided.
+    pub data: Option<StatusCodeData<'a>>,
+}
+
+impl StatusCodeRecord<'_> {
+    /// Returns the code type (`EFI_PROGRESS_CODE`, `EFI_ERROR_CODE` or `EFI_DEBUG_CODE`) without the severity.
+    pub fn kind(&self) -> EfiStatusCodeType {
+        self.code_type & EFI_STATUS_CODE_TYPE_MASK
+    }
+
+    /// Returns the severity of an error code (`EFI_ERROR_MINOR` to `EFI_ERROR_UNCONTAINED`).
+    pub fn severity(&self) -> EfiStatusCodeType {
+        self.code_type & EFI_STATUS_CODE_SEVERITY_MASK
+    }
+}
+
+/// Called with every status code reported after the listener is registered.
+pub type StatusCodeListener = fn(&StatusCodeRecord<'_>);
+
+/// Registration of status code listeners.
+///
+/// Produced by the [StatusCodeRouterProvider] component. The TPL a listener is registered at decides how it is
+/// called:
+///
+/// - [Tpl::HIGH_LEVEL]: the listener is called directly by `ReportStatusCode()`, at the TPL of the caller, and keeps
+///   being called after `ExitBootServices()`. It must therefore not use boot services, allocate memory, or block.
+/// - Lower TPLs: the status code is queued and the listener is called from an event notification at that TPL. These
+///   listeners are unregistered at `ExitBootServices()`. A status code is dropped for a listener whose queue is full,
+///   or whose extended data does not fit in a queue entry.
+///
+/// [StatusCodeRouterProvider]: crate::component::StatusCodeRouterProvider
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait StatusCodeRouter {
+    /// Registers a listener for all subsequently reported status codes.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::AlreadyStarted] if the listener is already registered.
+    /// - [EfiError::InvalidParameter] if `tpl` is above [Tpl::HIGH_LEVEL].
+    /// - [EfiError::OutOfResources] if 16 listeners are already registered at [Tpl::HIGH_LEVEL], or if the event that
+    ///   delivers queued status codes could not be created.
+    /// - [EfiError::Unsupported] if called after `ExitBootServices()`.
+    fn register_listener(&self, listener: StatusCodeListener, tpl: Tpl) -> Result<(), EfiError>;
+
+    /// Removes a listener registered with [StatusCodeRouter::register_listener].
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::NotFound] if the listener is not registered.
+    /// - [EfiError::Unsupported] if called after `ExitBootServices()`.
+    fn unregister_listener(&self, listener: StatusCodeListener) -> Result<(), EfiError>;
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use patina::pi::status_code::{EFI_ERROR_CODE, EFI_ERROR_MAJOR};
+
+    #[test]
+    fn kind_and_severity_split_the_code_type() {
+        let record = StatusCodeRecord {
+            code_type: EFI_ERROR_CODE | EFI_ERROR_MAJOR,
+            value: 0,
+            instance: 0,
+            caller_id: None,
+            data: None,
+        };
+        assert_eq!(record.kind(), EFI_ERROR_CODE);
+        assert_eq!(record.severity(), EFI_ERROR_MAJOR);
+    }
+}
@@ -0,0 +1,493 @@
+//! Variable Service Provider Component
+//!
+//! Creates the [VariableStore], routes the variable entries of the runtime services table to it and installs the
+//! Variable and Variable Write architectural protocols so that drivers depending on them can be dispatched.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate alloc;
+
+use core::{ffi::c_void, ptr};
+
+use patina::{
+    boot_services::{BootServices, StandardBootServices, event::EventType, tpl::Tpl},
+    component::{
+        component,
+        params::Config,
+        service::{
+            Service,
+            memory::MemoryManager,
+            runtime_table::{RuntimeServiceEntries, RuntimeServicesTable, VariableEntries},
+        },
+    },
+    error::{EfiError, Result},
+    pi::protocols::{variable, variable_write},
+    runtime_services::{RuntimeServices, StandardRuntimeServices},
+};
+use r_efi::efi;
+
+use crate::{
+    config::VariableConfig,
+    error::VariableError,
+    service::{NoVariableCrypto, NvStorage, VariableCrypto},
+    store::VariableStore,
+};
+
+/// State shared with the runtime services table entries, which receive no context pointer.
+struct Provider {
+    store: &'static VariableStore,
+    boot_services: StandardBootServices,
+    runtime_services: StandardRuntimeServices,
+}
+
+static PROVIDER: spin::Once<Provider> = spin::Once::new();
+
+/// Produces the UEFI variable services.
+///
+/// Takes the platform's [NvStorage] implementation and consumes the [VariableConfig] configuration. If a
+/// [VariableCrypto] implementation is added with [with_crypto](Self::with_crypto), authenticated variables and the
+/// Secure Boot policy variables are enabled. Both implementations are moved to runtime services memory with the rest
+/// of the store, and follow the store to its virtual addresses when the OS calls `SetVirtualAddressMap()`.
+///
+/// The variable services are boot-time only: the component runs from the DXE core image, which the OS reclaims after
+/// `ExitBootServices()`, so the core reports them as unsupported in the `EFI_RT_PROPERTIES_TABLE`.
+///
+/// ```rust,ignore
+/// commands.add_component(VariableServiceProvider::new(RamNvStorage::new(0x40000, 0x1000)?).with_crypto(MyCrypto));
+/// ```
+pub struct VariableServiceProvider<N: NvStorage + 'static, C: VariableCrypto + 'static = NoVariableCrypto> {
+    nv: N,
+    crypto: Option<C>,
+}
+
+impl<N: NvStorage + 'static> VariableServiceProvider<N> {
+    /// Creates the component for the variable flash region `nv`, without authenticated variables.
+    pub const fn new(nv: N) -> Self {
+        Self { nv, crypto: None }
+    }
+}
+
+#[component]
+impl<N: NvStorage + 'static, C: VariableCrypto + 'static> VariableServiceProvider<N, C> {
+    /// Enables authenticated variables, with signatures verified by `crypto`.
+    pub fn with_crypto<D: VariableCrypto + 'static>(self, crypto: D) -> VariableServiceProvider<N, D> {
+        VariableServiceProvider { nv: self.nv, crypto: Some(crypto) }
+    }
+
+    #[coverage(off)] // Component integration - the entries it installs are tested directly.
+    fn entry_point(
+        self,
+        config: Config<VariableConfig>,
+        memory_manager: Service<dyn MemoryManager>,
+        rt_table: Service<dyn RuntimeServicesTable>,
+        boot_services: StandardBootServices,
+        runtime_services: StandardRuntimeServices,
+    ) -> Result<()> {
+        if PROVIDER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        // The store serves runtime callers after boot services memory is reclaimed.
+        let nv: &'static dyn NvStorage = memory_manager.leak_in_runtime_memory(self.nv)?;
+        let store = VariableStore::new(&config, nv, *memory_manager)?;
+        let store: &'static VariableStore = memory_manager.leak_in_runtime_memory(store)?;
+        if let Some(crypto) = self.crypto {
+            let crypto: &'static dyn VariableCrypto = memory_manager.leak_in_runtime_memory(crypto)?;
+            store.enable_authentication(crypto, *memory_manager)?;
+        }
+        PROVIDER.call_once(|| Provider { store, boot_services: boot_services.clone(), runtime_services });
+
+        boot_services
+            .create_event(EventType::SIGNAL_EXIT_BOOT_SERVICES, Tpl::NOTIFY, Some(exit_boot_services), store)
+            .map_err(EfiError::from)?;
+        boot_services
+            .create_event(EventType::SIGNAL_VIRTUAL_ADDRESS_CHANGE, Tpl::NOTIFY, Some(virtual_address_change), store)
+            .map_err(EfiError::from)?;
+
+        rt_table.install(RuntimeServiceEntries::Variable(VariableEntries {
+            get_variable,
+            get_next_variable_name,
+            set_variable,
+            query_variable_info,
+        }))?;
+
+        for guid in [&variable::PROTOCOL_GUID, &variable_write::PROTOCOL_GUID] {
+            // SAFETY: The architectural protocols carry no interface; a null pointer is what consumers expect.
+            unsafe { boot_services.install_protocol_interface_unchecked(None, guid, ptr::null_mut()) }
+                .map_err(EfiError::from)?;
+        }
+
+        log::info!(target: "variable", "Variable services installed.");
+        Ok(())
+    }
+}
+
+extern "efiapi" fn exit_boot_services(_event: efi::Event, store: &'static VariableStore) {
+    store.exit_boot_services();
+}
+
+extern "efiapi" fn virtual_address_change(_event: efi::Event, store: &'static VariableStore) {
+    if let Some(provider) = PROVIDER.get() {
+        store.convert_pointers(&|address| provider.runtime_services.convert_pointer(address).ok());
+    }
+}
+
+/// Runs `f` against the installed store, at `TPL_NOTIFY` while boot services are available.
+fn with_store(f: impl FnOnce(&VariableStore) -> efi::Status) -> efi::Status {
+    let Some(provider) = PROVIDER.get() else {
+        return efi::Status::NOT_READY;
+    };
+    if provider.store.at_runtime() {
+        return f(provider.store);
+    }
+    let _tpl = provider.boot_services.raise_tpl_guarded(Tpl::NOTIFY);
+    f(provider.store)
+}
+
+/// Returns the null-terminated name at `name`, reading at most `max_size` bytes.
+///
+/// # Safety
+///
+/// `name` must be null or point to readable memory that is either null-terminated or at least `max_size` bytes long.
+unsafe fn name_from_ptr<'a>(name: *const u16, max_size: usize) -> Option<&'a [u16]> {
+    if name.is_null() {
+        return None;
+    }
+    let max_len = max_size / 2;
+    // SAFETY: Per the caller contract, every character up to the terminator (or max_len) is readable.
+    let len = (0..max_len).find(|i| unsafe { *name.add(*i) } == 0)?;
+    // SAFETY: The first len + 1 characters were just read.
+    Some(unsafe { core::slice::from_raw_parts(name, len + 1) })
+}
+
+fn status<T>(result: core::result::Result<T, VariableError>) -> efi::Status {
+    match result {
+        Ok(_) => efi::Status::SUCCESS,
+        Err(err) => err.into(),
+    }
+}
+
+extern "efiapi" fn get_variable(
+    name: *mut u16,
+    guid: *mut efi::Guid,
+    attributes: *mut u32,
+    data_size: *mut usize,
+    data: *mut c_void,
+) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_store(|store| unsafe { get_variable_with(store, name, guid, attributes, data_size, data) })
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `GetVariable()` in the UEFI specification.
+unsafe fn get_variable_with(
+    store: &VariableStore,
+    name: *const u16,
+    guid: *const efi::Guid,
+    attributes: *mut u32,
+    data_size: *mut usize,
+    data: *mut c_void,
+) -> efi::Status {
+    // SAFETY: The caller guarantees the name is readable.
+    let Some(name) = (unsafe { name_from_ptr(name, store.max_name_size()) }) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    if guid.is_null() || data_size.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: Both pointers were checked for null above and are otherwise valid per the caller contract.
+    let (guid, size) = unsafe { (&*guid, &mut *data_size) };
+    if data.is_null() && *size != 0 {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let buffer: &mut [u8] = if data.is_null() {
+        &mut []
+    } else {
+        // SAFETY: The caller provides a buffer of at least *size bytes.
+        unsafe { core::slice::from_raw_parts_mut(data as *mut u8, *size) }
+    };
+
+    let (result, attr) = match store.get(name, guid, buffer) {
+        Ok((len, attr)) => {
+            *size = len;
+            (Ok(()), attr)
+        }
+        Err((VariableError::BufferTooSmall(len), attr)) => {
+            *size = len;
+            (Err(VariableError::BufferTooSmall(len)), attr)
+        }
+        Err((err, _)) => return err.into(),
+    };
+    if !attributes.is_null() {
+        // SAFETY: Checked for null above; attributes are optional per the specification.
+        unsafe { attributes.write(attr) };
+    }
+    status(result)
+}
+
+extern "efiapi" fn get_next_variable_name(name_size: *mut usize, name: *mut u16, guid: *mut efi::Guid) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_store(|store| unsafe { get_next_variable_name_with(store, name_size, name, guid) })
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `GetNextVariableName()` in the UEFI specification.
+unsafe fn get_next_variable_name_with(
+    store: &VariableStore,
+    name_size: *mut usize,
+    name: *mut u16,
+    guid: *mut efi::Guid,
+) -> efi::Status {
+    if name_size.is_null() || name.is_null() || guid.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    // SAFETY: All pointers were checked for null above and the buffer holds *name_size bytes per the caller contract.
+    let (size, buffer, guid) =
+        unsafe { (&mut *name_size, core::slice::from_raw_parts_mut(name, *name_size / 2), &mut *guid) };
+
+    match store.next_name(buffer, guid) {
+        Ok(len) => {
+            *size = len;
+            efi::Status::SUCCESS
+        }
+        Err(VariableError::BufferTooSmall(len)) => {
+            *size = len;
+            efi::Status::BUFFER_TOO_SMALL
+        }
+        Err(err) => err.into(),
+    }
+}
+
+extern "efiapi" fn set_variable(
+    name: *mut u16,
+    guid: *mut efi::Guid,
+    attributes: u32,
+    data_size: usize,
+    data: *mut c_void,
+) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_store(|store| unsafe { set_variable_with(store, name, guid, attributes, data_size, data) })
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `SetVariable()` in the UEFI specification.
+unsafe fn set_variable_with(
+    store: &VariableStore,
+    name: *const u16,
+    guid: *const efi::Guid,
+    attributes: u32,
+    data_size: usize,
+    data: *const c_void,
+) -> efi::Status {
+    // SAFETY: The caller guarantees the name is readable.
+    let Some(name) = (unsafe { name_from_ptr(name, store.max_name_size()) }) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    if guid.is_null() || (data.is_null() && data_size != 0) {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    let data: &[u8] = if data_size == 0 {
+        &[]
+    } else {
+        // SAFETY: data was checked for null above and holds data_size bytes per the caller contract.
+        unsafe { core::slice::from_raw_parts(data as *const u8, data_size) }
+    };
+    // SAFETY: Checked for null above.
+    status(store.set(name, unsafe { &*guid }, attributes, data))
+}
+
+extern "efiapi" fn query_variable_info(
+    attributes: u32,
+    maximum_variable_storage_size: *mut u64,
+    remaining_variable_storage_size: *mut u64,
+    maximum_variable_size: *mut u64,
+) -> efi::Status {
+    // SAFETY: Pointer validity is the caller's responsibility per the UEFI specification.
+    with_store(|store| unsafe {
+        query_variable_info_with(
+            store,
+            attributes,
+            maximum_variable_storage_size,
+            remaining_variable_storage_size,
+            maximum_variable_size,
+        )
+    })
+}
+
+/// # Safety
+///
+/// All pointers must be null or valid as described for `QueryVariableInfo()` in the UEFI specification.
+unsafe fn query_variable_info_with(
+    store: &VariableStore,
+    attributes: u32,
+    maximum_variable_storage_size: *mut u64,
+    remaining_variable_storage_size: *mut u64,
+    maximum_variable_size: *mut u64,
+) -> efi::Status {
+    if maximum_variable_storage_size.is_null()
+        || remaining_variable_storage_size.is_null()
+        || maximum_variable_size.is_null()
+    {
+        return efi::Status::INVALID_PARAMETER;
+    }
+    match store.query(attributes) {
+        Ok(info) => {
+            // SAFETY: All pointers were checked for null above.
+            unsafe {
+                maximum_variable_storage_size.write(info.maximum_variable_storage_size);
+                remaining_variable_storage_size.write(info.remaining_variable_storage_size);
+                maximum_variable_size.write(info.maximum_variable_size);
+            }
+            efi::Status::SUCCESS
+        }
+        Err(err) => err.into(),
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::service::RamNvStorage;
+    use alloc::boxed::Box;
+    use patina::component::service::memory::StdMemoryManager;
+
+    const GUID: efi::Guid = efi::Guid::from_fields(0xabcd, 1, 2, 3, 4, &[5, 6, 7, 8, 9, 10]);
+    const ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+    fn store() -> VariableStore {
+        let memory_manager = StdMemoryManager::new();
+        let nv = Box::leak(Box::new(RamNvStorage::new(0x10000, 0x1000).unwrap()));
+        VariableStore::new(&VariableConfig::default(), nv, &memory_manager).unwrap()
+    }
+
+    fn name(s: &str) -> alloc::vec::Vec<u16> {
+        s.encode_utf16().chain(core::iter::once(0)).collect()
+    }
+
+    #[test]
+    fn entries_not_available_before_install() {
+        assert_eq!(
+            query_variable_info(ATTRIBUTES, ptr::null_mut(), ptr::null_mut(), ptr::null_mut()),
+            efi::Status::NOT_READY
+        );
+    }
+
+    #[test]
+    fn set_and_get_through_raw_entries() {
+        let store = store();
+        let mut n = name("Raw");
+        let mut data = [1u8, 2, 3, 4];
+
+        // SAFETY: All pointers reference live locals of the stated sizes.
+        unsafe {
+            assert_eq!(
+                set_variable_with(&store, n.as_ptr(), &GUID, ATTRIBUTES, data.len(), data.as_ptr() as *const c_void),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(
+                set_variable_with(&store, n.as_ptr(), &GUID, ATTRIBUTES, 4, ptr::null()),
+                efi::Status::INVALID_PARAMETER
+            );
+            assert_eq!(
+                set_variable_with(&store, ptr::null(), &GUID, ATTRIBUTES, 0, ptr::null()),
+                efi::Status::INVALID_PARAMETER
+            );
+
+            let mut size = 0usize;
+            let mut attributes = 0u32;
+            assert_eq!(
+                get_variable_with(&store, n.as_mut_ptr(), &GUID, &mut attributes, &mut size, ptr::null_mut()),
+                efi::Status::BUFFER_TOO_SMALL
+            );
+            assert_eq!((size, attributes), (4, ATTRIBUTES));
+
+            data.fill(0);
+            assert_eq!(
+                get_variable_with(
+                    &store,
+                    n.as_mut_ptr(),
+                    &GUID,
+                    ptr::null_mut(),
+                    &mut size,
+                    data.as_mut_ptr() as *mut c_void
+                ),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(data, [1, 2, 3, 4]);
+
+            let unterminated = [b'A' as u16; 0x2000];
+            assert_eq!(
+                get_variable_with(&store, unterminated.as_ptr(), &GUID, ptr::null_mut(), &mut size, ptr::null_mut()),
+                efi::Status::INVALID_PARAMETER
+            );
+        }
+    }
+
+    #[test]
+    fn enumerate_through_raw_entry() {
+        let store = store();
+        store.set(&name("First"), &GUID, ATTRIBUTES, &[1]).unwrap();
+        store.set(&name("Second"), &GUID, ATTRIBUTES, &[1]).unwrap();
+
+        let mut buffer = [0u16; 4];
+        let mut guid = efi::Guid::from_bytes(&[0; 16]);
+        let mut size = buffer.len() * 2;
+        // SAFETY: All pointers reference live locals of the stated sizes.
+        unsafe {
+            assert_eq!(
+                get_next_variable_name_with(&store, &mut size, buffer.as_mut_ptr(), &mut guid),
+                efi::Status::BUFFER_TOO_SMALL
+            );
+            assert_eq!(size, name("First").len() * 2);
+
+            let mut buffer = [0u16; 16];
+            let mut size = buffer.len() * 2;
+            assert_eq!(
+                get_next_variable_name_with(&store, &mut size, buffer.as_mut_ptr(), &mut guid),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(&buffer[..size / 2], name("First").as_slice());
+            assert_eq!(guid, GUID);
+
+            let mut size = buffer.len() * 2;
+            assert_eq!(
+                get_next_variable_name_with(&store, &mut size, buffer.as_mut_ptr(), &mut guid),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(&buffer[..size / 2], name("Second").as_slice());
+
+            let mut size = buffer.len() * 2;
+            assert_eq!(
+                get_next_variable_name_with(&store, &mut size, buffer.as_mut_ptr(), &mut guid),
+                efi::Status::NOT_FOUND
+            );
+        }
+    }
+
+    #[test]
+    fn query_through_raw_entry() {
+        let store = store();
+        let (mut max_storage, mut remaining, mut max_size) = (0u64, 0u64, 0u64);
+        // SAFETY: All pointers reference live locals.
+        unsafe {
+            assert_eq!(
+                query_variable_info_with(&store, ATTRIBUTES, &mut max_storage, &mut remaining, &mut max_size),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(max_storage, remaining);
+            assert!(max_size > 0);
+            assert_eq!(
+                query_variable_info_with(&store, ATTRIBUTES, ptr::null_mut(), &mut remaining, &mut max_size),
+                efi::Status::INVALID_PARAMETER
+            );
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Variable Store Configuration
+//!
+//! Size limits for the variable store. The defaults follow common EDK II platform settings for `PcdMaxVariableSize`,
+//! `PcdMaxHardwareErrorVariableSize`, `PcdHwErrStorageSize` and `PcdVariableStoreSize`.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+/// Configuration for the variable store.
+///
+/// The non-volatile store size is not configured here; it is determined by the size of the [NvStorage] region.
+///
+/// [NvStorage]: crate::service::NvStorage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VariableConfig {
+    /// Maximum size of a single variable, including its header and name.
+    pub max_variable_size: usize,
+    /// Maximum size of a single hardware error record variable, including its header and name.
+    pub max_hardware_error_variable_size: usize,
+    /// Space of the non-volatile store reserved for hardware error record variables. Zero disables them.
+    pub hardware_error_storage_size: usize,
+    /// Size of the volatile variable store.
+    pub volatile_store_size: usize,
+}
+
+impl Default for VariableConfig {
+    fn default() -> Self {
+        Self {
+            max_variable_size: 0x2000,
+            max_hardware_error_variable_size: 0x8000,
+            hardware_error_storage_size: 0,
+            volatile_store_size: 0x10000,
+        }
+    }
+}
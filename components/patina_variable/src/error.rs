@@ -0,0 +1,76 @@
+//! Variable Store Error Definitions
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::error::EfiError;
+use r_efi::efi;
+
+/// Errors produced by the variable store.
+///
+/// Each variant maps to the status code the UEFI specification requires for the corresponding runtime service
+/// failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableError {
+    /// The name, GUID, attributes or size of the request are invalid.
+    InvalidParameter,
+    /// The variable does not exist (or is not visible at runtime).
+    NotFound,
+    /// The provided buffer is too small. Contains the required size in bytes.
+    BufferTooSmall(usize),
+    /// There is not enough space left in the store to hold the variable.
+    OutOfResources,
+    /// The variable cannot be modified in the current phase.
+    WriteProtected,
+    /// The requested attributes are not supported by this store.
+    Unsupported,
+    /// The request failed an authentication check.
+    SecurityViolation,
+    /// The store contents are not a valid variable store.
+    VolumeCorrupted,
+    /// The underlying non-volatile storage reported an error.
+    Device(EfiError),
+}
+
+impl From<VariableError> for efi::Status {
+    fn from(value: VariableError) -> Self {
+        match value {
+            VariableError::InvalidParameter => efi::Status::INVALID_PARAMETER,
+            VariableError::NotFound => efi::Status::NOT_FOUND,
+            VariableError::BufferTooSmall(_) => efi::Status::BUFFER_TOO_SMALL,
+            VariableError::OutOfResources => efi::Status::OUT_OF_RESOURCES,
+            VariableError::WriteProtected => efi::Status::WRITE_PROTECTED,
+            VariableError::Unsupported => efi::Status::UNSUPPORTED,
+            VariableError::SecurityViolation => efi::Status::SECURITY_VIOLATION,
+            VariableError::VolumeCorrupted => efi::Status::VOLUME_CORRUPTED,
+            VariableError::Device(_) => efi::Status::DEVICE_ERROR,
+        }
+    }
+}
+
+impl From<VariableError> for EfiError {
+    fn from(value: VariableError) -> Self {
+        match value {
+            VariableError::Device(err) => err,
+            other => EfiError::from(efi::Status::from(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_map_to_spec_status_codes() {
+        assert_eq!(efi::Status::from(VariableError::BufferTooSmall(10)), efi::Status::BUFFER_TOO_SMALL);
+        assert_eq!(efi::Status::from(VariableError::Device(EfiError::Timeout)), efi::Status::DEVICE_ERROR);
+        assert_eq!(EfiError::from(VariableError::Device(EfiError::Timeout)), EfiError::Timeout);
+        assert_eq!(EfiError::from(VariableError::OutOfResources), EfiError::OutOfResources);
+        assert_eq!(EfiError::from(VariableError::SecurityViolation), EfiError::SecurityViolation);
+    }
+}
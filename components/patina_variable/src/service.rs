@@ -0,0 +1,292 @@
+//! Variable Service Definitions
+//!
+//! Defines the [NvStorage] trait that a platform implements to give the variable store access to the flash region
+//! backing non-volatile variables, along with [RamNvStorage], a memory backed implementation for platforms that
+//! emulate non-volatile variables and for host based testing. The optional [VariableCrypto] trait enables
+//! authenticated variables.
+//!
+//! The [VariableStore] itself is re-exported here. It implements the [RuntimeServices] trait so it can be driven
+//! directly (for example from host tests) with the same API that components use against the runtime services table.
+//!
+//! [RuntimeServices]: patina::runtime_services::RuntimeServices
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{ops::Range, slice};
+
+use patina::{
+    component::service::memory::{AllocationOptions, MemoryManager},
+    efi_types::EfiMemoryType,
+    error::EfiError,
+    runtime_services::VirtualAddressMap,
+    uefi_size_to_pages,
+};
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+pub use crate::store::VariableStore;
+
+/// Access to the flash region that holds the non-volatile variable store.
+///
+/// Offsets are relative to the start of the region, which must begin with the variable firmware volume header (or be
+/// fully erased, in which case the store is formatted on first use). Implementations must honor flash semantics:
+/// erased bytes read as `0xFF`, and the store only ever writes to erased bytes or clears bits of bytes that were
+/// previously written.
+///
+/// Passed to [VariableServiceProvider::new], which moves it to runtime services memory. Implementations that back
+/// runtime accessible variables must remain callable after `ExitBootServices()`, so they must not use boot services
+/// or memory that is reclaimed by the OS.
+///
+/// [VariableServiceProvider::new]: crate::component::VariableServiceProvider::new
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+#[allow(clippy::needless_lifetimes)] //https://github.com/rust-lang/rust-clippy/issues/6622
+pub trait NvStorage: Send + Sync {
+    /// Prepares the region before the store first reads it, allocating any memory it needs from `memory_manager`.
+    ///
+    /// Implementations that need no preparation do not need to implement this.
+    fn initialize<'a>(&self, _memory_manager: &'a dyn MemoryManager) -> Result<(), EfiError> {
+        Ok(())
+    }
+
+    /// Total size of the region in bytes.
+    fn size(&self) -> usize;
+
+    /// Erase block size of the region in bytes. The region size must be a multiple of this value.
+    fn block_size(&self) -> usize;
+
+    /// Reads `buffer.len()` bytes starting at `offset`.
+    fn read(&self, offset: usize, buffer: &mut [u8]) -> Result<(), EfiError>;
+
+    /// Writes `data` starting at `offset`.
+    fn write(&self, offset: usize, data: &[u8]) -> Result<(), EfiError>;
+
+    /// Erases the blocks covering `range`, which is always block aligned.
+    fn erase(&self, range: Range<usize>) -> Result<(), EfiError>;
+
+    /// Returns the spare region reclaim stages the compacted store in, like the spare block of the EDK II fault
+    /// tolerant write driver. It must be at least as large as the region, and the region size must be a multiple of
+    /// its block size. Implementations initialize and convert it along with the region.
+    ///
+    /// Implementations without a spare do not need to implement this. Reclaim then rewrites the region in place: a
+    /// reset in the middle of it is reported as a corrupted store on the next boot, except a reset between the erase
+    /// and the first write, which leaves an erased region that is formatted as new.
+    fn spare(&self) -> Option<&'static dyn NvStorage> {
+        None
+    }
+
+    /// Moves the addresses the implementation holds, such as that of a memory mapped flash region, to the virtual
+    /// addresses of `map` when the OS calls `SetVirtualAddressMap()`.
+    ///
+    /// Implementations that hold no address do not need to implement this.
+    fn convert_pointers<'a>(&self, _map: &'a dyn VirtualAddressMap) {}
+}
+
+/// Cryptographic operations needed to process time based authenticated variable writes.
+///
+/// The variable store does not carry a crypto library of its own. Platforms that support Secure Boot produce this
+/// service from their crypto provider, and host tests can substitute a software implementation. When no
+/// implementation is available, writes with `EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS` are rejected.
+///
+/// Passed to [VariableServiceProvider::with_crypto]. Like [NvStorage], implementations are moved to runtime services
+/// memory and must remain callable after `ExitBootServices()`.
+///
+/// [VariableServiceProvider::with_crypto]: crate::component::VariableServiceProvider::with_crypto
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+#[allow(clippy::needless_lifetimes)] //https://github.com/rust-lang/rust-clippy/issues/6622
+pub trait VariableCrypto: Send + Sync {
+    /// Verifies a detached PKCS#7 `SignedData` over `message`.
+    ///
+    /// `signed_data` is DER encoded. With a `trusted_certificate` (a DER encoded X.509 certificate), the signer must
+    /// chain to it. Without one, the signature only needs to be valid for the top-level certificate carried in
+    /// `signed_data` itself, which is how private authenticated variables are bound to their first writer.
+    fn pkcs7_verify<'a>(&self, signed_data: &[u8], trusted_certificate: Option<&'a [u8]>, message: &[u8]) -> bool;
+
+    /// Returns the SHA-256 digest of the top-level certificate of the signer chain in `signed_data`, or `None` if
+    /// `signed_data` cannot be parsed.
+    fn pkcs7_signer_digest(&self, signed_data: &[u8]) -> Option<[u8; 32]>;
+
+    /// Moves the addresses the implementation holds to the virtual addresses of `map` when the OS calls
+    /// `SetVirtualAddressMap()`.
+    ///
+    /// Implementations that hold no address do not need to implement this.
+    fn convert_pointers<'a>(&self, _map: &'a dyn VirtualAddressMap) {}
+}
+
+/// The [VariableCrypto] of a store without authenticated variables. It has no value.
+pub enum NoVariableCrypto {}
+
+impl VariableCrypto for NoVariableCrypto {
+    fn pkcs7_verify(&self, _signed_data: &[u8], _trusted_certificate: Option<&[u8]>, _message: &[u8]) -> bool {
+        match *self {}
+    }
+
+    fn pkcs7_signer_digest(&self, _signed_data: &[u8]) -> Option<[u8; 32]> {
+        match *self {}
+    }
+}
+
+/// A [NvStorage] implementation backed by memory.
+///
+/// Contents do not survive a reset, so this is only suitable for platforms without a variable flash region (or early
+/// bring-up) and for testing. The backing memory is allocated as `EfiRuntimeServicesData` when the store is created,
+/// so the store remains usable at runtime.
+pub struct RamNvStorage {
+    memory: spin::Mutex<&'static mut [u8]>,
+    size: usize,
+    block_size: usize,
+}
+
+impl RamNvStorage {
+    /// Creates a region of `size` bytes, which is allocated and erased by [NvStorage::initialize].
+    pub fn new(size: usize, block_size: usize) -> Result<Self, EfiError> {
+        if block_size == 0 || size == 0 || !size.is_multiple_of(block_size) {
+            return Err(EfiError::InvalidParameter);
+        }
+        Ok(Self { memory: spin::Mutex::new(&mut []), size, block_size })
+    }
+
+    /// Creates a region over existing memory, such as a memory mapped copy of the variable flash region.
+    pub fn from_slice(memory: &'static mut [u8], block_size: usize) -> Result<Self, EfiError> {
+        if block_size == 0 || memory.is_empty() || !memory.len().is_multiple_of(block_size) {
+            return Err(EfiError::InvalidParameter);
+        }
+        let size = memory.len();
+        Ok(Self { memory: spin::Mutex::new(memory), size, block_size })
+    }
+
+    /// Returns the bytes at `offset..offset + len`, or [EfiError::NotReady] if the region is not allocated yet.
+    fn check(&self, offset: usize, len: usize) -> Result<Range<usize>, EfiError> {
+        let end = offset.checked_add(len).ok_or(EfiError::InvalidParameter)?;
+        if end > self.size {
+            return Err(EfiError::InvalidParameter);
+        }
+        if self.memory.lock().is_empty() {
+            return Err(EfiError::NotReady);
+        }
+        Ok(offset..end)
+    }
+}
+
+impl NvStorage for RamNvStorage {
+    fn initialize(&self, memory_manager: &dyn MemoryManager) -> Result<(), EfiError> {
+        let mut memory = self.memory.lock();
+        if !memory.is_empty() {
+            return Ok(());
+        }
+        let allocation = memory_manager
+            .allocate_pages(
+                uefi_size_to_pages!(self.size),
+                AllocationOptions::new().with_memory_type(EfiMemoryType::RuntimeServicesData),
+            )
+            .map_err(|_| EfiError::OutOfResources)?
+            .leak_as_slice::<u8>();
+        let allocation = &mut allocation[..self.size];
+        allocation.fill(0xff);
+        *memory = allocation;
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    fn read(&self, offset: usize, buffer: &mut [u8]) -> Result<(), EfiError> {
+        let range = self.check(offset, buffer.len())?;
+        buffer.copy_from_slice(&self.memory.lock()[range]);
+        Ok(())
+    }
+
+    fn write(&self, offset: usize, data: &[u8]) -> Result<(), EfiError> {
+        let range = self.check(offset, data.len())?;
+        self.memory.lock()[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&self, range: Range<usize>) -> Result<(), EfiError> {
+        let range = self.check(range.start, range.len())?;
+        if !range.start.is_multiple_of(self.block_size) || !range.end.is_multiple_of(self.block_size) {
+            return Err(EfiError::InvalidParameter);
+        }
+        self.memory.lock()[range].fill(0xff);
+        Ok(())
+    }
+
+    fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        let mut memory = self.memory.lock();
+        // Memory the OS did not map stays where it was; it cannot be reached either way.
+        if let Some(address) = map.convert(memory.as_mut_ptr() as usize) {
+            let len = memory.len();
+            // SAFETY: The OS mapped the region, which is runtime services memory, at address.
+            *memory = unsafe { slice::from_raw_parts_mut(address as *mut u8, len) };
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use patina::component::service::memory::StdMemoryManager;
+
+    #[test]
+    fn ram_storage_starts_erased() {
+        let storage = RamNvStorage::new(0x2000, 0x1000).unwrap();
+        let mut buffer = [0u8; 0x10];
+        assert_eq!(storage.read(0x1ff0, &mut buffer), Err(EfiError::NotReady));
+        storage.initialize(&StdMemoryManager::new()).unwrap();
+        storage.read(0x1ff0, &mut buffer).unwrap();
+        assert_eq!(buffer, [0xff; 0x10]);
+        assert_eq!(storage.size(), 0x2000);
+        assert_eq!(storage.block_size(), 0x1000);
+    }
+
+    #[test]
+    fn ram_storage_read_write_erase() {
+        let storage = RamNvStorage::from_slice(Box::leak(vec![0u8; 0x2000].into_boxed_slice()), 0x1000).unwrap();
+        storage.write(0x10, &[1, 2, 3]).unwrap();
+        let mut buffer = [0u8; 3];
+        storage.read(0x10, &mut buffer).unwrap();
+        assert_eq!(buffer, [1, 2, 3]);
+
+        assert_eq!(storage.erase(0x10..0x1000), Err(EfiError::InvalidParameter));
+        storage.erase(0..0x1000).unwrap();
+        storage.read(0x10, &mut buffer).unwrap();
+        assert_eq!(buffer, [0xff; 3]);
+    }
+
+    #[test]
+    fn ram_storage_rejects_out_of_bounds() {
+        let storage = RamNvStorage::new(0x1000, 0x1000).unwrap();
+        storage.initialize(&StdMemoryManager::new()).unwrap();
+        assert_eq!(storage.write(0xfff, &[0, 0]), Err(EfiError::InvalidParameter));
+        assert_eq!(storage.read(usize::MAX, &mut [0]), Err(EfiError::InvalidParameter));
+        assert!(RamNvStorage::new(0x1800, 0x1000).is_err());
+    }
+
+    #[test]
+    fn ram_storage_follows_the_virtual_address_map() {
+        let physical: &'static mut [u8] = Box::leak(vec![1u8; 0x1000].into_boxed_slice());
+        let virtual_copy: &'static mut [u8] = Box::leak(vec![2u8; 0x1000].into_boxed_slice());
+        let (from, to) = (physical.as_ptr() as usize, virtual_copy.as_ptr() as usize);
+        let storage = RamNvStorage::from_slice(physical, 0x1000).unwrap();
+
+        storage.convert_pointers(&|address| (address == from).then_some(to));
+        let mut buffer = [0u8; 1];
+        storage.read(0, &mut buffer).unwrap();
+        assert_eq!(buffer, [2]);
+
+        // An address the OS did not map is kept.
+        storage.convert_pointers(&|_| None);
+        storage.read(0, &mut buffer).unwrap();
+        assert_eq!(buffer, [2]);
+    }
+}
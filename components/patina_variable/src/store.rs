@@ -0,0 +1,1228 @@
+//! Variable Store
+//!
+//! Implements the semantics of the UEFI variable services on top of two [Region]s: the non-volatile store on the
+//! variable flash region and the volatile store in memory. All buffers are allocated up front from runtime memory so
+//! that no allocation is needed to service a request, and all state is protected by a spin lock so the store remains
+//! usable once boot services are gone. For the same reason, only construction logs: requests may arrive after the
+//! logger is gone.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+mod auth;
+pub(crate) mod format;
+mod region;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use patina::{
+    component::service::memory::{AllocationOptions, MemoryManager},
+    efi_types::EfiMemoryType,
+    runtime_services::{
+        RuntimeServices, VirtualAddressMap,
+        variable_services::{GetVariableStatus, VariableInfo},
+    },
+    uefi_size_to_pages,
+};
+use r_efi::efi;
+use zerocopy::{FromBytes, IntoBytes};
+
+use crate::{
+    config::VariableConfig,
+    error::VariableError,
+    service::{NvStorage, VariableCrypto},
+};
+use format::{ERASED, FV_HEADER_LENGTH, Format, StoreHeader, Timestamp, VARIABLE_START_ID, VariableHeader};
+use region::{Located, NewVariable, Region};
+
+/// Attributes that may be passed to `SetVariable()`.
+const SETTABLE_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE
+    | efi::VARIABLE_BOOTSERVICE_ACCESS
+    | efi::VARIABLE_RUNTIME_ACCESS
+    | efi::VARIABLE_HARDWARE_ERROR_RECORD
+    | efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS
+    | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS
+    | efi::VARIABLE_APPEND_WRITE
+    | efi::VARIABLE_ENHANCED_AUTHENTICATED_ACCESS;
+
+/// Attributes a hardware error record must carry.
+const HARDWARE_ERROR_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE
+    | efi::VARIABLE_BOOTSERVICE_ACCESS
+    | efi::VARIABLE_RUNTIME_ACCESS
+    | efi::VARIABLE_HARDWARE_ERROR_RECORD;
+
+/// Vendor GUID of hardware error record variables (`gEfiHardwareErrorVariableGuid`).
+const HARDWARE_ERROR_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x414e6bdd, 0xe47b, 0x47cc, 0xb2, 0x44, &[0xbb, 0x61, 0x02, 0x0c, 0xf5, 0x16]);
+
+/// Required name prefix of hardware error record variables.
+const HARDWARE_ERROR_NAME_PREFIX: &[u8] = b"HwErrRec";
+
+/// Returns the null-terminated prefix of `name`, or `None` if there is no terminator.
+fn terminated(name: &[u16]) -> Option<&[u16]> {
+    name.iter().position(|c| *c == 0).map(|len| &name[..=len])
+}
+
+fn is_hardware_error_record(name: &[u16], guid: &efi::Guid) -> bool {
+    *guid == HARDWARE_ERROR_VARIABLE_GUID
+        && name.len() > HARDWARE_ERROR_NAME_PREFIX.len()
+        && name.iter().zip(HARDWARE_ERROR_NAME_PREFIX).all(|(c, p)| *c == *p as u16)
+}
+
+/// Moves `slice` to its virtual address in `map`, keeping it where it is if the OS did not map it.
+fn convert_slice(slice: &mut &'static mut [u8], map: &dyn VirtualAddressMap) {
+    if let Some(address) = map.convert(slice.as_mut_ptr() as usize) {
+        let len = slice.len();
+        // SAFETY: The slice is runtime services memory, which the OS mapped at address.
+        *slice = unsafe { core::slice::from_raw_parts_mut(address as *mut u8, len) };
+    }
+}
+
+/// Moves `reference` to its virtual address in `map`, keeping it where it is if the OS did not map it.
+///
+/// Only the data pointer is converted. The vtable of a trait object stays in the image that defined it.
+fn convert_ref<T: ?Sized>(reference: &mut &'static T, map: &dyn VirtualAddressMap) {
+    let pointer: *const T = *reference;
+    if let Some(address) = map.convert(pointer.addr()) {
+        // SAFETY: The referenced object is runtime services memory, which the OS mapped at address.
+        *reference = unsafe { &*pointer.with_addr(address) };
+    }
+}
+
+/// Mutable state of the store.
+struct Inner {
+    nv: Region,
+    volatile: Region,
+    /// Holds the compacted image of a region during reclaim.
+    reclaim_scratch: &'static mut [u8],
+    /// Holds the combined data of an append write.
+    data_scratch: &'static mut [u8],
+    /// Processes authenticated writes once [VariableStore::enable_authentication] has been called.
+    auth: Option<auth::Authenticator>,
+}
+
+/// A validated write to apply to the store.
+struct Write<'a> {
+    /// Null-terminated name.
+    name: &'a [u16],
+    guid: &'a efi::Guid,
+    /// Attributes as passed to `SetVariable()`, including `EFI_VARIABLE_APPEND_WRITE`.
+    attributes: u32,
+    data: &'a [u8],
+    /// Recorded in the variable header. Only authenticated variables carry a nonzero value.
+    time_stamp: Timestamp,
+    /// Set for writes the store makes on its own behalf, which are not subject to the runtime access rules.
+    internal: bool,
+}
+
+impl<'a> Write<'a> {
+    fn new(name: &'a [u16], guid: &'a efi::Guid, attributes: u32, data: &'a [u8]) -> Self {
+        Self { name, guid, attributes, data, time_stamp: Timestamp::default(), internal: false }
+    }
+}
+
+/// A region and a variable located in it.
+#[derive(Clone, Copy)]
+enum Found {
+    Nv(Located),
+    Volatile(Located),
+}
+
+impl Found {
+    fn located(&self) -> &Located {
+        match self {
+            Found::Nv(l) | Found::Volatile(l) => l,
+        }
+    }
+}
+
+impl Inner {
+    fn find(&self, name: &[u16], guid: &efi::Guid) -> Option<Found> {
+        self.nv
+            .find_by_name(name, guid)
+            .map(Found::Nv)
+            .or_else(|| self.volatile.find_by_name(name, guid).map(Found::Volatile))
+    }
+
+    fn region(&self, found: &Found) -> &Region {
+        match found {
+            Found::Nv(_) => &self.nv,
+            Found::Volatile(_) => &self.volatile,
+        }
+    }
+
+    fn region_mut(&mut self, found: &Found) -> &mut Region {
+        match found {
+            Found::Nv(_) => &mut self.nv,
+            Found::Volatile(_) => &mut self.volatile,
+        }
+    }
+}
+
+/// A UEFI variable store.
+///
+/// Produced by the [VariableServiceProvider](crate::component::VariableServiceProvider) component, which routes the
+/// variable entries of the runtime services table to it. It can also be constructed directly over any [NvStorage],
+/// which is how host based tests exercise it through the [RuntimeServices] trait:
+///
+/// ```rust
+/// use patina::{component::service::memory::StdMemoryManager, runtime_services::RuntimeServices};
+/// use patina_variable::{config::VariableConfig, service::{RamNvStorage, VariableStore}};
+/// use r_efi::efi;
+///
+/// let memory_manager = StdMemoryManager::new();
+/// let nv = Box::leak(Box::new(RamNvStorage::new(0x10000, 0x1000).unwrap()));
+/// let store = VariableStore::new(&VariableConfig::default(), nv, &memory_manager).unwrap();
+///
+/// const VENDOR: efi::Guid = efi::Guid::from_fields(0x1234, 0x5678, 0x9abc, 0xde, 0xf0, &[1, 2, 3, 4, 5, 6]);
+/// let name = [b'B' as u16, b'o' as u16, b'o' as u16, b't' as u16, 0];
+/// let attributes = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+/// store.set_variable(&name, &VENDOR, attributes, &vec![1u8, 2, 3]).unwrap();
+///
+/// let (data, read_attributes) = store.get_variable::<Vec<u8>>(&name, &VENDOR, None).unwrap();
+/// assert_eq!(data, [1, 2, 3]);
+/// assert_eq!(read_attributes, attributes);
+/// ```
+pub struct VariableStore {
+    inner: spin::Mutex<Inner>,
+    config: VariableConfig,
+    at_runtime: AtomicBool,
+}
+
+impl VariableStore {
+    /// Creates a store over `nv`, formatting the region if it is fully erased.
+    ///
+    /// All memory the store needs is allocated here as `EfiRuntimeServicesData`, after `nv` is initialized.
+    pub fn new(
+        config: &VariableConfig,
+        nv: &'static dyn NvStorage,
+        memory_manager: &dyn MemoryManager,
+    ) -> Result<Self, VariableError> {
+        nv.initialize(memory_manager).map_err(VariableError::Device)?;
+        let nv_size = nv.size();
+        if nv_size < FV_HEADER_LENGTH + StoreHeader::SIZE + config.hardware_error_storage_size
+            || config.volatile_store_size < StoreHeader::SIZE
+        {
+            log::error!(target: "variable", "Variable store configuration does not fit the NV region ({nv_size:#x} bytes).");
+            return Err(VariableError::InvalidParameter);
+        }
+
+        let allocate = |size| Self::allocate(memory_manager, size);
+
+        let nv_buffer = allocate(nv_size)?;
+        if let Some(spare) = nv.spare() {
+            if spare.size() < nv_size || spare.block_size() == 0 || !nv_size.is_multiple_of(spare.block_size()) {
+                log::error!(target: "variable", "Variable store spare region does not fit the NV region.");
+                return Err(VariableError::InvalidParameter);
+            }
+            Self::recover(nv, spare, nv_buffer)?;
+        }
+        nv.read(0, nv_buffer).map_err(VariableError::Device)?;
+        let (base, format) = Self::prepare_nv(nv, nv_buffer)?;
+
+        let mut inner = Inner {
+            nv: Region::new_nv(nv_buffer, nv, base, format),
+            volatile: Region::new_volatile(allocate(config.volatile_store_size)?, format),
+            reclaim_scratch: allocate(nv_size.max(config.volatile_store_size))?,
+            data_scratch: allocate(config.max_variable_size.max(config.max_hardware_error_variable_size))?,
+            auth: None,
+        };
+
+        if inner.nv.needs_reclaim() {
+            log::warn!(target: "variable", "Variable store has an interrupted write, reclaiming.");
+            let Inner { nv, reclaim_scratch, .. } = &mut inner;
+            nv.reclaim(reclaim_scratch, None, None)?;
+        }
+
+        log::info!(
+            target: "variable",
+            "Variable store initialized: {} variables, {:#x} bytes free.",
+            inner.nv.iter_valid().count(),
+            inner.nv.free_space()
+        );
+
+        Ok(Self { inner: spin::Mutex::new(inner), config: *config, at_runtime: AtomicBool::new(false) })
+    }
+
+    fn allocate(memory_manager: &dyn MemoryManager, size: usize) -> Result<&'static mut [u8], VariableError> {
+        let slice = memory_manager
+            .allocate_pages(
+                uefi_size_to_pages!(size),
+                AllocationOptions::new().with_memory_type(EfiMemoryType::RuntimeServicesData),
+            )
+            .map_err(|_| VariableError::OutOfResources)?
+            .leak_as_slice::<u8>();
+        Ok(&mut slice[..size])
+    }
+
+    /// Enables time based authenticated writes, with signatures verified by `crypto`.
+    ///
+    /// This also activates the Secure Boot policy for the `PK`, `KEK`, `db`, `dbx`, `dbt` and `dbr` variables and
+    /// publishes the mode variables (`SetupMode`, `SecureBoot`, `DeployedMode`, `AuditMode` and
+    /// `SignatureSupport`). The store must use the authenticated record format, which is the format of every store
+    /// formatted by this crate.
+    pub fn enable_authentication(
+        &self,
+        crypto: &'static dyn VariableCrypto,
+        memory_manager: &dyn MemoryManager,
+    ) -> Result<(), VariableError> {
+        let mut inner = self.inner.lock();
+        if inner.nv.format() != Format::Authenticated {
+            log::error!(target: "variable", "Authenticated variables need an authenticated variable store.");
+            return Err(VariableError::Unsupported);
+        }
+
+        let scratch = Self::allocate(memory_manager, auth::Authenticator::scratch_size(&self.config))?;
+        let auth = auth::Authenticator::new(crypto, scratch, &inner);
+        auth.publish_mode(self, &mut inner)?;
+        log::info!(target: "variable", "Authenticated variables enabled in {:?} mode.", auth.mode());
+        inner.auth = Some(auth);
+        Ok(())
+    }
+
+    /// Completes a reclaim that was interrupted after its compacted image was staged in `spare`, using `image` as the
+    /// buffer for the copy.
+    ///
+    /// A spare holding a valid firmware volume was fully programmed, so it supersedes whatever the region holds.
+    fn recover(nv: &dyn NvStorage, spare: &dyn NvStorage, image: &mut [u8]) -> Result<(), VariableError> {
+        spare.read(0, image).map_err(VariableError::Device)?;
+        let Some(base) = format::parse_fv_header(image) else {
+            return Ok(());
+        };
+        log::warn!(target: "variable", "Variable store reclaim was interrupted, restoring the staged copy.");
+        nv.erase(0..image.len()).map_err(VariableError::Device)?;
+        region::program(nv, image, base)?;
+        spare.erase(0..image.len()).map_err(VariableError::Device)
+    }
+
+    /// Validates (or formats) the firmware volume and store headers in `image`, returning the offset of the store
+    /// header and the record format.
+    fn prepare_nv(nv: &dyn NvStorage, image: &mut [u8]) -> Result<(usize, Format), VariableError> {
+        let base = match format::parse_fv_header(image) {
+            Some(length) => length,
+            None if image.iter().all(|b| *b == ERASED) => {
+                log::info!(target: "variable", "Formatting erased variable store.");
+                let header = format::fv_header(image.len(), nv.block_size());
+                image[..FV_HEADER_LENGTH].copy_from_slice(&header);
+                nv.write(0, &header).map_err(VariableError::Device)?;
+                FV_HEADER_LENGTH
+            }
+            None => {
+                log::error!(target: "variable", "Variable store region does not contain a valid firmware volume.");
+                return Err(VariableError::VolumeCorrupted);
+            }
+        };
+
+        let store_size = image.len() - base;
+        let header = StoreHeader::read_from_prefix(&image[base..]).map_err(|_| VariableError::VolumeCorrupted)?.0;
+        if header.is_erased() {
+            let header = StoreHeader::new(Format::Authenticated, store_size);
+            image[base..base + size_of::<StoreHeader>()].copy_from_slice(header.as_bytes());
+            nv.write(base, header.as_bytes()).map_err(VariableError::Device)?;
+            return Ok((base, Format::Authenticated));
+        }
+
+        match header.format() {
+            Some(format)
+                if header.format == format::STORE_FORMATTED
+                    && header.state == format::STORE_HEALTHY
+                    && header.size as usize == store_size =>
+            {
+                Ok((base, format))
+            }
+            _ => {
+                log::error!(target: "variable", "Variable store header is invalid.");
+                Err(VariableError::VolumeCorrupted)
+            }
+        }
+    }
+
+    /// Switches the store to runtime mode. Only variables with runtime access remain visible afterwards.
+    pub fn exit_boot_services(&self) {
+        self.at_runtime.store(true, Ordering::SeqCst);
+    }
+
+    /// Moves the buffers and backends of the store to the virtual addresses of `map`, while the OS calls
+    /// `SetVirtualAddressMap()`.
+    pub fn convert_pointers(&self, map: &dyn VirtualAddressMap) {
+        let mut inner = self.inner.lock();
+        let Inner { nv, volatile, reclaim_scratch, data_scratch, auth } = &mut *inner;
+        nv.convert_pointers(map);
+        volatile.convert_pointers(map);
+        convert_slice(reclaim_scratch, map);
+        convert_slice(data_scratch, map);
+        if let Some(auth) = auth {
+            auth.convert_pointers(map);
+        }
+    }
+
+    pub(crate) fn at_runtime(&self) -> bool {
+        self.at_runtime.load(Ordering::SeqCst)
+    }
+
+    /// Upper bound on the size in bytes of any variable name the store can hold.
+    pub(crate) fn max_name_size(&self) -> usize {
+        self.config.max_variable_size.max(self.config.max_hardware_error_variable_size)
+    }
+
+    fn visible(&self, attributes: u32) -> bool {
+        !self.at_runtime() || attributes & efi::VARIABLE_RUNTIME_ACCESS != 0
+    }
+
+    /// `GetVariable()`
+    ///
+    /// `name` must be null-terminated. Copies the data into `data` and returns the data size and attributes.
+    pub(crate) fn get(
+        &self,
+        name: &[u16],
+        guid: &efi::Guid,
+        data: &mut [u8],
+    ) -> Result<(usize, u32), (VariableError, u32)> {
+        let name = terminated(name).ok_or((VariableError::InvalidParameter, 0))?;
+        if name.len() == 1 {
+            return Err((VariableError::NotFound, 0));
+        }
+
+        let inner = self.inner.lock();
+        let found = inner.find(name, guid).ok_or((VariableError::NotFound, 0))?;
+        let variable = found.located();
+        let attributes = variable.header.attributes;
+        if !self.visible(attributes) {
+            return Err((VariableError::NotFound, 0));
+        }
+
+        let stored = inner.region(&found).data(variable);
+        if data.len() < stored.len() {
+            return Err((VariableError::BufferTooSmall(stored.len()), attributes));
+        }
+        data[..stored.len()].copy_from_slice(stored);
+        Ok((stored.len(), attributes))
+    }
+
+    /// `GetNextVariableName()`
+    ///
+    /// On entry `name` holds the null-terminated previous name (an empty name starts the enumeration) and `guid` its
+    /// vendor GUID. On success both are replaced by the next variable and the name size in bytes is returned. The
+    /// buffer is left untouched on error.
+    pub(crate) fn next_name(&self, name: &mut [u16], guid: &mut efi::Guid) -> Result<usize, VariableError> {
+        let prev_name = terminated(name).ok_or(VariableError::InvalidParameter)?;
+        let inner = self.inner.lock();
+
+        // Regions are enumerated non-volatile first, then volatile.
+        let regions = [&inner.nv, &inner.volatile];
+        let (mut region_index, mut after) = if prev_name.len() == 1 {
+            (0, None)
+        } else {
+            match inner.find(prev_name, guid).ok_or(VariableError::InvalidParameter)? {
+                Found::Nv(l) => (0, Some(l.offset)),
+                Found::Volatile(l) => (1, Some(l.offset)),
+            }
+        };
+
+        while region_index < regions.len() {
+            let region = regions[region_index];
+            let next = region
+                .iter_valid()
+                .filter(|v| after.is_none_or(|offset| v.offset > offset))
+                .find(|v| self.visible(v.header.attributes));
+
+            if let Some(variable) = next {
+                let stored = region.name_bytes(&variable);
+                if name.len() < stored.len() / 2 {
+                    return Err(VariableError::BufferTooSmall(stored.len()));
+                }
+                for (c, bytes) in name.iter_mut().zip(stored.chunks_exact(2)) {
+                    *c = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+                *guid = variable.header.vendor_guid;
+                return Ok(stored.len());
+            }
+
+            region_index += 1;
+            after = None;
+        }
+        Err(VariableError::NotFound)
+    }
+
+    /// `SetVariable()`
+    ///
+    /// `name` must be null-terminated.
+    pub(crate) fn set(
+        &self,
+        name: &[u16],
+        guid: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), VariableError> {
+        let name = terminated(name).ok_or(VariableError::InvalidParameter)?;
+        if name.len() == 1 {
+            return Err(VariableError::InvalidParameter);
+        }
+        self.check_set_attributes(name, guid, attributes, data.len())?;
+
+        let mut inner = self.inner.lock();
+        // The authenticator is taken out of the state while it runs so it can update the regions it inspects.
+        match inner.auth.take() {
+            Some(mut auth) => {
+                let result = auth.set(self, &mut inner, name, guid, attributes, data);
+                inner.auth = Some(auth);
+                result
+            }
+            None if attributes & efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS != 0 => {
+                Err(VariableError::InvalidParameter)
+            }
+            None => self.write(&mut inner, &Write::new(name, guid, attributes, data)),
+        }
+    }
+
+    /// Applies a write that has passed any authentication, following the update, append and delete rules of
+    /// `SetVariable()`.
+    fn write(&self, inner: &mut Inner, write: &Write) -> Result<(), VariableError> {
+        let Write { name, guid, data, time_stamp, internal, .. } = *write;
+        let append = write.attributes & efi::VARIABLE_APPEND_WRITE != 0;
+        let attributes = write.attributes & !efi::VARIABLE_APPEND_WRITE;
+        let delete = (data.is_empty() && !append)
+            || attributes & (efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS) == 0;
+        let at_runtime = self.at_runtime() && !internal;
+
+        let existing = inner.find(name, guid);
+
+        if let Some(found) = &existing {
+            let current = found.located().header.attributes;
+            if at_runtime {
+                if current & efi::VARIABLE_RUNTIME_ACCESS == 0 {
+                    return Err(VariableError::InvalidParameter);
+                }
+                if current & efi::VARIABLE_NON_VOLATILE == 0 {
+                    return Err(VariableError::WriteProtected);
+                }
+            }
+            if !delete && current != attributes {
+                return Err(VariableError::InvalidParameter);
+            }
+        } else if at_runtime
+            && !delete
+            && attributes & (efi::VARIABLE_RUNTIME_ACCESS | efi::VARIABLE_NON_VOLATILE)
+                != (efi::VARIABLE_RUNTIME_ACCESS | efi::VARIABLE_NON_VOLATILE)
+        {
+            return Err(VariableError::InvalidParameter);
+        }
+
+        if delete {
+            let found = existing.ok_or(VariableError::NotFound)?;
+            return self.delete(inner, &found, name, guid);
+        }
+
+        let Inner { nv, volatile, reclaim_scratch, data_scratch, .. } = inner;
+        let existing = existing.map(|found| match found {
+            Found::Nv(located) => (&*nv, located),
+            Found::Volatile(located) => (&*volatile, located),
+        });
+
+        let new_data: &[u8] = match &existing {
+            Some((region, located)) if append => {
+                let current = region.data(located);
+                let total = current.len() + data.len();
+                if total > data_scratch.len() {
+                    return Err(VariableError::InvalidParameter);
+                }
+                data_scratch[..current.len()].copy_from_slice(current);
+                data_scratch[current.len()..total].copy_from_slice(data);
+                &data_scratch[..total]
+            }
+            _ => data,
+        };
+
+        if let Some((region, located)) = &existing
+            && region.data(located) == new_data
+            && located.header.time_stamp == time_stamp
+        {
+            return Ok(());
+        }
+
+        let format = nv.format();
+        let hardware_error = attributes & efi::VARIABLE_HARDWARE_ERROR_RECORD != 0;
+        let max_size =
+            if hardware_error { self.config.max_hardware_error_variable_size } else { self.config.max_variable_size };
+        if format.header_size() + name.len() * 2 + new_data.len() > max_size {
+            return Err(VariableError::InvalidParameter);
+        }
+
+        let variable = NewVariable {
+            header: VariableHeader {
+                start_id: VARIABLE_START_ID,
+                state: ERASED,
+                attributes,
+                monotonic_count: 0,
+                time_stamp,
+                pub_key_index: 0,
+                name_size: 0,
+                data_size: 0,
+                vendor_guid: *guid,
+            },
+            name,
+            data: new_data,
+        };
+
+        let old = existing.map(|(_, located)| located);
+        let region = if attributes & efi::VARIABLE_NON_VOLATILE != 0 { nv } else { volatile };
+        if region.is_nv() {
+            let (used, capacity) = Self::usage(region, &self.config, hardware_error);
+            let freed = old.map_or(0, |o| o.header.total_size(format));
+            if used - freed + region.space_for(&variable) > capacity {
+                return Err(VariableError::OutOfResources);
+            }
+        }
+
+        Self::write_variable(region, reclaim_scratch, old, &variable)
+    }
+
+    /// Checks the attribute and size rules of `SetVariable()` that do not depend on the store contents.
+    fn check_set_attributes(
+        &self,
+        name: &[u16],
+        guid: &efi::Guid,
+        attributes: u32,
+        data_size: usize,
+    ) -> Result<(), VariableError> {
+        if attributes & !SETTABLE_ATTRIBUTES != 0 {
+            return Err(VariableError::InvalidParameter);
+        }
+        if attributes & (efi::VARIABLE_RUNTIME_ACCESS | efi::VARIABLE_BOOTSERVICE_ACCESS)
+            == efi::VARIABLE_RUNTIME_ACCESS
+        {
+            return Err(VariableError::InvalidParameter);
+        }
+        if attributes & (efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS | efi::VARIABLE_ENHANCED_AUTHENTICATED_ACCESS) != 0 {
+            return Err(VariableError::Unsupported);
+        }
+
+        if attributes & efi::VARIABLE_HARDWARE_ERROR_RECORD != 0 {
+            if self.config.hardware_error_storage_size == 0 {
+                return Err(VariableError::Unsupported);
+            }
+            if attributes & HARDWARE_ERROR_ATTRIBUTES != HARDWARE_ERROR_ATTRIBUTES
+                || !is_hardware_error_record(name, guid)
+            {
+                return Err(VariableError::InvalidParameter);
+            }
+        }
+
+        let max_size = if attributes & efi::VARIABLE_HARDWARE_ERROR_RECORD != 0 {
+            self.config.max_hardware_error_variable_size
+        } else {
+            self.config.max_variable_size
+        };
+        // Authenticated writes carry a descriptor ahead of the data, so their payload is checked once it is split off.
+        if data_size > max_size && attributes & efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS == 0 {
+            return Err(VariableError::InvalidParameter);
+        }
+        Ok(())
+    }
+
+    fn delete(&self, inner: &mut Inner, found: &Found, name: &[u16], guid: &efi::Guid) -> Result<(), VariableError> {
+        let region = inner.region_mut(found);
+        region.delete(found.located())?;
+        // An interrupted update can leave an older copy in deleted transition behind the current one.
+        while let Some(stale) = region.find_by_name(name, guid) {
+            region.delete(&stale)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `variable` into `region`, replacing `old` (if any) and reclaiming space if needed.
+    fn write_variable(
+        region: &mut Region,
+        scratch: &mut [u8],
+        old: Option<Located>,
+        variable: &NewVariable,
+    ) -> Result<(), VariableError> {
+        if region.space_for(variable) > region.free_space() {
+            // The old copy is dropped by the reclaim and the new one added in the same pass.
+            region.reclaim(scratch, old.map(|o| o.offset), Some(variable))?;
+            return Ok(());
+        }
+
+        if let Some(old) = &old {
+            region.set_state(old.offset, format::state::IN_DELETED_TRANSITION)?;
+        }
+        region.append(variable)?;
+        if let Some(old) = &old {
+            region.delete(old)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the space used by valid variables and the capacity for the class of variable selected by
+    /// `hardware_error`. The volatile region has a single class.
+    fn usage(region: &Region, config: &VariableConfig, hardware_error: bool) -> (usize, usize) {
+        let format = region.format();
+        let store_capacity = region.store_size() - StoreHeader::SIZE;
+        if !region.is_nv() {
+            let used = region.iter_valid().map(|v| v.header.total_size(format)).sum();
+            return (used, store_capacity);
+        }
+
+        let used = region
+            .iter_valid()
+            .filter(|v| (v.header.attributes & efi::VARIABLE_HARDWARE_ERROR_RECORD != 0) == hardware_error)
+            .map(|v| v.header.total_size(format))
+            .sum();
+        let capacity = if hardware_error {
+            config.hardware_error_storage_size
+        } else {
+            store_capacity - config.hardware_error_storage_size
+        };
+        (used, capacity)
+    }
+
+    /// `QueryVariableInfo()`
+    pub(crate) fn query(&self, attributes: u32) -> Result<VariableInfo, VariableError> {
+        if attributes == 0 || attributes & !SETTABLE_ATTRIBUTES != 0 || attributes & efi::VARIABLE_APPEND_WRITE != 0 {
+            return Err(VariableError::InvalidParameter);
+        }
+        if attributes & (efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS | efi::VARIABLE_ENHANCED_AUTHENTICATED_ACCESS) != 0 {
+            return Err(VariableError::Unsupported);
+        }
+        if attributes & (efi::VARIABLE_RUNTIME_ACCESS | efi::VARIABLE_BOOTSERVICE_ACCESS)
+            == efi::VARIABLE_RUNTIME_ACCESS
+        {
+            return Err(VariableError::InvalidParameter);
+        }
+        if self.at_runtime() && attributes & efi::VARIABLE_RUNTIME_ACCESS == 0 {
+            return Err(VariableError::InvalidParameter);
+        }
+
+        let hardware_error = attributes & efi::VARIABLE_HARDWARE_ERROR_RECORD != 0;
+        if hardware_error {
+            if attributes & HARDWARE_ERROR_ATTRIBUTES != HARDWARE_ERROR_ATTRIBUTES {
+                return Err(VariableError::InvalidParameter);
+            }
+            if self.config.hardware_error_storage_size == 0 {
+                return Err(VariableError::Unsupported);
+            }
+        }
+
+        let inner = self.inner.lock();
+        let region = if attributes & efi::VARIABLE_NON_VOLATILE != 0 { &inner.nv } else { &inner.volatile };
+        let header_size = region.format().header_size();
+        let (used, capacity) = Self::usage(region, &self.config, hardware_error);
+        let remaining = capacity.saturating_sub(used);
+
+        let max_size =
+            if hardware_error { self.config.max_hardware_error_variable_size } else { self.config.max_variable_size };
+        let maximum_variable_size =
+            if remaining < header_size { 0 } else { (max_size - header_size).min(remaining - header_size) };
+
+        Ok(VariableInfo {
+            maximum_variable_storage_size: capacity as u64,
+            remaining_variable_storage_size: remaining as u64,
+            maximum_variable_size: maximum_variable_size as u64,
+        })
+    }
+}
+
+impl RuntimeServices for VariableStore {
+    fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, efi::Status> {
+        self.query(attributes).map_err(efi::Status::from)
+    }
+
+    unsafe fn set_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), efi::Status> {
+        self.set(name, namespace, attributes, data).map_err(efi::Status::from)
+    }
+
+    unsafe fn get_variable_unchecked(
+        &self,
+        name: &mut [u16],
+        namespace: &efi::Guid,
+        data: Option<&mut [u8]>,
+    ) -> GetVariableStatus {
+        match self.get(name, namespace, data.unwrap_or_default()) {
+            Ok((data_size, attributes)) => GetVariableStatus::Success { data_size, attributes },
+            Err((VariableError::BufferTooSmall(data_size), attributes)) => {
+                GetVariableStatus::BufferTooSmall { data_size, attributes }
+            }
+            Err((err, _)) => GetVariableStatus::Error(err.into()),
+        }
+    }
+
+    unsafe fn get_next_variable_name_unchecked(
+        &self,
+        prev_name: &[u16],
+        prev_namespace: &efi::Guid,
+        next_name: &mut Vec<u16>,
+        next_namespace: &mut efi::Guid,
+    ) -> Result<(), efi::Status> {
+        next_name.clear();
+        next_name.extend_from_slice(prev_name);
+        *next_namespace = *prev_namespace;
+        loop {
+            match self.next_name(next_name, next_namespace) {
+                Ok(size) => {
+                    next_name.truncate(size / 2);
+                    return Ok(());
+                }
+                Err(VariableError::BufferTooSmall(size)) => next_name.resize(size / 2, 0),
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::service::RamNvStorage;
+    use core::sync::atomic::AtomicUsize;
+    use fallible_streaming_iterator::FallibleStreamingIterator;
+    use patina::{
+        component::service::memory::StdMemoryManager, error::EfiError,
+        runtime_services::variable_services::VariableNameIterator,
+    };
+
+    const NV_BS: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+    const NV_BS_RT: u32 = NV_BS | efi::VARIABLE_RUNTIME_ACCESS;
+    const BS: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS;
+    const GUID: efi::Guid = efi::Guid::from_fields(0x1234, 0x5678, 0x9abc, 0xde, 0xf0, &[1, 2, 3, 4, 5, 6]);
+
+    fn name(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(core::iter::once(0)).collect()
+    }
+
+    fn nv_storage(size: usize) -> &'static RamNvStorage {
+        let nv = Box::leak(Box::new(RamNvStorage::new(size, 0x1000).unwrap()));
+        nv.initialize(&StdMemoryManager::new()).unwrap();
+        nv
+    }
+
+    fn store_with(config: VariableConfig, nv: &'static RamNvStorage) -> VariableStore {
+        VariableStore::new(&config, nv, &StdMemoryManager::new()).unwrap()
+    }
+
+    fn store() -> VariableStore {
+        store_with(VariableConfig::default(), nv_storage(0x10000))
+    }
+
+    fn get(store: &VariableStore, n: &str) -> Result<(Vec<u8>, u32), efi::Status> {
+        store.get_variable::<Vec<u8>>(&name(n), &GUID, None)
+    }
+
+    #[test]
+    fn set_get_and_delete() {
+        let store = store();
+        store.set_variable(&name("Var"), &GUID, NV_BS, &vec![1u8, 2, 3]).unwrap();
+        assert_eq!(get(&store, "Var"), Ok((vec![1, 2, 3], NV_BS)));
+        assert_eq!(store.get_variable_size_and_attributes(&name("Var"), &GUID), Ok((3, NV_BS)));
+
+        store.set_variable(&name("Var"), &GUID, NV_BS, &vec![4u8; 10]).unwrap();
+        assert_eq!(get(&store, "Var"), Ok((vec![4; 10], NV_BS)));
+
+        store.set_variable(&name("Var"), &GUID, NV_BS, &Vec::<u8>::new()).unwrap();
+        assert_eq!(get(&store, "Var"), Err(efi::Status::NOT_FOUND));
+        assert_eq!(store.set_variable(&name("Var"), &GUID, NV_BS, &Vec::<u8>::new()), Err(efi::Status::NOT_FOUND));
+    }
+
+    #[test]
+    fn nv_variables_persist_across_instances() {
+        let nv = nv_storage(0x10000);
+        let first = store_with(VariableConfig::default(), nv);
+        first.set_variable(&name("Persist"), &GUID, NV_BS, &vec![7u8; 5]).unwrap();
+        first.set_variable(&name("Volatile"), &GUID, BS, &vec![8u8; 5]).unwrap();
+
+        let second = store_with(VariableConfig::default(), nv);
+        assert_eq!(get(&second, "Persist"), Ok((vec![7; 5], NV_BS)));
+        assert_eq!(get(&second, "Volatile"), Err(efi::Status::NOT_FOUND));
+    }
+
+    #[test]
+    fn convert_pointers_moves_every_buffer() {
+        let nv = nv_storage(0x10000);
+        let store = store_with(VariableConfig::default(), nv);
+        store.set_variable(&name("Before"), &GUID, NV_BS_RT, &vec![1u8]).unwrap();
+
+        // Identity mapped, so the store must keep working after every address it holds was converted.
+        let converted = spin::Mutex::new(Vec::new());
+        store.convert_pointers(&|address| {
+            converted.lock().push(address);
+            Some(address)
+        });
+        // The backend, the NV and volatile buffers and both scratch buffers.
+        assert_eq!(converted.lock().len(), 6);
+
+        store.set_variable(&name("After"), &GUID, NV_BS_RT, &vec![2u8]).unwrap();
+        assert_eq!(get(&store, "Before"), Ok((vec![1], NV_BS_RT)));
+        assert_eq!(get(&store, "After"), Ok((vec![2], NV_BS_RT)));
+    }
+
+    #[test]
+    fn interrupted_update_recovers_old_copy() {
+        let nv = nv_storage(0x10000);
+        let first = store_with(VariableConfig::default(), nv);
+        first.set_variable(&name("Var"), &GUID, NV_BS, &vec![1u8]).unwrap();
+
+        // Simulate a reset after the old copy was marked but before the new copy was completed.
+        let old = first.inner.lock().nv.find_by_name(&name("Var"), &GUID).unwrap();
+        first.inner.lock().nv.set_state(old.offset, format::state::IN_DELETED_TRANSITION).unwrap();
+        let end = first.inner.lock().nv.iter().last().unwrap();
+        let garbage_at = end.offset + end.header.total_size(Format::Authenticated);
+        nv.write(garbage_at, &[0xaa, 0x55, 0x7f]).unwrap();
+
+        let second = store_with(VariableConfig::default(), nv);
+        assert_eq!(get(&second, "Var"), Ok((vec![1], NV_BS)));
+        second.set_variable(&name("Var"), &GUID, NV_BS, &vec![2u8]).unwrap();
+        assert_eq!(get(&second, "Var"), Ok((vec![2], NV_BS)));
+        assert_eq!(second.inner.lock().nv.iter_valid().count(), 1);
+    }
+
+    #[test]
+    fn append_write() {
+        let store = store();
+        store.set_variable(&name("List"), &GUID, NV_BS, &vec![1u8, 2]).unwrap();
+        store.set_variable(&name("List"), &GUID, NV_BS | efi::VARIABLE_APPEND_WRITE, &vec![3u8]).unwrap();
+        assert_eq!(get(&store, "List"), Ok((vec![1, 2, 3], NV_BS)));
+
+        // Appending nothing is a no-op rather than a delete.
+        store.set_variable(&name("List"), &GUID, NV_BS | efi::VARIABLE_APPEND_WRITE, &Vec::<u8>::new()).unwrap();
+        assert_eq!(get(&store, "List"), Ok((vec![1, 2, 3], NV_BS)));
+
+        // Appending to a missing variable creates it.
+        store.set_variable(&name("New"), &GUID, BS | efi::VARIABLE_APPEND_WRITE, &vec![9u8]).unwrap();
+        assert_eq!(get(&store, "New"), Ok((vec![9], BS)));
+    }
+
+    #[test]
+    fn attribute_rules() {
+        let store = store();
+        let n = name("Var");
+        let rt_only = efi::VARIABLE_RUNTIME_ACCESS;
+        assert_eq!(store.set_variable(&n, &GUID, rt_only, &vec![1u8]), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(store.set_variable(&n, &GUID, 0x1000 | BS, &vec![1u8]), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(
+            store.set_variable(&n, &GUID, BS | efi::VARIABLE_AUTHENTICATED_WRITE_ACCESS, &vec![1u8]),
+            Err(efi::Status::UNSUPPORTED)
+        );
+        // Without a crypto service there is nothing to verify authenticated writes with.
+        assert_eq!(
+            store.set_variable(&n, &GUID, NV_BS | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS, &vec![1u8]),
+            Err(efi::Status::INVALID_PARAMETER)
+        );
+        assert_eq!(
+            store.set_variable(&n, &GUID, NV_BS_RT | efi::VARIABLE_HARDWARE_ERROR_RECORD, &vec![1u8]),
+            Err(efi::Status::UNSUPPORTED)
+        );
+        assert_eq!(store.set_variable(&[0], &GUID, BS, &vec![1u8]), Err(efi::Status::INVALID_PARAMETER));
+
+        store.set_variable(&n, &GUID, NV_BS, &vec![1u8]).unwrap();
+        assert_eq!(store.set_variable(&n, &GUID, BS, &vec![1u8]), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(store.set_variable(&n, &GUID, NV_BS_RT, &vec![1u8]), Err(efi::Status::INVALID_PARAMETER));
+
+        // Attributes of zero delete the variable.
+        store.set_variable(&n, &GUID, 0, &vec![1u8]).unwrap();
+        assert_eq!(get(&store, "Var"), Err(efi::Status::NOT_FOUND));
+    }
+
+    #[test]
+    fn size_limits() {
+        let store = store();
+        let max = VariableConfig::default().max_variable_size;
+        let too_big = vec![0u8; max];
+        assert_eq!(store.set_variable(&name("Big"), &GUID, NV_BS, &too_big), Err(efi::Status::INVALID_PARAMETER));
+
+        let fits = vec![0u8; max - Format::Authenticated.header_size() - name("Big").len() * 2];
+        store.set_variable(&name("Big"), &GUID, NV_BS, &fits).unwrap();
+    }
+
+    #[test]
+    fn reclaim_when_full() {
+        let nv = nv_storage(0x4000);
+        let store = store_with(VariableConfig::default(), nv);
+        let data = vec![0x5au8; 0x400];
+
+        // Repeated updates of the same variable exhaust the free space and force reclaims.
+        for i in 0..64u8 {
+            let mut data = data.clone();
+            data[0] = i;
+            store.set_variable(&name("Churn"), &GUID, NV_BS, &data).unwrap();
+        }
+        let (read, _) = get(&store, "Churn").unwrap();
+        assert_eq!(read[0], 63);
+
+        // The reclaimed store is also what is on flash.
+        let reopened = store_with(VariableConfig::default(), nv);
+        assert_eq!(get(&reopened, "Churn").unwrap().0[0], 63);
+    }
+
+    /// Storage that loses power once a budget of writes and erases, shared with its spare, runs out.
+    struct PowerLossStorage {
+        ram: &'static RamNvStorage,
+        spare: Option<&'static PowerLossStorage>,
+        budget: &'static AtomicUsize,
+    }
+
+    impl PowerLossStorage {
+        fn new(spare: bool) -> &'static Self {
+            let budget = Box::leak(Box::new(AtomicUsize::new(usize::MAX)));
+            let spare: Option<&'static Self> =
+                spare.then(|| &*Box::leak(Box::new(Self { ram: nv_storage(0x4000), spare: None, budget })));
+            Box::leak(Box::new(Self { ram: nv_storage(0x4000), spare, budget }))
+        }
+
+        fn spend(&self) -> Result<(), EfiError> {
+            self.budget
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |budget| budget.checked_sub(1))
+                .map(|_| ())
+                .map_err(|_| EfiError::DeviceError)
+        }
+    }
+
+    impl NvStorage for PowerLossStorage {
+        fn size(&self) -> usize {
+            self.ram.size()
+        }
+
+        fn block_size(&self) -> usize {
+            self.ram.block_size()
+        }
+
+        fn read(&self, offset: usize, buffer: &mut [u8]) -> Result<(), EfiError> {
+            self.ram.read(offset, buffer)
+        }
+
+        fn write(&self, offset: usize, data: &[u8]) -> Result<(), EfiError> {
+            self.spend()?;
+            self.ram.write(offset, data)
+        }
+
+        fn erase(&self, range: core::ops::Range<usize>) -> Result<(), EfiError> {
+            self.spend()?;
+            self.ram.erase(range)
+        }
+
+        fn spare(&self) -> Option<&'static dyn NvStorage> {
+            self.spare.map(|spare| spare as &'static dyn NvStorage)
+        }
+    }
+
+    /// Reclaims the NV region of a store over `nv` holding a superseded copy of `Keep`, losing power after `budget`
+    /// writes and erases, and returns whether the reclaim completed.
+    fn reclaim_with_power_loss(nv: &'static PowerLossStorage, budget: usize) -> bool {
+        let store = VariableStore::new(&VariableConfig::default(), nv, &StdMemoryManager::new()).unwrap();
+        store.set_variable(&name("Keep"), &GUID, NV_BS, &vec![1u8]).unwrap();
+        store.set_variable(&name("Keep"), &GUID, NV_BS, &vec![2u8]).unwrap();
+
+        nv.budget.store(budget, Ordering::SeqCst);
+        let mut inner = store.inner.lock();
+        let Inner { nv: region, reclaim_scratch, .. } = &mut *inner;
+        let completed = region.reclaim(reclaim_scratch, None, None).is_ok();
+        nv.budget.store(usize::MAX, Ordering::SeqCst);
+        completed
+    }
+
+    #[test]
+    fn reclaim_with_a_spare_survives_power_loss() {
+        // Whichever write or erase the reset interrupts, the next boot sees the variable.
+        for budget in 0.. {
+            let nv = PowerLossStorage::new(true);
+            let completed = reclaim_with_power_loss(nv, budget);
+
+            let reopened = VariableStore::new(&VariableConfig::default(), nv, &StdMemoryManager::new()).unwrap();
+            assert_eq!(get(&reopened, "Keep"), Ok((vec![2], NV_BS)), "power lost after {budget} operations");
+            let mut spare = [0u8; 0x10];
+            nv.spare.unwrap().read(0, &mut spare).unwrap();
+            assert_eq!(spare, [ERASED; 0x10]);
+            if completed {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn reclaim_without_a_spare_reports_a_partial_rewrite() {
+        // Power lost before the erase leaves the store as it was.
+        let nv = PowerLossStorage::new(false);
+        assert!(!reclaim_with_power_loss(nv, 0));
+        let reopened = VariableStore::new(&VariableConfig::default(), nv, &StdMemoryManager::new()).unwrap();
+        assert_eq!(get(&reopened, "Keep"), Ok((vec![2], NV_BS)));
+
+        // Power lost before the volume header is rewritten leaves a corrupted store, which is not reformatted.
+        let nv = PowerLossStorage::new(false);
+        assert!(!reclaim_with_power_loss(nv, 2));
+        assert!(matches!(
+            VariableStore::new(&VariableConfig::default(), nv, &StdMemoryManager::new()),
+            Err(VariableError::VolumeCorrupted)
+        ));
+    }
+
+    #[test]
+    fn out_of_resources() {
+        let store = store_with(VariableConfig::default(), nv_storage(0x2000));
+        let data = vec![0u8; 0x700];
+        let mut stored = 0;
+        let result = loop {
+            match store.set_variable(&name(&format!("V{stored}")), &GUID, NV_BS, &data) {
+                Ok(()) => stored += 1,
+                Err(status) => break status,
+            }
+        };
+        assert_eq!(result, efi::Status::OUT_OF_RESOURCES);
+        assert!(stored > 0);
+
+        // Deleting a variable frees its space for reuse.
+        store.set_variable(&name("V0"), &GUID, NV_BS, &Vec::<u8>::new()).unwrap();
+        store.set_variable(&name("Again"), &GUID, NV_BS, &data).unwrap();
+    }
+
+    #[test]
+    fn query_variable_info_accounting() {
+        let store = store();
+        let before = store.query_variable_info(NV_BS).unwrap();
+        assert_eq!(before.maximum_variable_storage_size, (0x10000 - FV_HEADER_LENGTH - StoreHeader::SIZE) as u64);
+        assert_eq!(before.remaining_variable_storage_size, before.maximum_variable_storage_size);
+        assert_eq!(
+            before.maximum_variable_size,
+            (VariableConfig::default().max_variable_size - Format::Authenticated.header_size()) as u64
+        );
+
+        store.set_variable(&name("Var"), &GUID, NV_BS, &vec![0u8; 100]).unwrap();
+        let after = store.query_variable_info(NV_BS).unwrap();
+        let used = format::header_align(Format::Authenticated.header_size() + name("Var").len() * 2 + 100);
+        assert_eq!(after.remaining_variable_storage_size, before.remaining_variable_storage_size - used as u64);
+
+        // Volatile variables are accounted separately.
+        let volatile = store.query_variable_info(BS).unwrap();
+        assert_eq!(volatile.maximum_variable_storage_size, (0x10000 - StoreHeader::SIZE) as u64);
+        assert_eq!(volatile.remaining_variable_storage_size, volatile.maximum_variable_storage_size);
+
+        assert_eq!(store.query_variable_info(0).unwrap_err(), efi::Status::INVALID_PARAMETER);
+        assert_eq!(
+            store.query_variable_info(efi::VARIABLE_RUNTIME_ACCESS).unwrap_err(),
+            efi::Status::INVALID_PARAMETER
+        );
+    }
+
+    #[test]
+    fn hardware_error_records() {
+        let config = VariableConfig { hardware_error_storage_size: 0x1000, ..Default::default() };
+        let store = store_with(config, nv_storage(0x10000));
+        let hw = NV_BS_RT | efi::VARIABLE_HARDWARE_ERROR_RECORD;
+
+        let info = store.query_variable_info(hw).unwrap();
+        assert_eq!(info.maximum_variable_storage_size, 0x1000);
+
+        assert_eq!(
+            store.set_variable(&name("NotHwErr"), &HARDWARE_ERROR_VARIABLE_GUID, hw, &vec![1u8]),
+            Err(efi::Status::INVALID_PARAMETER)
+        );
+        assert_eq!(
+            store.set_variable(&name("HwErrRec0001"), &HARDWARE_ERROR_VARIABLE_GUID, NV_BS_RT, &vec![1u8; 8]),
+            Ok(())
+        );
+        store.set_variable(&name("HwErrRec0002"), &HARDWARE_ERROR_VARIABLE_GUID, hw, &vec![1u8; 8]).unwrap();
+        assert!(store.query_variable_info(hw).unwrap().remaining_variable_storage_size < 0x1000);
+
+        let common = store.query_variable_info(NV_BS).unwrap();
+        assert_eq!(
+            common.maximum_variable_storage_size,
+            (0x10000 - FV_HEADER_LENGTH - StoreHeader::SIZE - 0x1000) as u64
+        );
+    }
+
+    #[test]
+    fn runtime_visibility() {
+        let store = store();
+        store.set_variable(&name("BootOnly"), &GUID, NV_BS, &vec![1u8]).unwrap();
+        store.set_variable(&name("Runtime"), &GUID, NV_BS_RT, &vec![2u8]).unwrap();
+        store.set_variable(&name("VolatileRt"), &GUID, BS | efi::VARIABLE_RUNTIME_ACCESS, &vec![3u8]).unwrap();
+        store.exit_boot_services();
+
+        assert_eq!(get(&store, "BootOnly"), Err(efi::Status::NOT_FOUND));
+        assert_eq!(get(&store, "Runtime"), Ok((vec![2], NV_BS_RT)));
+
+        let mut names = VariableNameIterator::new_from_first(&store);
+        let mut seen = Vec::new();
+        while let Some(id) = names.next().unwrap() {
+            seen.push(String::from_utf16_lossy(&id.name()[..id.name().len() - 1]));
+        }
+        assert_eq!(seen, ["Runtime", "VolatileRt"]);
+
+        assert_eq!(
+            store.set_variable(&name("Runtime"), &GUID, NV_BS_RT, &vec![4u8]),
+            Ok(()),
+            "NV runtime variables remain writable"
+        );
+        assert_eq!(
+            store.set_variable(&name("VolatileRt"), &GUID, BS | efi::VARIABLE_RUNTIME_ACCESS, &vec![4u8]),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+        assert_eq!(store.set_variable(&name("NewBs"), &GUID, NV_BS, &vec![4u8]), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(store.query_variable_info(NV_BS).unwrap_err(), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn name_iteration() {
+        let store = store();
+        assert_eq!(VariableNameIterator::new_from_first(&store).next().unwrap().map(|_| ()), None);
+
+        let other = efi::Guid::from_fields(9, 9, 9, 9, 9, &[9; 6]);
+        store.set_variable(&name("A"), &GUID, NV_BS, &vec![1u8]).unwrap();
+        store.set_variable(&name("B"), &GUID, BS, &vec![1u8]).unwrap();
+        store.set_variable(&name("ALongerName"), &other, NV_BS, &vec![1u8]).unwrap();
+        store.set_variable(&name("A"), &GUID, NV_BS, &vec![2u8]).unwrap();
+
+        let mut names = VariableNameIterator::new_from_first(&store);
+        let mut seen = Vec::new();
+        while let Some(id) = names.next().unwrap() {
+            seen.push((id.name().to_vec(), *id.namespace()));
+        }
+        assert_eq!(seen, [(name("ALongerName"), other), (name("A"), GUID), (name("B"), GUID)]);
+
+        assert_eq!(store.get_next_variable_name(&name("Missing"), &GUID).unwrap_err(), efi::Status::INVALID_PARAMETER);
+        assert_eq!(store.get_next_variable_name(&name("B"), &GUID).unwrap_err(), efi::Status::NOT_FOUND);
+    }
+
+    #[test]
+    fn get_buffer_too_small() {
+        let store = store();
+        store.set_variable(&name("Var"), &GUID, NV_BS, &vec![1u8; 16]).unwrap();
+        let mut buffer = [0u8; 4];
+        assert_eq!(store.get(&name("Var"), &GUID, &mut buffer), Err((VariableError::BufferTooSmall(16), NV_BS)));
+        assert_eq!(store.get(&[0], &GUID, &mut buffer), Err((VariableError::NotFound, 0)));
+        assert_eq!(store.get(&[b'A' as u16], &GUID, &mut buffer), Err((VariableError::InvalidParameter, 0)));
+    }
+
+    #[test]
+    fn rejects_corrupt_region() {
+        let nv = nv_storage(0x4000);
+        nv.write(0, &[0u8; 16]).unwrap();
+        assert!(matches!(
+            VariableStore::new(&VariableConfig::default(), nv, &StdMemoryManager::new()),
+            Err(VariableError::VolumeCorrupted)
+        ));
+    }
+
+    #[test]
+    fn reads_normal_format_store() {
+        // A store formatted by a non-authenticated C variable driver remains readable and writable.
+        let nv = nv_storage(0x4000);
+        nv.write(0, &format::fv_header(0x4000, 0x1000)).unwrap();
+        let header = StoreHeader::new(Format::Normal, 0x4000 - FV_HEADER_LENGTH);
+        nv.write(FV_HEADER_LENGTH, header.as_bytes()).unwrap();
+
+        let store = store_with(VariableConfig::default(), nv);
+        store.set_variable(&name("Var"), &GUID, NV_BS, &vec![1u8]).unwrap();
+        assert_eq!(store.inner.lock().nv.format(), Format::Normal);
+        let reopened = store_with(VariableConfig::default(), nv);
+        assert_eq!(get(&reopened, "Var"), Ok((vec![1], NV_BS)));
+    }
+}
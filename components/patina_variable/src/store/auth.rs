@@ -0,0 +1,834 @@
+//! Authenticated Variables
+//!
+//! Processes `SetVariable()` requests once authentication is enabled on the store: time based authenticated writes
+//! carrying an `EFI_VARIABLE_AUTHENTICATION_2` descriptor, and the Secure Boot policy for the platform key (`PK`), the
+//! key exchange key (`KEK`) and the image signature databases (`db`, `dbx`, `dbt` and `dbr`).
+//!
+//! The policy follows the UEFI specification:
+//!
+//! - In setup mode (no `PK` enrolled) the policy variables accept any well formed descriptor. Enrolling a `PK` enters
+//!   user mode, and deleting it returns to setup mode.
+//! - In user mode `PK` and `KEK` updates must be signed by the `PK`, and database updates by the `KEK` or the `PK`.
+//! - Deployed mode is entered from user mode by writing 1 to `DeployedMode`, after which the `PK` cannot be deleted.
+//! - Any other time based authenticated variable is private to its first writer: updates must be signed by the same
+//!   certificate, which is recorded in a variable under `EFI_CERT_DB_GUID`.
+//! - Every update must carry a timestamp later than the stored one, except appends, which keep the later of the two.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use patina::runtime_services::{
+    VirtualAddressMap,
+    authenticated_variables::{
+        self as authenticated, Authentication2, GLOBAL_VARIABLE_GUID, IMAGE_SECURITY_DATABASE_GUID, signature_type,
+    },
+};
+use r_efi::efi;
+
+use super::{Inner, VariableStore, Write, format::Timestamp};
+use crate::{config::VariableConfig, error::VariableError, service::VariableCrypto};
+
+/// `EFI_CERT_DB_GUID`: namespace of the records that bind private authenticated variables to their signer.
+const CERT_DB_GUID: efi::Guid =
+    efi::Guid::from_fields(0xd9bee56e, 0x75dc, 0x49d9, 0xb4, 0xd7, &[0xb5, 0x34, 0x21, 0x0f, 0x63, 0x7a]);
+
+/// Namespace of the variables that persist the state of the authenticator across resets.
+const AUTH_STATE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x5f1b2c4e, 0x9a3d, 0x4e27, 0x8c, 0x61, &[0x2d, 0x7e, 0x94, 0xb0, 0x13, 0xa8]);
+
+/// Attributes required of the Secure Boot policy variables.
+const POLICY_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE
+    | efi::VARIABLE_BOOTSERVICE_ACCESS
+    | efi::VARIABLE_RUNTIME_ACCESS
+    | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+
+/// Attributes of the Secure Boot mode variables.
+const MODE_ATTRIBUTES: u32 = efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+
+/// Attributes of the signer records under [CERT_DB_GUID].
+const CERT_DB_ATTRIBUTES: u32 =
+    efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+
+/// Attributes of the variables under [AUTH_STATE_GUID].
+const STATE_ATTRIBUTES: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS;
+
+/// Size of a signer record entry: the vendor GUID of the variable followed by the SHA-256 digest of its signer.
+const SIGNER_ENTRY_SIZE: usize = 16 + 32;
+
+/// Signature types accepted in the image signature databases, published in `SignatureSupport`.
+const SUPPORTED_SIGNATURE_TYPES: [efi::Guid; 9] = [
+    signature_type::SHA1,
+    signature_type::SHA256,
+    signature_type::SHA384,
+    signature_type::SHA512,
+    signature_type::RSA2048,
+    signature_type::X509,
+    signature_type::X509_SHA256,
+    signature_type::X509_SHA384,
+    signature_type::X509_SHA512,
+];
+
+/// Encodes an ASCII string as a null-terminated UCS-2 name. `N` must be one more than the string length.
+const fn ucs2<const N: usize>(s: &str) -> [u16; N] {
+    let bytes = s.as_bytes();
+    assert!(bytes.len() + 1 == N);
+    let mut name = [0u16; N];
+    let mut i = 0;
+    while i < bytes.len() {
+        name[i] = bytes[i] as u16;
+        i += 1;
+    }
+    name
+}
+
+const PK: [u16; 3] = ucs2("PK");
+const KEK: [u16; 4] = ucs2("KEK");
+const SETUP_MODE: [u16; 10] = ucs2("SetupMode");
+const SECURE_BOOT: [u16; 11] = ucs2("SecureBoot");
+const DEPLOYED_MODE: [u16; 13] = ucs2("DeployedMode");
+const AUDIT_MODE: [u16; 10] = ucs2("AuditMode");
+const SIGNATURE_SUPPORT: [u16; 17] = ucs2("SignatureSupport");
+const DB: [u16; 3] = ucs2("db");
+const DBX: [u16; 4] = ucs2("dbx");
+const DBT: [u16; 4] = ucs2("dbt");
+const DBR: [u16; 4] = ucs2("dbr");
+
+/// The Secure Boot mode of the platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Mode {
+    /// No platform key is enrolled.
+    Setup,
+    /// A platform key is enrolled and Secure Boot is enforced.
+    User,
+    /// User mode that can no longer return to setup mode by deleting the platform key.
+    Deployed,
+}
+
+/// How a write to a variable is authorized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Policy {
+    PlatformKey,
+    KeyExchangeKey,
+    ImageDatabase,
+    DeployedMode,
+    /// Maintained by the store and never writable by callers.
+    ReadOnly,
+    /// Any other variable, which is private to its signer if it is authenticated.
+    Private,
+}
+
+impl Policy {
+    fn of(name: &[u16], guid: &efi::Guid) -> Self {
+        if *guid == GLOBAL_VARIABLE_GUID {
+            if name == PK {
+                return Policy::PlatformKey;
+            }
+            if name == KEK {
+                return Policy::KeyExchangeKey;
+            }
+            if name == DEPLOYED_MODE {
+                return Policy::DeployedMode;
+            }
+            if name == SETUP_MODE || name == SECURE_BOOT || name == AUDIT_MODE || name == SIGNATURE_SUPPORT {
+                return Policy::ReadOnly;
+            }
+        } else if *guid == IMAGE_SECURITY_DATABASE_GUID {
+            if name == DB || name == DBX || name == DBT || name == DBR {
+                return Policy::ImageDatabase;
+            }
+        } else if *guid == CERT_DB_GUID || *guid == AUTH_STATE_GUID {
+            return Policy::ReadOnly;
+        }
+        Policy::Private
+    }
+
+    /// True for the variables that hold signature lists and are authorized by the Secure Boot key hierarchy.
+    fn is_secure_boot_database(self) -> bool {
+        matches!(self, Policy::PlatformKey | Policy::KeyExchangeKey | Policy::ImageDatabase)
+    }
+}
+
+fn timestamp(time: &efi::Time) -> Timestamp {
+    Timestamp {
+        year: time.year,
+        month: time.month,
+        day: time.day,
+        hour: time.hour,
+        minute: time.minute,
+        second: time.second,
+        ..Timestamp::default()
+    }
+}
+
+fn is_later(time: &Timestamp, than: &Timestamp) -> bool {
+    (time.year, time.month, time.day, time.hour, time.minute, time.second)
+        > (than.year, than.month, than.day, than.hour, than.minute, than.second)
+}
+
+fn internal<'a>(name: &'a [u16], guid: &'a efi::Guid, attributes: u32, data: &'a [u8]) -> Write<'a> {
+    Write { internal: true, ..Write::new(name, guid, attributes, data) }
+}
+
+/// Checks the signature lists written to a Secure Boot policy variable.
+fn check_signature_lists(policy: Policy, payload: &[u8]) -> Result<(), VariableError> {
+    let mut signatures = 0;
+    for list in authenticated::signature_lists(payload) {
+        let list = list.map_err(|_| VariableError::InvalidParameter)?;
+        let allowed = match policy {
+            Policy::PlatformKey => list.signature_type == signature_type::X509,
+            Policy::KeyExchangeKey => {
+                list.signature_type == signature_type::X509 || list.signature_type == signature_type::RSA2048
+            }
+            _ => SUPPORTED_SIGNATURE_TYPES.contains(&list.signature_type),
+        };
+        if !allowed {
+            return Err(VariableError::InvalidParameter);
+        }
+        signatures += list.signatures().count();
+    }
+
+    // The platform key is a single certificate.
+    if policy == Policy::PlatformKey && signatures != 1 {
+        return Err(VariableError::InvalidParameter);
+    }
+    Ok(())
+}
+
+/// Copies the signature lists in `new` to `out`, dropping signatures already present in `existing`. Returns the size
+/// of the filtered lists.
+fn filter_signature_lists(existing: &[u8], new: &[u8], out: &mut [u8]) -> usize {
+    fn put(out: &mut [u8], size: &mut usize, bytes: &[u8]) {
+        out[*size..*size + bytes.len()].copy_from_slice(bytes);
+        *size += bytes.len();
+    }
+
+    let mut size = 0;
+    for list in authenticated::signature_lists(new).map_while(Result::ok) {
+        let present = |owner: &efi::Guid, data: &[u8]| {
+            authenticated::signature_lists(existing)
+                .map_while(Result::ok)
+                .filter(|l| l.signature_type == list.signature_type && l.signature_size() == list.signature_size())
+                .any(|l| l.signatures().any(|s| s.owner == *owner && s.data == data))
+        };
+        let kept = list.signatures().filter(|s| !present(&s.owner, s.data)).count();
+        if kept == 0 {
+            continue;
+        }
+
+        let list_size = 16 + 12 + list.header.len() + kept * list.signature_size();
+        put(out, &mut size, list.signature_type.as_bytes());
+        put(out, &mut size, &(list_size as u32).to_le_bytes());
+        put(out, &mut size, &(list.header.len() as u32).to_le_bytes());
+        put(out, &mut size, &(list.signature_size() as u32).to_le_bytes());
+        put(out, &mut size, list.header);
+        for signature in list.signatures().filter(|s| !present(&s.owner, s.data)) {
+            put(out, &mut size, signature.owner.as_bytes());
+            put(out, &mut size, signature.data);
+        }
+    }
+    size
+}
+
+/// Authorizes writes against the Secure Boot policy and the rules of time based authenticated variables.
+pub(super) struct Authenticator {
+    crypto: &'static dyn VariableCrypto,
+    mode: Mode,
+    /// Holds the message a descriptor signs, and afterwards any data derived from the request.
+    scratch: &'static mut [u8],
+}
+
+impl Authenticator {
+    /// Size of the scratch buffer needed to process the largest write the store accepts.
+    pub(super) fn scratch_size(config: &VariableConfig) -> usize {
+        // The name and payload of a signed message are each bounded by the maximum variable size.
+        let max_size = config.max_variable_size.max(config.max_hardware_error_variable_size);
+        2 * max_size + size_of::<efi::Guid>() + size_of::<u32>() + size_of::<efi::Time>()
+    }
+
+    /// Creates an authenticator in the mode implied by the contents of the store.
+    pub(super) fn new(crypto: &'static dyn VariableCrypto, scratch: &'static mut [u8], inner: &Inner) -> Self {
+        let mode = if inner.find(&PK, &GLOBAL_VARIABLE_GUID).is_none() {
+            Mode::Setup
+        } else if inner.find(&DEPLOYED_MODE, &AUTH_STATE_GUID).is_some() {
+            Mode::Deployed
+        } else {
+            Mode::User
+        };
+        Self { crypto, mode, scratch }
+    }
+
+    /// Moves the crypto backend and the scratch buffer to the virtual addresses of `map`.
+    pub(super) fn convert_pointers(&mut self, map: &dyn VirtualAddressMap) {
+        self.crypto.convert_pointers(map);
+        super::convert_ref(&mut self.crypto, map);
+        super::convert_slice(&mut self.scratch, map);
+    }
+
+    pub(super) fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Writes the mode variables to reflect the current mode.
+    pub(super) fn publish_mode(&self, store: &VariableStore, inner: &mut Inner) -> Result<(), VariableError> {
+        let flags = [
+            (&SETUP_MODE[..], self.mode == Mode::Setup),
+            (&SECURE_BOOT[..], self.mode != Mode::Setup),
+            (&DEPLOYED_MODE[..], self.mode == Mode::Deployed),
+            (&AUDIT_MODE[..], false),
+        ];
+        for (name, value) in flags {
+            store.write(inner, &internal(name, &GLOBAL_VARIABLE_GUID, MODE_ATTRIBUTES, &[value as u8]))?;
+        }
+
+        let mut support = [0u8; SUPPORTED_SIGNATURE_TYPES.len() * 16];
+        for (entry, guid) in support.chunks_exact_mut(16).zip(&SUPPORTED_SIGNATURE_TYPES) {
+            entry.copy_from_slice(guid.as_bytes());
+        }
+        store.write(inner, &internal(&SIGNATURE_SUPPORT, &GLOBAL_VARIABLE_GUID, MODE_ATTRIBUTES, &support))
+    }
+
+    /// `SetVariable()` with authentication enabled. `name` is null-terminated and the attributes have passed the
+    /// checks that do not depend on the store contents.
+    pub(super) fn set(
+        &mut self,
+        store: &VariableStore,
+        inner: &mut Inner,
+        name: &[u16],
+        guid: &efi::Guid,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), VariableError> {
+        let policy = Policy::of(name, guid);
+        match policy {
+            Policy::ReadOnly => return Err(VariableError::WriteProtected),
+            Policy::DeployedMode => return self.set_deployed_mode(store, inner, attributes, data),
+            _ => {}
+        }
+
+        let existing = inner.find(name, guid).map(|found| found.located().header);
+        if attributes & efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS == 0 {
+            if policy.is_secure_boot_database() {
+                return Err(VariableError::InvalidParameter);
+            }
+            // Authenticated variables can only be updated (or deleted) with an authenticated write.
+            if existing
+                .is_some_and(|header| header.attributes & efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS != 0)
+            {
+                return Err(VariableError::WriteProtected);
+            }
+            return store.write(inner, &Write::new(name, guid, attributes, data));
+        }
+
+        if policy.is_secure_boot_database() && attributes & !efi::VARIABLE_APPEND_WRITE != POLICY_ATTRIBUTES {
+            return Err(VariableError::InvalidParameter);
+        }
+
+        let (descriptor, payload) = Authentication2::parse(data).map_err(|_| VariableError::SecurityViolation)?;
+        let time_stamp = timestamp(&descriptor.timestamp);
+        let append = attributes & efi::VARIABLE_APPEND_WRITE != 0;
+        if let Some(header) = &existing
+            && !append
+            && !is_later(&time_stamp, &header.time_stamp)
+        {
+            return Err(VariableError::SecurityViolation);
+        }
+
+        if policy.is_secure_boot_database() && !payload.is_empty() {
+            check_signature_lists(policy, payload)?;
+        }
+
+        let message_size =
+            authenticated::signed_message_into(name, guid, attributes, &descriptor.timestamp, payload, self.scratch)
+                .map_err(|_| VariableError::InvalidParameter)?;
+        if !self.verify(inner, policy, descriptor.signed_data, &self.scratch[..message_size], payload) {
+            return Err(VariableError::SecurityViolation);
+        }
+
+        let signer = if policy == Policy::Private {
+            let digest =
+                self.crypto.pkcs7_signer_digest(descriptor.signed_data).ok_or(VariableError::SecurityViolation)?;
+            if existing.is_some() && Self::recorded_signer(inner, name, guid) != Some(digest) {
+                return Err(VariableError::SecurityViolation);
+            }
+            Some(digest)
+        } else {
+            None
+        };
+
+        let delete = payload.is_empty() && !append;
+        if policy == Policy::PlatformKey && delete && self.mode == Mode::Deployed {
+            return Err(VariableError::WriteProtected);
+        }
+
+        let (payload, time_stamp) = match existing {
+            Some(header) if append => {
+                let payload = if matches!(policy, Policy::KeyExchangeKey | Policy::ImageDatabase) {
+                    let found = inner.find(name, guid).ok_or(VariableError::NotFound)?;
+                    let current = inner.region(&found).data(found.located());
+                    let size = filter_signature_lists(current, payload, self.scratch);
+                    &self.scratch[..size]
+                } else {
+                    payload
+                };
+                let time_stamp = if is_later(&time_stamp, &header.time_stamp) { time_stamp } else { header.time_stamp };
+                (payload, time_stamp)
+            }
+            _ => (payload, time_stamp),
+        };
+
+        store.write(inner, &Write { name, guid, attributes, data: payload, time_stamp, internal: false })?;
+
+        match policy {
+            Policy::PlatformKey => {
+                let mode = match self.mode {
+                    _ if delete => Mode::Setup,
+                    Mode::Setup => Mode::User,
+                    mode => mode,
+                };
+                if mode != self.mode {
+                    self.mode = mode;
+                    self.publish_mode(store, inner)?;
+                }
+            }
+            Policy::Private if delete => self.record_signer(store, inner, name, guid, None)?,
+            Policy::Private if existing.is_none() => self.record_signer(store, inner, name, guid, signer)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Handles a write to `DeployedMode`, which can only move the platform from user mode to deployed mode.
+    fn set_deployed_mode(
+        &mut self,
+        store: &VariableStore,
+        inner: &mut Inner,
+        attributes: u32,
+        data: &[u8],
+    ) -> Result<(), VariableError> {
+        if store.at_runtime() || self.mode != Mode::User {
+            return Err(VariableError::WriteProtected);
+        }
+        if attributes != MODE_ATTRIBUTES || data.len() != 1 || data[0] > 1 {
+            return Err(VariableError::InvalidParameter);
+        }
+        if data[0] == 1 {
+            store.write(inner, &internal(&DEPLOYED_MODE, &AUTH_STATE_GUID, STATE_ATTRIBUTES, &[1]))?;
+            self.mode = Mode::Deployed;
+            self.publish_mode(store, inner)?;
+        }
+        Ok(())
+    }
+
+    /// Verifies that `signed_data` signs `message` with a key that may authorize a write of `payload` to a `policy`
+    /// variable.
+    fn verify(&self, inner: &Inner, policy: Policy, signed_data: &[u8], message: &[u8], payload: &[u8]) -> bool {
+        let signed_by = |name: &[u16]| self.signed_by_key(inner, name, signed_data, message);
+        match policy {
+            // A platform key enrolled in setup mode must be signed by the certificate it holds.
+            Policy::PlatformKey if self.mode == Mode::Setup => {
+                self.signed_by_certificate_in(payload, signed_data, message)
+            }
+            _ if policy.is_secure_boot_database() && self.mode == Mode::Setup => true,
+            Policy::PlatformKey | Policy::KeyExchangeKey => signed_by(&PK),
+            Policy::ImageDatabase => signed_by(&KEK) || signed_by(&PK),
+            Policy::Private => self.crypto.pkcs7_verify(signed_data, None, message),
+            Policy::DeployedMode | Policy::ReadOnly => false,
+        }
+    }
+
+    /// True if `message` is signed by one of the X.509 certificates in the global key variable `name`.
+    fn signed_by_key(&self, inner: &Inner, name: &[u16], signed_data: &[u8], message: &[u8]) -> bool {
+        let Some(found) = inner.find(name, &GLOBAL_VARIABLE_GUID) else {
+            return false;
+        };
+        self.signed_by_certificate_in(inner.region(&found).data(found.located()), signed_data, message)
+    }
+
+    /// True if `message` is signed by one of the X.509 certificates in the signature lists `lists`.
+    fn signed_by_certificate_in(&self, lists: &[u8], signed_data: &[u8], message: &[u8]) -> bool {
+        authenticated::signature_lists(lists)
+            .map_while(Result::ok)
+            .filter(|list| list.signature_type == signature_type::X509)
+            .flat_map(|list| list.signatures())
+            .any(|certificate| self.crypto.pkcs7_verify(signed_data, Some(certificate.data), message))
+    }
+
+    /// Returns the signer digest recorded for the private variable `name`/`guid`.
+    fn recorded_signer(inner: &Inner, name: &[u16], guid: &efi::Guid) -> Option<[u8; 32]> {
+        let found = inner.find(name, &CERT_DB_GUID)?;
+        inner
+            .region(&found)
+            .data(found.located())
+            .chunks_exact(SIGNER_ENTRY_SIZE)
+            .find(|entry| entry[..16] == guid.as_bytes()[..])
+            .and_then(|entry| entry[16..].try_into().ok())
+    }
+
+    /// Records `signer` as the signer of the private variable `name`/`guid`, or removes its record if `None`.
+    fn record_signer(
+        &mut self,
+        store: &VariableStore,
+        inner: &mut Inner,
+        name: &[u16],
+        guid: &efi::Guid,
+        signer: Option<[u8; 32]>,
+    ) -> Result<(), VariableError> {
+        // Records are kept per name, with one entry for each vendor GUID that uses it.
+        let record = inner.find(name, &CERT_DB_GUID);
+        let mut size = 0;
+        if let Some(found) = &record {
+            let entries = inner.region(found).data(found.located()).chunks_exact(SIGNER_ENTRY_SIZE);
+            for entry in entries.filter(|entry| entry[..16] != guid.as_bytes()[..]) {
+                self.scratch[size..size + SIGNER_ENTRY_SIZE].copy_from_slice(entry);
+                size += SIGNER_ENTRY_SIZE;
+            }
+        }
+        if let Some(digest) = signer {
+            if size + SIGNER_ENTRY_SIZE > self.scratch.len() {
+                return Err(VariableError::OutOfResources);
+            }
+            self.scratch[size..size + 16].copy_from_slice(guid.as_bytes());
+            self.scratch[size + 16..size + SIGNER_ENTRY_SIZE].copy_from_slice(&digest);
+            size += SIGNER_ENTRY_SIZE;
+        }
+
+        if record.is_none() && size == 0 {
+            return Ok(());
+        }
+        store.write(inner, &internal(name, &CERT_DB_GUID, CERT_DB_ATTRIBUTES, &self.scratch[..size]))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::service::RamNvStorage;
+    use patina::{
+        component::service::memory::StdMemoryManager,
+        runtime_services::{
+            RuntimeServices,
+            authenticated_variables::{SignatureData, SignatureList},
+        },
+    };
+
+    const NV_BS_RT: u32 = efi::VARIABLE_NON_VOLATILE | efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+    const NV_BS_RT_AT: u32 = POLICY_ATTRIBUTES;
+    const APPEND: u32 = efi::VARIABLE_APPEND_WRITE;
+    const VENDOR: efi::Guid = efi::Guid::from_fields(0x1234, 0x5678, 0x9abc, 0xde, 0xf0, &[1, 2, 3, 4, 5, 6]);
+    const OWNER: efi::Guid = efi::Guid::from_fields(0x8765, 0x4321, 0xcba9, 0xfe, 0xdc, &[6, 5, 4, 3, 2, 1]);
+
+    /// Stands in for PKCS#7: signed data is a certificate followed by a digest of the certificate and the message.
+    struct TestCrypto;
+
+    static CRYPTO: TestCrypto = TestCrypto;
+
+    fn digest(parts: &[&[u8]]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (seed, chunk) in out.chunks_exact_mut(8).enumerate() {
+            let mut hash = 0xcbf29ce484222325u64 ^ seed as u64;
+            for byte in parts.iter().flat_map(|p| p.iter().chain(&[0xff])) {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x100000001b3);
+            }
+            chunk.copy_from_slice(&hash.to_le_bytes());
+        }
+        out
+    }
+
+    impl VariableCrypto for TestCrypto {
+        fn pkcs7_verify(&self, signed_data: &[u8], trusted_certificate: Option<&[u8]>, message: &[u8]) -> bool {
+            let Some(split) = signed_data.len().checked_sub(32) else {
+                return false;
+            };
+            let (certificate, signature) = signed_data.split_at(split);
+            trusted_certificate.is_none_or(|trusted| trusted == certificate)
+                && signature == digest(&[certificate, message])
+        }
+
+        fn pkcs7_signer_digest(&self, signed_data: &[u8]) -> Option<[u8; 32]> {
+            let certificate = &signed_data[..signed_data.len().checked_sub(32)?];
+            Some(digest(&[certificate]))
+        }
+    }
+
+    fn name(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(core::iter::once(0)).collect()
+    }
+
+    fn time(second: u8) -> efi::Time {
+        efi::Time { year: 2025, month: 6, day: 1, second, ..Default::default() }
+    }
+
+    fn open(nv: &'static RamNvStorage) -> VariableStore {
+        let memory_manager = StdMemoryManager::new();
+        let store = VariableStore::new(&VariableConfig::default(), nv, &memory_manager).unwrap();
+        store.enable_authentication(&CRYPTO, &memory_manager).unwrap();
+        store
+    }
+
+    fn store() -> (VariableStore, &'static RamNvStorage) {
+        let nv = Box::leak(Box::new(RamNvStorage::new(0x10000, 0x1000).unwrap()));
+        (open(nv), nv)
+    }
+
+    fn certificates(certificates: &[&[u8]]) -> Vec<u8> {
+        let mut lists = Vec::new();
+        for certificate in certificates {
+            let signature = SignatureData { owner: OWNER, data: certificate };
+            SignatureList::write(&signature_type::X509, &[signature], &mut lists);
+        }
+        lists
+    }
+
+    fn hashes(hashes: &[[u8; 32]]) -> Vec<u8> {
+        let signatures: Vec<_> = hashes.iter().map(|hash| SignatureData { owner: OWNER, data: hash }).collect();
+        let mut list = Vec::new();
+        SignatureList::write(&signature_type::SHA256, &signatures, &mut list);
+        list
+    }
+
+    /// Builds the data of an authenticated write of `payload` signed with `certificate`.
+    fn signed(
+        certificate: &[u8],
+        name: &[u16],
+        guid: &efi::Guid,
+        attributes: u32,
+        second: u8,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let message = authenticated::signed_message(name, guid, attributes, &time(second), payload);
+        let mut signed_data = certificate.to_vec();
+        signed_data.extend_from_slice(&digest(&[certificate, &message]));
+        let mut data = Vec::new();
+        Authentication2 { timestamp: time(second), signed_data: &signed_data }.write(&mut data);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn set(
+        store: &VariableStore,
+        certificate: &[u8],
+        (n, guid): (&str, &efi::Guid),
+        attributes: u32,
+        second: u8,
+        payload: &[u8],
+    ) -> Result<(), efi::Status> {
+        let data = signed(certificate, &name(n), guid, attributes, second, payload);
+        store.set_variable(&name(n), guid, attributes, &data)
+    }
+
+    fn get(store: &VariableStore, n: &str, guid: &efi::Guid) -> Result<Vec<u8>, efi::Status> {
+        store.get_variable::<Vec<u8>>(&name(n), guid, None).map(|(data, _)| data)
+    }
+
+    /// Returns `SetupMode`, `SecureBoot` and `DeployedMode`.
+    fn mode(store: &VariableStore) -> [u8; 3] {
+        ["SetupMode", "SecureBoot", "DeployedMode"].map(|n| get(store, n, &GLOBAL_VARIABLE_GUID).unwrap()[0])
+    }
+
+    const PK_VAR: (&str, &efi::Guid) = ("PK", &GLOBAL_VARIABLE_GUID);
+    const KEK_VAR: (&str, &efi::Guid) = ("KEK", &GLOBAL_VARIABLE_GUID);
+    const DB_VAR: (&str, &efi::Guid) = ("db", &IMAGE_SECURITY_DATABASE_GUID);
+
+    /// Enrolls `pk` as the platform key and `kek` as the key exchange key.
+    fn enroll(store: &VariableStore, pk: &[u8], kek: &[u8]) {
+        set(store, pk, PK_VAR, NV_BS_RT_AT, 1, &certificates(&[pk])).unwrap();
+        set(store, pk, KEK_VAR, NV_BS_RT_AT, 1, &certificates(&[kek])).unwrap();
+    }
+
+    #[test]
+    fn starts_in_setup_mode() {
+        let (store, _) = store();
+        assert_eq!(mode(&store), [1, 0, 0]);
+        assert_eq!(get(&store, "AuditMode", &GLOBAL_VARIABLE_GUID), Ok(vec![0]));
+        assert_eq!(get(&store, "SignatureSupport", &GLOBAL_VARIABLE_GUID).unwrap().len(), 9 * 16);
+
+        let bs_rt = efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+        assert_eq!(
+            store.set_variable(&name("SetupMode"), &GLOBAL_VARIABLE_GUID, bs_rt, &vec![0u8]),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+        assert_eq!(
+            store.set_variable(&name("Var"), &CERT_DB_GUID, NV_BS_RT, &vec![0u8]),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+    }
+
+    #[test]
+    fn key_hierarchy_authorizes_policy_updates() {
+        let (store, nv) = store();
+        enroll(&store, b"platform", b"exchange");
+        assert_eq!(mode(&store), [0, 1, 0]);
+
+        // The mode is derived from the enrolled keys after a reset.
+        let store = open(nv);
+        assert_eq!(mode(&store), [0, 1, 0]);
+
+        let kek = certificates(&[b"exchange", b"second"]);
+        assert_eq!(set(&store, b"exchange", KEK_VAR, NV_BS_RT_AT, 2, &kek), Err(efi::Status::SECURITY_VIOLATION));
+        set(&store, b"platform", KEK_VAR, NV_BS_RT_AT, 2, &kek).unwrap();
+        assert_eq!(get(&store, "KEK", &GLOBAL_VARIABLE_GUID), Ok(kek));
+
+        let db = hashes(&[[1; 32]]);
+        assert_eq!(set(&store, b"stranger", DB_VAR, NV_BS_RT_AT, 1, &db), Err(efi::Status::SECURITY_VIOLATION));
+        set(&store, b"second", DB_VAR, NV_BS_RT_AT, 1, &db).unwrap();
+        set(&store, b"platform", DB_VAR, NV_BS_RT_AT, 2, &db).unwrap();
+
+        // The signature must cover the payload.
+        let mut tampered = signed(b"second", &name("db"), &IMAGE_SECURITY_DATABASE_GUID, NV_BS_RT_AT, 3, &db);
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            store.set_variable(&name("db"), &IMAGE_SECURITY_DATABASE_GUID, NV_BS_RT_AT, &tampered),
+            Err(efi::Status::SECURITY_VIOLATION)
+        );
+    }
+
+    #[test]
+    fn platform_key_must_be_self_signed_in_setup_mode() {
+        let (store, _) = store();
+        let pk = certificates(&[b"platform"]);
+        assert_eq!(set(&store, b"anyone", PK_VAR, NV_BS_RT_AT, 1, &pk), Err(efi::Status::SECURITY_VIOLATION));
+        assert_eq!(get(&store, "PK", &GLOBAL_VARIABLE_GUID), Err(efi::Status::NOT_FOUND));
+        assert_eq!(mode(&store), [1, 0, 0]);
+
+        // The other policy variables need no signature until a platform key is enrolled.
+        set(&store, b"anyone", KEK_VAR, NV_BS_RT_AT, 1, &certificates(&[b"exchange"])).unwrap();
+        set(&store, b"platform", PK_VAR, NV_BS_RT_AT, 1, &pk).unwrap();
+        assert_eq!(mode(&store), [0, 1, 0]);
+    }
+
+    #[test]
+    fn policy_variable_rules() {
+        let (store, _) = store();
+        let pk = certificates(&[b"platform"]);
+        let nv_bs_rt = NV_BS_RT_AT & !efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+        assert_eq!(
+            store.set_variable(&name("PK"), &GLOBAL_VARIABLE_GUID, nv_bs_rt, &pk),
+            Err(efi::Status::INVALID_PARAMETER)
+        );
+        let bs_rt_at = NV_BS_RT_AT & !efi::VARIABLE_NON_VOLATILE;
+        assert_eq!(set(&store, b"x", PK_VAR, bs_rt_at, 1, &pk), Err(efi::Status::INVALID_PARAMETER));
+
+        // The platform key is exactly one X.509 certificate.
+        let two = certificates(&[b"one", b"two"]);
+        assert_eq!(set(&store, b"x", PK_VAR, NV_BS_RT_AT, 1, &two), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(set(&store, b"x", PK_VAR, NV_BS_RT_AT, 1, &hashes(&[[0; 32]])), Err(efi::Status::INVALID_PARAMETER));
+
+        let mut unknown = hashes(&[[0; 32]]);
+        unknown[0] ^= 1;
+        assert_eq!(set(&store, b"x", DB_VAR, NV_BS_RT_AT, 1, &unknown), Err(efi::Status::INVALID_PARAMETER));
+        assert_eq!(set(&store, b"x", DB_VAR, NV_BS_RT_AT, 1, &[0u8; 10]), Err(efi::Status::INVALID_PARAMETER));
+
+        assert_eq!(
+            store.set_variable(&name("db"), &IMAGE_SECURITY_DATABASE_GUID, NV_BS_RT_AT, &vec![0u8; 8]),
+            Err(efi::Status::SECURITY_VIOLATION)
+        );
+    }
+
+    #[test]
+    fn timestamps_must_increase() {
+        let (store, _) = store();
+        enroll(&store, b"platform", b"exchange");
+        let db = hashes(&[[1; 32]]);
+        set(&store, b"exchange", DB_VAR, NV_BS_RT_AT, 5, &db).unwrap();
+        assert_eq!(set(&store, b"exchange", DB_VAR, NV_BS_RT_AT, 5, &db), Err(efi::Status::SECURITY_VIOLATION));
+        assert_eq!(set(&store, b"exchange", DB_VAR, NV_BS_RT_AT, 4, &db), Err(efi::Status::SECURITY_VIOLATION));
+
+        // Appends may carry an older timestamp, but the stored timestamp does not move back.
+        set(&store, b"exchange", DB_VAR, NV_BS_RT_AT | APPEND, 3, &hashes(&[[2; 32]])).unwrap();
+        assert_eq!(set(&store, b"exchange", DB_VAR, NV_BS_RT_AT, 5, &db), Err(efi::Status::SECURITY_VIOLATION));
+        set(&store, b"exchange", DB_VAR, NV_BS_RT_AT, 6, &db).unwrap();
+    }
+
+    #[test]
+    fn append_skips_enrolled_signatures() {
+        let (store, _) = store();
+        enroll(&store, b"platform", b"exchange");
+        set(&store, b"exchange", DB_VAR, NV_BS_RT_AT, 1, &hashes(&[[1; 32]])).unwrap();
+        set(&store, b"exchange", DB_VAR, NV_BS_RT_AT | APPEND, 2, &hashes(&[[1; 32], [2; 32]])).unwrap();
+
+        let mut expected = hashes(&[[1; 32]]);
+        expected.extend(hashes(&[[2; 32]]));
+        assert_eq!(get(&store, "db", &IMAGE_SECURITY_DATABASE_GUID), Ok(expected.clone()));
+
+        // Appending only known signatures leaves the data as it is.
+        set(&store, b"exchange", DB_VAR, NV_BS_RT_AT | APPEND, 3, &hashes(&[[2; 32]])).unwrap();
+        assert_eq!(get(&store, "db", &IMAGE_SECURITY_DATABASE_GUID), Ok(expected));
+    }
+
+    #[test]
+    fn deleting_platform_key_returns_to_setup_mode() {
+        let (store, _) = store();
+        enroll(&store, b"platform", b"exchange");
+        assert_eq!(set(&store, b"exchange", PK_VAR, NV_BS_RT_AT, 2, &[]), Err(efi::Status::SECURITY_VIOLATION));
+        set(&store, b"platform", PK_VAR, NV_BS_RT_AT, 2, &[]).unwrap();
+        assert_eq!(mode(&store), [1, 0, 0]);
+        assert_eq!(get(&store, "PK", &GLOBAL_VARIABLE_GUID), Err(efi::Status::NOT_FOUND));
+
+        // The KEK remains, and can be replaced without a signature from a platform key.
+        set(&store, b"anyone", KEK_VAR, NV_BS_RT_AT, 2, &certificates(&[b"new"])).unwrap();
+    }
+
+    #[test]
+    fn deployed_mode() {
+        let (store, nv) = store();
+        let bs_rt = efi::VARIABLE_BOOTSERVICE_ACCESS | efi::VARIABLE_RUNTIME_ACCESS;
+        let deployed = name("DeployedMode");
+        assert_eq!(
+            store.set_variable(&deployed, &GLOBAL_VARIABLE_GUID, bs_rt, &vec![1u8]),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+
+        enroll(&store, b"platform", b"exchange");
+        assert_eq!(
+            store.set_variable(&deployed, &GLOBAL_VARIABLE_GUID, bs_rt, &vec![2u8]),
+            Err(efi::Status::INVALID_PARAMETER)
+        );
+        store.set_variable(&deployed, &GLOBAL_VARIABLE_GUID, bs_rt, &vec![1u8]).unwrap();
+        assert_eq!(mode(&store), [0, 1, 1]);
+
+        let store = open(nv);
+        assert_eq!(mode(&store), [0, 1, 1]);
+        assert_eq!(set(&store, b"platform", PK_VAR, NV_BS_RT_AT, 2, &[]), Err(efi::Status::WRITE_PROTECTED));
+        assert_eq!(
+            store.set_variable(&deployed, &GLOBAL_VARIABLE_GUID, bs_rt, &vec![0u8]),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+    }
+
+    #[test]
+    fn private_variables_are_bound_to_their_signer() {
+        let (store, _) = store();
+        let var = ("Private", &VENDOR);
+        set(&store, b"alice", var, NV_BS_RT_AT, 1, b"one").unwrap();
+        assert_eq!(get(&store, "Private", &VENDOR), Ok(b"one".to_vec()));
+        assert!(get(&store, "Private", &CERT_DB_GUID).is_ok());
+
+        assert_eq!(set(&store, b"bob", var, NV_BS_RT_AT, 2, b"two"), Err(efi::Status::SECURITY_VIOLATION));
+        assert_eq!(set(&store, b"alice", var, NV_BS_RT_AT, 1, b"two"), Err(efi::Status::SECURITY_VIOLATION));
+        set(&store, b"alice", var, NV_BS_RT_AT, 2, b"two").unwrap();
+        set(&store, b"alice", var, NV_BS_RT_AT | APPEND, 2, b"+").unwrap();
+        assert_eq!(get(&store, "Private", &VENDOR), Ok(b"two+".to_vec()));
+
+        // Neither an unauthenticated write nor an unauthenticated delete can touch it.
+        let attributes = NV_BS_RT_AT & !efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS;
+        assert_eq!(
+            store.set_variable(&name("Private"), &VENDOR, attributes, &vec![0u8]),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+        assert_eq!(
+            store.set_variable(&name("Private"), &VENDOR, 0, &Vec::<u8>::new()),
+            Err(efi::Status::WRITE_PROTECTED)
+        );
+
+        set(&store, b"alice", var, NV_BS_RT_AT, 3, &[]).unwrap();
+        assert_eq!(get(&store, "Private", &VENDOR), Err(efi::Status::NOT_FOUND));
+        assert_eq!(get(&store, "Private", &CERT_DB_GUID), Err(efi::Status::NOT_FOUND));
+
+        // Once deleted, the name can be claimed by another signer.
+        set(&store, b"bob", var, NV_BS_RT_AT, 1, b"bob").unwrap();
+    }
+}
@@ -0,0 +1,434 @@
+//! On-Flash Variable Store Format
+//!
+//! Layout definitions for the variable store, compatible with the `VARIABLE_STORE_HEADER` and
+//! `VARIABLE_HEADER` / `AUTHENTICATED_VARIABLE_HEADER` structures used by EDK II
+//! (`MdeModulePkg/Include/Guid/VariableFormat.h`). Keeping the layout identical allows a store written by a C variable
+//! driver to be consumed by this crate and vice versa.
+//!
+//! A non-volatile store lives inside a firmware volume whose file system GUID is [SYSTEM_NV_DATA_FV_GUID]:
+//!
+//! ```text
+//! +-----------------------------+ 0
+//! | EFI_FIRMWARE_VOLUME_HEADER  |
+//! | + block map                 |
+//! +-----------------------------+ header_length
+//! | VARIABLE_STORE_HEADER       |
+//! +-----------------------------+
+//! | VARIABLE_HEADER | name | data | pad to 4 bytes
+//! | VARIABLE_HEADER | name | data | pad to 4 bytes
+//! | ...                         |
+//! | 0xFF (erased)               |
+//! +-----------------------------+ header_length + store size
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::mem::size_of;
+
+use patina::pi::fw_fs::{
+    fv::{self, BlockMapEntry},
+    fvb::attributes::raw::fvb2,
+};
+use r_efi::efi;
+use zerocopy::{FromBytes, IntoBytes};
+use zerocopy_derive::*;
+
+/// Signature of a store holding `VARIABLE_HEADER` records (`gEfiVariableGuid`).
+pub(crate) const VARIABLE_STORE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xddcf3616, 0x3275, 0x4164, 0x98, 0xb6, &[0xfe, 0x85, 0x70, 0x7f, 0xfe, 0x7d]);
+
+/// Signature of a store holding `AUTHENTICATED_VARIABLE_HEADER` records (`gEfiAuthenticatedVariableGuid`).
+pub(crate) const AUTHENTICATED_VARIABLE_STORE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xaaf32c78, 0x947b, 0x439a, 0xa1, 0x80, &[0x2e, 0x14, 0x4e, 0xc3, 0x77, 0x92]);
+
+/// File system GUID of the firmware volume that contains the non-volatile variable store (`gEfiSystemNvDataFvGuid`).
+pub(crate) const SYSTEM_NV_DATA_FV_GUID: efi::Guid =
+    efi::Guid::from_fields(0xfff12b8d, 0x7696, 0x4c8b, 0xa9, 0x85, &[0x27, 0x47, 0x07, 0x5b, 0x4f, 0x50]);
+
+/// `VARIABLE_STORE_FORMATTED`
+pub(crate) const STORE_FORMATTED: u8 = 0x5a;
+/// `VARIABLE_STORE_HEALTHY`
+pub(crate) const STORE_HEALTHY: u8 = 0xfe;
+
+/// `VARIABLE_DATA`, the start marker of every variable header.
+pub(crate) const VARIABLE_START_ID: u16 = 0x55aa;
+
+/// Variable state bits. States only ever move by clearing bits so they can be updated in place on flash.
+pub(crate) mod state {
+    /// The variable is being replaced by a newer copy.
+    pub(crate) const IN_DELETED_TRANSITION: u8 = 0xfe;
+    /// The variable is deleted.
+    pub(crate) const DELETED: u8 = 0xfd;
+    /// Only the header has been written.
+    pub(crate) const HEADER_VALID_ONLY: u8 = 0x7f;
+    /// The variable is complete.
+    pub(crate) const ADDED: u8 = 0x3f;
+
+    /// A complete variable that is being replaced.
+    pub(crate) const ADDED_IN_TRANSITION: u8 = ADDED & IN_DELETED_TRANSITION;
+}
+
+/// Alignment of variable headers within the store.
+pub(crate) const HEADER_ALIGNMENT: usize = 4;
+
+/// Rounds `value` up to the next variable header boundary.
+pub(crate) const fn header_align(value: usize) -> usize {
+    (value + HEADER_ALIGNMENT - 1) & !(HEADER_ALIGNMENT - 1)
+}
+
+/// Erased flash value.
+pub(crate) const ERASED: u8 = 0xff;
+
+/// `VARIABLE_STORE_HEADER`
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+pub(crate) struct StoreHeader {
+    pub signature: [u8; 16],
+    pub size: u32,
+    pub format: u8,
+    pub state: u8,
+    pub reserved: u16,
+    pub reserved1: u32,
+}
+
+impl StoreHeader {
+    /// Size of the header, which is also the offset of the first variable.
+    pub(crate) const SIZE: usize = header_align(size_of::<Self>());
+
+    pub(crate) fn new(format: Format, size: usize) -> Self {
+        Self {
+            signature: *format.signature().as_bytes(),
+            size: size as u32,
+            format: STORE_FORMATTED,
+            state: STORE_HEALTHY,
+            reserved: 0,
+            reserved1: 0,
+        }
+    }
+
+    /// Returns the record format described by the header signature, if it is a store header at all.
+    pub(crate) fn format(&self) -> Option<Format> {
+        match efi::Guid::from_bytes(&self.signature) {
+            g if g == VARIABLE_STORE_GUID => Some(Format::Normal),
+            g if g == AUTHENTICATED_VARIABLE_STORE_GUID => Some(Format::Authenticated),
+            _ => None,
+        }
+    }
+
+    /// True if the header is completely erased, i.e. the store has never been formatted.
+    pub(crate) fn is_erased(&self) -> bool {
+        self.as_bytes().iter().all(|b| *b == ERASED)
+    }
+}
+
+/// `EFI_TIME` with a layout suitable for byte level serialization.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C)]
+pub struct Timestamp {
+    /// 1900 - 9999
+    pub year: u16,
+    /// 1 - 12
+    pub month: u8,
+    /// 1 - 31
+    pub day: u8,
+    /// 0 - 23
+    pub hour: u8,
+    /// 0 - 59
+    pub minute: u8,
+    /// 0 - 59
+    pub second: u8,
+    /// Reserved.
+    pub pad1: u8,
+    /// 0 - 999,999,999
+    pub nanosecond: u32,
+    /// -1440 to 1440 or 2047
+    pub time_zone: i16,
+    /// Daylight saving flags.
+    pub daylight: u8,
+    /// Reserved.
+    pub pad2: u8,
+}
+
+/// `VARIABLE_HEADER`
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+struct NormalHeader {
+    start_id: u16,
+    state: u8,
+    reserved: u8,
+    attributes: u32,
+    name_size: u32,
+    data_size: u32,
+    vendor_guid: [u8; 16],
+}
+
+/// `AUTHENTICATED_VARIABLE_HEADER`
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable, KnownLayout)]
+#[repr(C, packed)]
+struct AuthenticatedHeader {
+    start_id: u16,
+    state: u8,
+    reserved: u8,
+    attributes: u32,
+    monotonic_count: u64,
+    time_stamp: Timestamp,
+    pub_key_index: u32,
+    name_size: u32,
+    data_size: u32,
+    vendor_guid: [u8; 16],
+}
+
+/// The flavor of variable header used by a store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// `VARIABLE_HEADER` records.
+    Normal,
+    /// `AUTHENTICATED_VARIABLE_HEADER` records.
+    Authenticated,
+}
+
+impl Format {
+    pub(crate) fn signature(&self) -> efi::Guid {
+        match self {
+            Format::Normal => VARIABLE_STORE_GUID,
+            Format::Authenticated => AUTHENTICATED_VARIABLE_STORE_GUID,
+        }
+    }
+
+    pub(crate) const fn header_size(&self) -> usize {
+        match self {
+            Format::Normal => size_of::<NormalHeader>(),
+            Format::Authenticated => size_of::<AuthenticatedHeader>(),
+        }
+    }
+
+    /// Offset of the state byte within a variable header. Identical for both flavors.
+    pub(crate) const STATE_OFFSET: usize = 2;
+
+    /// Parses a variable header from the start of `bytes`.
+    ///
+    /// Returns `None` if there is no header at this location (end of the used area).
+    pub(crate) fn read_header(&self, bytes: &[u8]) -> Option<VariableHeader> {
+        let header = match self {
+            Format::Normal => {
+                let (h, _) = NormalHeader::read_from_prefix(bytes).ok()?;
+                VariableHeader {
+                    start_id: h.start_id,
+                    state: h.state,
+                    attributes: h.attributes,
+                    monotonic_count: 0,
+                    time_stamp: Timestamp::default(),
+                    pub_key_index: 0,
+                    name_size: h.name_size as usize,
+                    data_size: h.data_size as usize,
+                    vendor_guid: efi::Guid::from_bytes(&h.vendor_guid),
+                }
+            }
+            Format::Authenticated => {
+                let (h, _) = AuthenticatedHeader::read_from_prefix(bytes).ok()?;
+                VariableHeader {
+                    start_id: h.start_id,
+                    state: h.state,
+                    attributes: h.attributes,
+                    monotonic_count: h.monotonic_count,
+                    time_stamp: h.time_stamp,
+                    pub_key_index: h.pub_key_index,
+                    name_size: h.name_size as usize,
+                    data_size: h.data_size as usize,
+                    vendor_guid: efi::Guid::from_bytes(&h.vendor_guid),
+                }
+            }
+        };
+        (header.start_id == VARIABLE_START_ID).then_some(header)
+    }
+
+    /// Serializes `header` into the start of `bytes`, which must be at least [Self::header_size] long.
+    pub(crate) fn write_header(&self, header: &VariableHeader, bytes: &mut [u8]) {
+        match self {
+            Format::Normal => NormalHeader {
+                start_id: header.start_id,
+                state: header.state,
+                reserved: 0,
+                attributes: header.attributes,
+                name_size: header.name_size as u32,
+                data_size: header.data_size as u32,
+                vendor_guid: *header.vendor_guid.as_bytes(),
+            }
+            .write_to_prefix(bytes)
+            .expect("Buffer was sized for the header."),
+            Format::Authenticated => AuthenticatedHeader {
+                start_id: header.start_id,
+                state: header.state,
+                reserved: 0,
+                attributes: header.attributes,
+                monotonic_count: header.monotonic_count,
+                time_stamp: header.time_stamp,
+                pub_key_index: header.pub_key_index,
+                name_size: header.name_size as u32,
+                data_size: header.data_size as u32,
+                vendor_guid: *header.vendor_guid.as_bytes(),
+            }
+            .write_to_prefix(bytes)
+            .expect("Buffer was sized for the header."),
+        }
+    }
+}
+
+/// A format independent view of a variable header.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct VariableHeader {
+    pub start_id: u16,
+    pub state: u8,
+    pub attributes: u32,
+    pub monotonic_count: u64,
+    pub time_stamp: Timestamp,
+    pub pub_key_index: u32,
+    pub name_size: usize,
+    pub data_size: usize,
+    pub vendor_guid: efi::Guid,
+}
+
+impl VariableHeader {
+    /// Total space used by a variable with this header, including padding to the next header.
+    pub(crate) fn total_size(&self, format: Format) -> usize {
+        header_align(format.header_size() + self.name_size + self.data_size)
+    }
+
+    /// True if the variable is complete and has not been deleted.
+    pub(crate) fn is_valid(&self) -> bool {
+        self.state == state::ADDED || self.state == state::ADDED_IN_TRANSITION
+    }
+}
+
+/// Number of entries in the block map written by [fv_header], including the terminating entry.
+const BLOCK_MAP_ENTRIES: usize = 2;
+
+/// Length of the firmware volume header written by [fv_header].
+pub(crate) const FV_HEADER_LENGTH: usize = size_of::<fv::Header>() + BLOCK_MAP_ENTRIES * size_of::<BlockMapEntry>();
+
+/// Attributes used when formatting a new NV variable firmware volume.
+const FV_ATTRIBUTES: u32 = fvb2::READ_DISABLED_CAP
+    | fvb2::READ_ENABLED_CAP
+    | fvb2::READ_STATUS
+    | fvb2::WRITE_DISABLED_CAP
+    | fvb2::WRITE_ENABLED_CAP
+    | fvb2::WRITE_STATUS
+    | fvb2::STICKY_WRITE
+    | fvb2::MEMORY_MAPPED
+    | fvb2::ERASE_POLARITY
+    | fvb2::READ_LOCK_CAP
+    | fvb2::READ_LOCK_STATUS
+    | fvb2::WRITE_LOCK_CAP
+    | fvb2::WRITE_LOCK_STATUS;
+
+/// Builds a firmware volume header (with block map) describing an NV variable region of `fv_length` bytes.
+pub(crate) fn fv_header(fv_length: usize, block_size: usize) -> [u8; FV_HEADER_LENGTH] {
+    let mut bytes = [0u8; FV_HEADER_LENGTH];
+    bytes[16..32].copy_from_slice(SYSTEM_NV_DATA_FV_GUID.as_bytes());
+    bytes[32..40].copy_from_slice(&(fv_length as u64).to_le_bytes());
+    bytes[40..44].copy_from_slice(b"_FVH");
+    bytes[44..48].copy_from_slice(&FV_ATTRIBUTES.to_le_bytes());
+    bytes[48..50].copy_from_slice(&(FV_HEADER_LENGTH as u16).to_le_bytes());
+    bytes[55] = fv::FFS_REVISION;
+    let map = size_of::<fv::Header>();
+    bytes[map..map + 4].copy_from_slice(&((fv_length / block_size) as u32).to_le_bytes());
+    bytes[map + 4..map + 8].copy_from_slice(&(block_size as u32).to_le_bytes());
+
+    let sum = bytes.chunks_exact(2).fold(0u16, |acc, x| acc.wrapping_add(u16::from_le_bytes([x[0], x[1]])));
+    bytes[50..52].copy_from_slice(&0u16.wrapping_sub(sum).to_le_bytes());
+    bytes
+}
+
+/// Validates the firmware volume header at the start of `bytes` and returns its length.
+///
+/// Returns `None` if the region does not start with a valid NV variable firmware volume header.
+pub(crate) fn parse_fv_header(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < size_of::<fv::Header>() {
+        return None;
+    }
+    let file_system_guid = efi::Guid::from_bytes(bytes[16..32].try_into().ok()?);
+    let fv_length = u64::from_le_bytes(bytes[32..40].try_into().ok()?);
+    let header_length = u16::from_le_bytes(bytes[48..50].try_into().ok()?) as usize;
+
+    if &bytes[40..44] != b"_FVH"
+        || file_system_guid != SYSTEM_NV_DATA_FV_GUID
+        || header_length < size_of::<fv::Header>()
+        || !header_length.is_multiple_of(2)
+        || header_length > bytes.len()
+        || fv_length < header_length as u64
+    {
+        return None;
+    }
+
+    let sum =
+        bytes[..header_length].chunks_exact(2).fold(0u16, |acc, x| acc.wrapping_add(u16::from_le_bytes([x[0], x[1]])));
+    (sum == 0).then_some(header_length)
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_sizes_match_edk2() {
+        assert_eq!(StoreHeader::SIZE, 28);
+        assert_eq!(Format::Normal.header_size(), 32);
+        assert_eq!(Format::Authenticated.header_size(), 60);
+        assert_eq!(size_of::<Timestamp>(), 16);
+        assert_eq!(FV_HEADER_LENGTH, 72);
+    }
+
+    #[test]
+    fn variable_header_round_trip() {
+        for format in [Format::Normal, Format::Authenticated] {
+            let header = VariableHeader {
+                start_id: VARIABLE_START_ID,
+                state: state::ADDED,
+                attributes: 7,
+                monotonic_count: 0,
+                time_stamp: Timestamp::default(),
+                pub_key_index: 0,
+                name_size: 10,
+                data_size: 3,
+                vendor_guid: VARIABLE_STORE_GUID,
+            };
+            let mut bytes = [ERASED; 64];
+            format.write_header(&header, &mut bytes);
+            let parsed = format.read_header(&bytes).unwrap();
+            assert_eq!(parsed.state, state::ADDED);
+            assert_eq!(parsed.attributes, 7);
+            assert_eq!(parsed.name_size, 10);
+            assert_eq!(parsed.data_size, 3);
+            assert_eq!(parsed.vendor_guid, VARIABLE_STORE_GUID);
+            assert_eq!(bytes[Format::STATE_OFFSET], state::ADDED);
+            assert_eq!(parsed.total_size(format), header_align(format.header_size() + 13));
+        }
+        assert!(Format::Normal.read_header(&[ERASED; 32]).is_none());
+    }
+
+    #[test]
+    fn fv_header_is_valid() {
+        let header = fv_header(0x10000, 0x1000);
+        let mut region = [ERASED; 0x100];
+        region[..FV_HEADER_LENGTH].copy_from_slice(&header);
+        assert_eq!(parse_fv_header(&region), Some(FV_HEADER_LENGTH));
+
+        region[20] ^= 1;
+        assert_eq!(parse_fv_header(&region), None);
+        assert_eq!(parse_fv_header(&[ERASED; 0x100]), None);
+    }
+
+    #[test]
+    fn store_header_format_detection() {
+        assert_eq!(StoreHeader::new(Format::Normal, 0x100).format(), Some(Format::Normal));
+        assert_eq!(StoreHeader::new(Format::Authenticated, 0x100).format(), Some(Format::Authenticated));
+        let erased = StoreHeader::read_from_bytes(&[ERASED; 28][..]).unwrap();
+        assert!(erased.is_erased());
+        assert_eq!(erased.format(), None);
+    }
+}
@@ -0,0 +1,418 @@
+//! Variable Store Region
+//!
+//! A [Region] is an in-memory image of one variable store. For the non-volatile store the image mirrors the whole
+//! flash region (firmware volume header included) and every change is written through to the [NvStorage] at the same
+//! offset; the volatile store only exists in memory.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::ops::Range;
+
+use patina::runtime_services::VirtualAddressMap;
+use r_efi::efi;
+
+use super::format::{ERASED, Format, StoreHeader, VARIABLE_START_ID, VariableHeader, state};
+use crate::{error::VariableError, service::NvStorage};
+
+/// A variable located in a region.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Located {
+    /// Offset of the variable header within the region.
+    pub offset: usize,
+    /// The parsed header.
+    pub header: VariableHeader,
+}
+
+/// A variable to be written to a region.
+pub(crate) struct NewVariable<'a> {
+    /// Header of the variable. The state and size fields are filled in when written.
+    pub header: VariableHeader,
+    /// Null-terminated name.
+    pub name: &'a [u16],
+    /// Variable data.
+    pub data: &'a [u8],
+}
+
+impl NewVariable<'_> {
+    fn total_size(&self, format: Format) -> usize {
+        super::format::header_align(format.header_size() + self.name.len() * 2 + self.data.len())
+    }
+
+    /// Serializes the variable into `dst` with the given state.
+    fn encode(&self, format: Format, dst: &mut [u8], state: u8) {
+        let header = VariableHeader {
+            start_id: VARIABLE_START_ID,
+            state,
+            name_size: self.name.len() * 2,
+            data_size: self.data.len(),
+            ..self.header
+        };
+        format.write_header(&header, dst);
+        let name_start = format.header_size();
+        for (chunk, c) in dst[name_start..].chunks_exact_mut(2).zip(self.name) {
+            chunk.copy_from_slice(&c.to_le_bytes());
+        }
+        let data_start = name_start + header.name_size;
+        dst[data_start..data_start + self.data.len()].copy_from_slice(self.data);
+    }
+}
+
+/// Compares a stored little-endian UCS-2 name against `name`.
+pub(crate) fn name_matches(stored: &[u8], name: &[u16]) -> bool {
+    stored.len() == name.len() * 2
+        && stored.chunks_exact(2).zip(name).all(|(bytes, c)| u16::from_le_bytes([bytes[0], bytes[1]]) == *c)
+}
+
+/// Programs `image` into the erased `nv`, with the firmware volume header that ends at `base` last, so that an
+/// interrupted copy never holds a valid volume.
+pub(crate) fn program(nv: &dyn NvStorage, image: &[u8], base: usize) -> Result<(), VariableError> {
+    nv.write(base, &image[base..]).map_err(VariableError::Device)?;
+    nv.write(0, &image[..base]).map_err(VariableError::Device)
+}
+
+/// An in-memory image of a variable store.
+pub(crate) struct Region {
+    buffer: &'static mut [u8],
+    nv: Option<&'static dyn NvStorage>,
+    format: Format,
+    /// Offset of the store header within `buffer`.
+    base: usize,
+    /// Offset of the first free byte.
+    end: usize,
+}
+
+impl Region {
+    /// Creates a freshly formatted volatile region over `buffer`.
+    pub(crate) fn new_volatile(buffer: &'static mut [u8], format: Format) -> Self {
+        buffer.fill(ERASED);
+        let header = StoreHeader::new(format, buffer.len());
+        buffer[..size_of::<StoreHeader>()].copy_from_slice(zerocopy::IntoBytes::as_bytes(&header));
+        Self { buffer, nv: None, format, base: 0, end: StoreHeader::SIZE }
+    }
+
+    /// Creates a region over `buffer`, which holds a copy of the contents of `nv`. The store header is expected at
+    /// `base` and must already be valid.
+    pub(crate) fn new_nv(buffer: &'static mut [u8], nv: &'static dyn NvStorage, base: usize, format: Format) -> Self {
+        let mut region = Self { buffer, nv: Some(nv), format, base, end: base + StoreHeader::SIZE };
+        region.end = region.scan_end();
+        region
+    }
+
+    /// Moves the buffer and the backend of the region to the virtual addresses of `map`.
+    pub(crate) fn convert_pointers(&mut self, map: &dyn VirtualAddressMap) {
+        if let Some(nv) = &mut self.nv {
+            nv.convert_pointers(map);
+            super::convert_ref(nv, map);
+        }
+        super::convert_slice(&mut self.buffer, map);
+    }
+
+    pub(crate) fn format(&self) -> Format {
+        self.format
+    }
+
+    pub(crate) fn is_nv(&self) -> bool {
+        self.nv.is_some()
+    }
+
+    /// Size of the store, including the store header.
+    pub(crate) fn store_size(&self) -> usize {
+        self.buffer.len() - self.base
+    }
+
+    /// Offset just past the end of the store.
+    fn store_end(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Bytes available after the last variable.
+    pub(crate) fn free_space(&self) -> usize {
+        self.store_end() - self.end
+    }
+
+    /// True if the area after the last variable is not fully erased, which means a write was interrupted and the
+    /// region must be reclaimed before it can be appended to.
+    pub(crate) fn needs_reclaim(&self) -> bool {
+        self.buffer[self.end..].iter().any(|b| *b != ERASED)
+    }
+
+    fn scan_end(&self) -> usize {
+        let mut offset = self.base + StoreHeader::SIZE;
+        while let Some(header) = self.header_at(offset) {
+            let next = offset + header.total_size(self.format);
+            if next > self.store_end() {
+                break;
+            }
+            offset = next;
+        }
+        offset
+    }
+
+    fn header_at(&self, offset: usize) -> Option<VariableHeader> {
+        if offset + self.format.header_size() > self.store_end() {
+            return None;
+        }
+        self.format.read_header(&self.buffer[offset..])
+    }
+
+    /// Iterates over every variable header in the region, including deleted ones.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Located> + '_ {
+        let mut offset = self.base + StoreHeader::SIZE;
+        core::iter::from_fn(move || {
+            if offset >= self.end {
+                return None;
+            }
+            let header = self.header_at(offset)?;
+            let located = Located { offset, header };
+            offset += header.total_size(self.format);
+            Some(located)
+        })
+    }
+
+    /// Iterates over the variables that are visible to callers.
+    ///
+    /// A copy that is in deleted transition is only visible if the update that replaced it never completed.
+    pub(crate) fn iter_valid(&self) -> impl Iterator<Item = Located> + '_ {
+        self.iter().filter(|v| {
+            v.header.is_valid()
+                && (v.header.state == state::ADDED
+                    || self
+                        .find_matching(&v.header.vendor_guid, |stored| stored == self.name_bytes(v))
+                        .is_some_and(|f| f.offset == v.offset))
+        })
+    }
+
+    pub(crate) fn name_bytes(&self, variable: &Located) -> &[u8] {
+        let start = variable.offset + self.format.header_size();
+        &self.buffer[start..start + variable.header.name_size]
+    }
+
+    pub(crate) fn data(&self, variable: &Located) -> &[u8] {
+        let start = variable.offset + self.format.header_size() + variable.header.name_size;
+        &self.buffer[start..start + variable.header.data_size]
+    }
+
+    /// Finds the current copy of a variable whose stored name satisfies `matches`.
+    fn find_matching(&self, guid: &efi::Guid, matches: impl Fn(&[u8]) -> bool) -> Option<Located> {
+        let mut in_transition = None;
+        for variable in self.iter() {
+            if !variable.header.is_valid()
+                || variable.header.vendor_guid != *guid
+                || !matches(self.name_bytes(&variable))
+            {
+                continue;
+            }
+            if variable.header.state == state::ADDED {
+                return Some(variable);
+            }
+            in_transition.get_or_insert(variable);
+        }
+        in_transition
+    }
+
+    /// Finds the current copy of a variable.
+    pub(crate) fn find_by_name(&self, name: &[u16], guid: &efi::Guid) -> Option<Located> {
+        self.find_matching(guid, |stored| name_matches(stored, name))
+    }
+
+    /// Writes `range` of the in-memory image through to non-volatile storage.
+    fn flush(&self, range: Range<usize>) -> Result<(), VariableError> {
+        match self.nv {
+            Some(nv) => nv.write(range.start, &self.buffer[range]).map_err(VariableError::Device),
+            None => Ok(()),
+        }
+    }
+
+    /// Clears bits of a variable's state byte.
+    pub(crate) fn set_state(&mut self, offset: usize, new_state: u8) -> Result<(), VariableError> {
+        let at = offset + Format::STATE_OFFSET;
+        self.buffer[at] &= new_state;
+        self.flush(at..at + 1)
+    }
+
+    /// Marks a variable as deleted.
+    pub(crate) fn delete(&mut self, located: &Located) -> Result<(), VariableError> {
+        self.set_state(located.offset, state::DELETED)
+    }
+
+    /// Space the variable needs in this region.
+    pub(crate) fn space_for(&self, variable: &NewVariable) -> usize {
+        variable.total_size(self.format)
+    }
+
+    /// Appends a variable at the end of the region.
+    ///
+    /// For non-volatile regions this follows the same sequence as EDK II so an interrupted write can be recognized:
+    /// the header is written with an erased state, then marked header-valid, then the name and data are written and
+    /// finally the variable is marked added.
+    pub(crate) fn append(&mut self, variable: &NewVariable) -> Result<usize, VariableError> {
+        let size = variable.total_size(self.format);
+        if size > self.free_space() {
+            return Err(VariableError::OutOfResources);
+        }
+        let offset = self.end;
+        let range = offset..offset + size;
+
+        if self.nv.is_some() {
+            variable.encode(self.format, &mut self.buffer[range.clone()], ERASED);
+            let header_end = offset + self.format.header_size();
+            self.flush(offset..header_end)?;
+            self.set_state(offset, state::HEADER_VALID_ONLY)?;
+            self.flush(header_end..range.end)?;
+            self.set_state(offset, state::ADDED)?;
+        } else {
+            variable.encode(self.format, &mut self.buffer[range.clone()], state::ADDED);
+        }
+
+        self.end = range.end;
+        Ok(offset)
+    }
+
+    /// Compacts the region, dropping deleted and superseded variables.
+    ///
+    /// The variable at `skip` (if any) is dropped as well and `add` (if any) is appended to the compacted image before
+    /// it is committed, allowing an update of a large variable to succeed when only the combination of both fits.
+    ///
+    /// For non-volatile regions the compacted image is first staged in the spare region of the storage, if it has one,
+    /// so that [VariableStore::new](super::VariableStore::new) can complete a reclaim interrupted by a reset. The
+    /// storage is then erased and rewritten in full, and the spare erased again.
+    pub(crate) fn reclaim(
+        &mut self,
+        scratch: &mut [u8],
+        skip: Option<usize>,
+        add: Option<&NewVariable>,
+    ) -> Result<Option<usize>, VariableError> {
+        let scratch = &mut scratch[..self.buffer.len()];
+        scratch.fill(ERASED);
+        let first = self.base + StoreHeader::SIZE;
+        scratch[..first].copy_from_slice(&self.buffer[..first]);
+
+        let mut out = first;
+        for variable in self.iter_valid() {
+            if Some(variable.offset) == skip {
+                continue;
+            }
+            let size = variable.header.total_size(self.format);
+            scratch[out..out + size].copy_from_slice(&self.buffer[variable.offset..variable.offset + size]);
+            scratch[out + Format::STATE_OFFSET] = state::ADDED;
+            out += size;
+        }
+
+        let added = match add {
+            Some(variable) => {
+                let size = variable.total_size(self.format);
+                if out + size > scratch.len() {
+                    return Err(VariableError::OutOfResources);
+                }
+                variable.encode(self.format, &mut scratch[out..out + size], state::ADDED);
+                out += size;
+                Some(out - size)
+            }
+            None => None,
+        };
+
+        if let Some(nv) = self.nv {
+            let spare = nv.spare();
+            if let Some(spare) = spare {
+                spare.erase(0..scratch.len()).map_err(VariableError::Device)?;
+                program(spare, &scratch[..out], self.base)?;
+            }
+            nv.erase(0..scratch.len()).map_err(VariableError::Device)?;
+            program(nv, &scratch[..out], self.base)?;
+            if let Some(spare) = spare {
+                spare.erase(0..scratch.len()).map_err(VariableError::Device)?;
+            }
+        }
+        self.buffer.copy_from_slice(scratch);
+        self.end = out;
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::store::format::Timestamp;
+
+    fn header(attributes: u32, guid: efi::Guid) -> VariableHeader {
+        VariableHeader {
+            start_id: VARIABLE_START_ID,
+            state: ERASED,
+            attributes,
+            monotonic_count: 0,
+            time_stamp: Timestamp::default(),
+            pub_key_index: 0,
+            name_size: 0,
+            data_size: 0,
+            vendor_guid: guid,
+        }
+    }
+
+    fn volatile(size: usize) -> Region {
+        Region::new_volatile(Box::leak(vec![0u8; size].into_boxed_slice()), Format::Authenticated)
+    }
+
+    const GUID: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6, 7, 8, 9, 10, 11]);
+
+    #[test]
+    fn append_and_find() {
+        let mut region = volatile(0x200);
+        let name = [b'A' as u16, 0];
+        let variable = NewVariable { header: header(3, GUID), name: &name, data: &[1, 2, 3] };
+        let offset = region.append(&variable).unwrap();
+
+        let found = region.find_by_name(&name, &GUID).unwrap();
+        assert_eq!(found.offset, offset);
+        assert_eq!(region.data(&found), &[1, 2, 3]);
+        assert!(name_matches(region.name_bytes(&found), &name));
+        assert!(region.find_by_name(&[b'B' as u16, 0], &GUID).is_none());
+        assert!(!region.needs_reclaim());
+    }
+
+    #[test]
+    fn in_transition_copy_is_superseded() {
+        let mut region = volatile(0x200);
+        let name = [b'A' as u16, 0];
+        let old = region.append(&NewVariable { header: header(3, GUID), name: &name, data: &[1] }).unwrap();
+        region.set_state(old, state::IN_DELETED_TRANSITION).unwrap();
+
+        // Without a replacement the old copy is still the current one.
+        assert_eq!(region.find_by_name(&name, &GUID).unwrap().offset, old);
+
+        let new = region.append(&NewVariable { header: header(3, GUID), name: &name, data: &[2] }).unwrap();
+        assert_eq!(region.find_by_name(&name, &GUID).unwrap().offset, new);
+        assert_eq!(region.iter_valid().count(), 1);
+    }
+
+    #[test]
+    fn reclaim_compacts() {
+        let mut region = volatile(0x200);
+        let mut scratch = vec![0u8; 0x200];
+        let a = [b'A' as u16, 0];
+        let b = [b'B' as u16, 0];
+        let first = region.append(&NewVariable { header: header(3, GUID), name: &a, data: &[0; 64] }).unwrap();
+        region.append(&NewVariable { header: header(3, GUID), name: &b, data: &[1; 16] }).unwrap();
+        let found = region.find_by_name(&a, &GUID).unwrap();
+        region.delete(&found).unwrap();
+        assert_eq!(found.offset, first);
+
+        let before = region.free_space();
+        region.reclaim(&mut scratch, None, None).unwrap();
+        assert!(region.free_space() > before);
+        assert!(region.find_by_name(&a, &GUID).is_none());
+        assert_eq!(region.data(&region.find_by_name(&b, &GUID).unwrap()), &[1; 16]);
+    }
+
+    #[test]
+    fn append_fails_when_full() {
+        let mut region = volatile(0x80);
+        let name = [b'A' as u16, 0];
+        let variable = NewVariable { header: header(3, GUID), name: &name, data: &[0; 0x80] };
+        assert_eq!(region.append(&variable), Err(VariableError::OutOfResources));
+    }
+}
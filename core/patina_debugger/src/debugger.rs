@@ -296,7 +296,10 @@ impl<T: SerialIO> PatinaDebugger<T> {
         }
 
         if target.reboot_on_resume() {
-            // Reboot the system.
+            // Reboot the system, preferring the platform's reset path if one was provided.
+            if let Some(reset) = crate::RESET_HANDLER.get() {
+                reset();
+            }
             SystemArch::reboot();
             return Err(DebugError::RebootFailure);
         }
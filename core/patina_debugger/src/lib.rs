@@ -134,6 +134,9 @@ use patina_internal_cpu::interrupts::{ExceptionContext, InterruptManager};
 ///
 static DEBUGGER: spin::Once<&dyn Debugger> = spin::Once::new();
 
+/// Global routine used to reset the system. See [set_reset_handler].
+static RESET_HANDLER: spin::Once<ResetHandlerFn> = spin::Once::new();
+
 /// Type for monitor command functions. This will be invoked by the debugger when
 /// the associated monitor command is invoked.
 ///
@@ -146,6 +149,10 @@ static DEBUGGER: spin::Once<&dyn Debugger> = spin::Once::new();
 /// or using the `write!` macro.
 pub type MonitorCommandFn = fn(&mut core::str::SplitWhitespace<'_>, &mut dyn core::fmt::Write);
 
+/// Type for the routine used to reset the system. The routine is not expected to
+/// return; if it does, the debugger falls back to an architecture specific reset.
+pub type ResetHandlerFn = fn();
+
 /// Trait for debugger interaction. This is required to allow for a global to the
 /// platform specific debugger implementation. For safety, these routines should
 /// only be invoked on the global instance of the debugger.
@@ -286,6 +293,14 @@ pub fn add_monitor_command(cmd: &'static str, description: &'static str, functio
     }
 }
 
+/// Sets the routine the debugger uses to reset the system, for example after the
+/// `reboot` monitor command. This allows the reset to go through the platform's
+/// reset service, including its reset notifications. Without a handler, the debugger
+/// resets the system directly through architecture specific means.
+pub fn set_reset_handler(handler: ResetHandlerFn) {
+    RESET_HANDLER.call_once(|| handler);
+}
+
 /// Exception information for the debugger.
 #[allow(dead_code)]
 struct ExceptionInfo {
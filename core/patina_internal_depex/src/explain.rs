@@ -0,0 +1,360 @@
+//! Explainable evaluation of dependency expressions
+//!
+//! [Depex::explain](crate::Depex::explain) evaluates an expression into a [Proof], a tree that records which pushed
+//! GUIDs were present, how the operators combined them, and whether a scheduling opcode kept the expression from
+//! being satisfied. [Proof::minimal_missing] answers the question usually asked of an undispatched driver: which
+//! protocols are still needed.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt;
+use r_efi::efi;
+use uuid::Uuid;
+
+use crate::{Opcode, guid_from_uuid};
+
+/// The largest number of missing GUIDs for which [Proof::minimal_missing] searches every combination.
+const EXHAUSTIVE_SEARCH_LIMIT: usize = 16;
+
+/// The evaluation of a dependency expression, as a tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proof {
+    /// A pushed GUID, and whether it was present when the expression was evaluated.
+    Protocol {
+        /// The pushed GUID.
+        guid: efi::Guid,
+        /// Whether the GUID was present.
+        present: bool,
+    },
+    /// A TRUE or FALSE opcode.
+    Constant(bool),
+    /// The logical AND of two operands.
+    And(Box<Proof>, Box<Proof>),
+    /// The logical OR of two operands.
+    Or(Box<Proof>, Box<Proof>),
+    /// The logical NOT of an operand.
+    Not(Box<Proof>),
+    /// A schedule on request expression that has not been scheduled. It is false until it is, whatever its operand.
+    Sor(Box<Proof>),
+    /// An expression that waits for the driver with this file name to be dispatched, and runs before it.
+    Before(efi::Guid),
+    /// An expression that waits for the driver with this file name to be dispatched, and runs after it.
+    After(efi::Guid),
+    /// An expression that cannot be evaluated. `index` is the opcode at fault, or the length of the expression if it
+    /// ends before an `END` opcode.
+    Invalid {
+        /// The index of the opcode at fault.
+        index: usize,
+    },
+}
+
+impl Proof {
+    /// Whether the expression is satisfied.
+    pub fn value(&self) -> bool {
+        self.value_with(&[])
+    }
+
+    /// Whether the expression would be satisfied if `installed` were present as well.
+    fn value_with(&self, installed: &[efi::Guid]) -> bool {
+        match self {
+            Proof::Protocol { guid, present } => *present || installed.contains(guid),
+            Proof::Constant(value) => *value,
+            Proof::And(left, right) => left.value_with(installed) && right.value_with(installed),
+            Proof::Or(left, right) => left.value_with(installed) || right.value_with(installed),
+            Proof::Not(operand) => !operand.value_with(installed),
+            Proof::Sor(_) | Proof::Before(_) | Proof::After(_) | Proof::Invalid { .. } => false,
+        }
+    }
+
+    /// Returns every GUID in the expression that was not present, in expression order and without duplicates.
+    pub fn missing(&self) -> Vec<efi::Guid> {
+        let mut missing = Vec::new();
+        self.collect_missing(&mut missing);
+        missing
+    }
+
+    fn collect_missing(&self, missing: &mut Vec<efi::Guid>) {
+        match self {
+            Proof::Protocol { guid, present: false } if !missing.contains(guid) => missing.push(*guid),
+            Proof::And(left, right) | Proof::Or(left, right) => {
+                left.collect_missing(missing);
+                right.collect_missing(missing);
+            }
+            Proof::Not(operand) | Proof::Sor(operand) => operand.collect_missing(missing),
+            _ => (),
+        }
+    }
+
+    /// Returns the smallest set of missing GUIDs whose installation satisfies the expression.
+    ///
+    /// The result is empty if the expression is already satisfied, and `None` if installing protocols cannot satisfy
+    /// it: it is false whatever is installed, or it waits on scheduling rather than on protocols. When more than
+    /// sixteen GUIDs are missing, the set is minimal (no GUID can be dropped from it) rather than the smallest.
+    pub fn minimal_missing(&self) -> Option<Vec<efi::Guid>> {
+        if let Proof::Sor(_) | Proof::Before(_) | Proof::After(_) | Proof::Invalid { .. } = self {
+            return None;
+        }
+
+        let missing = self.missing();
+        if missing.len() <= EXHAUSTIVE_SEARCH_LIMIT {
+            let subset = |mask: u32| -> Vec<efi::Guid> {
+                missing.iter().enumerate().filter(|(bit, _)| mask & (1 << bit) != 0).map(|(_, guid)| *guid).collect()
+            };
+            return (0..1u32 << missing.len())
+                .filter(|mask| self.value_with(&subset(*mask)))
+                .min_by_key(|mask| mask.count_ones())
+                .map(subset);
+        }
+
+        // Too many combinations to try them all; drop GUIDs from the full set while the expression stays satisfied.
+        let mut installed = missing;
+        if !self.value_with(&installed) {
+            return None;
+        }
+        let mut index = 0;
+        while index < installed.len() {
+            let guid = installed.remove(index);
+            if !self.value_with(&installed) {
+                installed.insert(index, guid);
+                index += 1;
+            }
+        }
+        Some(installed)
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let value = self.value();
+        write!(f, "{:indent$}", "", indent = depth * 2)?;
+        match self {
+            Proof::Protocol { guid, present } => {
+                let state = if *present { "present" } else { "missing" };
+                writeln!(f, "{} {state}", Uuid::from_bytes_le(*guid.as_bytes()))
+            }
+            Proof::Constant(value) => writeln!(f, "{}", if *value { "TRUE" } else { "FALSE" }),
+            Proof::And(left, right) | Proof::Or(left, right) => {
+                let keyword = if matches!(self, Proof::And(..)) { "AND" } else { "OR" };
+                writeln!(f, "{keyword} => {value}")?;
+                left.fmt_tree(f, depth + 1)?;
+                right.fmt_tree(f, depth + 1)
+            }
+            Proof::Not(operand) => {
+                writeln!(f, "NOT => {value}")?;
+                operand.fmt_tree(f, depth + 1)
+            }
+            Proof::Sor(operand) => {
+                writeln!(f, "SOR, not scheduled => false")?;
+                operand.fmt_tree(f, depth + 1)
+            }
+            Proof::Before(guid) => writeln!(f, "BEFORE {} => false", Uuid::from_bytes_le(*guid.as_bytes())),
+            Proof::After(guid) => writeln!(f, "AFTER {} => false", Uuid::from_bytes_le(*guid.as_bytes())),
+            Proof::Invalid { index } => writeln!(f, "invalid opcode at index {index} => false"),
+        }
+    }
+}
+
+impl fmt::Display for Proof {
+    /// Writes the tree one node per line, indenting operands under their operator.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_tree(f, 0)
+    }
+}
+
+/// Evaluates `opcodes` into a proof tree. See [Depex::explain](crate::Depex::explain).
+pub(crate) fn explain(opcodes: &[Opcode], protocols: &[efi::Guid]) -> Proof {
+    match opcodes {
+        [Opcode::Before(uuid)] | [Opcode::Before(uuid), Opcode::End] => {
+            return guid_from_uuid(uuid).map_or(Proof::Invalid { index: 0 }, Proof::Before);
+        }
+        [Opcode::After(uuid)] | [Opcode::After(uuid), Opcode::End] => {
+            return guid_from_uuid(uuid).map_or(Proof::Invalid { index: 0 }, Proof::After);
+        }
+        [Opcode::Sor, ..] => {
+            return match explain_body(opcodes, 1, protocols) {
+                invalid @ Proof::Invalid { .. } => invalid,
+                proof => Proof::Sor(Box::new(proof)),
+            };
+        }
+        _ => (),
+    }
+    explain_body(opcodes, 0, protocols)
+}
+
+/// Evaluates the opcodes from `start` as a stack machine, building the tree instead of a boolean.
+fn explain_body(opcodes: &[Opcode], start: usize, protocols: &[efi::Guid]) -> Proof {
+    let mut stack: Vec<Proof> = Vec::new();
+    for (index, opcode) in opcodes.iter().enumerate().skip(start) {
+        let invalid = Proof::Invalid { index };
+        let proof = match opcode {
+            Opcode::Push(uuid, present) => match guid_from_uuid(uuid) {
+                Some(guid) => Proof::Protocol { guid, present: *present || protocols.contains(&guid) },
+                None => return invalid,
+            },
+            Opcode::True => Proof::Constant(true),
+            Opcode::False => Proof::Constant(false),
+            Opcode::Not => match stack.pop() {
+                Some(operand) => Proof::Not(Box::new(operand)),
+                None => return invalid,
+            },
+            Opcode::And | Opcode::Or => {
+                let (Some(right), Some(left)) = (stack.pop(), stack.pop()) else {
+                    return invalid;
+                };
+                if *opcode == Opcode::And {
+                    Proof::And(Box::new(left), Box::new(right))
+                } else {
+                    Proof::Or(Box::new(left), Box::new(right))
+                }
+            }
+            Opcode::End => return stack.pop().unwrap_or(invalid),
+            Opcode::Before(_) | Opcode::After(_) | Opcode::Sor | Opcode::Unknown | Opcode::Malformed { .. } => {
+                return invalid;
+            }
+        };
+        stack.push(proof);
+    }
+    Proof::Invalid { index: opcodes.len() }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::{
+        Depex,
+        text::{SymbolTable, compile},
+    };
+    use alloc::{format, string::String, vec};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn guid(index: u8) -> efi::Guid {
+        efi::Guid::from_fields(0x4c0e3d1a, 0x6b2f, 0x4e8d, 0x9a, 0x31, &[0x57, 0x0c, 0xd2, 0x18, 0xe4, index])
+    }
+
+    fn symbols() -> SymbolTable {
+        (0..20).map(|index| (format!("gGuid{index}"), guid(index))).collect()
+    }
+
+    fn explain(source: &str, protocols: &[efi::Guid]) -> Proof {
+        Depex::from(compile(source, &symbols()).unwrap()).explain(protocols)
+    }
+
+    fn protocol(index: u8, present: bool) -> Box<Proof> {
+        Box::new(Proof::Protocol { guid: guid(index), present })
+    }
+
+    #[test]
+    fn explain_should_record_presence_and_operators() {
+        let proof = explain("gGuid0 AND (gGuid1 OR NOT gGuid2)", &[guid(0), guid(2)]);
+        assert_eq!(
+            proof,
+            Proof::And(
+                protocol(0, true),
+                Box::new(Proof::Or(protocol(1, false), Box::new(Proof::Not(protocol(2, true)))))
+            )
+        );
+        assert!(!proof.value());
+        assert_eq!(proof.missing(), vec![guid(1)]);
+        assert_eq!(proof.minimal_missing(), Some(vec![guid(1)]));
+    }
+
+    #[test]
+    fn explain_should_report_scheduling_blockers() {
+        assert_eq!(explain("BEFORE gGuid3", &[]), Proof::Before(guid(3)));
+        assert_eq!(explain("AFTER gGuid4", &[guid(4)]), Proof::After(guid(4)));
+        assert_eq!(explain("SOR gGuid5", &[guid(5)]), Proof::Sor(protocol(5, true)));
+
+        for source in ["BEFORE gGuid3", "AFTER gGuid4", "SOR gGuid5"] {
+            let proof = explain(source, &[guid(3), guid(4), guid(5)]);
+            assert!(!proof.value(), "{source}");
+            assert_eq!(proof.minimal_missing(), None, "{source}");
+        }
+
+        let mut depex = Depex::from(compile("SOR gGuid5", &symbols()).unwrap());
+        depex.schedule();
+        assert_eq!(depex.explain(&[guid(5)]), *protocol(5, true));
+    }
+
+    #[test]
+    fn explain_should_report_invalid_opcodes() {
+        let explain_bytes = |bytes: &[u8]| Depex::from(bytes).explain(&[]);
+        assert_eq!(explain_bytes(&[0x03, 0x08]), Proof::Invalid { index: 0 });
+        assert_eq!(explain_bytes(&[0x06, 0x05]), Proof::Invalid { index: 2 });
+        assert_eq!(explain_bytes(&[0x06, 0x09, 0x08]), Proof::Invalid { index: 1 });
+        assert_eq!(explain_bytes(&[0x06, 0xff, 0x08]), Proof::Invalid { index: 1 });
+        assert_eq!(explain_bytes(&[0x08]), Proof::Invalid { index: 0 });
+        assert_eq!(explain_bytes(&[0x09, 0x08]), Proof::Invalid { index: 1 });
+        assert_eq!(Proof::Invalid { index: 0 }.minimal_missing(), None);
+    }
+
+    #[test]
+    fn minimal_missing_should_pick_the_smallest_alternative() {
+        let proof = explain("(gGuid0 AND gGuid1) OR gGuid2", &[]);
+        assert_eq!(proof.missing(), vec![guid(0), guid(1), guid(2)]);
+        assert_eq!(proof.minimal_missing(), Some(vec![guid(2)]));
+
+        assert_eq!(explain("gGuid0 AND NOT gGuid1", &[guid(0)]).minimal_missing(), Some(vec![]));
+        assert_eq!(explain("gGuid0 AND NOT gGuid0", &[]).minimal_missing(), None);
+        assert_eq!(explain("FALSE OR gGuid1", &[]).minimal_missing(), Some(vec![guid(1)]));
+        assert_eq!(explain("FALSE", &[]).minimal_missing(), None);
+    }
+
+    #[test]
+    fn minimal_missing_should_fall_back_to_pruning_for_large_expressions() {
+        let source = (0..20).map(|index| format!("gGuid{index}")).collect::<Vec<_>>().join(" AND ");
+        let proof = explain(&format!("({source}) OR gGuid19"), &[guid(0)]);
+        assert_eq!(proof.minimal_missing(), Some(vec![guid(19)]));
+
+        let proof = explain(&format!("({source}) AND NOT gGuid7"), &[]);
+        assert_eq!(proof.minimal_missing(), None);
+    }
+
+    #[test]
+    fn display_should_indent_operands() {
+        let proof = explain("SOR NOT gGuid1 AND TRUE", &[guid(1)]);
+        let expected = format!(
+            "SOR, not scheduled => false\n  AND => false\n    NOT => false\n      {} present\n    TRUE\n",
+            Uuid::from_bytes_le(*guid(1).as_bytes())
+        );
+        assert_eq!(format!("{proof}"), expected);
+    }
+
+    #[test]
+    fn explained_values_should_match_eval() {
+        let mut rng = StdRng::seed_from_u64(0xe4a1);
+        for _ in 0..300 {
+            let source = random_expression(&mut rng, 4);
+            let bytes = compile(&source, &symbols()).unwrap();
+            let protocols: Vec<_> = (0..6).filter(|_| rng.gen_bool(0.5)).map(guid).collect();
+            let proof = Depex::from(bytes.as_slice()).explain(&protocols);
+            assert_eq!(proof.value(), Depex::from(bytes.as_slice()).eval(&protocols), "{source}");
+
+            if let Some(minimal) = proof.minimal_missing() {
+                let mut installed = protocols.clone();
+                installed.extend(minimal.iter().copied());
+                assert!(Depex::from(bytes.as_slice()).eval(&installed), "{source}");
+                for skipped in &minimal {
+                    let fewer: Vec<_> = installed.iter().filter(|guid| *guid != skipped).copied().collect();
+                    assert!(!Depex::from(bytes.as_slice()).eval(&fewer), "{source}");
+                }
+            }
+        }
+    }
+
+    fn random_expression(rng: &mut StdRng, depth: u32) -> String {
+        match rng.gen_range(0..if depth == 0 { 3 } else { 6 }) {
+            0 => format!("gGuid{}", rng.gen_range(0..6)),
+            1 => "TRUE".into(),
+            2 => "FALSE".into(),
+            3 => format!("NOT ({})", random_expression(rng, depth - 1)),
+            4 => format!("({}) AND ({})", random_expression(rng, depth - 1), random_expression(rng, depth - 1)),
+            _ => format!("({}) OR ({})", random_expression(rng, depth - 1), random_expression(rng, depth - 1)),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! UEFI Dependency Expression (DEPEX) support
 //!
-//! This module provides a parser and evaluator for UEFI dependency expressions.
+//! This module provides a parser and evaluator for UEFI dependency expressions. The [text] module translates them
+//! to and from the EDK II text grammar, and the [explain] module reports why an expression is not satisfied.
 //!
 //! ## License
 //!
@@ -18,6 +19,9 @@ use core::mem;
 use r_efi::efi;
 use uuid::Uuid;
 
+pub mod explain;
+pub mod text;
+
 /// The size of a GUID in bytes
 const GUID_SIZE: usize = mem::size_of::<r_efi::efi::Guid>();
 
@@ -149,6 +153,11 @@ impl From<&[Opcode]> for Depex {
 }
 
 impl Depex {
+    /// The opcodes of the expression, in order.
+    pub fn opcodes(&self) -> &[Opcode] {
+        &self.expression
+    }
+
     /// Evaluates a DEPEX expression.
     pub fn eval(&mut self, protocols: &[efi::Guid]) -> bool {
         let mut stack = Vec::with_capacity(DEPEX_STACK_SIZE_INCREMENT);
@@ -279,6 +288,14 @@ impl Depex {
         false
     }
 
+    /// Evaluates the expression into a proof tree that records why it is, or is not, satisfied.
+    ///
+    /// The value of the tree matches [Depex::eval] for well formed expressions. Unlike [Depex::eval], this does not
+    /// cache the protocols found present.
+    pub fn explain(&self, protocols: &[efi::Guid]) -> explain::Proof {
+        explain::explain(&self.expression, protocols)
+    }
+
     /// If the depex expression is an associated dependency, it returns the associated dependency.
     pub fn is_associated(&self) -> Option<AssociatedDependency> {
         match self.expression.first() {
@@ -288,6 +305,27 @@ impl Depex {
         }
     }
 
+    /// Returns the GUIDs the expression waits on that are not in `protocols`, in expression order.
+    ///
+    /// These are the pushed protocols that are not installed, and the driver named by a BEFORE or AFTER opcode. The
+    /// result does not say whether installing them would satisfy the expression, as NOT or OR may be involved.
+    pub fn unsatisfied_guids(&self, protocols: &[efi::Guid]) -> Vec<efi::Guid> {
+        let mut unsatisfied = Vec::new();
+        for opcode in &self.expression {
+            let guid = match opcode {
+                Opcode::Push(uuid, false) => guid_from_uuid(uuid).filter(|guid| !protocols.contains(guid)),
+                Opcode::Before(uuid) | Opcode::After(uuid) => guid_from_uuid(uuid),
+                _ => None,
+            };
+            if let Some(guid) = guid
+                && !unsatisfied.contains(&guid)
+            {
+                unsatisfied.push(guid);
+            }
+        }
+        unsatisfied
+    }
+
     /// indicates that this is a "schedule on request" depex.
     pub fn is_sor(&self) -> bool {
         self.expression.first() == Some(&Opcode::Sor)
@@ -671,6 +709,27 @@ mod tests {
         assert!(!depex.eval(&[]));
     }
 
+    #[test]
+    fn unsatisfied_guids_should_list_missing_pushed_guids_once() {
+        let installed = Uuid::from_str("76b6bdfa-2acd-4462-9e3f-cb58c969d937").unwrap();
+        let missing = Uuid::from_str("0379be4e-d706-437d-b037-edb82fb772a4").unwrap();
+        let opcodes = [
+            Opcode::Push(missing, false),
+            Opcode::Push(installed, false),
+            Opcode::And,
+            Opcode::Push(missing, false),
+            Opcode::Or,
+            Opcode::End,
+        ];
+        let depex = Depex::from(opcodes.as_slice());
+        let installed = guid_from_uuid(&installed).unwrap();
+        assert_eq!(depex.unsatisfied_guids(&[installed]), vec![guid_from_uuid(&missing).unwrap()]);
+        assert!(depex.unsatisfied_guids(&[installed, guid_from_uuid(&missing).unwrap()]).is_empty());
+
+        let depex = Depex::from([Opcode::After(missing), Opcode::End].as_slice());
+        assert_eq!(depex.unsatisfied_guids(&[installed]), vec![guid_from_uuid(&missing).unwrap()]);
+    }
+
     #[test]
     fn opcode_before_should_panic_when_not_at_start_of_depex() {
         let opcodes = [Opcode::And, Opcode::Before(Uuid::from_str("76b6bdfa-2acd-4462-9e3f-cb58c969d937").unwrap())];
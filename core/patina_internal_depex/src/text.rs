@@ -0,0 +1,610 @@
+//! Text form of dependency expressions
+//!
+//! [compile] translates the EDK II `[Depex]` text grammar into binary dependency expressions, and [disassemble]
+//! translates opcode streams back into text. The grammar is:
+//!
+//! ```text
+//! depex   := "BEFORE" guid ["END"] | "AFTER" guid ["END"] | ["SOR"] expr ["END"]
+//! expr    := unary (("AND" | "OR") unary)*
+//! unary   := "NOT" unary | operand
+//! operand := "TRUE" | "FALSE" | guid | "(" expr ")"
+//! guid    := C name from the symbol table | registry format GUID (`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`)
+//! ```
+//!
+//! As in the EDK II tools, `AND` and `OR` have the same precedence and associate to the left, so
+//! `gA OR gB AND gC` is `(gA OR gB) AND gC`. The disassembler adds parentheses wherever operators differ to keep the
+//! text unambiguous to the reader.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, ops::Range};
+use r_efi::efi;
+use uuid::Uuid;
+
+use crate::{Opcode, guid_from_uuid};
+
+/// Maps GUID C names such as `gEfiVariableArchProtocolGuid` to their values.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, efi::Guid>,
+}
+
+impl SymbolTable {
+    /// Creates an empty symbol table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `name` for `guid`, returning the GUID previously associated with `name`, if any.
+    pub fn insert(&mut self, name: impl Into<String>, guid: efi::Guid) -> Option<efi::Guid> {
+        self.symbols.insert(name.into(), guid)
+    }
+
+    /// Returns the GUID named `name`.
+    pub fn get(&self, name: &str) -> Option<efi::Guid> {
+        self.symbols.get(name).copied()
+    }
+
+    /// Returns a name of `guid`, if it has one.
+    pub fn name_of(&self, guid: &efi::Guid) -> Option<&str> {
+        self.symbols.iter().find(|(_, value)| *value == guid).map(|(name, _)| name.as_str())
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, efi::Guid)> for SymbolTable {
+    fn from_iter<I: IntoIterator<Item = (S, efi::Guid)>>(iter: I) -> Self {
+        Self { symbols: iter.into_iter().map(|(name, guid)| (name.into(), guid)).collect() }
+    }
+}
+
+/// The reason a dependency expression failed to compile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileErrorKind {
+    /// A character that cannot start a token.
+    UnexpectedCharacter(char),
+    /// A name that is not in the symbol table.
+    UnknownSymbol(String),
+    /// A word that is neither a keyword, a C name nor a registry format GUID.
+    InvalidGuid(String),
+    /// A token, or the end of the expression, was found where the given element was expected.
+    Expected(&'static str),
+}
+
+/// An error compiling a dependency expression, with the byte range of the source it applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    /// The reason for the error.
+    pub kind: CompileErrorKind,
+    /// The byte range of the source that caused the error. Empty at the end of the source for a truncated expression.
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            CompileErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character `{c}`")?,
+            CompileErrorKind::UnknownSymbol(name) => write!(f, "unknown GUID name `{name}`")?,
+            CompileErrorKind::InvalidGuid(word) => write!(f, "`{word}` is not a GUID name or value")?,
+            CompileErrorKind::Expected(expected) => write!(f, "expected {expected}")?,
+        }
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Before,
+    After,
+    Sor,
+    End,
+    And,
+    Or,
+    Not,
+    True,
+    False,
+    Open,
+    Close,
+    Word(&'a str),
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token<'_>, Range<usize>)>, CompileError> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if is_word(c) => {
+                let mut end = start + c.len_utf8();
+                while let Some((index, c)) = chars.next_if(|(_, c)| is_word(*c)) {
+                    end = index + c.len_utf8();
+                }
+                let word = &source[start..end];
+                let token = match word {
+                    "BEFORE" => Token::Before,
+                    "AFTER" => Token::After,
+                    "SOR" => Token::Sor,
+                    "END" => Token::End,
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    "TRUE" => Token::True,
+                    "FALSE" => Token::False,
+                    _ => Token::Word(word),
+                };
+                tokens.push((token, start..end));
+                continue;
+            }
+            c => {
+                let span = start..start + c.len_utf8();
+                return Err(CompileError { kind: CompileErrorKind::UnexpectedCharacter(c), span });
+            }
+        };
+        tokens.push((token, start..start + 1));
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token<'a>, Range<usize>)>,
+    position: usize,
+    end: usize,
+    symbols: &'a SymbolTable,
+    opcodes: Vec<Opcode>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).map(|(token, _)| *token)
+    }
+
+    fn span(&self) -> Range<usize> {
+        self.tokens.get(self.position).map(|(_, span)| span.clone()).unwrap_or(self.end..self.end)
+    }
+
+    fn expected(&self, expected: &'static str) -> CompileError {
+        CompileError { kind: CompileErrorKind::Expected(expected), span: self.span() }
+    }
+
+    fn depex(&mut self) -> Result<(), CompileError> {
+        match self.peek() {
+            Some(token @ (Token::Before | Token::After)) => {
+                self.position += 1;
+                let guid = self.guid()?;
+                self.opcodes.push(if token == Token::Before { Opcode::Before(guid) } else { Opcode::After(guid) });
+            }
+            Some(Token::Sor) => {
+                self.position += 1;
+                self.opcodes.push(Opcode::Sor);
+                self.expr()?;
+            }
+            _ => self.expr()?,
+        }
+        if self.peek() == Some(Token::End) {
+            self.position += 1;
+        }
+        if self.position != self.tokens.len() {
+            return Err(self.expected("the end of the expression"));
+        }
+        self.opcodes.push(Opcode::End);
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<(), CompileError> {
+        self.unary()?;
+        while let Some(token @ (Token::And | Token::Or)) = self.peek() {
+            self.position += 1;
+            self.unary()?;
+            self.opcodes.push(if token == Token::And { Opcode::And } else { Opcode::Or });
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self) -> Result<(), CompileError> {
+        if self.peek() == Some(Token::Not) {
+            self.position += 1;
+            self.unary()?;
+            self.opcodes.push(Opcode::Not);
+            return Ok(());
+        }
+        self.operand()
+    }
+
+    fn operand(&mut self) -> Result<(), CompileError> {
+        match self.peek() {
+            Some(Token::True) => {
+                self.position += 1;
+                self.opcodes.push(Opcode::True);
+            }
+            Some(Token::False) => {
+                self.position += 1;
+                self.opcodes.push(Opcode::False);
+            }
+            Some(Token::Open) => {
+                self.position += 1;
+                self.expr()?;
+                if self.peek() != Some(Token::Close) {
+                    return Err(self.expected("`)`"));
+                }
+                self.position += 1;
+            }
+            Some(Token::Word(_)) => {
+                let guid = self.guid()?;
+                self.opcodes.push(Opcode::Push(guid, false));
+            }
+            _ => return Err(self.expected("a GUID, TRUE, FALSE, NOT or `(`")),
+        }
+        Ok(())
+    }
+
+    fn guid(&mut self) -> Result<Uuid, CompileError> {
+        let Some(Token::Word(word)) = self.peek() else {
+            return Err(self.expected("a GUID"));
+        };
+        let span = self.span();
+        self.position += 1;
+
+        if let Some(guid) = self.symbols.get(word) {
+            return Ok(Uuid::from_bytes_le(*guid.as_bytes()));
+        }
+        if word.len() == 36
+            && word.chars().enumerate().all(|(index, c)| match index {
+                8 | 13 | 18 | 23 => c == '-',
+                _ => c.is_ascii_hexdigit(),
+            })
+            && let Ok(uuid) = Uuid::try_parse(word)
+        {
+            return Ok(uuid);
+        }
+        let is_name = word.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && !word.contains('-');
+        let kind = if is_name {
+            CompileErrorKind::UnknownSymbol(word.to_string())
+        } else {
+            CompileErrorKind::InvalidGuid(word.to_string())
+        };
+        Err(CompileError { kind, span })
+    }
+}
+
+/// Compiles a dependency expression in the EDK II text grammar into its binary form.
+///
+/// GUID names are resolved with `symbols`, and registry format GUIDs may be used directly. An `END` opcode is added to
+/// the expression if the source does not end with one.
+///
+/// ## Example
+///
+/// ```rust
+/// use patina_internal_depex::{Depex, text::{SymbolTable, compile}};
+/// use r_efi::efi;
+///
+/// let variable_arch = efi::Guid::from_fields(0x1e5668e2, 0x8481, 0x11d4, 0xbc, 0xf1, &[0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81]);
+/// let symbols = SymbolTable::from_iter([("gEfiVariableArchProtocolGuid", variable_arch)]);
+///
+/// let bytes = compile("gEfiVariableArchProtocolGuid AND NOT FALSE", &symbols).unwrap();
+/// assert_eq!(bytes.len(), 1 + 16 + 4);
+/// assert!(Depex::from(bytes).eval(&[variable_arch]));
+///
+/// let error = compile("gEfiVariableArchProtocolGuid AND gMissingGuid", &symbols).unwrap_err();
+/// assert_eq!(error.span, 33..45);
+/// ```
+pub fn compile(source: &str, symbols: &SymbolTable) -> Result<Vec<u8>, CompileError> {
+    let mut parser = Parser { tokens: tokenize(source)?, position: 0, end: source.len(), symbols, opcodes: Vec::new() };
+    parser.depex()?;
+
+    let mut bytes = Vec::new();
+    for opcode in &parser.opcodes {
+        let (byte, guid) = match opcode {
+            Opcode::Before(guid) => (0x00, Some(guid)),
+            Opcode::After(guid) => (0x01, Some(guid)),
+            Opcode::Push(guid, _) => (0x02, Some(guid)),
+            Opcode::And => (0x03, None),
+            Opcode::Or => (0x04, None),
+            Opcode::Not => (0x05, None),
+            Opcode::True => (0x06, None),
+            Opcode::False => (0x07, None),
+            Opcode::End => (0x08, None),
+            Opcode::Sor => (0x09, None),
+            Opcode::Unknown | Opcode::Malformed { .. } => unreachable!("not produced by the parser"),
+        };
+        bytes.push(byte);
+        if let Some(guid) = guid {
+            bytes.extend_from_slice(&guid.to_bytes_le());
+        }
+    }
+    Ok(bytes)
+}
+
+/// An opcode stream that cannot be expressed in the text grammar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisassembleError {
+    /// The index of the opcode that cannot be expressed, or the length of the stream if it ends early.
+    pub index: usize,
+}
+
+impl fmt::Display for DisassembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "opcode {} cannot be expressed in text", self.index)
+    }
+}
+
+/// An operand of the expression being disassembled, with the binary operator at its root, if any.
+struct Operand {
+    text: String,
+    operator: Option<Opcode>,
+}
+
+/// Disassembles an opcode stream into the EDK II text grammar.
+///
+/// GUIDs are written with their name from `symbols`, or in registry format if they have none. The result compiles
+/// back into the same opcodes. Streams that do not form a single well formed expression terminated by `END` cannot be
+/// disassembled.
+///
+/// ## Example
+///
+/// ```rust
+/// use patina_internal_depex::{Depex, text::{SymbolTable, disassemble}};
+///
+/// let depex = Depex::from(vec![0x06, 0x07, 0x05, 0x04, 0x06, 0x03, 0x08]);
+/// assert_eq!(disassemble(depex.opcodes(), &SymbolTable::new()).unwrap(), "(TRUE OR NOT FALSE) AND TRUE");
+/// ```
+pub fn disassemble(opcodes: &[Opcode], symbols: &SymbolTable) -> Result<String, DisassembleError> {
+    let guid_text = |uuid: &Uuid| match guid_from_uuid(uuid).and_then(|guid| symbols.name_of(&guid)) {
+        Some(name) => name.to_string(),
+        None => uuid.to_string(),
+    };
+
+    let (prefix, body) = match opcodes {
+        [Opcode::Before(uuid), Opcode::End] => return Ok(format!("BEFORE {}", guid_text(uuid))),
+        [Opcode::After(uuid), Opcode::End] => return Ok(format!("AFTER {}", guid_text(uuid))),
+        [Opcode::Sor, body @ ..] => ("SOR ", body),
+        body => ("", body),
+    };
+    let offset = opcodes.len() - body.len();
+
+    let mut stack: Vec<Operand> = Vec::new();
+    for (index, opcode) in body.iter().enumerate() {
+        let error = DisassembleError { index: offset + index };
+        let operand = match opcode {
+            Opcode::Push(uuid, _) => Operand { text: guid_text(uuid), operator: None },
+            Opcode::True => Operand { text: "TRUE".to_string(), operator: None },
+            Opcode::False => Operand { text: "FALSE".to_string(), operator: None },
+            Opcode::Not => {
+                let operand = stack.pop().ok_or(error)?;
+                let text = match operand.operator {
+                    Some(_) => format!("NOT ({})", operand.text),
+                    None => format!("NOT {}", operand.text),
+                };
+                Operand { text, operator: None }
+            }
+            Opcode::And | Opcode::Or => {
+                let right = stack.pop().ok_or(error.clone())?;
+                let left = stack.pop().ok_or(error)?;
+                let keyword = if *opcode == Opcode::And { "AND" } else { "OR" };
+                // Operators associate to the left, so a right operand with an operator always needs parentheses.
+                let left = match &left.operator {
+                    Some(operator) if operator != opcode => format!("({})", left.text),
+                    _ => left.text,
+                };
+                let right = match right.operator {
+                    Some(_) => format!("({})", right.text),
+                    None => right.text,
+                };
+                Operand { text: format!("{left} {keyword} {right}"), operator: Some(opcode.clone()) }
+            }
+            Opcode::End if index == body.len() - 1 && stack.len() == 1 => {
+                return Ok(format!("{prefix}{}", stack.pop().unwrap().text));
+            }
+            _ => return Err(error),
+        };
+        stack.push(operand);
+    }
+    Err(DisassembleError { index: opcodes.len() })
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::Depex;
+    use alloc::{boxed::Box, vec, vec::Vec};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    fn guid(index: u8) -> efi::Guid {
+        efi::Guid::from_fields(
+            0x1e5668e2 + index as u32,
+            0x8481,
+            0x11d4,
+            0xbc,
+            0xf1,
+            &[0, 0x80, 0xc7, 0x3c, 0x88, index],
+        )
+    }
+
+    fn symbols() -> SymbolTable {
+        (0..8).map(|index| (format!("gGuid{index}"), guid(index))).collect()
+    }
+
+    fn push(index: u8) -> Opcode {
+        Opcode::Push(Uuid::from_bytes_le(*guid(index).as_bytes()), false)
+    }
+
+    fn opcodes(source: &str) -> Vec<Opcode> {
+        Depex::from(compile(source, &symbols()).unwrap()).opcodes().to_vec()
+    }
+
+    fn error(source: &str) -> (CompileErrorKind, Range<usize>) {
+        let error = compile(source, &symbols()).unwrap_err();
+        (error.kind, error.span)
+    }
+
+    #[test]
+    fn compile_should_follow_edk2_precedence() {
+        assert_eq!(opcodes("gGuid0"), vec![push(0), Opcode::End]);
+        assert_eq!(
+            opcodes("gGuid0 OR gGuid1 AND gGuid2"),
+            vec![push(0), push(1), Opcode::Or, push(2), Opcode::And, Opcode::End]
+        );
+        assert_eq!(
+            opcodes("gGuid0 AND (gGuid1 OR NOT gGuid2) END"),
+            vec![push(0), push(1), push(2), Opcode::Not, Opcode::Or, Opcode::And, Opcode::End]
+        );
+        assert_eq!(
+            opcodes("NOT NOT (TRUE)\n\tAND FALSE"),
+            vec![Opcode::True, Opcode::Not, Opcode::Not, Opcode::False, Opcode::And, Opcode::End]
+        );
+    }
+
+    #[test]
+    fn compile_should_support_sor_before_and_after() {
+        assert_eq!(opcodes("SOR gGuid1"), vec![Opcode::Sor, push(1), Opcode::End]);
+        let Opcode::Push(uuid, _) = push(3) else { unreachable!() };
+        assert_eq!(opcodes("BEFORE gGuid3"), vec![Opcode::Before(uuid), Opcode::End]);
+        assert_eq!(opcodes(&format!("AFTER {uuid} END")), vec![Opcode::After(uuid), Opcode::End]);
+        assert_eq!(opcodes(&uuid.to_string().to_uppercase()), vec![push(3), Opcode::End]);
+    }
+
+    #[test]
+    fn compile_errors_should_have_precise_spans() {
+        assert_eq!(error("gGuid0 AND gMissing"), (CompileErrorKind::UnknownSymbol("gMissing".into()), 11..19));
+        assert_eq!(error("gGuid0 & gGuid1"), (CompileErrorKind::UnexpectedCharacter('&'), 7..8));
+        assert_eq!(error("gGuid0 AND 1234-5678"), (CompileErrorKind::InvalidGuid("1234-5678".into()), 11..20));
+        assert_eq!(error("(gGuid0 OR gGuid1"), (CompileErrorKind::Expected("`)`"), 17..17));
+        assert_eq!(error("gGuid0 AND"), (CompileErrorKind::Expected("a GUID, TRUE, FALSE, NOT or `(`"), 10..10));
+        assert_eq!(error("gGuid0 gGuid1"), (CompileErrorKind::Expected("the end of the expression"), 7..13));
+        assert_eq!(
+            error("gGuid0 AND BEFORE gGuid1"),
+            (CompileErrorKind::Expected("a GUID, TRUE, FALSE, NOT or `(`"), 11..17)
+        );
+        assert_eq!(
+            error("BEFORE gGuid0 AND gGuid1"),
+            (CompileErrorKind::Expected("the end of the expression"), 14..17)
+        );
+        assert_eq!(error("AFTER TRUE"), (CompileErrorKind::Expected("a GUID"), 6..10));
+        assert_eq!(error("gGuid0 END SOR"), (CompileErrorKind::Expected("the end of the expression"), 11..14));
+        assert_eq!(error(""), (CompileErrorKind::Expected("a GUID, TRUE, FALSE, NOT or `(`"), 0..0));
+
+        let error = compile("gGuid0 AND gMissing", &symbols()).unwrap_err();
+        assert_eq!(error.to_string(), "unknown GUID name `gMissing` at 11..19");
+    }
+
+    #[test]
+    fn disassemble_should_use_names_and_minimal_parentheses() {
+        let symbols = symbols();
+        let disassemble = |source: &str| disassemble(&opcodes(source), &symbols).unwrap();
+        assert_eq!(disassemble("gGuid0 AND (gGuid1 OR NOT gGuid2)"), "gGuid0 AND (gGuid1 OR NOT gGuid2)");
+        assert_eq!(disassemble("gGuid0 OR gGuid1 AND gGuid2"), "(gGuid0 OR gGuid1) AND gGuid2");
+        assert_eq!(disassemble("gGuid0 AND gGuid1 AND (gGuid2 AND TRUE)"), "gGuid0 AND gGuid1 AND (gGuid2 AND TRUE)");
+        assert_eq!(disassemble("NOT (gGuid0 OR FALSE)"), "NOT (gGuid0 OR FALSE)");
+        assert_eq!(disassemble("SOR gGuid0"), "SOR gGuid0");
+        assert_eq!(disassemble("BEFORE gGuid7"), "BEFORE gGuid7");
+        assert_eq!(disassemble(&Uuid::from_u128(0x1234).to_string()), "00000000-0000-0000-0000-000000001234");
+    }
+
+    #[test]
+    fn disassemble_should_reject_malformed_streams() {
+        let symbols = symbols();
+        assert_eq!(disassemble(&[Opcode::And, Opcode::End], &symbols), Err(DisassembleError { index: 0 }));
+        assert_eq!(
+            disassemble(&[Opcode::True, Opcode::True, Opcode::End], &symbols),
+            Err(DisassembleError { index: 2 })
+        );
+        assert_eq!(disassemble(&[Opcode::True], &symbols), Err(DisassembleError { index: 1 }));
+        assert_eq!(
+            disassemble(&[Opcode::True, Opcode::Sor, Opcode::End], &symbols),
+            Err(DisassembleError { index: 1 })
+        );
+        assert_eq!(
+            disassemble(&[Opcode::True, Opcode::End, Opcode::True], &symbols),
+            Err(DisassembleError { index: 1 })
+        );
+        assert_eq!(disassemble(&[Opcode::Unknown], &symbols), Err(DisassembleError { index: 0 }));
+    }
+
+    /// A dependency expression tree, the reference for the compiled expression.
+    #[derive(Debug)]
+    enum Expr {
+        Guid(u8),
+        True,
+        False,
+        Not(Box<Expr>),
+        And(Box<Expr>, Box<Expr>),
+        Or(Box<Expr>, Box<Expr>),
+    }
+
+    impl Expr {
+        fn random(rng: &mut StdRng, depth: u32) -> Self {
+            match rng.gen_range(0..if depth == 0 { 3 } else { 6 }) {
+                0 => Expr::Guid(rng.gen_range(0..8)),
+                1 => Expr::True,
+                2 => Expr::False,
+                3 => Expr::Not(Box::new(Self::random(rng, depth - 1))),
+                4 => Expr::And(Box::new(Self::random(rng, depth - 1)), Box::new(Self::random(rng, depth - 1))),
+                _ => Expr::Or(Box::new(Self::random(rng, depth - 1)), Box::new(Self::random(rng, depth - 1))),
+            }
+        }
+
+        /// Writes the expression fully parenthesized, so that its meaning does not depend on precedence.
+        fn text(&self) -> String {
+            match self {
+                Expr::Guid(index) => format!("gGuid{index}"),
+                Expr::True => "TRUE".to_string(),
+                Expr::False => "FALSE".to_string(),
+                Expr::Not(operand) => format!("NOT ({})", operand.text()),
+                Expr::And(left, right) => format!("({}) AND ({})", left.text(), right.text()),
+                Expr::Or(left, right) => format!("({}) OR ({})", left.text(), right.text()),
+            }
+        }
+
+        fn eval(&self, installed: &[efi::Guid]) -> bool {
+            match self {
+                Expr::Guid(index) => installed.contains(&guid(*index)),
+                Expr::True => true,
+                Expr::False => false,
+                Expr::Not(operand) => !operand.eval(installed),
+                Expr::And(left, right) => left.eval(installed) && right.eval(installed),
+                Expr::Or(left, right) => left.eval(installed) || right.eval(installed),
+            }
+        }
+    }
+
+    #[test]
+    fn compiled_expressions_should_eval_like_the_reference_evaluator() {
+        let symbols = symbols();
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..500 {
+            let expr = Expr::random(&mut rng, 5);
+            let bytes = compile(&expr.text(), &symbols).unwrap();
+            for _ in 0..8 {
+                let installed: Vec<_> = (0..8).filter(|_| rng.gen_bool(0.5)).map(guid).collect();
+                assert_eq!(Depex::from(bytes.as_slice()).eval(&installed), expr.eval(&installed), "{}", expr.text());
+            }
+        }
+    }
+
+    #[test]
+    fn disassembled_expressions_should_compile_to_the_same_bytes() {
+        let symbols = symbols();
+        let mut rng = StdRng::seed_from_u64(0xd15a);
+        for _ in 0..500 {
+            let text = Expr::random(&mut rng, 5).text();
+            let text = if rng.gen_bool(0.2) { format!("SOR {text}") } else { text };
+            let bytes = compile(&text, &symbols).unwrap();
+            let disassembled = disassemble(Depex::from(bytes.as_slice()).opcodes(), &symbols).unwrap();
+            assert_eq!(compile(&disassembled, &symbols).unwrap(), bytes, "{text} -> {disassembled}");
+        }
+    }
+}
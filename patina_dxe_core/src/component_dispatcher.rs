@@ -10,11 +10,18 @@
 //!
 extern crate alloc;
 
-use crate::tpl_mutex::TplMutex;
+use crate::{dispatch_report, tpl_mutex::TplMutex};
 use patina::{
+    Guid,
     boot_services::StandardBootServices,
-    component::{IntoComponent, Storage, service::IntoService},
-    pi::hob::HobList,
+    component::{
+        IntoComponent, Storage,
+        service::{
+            IntoService,
+            dispatch_report::{DispatchKind, Requirement, UndispatchedComponent},
+        },
+    },
+    pi::hob::{HobList, HobTrait},
     runtime_services::StandardRuntimeServices,
 };
 use r_efi::efi;
@@ -105,6 +112,8 @@ pub(crate) struct ComponentDispatcher {
     components: Vec<Box<dyn patina::component::Component>>,
     /// Components that failed to initialize and are not ready for dispatch attempts.
     rejected: Vec<Box<dyn patina::component::Component>>,
+    /// Event groups that have been signalled, releasing the components they trigger for dispatch.
+    signalled: Vec<efi::Guid>,
     /// Storage for components to use during execution.
     storage: Storage,
 }
@@ -128,7 +137,7 @@ impl ComponentDispatcher {
     /// Creates a new ComponentDispatcher.
     #[inline(always)]
     pub(crate) const fn new() -> Self {
-        Self { components: Vec::new(), rejected: Vec::new(), storage: Storage::new() }
+        Self { components: Vec::new(), rejected: Vec::new(), signalled: Vec::new(), storage: Storage::new() }
     }
 
     /// Applies the component information provided by the given type implementing [ComponentInfo].
@@ -174,7 +183,7 @@ impl ComponentDispatcher {
         self.storage.set_runtime_services(rs);
     }
 
-    /// Parses the HOB list producing a `Hob\<T\>` struct for each guided HOB found with a registered parser.
+    /// Parses the HOB list producing a `Hob\<T\>` struct for each HOB found with a registered parser.
     pub(crate) fn insert_hobs(&mut self, hob_list: &HobList<'_>) {
         for hob in hob_list.iter() {
             if let patina::pi::hob::Hob::GuidHob(guid, data) = hob {
@@ -194,31 +203,84 @@ impl ComponentDispatcher {
                         parser_func(data, &mut self.storage);
                     }
                 }
+            } else {
+                // Other HOB types have no GUID; their parsers are registered for the HOB type and see the whole HOB.
+                let parser_funcs = self.storage.get_hob_type_parsers(hob.header().r#type);
+                if !parser_funcs.is_empty() {
+                    // SAFETY: The HOB list references valid HOBs of the size reported for their type.
+                    let data = unsafe { core::slice::from_raw_parts(hob.as_ptr::<u8>(), hob.size()) };
+                    for parser_func in parser_funcs {
+                        parser_func(data, &mut self.storage);
+                    }
+                }
             }
         }
     }
 
+    /// Returns the event groups that trigger a component and have not been signalled yet, without duplicates.
+    pub(crate) fn pending_triggers(&self) -> Vec<efi::Guid> {
+        let mut triggers = Vec::new();
+        for trigger in self.components.iter().filter_map(|component| self.waiting_on(component.as_ref())) {
+            if !triggers.contains(&trigger) {
+                triggers.push(trigger);
+            }
+        }
+        triggers
+    }
+
+    /// Records that `event_group` was signalled and dispatches until no further component can be dispatched.
+    ///
+    /// Returns `true` if at least one component was dispatched.
+    pub(crate) fn signal_event_group(&mut self, event_group: efi::Guid) -> bool {
+        if !self.signalled.contains(&event_group) {
+            self.signalled.push(event_group);
+        }
+        let mut dispatched = false;
+        while self.dispatch() {
+            dispatched = true;
+        }
+        dispatched
+    }
+
+    /// Returns the event group `component` is waiting on, if it has a trigger that has not been signalled.
+    fn waiting_on(&self, component: &dyn patina::component::Component) -> Option<efi::Guid> {
+        component.metadata().trigger().filter(|trigger| !self.signalled.contains(trigger))
+    }
+
     /// Attempts to dispatch all components.
     ///
-    /// This method will perform a single pass over all registered components, attempting to run each one.
+    /// This method will perform a single pass over all registered components, attempting to run each one. Components
+    /// whose triggering event group has not been signalled are skipped.
     ///
     /// Returns `true` if at least one component was successfully dispatched, `false` otherwise.
     pub(crate) fn dispatch(&mut self) -> bool {
         let len = self.components.len();
+        let signalled = &self.signalled;
         self.components.retain_mut(|component| {
+            if component.metadata().trigger().is_some_and(|trigger| !signalled.contains(&trigger)) {
+                return true;
+            }
             // Ok(true): Dispatchable and dispatched returning success
             // Ok(false): Not dispatchable at this time.
             // Err(e): Dispatchable and dispatched returning failure
             let name = component.metadata().name();
             log::trace!("Dispatch Start: Id = [{name:?}]");
+            let timestamp = dispatch_report::timestamp();
             !match component.run(&mut self.storage) {
                 Ok(true) => {
                     log::info!("Dispatched: Id = [{name:?}] Status = [Success]");
+                    dispatch_report::record_dispatch(
+                        DispatchKind::Component,
+                        name.into_owned(),
+                        timestamp,
+                        efi::Status::SUCCESS,
+                    );
                     true
                 }
                 Ok(false) => false,
                 Err(err) => {
                     log::error!("Dispatched: Id = [{name:?}] Status = [Failed] Error = [{err:?}]");
+                    dispatch_report::record_dispatch(DispatchKind::Component, name.into_owned(), timestamp, err.into());
                     debug_assert!(false);
                     true // Component dispatched, even if it did fail, so remove from self.components to avoid re-dispatch.
                 }
@@ -227,18 +289,48 @@ impl ComponentDispatcher {
         len != self.components.len()
     }
 
+    /// Returns all components that were not dispatched, with the requirements that kept them from being dispatched.
+    pub(crate) fn undispatched_components(&self) -> Vec<UndispatchedComponent> {
+        // Components that failed to initialize are not waiting on their trigger, as they are never dispatched.
+        self.components
+            .iter()
+            .map(|component| (component, self.waiting_on(component.as_ref())))
+            .chain(self.rejected.iter().map(|component| (component, None)))
+            .map(|(component, trigger)| {
+                let metadata = component.metadata();
+                let mut unmet = metadata.unmet_params().to_vec();
+                // Components that failed to initialize only have an error message.
+                if unmet.is_empty()
+                    && let Some(message) = metadata.error_message()
+                {
+                    unmet.push(Requirement::Other(message.into_owned()));
+                }
+                if let Some(trigger) = trigger {
+                    unmet.push(Requirement::Other(alloc::format!("event group {}", Guid::from_ref(&trigger))));
+                }
+                UndispatchedComponent { name: metadata.name().into_owned(), unmet }
+            })
+            .collect()
+    }
+
     /// Logs all components that were not dispatched, and the parameter that was not satisfied that prevented dispatch.
+    ///
+    /// Components waiting on an event group that has not been signalled yet are not listed.
     #[coverage(off)]
     pub(crate) fn display_not_dispatched(&self) {
-        if !self.components.is_empty() || !self.rejected.is_empty() {
+        let not_dispatched = || {
+            self.components
+                .iter()
+                .filter(|component| self.waiting_on(component.as_ref()).is_none())
+                .chain(&self.rejected)
+        };
+        if not_dispatched().next().is_some() {
             let name_len = "name".len();
             let param_len = "error message".len();
 
-            let not_dispatched = self.components.iter().chain(&self.rejected);
-            let max_name_len = not_dispatched.map(|c| c.metadata().name().len()).max().unwrap_or(name_len);
+            let max_name_len = not_dispatched().map(|c| c.metadata().name().len()).max().unwrap_or(name_len);
 
-            let not_dispatched = self.components.iter().chain(&self.rejected);
-            let max_param_len = not_dispatched
+            let max_param_len = not_dispatched()
                 .map(|c| c.metadata().error_message().map(|s| s.len()).unwrap_or(0))
                 .max()
                 .unwrap_or(param_len);
@@ -247,8 +339,7 @@ impl ComponentDispatcher {
             log::warn!("{:-<max_name_len$} {:-<max_param_len$}", "", "");
             log::warn!("{:<max_name_len$} {:<max_param_len$}", "name", "error message");
 
-            let not_dispatched = self.components.iter().chain(&self.rejected);
-            for component in not_dispatched {
+            for component in not_dispatched() {
                 let metadata = component.metadata();
                 log::warn!(
                     "{:<max_name_len$} {:<max_param_len$}",
@@ -424,6 +515,38 @@ mod tests {
         assert!(dispatcher.dispatch());
     }
 
+    #[test]
+    fn test_capsule_hobs_are_available_to_components() {
+        use patina::pi::hob::{Capsule, UEFI_CAPSULE, header};
+
+        let capsule = |base_address, length| Capsule {
+            header: header::Hob { r#type: UEFI_CAPSULE, length: size_of::<Capsule>() as u16, reserved: 0 },
+            base_address,
+            length,
+        };
+        let (capsule1, capsule2) = (capsule(0x1000, 0x80), capsule(0x2000, 0x40));
+        let mut hob_list = HobList::new();
+        hob_list.push(patina::pi::hob::Hob::Capsule(&capsule1));
+        hob_list.push(patina::pi::hob::Hob::Capsule(&capsule2));
+
+        struct TestComponent;
+
+        #[component]
+        impl TestComponent {
+            fn entry_point(self, capsules: patina::component::hob::Hob<Capsule>) -> patina::error::Result<()> {
+                let capsules = capsules.iter().map(|c| (c.base_address, c.length)).collect::<Vec<_>>();
+                assert_eq!(capsules, [(0x1000, 0x80), (0x2000, 0x40)]);
+                Ok(())
+            }
+        }
+
+        let mut dispatcher = ComponentDispatcher::default();
+        dispatcher.insert_component(0, TestComponent.into_component());
+        dispatcher.insert_hobs(&hob_list);
+
+        assert!(dispatcher.dispatch());
+    }
+
     #[test]
     fn test_reentrant_lock_correctly_displays_name() {
         assert!(
@@ -457,6 +580,35 @@ mod tests {
         dispatcher.display_not_dispatched();
     }
 
+    #[test]
+    fn test_undispatched_components_lists_unmet_requirements() {
+        struct TestComponent;
+
+        trait TestService {}
+
+        #[component]
+        impl TestComponent {
+            fn entry_point(
+                self,
+                _: patina::component::service::Service<dyn TestService>,
+                _: patina::component::params::ConfigMut<u32>,
+            ) -> patina::error::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut dispatcher = ComponentDispatcher::default();
+        dispatcher.insert_component(0, TestComponent.into_component());
+        dispatcher.lock_configs();
+        assert!(!dispatcher.dispatch());
+
+        let undispatched = dispatcher.undispatched_components();
+        assert_eq!(undispatched.len(), 1);
+        assert!(undispatched[0].name.ends_with("TestComponent"));
+        assert!(matches!(&undispatched[0].unmet[..], [Requirement::Service(service), Requirement::Config(config)]
+            if service.ends_with("TestService") && config == "u32"));
+    }
+
     #[test]
     fn test_dispatch_still_succeeds_with_error_in_component() {
         struct TestComponent;
@@ -472,4 +624,60 @@ mod tests {
         dispatcher.insert_component(0, TestComponent.into_component());
         assert!(dispatcher.dispatch(), "Dispatch should succeed even if component fails");
     }
+
+    #[test]
+    fn test_event_triggered_component_waits_for_event_group() {
+        const TEST_EVENT_GROUP: efi::Guid =
+            efi::Guid::from_fields(0x2a3c2d6e, 0x7f14, 0x4d0b, 0x9a, 0x61, &[0x1e, 0x53, 0x04, 0xb8, 0xc2, 0x7d]);
+
+        struct TestComponent;
+
+        #[component(event = TEST_EVENT_GROUP)]
+        impl TestComponent {
+            fn entry_point(self) -> patina::error::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut dispatcher = ComponentDispatcher::default();
+        dispatcher.insert_component(0, TestComponent.into_component());
+        dispatcher.insert_component(1, TestComponent.into_component());
+        assert!(!dispatcher.dispatch());
+        assert_eq!(dispatcher.pending_triggers(), vec![TEST_EVENT_GROUP]);
+
+        let undispatched = dispatcher.undispatched_components();
+        assert_eq!(undispatched.len(), 2);
+        assert!(
+            matches!(&undispatched[0].unmet[..], [Requirement::Other(reason)] if reason.starts_with("event group"))
+        );
+
+        assert!(dispatcher.signal_event_group(TEST_EVENT_GROUP));
+        assert!(dispatcher.pending_triggers().is_empty());
+        assert!(dispatcher.undispatched_components().is_empty());
+        assert!(!dispatcher.signal_event_group(TEST_EVENT_GROUP));
+    }
+
+    #[test]
+    fn test_exit_boot_services_triggered_component_is_rejected() {
+        struct TestComponent;
+
+        #[component(event = efi::EVENT_GROUP_EXIT_BOOT_SERVICES)]
+        impl TestComponent {
+            fn entry_point(self) -> patina::error::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut dispatcher = ComponentDispatcher::default();
+        dispatcher.insert_component(0, TestComponent.into_component());
+        assert!(dispatcher.pending_triggers().is_empty());
+
+        let undispatched = dispatcher.undispatched_components();
+        assert_eq!(undispatched.len(), 1);
+        let [Requirement::Other(reason)] = &undispatched[0].unmet[..] else {
+            panic!("Unexpected requirements: {:?}", undispatched[0].unmet);
+        };
+        assert!(reason.ends_with("cannot trigger a component"));
+        assert!(!dispatcher.signal_event_group(efi::EVENT_GROUP_EXIT_BOOT_SERVICES));
+    }
 }
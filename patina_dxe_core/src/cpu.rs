@@ -10,13 +10,31 @@
 //!
 mod cpu_arch_protocol;
 #[cfg(all(target_os = "uefi", target_arch = "aarch64"))]
+mod generic_timer;
+#[cfg(target_arch = "x86_64")]
+mod hpet_timer;
+#[cfg(all(target_os = "uefi", target_arch = "aarch64"))]
 mod hw_interrupt_protocol;
+#[cfg(target_arch = "x86_64")]
+mod local_apic_timer;
+mod metronome_arch_protocol;
 mod perf_timer;
+mod timer_arch_protocol;
+mod watchdog_arch_protocol;
 
 pub(crate) use cpu_arch_protocol::CpuArchProtocolInstaller;
 #[cfg(all(target_os = "uefi", target_arch = "aarch64"))]
+pub use generic_timer::{DEFAULT_EL1_PHYSICAL_TIMER_INTERRUPT, GenericTimer};
+#[cfg(target_arch = "x86_64")]
+pub use hpet_timer::HpetTimer;
+#[cfg(all(target_os = "uefi", target_arch = "aarch64"))]
 pub(crate) use hw_interrupt_protocol::HwInterruptProtocolInstaller;
-pub(crate) use perf_timer::PerfTimer;
+#[cfg(target_arch = "x86_64")]
+pub use local_apic_timer::LocalApicTimer;
+pub use metronome_arch_protocol::MetronomeArchProtocolInstaller;
+pub(crate) use perf_timer::{PerfTimer, arch_cpu_count};
+pub use timer_arch_protocol::{TickSource, TimerArchProtocolInstaller};
+pub use watchdog_arch_protocol::WatchdogArchProtocolInstaller;
 
 use patina_internal_cpu::{cpu::EfiCpu, interrupts::Interrupts};
 
@@ -0,0 +1,104 @@
+//! AArch64 Generic Timer Tick Source
+//!
+//! Drives the timer arch protocol with the EL1 physical timer of the generic timer. The timer interrupt is a PPI
+//! delivered through the GIC, so it is registered with the hardware interrupt protocols installed by the core.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use aarch64_cpu::registers::{CNTFRQ_EL0, CNTP_CTL_EL0, CNTP_CVAL_EL0, CNTPCT_EL0, Readable, Writeable};
+use core::sync::atomic::{AtomicU64, Ordering};
+use patina::{
+    component::service::perf_timer::ArchTimerFunctionality,
+    error::{EfiError, Result},
+};
+use patina_internal_cpu::interrupts::{ExceptionContext, InterruptHandler, InterruptManager};
+use spin::Once;
+
+use super::{TickSource, hw_interrupt_protocol};
+
+/// The GIC interrupt ID of the non-secure EL1 physical timer, as recommended by the Server Base System Architecture.
+pub const DEFAULT_EL1_PHYSICAL_TIMER_INTERRUPT: u32 = 30;
+
+/// The handler the timer interrupt is delivered to. The hardware interrupt protocols do not provide a context.
+static TICK_HANDLER: Once<&'static dyn InterruptHandler> = Once::new();
+
+extern "efiapi" fn generic_timer_interrupt(interrupt_source: u64, context: &mut ExceptionContext) {
+    if let Some(handler) = TICK_HANDLER.get() {
+        handler.handle_interrupt(interrupt_source as usize, context);
+    }
+}
+
+/// A [TickSource] backed by the EL1 physical timer, delivering ticks on the given GIC interrupt ID.
+pub struct GenericTimer {
+    interrupt: u32,
+    /// The number of counts between two ticks, or 0 if the timer is stopped.
+    interval: AtomicU64,
+}
+
+impl GenericTimer {
+    /// Creates a new `GenericTimer` that interrupts on the GIC interrupt ID `interrupt`.
+    pub const fn new(interrupt: u32) -> Self {
+        Self { interrupt, interval: AtomicU64::new(0) }
+    }
+}
+
+impl Default for GenericTimer {
+    fn default() -> Self {
+        Self::new(DEFAULT_EL1_PHYSICAL_TIMER_INTERRUPT)
+    }
+}
+
+impl TickSource for GenericTimer {
+    fn connect(
+        &self,
+        _interrupt_manager: &dyn InterruptManager,
+        _perf_timer: &dyn ArchTimerFunctionality,
+        handler: &'static dyn InterruptHandler,
+    ) -> Result<()> {
+        if CNTFRQ_EL0.get() == 0 {
+            log::error!("The generic timer frequency is not set.");
+            return Err(EfiError::DeviceError);
+        }
+        if TICK_HANDLER.is_completed() {
+            return Err(EfiError::AlreadyStarted);
+        }
+        TICK_HANDLER.call_once(|| handler);
+
+        CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::CLEAR + CNTP_CTL_EL0::IMASK::SET);
+        let status = hw_interrupt_protocol::register_interrupt_source(self.interrupt as u64, generic_timer_interrupt);
+        if status.is_error() {
+            log::error!("Failed to register the generic timer interrupt {}: {status:#x?}", self.interrupt);
+            return Err(status.into());
+        }
+        Ok(())
+    }
+
+    fn set_period(&self, period: u64) -> Result<()> {
+        CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::CLEAR + CNTP_CTL_EL0::IMASK::SET);
+        if period == 0 {
+            self.interval.store(0, Ordering::SeqCst);
+            return Ok(());
+        }
+
+        let interval = (CNTFRQ_EL0.get() as u128 * period as u128 / 10_000_000).clamp(1, u64::MAX as u128) as u64;
+        self.interval.store(interval, Ordering::SeqCst);
+        CNTP_CVAL_EL0.set(CNTPCT_EL0.get().wrapping_add(interval));
+        CNTP_CTL_EL0.write(CNTP_CTL_EL0::ENABLE::SET + CNTP_CTL_EL0::IMASK::CLEAR);
+        Ok(())
+    }
+
+    fn acknowledge(&self) {
+        // The timer condition stays asserted until the compare value is moved past the counter.
+        let interval = self.interval.load(Ordering::SeqCst);
+        if interval != 0 {
+            let now = CNTPCT_EL0.get();
+            let next = CNTP_CVAL_EL0.get().wrapping_add(interval);
+            CNTP_CVAL_EL0.set(if next > now { next } else { now.wrapping_add(interval) });
+        }
+        hw_interrupt_protocol::end_of_interrupt(self.interrupt as u64);
+    }
+}
@@ -0,0 +1,141 @@
+//! x64 HPET Tick Source
+//!
+//! Drives the timer arch protocol with timer 0 of a High Precision Event Timer in periodic mode. The interrupt is
+//! delivered as a message (FSB interrupt delivery) directly to the local APIC of the boot processor, so no I/O APIC
+//! routing is required, but the HPET must support FSB interrupt delivery and periodic mode on timer 0.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::sync::atomic::{AtomicU64, Ordering};
+use patina::{
+    component::service::perf_timer::ArchTimerFunctionality,
+    error::{EfiError, Result},
+};
+use patina_internal_cpu::interrupts::{ExceptionType, HandlerType, InterruptHandler, InterruptManager};
+
+use super::{
+    TickSource,
+    local_apic_timer::{self, FIRST_INTERRUPT_VECTOR},
+};
+
+const HPET_CAPABILITIES: usize = 0x000;
+const HPET_CONFIG: usize = 0x010;
+const HPET_MAIN_COUNTER: usize = 0x0F0;
+const HPET_TIMER0_CONFIG: usize = 0x100;
+const HPET_TIMER0_COMPARATOR: usize = 0x108;
+const HPET_TIMER0_FSB_ROUTE: usize = 0x110;
+
+const CONFIG_ENABLE: u64 = 1 << 0;
+
+const TIMER_INTERRUPT_ENABLE: u64 = 1 << 2;
+const TIMER_PERIODIC: u64 = 1 << 3;
+const TIMER_PERIODIC_CAPABLE: u64 = 1 << 4;
+const TIMER_VALUE_SET: u64 = 1 << 6;
+const TIMER_FSB_ENABLE: u64 = 1 << 14;
+const TIMER_FSB_CAPABLE: u64 = 1 << 15;
+
+/// The maximum counter period allowed by the HPET specification, in femtoseconds (100ns).
+const MAX_COUNTER_PERIOD_FS: u64 = 100_000_000;
+
+/// The base address of the message address of interrupts delivered to a local APIC.
+const MSI_ADDRESS_BASE: u64 = 0xFEE0_0000;
+
+/// A [TickSource] backed by timer 0 of the HPET at `base`, delivering ticks on the given interrupt vector.
+pub struct HpetTimer {
+    base: u64,
+    vector: u8,
+    /// The period of the main counter in femtoseconds, read when connected.
+    counter_period: AtomicU64,
+}
+
+impl HpetTimer {
+    /// Creates a new `HpetTimer` for the HPET at `base` that interrupts on `vector`, which must be 32 or above.
+    ///
+    /// ## Safety
+    ///
+    /// `base` must point to the HPET register space, which must be mapped, and access to these registers must be
+    /// exclusive to this instance.
+    pub const unsafe fn new(base: u64, vector: u8) -> Self {
+        Self { base, vector, counter_period: AtomicU64::new(0) }
+    }
+
+    fn read(&self, offset: usize) -> u64 {
+        // Safety: the invariants of `new` guarantee that the HPET register space is mapped at `base`.
+        unsafe { core::ptr::read_volatile((self.base as usize + offset) as *const u64) }
+    }
+
+    fn write(&self, offset: usize, value: u64) {
+        // Safety: the invariants of `new` guarantee that the HPET register space is mapped at `base`.
+        unsafe { core::ptr::write_volatile((self.base as usize + offset) as *mut u64, value) }
+    }
+
+    fn stop(&self) {
+        self.write(HPET_CONFIG, self.read(HPET_CONFIG) & !CONFIG_ENABLE);
+        self.write(HPET_TIMER0_CONFIG, self.read(HPET_TIMER0_CONFIG) & !TIMER_INTERRUPT_ENABLE);
+    }
+}
+
+impl TickSource for HpetTimer {
+    fn connect(
+        &self,
+        interrupt_manager: &dyn InterruptManager,
+        _perf_timer: &dyn ArchTimerFunctionality,
+        handler: &'static dyn InterruptHandler,
+    ) -> Result<()> {
+        if self.vector < FIRST_INTERRUPT_VECTOR {
+            log::error!("HPET timer vector {:#x} is reserved for exceptions.", self.vector);
+            return Err(EfiError::InvalidParameter);
+        }
+
+        let counter_period = self.read(HPET_CAPABILITIES) >> 32;
+        if counter_period == 0 || counter_period > MAX_COUNTER_PERIOD_FS {
+            log::error!("HPET counter period {counter_period} fs is invalid.");
+            return Err(EfiError::DeviceError);
+        }
+        let timer_config = self.read(HPET_TIMER0_CONFIG);
+        if timer_config & TIMER_PERIODIC_CAPABLE == 0 || timer_config & TIMER_FSB_CAPABLE == 0 {
+            log::error!("HPET timer 0 does not support periodic mode with FSB interrupt delivery.");
+            return Err(EfiError::Unsupported);
+        }
+        self.counter_period.store(counter_period, Ordering::SeqCst);
+        self.stop();
+
+        // Route the interrupt as a fixed, edge triggered message to the local APIC of this processor.
+        let address = MSI_ADDRESS_BASE | ((local_apic_timer::apic_id() as u64 & 0xFF) << 12);
+        self.write(HPET_TIMER0_FSB_ROUTE, (address << 32) | self.vector as u64);
+        self.write(HPET_TIMER0_CONFIG, (self.read(HPET_TIMER0_CONFIG) | TIMER_FSB_ENABLE) & !TIMER_INTERRUPT_ENABLE);
+
+        interrupt_manager.register_exception_handler(self.vector as ExceptionType, HandlerType::Handler(handler))
+    }
+
+    fn set_period(&self, period: u64) -> Result<()> {
+        self.stop();
+        if period == 0 {
+            return Ok(());
+        }
+
+        // The period is in 100ns units, which are 10^8 femtoseconds.
+        let counter_period = self.counter_period.load(Ordering::SeqCst);
+        let comparator = (period as u128 * MAX_COUNTER_PERIOD_FS as u128 / counter_period as u128).max(1) as u64;
+
+        self.write(HPET_MAIN_COUNTER, 0);
+        self.write(
+            HPET_TIMER0_CONFIG,
+            self.read(HPET_TIMER0_CONFIG) | TIMER_INTERRUPT_ENABLE | TIMER_PERIODIC | TIMER_VALUE_SET,
+        );
+        // With the value set bit, the first write sets the comparator and the second write sets the period.
+        self.write(HPET_TIMER0_COMPARATOR, comparator);
+        self.write(HPET_TIMER0_COMPARATOR, comparator);
+        self.write(HPET_CONFIG, self.read(HPET_CONFIG) | CONFIG_ENABLE);
+        Ok(())
+    }
+
+    fn acknowledge(&self) {
+        // Message signaled interrupts are edge triggered, so only the local APIC needs to be acknowledged.
+        local_apic_timer::end_of_interrupt();
+    }
+}
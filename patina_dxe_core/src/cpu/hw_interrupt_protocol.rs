@@ -5,7 +5,7 @@ use patina_internal_cpu::interrupts::{
     ExceptionContext, InterruptHandler, InterruptManager, gic_manager::AArch64InterruptInitializer,
 };
 use r_efi::efi;
-use spin::rwlock::RwLock;
+use spin::{Once, rwlock::RwLock};
 
 use arm_gic::{
     Trigger,
@@ -477,6 +477,30 @@ impl HwInterruptProtocolHandler {
     }
 }
 
+/// The handler behind the hardware interrupt protocols, for use by other core interrupt sources.
+static HW_INTERRUPT_HANDLER: Once<&'static HwInterruptProtocolHandler> = Once::new();
+
+/// Registers `handler` for `interrupt_source` with the hardware interrupt protocols and enables the interrupt.
+///
+/// Returns `NOT_READY` if the hardware interrupt protocols have not been installed yet.
+pub(crate) fn register_interrupt_source(interrupt_source: u64, handler: HwInterruptHandler) -> efi::Status {
+    match HW_INTERRUPT_HANDLER.get() {
+        Some(hw_interrupt_handler) => {
+            hw_interrupt_handler.register_interrupt_source(interrupt_source as usize, handler)
+        }
+        None => efi::Status::NOT_READY,
+    }
+}
+
+/// Signals the end of `interrupt_source` to the interrupt controller.
+pub(crate) fn end_of_interrupt(interrupt_source: u64) {
+    if let Some(hw_interrupt_handler) = HW_INTERRUPT_HANDLER.get()
+        && let Err(err) = hw_interrupt_handler.aarch64_int.lock().end_of_interrupt(interrupt_source)
+    {
+        log::error!("Failed to end interrupt {interrupt_source:#x}: {err:?}");
+    }
+}
+
 /// A component to install the two hardware interrupt protocols.
 pub(crate) struct HwInterruptProtocolInstaller {
     /// The GIC base addresses.
@@ -508,7 +532,9 @@ impl HwInterruptProtocolInstaller {
         let handlers = vec![None; max_int as usize];
 
         // Prepare context for the v1 interrupt handler
-        let hw_int_protocol_handler = Box::leak(Box::new(HwInterruptProtocolHandler::new(handlers, aarch64_int)));
+        let hw_int_protocol_handler: &'static HwInterruptProtocolHandler =
+            Box::leak(Box::new(HwInterruptProtocolHandler::new(handlers, aarch64_int)));
+        HW_INTERRUPT_HANDLER.call_once(|| hw_int_protocol_handler);
         // Produce Interrupt Protocol with the initialized GIC
         let interrupt_protocol = Box::leak(Box::new(EfiHardwareInterruptProtocol::new(hw_int_protocol_handler)));
 
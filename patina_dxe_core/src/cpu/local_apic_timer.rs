@@ -0,0 +1,180 @@
+//! x64 Local APIC Timer Tick Source
+//!
+//! Drives the timer arch protocol with the local APIC timer of the boot processor, in periodic mode. The local APIC
+//! timer frequency is not architecturally discoverable, so it is calibrated against the performance timer when
+//! connected.
+//!
+//! Both the xAPIC (MMIO) and x2APIC (MSR) register interfaces are supported. In xAPIC mode, the local APIC register
+//! space must be mapped.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::{
+    arch::asm,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use patina::{
+    component::service::perf_timer::ArchTimerFunctionality,
+    error::{EfiError, Result},
+};
+use patina_internal_cpu::interrupts::{ExceptionType, HandlerType, InterruptHandler, InterruptManager};
+
+use super::TickSource;
+
+const IA32_APIC_BASE_MSR: u32 = 0x1B;
+const APIC_BASE_X2APIC_ENABLE: u64 = 1 << 10;
+const APIC_BASE_ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+const X2APIC_MSR_BASE: u32 = 0x800;
+
+const APIC_ID: usize = 0x20;
+const APIC_EOI: usize = 0xB0;
+const APIC_LVT_TIMER: usize = 0x320;
+const APIC_TIMER_INITIAL_COUNT: usize = 0x380;
+const APIC_TIMER_CURRENT_COUNT: usize = 0x390;
+const APIC_TIMER_DIVIDE_CONFIG: usize = 0x3E0;
+
+const LVT_MASKED: u32 = 1 << 16;
+const LVT_TIMER_PERIODIC: u32 = 1 << 17;
+const TIMER_DIVIDE_BY_1: u32 = 0b1011;
+
+/// The first vector available for interrupts, vectors below are reserved for exceptions.
+pub(super) const FIRST_INTERRUPT_VECTOR: u8 = 32;
+
+/// The period of time the local APIC timer is calibrated over, in microseconds.
+const CALIBRATION_PERIOD_US: u64 = 1000;
+
+/// Reads a model specific register.
+///
+/// ## Safety
+///
+/// `msr` must be a valid MSR for the processor.
+unsafe fn rdmsr(msr: u32) -> u64 {
+    let (low, high): (u32, u32);
+    // Safety: the caller guarantees that the msr is valid.
+    unsafe { asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags)) };
+    ((high as u64) << 32) | low as u64
+}
+
+/// Writes a model specific register.
+///
+/// ## Safety
+///
+/// `msr` must be a valid MSR for the processor, and `value` a valid value for it.
+unsafe fn wrmsr(msr: u32, value: u64) {
+    let (low, high) = (value as u32, (value >> 32) as u32);
+    // Safety: the caller guarantees that the msr and value are valid.
+    unsafe { asm!("wrmsr", in("ecx") msr, in("eax") low, in("edx") high, options(nostack, preserves_flags)) };
+}
+
+/// Returns the IA32_APIC_BASE MSR of the current processor.
+fn apic_base() -> u64 {
+    // Safety: IA32_APIC_BASE is architectural on all x64 processors.
+    unsafe { rdmsr(IA32_APIC_BASE_MSR) }
+}
+
+/// Reads the local APIC register at `offset` of the current processor.
+fn read_register(offset: usize) -> u32 {
+    let base = apic_base();
+    if base & APIC_BASE_X2APIC_ENABLE != 0 {
+        // Safety: in x2APIC mode, the local APIC registers are the MSRs from 0x800.
+        unsafe { rdmsr(X2APIC_MSR_BASE + (offset >> 4) as u32) as u32 }
+    } else {
+        // Safety: in xAPIC mode, the local APIC registers are mapped at the APIC base.
+        unsafe { core::ptr::read_volatile(((base & APIC_BASE_ADDRESS_MASK) as usize + offset) as *const u32) }
+    }
+}
+
+/// Writes the local APIC register at `offset` of the current processor.
+fn write_register(offset: usize, value: u32) {
+    let base = apic_base();
+    if base & APIC_BASE_X2APIC_ENABLE != 0 {
+        // Safety: in x2APIC mode, the local APIC registers are the MSRs from 0x800.
+        unsafe { wrmsr(X2APIC_MSR_BASE + (offset >> 4) as u32, value as u64) }
+    } else {
+        // Safety: in xAPIC mode, the local APIC registers are mapped at the APIC base.
+        unsafe { core::ptr::write_volatile(((base & APIC_BASE_ADDRESS_MASK) as usize + offset) as *mut u32, value) }
+    }
+}
+
+/// Returns the local APIC ID of the current processor.
+pub(super) fn apic_id() -> u32 {
+    if apic_base() & APIC_BASE_X2APIC_ENABLE != 0 { read_register(APIC_ID) } else { read_register(APIC_ID) >> 24 }
+}
+
+/// Signals the end of the interrupt being serviced to the local APIC of the current processor.
+pub(super) fn end_of_interrupt() {
+    write_register(APIC_EOI, 0);
+}
+
+/// A [TickSource] backed by the local APIC timer, delivering ticks on the given interrupt vector.
+pub struct LocalApicTimer {
+    vector: u8,
+    /// The local APIC timer frequency in Hz, calibrated when connected.
+    frequency: AtomicU64,
+}
+
+impl LocalApicTimer {
+    /// Creates a new `LocalApicTimer` that interrupts on `vector`, which must be 32 or above.
+    pub const fn new(vector: u8) -> Self {
+        Self { vector, frequency: AtomicU64::new(0) }
+    }
+}
+
+impl TickSource for LocalApicTimer {
+    fn connect(
+        &self,
+        interrupt_manager: &dyn InterruptManager,
+        perf_timer: &dyn ArchTimerFunctionality,
+        handler: &'static dyn InterruptHandler,
+    ) -> Result<()> {
+        if self.vector < FIRST_INTERRUPT_VECTOR {
+            log::error!("Local APIC timer vector {:#x} is reserved for exceptions.", self.vector);
+            return Err(EfiError::InvalidParameter);
+        }
+        let perf_frequency = perf_timer.perf_frequency();
+        if perf_frequency == 0 {
+            log::error!("The performance timer frequency is unknown, the local APIC timer cannot be calibrated.");
+            return Err(EfiError::Unsupported);
+        }
+
+        // Count down from the maximum count over the calibration period with the interrupt masked.
+        write_register(APIC_LVT_TIMER, LVT_MASKED | self.vector as u32);
+        write_register(APIC_TIMER_DIVIDE_CONFIG, TIMER_DIVIDE_BY_1);
+        write_register(APIC_TIMER_INITIAL_COUNT, u32::MAX);
+        let calibration_counts = perf_frequency * CALIBRATION_PERIOD_US / 1_000_000;
+        let start = perf_timer.cpu_count();
+        while perf_timer.cpu_count().wrapping_sub(start) < calibration_counts {
+            core::hint::spin_loop();
+        }
+        let elapsed = u32::MAX - read_register(APIC_TIMER_CURRENT_COUNT);
+        write_register(APIC_TIMER_INITIAL_COUNT, 0);
+
+        let frequency = elapsed as u64 * 1_000_000 / CALIBRATION_PERIOD_US;
+        log::info!("Local APIC timer frequency calibrated to {frequency} Hz.");
+        self.frequency.store(frequency, Ordering::SeqCst);
+
+        interrupt_manager.register_exception_handler(self.vector as ExceptionType, HandlerType::Handler(handler))
+    }
+
+    fn set_period(&self, period: u64) -> Result<()> {
+        if period == 0 {
+            write_register(APIC_LVT_TIMER, LVT_MASKED | self.vector as u32);
+            write_register(APIC_TIMER_INITIAL_COUNT, 0);
+            return Ok(());
+        }
+
+        let count = (self.frequency.load(Ordering::SeqCst) as u128 * period as u128 / 10_000_000)
+            .clamp(1, u32::MAX as u128) as u32;
+        write_register(APIC_LVT_TIMER, LVT_TIMER_PERIODIC | self.vector as u32);
+        write_register(APIC_TIMER_INITIAL_COUNT, count);
+        Ok(())
+    }
+
+    fn acknowledge(&self) {
+        end_of_interrupt();
+    }
+}
@@ -0,0 +1,170 @@
+//! DXE Core Metronome Architectural Protocol
+//!
+//! Produces the Metronome Architectural Protocol by busy waiting on the performance timer, which backs the `Stall()`
+//! boot service.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use patina::{
+    boot_services::{BootServices, StandardBootServices},
+    component::{
+        component,
+        service::{Service, perf_timer::ArchTimerFunctionality},
+    },
+    error::{EfiError, Result},
+    pi::protocols::metronome::{PROTOCOL_GUID, Protocol},
+    uefi_protocol::ProtocolInterface,
+};
+use r_efi::efi;
+
+/// The metronome tick period, in 100ns units.
+const TICK_PERIOD: u32 = 1;
+
+/// The number of `TICK_PERIOD` ticks per second.
+const TICKS_PER_SECOND: u128 = 10_000_000 / TICK_PERIOD as u128;
+
+#[repr(C)]
+struct EfiMetronomeArchProtocolImpl {
+    protocol: Protocol,
+
+    // Crate accessible fields
+    pub(crate) perf_timer: Service<dyn ArchTimerFunctionality>,
+}
+
+unsafe impl ProtocolInterface for EfiMetronomeArchProtocolImpl {
+    const PROTOCOL_GUID: efi::Guid = PROTOCOL_GUID;
+}
+
+// EfiMetronomeArchProtocolImpl function pointers implementations.
+
+extern "efiapi" fn wait_for_tick(this: *const Protocol, tick_number: u32) -> efi::Status {
+    // Safety: a non-null `this` is the protocol field of an EfiMetronomeArchProtocolImpl, which is #[repr(C)].
+    let Some(metronome) = (unsafe { this.cast::<EfiMetronomeArchProtocolImpl>().as_ref() }) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let perf_timer = &metronome.perf_timer;
+    let frequency = perf_timer.perf_frequency();
+    if frequency == 0 {
+        return efi::Status::DEVICE_ERROR;
+    }
+
+    // Round up, so that the wait is at least the requested number of ticks.
+    let counts = (tick_number as u128 * frequency as u128).div_ceil(TICKS_PER_SECOND);
+    let start = perf_timer.cpu_count();
+    while (perf_timer.cpu_count().wrapping_sub(start) as u128) < counts {
+        core::hint::spin_loop();
+    }
+    efi::Status::SUCCESS
+}
+
+impl EfiMetronomeArchProtocolImpl {
+    fn new(perf_timer: Service<dyn ArchTimerFunctionality>) -> Self {
+        Self {
+            protocol: Protocol { wait_for_tick, tick_period: TICK_PERIOD },
+
+            // private data
+            perf_timer,
+        }
+    }
+}
+
+/// This component installs the metronome arch protocol, measuring ticks with the performance timer.
+///
+/// Platforms that produce the metronome arch protocol through a separate driver must not add this component.
+#[derive(Default)]
+pub struct MetronomeArchProtocolInstaller;
+
+#[component]
+impl MetronomeArchProtocolInstaller {
+    fn entry_point(self, perf_timer: Service<dyn ArchTimerFunctionality>, bs: StandardBootServices) -> Result<()> {
+        if perf_timer.perf_frequency() == 0 {
+            log::error!("The performance timer frequency is unknown, EFI_METRONOME_ARCH_PROTOCOL not installed");
+            return Err(EfiError::Unsupported);
+        }
+
+        let interface = Box::leak(Box::new(EfiMetronomeArchProtocolImpl::new(perf_timer)));
+
+        bs.install_protocol_interface(None, interface)
+            .inspect_err(|_| log::error!("Failed to install EFI_METRONOME_ARCH_PROTOCOL"))?;
+        log::info!("installed EFI_METRONOME_ARCH_PROTOCOL_GUID");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use patina::component::service::IntoService;
+
+    /// A performance timer that advances by one count every time it is read.
+    #[derive(IntoService)]
+    #[service(dyn ArchTimerFunctionality)]
+    struct SteppingTimer {
+        frequency: u64,
+        count: AtomicU64,
+    }
+
+    impl ArchTimerFunctionality for SteppingTimer {
+        fn cpu_count(&self) -> u64 {
+            self.count.fetch_add(1, Ordering::SeqCst)
+        }
+
+        fn perf_frequency(&self) -> u64 {
+            self.frequency
+        }
+    }
+
+    fn metronome(frequency: u64) -> EfiMetronomeArchProtocolImpl {
+        EfiMetronomeArchProtocolImpl::new(Service::mock(Box::new(SteppingTimer {
+            frequency,
+            count: AtomicU64::new(u64::MAX - 5),
+        })))
+    }
+
+    /// Returns the number of times the performance timer was read.
+    fn counts_read(metronome: &EfiMetronomeArchProtocolImpl) -> u64 {
+        metronome.perf_timer.cpu_count().wrapping_sub(u64::MAX - 5)
+    }
+
+    #[test]
+    fn test_wait_for_tick_waits_for_tick_duration() {
+        // 100 counts per tick: the timer is read once for the start, and once per count until 500 counts elapsed.
+        let metronome = metronome(1_000_000_000);
+        assert_eq!(wait_for_tick(&metronome.protocol, 5), efi::Status::SUCCESS);
+        assert_eq!(counts_read(&metronome), 501);
+    }
+
+    #[test]
+    fn test_wait_for_tick_rounds_up_partial_counts() {
+        // A tick is a tenth of a count, so any wait is at least a full count.
+        let metronome = metronome(1_000_000);
+        assert_eq!(wait_for_tick(&metronome.protocol, 1), efi::Status::SUCCESS);
+        assert_eq!(counts_read(&metronome), 2);
+
+        assert_eq!(wait_for_tick(&metronome.protocol, 0), efi::Status::SUCCESS);
+    }
+
+    #[test]
+    fn test_wait_for_tick_errors() {
+        assert_eq!(wait_for_tick(core::ptr::null(), 1), efi::Status::INVALID_PARAMETER);
+
+        let metronome = metronome(0);
+        assert_eq!(wait_for_tick(&metronome.protocol, 1), efi::Status::DEVICE_ERROR);
+    }
+
+    #[test]
+    fn test_tick_period_is_within_spec() {
+        // The PI specification limits the tick period to 200 microseconds.
+        assert!(metronome(1).protocol.tick_period <= 2000);
+    }
+}
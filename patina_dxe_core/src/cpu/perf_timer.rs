@@ -58,7 +58,7 @@ impl Default for PerfTimer {
 ///
 /// Skip coverage as any value could be valid, including 0.
 #[coverage(off)]
-fn arch_cpu_count() -> u64 {
+pub(crate) fn arch_cpu_count() -> u64 {
     #[cfg(target_arch = "x86_64")]
     {
         use core::arch::x86_64;
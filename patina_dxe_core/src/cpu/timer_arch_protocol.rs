@@ -0,0 +1,325 @@
+//! DXE Core Timer Architectural Protocol
+//!
+//! Produces the Timer Architectural Protocol on top of a [TickSource], the hardware that raises the periodic timer
+//! interrupt. The core consumes the protocol like any other timer driver, registering its tick handler through
+//! `RegisterHandler` once the protocol is installed.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::sync::atomic::{AtomicU64, Ordering};
+use patina::{
+    boot_services::{BootServices, StandardBootServices},
+    component::{
+        component,
+        service::{Service, perf_timer::ArchTimerFunctionality},
+    },
+    error::Result,
+    pi::protocols::timer::{EfiTimerNotify, PROTOCOL_GUID, Protocol},
+};
+use patina_internal_cpu::interrupts::{ExceptionContext, ExceptionType, InterruptHandler, InterruptManager};
+use r_efi::efi;
+use spin::RwLock;
+
+use crate::events;
+
+/// The default timer period, in 100ns units (10ms).
+pub(crate) const DEFAULT_TIMER_PERIOD: u64 = 100_000;
+
+/// The hardware that produces the periodic timer interrupt behind the Timer Architectural Protocol.
+///
+/// Implementations program the hardware and deliver its interrupt to the provided handler, which is responsible for
+/// calling [acknowledge](TickSource::acknowledge) before notifying the core of the elapsed time.
+#[cfg_attr(test, mockall::automock)]
+pub trait TickSource: Send + Sync {
+    /// Prepares the hardware and routes its interrupt to `handler`.
+    ///
+    /// `perf_timer` may be used to calibrate hardware whose frequency is not otherwise known.
+    fn connect(
+        &self,
+        interrupt_manager: &dyn InterruptManager,
+        perf_timer: &dyn ArchTimerFunctionality,
+        handler: &'static dyn InterruptHandler,
+    ) -> Result<()>;
+
+    /// Programs the hardware to interrupt every `period` 100ns units. A period of 0 stops the interrupt.
+    fn set_period(&self, period: u64) -> Result<()>;
+
+    /// Acknowledges the interrupt being handled, re-arming the hardware for the next one if required.
+    fn acknowledge(&self);
+}
+
+#[repr(C)]
+struct EfiTimerArchProtocolImpl {
+    protocol: Protocol,
+
+    // Crate accessible fields
+    source: Box<dyn TickSource>,
+    period: AtomicU64,
+    notify: RwLock<Option<EfiTimerNotify>>,
+}
+
+// Helper function to convert a raw pointer to a reference, returning `None` for a null pointer.
+fn get_impl_ref<'a>(this: *mut Protocol) -> Option<&'a EfiTimerArchProtocolImpl> {
+    // Safety: a non-null `this` is the protocol field of an EfiTimerArchProtocolImpl, which is #[repr(C)].
+    unsafe { this.cast::<EfiTimerArchProtocolImpl>().as_ref() }
+}
+
+// EfiTimerArchProtocolImpl function pointers implementations.
+
+extern "efiapi" fn register_handler(this: *mut Protocol, notify_function: Option<EfiTimerNotify>) -> efi::Status {
+    let Some(timer) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    // C callers pass a null function pointer, `None`, to unregister the handler.
+    let mut notify = timer.notify.write();
+    match (*notify, notify_function) {
+        (None, None) => efi::Status::INVALID_PARAMETER,
+        (Some(_), Some(_)) => efi::Status::ALREADY_STARTED,
+        (_, notify_function) => {
+            *notify = notify_function;
+            efi::Status::SUCCESS
+        }
+    }
+}
+
+extern "efiapi" fn set_timer_period(this: *mut Protocol, timer_period: u64) -> efi::Status {
+    let Some(timer) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    match timer.source.set_period(timer_period) {
+        Ok(()) => {
+            timer.period.store(timer_period, Ordering::SeqCst);
+            efi::Status::SUCCESS
+        }
+        Err(err) => err.into(),
+    }
+}
+
+extern "efiapi" fn get_timer_period(this: *mut Protocol, timer_period: *mut u64) -> efi::Status {
+    let Some(timer) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    if timer_period.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // Safety: caller must ensure that timer_period is a valid pointer. It is null-checked above.
+    unsafe { timer_period.write_unaligned(timer.period.load(Ordering::SeqCst)) };
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn generate_soft_interrupt(this: *mut Protocol) -> efi::Status {
+    let Some(timer) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let tpl = events::raise_tpl(efi::TPL_HIGH_LEVEL);
+    timer.notify();
+    events::restore_tpl(tpl);
+    efi::Status::SUCCESS
+}
+
+impl EfiTimerArchProtocolImpl {
+    fn new(source: Box<dyn TickSource>) -> Self {
+        Self {
+            protocol: Protocol { register_handler, set_timer_period, get_timer_period, generate_soft_interrupt },
+
+            // private data
+            source,
+            period: AtomicU64::new(0),
+            notify: RwLock::new(None),
+        }
+    }
+
+    /// Handles a tick of the timer, informing the registered handler of the time elapsed since the previous one.
+    fn tick(&self) {
+        let tpl = events::raise_tpl(efi::TPL_HIGH_LEVEL);
+        self.source.acknowledge();
+        self.notify();
+        events::restore_tpl(tpl);
+    }
+
+    fn notify(&self) {
+        // The handler is only replaced outside of interrupt context, so a contended lock skips a single tick.
+        if let Some(notify) = self.notify.try_read().and_then(|notify| *notify) {
+            notify(self.period.load(Ordering::SeqCst));
+        }
+    }
+}
+
+impl InterruptHandler for EfiTimerArchProtocolImpl {
+    fn handle_interrupt(&'static self, _exception_type: ExceptionType, _context: &mut ExceptionContext) {
+        self.tick();
+    }
+}
+
+/// This component installs the timer arch protocol, driven by the given [TickSource].
+///
+/// The timer is started with a period of 10ms. Platforms that produce the timer arch protocol through a separate
+/// driver must not add this component.
+pub struct TimerArchProtocolInstaller {
+    source: Box<dyn TickSource>,
+}
+
+#[component]
+impl TimerArchProtocolInstaller {
+    /// Creates a new `TimerArchProtocolInstaller` driven by `source`.
+    pub fn new(source: impl TickSource + 'static) -> Self {
+        Self { source: Box::new(source) }
+    }
+
+    fn entry_point(
+        self,
+        interrupt_manager: Service<dyn InterruptManager>,
+        perf_timer: Service<dyn ArchTimerFunctionality>,
+        bs: StandardBootServices,
+    ) -> Result<()> {
+        let timer: &'static EfiTimerArchProtocolImpl = Box::leak(Box::new(EfiTimerArchProtocolImpl::new(self.source)));
+        let protocol = &timer.protocol as *const Protocol as *mut Protocol;
+
+        timer
+            .source
+            .connect(&**interrupt_manager, &**perf_timer, timer)
+            .inspect_err(|_| log::error!("Failed to connect the timer tick source"))?;
+
+        let status = set_timer_period(protocol, DEFAULT_TIMER_PERIOD);
+        if status.is_error() {
+            log::error!("Failed to set the default timer period: {status:#x?}");
+            return Err(status.into());
+        }
+
+        // Safety: the protocol is the first field of a leaked EfiTimerArchProtocolImpl, so it lives forever.
+        unsafe { bs.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, protocol.cast()) }
+            .inspect_err(|_| log::error!("Failed to install EFI_TIMER_ARCH_PROTOCOL"))?;
+        log::info!("installed EFI_TIMER_ARCH_PROTOCOL_GUID");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    use core::{
+        ffi::c_void,
+        sync::atomic::{AtomicBool, AtomicU64},
+    };
+    use mockall::predicate::eq;
+    use patina::error::EfiError;
+
+    fn with_locked_state<F: Fn() + std::panic::RefUnwindSafe>(f: F) {
+        crate::test_support::with_global_lock(|| {
+            f();
+        })
+        .unwrap();
+    }
+
+    static LAST_TICK: AtomicU64 = AtomicU64::new(0);
+    extern "efiapi" fn recording_notify(time: u64) {
+        LAST_TICK.store(time, Ordering::SeqCst);
+    }
+
+    extern "efiapi" fn other_notify(_time: u64) {}
+
+    fn timer(source: MockTickSource) -> &'static mut EfiTimerArchProtocolImpl {
+        Box::leak(Box::new(EfiTimerArchProtocolImpl::new(Box::new(source))))
+    }
+
+    #[test]
+    fn test_register_handler() {
+        let timer = timer(MockTickSource::new());
+
+        assert_eq!(register_handler(core::ptr::null_mut(), Some(recording_notify)), efi::Status::INVALID_PARAMETER);
+        assert_eq!(register_handler(&mut timer.protocol, Some(recording_notify)), efi::Status::SUCCESS);
+        assert_eq!(register_handler(&mut timer.protocol, Some(other_notify)), efi::Status::ALREADY_STARTED);
+        assert!(timer.notify.read().is_some());
+
+        assert_eq!(register_handler(&mut timer.protocol, None), efi::Status::SUCCESS);
+        assert!(timer.notify.read().is_none());
+        assert_eq!(register_handler(&mut timer.protocol, None), efi::Status::INVALID_PARAMETER);
+        assert_eq!(register_handler(&mut timer.protocol, Some(other_notify)), efi::Status::SUCCESS);
+    }
+
+    #[test]
+    fn test_set_and_get_timer_period() {
+        let mut source = MockTickSource::new();
+        source.expect_set_period().with(eq(DEFAULT_TIMER_PERIOD)).returning(|_| Ok(()));
+        source.expect_set_period().with(eq(u64::MAX)).returning(|_| Err(EfiError::DeviceError));
+        let timer = timer(source);
+
+        assert_eq!(set_timer_period(&mut timer.protocol, DEFAULT_TIMER_PERIOD), efi::Status::SUCCESS);
+        assert_eq!(set_timer_period(&mut timer.protocol, u64::MAX), efi::Status::DEVICE_ERROR);
+
+        let mut period = 0;
+        assert_eq!(get_timer_period(&mut timer.protocol, &mut period), efi::Status::SUCCESS);
+        assert_eq!(period, DEFAULT_TIMER_PERIOD);
+        assert_eq!(get_timer_period(&mut timer.protocol, core::ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+    }
+
+    #[test]
+    fn test_tick_acknowledges_and_notifies_elapsed_period() {
+        with_locked_state(|| {
+            let mut source = MockTickSource::new();
+            source.expect_set_period().returning(|_| Ok(()));
+            source.expect_acknowledge().times(2).return_const(());
+            let timer = timer(source);
+
+            // Ticks before a handler is registered are acknowledged but not reported.
+            timer.tick();
+
+            assert_eq!(set_timer_period(&mut timer.protocol, 1234), efi::Status::SUCCESS);
+            assert_eq!(register_handler(&mut timer.protocol, Some(recording_notify)), efi::Status::SUCCESS);
+            timer.tick();
+            assert_eq!(LAST_TICK.swap(0, Ordering::SeqCst), 1234);
+
+            // A soft interrupt notifies without acknowledging the hardware.
+            assert_eq!(generate_soft_interrupt(&mut timer.protocol), efi::Status::SUCCESS);
+            assert_eq!(LAST_TICK.swap(0, Ordering::SeqCst), 1234);
+        });
+    }
+
+    #[test]
+    fn test_ticks_expire_core_timer_events() {
+        static EVENT_SIGNALLED: AtomicBool = AtomicBool::new(false);
+        extern "efiapi" fn timer_event_notify(_event: efi::Event, _context: *mut c_void) {
+            EVENT_SIGNALLED.store(true, Ordering::SeqCst);
+        }
+
+        with_locked_state(|| {
+            let mut source = MockTickSource::new();
+            source.expect_set_period().returning(|_| Ok(()));
+            source.expect_acknowledge().return_const(());
+            let timer = timer(source);
+            assert_eq!(set_timer_period(&mut timer.protocol, DEFAULT_TIMER_PERIOD), efi::Status::SUCCESS);
+            assert_eq!(register_handler(&mut timer.protocol, Some(events::timer_tick)), efi::Status::SUCCESS);
+
+            let event = events::EVENT_DB
+                .create_event(
+                    efi::EVT_TIMER | efi::EVT_NOTIFY_SIGNAL,
+                    efi::TPL_CALLBACK,
+                    Some(timer_event_notify),
+                    None,
+                    None,
+                )
+                .unwrap();
+            EVENT_SIGNALLED.store(false, Ordering::SeqCst);
+            assert_eq!(events::set_timer(event, efi::TIMER_RELATIVE, 2 * DEFAULT_TIMER_PERIOD), efi::Status::SUCCESS);
+
+            timer.tick();
+            assert!(!EVENT_SIGNALLED.load(Ordering::SeqCst));
+            timer.tick();
+            assert!(EVENT_SIGNALLED.load(Ordering::SeqCst));
+
+            events::EVENT_DB.close_event(event).unwrap();
+        });
+    }
+}
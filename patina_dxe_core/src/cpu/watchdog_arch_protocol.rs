@@ -0,0 +1,274 @@
+//! DXE Core Watchdog Timer Architectural Protocol
+//!
+//! Produces the Watchdog Timer Architectural Protocol with a timer event. When the watchdog expires, the registered
+//! handler is responsible for resetting the platform. The reset hook provided by the platform is only used when no
+//! handler is registered.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::boxed::Box;
+use core::{
+    ffi::c_void,
+    ptr,
+    sync::atomic::{AtomicPtr, AtomicU64, Ordering},
+};
+use patina::{
+    boot_services::{BootServices, StandardBootServices},
+    component::component,
+    error::Result,
+    pi::protocols::watchdog::{PROTOCOL_GUID, Protocol, WatchdogTimerNotify},
+};
+use r_efi::efi;
+use spin::RwLock;
+
+use crate::events::{self, EVENT_DB};
+
+#[repr(C)]
+struct EfiWatchdogArchProtocolImpl {
+    protocol: Protocol,
+
+    // Crate accessible fields
+    event: AtomicPtr<c_void>,
+    period: AtomicU64,
+    notify: RwLock<Option<WatchdogTimerNotify>>,
+    reset: fn(),
+}
+
+// Helper function to convert a raw pointer to a reference, returning `None` for a null pointer.
+fn get_impl_ref<'a>(this: *const Protocol) -> Option<&'a EfiWatchdogArchProtocolImpl> {
+    // Safety: a non-null `this` is the protocol field of an EfiWatchdogArchProtocolImpl, which is #[repr(C)].
+    unsafe { this.cast::<EfiWatchdogArchProtocolImpl>().as_ref() }
+}
+
+// EfiWatchdogArchProtocolImpl function pointers implementations.
+
+extern "efiapi" fn register_handler(
+    this: *const Protocol,
+    notify_function: Option<WatchdogTimerNotify>,
+) -> efi::Status {
+    let Some(watchdog) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    // C callers pass a null function pointer, `None`, to unregister the handler.
+    let mut notify = watchdog.notify.write();
+    match (*notify, notify_function) {
+        (None, None) => efi::Status::INVALID_PARAMETER,
+        (Some(_), Some(_)) => efi::Status::ALREADY_STARTED,
+        (_, notify_function) => {
+            *notify = notify_function;
+            efi::Status::SUCCESS
+        }
+    }
+}
+
+extern "efiapi" fn set_timer_period(this: *const Protocol, timer_period: u64) -> efi::Status {
+    let Some(watchdog) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+
+    let timer_type = if timer_period == 0 { efi::TIMER_CANCEL } else { efi::TIMER_RELATIVE };
+    let status = events::set_timer(watchdog.event.load(Ordering::SeqCst), timer_type, timer_period);
+    if status.is_error() {
+        return efi::Status::DEVICE_ERROR;
+    }
+    watchdog.period.store(timer_period, Ordering::SeqCst);
+    efi::Status::SUCCESS
+}
+
+extern "efiapi" fn get_timer_period(this: *const Protocol, timer_period: *mut u64) -> efi::Status {
+    let Some(watchdog) = get_impl_ref(this) else {
+        return efi::Status::INVALID_PARAMETER;
+    };
+    if timer_period.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // Safety: caller must ensure that timer_period is a valid pointer. It is null-checked above.
+    unsafe { timer_period.write_unaligned(watchdog.period.load(Ordering::SeqCst)) };
+    efi::Status::SUCCESS
+}
+
+// Invoked when the watchdog timer event expires. The context is the EfiWatchdogArchProtocolImpl the event belongs to.
+//
+// A registered handler takes over the reset, as the core handler resets through ResetSystem() so that reset filters
+// and notifications run. The platform reset hook is the only path when no handler is registered.
+extern "efiapi" fn watchdog_expired(_event: efi::Event, context: *mut c_void) {
+    let Some(watchdog) = get_impl_ref(context as *const Protocol) else {
+        return;
+    };
+
+    let notify = *watchdog.notify.read();
+    match notify {
+        Some(notify) => notify(watchdog.period.load(Ordering::SeqCst)),
+        None => {
+            log::error!("Watchdog timer expired with no handler registered, resetting the platform.");
+            (watchdog.reset)();
+        }
+    }
+}
+
+impl EfiWatchdogArchProtocolImpl {
+    fn new(reset: fn()) -> Self {
+        Self {
+            protocol: Protocol { register_handler, set_timer_period, get_timer_period },
+
+            // private data
+            event: AtomicPtr::new(ptr::null_mut()),
+            period: AtomicU64::new(0),
+            notify: RwLock::new(None),
+            reset,
+        }
+    }
+
+    /// Creates the timer event that expires the watchdog.
+    fn create_event(&'static self) -> Result<()> {
+        let event = EVENT_DB.create_event(
+            efi::EVT_TIMER | efi::EVT_NOTIFY_SIGNAL,
+            efi::TPL_NOTIFY,
+            Some(watchdog_expired),
+            Some(self as *const Self as *mut c_void),
+            None,
+        )?;
+        self.event.store(event, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// This component installs the watchdog timer arch protocol.
+///
+/// When the watchdog expires, the handler registered by the core resets the platform through `ResetSystem()`. The
+/// platform provided `reset` hook is only invoked when no handler is registered, such as before the core registers
+/// its handler. Platforms that produce the watchdog timer arch protocol through a separate driver must not add this
+/// component.
+pub struct WatchdogArchProtocolInstaller {
+    reset: fn(),
+}
+
+#[component]
+impl WatchdogArchProtocolInstaller {
+    /// Creates a new `WatchdogArchProtocolInstaller` that resets the platform with `reset` when the watchdog expires
+    /// and no handler is registered.
+    pub fn new(reset: fn()) -> Self {
+        Self { reset }
+    }
+
+    fn entry_point(self, bs: StandardBootServices) -> Result<()> {
+        let watchdog: &'static EfiWatchdogArchProtocolImpl =
+            Box::leak(Box::new(EfiWatchdogArchProtocolImpl::new(self.reset)));
+        watchdog.create_event().inspect_err(|_| log::error!("Failed to create the watchdog timer event"))?;
+
+        let protocol = &watchdog.protocol as *const Protocol as *mut c_void;
+        // Safety: the protocol is the first field of a leaked EfiWatchdogArchProtocolImpl, so it lives forever.
+        unsafe { bs.install_protocol_interface_unchecked(None, &PROTOCOL_GUID, protocol) }
+            .inspect_err(|_| log::error!("Failed to install EFI_WATCHDOG_TIMER_ARCH_PROTOCOL"))?;
+        log::info!("installed EFI_WATCHDOG_TIMER_ARCH_PROTOCOL_GUID");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    use core::sync::atomic::AtomicBool;
+
+    fn with_locked_state<F: Fn() + std::panic::RefUnwindSafe>(f: F) {
+        crate::test_support::with_global_lock(|| {
+            f();
+        })
+        .unwrap();
+    }
+
+    static NOTIFIED_PERIOD: AtomicU64 = AtomicU64::new(0);
+    extern "efiapi" fn recording_notify(time: u64) {
+        NOTIFIED_PERIOD.store(time, Ordering::SeqCst);
+    }
+
+    static RESET: AtomicBool = AtomicBool::new(false);
+    fn recording_reset() {
+        RESET.store(true, Ordering::SeqCst);
+    }
+
+    fn watchdog() -> &'static EfiWatchdogArchProtocolImpl {
+        let watchdog: &'static EfiWatchdogArchProtocolImpl =
+            Box::leak(Box::new(EfiWatchdogArchProtocolImpl::new(recording_reset)));
+        watchdog.create_event().unwrap();
+        watchdog
+    }
+
+    #[test]
+    fn test_register_handler() {
+        with_locked_state(|| {
+            let watchdog = watchdog();
+
+            assert_eq!(register_handler(ptr::null(), Some(recording_notify)), efi::Status::INVALID_PARAMETER);
+            assert_eq!(register_handler(&watchdog.protocol, Some(recording_notify)), efi::Status::SUCCESS);
+            assert_eq!(register_handler(&watchdog.protocol, Some(recording_notify)), efi::Status::ALREADY_STARTED);
+            assert_eq!(register_handler(&watchdog.protocol, None), efi::Status::SUCCESS);
+            assert!(watchdog.notify.read().is_none());
+            assert_eq!(register_handler(&watchdog.protocol, None), efi::Status::INVALID_PARAMETER);
+
+            EVENT_DB.close_event(watchdog.event.load(Ordering::SeqCst)).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_watchdog_expiry_notifies_the_handler_instead_of_resetting() {
+        with_locked_state(|| {
+            let watchdog = watchdog();
+            RESET.store(false, Ordering::SeqCst);
+            NOTIFIED_PERIOD.store(0, Ordering::SeqCst);
+            assert_eq!(register_handler(&watchdog.protocol, Some(recording_notify)), efi::Status::SUCCESS);
+
+            assert_eq!(set_timer_period(&watchdog.protocol, 300), efi::Status::SUCCESS);
+            let mut period = 0;
+            assert_eq!(get_timer_period(&watchdog.protocol, &mut period), efi::Status::SUCCESS);
+            assert_eq!(period, 300);
+
+            events::timer_tick(200);
+            assert_eq!(NOTIFIED_PERIOD.load(Ordering::SeqCst), 0);
+            events::timer_tick(100);
+            assert_eq!(NOTIFIED_PERIOD.load(Ordering::SeqCst), 300);
+            assert!(!RESET.load(Ordering::SeqCst));
+
+            EVENT_DB.close_event(watchdog.event.load(Ordering::SeqCst)).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_watchdog_expiry_resets_without_a_handler() {
+        with_locked_state(|| {
+            let watchdog = watchdog();
+            RESET.store(false, Ordering::SeqCst);
+
+            assert_eq!(set_timer_period(&watchdog.protocol, 300), efi::Status::SUCCESS);
+            events::timer_tick(300);
+            assert!(RESET.load(Ordering::SeqCst));
+
+            EVENT_DB.close_event(watchdog.event.load(Ordering::SeqCst)).unwrap();
+        });
+    }
+
+    #[test]
+    fn test_disabled_watchdog_does_not_expire() {
+        with_locked_state(|| {
+            let watchdog = watchdog();
+            RESET.store(false, Ordering::SeqCst);
+
+            assert_eq!(set_timer_period(&watchdog.protocol, 300), efi::Status::SUCCESS);
+            assert_eq!(set_timer_period(&watchdog.protocol, 0), efi::Status::SUCCESS);
+            events::timer_tick(500);
+            assert!(!RESET.load(Ordering::SeqCst));
+
+            assert_eq!(get_timer_period(&watchdog.protocol, ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+            EVENT_DB.close_event(watchdog.event.load(Ordering::SeqCst)).unwrap();
+        });
+    }
+}
@@ -0,0 +1,151 @@
+//! DXE Core Dispatch Report
+//!
+//! Records every dispatch of a Patina component or UEFI driver, and keeps a snapshot of what was left undispatched
+//! after each dispatch pass. The report is produced as the [DispatchReporter] service, and installed as the
+//! [DISPATCH_REPORT_TABLE](patina::guids::DISPATCH_REPORT_TABLE) configuration table at ReadyToBoot.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{string::String, vec::Vec};
+use core::ptr;
+
+use patina::{
+    component::service::{
+        IntoService,
+        dispatch_report::{
+            DispatchKind, DispatchRecord, DispatchReport, DispatchReportTableHeader, DispatchReporter,
+            UndispatchedComponent, UndispatchedDriver,
+        },
+    },
+    error::EfiError,
+    guids,
+};
+use r_efi::efi;
+
+use crate::{
+    allocator::{core_allocate_pool, core_free_pool},
+    config_tables::core_install_configuration_table,
+    cpu, systemtables,
+    tpl_mutex::TplMutex,
+};
+
+static DISPATCH_REPORT: TplMutex<DispatchReport> = TplMutex::new(
+    efi::TPL_NOTIFY,
+    DispatchReport {
+        perf_frequency: 0,
+        dispatched: Vec::new(),
+        undispatched_components: Vec::new(),
+        undispatched_drivers: Vec::new(),
+    },
+    "DispatchReport",
+);
+
+/// Returns the timestamp to record for a dispatch that starts now.
+pub(crate) fn timestamp() -> u64 {
+    cpu::arch_cpu_count()
+}
+
+/// Sets the frequency of the counter that [timestamp] reads.
+pub(crate) fn set_perf_frequency(perf_frequency: u64) {
+    DISPATCH_REPORT.lock().perf_frequency = perf_frequency;
+}
+
+/// Appends a dispatch to the report.
+///
+/// Must not be called while the dispatched entry point runs, as it may dispatch as well.
+pub(crate) fn record_dispatch(kind: DispatchKind, name: String, timestamp: u64, status: efi::Status) {
+    DISPATCH_REPORT.lock().dispatched.push(DispatchRecord { kind, name, timestamp, status });
+}
+
+/// Replaces what the report lists as undispatched.
+pub(crate) fn set_undispatched(components: Vec<UndispatchedComponent>, drivers: Vec<UndispatchedDriver>) {
+    let mut report = DISPATCH_REPORT.lock();
+    report.undispatched_components = components;
+    report.undispatched_drivers = drivers;
+}
+
+/// Installs the report as a configuration table, replacing the one installed at a previous ReadyToBoot.
+#[coverage(off)]
+pub(crate) fn install_dispatch_report_table() -> Result<(), EfiError> {
+    let json = DISPATCH_REPORT.lock().to_json();
+    let length = u32::try_from(json.len()).map_err(|_| EfiError::OutOfResources)?;
+    let table = core_allocate_pool(efi::BOOT_SERVICES_DATA, size_of::<DispatchReportTableHeader>() + json.len())?;
+
+    // SAFETY: The pool allocation is large enough for the header followed by the JSON text, and pool allocations are
+    // aligned for the header.
+    unsafe {
+        let header = table as *mut DispatchReportTableHeader;
+        header.write(DispatchReportTableHeader { version: DispatchReportTableHeader::VERSION, length });
+        ptr::copy_nonoverlapping(json.as_ptr(), header.add(1) as *mut u8, json.len());
+    }
+
+    let mut st = systemtables::SYSTEM_TABLE.lock();
+    let st = st.as_mut().expect("System Table not initialized!");
+    match core_install_configuration_table(guids::DISPATCH_REPORT_TABLE, table, st) {
+        Ok(previous) => {
+            if let Some(previous) = previous {
+                core_free_pool(previous.as_ptr())?;
+            }
+            Ok(())
+        }
+        Err(err) => {
+            core_free_pool(table)?;
+            Err(err)
+        }
+    }
+}
+
+/// Provides the dispatch report recorded by the core.
+#[derive(IntoService, Default)]
+#[service(dyn DispatchReporter)]
+pub(crate) struct CoreDispatchReporter;
+
+impl DispatchReporter for CoreDispatchReporter {
+    fn report(&self) -> DispatchReport {
+        DISPATCH_REPORT.lock().clone()
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::test_support::with_global_lock;
+    use alloc::vec;
+    use patina::component::service::dispatch_report::Requirement;
+
+    #[test]
+    fn test_reporter_returns_recorded_dispatches() {
+        with_global_lock(|| {
+            let guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+            set_perf_frequency(1_000_000);
+            let start = timestamp();
+            record_dispatch(DispatchKind::Component, "test::Component".into(), start, efi::Status::SUCCESS);
+            set_undispatched(
+                vec![UndispatchedComponent {
+                    name: "test::Waiting".into(),
+                    unmet: vec![Requirement::Service("dyn test::Service".into())],
+                }],
+                vec![UndispatchedDriver { file: guid, unsatisfied: vec![guid] }],
+            );
+
+            let report = CoreDispatchReporter.report();
+            assert_eq!(report.perf_frequency, 1_000_000);
+            // Tests of the dispatchers that run concurrently record dispatches as well.
+            assert!(report.dispatched.contains(&DispatchRecord {
+                kind: DispatchKind::Component,
+                name: "test::Component".into(),
+                timestamp: start,
+                status: efi::Status::SUCCESS,
+            }));
+            assert!(timestamp() >= start);
+            assert_eq!(report.undispatched_components[0].name, "test::Waiting");
+            assert_eq!(report.undispatched_drivers, vec![UndispatchedDriver { file: guid, unsatisfied: vec![guid] }]);
+        })
+        .unwrap();
+    }
+}
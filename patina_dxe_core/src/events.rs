@@ -291,7 +291,7 @@ pub extern "efiapi" fn restore_tpl(new_tpl: efi::Tpl) {
     }
 }
 
-extern "efiapi" fn timer_tick(time: u64) {
+pub(crate) extern "efiapi" fn timer_tick(time: u64) {
     let old_tpl = raise_tpl(efi::TPL_HIGH_LEVEL);
     SYSTEM_TIME.fetch_add(time, Ordering::SeqCst);
     let current_time = SYSTEM_TIME.load(Ordering::SeqCst);
@@ -304,7 +304,7 @@ extern "efiapi" fn timer_available_callback(event: efi::Event, _context: *mut c_
         Ok(timer_arch_ptr) => {
             let timer_arch_ptr = timer_arch_ptr as *mut timer::Protocol;
             let timer_arch = unsafe { &*(timer_arch_ptr) };
-            (timer_arch.register_handler)(timer_arch_ptr, timer_tick);
+            (timer_arch.register_handler)(timer_arch_ptr, Some(timer_tick));
             if let Err(status_err) = EVENT_DB.close_event(event) {
                 log::warn!("Could not close event for timer_available_callback due to error {status_err:?}");
             }
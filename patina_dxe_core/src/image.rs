@@ -317,6 +317,7 @@ impl PrivateImageData {
             runtime::add_runtime_image(
                 self.image_info.image_base,
                 self.image_info.image_size,
+                &self.pe_info,
                 &self.relocation_data,
                 handle,
             )?;
@@ -703,8 +704,9 @@ extern "efiapi" fn runtime_image_protection_fixup_ebs(event: efi::Event, _contex
 }
 
 // Reads an image buffer using simple file system or load file protocols.
-// Return value is (image_buffer, device_handle, from_fv, authentication_status).
-// Note: presently none of the supported methods return `from_fv` or `authentication_status`.
+// Return value is (image_buffer, from_fv, device_handle, authentication_status).
+// Note: images read from a firmware volume file set `from_fv`, but presently no method returns an
+// `authentication_status`.
 fn get_buffer_by_file_path(
     boot_policy: bool,
     file_path: *mut efi::protocols::device_path::Protocol,
@@ -908,8 +910,12 @@ fn authenticate_image(
             image.len(),
             boot_policy,
         );
-        if security_status == efi::Status::SUCCESS && from_fv {
-            let security = security_protocol.expect("Security Arch must be installed if Security2 Arch is installed");
+        // The Security Arch protocol is expected alongside Security2, but drivers dispatched before it is installed
+        // are only checked against Security2.
+        if security_status == efi::Status::SUCCESS
+            && from_fv
+            && let Some(security) = security_protocol
+        {
             security_status = (security.file_authentication_state)(
                 security as *const _ as *mut pi::protocols::security::Protocol,
                 authentication_status,
@@ -947,6 +953,8 @@ impl From<EfiError> for ImageStatus {
 /// * parent_image_handle - the handle of the image that is loading this one.
 /// * file_path - optional device path describing where to load the image from.
 /// * image - optional slice containing the image data.
+/// * fv_authentication_status - for an `image` extracted from a firmware volume file, the authentication status of
+///   the section it was read from. Such images are authenticated as images loaded from a firmware volume.
 ///
 /// One of `file_path` or `image` must be specified.
 /// returns the image handle of the freshly loaded image.
@@ -959,6 +967,7 @@ pub fn core_load_image(
     parent_image_handle: efi::Handle,
     file_path: *mut efi::protocols::device_path::Protocol,
     image: Option<&[u8]>,
+    fv_authentication_status: Option<u32>,
 ) -> Result<efi::Handle, ImageStatus> {
     perf_load_image_begin(core::ptr::null_mut(), create_performance_measurement);
 
@@ -980,16 +989,18 @@ pub fn core_load_image(
 
     let (image_to_load, from_fv, device_handle, authentication_status) = match image {
         Some(image) => {
+            let (from_fv, authentication_status) =
+                (fv_authentication_status.is_some(), fv_authentication_status.unwrap_or(0));
             // If the buffer is specified and the device_path resolves with core_locate_device_path, then use the
             // resolved handle as the device_handle. Note: the associated device path for the device_handle will
             // likely be shorter than file_path.
             if let Ok((_device_path, device_handle)) =
                 core_locate_device_path(efi::protocols::device_path::PROTOCOL_GUID, file_path)
             {
-                (image.to_vec(), false, device_handle, 0)
+                (image.to_vec(), from_fv, device_handle, authentication_status)
             } else {
                 // (i.e. it doesn't correspond to anything that actually exists in the system)
-                (image.to_vec(), false, protocol_db::INVALID_HANDLE, 0)
+                (image.to_vec(), from_fv, protocol_db::INVALID_HANDLE, authentication_status)
             }
         }
         None => get_buffer_by_file_path(boot_policy, file_path)?,
@@ -1131,7 +1142,7 @@ extern "efiapi" fn load_image(
         Some(unsafe { from_raw_parts(source_buffer as *const u8, source_size) })
     };
 
-    let (handle, status) = match core_load_image(boot_policy.into(), parent_image_handle, device_path, image) {
+    let (handle, status) = match core_load_image(boot_policy.into(), parent_image_handle, device_path, image, None) {
         Ok(handle) => (handle, efi::Status::SUCCESS),
         Err(ImageStatus::AccessDenied) => (null_mut(), efi::Status::ACCESS_DENIED),
         Err(ImageStatus::SecurityViolation(handle)) => (handle, efi::Status::SECURITY_VIOLATION),
@@ -1473,7 +1484,7 @@ impl Buffer {
 #[coverage(off)]
 mod tests {
     extern crate std;
-    use super::{empty_image_info, get_buffer_by_file_path, load_image};
+    use super::{ImageStatus, core_load_image, empty_image_info, get_buffer_by_file_path, load_image};
     use crate::{
         image::{PRIVATE_IMAGE_DATA, PrivateImageData, exit, start_image, unload_image},
         pecoff::UefiPeInfo,
@@ -1788,8 +1799,8 @@ mod tests {
                 _authentication_status: u32,
                 _file: *mut efi::protocols::device_path::Protocol,
             ) -> efi::Status {
-                // should not be called, since `from_fv` is not presently true in our implementation for any
-                // source of FV, which means only Security2 should be used.
+                // should not be called, since the image passed to core_load_image below is not from a firmware
+                // volume, which means only Security2 should be used.
                 unreachable!()
             }
 
@@ -1858,6 +1869,114 @@ mod tests {
         });
     }
 
+    #[test]
+    fn load_image_from_fv_should_pass_the_authentication_status_to_security_arch() {
+        with_locked_state(|| {
+            let mut test_file =
+                File::open(test_collateral!("test_image_msvc_hii.pe32")).expect("failed to open test file.");
+            let mut image: Vec<u8> = Vec::new();
+            test_file.read_to_end(&mut image).expect("failed to read test file");
+
+            const AUTHENTICATION_STATUS: u32 = patina::pi::fw_fs::ffs::section::auth_status::IMAGE_SIGNED
+                | patina::pi::fw_fs::ffs::section::auth_status::TEST_FAILED;
+
+            // Mock Security Arch protocol
+            static SECURITY_CALL_EXECUTED: AtomicBool = AtomicBool::new(false);
+            extern "efiapi" fn mock_file_authentication_state(
+                _this: *mut pi::protocols::security::Protocol,
+                authentication_status: u32,
+                _file: *mut efi::protocols::device_path::Protocol,
+            ) -> efi::Status {
+                assert_eq!(authentication_status, AUTHENTICATION_STATUS);
+                SECURITY_CALL_EXECUTED.store(true, core::sync::atomic::Ordering::SeqCst);
+                efi::Status::SECURITY_VIOLATION
+            }
+
+            // Mock Security2 Arch protocol
+            extern "efiapi" fn mock_file_authentication(
+                _this: *mut pi::protocols::security2::Protocol,
+                _file: *mut efi::protocols::device_path::Protocol,
+                _file_buffer: *mut c_void,
+                _file_size: usize,
+                _boot_policy: bool,
+            ) -> efi::Status {
+                efi::Status::SUCCESS
+            }
+
+            let security_protocol =
+                pi::protocols::security::Protocol { file_authentication_state: mock_file_authentication_state };
+            let security2_protocol =
+                pi::protocols::security2::Protocol { file_authentication: mock_file_authentication };
+
+            PROTOCOL_DB
+                .install_protocol_interface(
+                    None,
+                    pi::protocols::security::PROTOCOL_GUID,
+                    &security_protocol as *const _ as *mut _,
+                )
+                .unwrap();
+            PROTOCOL_DB
+                .install_protocol_interface(
+                    None,
+                    pi::protocols::security2::PROTOCOL_GUID,
+                    &security2_protocol as *const _ as *mut _,
+                )
+                .unwrap();
+
+            let result = core_load_image(
+                false,
+                protocol_db::DXE_CORE_HANDLE,
+                core::ptr::null_mut(),
+                Some(&image),
+                Some(AUTHENTICATION_STATUS),
+            );
+            assert!(matches!(result, Err(ImageStatus::SecurityViolation(handle)) if !handle.is_null()));
+            assert!(SECURITY_CALL_EXECUTED.load(core::sync::atomic::Ordering::SeqCst));
+        });
+    }
+
+    #[test]
+    fn load_image_from_fv_should_succeed_with_only_security2_arch() {
+        with_locked_state(|| {
+            let mut test_file =
+                File::open(test_collateral!("test_image_msvc_hii.pe32")).expect("failed to open test file.");
+            let mut image: Vec<u8> = Vec::new();
+            test_file.read_to_end(&mut image).expect("failed to read test file");
+
+            // Mock Security2 Arch protocol
+            extern "efiapi" fn mock_file_authentication(
+                _this: *mut pi::protocols::security2::Protocol,
+                _file: *mut efi::protocols::device_path::Protocol,
+                _file_buffer: *mut c_void,
+                _file_size: usize,
+                _boot_policy: bool,
+            ) -> efi::Status {
+                efi::Status::SUCCESS
+            }
+
+            let security2_protocol =
+                pi::protocols::security2::Protocol { file_authentication: mock_file_authentication };
+
+            PROTOCOL_DB
+                .install_protocol_interface(
+                    None,
+                    pi::protocols::security2::PROTOCOL_GUID,
+                    &security2_protocol as *const _ as *mut _,
+                )
+                .unwrap();
+
+            // Drivers dispatched before the Security Arch protocol is installed are only checked by Security2.
+            let result = core_load_image(
+                false,
+                protocol_db::DXE_CORE_HANDLE,
+                core::ptr::null_mut(),
+                Some(&image),
+                Some(patina::pi::fw_fs::ffs::section::auth_status::IMAGE_SIGNED),
+            );
+            assert!(result.is_ok());
+        });
+    }
+
     #[test]
     fn load_image_with_auth_err_security_violation_should_continue_to_load_image() {
         with_locked_state(|| {
@@ -76,6 +76,7 @@ mod cpu;
 #[cfg(feature = "debugger_reload")]
 mod debugger_reload;
 mod decompress;
+mod dispatch_report;
 mod driver_services;
 mod dxe_services;
 mod event_db;
@@ -98,7 +99,14 @@ mod tpl_mutex;
 pub use {component_dispatcher::MockComponentInfo, cpu::MockCpuInfo};
 
 pub use component_dispatcher::{Add, Component, ComponentInfo, Config, Service};
-pub use cpu::{CpuInfo, GicBases};
+pub use cpu::{
+    CpuInfo, GicBases, MetronomeArchProtocolInstaller, TickSource, TimerArchProtocolInstaller,
+    WatchdogArchProtocolInstaller,
+};
+#[cfg(all(target_os = "uefi", target_arch = "aarch64"))]
+pub use cpu::{DEFAULT_EL1_PHYSICAL_TIMER_INTERRUPT, GenericTimer};
+#[cfg(target_arch = "x86_64")]
+pub use cpu::{HpetTimer, LocalApicTimer};
 
 use spin::Once;
 
@@ -122,7 +130,7 @@ use memory_manager::CoreMemoryManager;
 use mu_rust_helpers::{function, guid::CALLER_ID};
 use patina::{
     boot_services::StandardBootServices,
-    component::IntoComponent,
+    component::{IntoComponent, service::perf_timer::ArchTimerFunctionality},
     error::{self, Result},
     performance::{
         logging::{perf_function_begin, perf_function_end},
@@ -451,15 +459,17 @@ impl<P: PlatformInfo> Core<P> {
         patina_debugger::add_monitor_command("gcd", "Prints the GCD", |_, out| {
             let _ = write!(out, "GCD -\n{GCD}");
         });
+        patina_debugger::set_reset_handler(|| runtime::reset_system(efi::RESET_COLD, efi::Status::SUCCESS, &[]));
 
         #[cfg(feature = "debugger_reload")]
         debugger_reload::initialize_debugger_reload(physical_hob_list);
 
+        // The debugger, the service and the dispatch report share one timer, so the frequency is calibrated once.
+        let perf_timer: &'static cpu::PerfTimer =
+            Box::leak(Box::new(cpu::PerfTimer::with_frequency(P::CpuInfo::perf_timer_frequency().unwrap_or(0))));
+
         // Initialize the debugger if it is enabled.
-        patina_debugger::initialize(
-            &mut interrupt_manager,
-            Some(Box::leak(Box::new(cpu::PerfTimer::with_frequency(P::CpuInfo::perf_timer_frequency().unwrap_or(0))))),
-        );
+        patina_debugger::initialize(&mut interrupt_manager, Some(perf_timer));
 
         #[cfg(feature = "debugger_reload")]
         debugger_reload::tear_down_debugger_reload();
@@ -470,8 +480,11 @@ impl<P: PlatformInfo> Core<P> {
         component_dispatcher.add_service(cpu);
         component_dispatcher.add_service(interrupt_manager);
         component_dispatcher.add_service(CoreMemoryManager);
-        component_dispatcher
-            .add_service(cpu::PerfTimer::with_frequency(P::CpuInfo::perf_timer_frequency().unwrap_or(0)));
+        component_dispatcher.add_service(systemtables::CoreRuntimeServicesTable::default());
+        component_dispatcher.add_service(perf_timer);
+        component_dispatcher.add_service(dispatch_report::CoreDispatchReporter);
+        component_dispatcher.add_service(pi_dispatcher::CoreFirmwareVolumes::new::<P>());
+        dispatch_report::set_perf_frequency(perf_timer.perf_frequency());
 
         relocated_hob_list
     }
@@ -479,12 +492,17 @@ impl<P: PlatformInfo> Core<P> {
     /// Performs a combined dispatch of Patina components and UEFI drivers.
     ///
     /// This function will continue to loop and perform dispatching until no components have been dispatched in a full
-    /// iteration. The dispatching process involves a loop of two distinct dispatch phases:
+    /// iteration and no protocol has been installed since the previous one. The dispatching process involves a loop of
+    /// two distinct dispatch phases:
     ///
     /// 1. A single iteration of dispatching Patina components, retaining those that were not dispatched.
     /// 2. A single iteration of dispatching UEFI drivers via the dispatcher module.
+    ///
+    /// Components waiting on a `Protocol` param are retried whenever the set of installed protocols changes, even if
+    /// the protocol was installed outside of dispatch, such as by an event notification.
     fn core_dispatcher(&self) -> Result<()> {
         perf_function_begin(function!(), &CALLER_ID, create_performance_measurement);
+        let mut protocols = PROTOCOL_DB.registered_protocols();
         loop {
             // Patina component dispatch
             let dispatched = self.component_dispatcher.lock().dispatch();
@@ -496,15 +514,94 @@ impl<P: PlatformInfo> Core<P> {
                     .dispatch()
                     .inspect_err(|err| log::error!("UEFI Driver Dispatch error: {err:?}"))?;
 
-            if !dispatched {
+            let installed = PROTOCOL_DB.registered_protocols();
+            if !dispatched && installed == protocols {
                 break;
             }
+            protocols = installed;
         }
+        self.update_dispatch_report();
         perf_function_end(function!(), &CALLER_ID, create_performance_measurement);
 
         Ok(())
     }
 
+    /// Updates the dispatch report with the components and drivers that have not been dispatched.
+    fn update_dispatch_report(&self) {
+        // A component signaling ReadyToBoot holds the dispatcher lock; the report of the last pass is kept then.
+        let Some(component_dispatcher) = self.component_dispatcher.try_lock() else {
+            return;
+        };
+        let components = component_dispatcher.undispatched_components();
+        drop(component_dispatcher);
+        dispatch_report::set_undispatched(components, self.pi_dispatcher.undispatched_drivers());
+    }
+
+    /// Registers an event for each event group that triggers a component, so that the components it triggers are
+    /// dispatched when the group is signalled.
+    ///
+    /// Components triggered by the ExitBootServices event groups fail to initialize, so no event is registered for
+    /// those groups and the dispatch report is never updated while ExitBootServices is running.
+    fn init_component_triggers(&self) {
+        let triggers = self.component_dispatcher.lock().pending_triggers();
+        for event_group in triggers {
+            // The event group is the context of the notify function, so it is leaked to live as long as the event.
+            let context = Box::into_raw(Box::new(event_group));
+            if let Err(status) = events::EVENT_DB.create_event(
+                efi::EVT_NOTIFY_SIGNAL,
+                efi::TPL_CALLBACK,
+                Some(Self::dispatch_triggered_components_efiapi),
+                Some(context.cast()),
+                Some(event_group),
+            ) {
+                log::error!(
+                    "Failed to register an event for components triggered by event group {}! Status {status:#X?}",
+                    patina::Guid::from_ref(&event_group)
+                );
+                // SAFETY: The event was not created, so the context was never shared.
+                drop(unsafe { Box::from_raw(context) });
+            }
+        }
+    }
+
+    extern "efiapi" fn dispatch_triggered_components_efiapi(_event: efi::Event, context: *mut c_void) {
+        // SAFETY: The context is the event group leaked for this event by init_component_triggers.
+        let event_group = unsafe { *context.cast::<efi::Guid>() };
+        let core = Self::instance();
+        let Some(mut component_dispatcher) = core.component_dispatcher.try_lock() else {
+            log::error!(
+                "Component dispatcher busy, components triggered by event group {} were not dispatched.",
+                patina::Guid::from_ref(&event_group)
+            );
+            return;
+        };
+        component_dispatcher.signal_event_group(event_group);
+        drop(component_dispatcher);
+        core.update_dispatch_report();
+    }
+
+    /// Registers the ReadyToBoot event that publishes the dispatch report as a configuration table.
+    fn init_dispatch_report_support(&self) {
+        if let Err(status) = events::EVENT_DB.create_event(
+            efi::EVT_NOTIFY_SIGNAL,
+            efi::TPL_CALLBACK,
+            Some(Self::install_dispatch_report_efiapi),
+            None,
+            Some(efi::EVENT_GROUP_READY_TO_BOOT),
+        ) {
+            log::error!(
+                "Failed to register an event at Ready to Boot to install the dispatch report! Status {status:#X?}"
+            );
+        }
+    }
+
+    extern "efiapi" fn install_dispatch_report_efiapi(_event: efi::Event, _context: *mut c_void) {
+        Self::instance().update_dispatch_report();
+        if let Err(err) = dispatch_report::install_dispatch_report_table() {
+            log::error!("Failed to install the dispatch report table: {err:?}");
+        }
+    }
+
     fn initialize_system_table(&self, physical_hob_list: *mut c_void) -> Result<()> {
         // Instantiate system table.
         systemtables::init_system_table();
@@ -518,7 +615,7 @@ impl<P: PlatformInfo> Core<P> {
         protocols::init_protocol_support(st.boot_services_mut());
         misc_boot_services::init_misc_boot_services_support(st.boot_services_mut());
         config_tables::init_config_tables_support(st.boot_services_mut());
-        runtime::init_runtime_support(st.runtime_services_mut());
+        runtime::init_runtime_support(st);
         image::init_image_support(self.hob_list(), st);
         self.pi_dispatcher.init();
         self.install_dxe_services_table(st);
@@ -540,6 +637,7 @@ impl<P: PlatformInfo> Core<P> {
         allocator::install_memory_type_info_table(st).expect("Unable to create Memory Type Info Table");
 
         memory_attributes_table::init_memory_attributes_table_support();
+        self.init_dispatch_report_support();
 
         self.component_dispatcher.lock().set_boot_services(StandardBootServices::new(st.boot_services()));
         self.component_dispatcher.lock().set_runtime_services(StandardRuntimeServices::new(st.runtime_services()));
@@ -583,6 +681,10 @@ impl<P: PlatformInfo> Core<P> {
         self.component_dispatcher.lock().insert_hobs(self.hob_list());
         log::info!("Finished.");
 
+        log::info!("Registering component event triggers.");
+        self.init_component_triggers();
+        log::info!("Finished.");
+
         log::info!("Installing Firmware Volumes from HOB list.");
         self.pi_dispatcher.install_firmware_volumes_from_hoblist(self.hob_list())?;
         log::info!("Finished.");
@@ -6,6 +6,7 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+use alloc::vec::Vec;
 use core::{ffi::c_void, slice::from_raw_parts, sync::atomic::Ordering};
 use patina::{
     guids,
@@ -13,7 +14,7 @@ use patina::{
 };
 use patina_internal_cpu::interrupts;
 use r_efi::efi;
-use spin::Once;
+use spin::{Mutex, Once};
 
 use crate::{
     GCD, allocator::terminate_memory_map, events::EVENT_DB, protocols::PROTOCOL_DB, systemtables::SYSTEM_TABLE,
@@ -45,6 +46,9 @@ unsafe impl<T> Sync for ArchProtocolPtr<T> {}
 static METRONOME_ARCH_PTR: ArchProtocolPtr<protocols::metronome::Protocol> = ArchProtocolPtr::new();
 static WATCHDOG_ARCH_PTR: ArchProtocolPtr<protocols::watchdog::Protocol> = ArchProtocolPtr::new();
 
+// The watchdog code and data of the last SetWatchdogTimer() call, reported when the watchdog expires.
+static WATCHDOG_DATA: Mutex<(u64, Vec<u8>)> = Mutex::new((0, Vec::new()));
+
 // TODO [BEGIN]: LOCAL (TEMP) GUID DEFINITIONS (MOVE LATER)
 
 // These will likely get moved to different places. DXE Core GUID is the GUID of this DXE Core instance.
@@ -107,11 +111,20 @@ extern "efiapi" fn stall(microseconds: usize) -> efi::Status {
 // EFI_BOOT_SERVICES.ExitBootServices() the watchdog timer is disabled.
 extern "efiapi" fn set_watchdog_timer(
     timeout: usize,
-    _watchdog_code: u64,
-    _data_size: usize,
-    _data: *mut efi::Char16,
+    watchdog_code: u64,
+    data_size: usize,
+    data: *mut efi::Char16,
 ) -> efi::Status {
     const WATCHDOG_TIMER_CALIBRATE_PER_SECOND: u64 = 10000000;
+    {
+        let mut watchdog_data = WATCHDOG_DATA.lock();
+        watchdog_data.0 = watchdog_code;
+        watchdog_data.1.clear();
+        if !data.is_null() {
+            // Safety: caller must ensure that data points to data_size bytes. It is null-checked above.
+            watchdog_data.1.extend_from_slice(unsafe { from_raw_parts(data as *const u8, data_size) });
+        }
+    }
     if let Some(watchdog_ptr) = WATCHDOG_ARCH_PTR.get() {
         // Safety: watchdog_ptr is guaranteed to be a valid pointer to the watchdog protocol if it is Some.
         let watchdog = unsafe { watchdog_ptr.as_mut().unwrap() };
@@ -154,6 +167,12 @@ extern "efiapi" fn watchdog_arch_available(event: efi::Event, _context: *mut c_v
             // associated with the watchdog arch guid.
             assert!(!watchdog_arch_ptr.is_null(), "Located watchdog protocol pointer is null.");
             unsafe { WATCHDOG_ARCH_PTR.init(watchdog_arch_ptr) };
+            let watchdog = watchdog_arch_ptr as *const protocols::watchdog::Protocol;
+            // Safety: watchdog is a valid pointer to the watchdog protocol, checked for null above.
+            let status = unsafe { ((*watchdog).register_handler)(watchdog, Some(watchdog_expired)) };
+            if status.is_error() {
+                log::warn!("Could not register the watchdog expiry handler due to error {status:#x?}");
+            }
             if let Err(status_err) = EVENT_DB.close_event(event) {
                 log::warn!("Could not close event for watchdog_arch_available due to error {status_err:?}");
             }
@@ -162,6 +181,19 @@ extern "efiapi" fn watchdog_arch_available(event: efi::Event, _context: *mut c_v
     }
 }
 
+// This handler is invoked by the Watchdog Timer Architectural protocol when the watchdog expires. The expiry is logged
+// with the watchdog code and data from SetWatchdogTimer() and the platform is reset through ResetSystem(), so any
+// reset filters and notifications run as they would for any other reset.
+extern "efiapi" fn watchdog_expired(_time: u64) {
+    match WATCHDOG_DATA.try_lock() {
+        Some(watchdog_data) => {
+            log::error!("Watchdog timer expired. Watchdog code: {:#x}", watchdog_data.0);
+            crate::runtime::reset_system(efi::RESET_COLD, efi::Status::TIMEOUT, &watchdog_data.1);
+        }
+        None => crate::runtime::reset_system(efi::RESET_COLD, efi::Status::TIMEOUT, &[]),
+    }
+}
+
 pub extern "efiapi" fn exit_boot_services(_handle: efi::Handle, map_key: usize) -> efi::Status {
     static EXIT_BOOT_SERVICES_CALLED: Once<()> = Once::new();
 
@@ -450,7 +482,7 @@ mod tests {
             static SET_PERIOD_CALLED: Once<()> = Once::new();
             extern "efiapi" fn register_handler(
                 _this: *const patina::pi::protocols::watchdog::Protocol,
-                _notify: watchdog::WatchdogTimerNotify,
+                _notify: Option<watchdog::WatchdogTimerNotify>,
             ) -> efi::Status {
                 unimplemented!()
             }
@@ -483,6 +515,35 @@ mod tests {
             }
         });
     }
+    #[test]
+    fn test_misc_watchdog_expiry_resets_the_system() {
+        with_locked_state(|st| {
+            static RESET: Mutex<Option<(efi::ResetType, efi::Status, Vec<u8>)>> = Mutex::new(None);
+            extern "efiapi" fn reset_system(
+                reset_type: efi::ResetType,
+                status: efi::Status,
+                data_size: usize,
+                data: *mut c_void,
+            ) {
+                // SAFETY: Test code only - the watchdog data is passed through from the test below.
+                let data = unsafe { from_raw_parts(data as *const u8, data_size) }.to_vec();
+                *RESET.lock() = Some((reset_type, status, data));
+            }
+
+            crate::runtime::init_runtime_support(st);
+            st.runtime_services_mut().reset_system = reset_system;
+
+            let data: [efi::Char16; 3] = [b'W' as u16, b'D' as u16, 0];
+            let _ = set_watchdog_timer(300, 0x1_0000, size_of_val(&data), data.as_ptr() as *mut efi::Char16);
+            watchdog_expired(0);
+
+            let (reset_type, status, reset_data) = RESET.lock().take().expect("ResetSystem() was not called.");
+            assert_eq!(reset_type, efi::RESET_COLD);
+            assert_eq!(status, efi::Status::TIMEOUT);
+            assert_eq!(reset_data, [b'W', 0, b'D', 0, 0, 0]);
+        });
+    }
+
     #[test]
     fn test_misc_stall() {
         with_locked_state(|st| {
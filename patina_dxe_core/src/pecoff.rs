@@ -277,11 +277,11 @@ pub fn relocate_image(
 
     let adjustment = (destination as u64).wrapping_sub(base + rva_offset as u64);
 
-    if adjustment == 0 || pe_info.reloc_dir.is_none() {
+    // Relocations are walked even when the adjustment is zero so that the fixup values are recorded. Runtime images
+    // need them to be re-applied when the OS switches to virtual addressing.
+    let Some(dir) = pe_info.reloc_dir else {
         return Ok(Vec::new());
-    }
-
-    let dir = pe_info.reloc_dir.expect("Reloc Dir was not None above.");
+    };
     let relocation_data = image
         .get((dir.virtual_address as usize)..(dir.virtual_address as usize + dir.size as usize))
         .ok_or(error::Error::BufferTooShort(dir.size as usize, "image"))?;
@@ -297,8 +297,9 @@ pub fn relocate_image(
             match fixup_type {
                 IMAGE_REL_BASED_ABSOLUTE => {}
                 IMAGE_REL_BASED_HIGHLOW => {
-                    let value = image.pread_with::<u32>(fixup, LE)?;
-                    image.pwrite_with(value.wrapping_add(adjustment as u32), fixup, LE)?;
+                    let value = image.pread_with::<u32>(fixup, LE)?.wrapping_add(adjustment as u32);
+                    image.pwrite_with(value, fixup, LE)?;
+                    reloc.value = value as u64;
                 }
                 IMAGE_REL_BASED_DIR64 => {
                     let mut value = image.pread_with::<u64>(fixup, LE)?;
@@ -316,7 +317,9 @@ pub fn relocate_image(
                     let subslice = image.get_mut(fixup..fixup + 8).ok_or(error::Error::BufferTooShort(8, "image"))?;
                     subslice.copy_from_slice(&value.to_le_bytes()[..]);
                 }
-                _ => todo!(), // Other fixups not implemented at this time
+                // Other fixups are not supported, but an image that is not moved needs none of them.
+                _ if adjustment == 0 => {}
+                _ => return Err(error::Error::UnsupportedRelocation(fixup_type)),
             }
         }
     }
@@ -336,7 +339,7 @@ pub fn flatten_runtime_relocation_data(relocation_data: &[RelocationBlock]) -> &
                 IMAGE_REL_BASED_ABSOLUTE => 0,
                 IMAGE_REL_BASED_HIGHLOW => core::mem::size_of::<u32>(),
                 IMAGE_REL_BASED_DIR64 => core::mem::size_of::<u64>(),
-                _ => todo!(), // Other fixups not implemented at this time
+                _ => 0, // Other fixups are only left in images that were not moved, and are not recorded.
             }
         }
     }
@@ -353,7 +356,7 @@ pub fn flatten_runtime_relocation_data(relocation_data: &[RelocationBlock]) -> &
                 IMAGE_REL_BASED_DIR64 => {
                     flat_data.extend_from_slice(&reloc.value.to_le_bytes());
                 }
-                _ => todo!(), // Other fixups not implemented at this time
+                _ => {}
             }
         }
     }
@@ -363,6 +366,77 @@ pub fn flatten_runtime_relocation_data(relocation_data: &[RelocationBlock]) -> &
     flat_data.leak()
 }
 
+/// A single fixup of a loaded runtime image, recorded so it can be re-applied during SetVirtualAddressMap().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuntimeFixup {
+    /// Offset of the fixup from the start of the loaded image.
+    pub offset: usize,
+    /// The `IMAGE_REL_BASED_*` type of the fixup.
+    pub fixup_type: u16,
+    /// The value written at `offset` when the image was last relocated.
+    pub value: u64,
+}
+
+/// Converts the relocation blocks returned by [`relocate_image`] into a list of fixups that can be re-applied to a
+/// runtime image without parsing its headers again.
+///
+/// The list is allocated from runtime services data, as it must remain available after ExitBootServices().
+pub fn runtime_fixups(pe_info: &UefiPeInfo, relocation_data: &[RelocationBlock]) -> &'static [RuntimeFixup] {
+    let rva_offset = match pe_info.header_type {
+        HeaderType::Te(rva_offset) => rva_offset,
+        HeaderType::Pe => 0,
+    };
+
+    let count = relocation_data
+        .iter()
+        .flat_map(|block| block.relocations.iter())
+        .filter(|reloc| reloc.type_and_offset >> 12 != IMAGE_REL_BASED_ABSOLUTE)
+        .count();
+
+    let mut fixups = Vec::with_capacity_in(count, &crate::allocator::EFI_RUNTIME_SERVICES_DATA_ALLOCATOR);
+    for block in relocation_data {
+        for reloc in &block.relocations {
+            let fixup_type = reloc.type_and_offset >> 12;
+            if fixup_type == IMAGE_REL_BASED_ABSOLUTE {
+                continue;
+            }
+            let offset = block.block_header.page_rva as usize + (reloc.type_and_offset & 0xFFF) as usize - rva_offset;
+            fixups.push(RuntimeFixup { offset, fixup_type, value: reloc.value });
+        }
+    }
+    fixups.leak()
+}
+
+/// Re-applies the fixups of a runtime image that is being moved by `adjustment` bytes.
+///
+/// This runs during SetVirtualAddressMap(), so it does not allocate. A fixup is only applied if the image still holds
+/// the value recorded when it was loaded; a value that the driver has since changed is assumed to already be
+/// something other than an image address and is left alone.
+///
+/// ## Errors
+///
+/// Returns [`Parse`](error::Error::Parse) error if a fixup lies outside of the image.
+pub fn relocate_runtime_image(image: &mut [u8], fixups: &[RuntimeFixup], adjustment: u64) -> error::Result<()> {
+    for fixup in fixups {
+        match fixup.fixup_type {
+            IMAGE_REL_BASED_HIGHLOW => {
+                let value = image.pread_with::<u32>(fixup.offset, LE)?;
+                if value as u64 == fixup.value {
+                    image.pwrite_with(value.wrapping_add(adjustment as u32), fixup.offset, LE)?;
+                }
+            }
+            IMAGE_REL_BASED_DIR64 => {
+                let value = image.pread_with::<u64>(fixup.offset, LE)?;
+                if value == fixup.value {
+                    image.pwrite_with(value.wrapping_add(adjustment), fixup.offset, LE)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 /// Attempts to load the HII resource section data for a given PE32 image.
 ///
 /// Extracts the HII resource section data from the provided image, returning None
@@ -719,6 +793,37 @@ mod tests {
         assert_eq!(relocated_image, reclocated_image_copy);
     }
 
+    #[test]
+    fn pe_relocate_image_should_only_reject_unsupported_fixups_when_moving_the_image() {
+        let image = include_bytes!("../resources/test/pe32/test_image.pe32");
+        let image_info = UefiPeInfo::parse(image).unwrap();
+        let mut loaded_image: Vec<u8> = vec![0; image_info.size_of_image as usize];
+        load_image(&image_info, image, &mut loaded_image).unwrap();
+
+        // Turn the first fixup into an IMAGE_REL_BASED_HIGHADJ fixup, which is not supported.
+        let reloc_dir = image_info.reloc_dir.unwrap();
+        let blocks = &loaded_image[reloc_dir.virtual_address as usize..][..reloc_dir.size as usize];
+        let offset = reloc_dir.virtual_address as usize
+            + size_of::<relocation::BaseRelocationBlockHeader>()
+            + 2 * parse_relocation_blocks(blocks).unwrap()[0]
+                .relocations
+                .iter()
+                .position(|reloc| reloc.type_and_offset >> 12 != IMAGE_REL_BASED_ABSOLUTE)
+                .unwrap();
+        let type_and_offset = loaded_image.pread_with::<u16>(offset, LE).unwrap();
+        loaded_image.pwrite_with((type_and_offset & 0xFFF) | (4 << 12), offset, LE).unwrap();
+
+        let base = loaded_image.pread_with::<u64>(image_info.image_base_header_field_offset, LE).unwrap();
+        let mut unmoved_image = loaded_image.clone();
+        relocate_image(&image_info, base as usize, &mut unmoved_image, &Vec::new()).unwrap();
+        assert_eq!(unmoved_image, loaded_image);
+
+        match relocate_image(&image_info, 0x04158000, &mut loaded_image, &Vec::new()) {
+            Err(error::Error::UnsupportedRelocation(4)) => {}
+            result => panic!("Expected UnsupportedRelocation error, got {result:?}"),
+        }
+    }
+
     #[test]
     fn test_relocate_image_with_missing_reloc_dir() {
         let image = include_bytes!("../resources/test/te/test_image_with_reloc_section.te");
@@ -735,6 +840,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn pe_relocate_runtime_image_should_match_relocate_image() {
+        let image = include_bytes!("../resources/test/pe32/test_image.pe32");
+        let image_info = UefiPeInfo::parse(image).unwrap();
+
+        let mut runtime_image: Vec<u8> = vec![0; image_info.size_of_image as usize];
+        load_image(&image_info, image, &mut runtime_image).unwrap();
+        let blocks = relocate_image(&image_info, 0x04158000, &mut runtime_image, &Vec::new()).unwrap();
+        let fixups = runtime_fixups(&image_info, &blocks);
+        assert!(!fixups.is_empty());
+
+        let mut reference_image: Vec<u8> = vec![0; image_info.size_of_image as usize];
+        load_image(&image_info, image, &mut reference_image).unwrap();
+        relocate_image(&image_info, 0x80000415, &mut reference_image, &Vec::new()).unwrap();
+
+        relocate_runtime_image(&mut runtime_image, fixups, 0x80000415u64.wrapping_sub(0x04158000)).unwrap();
+
+        // relocate_runtime_image leaves the headers alone, so only the image base field may differ.
+        let base_field = image_info.image_base_header_field_offset..image_info.image_base_header_field_offset + 8;
+        runtime_image[base_field.clone()].copy_from_slice(&reference_image[base_field]);
+        assert_eq!(runtime_image, reference_image);
+    }
+
+    #[test]
+    fn relocate_runtime_image_should_skip_modified_fixups() {
+        let mut image = vec![0u8; 0x20];
+        image[0x00..0x08].copy_from_slice(&0x1000u64.to_le_bytes());
+        image[0x08..0x10].copy_from_slice(&0x2000u64.to_le_bytes());
+        image[0x10..0x14].copy_from_slice(&0x3000u32.to_le_bytes());
+
+        let fixups = [
+            RuntimeFixup { offset: 0x00, fixup_type: IMAGE_REL_BASED_DIR64, value: 0x1000 },
+            RuntimeFixup { offset: 0x08, fixup_type: IMAGE_REL_BASED_DIR64, value: 0x1234 },
+            RuntimeFixup { offset: 0x10, fixup_type: IMAGE_REL_BASED_HIGHLOW, value: 0x3000 },
+        ];
+        relocate_runtime_image(&mut image, &fixups, 0x100).unwrap();
+
+        assert_eq!(image.pread_with::<u64>(0x00, LE).unwrap(), 0x1100);
+        assert_eq!(image.pread_with::<u64>(0x08, LE).unwrap(), 0x2000);
+        assert_eq!(image.pread_with::<u32>(0x10, LE).unwrap(), 0x3100);
+
+        let out_of_bounds = [RuntimeFixup { offset: 0x1C, fixup_type: IMAGE_REL_BASED_DIR64, value: 0 }];
+        assert!(matches!(relocate_runtime_image(&mut image, &out_of_bounds, 0x100), Err(error::Error::Parse(_))));
+    }
+
     #[test]
     fn pe_load_resource_section_should_succeed() {
         // test_image_<toolchain>_hii.pe32 file is just a copy of TftpDynamicCommand.efi module copied and renamed.
@@ -21,6 +21,8 @@ pub enum Error {
     BadSignature(u16),
     /// The parsed PeCoff image does not contain an Optional Header.
     NoOptionalHeader,
+    /// The image must be moved, but has a fixup of a type that is not supported.
+    UnsupportedRelocation(u16),
 }
 
 impl From<scroll::Error> for Error {
@@ -15,11 +15,15 @@ mod section_decompress;
 use alloc::{
     boxed::Box,
     collections::{BTreeMap, BTreeSet},
+    format,
+    string::{String, ToString},
     vec::Vec,
 };
 use core::{cmp::Ordering, ffi::c_void};
 use mu_rust_helpers::{function, guid::guid_fmt};
 use patina::{
+    Guid,
+    component::service::dispatch_report::{DispatchKind, UndispatchedDriver},
     error::EfiError,
     performance::{
         logging::{perf_function_begin, perf_function_end},
@@ -31,17 +35,18 @@ use patina_ffs::{
     section::{Section, SectionExtractor},
     volume::VolumeRef,
 };
-use patina_internal_depex::{AssociatedDependency, Depex, Opcode};
+use patina_internal_depex::{AssociatedDependency, Depex, Opcode, explain::Proof};
 use patina_internal_device_path::concat_device_path_to_boxed_slice;
 use r_efi::efi;
 
 use mu_rust_helpers::guid::CALLER_ID;
 
+pub(crate) use fv::CoreFirmwareVolumes;
 use fv::device_path_bytes_for_fv_file;
 use section_decompress::CoreExtractor;
 
 use crate::{
-    PlatformInfo,
+    PlatformInfo, dispatch_report,
     events::EVENT_DB,
     image::{ImageStatus, core_load_image, core_start_image},
     protocol_db::DXE_CORE_HANDLE,
@@ -98,12 +103,48 @@ impl<P: PlatformInfo> PiDispatcher<P> {
     }
 
     /// Displays drivers that were discovered but not dispatched.
+    ///
+    /// Each driver is reported with the smallest set of protocols that would satisfy its depex, or with what else holds
+    /// it back. The full depex evaluation is logged at debug level.
     pub fn display_discovered_not_dispatched(&self) {
-        for driver in &self.dispatcher_context.lock().pending_drivers {
-            log::warn!("Driver {:?} found but not dispatched.", guid_fmt!(driver.file_name));
+        let protocols = PROTOCOL_DB.registered_protocols();
+        let dispatcher = self.dispatcher_context.lock();
+        let associated = dispatcher.associated_before.values().chain(dispatcher.associated_after.values()).flatten();
+        for driver in dispatcher.pending_drivers.iter().chain(associated) {
+            let proof = match &driver.depex {
+                Some(depex) => depex.explain(&protocols),
+                None => Depex::from(ALL_ARCH_DEPEX).explain(&protocols),
+            };
+            let file_name = guid_fmt!(driver.file_name);
+            log::warn!("Driver {file_name:?} found but not dispatched, {}.", undispatched_reason(&proof));
+            log::debug!("Depex evaluation for driver {file_name:?}:\n{proof}");
+        }
+        for file_name in &dispatcher.missing_apriori_files {
+            log::warn!("A priori file lists driver {:?}, which was not found.", guid_fmt!(file_name));
         }
     }
 
+    /// Returns the drivers that were discovered but not dispatched, with the depex GUIDs they are waiting on.
+    ///
+    /// Drivers without a depex are waiting on the architectural protocols.
+    pub fn undispatched_drivers(&self) -> Vec<UndispatchedDriver> {
+        let protocols = PROTOCOL_DB.registered_protocols();
+        let dispatcher = self.dispatcher_context.lock();
+        let associated = dispatcher.associated_before.values().chain(dispatcher.associated_after.values()).flatten();
+        dispatcher
+            .pending_drivers
+            .iter()
+            .chain(associated)
+            .map(|driver| UndispatchedDriver {
+                file: driver.file_name,
+                unsatisfied: match &driver.depex {
+                    Some(depex) => depex.unsatisfied_guids(&protocols),
+                    None => Depex::from(ALL_ARCH_DEPEX).unsatisfied_guids(&protocols),
+                },
+            })
+            .collect()
+    }
+
     /// Initializes the dispatcher by registering for FV protocol installation events.
     pub fn init(&self) {
         //set up call back for FV protocol installation.
@@ -169,9 +210,13 @@ impl<P: PlatformInfo> PiDispatcher<P> {
                 }
             }
 
+            // Drivers listed in a priori files go first, in the listed order, regardless of their depex.
+            let apriori: Vec<_> = dispatcher.apriori_drivers.drain(..).collect();
+
             // insert contents of associated_before/after at the appropriate point in the schedule if the associated driver is present.
-            scheduled = scheduled_driver_candidates
+            scheduled = apriori
                 .into_iter()
+                .chain(scheduled_driver_candidates)
                 .flat_map(|scheduled_driver| {
                     let filename = OrdGuid(scheduled_driver.file_name);
                     let mut list = dispatcher.associated_before.remove(&filename).unwrap_or_default();
@@ -189,7 +234,8 @@ impl<P: PlatformInfo> PiDispatcher<P> {
             if driver.image_handle.is_none() {
                 log::info!("Loading file: {:?}", guid_fmt!(driver.file_name));
                 let data = driver.pe32.try_content_as_slice()?;
-                match core_load_image(false, DXE_CORE_HANDLE, driver.device_path, Some(data)) {
+                let authentication_status = Some(driver.pe32.authentication_status());
+                match core_load_image(false, DXE_CORE_HANDLE, driver.device_path, Some(data), authentication_status) {
                     Ok(handle) => {
                         driver.image_handle = Some(handle);
                         driver.security_status = efi::Status::SUCCESS;
@@ -202,7 +248,15 @@ impl<P: PlatformInfo> PiDispatcher<P> {
                         driver.image_handle = None;
                         driver.security_status = efi::Status::ACCESS_DENIED;
                     }
-                    Err(ImageStatus::LoadError(err)) => log::error!("Failed to load: load_image returned {err:x?}"),
+                    Err(ImageStatus::LoadError(err)) => {
+                        log::error!("Failed to load: load_image returned {err:x?}");
+                        dispatch_report::record_dispatch(
+                            DispatchKind::Driver,
+                            Guid::from_ref(&driver.file_name).to_string(),
+                            dispatch_report::timestamp(),
+                            err.into(),
+                        );
+                    }
                 }
             }
 
@@ -212,7 +266,14 @@ impl<P: PlatformInfo> PiDispatcher<P> {
                         dispatch_attempted = true;
                         // Note: ignore error result of core_start_image here - an image returning an error code is expected in some
                         // cases, and a debug output for that is already implemented in core_start_image.
-                        let _status = core_start_image(image_handle);
+                        let timestamp = dispatch_report::timestamp();
+                        let status = core_start_image(image_handle).err().unwrap_or(efi::Status::SUCCESS);
+                        dispatch_report::record_dispatch(
+                            DispatchKind::Driver,
+                            Guid::from_ref(&driver.file_name).to_string(),
+                            timestamp,
+                            status,
+                        );
                     }
                     efi::Status::SECURITY_VIOLATION => {
                         log::info!(
@@ -419,6 +480,23 @@ impl<P: PlatformInfo> PiDispatcher<P> {
     }
 }
 
+/// Describes why a driver whose depex evaluated to `proof` was not dispatched.
+fn undispatched_reason(proof: &Proof) -> String {
+    match (proof, proof.minimal_missing()) {
+        (_, Some(missing)) if missing.is_empty() => "although its depex is satisfied".to_string(),
+        (_, Some(missing)) => {
+            let missing = missing.iter().map(|guid| guid_fmt!(guid).to_string()).collect::<Vec<_>>();
+            format!("missing protocols: {}", missing.join(", "))
+        }
+        (Proof::Sor(_), None) => "it is schedule on request and was not scheduled".to_string(),
+        (Proof::Before(guid) | Proof::After(guid), None) => {
+            format!("driver {:?} it is ordered against was not dispatched", guid_fmt!(guid))
+        }
+        (Proof::Invalid { index }, None) => format!("its depex is invalid at opcode {index}"),
+        (_, None) => "no set of protocols satisfies its depex".to_string(),
+    }
+}
+
 struct PendingDriver {
     firmware_volume_handle: efi::Handle,
     device_path: *mut efi::protocols::device_path::Protocol,
@@ -452,12 +530,11 @@ impl PendingFirmwareVolumeImage {
         let file_path = device_path_bytes_for_fv_file(self.parent_fv_handle, self.file_name)
             .map_err(|status| EfiError::status_to_result(status).unwrap_err())?;
 
-        //Important Note: the present section extraction implementation does not support section extraction-based
-        //authentication status, so it is hard-coded to zero here. The primary security handlers for the main usage
-        //scenarios (TPM measurement and UEFI Secure Boot) do not use it.
+        let authentication_status =
+            self.fv_sections.iter().fold(0, |status, section| status | section.authentication_status());
         let status = (security_protocol.file_authentication_state)(
             security_protocol as *const _ as *mut patina::pi::protocols::security::Protocol,
-            0,
+            authentication_status,
             file_path.as_ptr() as *const _ as *mut efi::protocols::device_path::Protocol,
         );
         EfiError::status_to_result(status)
@@ -488,6 +565,8 @@ struct DispatcherContext {
     associated_before: BTreeMap<OrdGuid, Vec<PendingDriver>>,
     associated_after: BTreeMap<OrdGuid, Vec<PendingDriver>>,
     processed_fvs: BTreeSet<efi::Handle>,
+    apriori_drivers: Vec<PendingDriver>,
+    missing_apriori_files: Vec<efi::Guid>,
 }
 
 impl DispatcherContext {
@@ -501,6 +580,8 @@ impl DispatcherContext {
             associated_before: BTreeMap::new(),
             associated_after: BTreeMap::new(),
             processed_fvs: BTreeSet::new(),
+            apriori_drivers: Vec::new(),
+            missing_apriori_files: Vec::new(),
         }
     }
 
@@ -560,8 +641,15 @@ impl DispatcherContext {
                     }
                 };
 
+                let mut apriori_file_names = Vec::new();
                 for file in fv.files() {
                     let file = file?;
+                    if file.file_type_raw() == ffs::file::raw::r#type::FREEFORM
+                        && file.name() == ffs::guid::EFI_APRIORI_GUID
+                    {
+                        apriori_file_names =
+                            apriori_file_names_from_sections(&file.sections_with_extractor(extractor)?);
+                    }
                     if file.file_type_raw() == ffs::file::raw::r#type::DRIVER {
                         let file = file.clone();
                         let file_name = file.name();
@@ -673,11 +761,31 @@ impl DispatcherContext {
                         }
                     }
                 }
+                self.schedule_apriori_drivers(handle, &apriori_file_names);
             }
         }
         Ok(())
     }
 
+    /// Moves the drivers of the FV on `handle` that are listed in its a priori file to the a priori schedule.
+    fn schedule_apriori_drivers(&mut self, handle: efi::Handle, file_names: &[efi::Guid]) {
+        for file_name in file_names {
+            let position = self.pending_drivers.iter().position(|driver| {
+                driver.firmware_volume_handle == handle && OrdGuid(driver.file_name) == OrdGuid(*file_name)
+            });
+            match position {
+                Some(index) => {
+                    let driver = self.pending_drivers.remove(index);
+                    self.apriori_drivers.push(driver);
+                }
+                None => {
+                    log::warn!("A priori file lists driver {:?}, which is not in the FV.", guid_fmt!(file_name));
+                    self.missing_apriori_files.push(*file_name);
+                }
+            }
+        }
+    }
+
     fn schedule(&mut self, handle: efi::Handle, file: &efi::Guid) -> Result<(), EfiError> {
         for driver in self.pending_drivers.iter_mut() {
             if driver.firmware_volume_handle == handle
@@ -705,6 +813,24 @@ impl DispatcherContext {
 
 unsafe impl Send for DispatcherContext {}
 
+/// Returns the file names listed in the raw section of an a priori file.
+fn apriori_file_names_from_sections(sections: &[Section]) -> Vec<efi::Guid> {
+    let Some(content) = sections
+        .iter()
+        .find(|section| section.section_type() == Some(ffs::section::Type::Raw))
+        .and_then(|section| section.try_content_as_slice().ok())
+    else {
+        log::warn!("A priori file does not contain a raw section.");
+        return Vec::new();
+    };
+
+    let names = content.chunks_exact(size_of::<efi::Guid>());
+    if !names.remainder().is_empty() {
+        log::warn!("A priori file size is not a multiple of the GUID size; ignoring the trailing bytes.");
+    }
+    names.map(|name| efi::Guid::from_bytes(name.try_into().expect("chunk is GUID sized"))).collect()
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
@@ -712,7 +838,7 @@ mod tests {
     use std::{fs::File, io::Read, vec};
 
     use log::{Level, LevelFilter, Metadata, Record};
-    use patina::pi;
+    use patina::{component::service::dispatch_report::DispatchReporter, pi};
     use patina_ffs_extractors::NullSectionExtractor;
     use patina_internal_device_path::DevicePathWalker;
     use uuid::uuid;
@@ -1598,6 +1724,182 @@ mod tests {
         });
     }
 
+    const APRIORI_DRIVER_A: efi::Guid =
+        efi::Guid::from_fields(0xa0000001, 0x0000, 0x0000, 0x00, 0x00, &[0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    const APRIORI_DRIVER_B: efi::Guid =
+        efi::Guid::from_fields(0xa0000001, 0x0000, 0x0000, 0x00, 0x00, &[0x00, 0x00, 0x00, 0x00, 0x00, 0x02]);
+    const DEPEX_DRIVER: efi::Guid =
+        efi::Guid::from_fields(0xa0000001, 0x0000, 0x0000, 0x00, 0x00, &[0x00, 0x00, 0x00, 0x00, 0x00, 0x03]);
+    const MISSING_DRIVER: efi::Guid =
+        efi::Guid::from_fields(0xa0000001, 0x0000, 0x0000, 0x00, 0x00, &[0x00, 0x00, 0x00, 0x00, 0x00, 0x04]);
+
+    // Builds an FV with three drivers whose depex is FALSE, and an a priori file listing `apriori`.
+    fn apriori_test_fv(apriori: &[efi::Guid]) -> Vec<u8> {
+        use patina::pi::fw_fs::fv::BlockMapEntry;
+        use patina_ffs::{file::File, section::SectionHeader, volume::Volume};
+
+        let section = |section_type: u8, data: Vec<u8>| {
+            Section::new_from_header_with_data(SectionHeader::Standard(section_type, data.len() as u32), data).unwrap()
+        };
+
+        let mut fv = Volume::new(vec![BlockMapEntry { num_blocks: 1, length: 0x1000 }]);
+        let mut apriori_file = File::new(ffs::guid::EFI_APRIORI_GUID, ffs::file::raw::r#type::FREEFORM);
+        let names = apriori.iter().flat_map(|name| *name.as_bytes()).collect();
+        apriori_file.sections_mut().push(section(ffs::section::raw_type::RAW, names));
+        fv.files_mut().push(apriori_file);
+
+        for name in [APRIORI_DRIVER_A, APRIORI_DRIVER_B, DEPEX_DRIVER] {
+            let mut driver = File::new(name, ffs::file::raw::r#type::DRIVER);
+            // Push FALSE, End.
+            driver.sections_mut().push(section(ffs::section::raw_type::DXE_DEPEX, vec![0x07, 0x08]));
+            // Not a valid image, so loading fails after the driver has been scheduled.
+            driver.sections_mut().push(section(ffs::section::raw_type::PE32, vec![0xA5; 64]));
+            fv.files_mut().push(driver);
+        }
+        fv.serialize().unwrap()
+    }
+
+    #[test]
+    fn test_apriori_file_names_from_sections() {
+        use patina_ffs::section::SectionHeader;
+
+        let mut names = [APRIORI_DRIVER_A.as_bytes().as_slice(), APRIORI_DRIVER_B.as_bytes()].concat();
+        names.extend_from_slice(&[0xFF; 3]);
+        let raw = Section::new_from_header_with_data(
+            SectionHeader::Standard(ffs::section::raw_type::RAW, names.len() as u32),
+            names,
+        )
+        .unwrap();
+        assert_eq!(apriori_file_names_from_sections(&[raw]), vec![APRIORI_DRIVER_A, APRIORI_DRIVER_B]);
+
+        let pe32 =
+            Section::new_from_header_with_data(SectionHeader::Standard(ffs::section::raw_type::PE32, 4), vec![0; 4])
+                .unwrap();
+        assert!(apriori_file_names_from_sections(&[pe32]).is_empty());
+    }
+
+    #[test]
+    fn test_add_fv_handle_with_apriori_file() {
+        set_logger();
+        let fv = apriori_test_fv(&[APRIORI_DRIVER_B, MISSING_DRIVER, APRIORI_DRIVER_A]).into_boxed_slice();
+        let fv_raw = Box::into_raw(fv);
+
+        with_locked_state(|| {
+            static CORE: MockCore = MockCore::new(NullSectionExtractor::new());
+            CORE.override_instance();
+
+            // Safety: fv is leaked to ensure it is not freed and remains valid for the duration of the program.
+            let handle =
+                unsafe { CORE.pi_dispatcher.install_firmware_volume(fv_raw.expose_provenance() as u64, None).unwrap() };
+            CORE.pi_dispatcher.add_fv_handles(vec![handle]).expect("Failed to add FV handle");
+
+            let dispatcher = CORE.pi_dispatcher.dispatcher_context.lock();
+            let apriori: Vec<_> = dispatcher.apriori_drivers.iter().map(|driver| driver.file_name).collect();
+            assert_eq!(apriori, vec![APRIORI_DRIVER_B, APRIORI_DRIVER_A]);
+            assert_eq!(dispatcher.missing_apriori_files, vec![MISSING_DRIVER]);
+            assert_eq!(dispatcher.pending_drivers.len(), 1);
+            assert_eq!(dispatcher.pending_drivers[0].file_name, DEPEX_DRIVER);
+        });
+
+        let _dropped_fv = unsafe { Box::from_raw(fv_raw) };
+    }
+
+    #[test]
+    fn test_dispatch_apriori_drivers_ignore_depex() {
+        set_logger();
+        let fv = apriori_test_fv(&[APRIORI_DRIVER_A, APRIORI_DRIVER_B]).into_boxed_slice();
+        let fv_raw = Box::into_raw(fv);
+
+        with_locked_state(|| {
+            static CORE: MockCore = MockCore::new(NullSectionExtractor::new());
+            CORE.override_instance();
+
+            // Safety: fv is leaked to ensure it is not freed and remains valid for the duration of the program.
+            let handle =
+                unsafe { CORE.pi_dispatcher.install_firmware_volume(fv_raw.expose_provenance() as u64, None).unwrap() };
+            CORE.pi_dispatcher.add_fv_handles(vec![handle]).expect("Failed to add FV handle");
+
+            // The a priori drivers are scheduled despite their FALSE depex; their images fail to load, so nothing
+            // actually runs.
+            assert_eq!(CORE.pi_dispatcher.dispatch(), Ok(false));
+
+            let dispatcher = CORE.pi_dispatcher.dispatcher_context.lock();
+            assert!(dispatcher.apriori_drivers.is_empty());
+            assert!(dispatcher.missing_apriori_files.is_empty());
+            assert_eq!(dispatcher.pending_drivers.len(), 1);
+            assert_eq!(dispatcher.pending_drivers[0].file_name, DEPEX_DRIVER);
+            drop(dispatcher);
+
+            // Both load failures are in the dispatch report, and the FALSE depex waits on no GUID.
+            let report = crate::dispatch_report::CoreDispatchReporter.report();
+            for driver in [APRIORI_DRIVER_A, APRIORI_DRIVER_B] {
+                let name = Guid::from_ref(&driver).to_string();
+                assert!(report.dispatched.iter().any(|record| record.kind == DispatchKind::Driver
+                    && record.name == name
+                    && record.status.is_error()));
+            }
+            assert_eq!(
+                CORE.pi_dispatcher.undispatched_drivers(),
+                vec![UndispatchedDriver { file: DEPEX_DRIVER, unsatisfied: vec![] }]
+            );
+        });
+
+        let _dropped_fv = unsafe { Box::from_raw(fv_raw) };
+    }
+
+    #[test]
+    fn test_undispatched_reason() {
+        let depex = |bytes: &[u8], protocols: &[efi::Guid]| Depex::from(bytes).explain(protocols);
+        let push = |guid: &efi::Guid| [&[0x02], guid.as_bytes().as_slice()].concat();
+
+        let or = [push(&APRIORI_DRIVER_A), push(&APRIORI_DRIVER_B), vec![0x04, 0x08]].concat();
+        assert_eq!(
+            undispatched_reason(&depex(&or, &[])),
+            format!("missing protocols: {:?}", guid_fmt!(APRIORI_DRIVER_A))
+        );
+        assert_eq!(undispatched_reason(&depex(&or, &[APRIORI_DRIVER_B])), "although its depex is satisfied");
+
+        let and = [push(&APRIORI_DRIVER_A), push(&APRIORI_DRIVER_B), vec![0x03, 0x08]].concat();
+        assert_eq!(
+            undispatched_reason(&depex(&and, &[])),
+            format!("missing protocols: {:?}, {:?}", guid_fmt!(APRIORI_DRIVER_A), guid_fmt!(APRIORI_DRIVER_B))
+        );
+
+        let after = [vec![0x01], DEPEX_DRIVER.as_bytes().to_vec(), vec![0x08]].concat();
+        assert_eq!(
+            undispatched_reason(&depex(&after, &[])),
+            format!("driver {:?} it is ordered against was not dispatched", guid_fmt!(DEPEX_DRIVER))
+        );
+        assert_eq!(
+            undispatched_reason(&depex(&[0x09, 0x06, 0x08], &[])),
+            "it is schedule on request and was not scheduled"
+        );
+        assert_eq!(undispatched_reason(&depex(&[0x03, 0x08], &[])), "its depex is invalid at opcode 0");
+        assert_eq!(undispatched_reason(&depex(&[0x07, 0x08], &[])), "no set of protocols satisfies its depex");
+    }
+
+    #[test]
+    fn test_display_discovered_not_dispatched_with_pending_depex_driver() {
+        set_logger();
+        let fv = apriori_test_fv(&[]).into_boxed_slice();
+        let fv_raw = Box::into_raw(fv);
+
+        with_locked_state(|| {
+            static CORE: MockCore = MockCore::new(NullSectionExtractor::new());
+            CORE.override_instance();
+
+            // Safety: fv is leaked to ensure it is not freed and remains valid for the duration of the program.
+            let handle =
+                unsafe { CORE.pi_dispatcher.install_firmware_volume(fv_raw.expose_provenance() as u64, None).unwrap() };
+            CORE.pi_dispatcher.add_fv_handles(vec![handle]).expect("Failed to add FV handle");
+            assert_eq!(CORE.pi_dispatcher.dispatcher_context.lock().pending_drivers.len(), 3);
+
+            CORE.pi_dispatcher.display_discovered_not_dispatched();
+        });
+
+        let _dropped_fv = unsafe { Box::from_raw(fv_raw) };
+    }
+
     #[test]
     fn test_dispatch_with_corrupted_fv_section() {
         set_logger();
@@ -8,21 +8,37 @@
 //!
 use core::{
     ffi::c_void,
+    iter,
     mem::{self, size_of},
     num::NonZeroUsize,
-    ptr::NonNull,
+    ptr::{self, NonNull},
     slice,
 };
 
-use alloc::{boxed::Box, collections::BTreeMap};
-use patina::pi::{
-    self,
-    fw_fs::{ffs, fv, fvb},
-    hob,
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec, vec::Vec};
+use patina::{
+    base::{align_up, guid::Guid},
+    component::service::{
+        IntoService,
+        firmware_volume::{FirmwareVolumeInfo, FirmwareVolumes},
+    },
+    pi::{
+        self,
+        fw_fs::{ffs, fv, fvb},
+        hob,
+        protocols::firmware_volume::{
+            EFI_FV_RELIABLE_WRITE, EFI_FV_UNRELIABLE_WRITE, EfiFirmwareVolumeInfo, EfiFvWritePolicy,
+            FIRMWARE_VOLUME_INFO_ID,
+        },
+    },
 };
 
 use patina::error::EfiError;
-use patina_ffs::{file::FileRef, section::SectionExtractor, volume::VolumeRef};
+use patina_ffs::{
+    file::{File, FileRef},
+    section::SectionExtractor,
+    volume::{VolumeRef, alignment_pad_file},
+};
 use patina_internal_device_path::concat_device_path_to_boxed_slice;
 use r_efi::efi::{self, MEMORY_MAPPED_IO};
 
@@ -65,6 +81,18 @@ impl Metadata {
     }
 }
 
+/// A file to be written to a FV through the FV protocol's WriteFile method.
+struct WriteFileData<'a> {
+    /// The name of the file.
+    name: efi::Guid,
+    /// The FFS file type of the file.
+    file_type: fv::EfiFvFileType,
+    /// The `EFI_FV_FILE_ATTRIBUTES` of the file.
+    attributes: fv::file::EfiFvFileAttributes,
+    /// The file content. An empty file deletes the existing file of the same name.
+    content: &'a [u8],
+}
+
 /// Stored protocol data for any FV/FVB protocols installed by the DXE core.
 pub(super) struct FvProtocolData<P: PlatformInfo> {
     /// A map of installed FV/FVB protocol pointers (key) and the corresponding metadata (value).
@@ -290,6 +318,319 @@ impl<P: PlatformInfo> FvProtocolData<P> {
         Ok((file.name(), attributes, file.data().len(), file.file_type_raw()))
     }
 
+    /// Rust implementation of the FVB protocol's write method.
+    ///
+    /// Returns the number of bytes written, which is less than `data.len()` if the write would cross the end of the
+    /// block.
+    fn fvb_write(
+        &self,
+        protocol: NonNull<pi::protocols::firmware_volume_block::Protocol>,
+        lba: efi::Lba,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<usize, EfiError> {
+        let physical_address = self.get_fvb_address(protocol).ok_or(EfiError::NotFound)?;
+
+        // Safety: physical_address must point to a valid FV (i.e. private_data is correctly constructed and
+        // its invariants - like not removing fv once installed - are upheld).
+        let fv = unsafe { VolumeRef::new_from_address(physical_address) }?;
+
+        if (fv.attributes() & fvb::attributes::raw::fvb2::WRITE_STATUS) == 0 {
+            return Err(EfiError::AccessDenied);
+        }
+
+        let lba = lba.try_into().map_err(|_| EfiError::InvalidParameter)?;
+        let (lba_base_addr, block_size) = fv.lba_info(lba).map(|(addr, size, _)| (addr as usize, size as usize))?;
+
+        // writes may not cross the end of the block.
+        let bytes_to_write = data.len().min(block_size.saturating_sub(offset));
+
+        // Safety: the written range is within the block, which the block map check on instantiation of the VolumeRef
+        // guarantees is within the FV. The FV has been reported as writable by its attributes.
+        unsafe { write_fv(physical_address, lba_base_addr + offset, &data[..bytes_to_write]) };
+
+        Ok(bytes_to_write)
+    }
+
+    /// Rust implementation of the FVB protocol's erase_blocks method.
+    ///
+    /// `lba_list` holds the `(starting lba, number of blocks)` pairs of the variadic EFIAPI argument list. Either all
+    /// of the listed blocks are erased, or none of them are.
+    fn fvb_erase_blocks(
+        &self,
+        protocol: NonNull<pi::protocols::firmware_volume_block::Protocol>,
+        lba_list: &[(efi::Lba, usize)],
+    ) -> Result<(), EfiError> {
+        let physical_address = self.get_fvb_address(protocol).ok_or(EfiError::NotFound)?;
+
+        // Safety: physical_address must point to a valid FV (i.e. private_data is correctly constructed and
+        // its invariants - like not removing fv once installed - are upheld).
+        let fv = unsafe { VolumeRef::new_from_address(physical_address) }?;
+
+        if (fv.attributes() & fvb::attributes::raw::fvb2::WRITE_STATUS) == 0 {
+            return Err(EfiError::AccessDenied);
+        }
+
+        // Validate the whole list before erasing anything.
+        let mut regions = Vec::new();
+        for &(lba, num_blocks) in lba_list {
+            let last_lba = num_blocks
+                .checked_sub(1)
+                .and_then(|blocks| lba.checked_add(blocks as u64))
+                .ok_or(EfiError::InvalidParameter)?;
+            let lba = lba.try_into().map_err(|_| EfiError::InvalidParameter)?;
+            let last_lba = last_lba.try_into().map_err(|_| EfiError::InvalidParameter)?;
+
+            let (start, _, _) = fv.lba_info(lba)?;
+            let (last_start, last_size, _) = fv.lba_info(last_lba)?;
+            regions.push((start as usize, (last_start + last_size - start) as usize));
+        }
+
+        let erase_byte = fv.erase_byte();
+        for (offset, length) in regions {
+            // Safety: the erased range is made of blocks from the block map, which the check on instantiation of the
+            // VolumeRef guarantees are within the FV. The FV has been reported as writable by its attributes.
+            unsafe { erase_fv(physical_address, offset, length, erase_byte) };
+        }
+
+        Ok(())
+    }
+
+    /// Rust implementation of the FV protocol's write_file method.
+    ///
+    /// Existing files of the same name are replaced: the old file is marked for update, the new file is written to
+    /// free space, and the old file is then marked deleted. With [`EFI_FV_RELIABLE_WRITE`] every old file is marked
+    /// for update before any new file is written, so an interrupted write leaves either the old or the new set of
+    /// files valid. If the free space is too small, an unreliable write first reclaims the volume by rewriting it
+    /// without the deleted files. The reclaim is not fault tolerant, so a reliable write that needs one fails with
+    /// [`EfiError::OutOfResources`] instead, leaving the volume unchanged.
+    fn fv_write_file(
+        &self,
+        protocol: NonNull<pi::protocols::firmware_volume::Protocol>,
+        write_policy: EfiFvWritePolicy,
+        files: &[WriteFileData],
+    ) -> Result<(), EfiError> {
+        let physical_address = self.get_fv_address(protocol).ok_or(EfiError::NotFound)?;
+
+        // Safety: physical_address must point to a valid FV (i.e. private_data is correctly constructed and
+        // its invariants - like not removing fv once installed - are upheld).
+        let fv = unsafe { VolumeRef::new_from_address(physical_address) }?;
+
+        if (fv.attributes() & fvb::attributes::raw::fvb2::WRITE_STATUS) == 0 {
+            return Err(EfiError::WriteProtected);
+        }
+
+        if write_policy != EFI_FV_UNRELIABLE_WRITE && write_policy != EFI_FV_RELIABLE_WRITE {
+            return Err(EfiError::InvalidParameter);
+        }
+
+        // Per PI spec 1.8A, V3, section 2.1.4.1.8, pad files may not be explicitly created. A file may only be written
+        // once per call.
+        for (index, file) in files.iter().enumerate() {
+            if file.file_type == ffs::file::raw::r#type::FFS_PAD || files[..index].iter().any(|f| f.name == file.name) {
+                return Err(EfiError::InvalidParameter);
+            }
+        }
+
+        let erase_polarity = fv.erase_byte() != 0;
+
+        // Serialize the new files and locate the existing files they replace.
+        let mut updates = Vec::new();
+        for file in files {
+            let old_offset = match fv.files().find(|f| f.as_ref().is_ok_and(|f| f.name() == file.name) || f.is_err()) {
+                Some(Ok(old_file)) => Some(old_file.data().as_ptr().addr() - physical_address as usize),
+                Some(Err(err)) => return Err(err.into()),
+                None if file.content.is_empty() => return Err(EfiError::NotFound),
+                None => None,
+            };
+
+            let new_file = if file.content.is_empty() {
+                None
+            } else {
+                let mut new_file = File::new_with_content(file.name, file.file_type, file.content.to_vec());
+                new_file.set_fv_attributes(file.attributes)?;
+                new_file.set_erase_polarity(erase_polarity);
+                Some(new_file.serialize()?)
+            };
+
+            updates.push((old_offset, new_file));
+        }
+
+        // Lay the new files out in the free space, or reclaim the volume if they do not fit.
+        let mut offset = fv.free_space_offset()?;
+        let mut placements = Vec::new();
+        for (_, new_file) in updates.iter() {
+            let Some(new_file) = new_file else {
+                placements.push(None);
+                continue;
+            };
+            let file_ref = FileRef::new(new_file)?;
+            let pad_file = alignment_pad_file(offset, &file_ref)?.map(|pad| pad.serialize()).transpose()?;
+            let file_offset = offset + pad_file.as_ref().map_or(0, |pad| pad.len());
+            offset = file_offset + new_file.len();
+            placements.push(Some((pad_file, file_offset, file_ref.content_offset())));
+            offset = align_up(offset as u64, 8).map_err(|_| EfiError::OutOfResources)? as usize;
+        }
+
+        if offset > fv.size() as usize {
+            if write_policy == EFI_FV_RELIABLE_WRITE {
+                return Err(EfiError::OutOfResources);
+            }
+            let new_files = updates.iter().filter_map(|(_, new_file)| new_file.as_deref());
+            // Safety: physical_address points to the FV described by fv.
+            let image = unsafe { reclaimed_volume(physical_address, &fv, files, new_files) }?;
+            let erase_byte = fv.erase_byte();
+            let block_sizes = fv
+                .block_map()
+                .iter()
+                .flat_map(|entry| iter::repeat_n(entry.length as usize, entry.num_blocks as usize))
+                .collect::<Vec<_>>();
+            if block_sizes.iter().sum::<usize>() != image.len() {
+                return Err(EfiError::VolumeCorrupted);
+            }
+
+            // Only the blocks that change are erased and re-programmed.
+            let mut block_offset = 0;
+            for block_size in block_sizes {
+                let block = &image[block_offset..block_offset + block_size];
+                // Safety: the block map check on instantiation of the VolumeRef guarantees the block is within the FV.
+                let current = unsafe {
+                    slice::from_raw_parts((physical_address as usize + block_offset) as *const u8, block_size)
+                };
+                if current != block {
+                    // Safety: the block is within the FV, which has been reported as writable by its attributes.
+                    unsafe {
+                        erase_fv(physical_address, block_offset, block_size, erase_byte);
+                        write_fv(physical_address, block_offset, block);
+                    }
+                }
+                block_offset += block_size;
+            }
+            return Ok(());
+        }
+
+        // Reliable writes update all files as a single group, unreliable writes update one file at a time.
+        let group_size = if write_policy == EFI_FV_RELIABLE_WRITE { updates.len().max(1) } else { 1 };
+        for (group, group_placements) in updates.chunks(group_size).zip(placements.chunks(group_size)) {
+            for (old_offset, _) in group {
+                if let Some(old_offset) = old_offset {
+                    // Safety: old_offset is the offset of a valid file within the writable FV.
+                    unsafe {
+                        program_file_state(
+                            physical_address,
+                            *old_offset,
+                            ffs::file::raw::state::MARKED_FOR_UPDATE,
+                            erase_polarity,
+                        )
+                    };
+                }
+            }
+            for ((_, new_file), placement) in group.iter().zip(group_placements) {
+                if let (Some(new_file), Some((pad_file, file_offset, header_size))) = (new_file, placement) {
+                    // Safety: the layout above verified that the pad file and the new file fit in the free space of
+                    // the writable FV.
+                    unsafe {
+                        if let Some(pad_file) = pad_file {
+                            write_fv(physical_address, file_offset - pad_file.len(), pad_file);
+                        }
+                        write_ffs_file(physical_address, *file_offset, new_file, *header_size, erase_polarity);
+                    }
+                }
+            }
+            for (old_offset, _) in group {
+                if let Some(old_offset) = old_offset {
+                    // Safety: old_offset is the offset of a valid file within the writable FV.
+                    unsafe {
+                        program_file_state(
+                            physical_address,
+                            *old_offset,
+                            ffs::file::raw::state::DELETED,
+                            erase_polarity,
+                        )
+                    };
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the information about the FV at `physical_address` that is reported by GetInfo and the
+    /// [FirmwareVolumes] service.
+    fn volume_info(physical_address: u64) -> Result<FirmwareVolumeInfo, EfiError> {
+        // Safety: physical_address must point to a valid FV (i.e. private_data is correctly constructed and
+        // its invariants - like not removing fv once installed - are upheld).
+        let fv = unsafe { VolumeRef::new_from_address(physical_address) }?;
+
+        let ext_header = fv.ext_header().map(|(header, data)| {
+            let mut bytes = header.fv_name.as_bytes().to_vec();
+            bytes.extend_from_slice(&header.ext_header_size.to_le_bytes());
+            bytes.extend(data);
+            bytes
+        });
+
+        Ok(FirmwareVolumeInfo {
+            base_address: physical_address,
+            size: fv.size(),
+            attributes: fv.attributes(),
+            file_system_guid: fv.file_system_guid(),
+            fv_name: fv.fv_name(),
+            ext_header,
+            block_map: fv.block_map().clone(),
+            free_space: fv.size().saturating_sub(fv.free_space_offset()? as u64),
+        })
+    }
+
+    /// Returns the information about every FV that a FV protocol is installed for.
+    fn volumes(&self) -> Vec<FirmwareVolumeInfo> {
+        self.fv_metadata
+            .values()
+            .filter(|metadata| matches!(metadata.protocol, Protocol::Fv(_)))
+            .filter_map(|metadata| Self::volume_info(metadata.physical_address).ok())
+            .collect()
+    }
+
+    /// Rust implementation of the FV protocol's GetInfo method.
+    ///
+    /// Returns the information of the given type, serialized as the C structure the type GUID names.
+    fn fv_get_info(
+        &self,
+        protocol: NonNull<pi::protocols::firmware_volume::Protocol>,
+        information_type: efi::Guid,
+    ) -> Result<Vec<u8>, EfiError> {
+        let physical_address = self.get_fv_address(protocol).ok_or(EfiError::NotFound)?;
+        let info = Self::volume_info(physical_address)?;
+
+        match information_type {
+            efi::protocols::file::SYSTEM_INFO_ID => Ok(file_system_info(&info)),
+            FIRMWARE_VOLUME_INFO_ID => Ok(firmware_volume_info(&info)),
+            _ => Err(EfiError::Unsupported),
+        }
+    }
+
+    /// Rust implementation of the FV protocol's SetInfo method.
+    ///
+    /// The supported information types are derived from the FV header, so they cannot be set.
+    fn fv_set_info(
+        &self,
+        protocol: NonNull<pi::protocols::firmware_volume::Protocol>,
+        information_type: efi::Guid,
+        buffer: &[u8],
+    ) -> Result<(), EfiError> {
+        self.get_fv_address(protocol).ok_or(EfiError::NotFound)?;
+
+        let header_size = match information_type {
+            efi::protocols::file::SYSTEM_INFO_ID => mem::offset_of!(efi::protocols::file::SystemInfo, volume_label),
+            FIRMWARE_VOLUME_INFO_ID => size_of::<EfiFirmwareVolumeInfo>(),
+            _ => return Err(EfiError::Unsupported),
+        };
+
+        if buffer.len() < header_size {
+            return Err(EfiError::BadBufferSize);
+        }
+        Err(EfiError::WriteProtected)
+    }
+
     fn new_fvb_protocol(parent_handle: Option<efi::Handle>) -> Box<pi::protocols::firmware_volume_block::Protocol> {
         Box::new(pi::protocols::firmware_volume_block::Protocol {
             get_attributes: Self::fvb_get_attributes_efiapi,
@@ -298,7 +639,15 @@ impl<P: PlatformInfo> FvProtocolData<P> {
             get_block_size: Self::fvb_get_block_size_efiapi,
             read: Self::fvb_read_efiapi,
             write: Self::fvb_write_efiapi,
-            erase_blocks: Self::fvb_erase_blocks_efiapi,
+            // The protocol definition of EraseBlocks is not variadic, since rust only supports variadic functions for
+            // "unsafe extern C". For the supported UEFI targets "efiapi" and "C" match, so a transmute is sufficient.
+            // Safety: the function signatures differ only in the variadic arguments, which callers must provide.
+            erase_blocks: unsafe {
+                mem::transmute::<
+                    unsafe extern "C" fn(*mut pi::protocols::firmware_volume_block::Protocol, ...) -> efi::Status,
+                    pi::protocols::firmware_volume_block::EraseBlocks,
+                >(Self::fvb_erase_blocks_efiapi)
+            },
             parent_handle: parent_handle.unwrap_or(core::ptr::null_mut()),
         })
     }
@@ -450,6 +799,160 @@ impl<P: PlatformInfo> FvProtocolData<P> {
     }
 }
 
+/// Builds a compacted image of the FV at `base_address` that holds its valid files, except those replaced by `files`,
+/// followed by `new_files`.
+///
+/// ## Safety
+///
+/// Caller must ensure that `base_address` points to the FV described by `fv`.
+unsafe fn reclaimed_volume<'a>(
+    base_address: u64,
+    fv: &VolumeRef<'_>,
+    files: &[WriteFileData],
+    new_files: impl Iterator<Item = &'a [u8]>,
+) -> Result<Vec<u8>, EfiError> {
+    let size = fv.size() as usize;
+    let erase_byte = fv.erase_byte();
+
+    let mut old_files = Vec::new();
+    for file in fv.files() {
+        let file = file?;
+        if !files.iter().any(|f| f.name == file.name()) {
+            old_files.push(file);
+        }
+    }
+    let new_files = new_files.map(FileRef::new).collect::<Result<Vec<_>, _>>()?;
+
+    // The FV header, block map and extended header are kept as they are.
+    // Safety: caller must ensure that base_address points to the FV, which holds at least content_offset bytes.
+    let mut image = unsafe { slice::from_raw_parts(base_address as *const u8, fv.content_offset()) }.to_vec();
+    let mut used = image.len();
+    for file in old_files.iter().chain(new_files.iter()) {
+        if let Some(pad_file) = alignment_pad_file(image.len(), file)? {
+            image.extend(pad_file.serialize()?);
+        }
+        image.extend_from_slice(file.data());
+        used = image.len();
+        // files start at 8-byte aligned offsets.
+        image.resize(align_up(used as u64, 8).map_err(|_| EfiError::OutOfResources)? as usize, erase_byte);
+    }
+
+    if used > size {
+        return Err(EfiError::OutOfResources);
+    }
+    image.resize(size, erase_byte);
+    Ok(image)
+}
+
+/// Writes `data` to the FV at `base_address`, starting at `offset` bytes from the start of the FV.
+///
+/// ## Safety
+///
+/// Caller must ensure that `base_address` points to a writable FV of at least `offset + data.len()` bytes.
+unsafe fn write_fv(base_address: u64, offset: usize, data: &[u8]) {
+    // Safety: caller must ensure the destination is valid. `data` may alias the FV, so an overlapping copy is used.
+    unsafe { ptr::copy(data.as_ptr(), (base_address as usize + offset) as *mut u8, data.len()) };
+}
+
+/// Erases `length` bytes of the FV at `base_address`, starting at `offset` bytes from the start of the FV.
+///
+/// ## Safety
+///
+/// Caller must ensure that `base_address` points to a writable FV of at least `offset + length` bytes.
+unsafe fn erase_fv(base_address: u64, offset: usize, length: usize, erase_byte: u8) {
+    // Safety: caller must ensure the destination is valid.
+    unsafe { ptr::write_bytes((base_address as usize + offset) as *mut u8, erase_byte, length) };
+}
+
+/// Programs `state` bits in the header of the FFS file at `offset` bytes from the start of the FV at `base_address`.
+///
+/// Programming a bit moves it away from the erase polarity, so state bits can only be set, never cleared.
+///
+/// ## Safety
+///
+/// Caller must ensure that `base_address` points to a writable FV with a file header at `offset`.
+unsafe fn program_file_state(base_address: u64, offset: usize, state: u8, erase_polarity: bool) {
+    let state_ptr = (base_address as usize + offset + mem::offset_of!(ffs::file::Header, state)) as *mut u8;
+    // Safety: caller must ensure that the file header is valid and writable.
+    unsafe {
+        let current = state_ptr.read_volatile();
+        state_ptr.write_volatile(if erase_polarity { current & !state } else { current | state });
+    }
+}
+
+/// Writes the serialized FFS `file` to erased space at `offset` bytes from the start of the FV at `base_address`.
+///
+/// The header and content are written following the state transitions of PI spec 1.8A, V3, section 2.1.4.1.4, so a
+/// write that is interrupted leaves a file that readers skip.
+///
+/// ## Safety
+///
+/// Caller must ensure that `base_address` points to a writable FV with at least `file.len()` erased bytes at `offset`.
+unsafe fn write_ffs_file(base_address: u64, offset: usize, file: &[u8], header_size: usize, erase_polarity: bool) {
+    let state_offset = mem::offset_of!(ffs::file::Header, state);
+    // Safety: caller must ensure that the destination is valid, writable and erased.
+    unsafe {
+        program_file_state(base_address, offset, ffs::file::raw::state::HEADER_CONSTRUCTION, erase_polarity);
+        write_fv(base_address, offset, &file[..state_offset]);
+        write_fv(base_address, offset + state_offset + 1, &file[state_offset + 1..header_size]);
+        program_file_state(base_address, offset, ffs::file::raw::state::HEADER_VALID, erase_polarity);
+        write_fv(base_address, offset + header_size, &file[header_size..]);
+        program_file_state(base_address, offset, ffs::file::raw::state::DATA_VALID, erase_polarity);
+    }
+}
+
+/// Serializes `info` as an `EFI_FILE_SYSTEM_INFO` structure.
+///
+/// The volume label is the FV name from the extended header, or empty if the FV has none.
+fn file_system_info(info: &FirmwareVolumeInfo) -> Vec<u8> {
+    let label = match info.fv_name {
+        Some(fv_name) => format!("{}", Guid::from_ref(&fv_name)),
+        None => String::new(),
+    };
+    let label_offset = mem::offset_of!(efi::protocols::file::SystemInfo, volume_label);
+    let size = label_offset + (label.encode_utf16().count() + 1) * size_of::<u16>();
+
+    let mut buffer = vec![0u8; size.max(size_of::<efi::protocols::file::SystemInfo>())];
+    let header = efi::protocols::file::SystemInfo {
+        size: size as u64,
+        read_only: (info.attributes & fvb::attributes::raw::fvb2::WRITE_STATUS == 0).into(),
+        volume_size: info.size,
+        free_space: info.free_space,
+        block_size: info.block_map.first().map_or(0, |entry| entry.length),
+        volume_label: [],
+    };
+    // Safety: buffer is at least as large as the SystemInfo structure.
+    unsafe { (buffer.as_mut_ptr() as *mut efi::protocols::file::SystemInfo).write_unaligned(header) };
+    for (bytes, char) in buffer[label_offset..].chunks_exact_mut(size_of::<u16>()).zip(label.encode_utf16()) {
+        bytes.copy_from_slice(&char.to_le_bytes());
+    }
+    // The label is already null-terminated, as the buffer is zeroed.
+    buffer.truncate(size);
+    buffer
+}
+
+/// Serializes `info` as an [EfiFirmwareVolumeInfo] structure followed by the extended header.
+fn firmware_volume_info(info: &FirmwareVolumeInfo) -> Vec<u8> {
+    let ext_header = info.ext_header.as_deref().unwrap_or_default();
+    let size = size_of::<EfiFirmwareVolumeInfo>() + ext_header.len();
+
+    let mut buffer = vec![0u8; size];
+    let header = EfiFirmwareVolumeInfo {
+        size: size as u64,
+        base_address: info.base_address,
+        fv_length: info.size,
+        attributes: info.attributes as fv::attributes::EfiFvAttributes,
+        free_space: info.free_space,
+        file_system_guid: info.file_system_guid,
+        fv_name: info.fv_name.unwrap_or(efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6])),
+        ext_header_size: ext_header.len() as u32,
+    };
+    // Safety: buffer is at least as large as the EfiFirmwareVolumeInfo structure.
+    unsafe { (buffer.as_mut_ptr() as *mut EfiFirmwareVolumeInfo).write_unaligned(header) };
+    buffer[size_of::<EfiFirmwareVolumeInfo>()..].copy_from_slice(ext_header);
+    buffer
+}
+
 // FV / FVB EFIAPI compliant protocol method implementations.
 #[coverage(off)]
 impl<P: PlatformInfo> FvProtocolData<P> {
@@ -578,21 +1081,65 @@ impl<P: PlatformInfo> FvProtocolData<P> {
 
     /// EFIAPI compliant FVB protocol Write method.
     extern "efiapi" fn fvb_write_efiapi(
-        _this: *mut pi::protocols::firmware_volume_block::Protocol,
-        _lba: efi::Lba,
-        _offset: usize,
-        _num_bytes: *mut usize,
-        _buffer: *mut core::ffi::c_void,
+        this: *mut pi::protocols::firmware_volume_block::Protocol,
+        lba: efi::Lba,
+        offset: usize,
+        num_bytes: *mut usize,
+        buffer: *mut core::ffi::c_void,
     ) -> efi::Status {
-        efi::Status::UNSUPPORTED
+        if num_bytes.is_null() || buffer.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+
+        let Some(protocol) = NonNull::new(this) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        // Safety: caller must provide valid pointers for num_bytes and buffer. They are null-checked above.
+        let bytes_to_write = unsafe { num_bytes.read_unaligned() };
+        // Safety: caller must provide a buffer that is valid for reads of num_bytes bytes. It is null-checked above.
+        let data = unsafe { slice::from_raw_parts(buffer as *const u8, bytes_to_write) };
+
+        let bytes_written = match Self::instance().fvb_write(protocol, lba, offset, data) {
+            Err(err) => return err.into(),
+            Ok(bytes_written) => bytes_written,
+        };
+
+        // Safety: caller must provide a valid pointer for num_bytes. It is null-checked above.
+        unsafe { num_bytes.write_unaligned(bytes_written) };
+
+        if bytes_written != bytes_to_write { efi::Status::BAD_BUFFER_SIZE } else { efi::Status::SUCCESS }
     }
 
     /// EFIAPI compliant FVB protocol EraseBlocks method.
-    extern "efiapi" fn fvb_erase_blocks_efiapi(
-        _this: *mut pi::protocols::firmware_volume_block::Protocol,
-        //... TODO: this should be variadic; however, variadic and eficall don't mix well presently.
+    ///
+    /// The arguments following `this` are `(efi::Lba, usize)` pairs, terminated by an `EFI_LBA_LIST_TERMINATOR` LBA.
+    /// This is declared `extern "C"` because variadic functions are only supported for that ABI; see
+    /// [`Self::new_fvb_protocol`].
+    unsafe extern "C" fn fvb_erase_blocks_efiapi(
+        this: *mut pi::protocols::firmware_volume_block::Protocol,
+        mut args: ...
     ) -> efi::Status {
-        efi::Status::UNSUPPORTED
+        let Some(protocol) = NonNull::new(this) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        let mut lba_list = Vec::new();
+        loop {
+            // Safety: caller must terminate the argument list with EFI_LBA_LIST_TERMINATOR.
+            let lba: efi::Lba = unsafe { args.arg() };
+            if lba == pi::protocols::firmware_volume_block::EFI_LBA_LIST_TERMINATOR {
+                break;
+            }
+            // Safety: caller must follow each LBA with the number of blocks to erase.
+            let num_blocks: usize = unsafe { args.arg() };
+            lba_list.push((lba, num_blocks));
+        }
+
+        match Self::instance().fvb_erase_blocks(protocol, &lba_list) {
+            Err(err) => err.into(),
+            Ok(()) => efi::Status::SUCCESS,
+        }
     }
 
     /// EFIAPI compliant FV protocol GetVolumeAttributes method.
@@ -811,12 +1358,50 @@ impl<P: PlatformInfo> FvProtocolData<P> {
 
     /// EFIAPI compliant FV protocol WriteFile method.
     extern "efiapi" fn fv_write_file_efiapi(
-        _this: *const pi::protocols::firmware_volume::Protocol,
-        _number_of_files: u32,
-        _write_policy: pi::protocols::firmware_volume::EfiFvWritePolicy,
-        _file_data: *mut pi::protocols::firmware_volume::EfiFvWriteFileData,
+        this: *const pi::protocols::firmware_volume::Protocol,
+        number_of_files: u32,
+        write_policy: pi::protocols::firmware_volume::EfiFvWritePolicy,
+        file_data: *mut pi::protocols::firmware_volume::EfiFvWriteFileData,
     ) -> efi::Status {
-        efi::Status::UNSUPPORTED
+        if file_data.is_null() && number_of_files != 0 {
+            return efi::Status::INVALID_PARAMETER;
+        }
+
+        let Some(protocol) = NonNull::new(this as *mut pi::protocols::firmware_volume::Protocol) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        let file_data = if number_of_files == 0 {
+            &[]
+        } else {
+            // Safety: caller must provide an array of number_of_files entries. It is null-checked above.
+            unsafe { slice::from_raw_parts(file_data, number_of_files as usize) }
+        };
+
+        let mut files = Vec::new();
+        for data in file_data {
+            if data.name_guid.is_null() || (data.buffer.is_null() && data.buffer_size != 0) {
+                return efi::Status::INVALID_PARAMETER;
+            }
+            let content = if data.buffer_size == 0 {
+                &[][..]
+            } else {
+                // Safety: caller must provide a buffer of buffer_size bytes. It is null-checked above.
+                unsafe { slice::from_raw_parts(data.buffer as *const u8, data.buffer_size as usize) }
+            };
+            files.push(WriteFileData {
+                // Safety: caller must provide a valid pointer for name_guid. It is null-checked above.
+                name: unsafe { data.name_guid.read_unaligned() },
+                file_type: data.file_type,
+                attributes: data.file_attributes,
+                content,
+            });
+        }
+
+        match Self::instance().fv_write_file(protocol, write_policy, &files) {
+            Err(err) => err.into(),
+            Ok(()) => efi::Status::SUCCESS,
+        }
     }
 
     /// EFIAPI compliant FV protocol GetNextFile method.
@@ -870,22 +1455,93 @@ impl<P: PlatformInfo> FvProtocolData<P> {
 
     /// EFIAPI compliant FV protocol GetInfo method.
     extern "efiapi" fn fv_get_info_efiapi(
-        _this: *const pi::protocols::firmware_volume::Protocol,
-        _information_type: *const efi::Guid,
-        _buffer_size: *mut usize,
-        _buffer: *mut c_void,
+        this: *const pi::protocols::firmware_volume::Protocol,
+        information_type: *const efi::Guid,
+        buffer_size: *mut usize,
+        buffer: *mut c_void,
     ) -> efi::Status {
-        efi::Status::UNSUPPORTED
+        if information_type.is_null() || buffer_size.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+
+        let Some(protocol) = NonNull::new(this as *mut pi::protocols::firmware_volume::Protocol) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        // Safety: caller must provide a valid pointer for information_type. It is null-checked above.
+        let information_type = unsafe { information_type.read_unaligned() };
+
+        let info = match Self::instance().fv_get_info(protocol, information_type) {
+            Err(err) => return err.into(),
+            Ok(info) => info,
+        };
+
+        // Safety: caller must provide a valid pointer for buffer_size. It is null-checked above.
+        let available = unsafe { buffer_size.read_unaligned() };
+        // Safety: caller must provide a valid pointer for buffer_size. It is null-checked above.
+        unsafe { buffer_size.write_unaligned(info.len()) };
+        if available < info.len() {
+            return efi::Status::BUFFER_TOO_SMALL;
+        }
+
+        if buffer.is_null() {
+            return efi::Status::INVALID_PARAMETER;
+        }
+
+        // Safety: caller must provide a buffer of at least buffer_size bytes. It is null-checked above.
+        unsafe { ptr::copy_nonoverlapping(info.as_ptr(), buffer as *mut u8, info.len()) };
+        efi::Status::SUCCESS
     }
 
     /// EFIAPI compliant FV protocol SetInfo method.
     extern "efiapi" fn fv_set_info_efiapi(
-        _this: *const pi::protocols::firmware_volume::Protocol,
-        _information_type: *const efi::Guid,
-        _buffer_size: usize,
-        _buffer: *const c_void,
+        this: *const pi::protocols::firmware_volume::Protocol,
+        information_type: *const efi::Guid,
+        buffer_size: usize,
+        buffer: *const c_void,
     ) -> efi::Status {
-        efi::Status::UNSUPPORTED
+        if information_type.is_null() || (buffer.is_null() && buffer_size != 0) {
+            return efi::Status::INVALID_PARAMETER;
+        }
+
+        let Some(protocol) = NonNull::new(this as *mut pi::protocols::firmware_volume::Protocol) else {
+            return efi::Status::INVALID_PARAMETER;
+        };
+
+        // Safety: caller must provide a valid pointer for information_type. It is null-checked above.
+        let information_type = unsafe { information_type.read_unaligned() };
+        let buffer = if buffer_size == 0 {
+            &[][..]
+        } else {
+            // Safety: caller must provide a buffer of buffer_size bytes. It is null-checked above.
+            unsafe { slice::from_raw_parts(buffer as *const u8, buffer_size) }
+        };
+
+        match Self::instance().fv_set_info(protocol, information_type, buffer) {
+            Err(err) => err.into(),
+            Ok(()) => efi::Status::SUCCESS,
+        }
+    }
+}
+
+/// Provides information about the FVs installed by the core.
+#[derive(IntoService)]
+#[service(dyn FirmwareVolumes)]
+pub(crate) struct CoreFirmwareVolumes {
+    /// Returns the volumes of the core's FV protocol data, which is specific to the platform.
+    volumes: fn() -> Vec<FirmwareVolumeInfo>,
+}
+
+impl CoreFirmwareVolumes {
+    /// Creates a service backed by the FV protocol data of the `Core<P>` singleton.
+    pub(crate) fn new<P: PlatformInfo>() -> Self {
+        Self { volumes: || FvProtocolData::<P>::instance().volumes() }
+    }
+}
+
+impl FirmwareVolumes for CoreFirmwareVolumes {
+    fn volumes(&self) -> Vec<FirmwareVolumeInfo> {
+        (self.volumes)()
     }
 }
 
@@ -1158,19 +1814,7 @@ mod tests {
             CORE.pi_dispatcher.fv_data.lock().fv_metadata.insert(fvb_intf_invalid_void.addr(), private_data4);
 
             /* Create a Firmware Volume Block Interface without Physical address populated  */
-            let mut fvb_intf_data_n = Box::from(pi::protocols::firmware_volume_block::Protocol {
-                get_attributes: MockProtocolData::fvb_get_attributes_efiapi,
-                set_attributes: MockProtocolData::fvb_set_attributes_efiapi,
-                get_physical_address: MockProtocolData::fvb_get_physical_address_efiapi,
-                get_block_size: MockProtocolData::fvb_get_block_size_efiapi,
-                read: MockProtocolData::fvb_read_efiapi,
-                write: MockProtocolData::fvb_write_efiapi,
-                erase_blocks: MockProtocolData::fvb_erase_blocks_efiapi,
-                parent_handle: match parent_handle {
-                    Some(handle) => handle,
-                    None => core::ptr::null_mut(),
-                },
-            });
+            let mut fvb_intf_data_n = MockProtocolData::new_fvb_protocol(parent_handle);
             let fvb_intf_data_n_mut = fvb_intf_data_n.as_mut() as *mut pi::protocols::firmware_volume_block::Protocol;
 
             // Safety: the following test code must uphold the safety expectations of the unsafe
@@ -1315,7 +1959,10 @@ mod tests {
                 };
 
                 let fvb_test_erase_block = || {
-                    MockProtocolData::fvb_erase_blocks_efiapi(fvb_ptr_mut_prot);
+                    MockProtocolData::fvb_erase_blocks_efiapi(
+                        fvb_ptr_mut_prot,
+                        pi::protocols::firmware_volume_block::EFI_LBA_LIST_TERMINATOR,
+                    );
                 };
 
                 let fvb_test_get_physical_address = || {
@@ -1354,6 +2001,8 @@ mod tests {
                     if buffer_valid3.is_null() {
                         panic!("Memory allocation failed!");
                     }
+                    // Write back the current FV content so the FV stays intact for the rest of the test.
+                    ptr::copy_nonoverlapping(fv.as_ptr(), buffer_valid3 as *mut u8, 1000);
 
                     MockProtocolData::fvb_write_efiapi(
                         fvb_ptr_mut_prot,
@@ -1966,4 +2615,397 @@ mod tests {
         })
         .unwrap()
     }
+
+    // Builds a RAM-backed FV of `num_blocks` 0x1000-byte blocks and registers FV and FVB protocols for it.
+    fn install_ram_volume(
+        core: &'static MockCore,
+        attributes: fvb::attributes::EfiFvbAttributes2,
+        num_blocks: u32,
+    ) -> (u64, *const pi::protocols::firmware_volume::Protocol, *mut pi::protocols::firmware_volume_block::Protocol)
+    {
+        let mut volume = patina_ffs::volume::Volume::new(vec![fv::BlockMapEntry { num_blocks, length: 0x1000 }]);
+        volume.set_attributes(attributes);
+        volume.set_capacity(num_blocks as usize * 0x1000);
+        let base_address = volume.serialize().unwrap().leak().as_ptr() as u64;
+
+        let fv_interface = MockProtocolData::new_fv_protocol(None);
+        let fv_ptr = NonNull::from(&*fv_interface);
+        core.pi_dispatcher
+            .fv_data
+            .lock()
+            .fv_metadata
+            .insert(fv_ptr.addr(), Metadata::new_fv(fv_interface, base_address));
+
+        let fvb_interface = MockProtocolData::new_fvb_protocol(None);
+        let fvb_ptr = NonNull::from(&*fvb_interface);
+        core.pi_dispatcher
+            .fv_data
+            .lock()
+            .fv_metadata
+            .insert(fvb_ptr.addr(), Metadata::new_fvb(fvb_interface, base_address));
+
+        (base_address, fv_ptr.as_ptr(), fvb_ptr.as_ptr())
+    }
+
+    const WRITABLE_FV: fvb::attributes::EfiFvbAttributes2 = fvb::attributes::raw::fvb2::READ_STATUS
+        | fvb::attributes::raw::fvb2::WRITE_STATUS
+        | fvb::attributes::raw::fvb2::ERASE_POLARITY;
+
+    fn write_file(
+        fv_ptr: *const pi::protocols::firmware_volume::Protocol,
+        write_policy: EfiFvWritePolicy,
+        files: &mut [(efi::Guid, Vec<u8>)],
+    ) -> efi::Status {
+        let mut file_data = files
+            .iter_mut()
+            .map(|(name, content)| pi::protocols::firmware_volume::EfiFvWriteFileData {
+                name_guid: name,
+                file_type: ffs::file::raw::r#type::FREEFORM,
+                file_attributes: 0,
+                buffer: content.as_mut_ptr() as *mut c_void,
+                buffer_size: content.len() as u32,
+            })
+            .collect::<Vec<_>>();
+        MockProtocolData::fv_write_file_efiapi(fv_ptr, file_data.len() as u32, write_policy, file_data.as_mut_ptr())
+    }
+
+    fn read_file(fv_ptr: *const pi::protocols::firmware_volume::Protocol, name: efi::Guid) -> Option<Vec<u8>> {
+        let protocol = NonNull::new(fv_ptr as *mut pi::protocols::firmware_volume::Protocol).unwrap();
+        MockProtocolData::instance().fv_read_file(protocol, name).ok().map(|file| file.content().to_vec())
+    }
+
+    // Returns the states of all files in the volume, including deleted ones.
+    fn file_states(base_address: u64) -> Vec<(efi::Guid, Option<ffs::file::State>)> {
+        let fv = unsafe { VolumeRef::new_from_address(base_address) }.unwrap();
+        let data = unsafe { slice::from_raw_parts(base_address as *const u8, fv.size() as usize) };
+        let mut offset = fv.content_offset();
+        let mut states = Vec::new();
+        while offset + size_of::<ffs::file::Header>() <= data.len()
+            && data[offset..offset + 24].iter().any(|&b| b != 0xff)
+        {
+            let header = unsafe { ptr::read_unaligned(data[offset..].as_ptr() as *const ffs::file::Header) };
+            states.push((header.name, patina_ffs::file::header_state(header.state, true)));
+            let size = u32::from_le_bytes([header.size[0], header.size[1], header.size[2], 0]) as usize;
+            offset = align_up((offset + size) as u64, 8).unwrap() as usize;
+        }
+        states
+    }
+
+    #[test]
+    fn test_fvb_write_and_erase_blocks() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (base_address, _, fvb_ptr) = install_ram_volume(&CORE, WRITABLE_FV, 4);
+            let block = |lba: usize| unsafe {
+                slice::from_raw_parts((base_address as usize + lba * 0x1000) as *const u8, 0x1000)
+            };
+
+            // A write within a block.
+            let mut data = [0x5au8; 0x20];
+            let mut num_bytes = data.len();
+            let status =
+                MockProtocolData::fvb_write_efiapi(fvb_ptr, 2, 0x10, &mut num_bytes, data.as_mut_ptr() as *mut c_void);
+            assert_eq!(status, efi::Status::SUCCESS);
+            assert_eq!(num_bytes, data.len());
+            assert_eq!(&block(2)[0x10..0x30], &data);
+
+            // A write across the end of the block is truncated.
+            let mut num_bytes = data.len();
+            let status =
+                MockProtocolData::fvb_write_efiapi(fvb_ptr, 2, 0xff0, &mut num_bytes, data.as_mut_ptr() as *mut c_void);
+            assert_eq!(status, efi::Status::BAD_BUFFER_SIZE);
+            assert_eq!(num_bytes, 0x10);
+            assert!(block(3).iter().all(|&b| b == 0xff));
+
+            // The erase of a list with an invalid LBA erases nothing.
+            let terminator = pi::protocols::firmware_volume_block::EFI_LBA_LIST_TERMINATOR;
+            let status =
+                unsafe { MockProtocolData::fvb_erase_blocks_efiapi(fvb_ptr, 2u64, 1usize, 3u64, 2usize, terminator) };
+            assert_eq!(status, efi::Status::INVALID_PARAMETER);
+            assert_eq!(&block(2)[0x10..0x30], &data);
+
+            let status = unsafe { MockProtocolData::fvb_erase_blocks_efiapi(fvb_ptr, 2u64, 2usize, terminator) };
+            assert_eq!(status, efi::Status::SUCCESS);
+            assert!(block(2).iter().all(|&b| b == 0xff));
+
+            // A read-only volume can be neither written nor erased.
+            let (_, _, fvb_ptr) = install_ram_volume(&CORE, WRITABLE_FV & !fvb::attributes::raw::fvb2::WRITE_STATUS, 2);
+            let mut num_bytes = data.len();
+            let status =
+                MockProtocolData::fvb_write_efiapi(fvb_ptr, 1, 0, &mut num_bytes, data.as_mut_ptr() as *mut c_void);
+            assert_eq!(status, efi::Status::ACCESS_DENIED);
+            let status = unsafe { MockProtocolData::fvb_erase_blocks_efiapi(fvb_ptr, 1u64, 1usize, terminator) };
+            assert_eq!(status, efi::Status::ACCESS_DENIED);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_fv_write_file_replaces_and_deletes_files() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (base_address, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV, 4);
+            let name_a = efi::Guid::from_fields(0xa, 0, 0, 0, 0, &[0; 6]);
+            let name_b = efi::Guid::from_fields(0xb, 0, 0, 0, 0, &[0; 6]);
+
+            assert_eq!(
+                write_file(
+                    fv_ptr,
+                    EFI_FV_UNRELIABLE_WRITE,
+                    &mut [(name_a, b"first".to_vec()), (name_b, b"other".to_vec())]
+                ),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(read_file(fv_ptr, name_a).unwrap(), b"first");
+            assert_eq!(read_file(fv_ptr, name_b).unwrap(), b"other");
+
+            // Replacing a file marks the old copy deleted and appends the new one.
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_RELIABLE_WRITE, &mut [(name_a, b"second".to_vec())]),
+                efi::Status::SUCCESS
+            );
+            assert_eq!(read_file(fv_ptr, name_a).unwrap(), b"second");
+            assert_eq!(
+                file_states(base_address),
+                vec![
+                    (name_a, Some(ffs::file::State::Deleted)),
+                    (name_b, Some(ffs::file::State::DataValid)),
+                    (name_a, Some(ffs::file::State::DataValid)),
+                ]
+            );
+
+            // An interrupted update leaves the old file readable until the new file is valid.
+            let old_offset = unsafe { VolumeRef::new_from_address(base_address) }
+                .unwrap()
+                .files()
+                .find_map(|f| f.ok().filter(|f| f.name() == name_b))
+                .map(|f| f.data().as_ptr().addr() - base_address as usize)
+                .unwrap();
+            unsafe { program_file_state(base_address, old_offset, ffs::file::raw::state::MARKED_FOR_UPDATE, true) };
+            assert_eq!(read_file(fv_ptr, name_b).unwrap(), b"other");
+
+            // A zero-length write deletes the file.
+            assert_eq!(write_file(fv_ptr, EFI_FV_UNRELIABLE_WRITE, &mut [(name_b, Vec::new())]), efi::Status::SUCCESS);
+            assert_eq!(read_file(fv_ptr, name_b), None);
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_UNRELIABLE_WRITE, &mut [(name_b, Vec::new())]),
+                efi::Status::NOT_FOUND
+            );
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_fv_write_file_reclaims_space() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (base_address, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV, 2);
+            let name_a = efi::Guid::from_fields(0xa, 0, 0, 0, 0, &[0; 6]);
+            let name_b = efi::Guid::from_fields(0xb, 0, 0, 0, 0, &[0; 6]);
+
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_UNRELIABLE_WRITE, &mut [(name_b, vec![0xb; 0x100])]),
+                efi::Status::SUCCESS
+            );
+
+            // Each update of a 0x600 byte file consumes free space until the volume must be reclaimed. A reliable write
+            // cannot reclaim, so it is refused and leaves the volume unchanged, and an unreliable one is made instead.
+            let mut refused = 0;
+            for round in 0..8u8 {
+                match write_file(fv_ptr, EFI_FV_RELIABLE_WRITE, &mut [(name_a, vec![round; 0x600])]) {
+                    efi::Status::SUCCESS => {}
+                    status => {
+                        assert_eq!(status, efi::Status::OUT_OF_RESOURCES);
+                        assert_eq!(read_file(fv_ptr, name_a).unwrap(), vec![round - 1; 0x600]);
+                        refused += 1;
+                        assert_eq!(
+                            write_file(fv_ptr, EFI_FV_UNRELIABLE_WRITE, &mut [(name_a, vec![round; 0x600])]),
+                            efi::Status::SUCCESS
+                        );
+                    }
+                }
+                assert_eq!(read_file(fv_ptr, name_a).unwrap(), vec![round; 0x600]);
+                assert_eq!(read_file(fv_ptr, name_b).unwrap(), vec![0xb; 0x100]);
+            }
+            assert!(refused > 0);
+            // Without reclaiming, the volume would hold all nine copies of the files.
+            assert!(file_states(base_address).len() < 9);
+
+            // Files that do not fit even after reclaiming space leave the volume unchanged.
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_UNRELIABLE_WRITE, &mut [(name_b, vec![0; 0x2000])]),
+                efi::Status::OUT_OF_RESOURCES
+            );
+            assert_eq!(read_file(fv_ptr, name_b).unwrap(), vec![0xb; 0x100]);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_fv_write_file_rejects_invalid_writes() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (_, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV, 2);
+            let name = efi::Guid::from_fields(0xa, 0, 0, 0, 0, &[0; 6]);
+
+            assert_eq!(write_file(fv_ptr, 2, &mut [(name, b"data".to_vec())]), efi::Status::INVALID_PARAMETER);
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_RELIABLE_WRITE, &mut [(name, b"one".to_vec()), (name, b"two".to_vec())]),
+                efi::Status::INVALID_PARAMETER
+            );
+            assert_eq!(read_file(fv_ptr, name), None);
+
+            let (_, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV & !fvb::attributes::raw::fvb2::WRITE_STATUS, 2);
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_UNRELIABLE_WRITE, &mut [(name, b"data".to_vec())]),
+                efi::Status::WRITE_PROTECTED
+            );
+        })
+        .unwrap();
+    }
+
+    fn get_info(
+        fv_ptr: *const pi::protocols::firmware_volume::Protocol,
+        information_type: efi::Guid,
+    ) -> (efi::Status, Vec<u8>) {
+        let mut size = 0;
+        let status = MockProtocolData::fv_get_info_efiapi(fv_ptr, &information_type, &mut size, ptr::null_mut());
+        if status != efi::Status::BUFFER_TOO_SMALL {
+            return (status, Vec::new());
+        }
+        let mut buffer = vec![0u8; size];
+        let status = MockProtocolData::fv_get_info_efiapi(
+            fv_ptr,
+            &information_type,
+            &mut size,
+            buffer.as_mut_ptr() as *mut c_void,
+        );
+        assert_eq!(size, buffer.len());
+        (status, buffer)
+    }
+
+    #[test]
+    fn test_fv_get_info_reports_volume() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (base_address, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV, 4);
+            let name = efi::Guid::from_fields(0xb, 0, 0, 0, 0, &[0; 6]);
+            assert_eq!(
+                write_file(fv_ptr, EFI_FV_RELIABLE_WRITE, &mut [(name, vec![0x5a; 0x100])]),
+                efi::Status::SUCCESS
+            );
+            let fv = unsafe { VolumeRef::new_from_address(base_address) }.unwrap();
+            let free_space = 0x4000 - fv.free_space_offset().unwrap() as u64;
+            assert!(free_space < 0x4000 - 0x100);
+
+            let (status, buffer) = get_info(fv_ptr, efi::protocols::file::SYSTEM_INFO_ID);
+            assert_eq!(status, efi::Status::SUCCESS);
+            let label_offset = mem::offset_of!(efi::protocols::file::SystemInfo, volume_label);
+            // The volume has no name, so the label is empty.
+            assert_eq!(buffer.len(), label_offset + size_of::<u16>());
+            let mut header = vec![0u8; size_of::<efi::protocols::file::SystemInfo>()];
+            header[..buffer.len()].copy_from_slice(&buffer);
+            let info = unsafe { ptr::read_unaligned(header.as_ptr() as *const efi::protocols::file::SystemInfo) };
+            assert_eq!(info.size, buffer.len() as u64);
+            assert!(!bool::from(info.read_only));
+            assert_eq!(info.volume_size, 0x4000);
+            assert_eq!(info.free_space, free_space);
+            assert_eq!(info.block_size, 0x1000);
+
+            let (status, buffer) = get_info(fv_ptr, FIRMWARE_VOLUME_INFO_ID);
+            assert_eq!(status, efi::Status::SUCCESS);
+            assert_eq!(buffer.len(), size_of::<EfiFirmwareVolumeInfo>());
+            let info = unsafe { ptr::read_unaligned(buffer.as_ptr() as *const EfiFirmwareVolumeInfo) };
+            assert_eq!(info.size, buffer.len() as u64);
+            assert_eq!(info.base_address, base_address);
+            assert_eq!(info.fv_length, 0x4000);
+            assert_eq!(info.attributes, WRITABLE_FV as fv::attributes::EfiFvAttributes);
+            assert_eq!(info.free_space, free_space);
+            assert_eq!(info.file_system_guid, fv.file_system_guid());
+            assert_eq!(info.fv_name, efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]));
+            assert_eq!(info.ext_header_size, 0);
+
+            let (status, _) = get_info(fv_ptr, efi::protocols::file::SYSTEM_VOLUME_LABEL_ID);
+            assert_eq!(status, efi::Status::UNSUPPORTED);
+
+            let (_, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV & !fvb::attributes::raw::fvb2::WRITE_STATUS, 2);
+            let (status, buffer) = get_info(fv_ptr, efi::protocols::file::SYSTEM_INFO_ID);
+            assert_eq!(status, efi::Status::SUCCESS);
+            let mut header = vec![0u8; size_of::<efi::protocols::file::SystemInfo>()];
+            header[..buffer.len()].copy_from_slice(&buffer);
+            let info = unsafe { ptr::read_unaligned(header.as_ptr() as *const efi::protocols::file::SystemInfo) };
+            assert!(bool::from(info.read_only));
+            assert_eq!(info.volume_size, 0x2000);
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_fv_set_info_rejects_changes() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (_, fv_ptr, _) = install_ram_volume(&CORE, WRITABLE_FV, 2);
+            let (_, buffer) = get_info(fv_ptr, FIRMWARE_VOLUME_INFO_ID);
+            let set_info = |information_type: efi::Guid, buffer: &[u8]| {
+                MockProtocolData::fv_set_info_efiapi(
+                    fv_ptr,
+                    &information_type,
+                    buffer.len(),
+                    buffer.as_ptr() as *const c_void,
+                )
+            };
+
+            assert_eq!(set_info(FIRMWARE_VOLUME_INFO_ID, &buffer), efi::Status::WRITE_PROTECTED);
+            assert_eq!(set_info(FIRMWARE_VOLUME_INFO_ID, &buffer[..8]), efi::Status::BAD_BUFFER_SIZE);
+            assert_eq!(set_info(efi::protocols::file::SYSTEM_VOLUME_LABEL_ID, &buffer), efi::Status::UNSUPPORTED);
+            assert_eq!(
+                MockProtocolData::fv_set_info_efiapi(fv_ptr, &FIRMWARE_VOLUME_INFO_ID, 8, ptr::null()),
+                efi::Status::INVALID_PARAMETER
+            );
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_firmware_volumes_service_lists_installed_volumes() {
+        test_support::with_global_lock(|| {
+            static CORE: MockCore = MockCore::new(CompositeSectionExtractor::new());
+            CORE.override_instance();
+
+            let (first, _, _) = install_ram_volume(&CORE, WRITABLE_FV, 2);
+            let (second, _, _) = install_ram_volume(&CORE, WRITABLE_FV & !fvb::attributes::raw::fvb2::WRITE_STATUS, 4);
+
+            let service = CoreFirmwareVolumes::new::<MockPlatformInfo>();
+            let volumes = service.volumes();
+            // Each volume has a FV and a FVB protocol installed, but is only listed once.
+            assert_eq!(volumes.len(), 2);
+
+            let first = volumes.iter().find(|volume| volume.base_address == first).unwrap();
+            assert_eq!(first.size, 0x2000);
+            assert!(first.is_writable());
+            assert_eq!(first.block_map, vec![fv::BlockMapEntry { num_blocks: 2, length: 0x1000 }]);
+            assert_eq!(first.fv_name, None);
+            assert_eq!(first.ext_header, None);
+
+            let content_offset = unsafe { VolumeRef::new_from_address(second) }.unwrap().content_offset() as u64;
+            let second = volumes.iter().find(|volume| volume.base_address == second).unwrap();
+            assert_eq!(second.size, 0x4000);
+            assert!(!second.is_writable());
+            // The volume is empty, so everything after the header is free.
+            assert_eq!(second.free_space, 0x4000 - content_offset);
+        })
+        .unwrap();
+    }
 }
@@ -74,4 +74,16 @@ impl<E: SectionExtractor> SectionExtractor for CoreExtractor<E> {
         }
         self.0.extract(section)
     }
+
+    fn extract_with_authentication_status(
+        &self,
+        section: &patina_ffs::section::Section,
+    ) -> Result<(vec::Vec<u8>, u32), FirmwareFileSystemError> {
+        match Self::uefi_decompress_extract(section) {
+            Err(FirmwareFileSystemError::Unsupported) => (),
+            Err(err) => return Err(err),
+            Ok(buffer) => return Ok((buffer, 0)),
+        }
+        self.0.extract_with_authentication_status(section)
+    }
 }
@@ -7,20 +7,72 @@
 //! SPDX-License-Identifier: Apache-2.0
 //!
 
-use core::{ffi::c_void, ptr};
+use core::{ffi::c_void, mem::size_of, ptr, slice};
 
-use alloc::collections::LinkedList;
-use patina::error::EfiError;
+use alloc::{boxed::Box, collections::LinkedList};
+use patina::{base::UEFI_PAGE_SHIFT, error::EfiError};
 use r_efi::efi;
 use spin::Mutex;
 
-use crate::{events::EVENT_DB, pecoff::relocation::RelocationBlock, protocols::PROTOCOL_DB};
+use crate::{
+    allocator::EFI_RUNTIME_SERVICES_DATA_ALLOCATOR,
+    config_tables::core_install_configuration_table,
+    events::EVENT_DB,
+    pecoff::{self, RuntimeFixup, UefiPeInfo, relocation::RelocationBlock},
+    protocols::PROTOCOL_DB,
+    systemtables::EfiSystemTable,
+};
 use patina::pi::{list_entry, protocols::runtime};
 
+/// The `EFI_RT_PROPERTIES_TABLE` bit of each entry of the runtime services table, in table order.
+const RUNTIME_SERVICES_SUPPORTED: [u32; 14] = [
+    efi::RT_SUPPORTED_GET_TIME,
+    efi::RT_SUPPORTED_SET_TIME,
+    efi::RT_SUPPORTED_GET_WAKEUP_TIME,
+    efi::RT_SUPPORTED_SET_WAKEUP_TIME,
+    efi::RT_SUPPORTED_SET_VIRTUAL_ADDRESS_MAP,
+    efi::RT_SUPPORTED_CONVERT_POINTER,
+    efi::RT_SUPPORTED_GET_VARIABLE,
+    efi::RT_SUPPORTED_GET_NEXT_VARIABLE_NAME,
+    efi::RT_SUPPORTED_SET_VARIABLE,
+    efi::RT_SUPPORTED_GET_NEXT_HIGH_MONOTONIC_COUNT,
+    efi::RT_SUPPORTED_RESET_SYSTEM,
+    efi::RT_SUPPORTED_UPDATE_CAPSULE,
+    efi::RT_SUPPORTED_QUERY_CAPSULE_CAPABILITIES,
+    efi::RT_SUPPORTED_QUERY_VARIABLE_INFO,
+];
+
+/// Returns the entries of the runtime services table as addresses.
+///
+/// ## Safety
+///
+/// `rt` must point to a runtime services table that is not otherwise referenced while the slice is used.
+unsafe fn runtime_services_entries<'a>(rt: *mut efi::RuntimeServices) -> &'a mut [usize] {
+    // SAFETY: The runtime services table is a table header followed only by function pointers.
+    unsafe {
+        slice::from_raw_parts_mut(
+            rt.byte_add(size_of::<efi::TableHeader>()) as *mut usize,
+            (size_of::<efi::RuntimeServices>() - size_of::<efi::TableHeader>()) / size_of::<usize>(),
+        )
+    }
+}
+
+/// A runtime image along with the fixups needed to move it to its virtual address.
+///
+/// Only `entry` is published through the runtime architectural protocol.
+struct RuntimeImage {
+    entry: runtime::ImageEntry,
+    fixups: &'static [RuntimeFixup],
+}
+
 struct RuntimeData {
     runtime_arch_ptr: *mut runtime::Protocol,
-    runtime_images: LinkedList<runtime::ImageEntry, &'static crate::allocator::UefiAllocatorWithFsb>,
+    runtime_images: LinkedList<RuntimeImage, &'static crate::allocator::UefiAllocatorWithFsb>,
     runtime_events: LinkedList<runtime::EventEntry, &'static crate::allocator::UefiAllocatorWithFsb>,
+    system_table: *mut efi::SystemTable,
+    rt_properties: *mut efi::RtPropertiesTable,
+    at_runtime: bool,
+    virtual_mode: bool,
 }
 
 unsafe impl Sync for RuntimeData {}
@@ -28,12 +80,93 @@ unsafe impl Send for RuntimeData {}
 
 static RUNTIME_DATA: Mutex<RuntimeData> = Mutex::new(RuntimeData::new());
 
+// The virtual map is only published while SetVirtualAddressMap() is running, so that the virtual address change
+// notifications can call ConvertPointer() while RUNTIME_DATA is held.
+static VIRTUAL_MAP: Mutex<Option<VirtualMap>> = Mutex::new(None);
+
+/// A validated view of the memory map handed to SetVirtualAddressMap().
+#[derive(Clone, Copy)]
+struct VirtualMap {
+    descriptors: *const u8,
+    count: usize,
+    descriptor_size: usize,
+}
+
+// SAFETY: The map is only read, and only for the duration of the SetVirtualAddressMap() call that provided it.
+unsafe impl Send for VirtualMap {}
+
+impl VirtualMap {
+    /// Validates the layout of the memory map and the alignment of every runtime descriptor in it.
+    ///
+    /// ## Safety
+    ///
+    /// `map` must point to `map_size` readable bytes for as long as the returned value is used.
+    unsafe fn new(
+        map_size: usize,
+        descriptor_size: usize,
+        descriptor_version: u32,
+        map: *const efi::MemoryDescriptor,
+    ) -> Result<Self, efi::Status> {
+        if map.is_null()
+            || descriptor_version != efi::MEMORY_DESCRIPTOR_VERSION
+            || descriptor_size < size_of::<efi::MemoryDescriptor>()
+            || !descriptor_size.is_multiple_of(size_of::<u64>())
+            || !map_size.is_multiple_of(descriptor_size)
+        {
+            return Err(efi::Status::INVALID_PARAMETER);
+        }
+
+        let map = Self { descriptors: map as *const u8, count: map_size / descriptor_size, descriptor_size };
+        let page_mask = (1u64 << UEFI_PAGE_SHIFT) - 1;
+        for descriptor in map.runtime_descriptors() {
+            if descriptor.physical_start & page_mask != 0
+                || descriptor.virtual_start & page_mask != 0
+                || descriptor
+                    .number_of_pages
+                    .checked_mul(1 << UEFI_PAGE_SHIFT)
+                    .and_then(|len| descriptor.physical_start.checked_add(len))
+                    .is_none()
+            {
+                return Err(efi::Status::INVALID_PARAMETER);
+            }
+        }
+        Ok(map)
+    }
+
+    fn runtime_descriptors(&self) -> impl Iterator<Item = efi::MemoryDescriptor> + '_ {
+        (0..self.count)
+            .map(|index| {
+                // SAFETY: VirtualMap::new() guarantees that count descriptors of descriptor_size bytes are readable.
+                unsafe {
+                    (self.descriptors.add(index * self.descriptor_size) as *const efi::MemoryDescriptor)
+                        .read_unaligned()
+                }
+            })
+            .filter(|descriptor| descriptor.attribute & efi::MEMORY_RUNTIME != 0)
+    }
+
+    /// Returns the virtual address of `address`, if it lies within a runtime descriptor of the map.
+    fn convert(&self, address: usize) -> Option<usize> {
+        let address = address as u64;
+        self.runtime_descriptors()
+            .find(|descriptor| {
+                address >= descriptor.physical_start
+                    && address - descriptor.physical_start < descriptor.number_of_pages << UEFI_PAGE_SHIFT
+            })
+            .map(|descriptor| (address - descriptor.physical_start + descriptor.virtual_start) as usize)
+    }
+}
+
 impl RuntimeData {
     const fn new() -> Self {
         Self {
             runtime_arch_ptr: ptr::null_mut(),
             runtime_images: LinkedList::new_in(&crate::allocator::EFI_RUNTIME_SERVICES_DATA_ALLOCATOR),
             runtime_events: LinkedList::new_in(&crate::allocator::EFI_RUNTIME_SERVICES_DATA_ALLOCATOR),
+            system_table: ptr::null_mut(),
+            rt_properties: ptr::null_mut(),
+            at_runtime: false,
+            virtual_mode: false,
         }
     }
 
@@ -47,10 +180,10 @@ impl RuntimeData {
         unsafe {
             // Update the image links
             let mut prev = &mut (*self.runtime_arch_ptr).image_head;
-            for entry in self.runtime_images.iter_mut() {
-                prev.forward_link = (&mut entry.link) as *mut _;
-                entry.link.back_link = prev as *mut _;
-                prev = &mut entry.link;
+            for image in self.runtime_images.iter_mut() {
+                prev.forward_link = (&mut image.entry.link) as *mut _;
+                image.entry.link.back_link = prev as *mut _;
+                prev = &mut image.entry.link;
             }
             prev.forward_link = &mut (*self.runtime_arch_ptr).image_head as *mut _;
             (*self.runtime_arch_ptr).image_head.back_link = prev as *mut _;
@@ -66,9 +199,150 @@ impl RuntimeData {
             (*self.runtime_arch_ptr).event_head.back_link = prev as *mut _;
         }
     }
+
+    /// Returns the `EFI_RT_PROPERTIES_TABLE` bits of the runtime services whose entry in `entries` lies in a runtime
+    /// image.
+    ///
+    /// Only runtime images are loaded into `EfiRuntimeServicesCode`. Every other entry, including those of the DXE core
+    /// and of the components linked into it, is in boot services code that the OS reclaims after ExitBootServices().
+    fn runtime_services_supported(&self, entries: &[usize]) -> u32 {
+        entries
+            .iter()
+            .zip(RUNTIME_SERVICES_SUPPORTED)
+            .filter(|&(&entry, _)| {
+                self.runtime_images.iter().any(|image| {
+                    let base = image.entry.image_base as usize;
+                    (base..base + image.entry.image_size as usize).contains(&entry)
+                })
+            })
+            .fold(0, |supported, (_, bit)| supported | bit)
+    }
+
+    /// Moves the runtime environment to the virtual addresses described by `map`.
+    ///
+    /// Nothing is modified unless every runtime image, the tables of the system table and every runtime service the
+    /// `EFI_RT_PROPERTIES_TABLE` reports as supported can be mapped. The other runtime services are boot services code
+    /// and keep their physical address. This runs after ExitBootServices(), so it must not allocate, log or take TPL
+    /// locks.
+    fn set_virtual_address_map(&mut self, map: VirtualMap) -> Result<(), efi::Status> {
+        if !self.at_runtime || self.virtual_mode {
+            return Err(efi::Status::UNSUPPORTED);
+        }
+
+        for image in self.runtime_images.iter() {
+            let base = image.entry.image_base as usize;
+            let last = base + (image.entry.image_size as usize).saturating_sub(1);
+            match (map.convert(base), map.convert(last)) {
+                (Some(virtual_base), Some(virtual_last)) if virtual_last.wrapping_sub(virtual_base) == last - base => {}
+                _ => return Err(efi::Status::NO_MAPPING),
+            }
+        }
+
+        // SAFETY: rt_properties is either null or the table installed by init_runtime_support().
+        let supported = unsafe { self.rt_properties.as_ref() }.map_or(u32::MAX, |rt| rt.runtime_services_supported);
+        // SAFETY: system_table is either null or the global system table, which lives in runtime services data.
+        if let Some(st) = unsafe { self.system_table.as_ref() } {
+            let tables = [st.runtime_services as usize, st.configuration_table as usize, st.firmware_vendor as usize];
+            if tables.iter().any(|&table| table != 0 && map.convert(table).is_none()) {
+                return Err(efi::Status::NOT_FOUND);
+            }
+            if !st.runtime_services.is_null() {
+                // SAFETY: The runtime services table is only read here, while RUNTIME_DATA is held.
+                let entries = unsafe { runtime_services_entries(st.runtime_services) };
+                if entries
+                    .iter()
+                    .zip(RUNTIME_SERVICES_SUPPORTED)
+                    .any(|(&entry, bit)| supported & bit != 0 && map.convert(entry).is_none())
+                {
+                    return Err(efi::Status::NOT_FOUND);
+                }
+            }
+        }
+
+        self.virtual_mode = true;
+        // SAFETY: runtime_arch_ptr is either null or points to the installed runtime architectural protocol.
+        if let Some(arch) = unsafe { self.runtime_arch_ptr.as_mut() } {
+            arch.memory_descriptor_size = map.descriptor_size;
+            arch.memory_descriptor_version = efi::MEMORY_DESCRIPTOR_VERSION;
+            arch.memory_map_size = map.count * map.descriptor_size;
+            arch.memory_map_virtual = map.descriptors as *mut efi::MemoryDescriptor;
+            arch.virtual_mode.store(true, core::sync::atomic::Ordering::Relaxed);
+        }
+
+        *VIRTUAL_MAP.lock() = Some(map);
+
+        for event in self.runtime_events.iter() {
+            if event.event_type & efi::EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE == efi::EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE {
+                (event.notify_function)(event.event, event.context);
+            }
+        }
+
+        for image in self.runtime_images.iter() {
+            let base = image.entry.image_base as usize;
+            let adjustment =
+                (map.convert(base).expect("Image mapping checked above.") as u64).wrapping_sub(base as u64);
+            // SAFETY: The image was loaded at image_base and spans image_size bytes, which are still mapped 1:1.
+            let bytes = unsafe { slice::from_raw_parts_mut(base as *mut u8, image.entry.image_size as usize) };
+            // The fixup offsets were bounds checked when the image was relocated at load time.
+            let _ = pecoff::relocate_runtime_image(bytes, image.fixups, adjustment);
+        }
+
+        // SAFETY: system_table is either null or the global system table, which lives in runtime services data.
+        if let Some(st) = unsafe { self.system_table.as_mut() } {
+            // SAFETY: The runtime services table pointer was set up with the system table and is still physical.
+            if let Some(rt) = unsafe { st.runtime_services.as_mut() } {
+                // SAFETY: rt is the runtime services table, which is only accessed through entries in this loop.
+                for entry in unsafe { runtime_services_entries(rt) } {
+                    // Only the unsupported services, checked above, are left unconverted.
+                    if let Some(virtual_entry) = map.convert(*entry) {
+                        *entry = virtual_entry;
+                    }
+                }
+                rt.hdr.crc32 = 0;
+                // SAFETY: rt is a valid reference to the runtime services table.
+                rt.hdr.crc32 = crc32fast::hash(unsafe {
+                    slice::from_raw_parts(rt as *const _ as *const u8, size_of::<efi::RuntimeServices>())
+                });
+            }
+
+            // Only null tables, checked above, are left unconverted.
+            let convert = |table: usize| map.convert(table).unwrap_or(table);
+            st.firmware_vendor = convert(st.firmware_vendor as usize) as _;
+            st.configuration_table = convert(st.configuration_table as usize) as _;
+            st.runtime_services = convert(st.runtime_services as usize) as _;
+            st.hdr.crc32 = 0;
+            // SAFETY: st is a valid reference to the system table.
+            st.hdr.crc32 = crc32fast::hash(unsafe {
+                slice::from_raw_parts(st as *const _ as *const u8, size_of::<efi::SystemTable>())
+            });
+        }
+
+        *VIRTUAL_MAP.lock() = None;
+        Ok(())
+    }
 }
 
-pub fn init_runtime_support(_rt: &mut efi::RuntimeServices) {
+pub fn init_runtime_support(system_table: &mut EfiSystemTable) {
+    RUNTIME_DATA.lock().system_table = system_table.system_table_mut() as *mut efi::SystemTable;
+
+    let rt = system_table.runtime_services_mut();
+    rt.set_virtual_address_map = set_virtual_address_map;
+    rt.convert_pointer = convert_pointer;
+
+    // The services that stay usable after ExitBootServices() are only known then; until that point, advertise them all.
+    let rt_properties = Box::leak(Box::new_in(
+        efi::RtPropertiesTable {
+            version: efi::RT_PROPERTIES_TABLE_VERSION,
+            length: size_of::<efi::RtPropertiesTable>() as u16,
+            runtime_services_supported: RUNTIME_SERVICES_SUPPORTED.iter().fold(0, |supported, bit| supported | bit),
+        },
+        &EFI_RUNTIME_SERVICES_DATA_ALLOCATOR,
+    )) as *mut efi::RtPropertiesTable;
+    match core_install_configuration_table(efi::RT_PROPERTIES_TABLE_GUID, rt_properties as *mut c_void, system_table) {
+        Ok(_) => RUNTIME_DATA.lock().rt_properties = rt_properties,
+        Err(err) => log::error!("Failed to install the runtime properties table: {err:?}"),
+    }
+
     // Setup a event callback for the runtime protocol.
     let event = EVENT_DB
         .create_event(efi::EVT_NOTIFY_SIGNAL, efi::TPL_CALLBACK, Some(runtime_protocol_notify), None, None)
@@ -80,10 +354,91 @@ pub fn init_runtime_support(_rt: &mut efi::RuntimeServices) {
 }
 
 pub fn finalize_runtime_support() {
-    let data = RUNTIME_DATA.lock();
+    let mut data = RUNTIME_DATA.lock();
+    data.at_runtime = true;
     if !data.runtime_arch_ptr.is_null() {
         unsafe { (*data.runtime_arch_ptr).at_runtime.store(true, core::sync::atomic::Ordering::Relaxed) };
     }
+
+    // Tell the OS which runtime services it can still call: those whose code was loaded as EfiRuntimeServicesCode.
+    // SAFETY: system_table is either null or the global system table, which lives in runtime services data.
+    let rt = unsafe { data.system_table.as_ref() }.map_or(ptr::null_mut(), |st| st.runtime_services);
+    // SAFETY: rt_properties is either null or the table installed by init_runtime_support(), in runtime services data.
+    if !rt.is_null()
+        && let Some(rt_properties) = unsafe { data.rt_properties.as_mut() }
+    {
+        // SAFETY: rt is the runtime services table, which is only read while RUNTIME_DATA is held.
+        let entries = unsafe { runtime_services_entries(rt) };
+        rt_properties.runtime_services_supported = data.runtime_services_supported(entries);
+    }
+}
+
+/// Resets the system through the `ResetSystem()` entry of the runtime services table.
+///
+/// The table is reached through the pointer recorded by [init_runtime_support] instead of the system table lock, so
+/// this can be called from interrupt or exception context. Returns if the reset could not be performed, for example
+/// because no reset implementation has been installed yet.
+pub fn reset_system(reset_type: efi::ResetType, status: efi::Status, data: &[u8]) {
+    let Some(system_table) = RUNTIME_DATA.try_lock().map(|data| data.system_table) else {
+        return;
+    };
+    // SAFETY: system_table is either null or the global system table, which lives in runtime services data.
+    let Some(rt) = (unsafe { system_table.as_ref() }).and_then(|st| unsafe { st.runtime_services.as_ref() }) else {
+        return;
+    };
+    let data_ptr = if data.is_empty() { ptr::null_mut() } else { data.as_ptr() as *mut c_void };
+    (rt.reset_system)(reset_type, status, data.len(), data_ptr);
+}
+
+/// Returns true once SetVirtualAddressMap() has completed.
+pub fn is_virtual_mode() -> bool {
+    RUNTIME_DATA.lock().virtual_mode
+}
+
+extern "efiapi" fn set_virtual_address_map(
+    memory_map_size: usize,
+    descriptor_size: usize,
+    descriptor_version: u32,
+    virtual_map: *mut efi::MemoryDescriptor,
+) -> efi::Status {
+    // SAFETY: The caller provides a memory map of memory_map_size bytes, valid for the duration of this call.
+    let map = match unsafe { VirtualMap::new(memory_map_size, descriptor_size, descriptor_version, virtual_map) } {
+        Ok(map) => map,
+        Err(status) => return status,
+    };
+
+    match RUNTIME_DATA.lock().set_virtual_address_map(map) {
+        Ok(()) => efi::Status::SUCCESS,
+        Err(status) => status,
+    }
+}
+
+extern "efiapi" fn convert_pointer(debug_disposition: usize, address: *mut *mut c_void) -> efi::Status {
+    if address.is_null() {
+        return efi::Status::INVALID_PARAMETER;
+    }
+
+    // SAFETY: address was checked for null and the caller guarantees it points to a pointer.
+    let pointer = unsafe { address.read_unaligned() };
+    if pointer.is_null() {
+        return match debug_disposition & efi::OPTIONAL_POINTER as usize {
+            0 => efi::Status::INVALID_PARAMETER,
+            _ => efi::Status::SUCCESS,
+        };
+    }
+
+    let Some(map) = *VIRTUAL_MAP.lock() else {
+        return efi::Status::UNSUPPORTED;
+    };
+
+    match map.convert(pointer as usize) {
+        Some(converted) => {
+            // SAFETY: address was checked for null and the caller guarantees it points to a pointer.
+            unsafe { address.write_unaligned(converted as *mut c_void) };
+            efi::Status::SUCCESS
+        }
+        None => efi::Status::NOT_FOUND,
+    }
 }
 
 extern "efiapi" fn runtime_protocol_notify(_event: efi::Event, _context: *mut c_void) {
@@ -135,18 +490,23 @@ pub fn remove_runtime_event(event: efi::Event) -> Result<(), EfiError> {
 pub fn add_runtime_image(
     image_base: *mut c_void,
     image_size: u64,
+    pe_info: &UefiPeInfo,
     relocation_data: &[RelocationBlock],
     handle: efi::Handle,
 ) -> Result<(), EfiError> {
     let mut data = RUNTIME_DATA.lock();
 
-    let relocation_data = crate::pecoff::flatten_runtime_relocation_data(relocation_data);
-    data.runtime_images.push_back(runtime::ImageEntry {
-        image_base,
-        image_size,
-        relocation_data: relocation_data.as_mut_ptr() as *mut _,
-        handle,
-        link: list_entry::Entry { forward_link: ptr::null_mut(), back_link: ptr::null_mut() },
+    let fixups = pecoff::runtime_fixups(pe_info, relocation_data);
+    let relocation_data = pecoff::flatten_runtime_relocation_data(relocation_data);
+    data.runtime_images.push_back(RuntimeImage {
+        entry: runtime::ImageEntry {
+            image_base,
+            image_size,
+            relocation_data: relocation_data.as_mut_ptr() as *mut _,
+            handle,
+            link: list_entry::Entry { forward_link: ptr::null_mut(), back_link: ptr::null_mut() },
+        },
+        fixups,
     });
 
     data.update_protocol_lists();
@@ -155,7 +515,7 @@ pub fn add_runtime_image(
 
 pub fn remove_runtime_image(image_handle: efi::Handle) -> Result<(), EfiError> {
     let mut data = RUNTIME_DATA.lock();
-    for _ in data.runtime_images.extract_if(|entry| entry.handle == image_handle) {}
+    for _ in data.runtime_images.extract_if(|image| image.entry.handle == image_handle) {}
     data.update_protocol_lists();
     Ok(())
 }
@@ -188,13 +548,16 @@ mod tests {
         // Do nothing
     }
 
-    fn new_image(handle: usize) -> runtime::ImageEntry {
-        runtime::ImageEntry {
-            image_base: ptr::null_mut(),
-            image_size: 0,
-            relocation_data: ptr::null_mut(),
-            handle: handle as efi::Handle,
-            link: list_entry::Entry { forward_link: ptr::null_mut(), back_link: ptr::null_mut() },
+    fn new_image(handle: usize) -> RuntimeImage {
+        RuntimeImage {
+            entry: runtime::ImageEntry {
+                image_base: ptr::null_mut(),
+                image_size: 0,
+                relocation_data: ptr::null_mut(),
+                handle: handle as efi::Handle,
+                link: list_entry::Entry { forward_link: ptr::null_mut(), back_link: ptr::null_mut() },
+            },
+            fixups: &[],
         }
     }
 
@@ -259,7 +622,7 @@ mod tests {
 
             // Remove all the odd images
             for i in (0..10).filter(|x| x % 2 == 1) {
-                for _ in data.runtime_images.extract_if(|entry| entry.handle == i as efi::Handle) {}
+                for _ in data.runtime_images.extract_if(|image| image.entry.handle == i as efi::Handle) {}
             }
             data.update_protocol_lists();
 
@@ -349,4 +712,254 @@ mod tests {
             }
         });
     }
+
+    const VIRTUAL_OFFSET: usize = 0xFFFF_8000_0000_0000;
+
+    fn descriptor(
+        physical_start: u64,
+        number_of_pages: u64,
+        virtual_start: u64,
+        attribute: u64,
+    ) -> efi::MemoryDescriptor {
+        efi::MemoryDescriptor {
+            r#type: efi::RUNTIME_SERVICES_DATA,
+            physical_start,
+            virtual_start,
+            number_of_pages,
+            attribute,
+        }
+    }
+
+    fn virtual_map(descriptors: &[efi::MemoryDescriptor]) -> VirtualMap {
+        // SAFETY: The descriptors slice outlives every use of the map in these tests.
+        unsafe {
+            VirtualMap::new(
+                size_of_val(descriptors),
+                size_of::<efi::MemoryDescriptor>(),
+                efi::MEMORY_DESCRIPTOR_VERSION,
+                descriptors.as_ptr(),
+            )
+        }
+        .unwrap()
+    }
+
+    fn crc_is_valid<T>(table: *mut T, crc: *mut u32) -> bool {
+        // SAFETY: Test code only - both pointers reference the same live table.
+        unsafe {
+            let expected = *crc;
+            *crc = 0;
+            let actual = crc32fast::hash(core::slice::from_raw_parts(table as *const u8, size_of::<T>()));
+            *crc = expected;
+            actual == expected
+        }
+    }
+
+    extern "efiapi" fn convert_context(_event: efi::Event, context: *mut c_void) {
+        assert_eq!(convert_pointer(0, context as *mut *mut c_void), efi::Status::SUCCESS);
+    }
+
+    #[test]
+    fn test_virtual_map_validation() {
+        let descriptors = [descriptor(0x1000, 1, 0x8000_1000, efi::MEMORY_RUNTIME)];
+        let size = size_of::<efi::MemoryDescriptor>();
+        let version = efi::MEMORY_DESCRIPTOR_VERSION;
+
+        // SAFETY: Test code only - the descriptors outlive every map created below.
+        unsafe {
+            assert!(VirtualMap::new(size, size, version, descriptors.as_ptr()).is_ok());
+            assert_eq!(VirtualMap::new(size, size, version, ptr::null()).err(), Some(efi::Status::INVALID_PARAMETER));
+            assert_eq!(
+                VirtualMap::new(size, size, version + 1, descriptors.as_ptr()).err(),
+                Some(efi::Status::INVALID_PARAMETER)
+            );
+            assert_eq!(
+                VirtualMap::new(size, size - 8, version, descriptors.as_ptr()).err(),
+                Some(efi::Status::INVALID_PARAMETER)
+            );
+            assert_eq!(
+                VirtualMap::new(size - 8, size, version, descriptors.as_ptr()).err(),
+                Some(efi::Status::INVALID_PARAMETER)
+            );
+        }
+
+        let unaligned = [descriptor(0x1000, 1, 0x8000_1800, efi::MEMORY_RUNTIME)];
+        // SAFETY: Test code only - the descriptors outlive the map.
+        let result = unsafe { VirtualMap::new(size, size, version, unaligned.as_ptr()) };
+        assert_eq!(result.err(), Some(efi::Status::INVALID_PARAMETER));
+
+        // Alignment is only enforced for runtime descriptors.
+        let boot_time = [descriptor(0x1000, 1, 0x8000_1800, 0)];
+        // SAFETY: Test code only - the descriptors outlive the map.
+        assert!(unsafe { VirtualMap::new(size, size, version, boot_time.as_ptr()) }.is_ok());
+    }
+
+    #[test]
+    fn test_virtual_map_convert() {
+        let descriptors = [
+            descriptor(0x1000, 2, 0x8000_0000, efi::MEMORY_RUNTIME),
+            descriptor(0x4000, 1, 0x9000_0000, 0),
+            descriptor(0x5000, 1, 0xA000_0000, efi::MEMORY_RUNTIME),
+        ];
+        let map = virtual_map(&descriptors);
+
+        assert_eq!(map.convert(0x1000), Some(0x8000_0000));
+        assert_eq!(map.convert(0x2FFF), Some(0x8000_1FFF));
+        assert_eq!(map.convert(0x5010), Some(0xA000_0010));
+        assert_eq!(map.convert(0x3000), None);
+        assert_eq!(map.convert(0x4000), None);
+    }
+
+    #[test]
+    fn test_convert_pointer() {
+        with_locked_state(|| {
+            let descriptors = [descriptor(0x1000, 1, 0x8000_0000, efi::MEMORY_RUNTIME)];
+            let mut pointer = 0x1010 as *mut c_void;
+
+            // Outside of SetVirtualAddressMap() there is nothing to convert against.
+            assert_eq!(convert_pointer(0, &mut pointer), efi::Status::UNSUPPORTED);
+            assert_eq!(convert_pointer(0, ptr::null_mut()), efi::Status::INVALID_PARAMETER);
+
+            *VIRTUAL_MAP.lock() = Some(virtual_map(&descriptors));
+
+            assert_eq!(convert_pointer(0, &mut pointer), efi::Status::SUCCESS);
+            assert_eq!(pointer as usize, 0x8000_0010);
+
+            let mut unmapped = 0x2000 as *mut c_void;
+            assert_eq!(convert_pointer(0, &mut unmapped), efi::Status::NOT_FOUND);
+            assert_eq!(unmapped as usize, 0x2000);
+
+            let mut null = ptr::null_mut();
+            assert_eq!(convert_pointer(0, &mut null), efi::Status::INVALID_PARAMETER);
+            assert_eq!(convert_pointer(efi::OPTIONAL_POINTER as usize, &mut null), efi::Status::SUCCESS);
+
+            *VIRTUAL_MAP.lock() = None;
+        });
+    }
+
+    #[test]
+    fn test_runtime_services_supported() {
+        with_locked_state(|| {
+            let mut data = setup_protocol_and_data();
+            let mut runtime_image = new_image(1);
+            runtime_image.entry.image_base = 0x10000 as *mut c_void;
+            runtime_image.entry.image_size = 0x1000;
+            data.runtime_images.push_back(runtime_image);
+
+            // Only the entries inside the runtime image remain supported after ExitBootServices().
+            let mut entries = [0x20000usize; 14];
+            entries[0] = 0x11000;
+            entries[6] = 0x10010;
+            entries[10] = 0x10FFF;
+            assert_eq!(
+                data.runtime_services_supported(&entries),
+                efi::RT_SUPPORTED_GET_VARIABLE | efi::RT_SUPPORTED_RESET_SYSTEM
+            );
+
+            data.runtime_images.clear();
+            assert_eq!(data.runtime_services_supported(&entries), 0);
+        });
+    }
+
+    #[test]
+    fn test_set_virtual_address_map() {
+        with_locked_state(|| {
+            crate::systemtables::init_system_table();
+            let mut data = setup_protocol_and_data();
+            data.system_table = crate::systemtables::SYSTEM_TABLE.lock().as_mut().unwrap().system_table_mut();
+            // SAFETY: Test code only - the system table was just initialized.
+            let rt = unsafe { (*data.system_table).runtime_services };
+            // SAFETY: Test code only - the runtime services table was just initialized.
+            let get_time = unsafe { (*rt).get_time } as *const () as usize;
+
+            // A fake runtime image holding a pointer to itself, plus a fixup that the driver has since overwritten.
+            let image = Box::leak(vec![0u64; 4].into_boxed_slice());
+            let image_base = image.as_mut_ptr() as usize;
+            image[0] = image_base as u64 + 0x10;
+            image[1] = 0x1234;
+            let fixups = Box::leak(Box::new([
+                RuntimeFixup { offset: 0, fixup_type: 10, value: image_base as u64 + 0x10 },
+                RuntimeFixup { offset: 8, fixup_type: 10, value: image_base as u64 + 0x18 },
+            ]));
+            let mut runtime_image = new_image(1);
+            runtime_image.entry.image_base = image_base as *mut c_void;
+            runtime_image.entry.image_size = 0x20;
+            runtime_image.fixups = fixups;
+            data.runtime_images.push_back(runtime_image);
+
+            let signalled = Box::leak(Box::new(0x5000 as *mut c_void));
+            let not_signalled = Box::leak(Box::new(0x6000 as *mut c_void));
+            let mut event = new_event(1);
+            event.event_type = efi::EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE;
+            event.notify_function = convert_context;
+            event.context = signalled as *mut *mut c_void as *mut c_void;
+            data.runtime_events.push_back(event);
+            let mut event = new_event(2);
+            event.event_type = efi::EVT_SIGNAL_EXIT_BOOT_SERVICES | efi::EVT_RUNTIME;
+            event.notify_function = convert_context;
+            event.context = not_signalled as *mut *mut c_void as *mut c_void;
+            data.runtime_events.push_back(event);
+            data.update_protocol_lists();
+
+            // Map the low half of the address space so that every pointer the test process holds can be converted.
+            let descriptors = [descriptor(0, 1 << (47 - UEFI_PAGE_SHIFT), VIRTUAL_OFFSET as u64, efi::MEMORY_RUNTIME)];
+            let map = virtual_map(&descriptors);
+
+            // Only valid after ExitBootServices().
+            assert_eq!(data.set_virtual_address_map(map), Err(efi::Status::UNSUPPORTED));
+            data.at_runtime = true;
+
+            // Nothing changes if a runtime image is left out of the map.
+            let partial = [descriptor(0x1000, 1, 0x8000_0000, efi::MEMORY_RUNTIME)];
+            assert_eq!(data.set_virtual_address_map(virtual_map(&partial)), Err(efi::Status::NO_MAPPING));
+            assert!(!data.virtual_mode);
+
+            // Nor if a runtime service reported as supported is left out of the map.
+            let get_time_page = (get_time as u64) & !((1 << UEFI_PAGE_SHIFT) - 1);
+            let low_half_pages = 1 << (47 - UEFI_PAGE_SHIFT);
+            let without_get_time = [
+                descriptor(0, get_time_page >> UEFI_PAGE_SHIFT, VIRTUAL_OFFSET as u64, efi::MEMORY_RUNTIME),
+                descriptor(
+                    get_time_page + (1 << UEFI_PAGE_SHIFT),
+                    low_half_pages - (get_time_page >> UEFI_PAGE_SHIFT) - 1,
+                    get_time_page + (1 << UEFI_PAGE_SHIFT) + VIRTUAL_OFFSET as u64,
+                    efi::MEMORY_RUNTIME,
+                ),
+            ];
+            let rt_properties = Box::leak(Box::new(efi::RtPropertiesTable {
+                version: efi::RT_PROPERTIES_TABLE_VERSION,
+                length: size_of::<efi::RtPropertiesTable>() as u16,
+                runtime_services_supported: efi::RT_SUPPORTED_GET_TIME,
+            }));
+            data.rt_properties = rt_properties;
+            assert_eq!(data.set_virtual_address_map(virtual_map(&without_get_time)), Err(efi::Status::NOT_FOUND));
+            assert!(!data.virtual_mode);
+            data.rt_properties = ptr::null_mut();
+
+            assert_eq!(data.set_virtual_address_map(map), Ok(()));
+            assert!(data.virtual_mode);
+            // SAFETY: Test code only - the protocol was leaked by setup_protocol_and_data.
+            assert!(unsafe { (*data.runtime_arch_ptr).virtual_mode.load(core::sync::atomic::Ordering::Relaxed) });
+
+            assert_eq!(image[0], (image_base + VIRTUAL_OFFSET) as u64 + 0x10);
+            assert_eq!(image[1], 0x1234);
+            assert_eq!(*signalled as usize, 0x5000 + VIRTUAL_OFFSET);
+            assert_eq!(*not_signalled as usize, 0x6000);
+
+            // SAFETY: Test code only - the tables are still accessible through their physical addresses.
+            unsafe {
+                assert_eq!((*rt).get_time as *const () as usize, get_time + VIRTUAL_OFFSET);
+                assert!(crc_is_valid(rt, &mut (*rt).hdr.crc32));
+                assert_eq!((*data.system_table).runtime_services as usize, rt as usize + VIRTUAL_OFFSET);
+                assert!(crc_is_valid(data.system_table, &mut (*data.system_table).hdr.crc32));
+            }
+
+            // The transition happens once, and ConvertPointer() is no longer usable afterwards.
+            assert_eq!(data.set_virtual_address_map(map), Err(efi::Status::UNSUPPORTED));
+            let mut pointer = 0x5000 as *mut c_void;
+            assert_eq!(convert_pointer(0, &mut pointer), efi::Status::UNSUPPORTED);
+
+            // Leave a usable system table behind for other tests.
+            crate::systemtables::init_system_table();
+        });
+    }
 }
@@ -8,10 +8,26 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
-use core::{ffi::c_void, mem::size_of, slice::from_raw_parts};
+use core::{
+    ffi::c_void,
+    mem::size_of,
+    slice::from_raw_parts,
+    sync::atomic::{AtomicU8, Ordering},
+};
 
 use alloc::{alloc::Allocator, boxed::Box};
-use patina::{boot_services::BootServices, component::component, pi::error_codes::EFI_NOT_AVAILABLE_YET};
+use patina::{
+    boot_services::BootServices,
+    component::{
+        component,
+        service::{
+            IntoService,
+            runtime_table::{RuntimeServiceEntries, RuntimeServicesTable},
+        },
+    },
+    error::EfiError,
+    pi::error_codes::EFI_NOT_AVAILABLE_YET,
+};
 use r_efi::efi;
 
 use crate::{allocator::EFI_RUNTIME_SERVICES_DATA_ALLOCATOR, tpl_mutex};
@@ -724,6 +740,71 @@ pub fn init_system_table() {
     _ = SYSTEM_TABLE.lock().insert(table);
 }
 
+/// Core implementation of the [RuntimeServicesTable] service.
+///
+/// Tracks which entry groups have been installed so that two producers cannot silently replace each other.
+#[derive(IntoService, Default)]
+#[service(dyn RuntimeServicesTable)]
+pub(crate) struct CoreRuntimeServicesTable {
+    installed: AtomicU8,
+}
+
+impl CoreRuntimeServicesTable {
+    fn group_bit(entries: &RuntimeServiceEntries) -> u8 {
+        match entries {
+            RuntimeServiceEntries::Variable(_) => 1 << 0,
+            RuntimeServiceEntries::Time(_) => 1 << 1,
+            RuntimeServiceEntries::Reset(_) => 1 << 2,
+            RuntimeServiceEntries::Capsule(_) => 1 << 3,
+            RuntimeServiceEntries::MonotonicCounter(_) => 1 << 4,
+        }
+    }
+}
+
+impl RuntimeServicesTable for CoreRuntimeServicesTable {
+    fn install(&self, entries: RuntimeServiceEntries) -> Result<(), EfiError> {
+        let mut st = SYSTEM_TABLE.lock();
+        let st = st.as_mut().ok_or(EfiError::NotReady)?;
+
+        // The table has been handed over to the OS and converted to virtual addresses.
+        if crate::runtime::is_virtual_mode() {
+            return Err(EfiError::WriteProtected);
+        }
+
+        let bit = Self::group_bit(&entries);
+        if self.installed.fetch_or(bit, Ordering::SeqCst) & bit != 0 {
+            log::error!("{} runtime services have already been installed.", entries.name());
+            return Err(EfiError::AlreadyStarted);
+        }
+
+        let rt = st.runtime_services_mut();
+        match entries {
+            RuntimeServiceEntries::Variable(e) => {
+                rt.get_variable = e.get_variable;
+                rt.get_next_variable_name = e.get_next_variable_name;
+                rt.set_variable = e.set_variable;
+                rt.query_variable_info = e.query_variable_info;
+            }
+            RuntimeServiceEntries::Time(e) => {
+                rt.get_time = e.get_time;
+                rt.set_time = e.set_time;
+                rt.get_wakeup_time = e.get_wakeup_time;
+                rt.set_wakeup_time = e.set_wakeup_time;
+            }
+            RuntimeServiceEntries::Reset(e) => rt.reset_system = e.reset_system,
+            RuntimeServiceEntries::Capsule(e) => {
+                rt.update_capsule = e.update_capsule;
+                rt.query_capsule_capabilities = e.query_capsule_capabilities;
+            }
+            RuntimeServiceEntries::MonotonicCounter(e) => rt.get_next_high_mono_count = e.get_next_high_mono_count,
+        }
+        st.checksum_all();
+
+        log::info!("Installed {} runtime services.", entries.name());
+        Ok(())
+    }
+}
+
 /// A component to register a callback that recalculates the CRC32 checksum of the system table
 /// when certain protocols are installed.
 #[derive(Default)]
@@ -829,4 +910,29 @@ mod tests {
             assert_eq!(table.system_table_mut().boot_services, core::ptr::null_mut());
         })
     }
+
+    #[test]
+    fn test_runtime_services_table_install() {
+        use patina::component::service::runtime_table::ResetEntries;
+
+        extern "efiapi" fn reset_system(_: efi::ResetType, _: efi::Status, _: usize, _: *mut c_void) {}
+
+        with_locked_state(|| {
+            init_system_table();
+            let runtime_services_crc32 = SYSTEM_TABLE.lock().as_mut().unwrap().runtime_services().hdr.crc32;
+
+            let table = CoreRuntimeServicesTable::default();
+            let entries = RuntimeServiceEntries::Reset(ResetEntries { reset_system });
+            assert_eq!(table.install(entries), Ok(()));
+
+            let mut st = SYSTEM_TABLE.lock();
+            let rt = st.as_mut().unwrap().runtime_services();
+            assert_eq!(rt.reset_system as usize, reset_system as *const () as usize);
+            assert_ne!(rt.hdr.crc32, runtime_services_crc32);
+            drop(st);
+
+            // A second producer for the same group is rejected.
+            assert_eq!(table.install(entries), Err(EfiError::AlreadyStarted));
+        })
+    }
 }
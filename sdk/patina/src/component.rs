@@ -49,6 +49,31 @@
 //! - Validates parameters for conflicts at compile time
 //! - Generates the `IntoComponent` trait implementation
 //!
+//! ### Event Triggered Components
+//!
+//! Components run during the dispatch loop by default. Work that must happen at a later point of boot, such as
+//! ReadyToBoot or EndOfDxe, can instead name the event group that triggers it:
+//!
+//! ```rust,ignore
+//! use patina::component::component;
+//! use patina::error::Result;
+//!
+//! pub struct MyReadyToBootComponent;
+//!
+//! #[component(event = r_efi::efi::EVENT_GROUP_READY_TO_BOOT)]
+//! impl MyReadyToBootComponent {
+//!     fn entry_point(self) -> Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//! ```
+//!
+//! The core does not dispatch the component until the event group is signalled. From then on, it is dispatched as
+//! soon as its parameters are available.
+//!
+//! The ExitBootServices and BeforeExitBootServices event groups cannot trigger a component, as the memory map must not
+//! change while ExitBootServices is running. A component naming either fails to initialize and is never dispatched.
+//!
 //! ### Parameter Validation
 //!
 //! The attribute validates that there are no conflicting parameter combinations such as:
@@ -79,6 +104,7 @@
 //! | Config\<T\>                  | An immutable config value that will only be available once the underlying data has been locked. See The [params] module for more info.                                |
 //! | ConfigMut\<T\>               | A mutable config value that will only be available while the underlying data is unlocked. See the [params] module for more info.                                      |
 //! | Service\<T\>                 | A wrapper for producing and consuming services of a particular interface, `T`, that is agnostic to the underlying implementation. See [service] module for more info. |
+//! | Protocol\<P\>                | A UEFI protocol interface. The component waits until the protocol is installed, for example by a UEFI driver. See the [params] module for more info.              |
 //! | StandardBootServices         | Rust implementation of Boot Services                                                                                                                                  |
 //!
 //! ### Examples
@@ -67,6 +67,7 @@ use core::{any::Any, ops::Deref};
 use super::{
     metadata::MetaData,
     params::Param,
+    service::dispatch_report::Requirement,
     storage::{Storage, UnsafeStorageCell},
 };
 
@@ -76,7 +77,8 @@ use super::{
 /// the freedom to parse the byte array in anyway they see fit. It could be as simple as casting the byte array to a
 /// struct or a more complex parsing process.
 ///
-/// This trait is used to parse guided HOBs as specified in the PI specification.
+/// This trait is used to parse guided HOBs as specified in the PI specification. HOBs of other types, which have no
+/// GUID, are parsed by setting [FromHob::HOB_TYPE].
 ///
 /// ## Example
 ///
@@ -110,8 +112,16 @@ use super::{
 /// ```
 pub trait FromHob: Sized + 'static {
     /// The guid value associated with the guided HOB to parse.
+    ///
+    /// Not used for HOBs that are not GUIDed, see [FromHob::HOB_TYPE].
     const HOB_GUID: OwnedGuid;
 
+    /// The type of HOB to parse.
+    ///
+    /// GUIDed HOBs, the default, are matched by [FromHob::HOB_GUID] and parsed from the data that follows the GUID.
+    /// HOBs of any other type are matched by their type and parsed from the whole HOB, starting at its header.
+    const HOB_TYPE: u16 = crate::pi::hob::GUID_EXTENSION;
+
     /// Registers the parsed hob with the provided [Storage] instance.
     fn register(bytes: &[u8], storage: &mut Storage) {
         storage.add_hob(Self::parse(bytes));
@@ -235,6 +245,10 @@ unsafe impl<T: FromHob + 'static> Param for Hob<'_, T> {
         !unsafe { storage.storage() }.get_raw_hob(*state).is_empty()
     }
 
+    fn requirement() -> Requirement {
+        Requirement::Hob(core::any::type_name::<T>().into())
+    }
+
     fn init_state(storage: &mut Storage, _meta: &mut MetaData) -> Result<Self::State, Cow<'static, str>> {
         storage.add_hob_parser::<T>();
         Ok(storage.register_hob::<T>())
@@ -289,6 +303,27 @@ impl<'h, T: FromHob + 'static> IntoIterator for &Hob<'h, T> {
     }
 }
 
+/// UEFI capsule HOBs, which describe the capsules coalesced by the pre-DXE phase.
+impl FromHob for crate::pi::hob::Capsule {
+    const HOB_GUID: OwnedGuid = crate::Guid::from_fields(0, 0, 0, 0, 0, [0; 6]);
+    const HOB_TYPE: u16 = crate::pi::hob::UEFI_CAPSULE;
+
+    fn parse(bytes: &[u8]) -> Self {
+        let field = |offset: usize| {
+            bytes.get(offset..offset + 8).map_or(0, |b| u64::from_le_bytes(b.try_into().expect("8 byte slice")))
+        };
+        crate::pi::hob::Capsule {
+            header: crate::pi::hob::header::Hob {
+                r#type: crate::pi::hob::UEFI_CAPSULE,
+                length: core::mem::size_of::<Self>() as u16,
+                reserved: 0,
+            },
+            base_address: field(core::mem::offset_of!(Self, base_address)),
+            length: field(core::mem::offset_of!(Self, length)),
+        }
+    }
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
@@ -358,6 +393,30 @@ mod tests {
         assert!(iter.next().is_none()); // No more elements
     }
 
+    #[test]
+    fn test_capsule_hob_parse() {
+        use crate::pi::hob::{Capsule, UEFI_CAPSULE};
+
+        let mut storage = Storage::new();
+        storage.add_hob_parser::<Capsule>();
+        let id = storage.register_hob::<Capsule>();
+        // Capsule HOBs are parsed from the whole HOB, and found by their type rather than a GUID.
+        let header = [UEFI_CAPSULE.to_le_bytes(), 24_u16.to_le_bytes(), [0; 2], [0; 2]].concat();
+        let bytes = [header, 0x1000_u64.to_le_bytes().to_vec(), 0x200_u64.to_le_bytes().to_vec()].concat();
+        assert!(storage.get_hob_parsers(&Capsule::HOB_GUID).is_empty());
+        for parser in storage.get_hob_type_parsers(UEFI_CAPSULE) {
+            parser(&bytes, &mut storage);
+        }
+
+        let hob: Hob<Capsule> = Hob::from(storage.get_raw_hob(id));
+        assert_eq!(hob.header.r#type, UEFI_CAPSULE);
+        assert_eq!(hob.base_address, 0x1000);
+        assert_eq!(hob.length, 0x200);
+
+        // Truncated contents parse as an empty capsule rather than panicking.
+        assert_eq!(Capsule::parse(&bytes[..16]).length, 0);
+    }
+
     #[test]
     fn test_component_flow() {
         struct MyComponent;
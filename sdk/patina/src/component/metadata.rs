@@ -12,7 +12,10 @@
 use core::fmt;
 use fixedbitset::FixedBitSet;
 
-use alloc::borrow::Cow;
+use alloc::{borrow::Cow, vec::Vec};
+use r_efi::efi;
+
+use super::service::dispatch_report::Requirement;
 
 /// Metadata for a component. Not used for execution, but referenced by the scheduler.
 #[derive(Default, Debug)]
@@ -23,12 +26,22 @@ pub struct MetaData {
     name: Cow<'static, str>,
     /// The error message preventing the component from being dispatched.
     error_message: Option<Cow<'static, str>>,
+    /// The requirements of the params that were not available on the last dispatch attempt.
+    unmet_params: Vec<Requirement>,
+    /// The event group that must be signalled before the component is dispatched, if any.
+    trigger: Option<efi::Guid>,
 }
 
 impl MetaData {
     /// Creates a new metadata object for a component.
     pub fn new<S>() -> Self {
-        Self { access: Access::new(), name: Cow::from(core::any::type_name::<S>()), error_message: None }
+        Self {
+            access: Access::new(),
+            name: Cow::from(core::any::type_name::<S>()),
+            error_message: None,
+            unmet_params: Vec::new(),
+            trigger: None,
+        }
     }
 
     /// Returns the name of the component, including the module path.
@@ -49,6 +62,30 @@ impl MetaData {
         self.error_message.clone()
     }
 
+    /// Sets the requirements of the params that were not available on the last dispatch attempt.
+    #[inline(always)]
+    pub fn set_unmet_params(&mut self, params: Vec<Requirement>) {
+        self.unmet_params = params;
+    }
+
+    /// Returns the requirements of the params that were not available on the last dispatch attempt.
+    #[inline(always)]
+    pub fn unmet_params(&self) -> &[Requirement] {
+        &self.unmet_params
+    }
+
+    /// Sets the event group that must be signalled before the component is dispatched.
+    #[inline(always)]
+    pub fn set_trigger(&mut self, event_group: efi::Guid) {
+        self.trigger = Some(event_group);
+    }
+
+    /// Returns the event group that must be signalled before the component is dispatched, if any.
+    #[inline(always)]
+    pub fn trigger(&self) -> Option<efi::Guid> {
+        self.trigger
+    }
+
     /// Returns mutable access to the param usage metadata for the component.
     #[inline(always)]
     pub(crate) fn access_mut(&mut self) -> &mut Access {
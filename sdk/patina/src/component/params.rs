@@ -88,6 +88,12 @@
 //! Once a config datum is locked, it cannot be unlocked, and no further components that have a [ConfigMut] parameter
 //! will be executed.
 //!
+//! ## `Protocol`
+//!
+//! The [Protocol] [Param] type lets a component wait on a UEFI protocol, typically one installed by a UEFI driver
+//! rather than produced as a service. The component is executed once the protocol is installed, with a reference to
+//! its interface. Wrap it in an [Option] to run regardless, and use the protocol only if it is already installed.
+//!
 //! ## License
 //!
 //! Copyright (c) Microsoft Corporation.
@@ -102,16 +108,17 @@ use core::{
     ops::{Deref, DerefMut},
 };
 
-use alloc::{borrow::Cow, boxed::Box};
+use alloc::{borrow::Cow, boxed::Box, vec::Vec};
 
 use crate::{
-    boot_services::StandardBootServices,
+    boot_services::{BootServices, StandardBootServices},
     component::{
         metadata::MetaData,
-        service::IntoService,
+        service::{IntoService, dispatch_report::Requirement},
         storage::{Deferred, Storage, UnsafeStorageCell},
     },
     runtime_services::StandardRuntimeServices,
+    uefi_protocol::ProtocolInterface,
 };
 
 use super::storage::ConfigRaw;
@@ -160,6 +167,22 @@ pub unsafe trait Param {
         }
     }
 
+    /// Returns what a component waits on while this parameter fails [validate](Param::validate).
+    ///
+    /// Parameters that wrap a service, config, HOB or protocol report it, named by the wrapped type. Others are
+    /// reported as [Requirement::Other], named by their own type.
+    fn requirement() -> Requirement {
+        Requirement::Other(core::any::type_name::<Self>().into())
+    }
+
+    /// Returns the [requirement](Param::requirement) of every parameter that fails [validate](Param::validate).
+    ///
+    /// Unlike [try_validate](Param::try_validate), which stops at the first failure, parameter tuples report all of
+    /// their unmet parameters.
+    fn unmet_params(state: &Self::State, storage: UnsafeStorageCell) -> Vec<Requirement> {
+        if Self::validate(state, storage) { Vec::new() } else { alloc::vec![Self::requirement()] }
+    }
+
     /// Initializes this Parameter's [State](Param::State).
     ///
     /// This is when the parameter should register its access requirements with the [MetaData]. See this module's
@@ -427,6 +450,10 @@ unsafe impl<T: Default + 'static> Param for Config<'_, T> {
         unsafe { storage.storage() }.get_raw_config(*state).is_locked()
     }
 
+    fn requirement() -> Requirement {
+        Requirement::Config(core::any::type_name::<T>().into())
+    }
+
     fn init_state(storage: &mut Storage, meta: &mut MetaData) -> Result<Self::State, Cow<'static, str>> {
         let id = storage.add_config_default_if_not_present::<T>();
 
@@ -532,6 +559,10 @@ unsafe impl<T: Default + 'static> Param for ConfigMut<'_, T> {
         !unsafe { storage.storage() }.get_raw_config(*state).is_locked()
     }
 
+    fn requirement() -> Requirement {
+        Requirement::Config(core::any::type_name::<T>().into())
+    }
+
     fn init_state(storage: &mut Storage, meta: &mut MetaData) -> Result<Self::State, Cow<'static, str>> {
         let id = storage.add_config_default_if_not_present::<T>();
         // All config is locked by default. We only unlock it (like below) when a component is detected that needs
@@ -711,6 +742,97 @@ unsafe impl Param for StandardRuntimeServices {
     }
 }
 
+/// A UEFI protocol installed in the protocol database, such as one produced by a UEFI driver.
+///
+/// A component with a `Protocol<P>` parameter is not executed until an instance of `P` is installed, and is then
+/// given the instance `LocateProtocol` returns. The core retries waiting components as protocols are installed, so no
+/// protocol notify event is needed.
+///
+/// The interface is shared with its producer and every other consumer, so it is only handed out by shared reference.
+/// State changes go through the functions the protocol itself provides.
+///
+/// ## Example
+///
+/// ``` rust
+/// use patina::{component::params::Protocol, error::Result};
+/// use r_efi::efi::protocols::device_path;
+///
+/// fn my_component(device_path: Protocol<device_path::Protocol>) -> Result<()> {
+///     log::info!("Device path type {}", device_path.r#type);
+///     Ok(())
+/// }
+/// ```
+pub struct Protocol<P: ProtocolInterface + 'static> {
+    interface: &'static P,
+}
+
+impl<P: ProtocolInterface + 'static> Protocol<P> {
+    /// Creates an instance of Protocol by leaking `interface`.
+    ///
+    /// This function is intended for testing purposes only. Dropping the returned value will cause a memory leak as
+    /// the underlying (leaked) interface cannot be deallocated.
+    ///
+    /// ## Example
+    /// ``` rust
+    /// use patina::component::params::Protocol;
+    /// use r_efi::efi::protocols::device_path;
+    ///
+    /// fn my_component_to_test(device_path: Protocol<device_path::Protocol>) {
+    ///     assert_eq!(device_path.r#type, device_path::TYPE_END);
+    /// }
+    ///
+    /// #[test]
+    /// fn test_my_component() {
+    ///     let end = device_path::Protocol { r#type: device_path::TYPE_END, sub_type: 0xff, length: [4, 0] };
+    ///     my_component_to_test(Protocol::mock(end));
+    /// }
+    /// ```
+    #[allow(clippy::test_attr_in_doctest)]
+    pub fn mock(interface: P) -> Self {
+        Protocol { interface: Box::leak(Box::new(interface)) }
+    }
+}
+
+impl<P: ProtocolInterface + 'static> Deref for Protocol<P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        self.interface
+    }
+}
+
+// SAFETY: Protocol parameters only read the protocol database through boot services and register no Storage access.
+unsafe impl<P: ProtocolInterface + 'static> Param for Protocol<P> {
+    type State = ();
+    type Item<'storage, 'state> = Self;
+
+    unsafe fn get_param<'storage, 'state>(
+        _state: &'state Self::State,
+        storage: UnsafeStorageCell<'storage>,
+    ) -> Self::Item<'storage, 'state> {
+        // SAFETY: Boot services are immutably borrowed from storage. The ProtocolInterface implementation guarantees
+        // the interface has the layout of `P`.
+        let interface = unsafe { storage.storage().boot_services().locate_protocol::<P>(None) };
+        Protocol { interface: interface.expect("Protocol parameter is validated before it is retrieved.") }
+    }
+
+    fn validate(_state: &Self::State, storage: UnsafeStorageCell) -> bool {
+        // Safety: Storage access is valid - UnsafeStorageCell ensures proper synchronization.
+        let boot_services = unsafe { storage.storage() }.boot_services();
+        // SAFETY: The interface pointer is only checked for presence, never dereferenced.
+        boot_services.is_init()
+            && unsafe { boot_services.locate_protocol_unchecked(&P::PROTOCOL_GUID, core::ptr::null_mut()) }.is_ok()
+    }
+
+    fn requirement() -> Requirement {
+        Requirement::Protocol(core::any::type_name::<P>().into())
+    }
+
+    fn init_state(_storage: &mut Storage, _meta: &mut MetaData) -> Result<Self::State, Cow<'static, str>> {
+        Ok(())
+    }
+}
+
 macro_rules! impl_component_param_tuple {
     ($($param: ident), *) => {
         #[allow(non_snake_case)]
@@ -741,6 +863,16 @@ macro_rules! impl_component_param_tuple {
                 Ok(())
             }
 
+            fn unmet_params(state: &Self::State, _storage: UnsafeStorageCell) -> Vec<Requirement> {
+                let ($($param,)*) = state;
+                #[allow(unused_mut)]
+                let mut unmet = Vec::new();
+                $(
+                    unmet.extend($param::unmet_params($param, _storage));
+                )*
+                unmet
+            }
+
             // This function is not used as we are overwriting the try_validate to call the individual param validate function
             // instead of this one.
             fn validate(_state: &Self::State, _storage: UnsafeStorageCell) -> bool {
@@ -951,6 +1083,402 @@ mod tests {
         let _ = unsafe { <StandardBootServices as Param>::get_param(&(), cell_storage) };
     }
 
+    #[repr(C)]
+    struct TestProtocol {
+        value: u32,
+    }
+
+    // SAFETY: Test code - the GUID is only ever installed with a TestProtocol interface.
+    unsafe impl ProtocolInterface for TestProtocol {
+        const PROTOCOL_GUID: r_efi::efi::Guid = r_efi::efi::Guid::from_fields(
+            0x7d2a4a4b,
+            0x3c1e,
+            0x4f0b,
+            0x9e,
+            0x1d,
+            &[0x5a, 0x37, 0x8c, 0x10, 0x42, 0x6e],
+        );
+    }
+
+    static INSTALLED_TEST_PROTOCOL: core::sync::atomic::AtomicPtr<TestProtocol> =
+        core::sync::atomic::AtomicPtr::new(core::ptr::null_mut());
+
+    extern "efiapi" fn mock_locate_protocol(
+        protocol: *mut r_efi::efi::Guid,
+        _registration: *mut core::ffi::c_void,
+        interface: *mut *mut core::ffi::c_void,
+    ) -> r_efi::efi::Status {
+        let installed = INSTALLED_TEST_PROTOCOL.load(core::sync::atomic::Ordering::SeqCst);
+        // SAFETY: Test code - the caller passes a valid GUID pointer.
+        if installed.is_null() || unsafe { *protocol } != TestProtocol::PROTOCOL_GUID {
+            return r_efi::efi::Status::NOT_FOUND;
+        }
+        // SAFETY: Test code - the caller passes a valid interface pointer.
+        unsafe { interface.write(installed.cast()) };
+        r_efi::efi::Status::SUCCESS
+    }
+
+    /// Builds a boot services table where every service but `LocateProtocol` reports `EFI_UNSUPPORTED`.
+    fn mock_efi_boot_services() -> &'static r_efi::efi::BootServices {
+        use core::ffi::c_void;
+        use r_efi::efi;
+
+        extern "efiapi" fn raise_tpl(_new_tpl: efi::Tpl) -> efi::Tpl {
+            efi::TPL_APPLICATION
+        }
+        extern "efiapi" fn restore_tpl(_old_tpl: efi::Tpl) {}
+        extern "efiapi" fn allocate_pages(
+            _type: u32,
+            _memory_type: u32,
+            _pages: usize,
+            _memory: *mut u64,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn free_pages(_memory: u64, _pages: usize) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn get_memory_map(
+            _memory_map_size: *mut usize,
+            _memory_map: *mut efi::MemoryDescriptor,
+            _map_key: *mut usize,
+            _descriptor_size: *mut usize,
+            _descriptor_version: *mut u32,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn allocate_pool(_pool_type: u32, _size: usize, _buffer: *mut *mut c_void) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn free_pool(_buffer: *mut c_void) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn create_event(
+            _event_type: u32,
+            _notify_tpl: efi::Tpl,
+            _notify_function: Option<efi::EventNotify>,
+            _notify_context: *mut c_void,
+            _event: *mut efi::Event,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn set_timer(_event: efi::Event, _type: u32, _trigger_time: u64) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn wait_for_event(
+            _number_of_events: usize,
+            _event: *mut efi::Event,
+            _index: *mut usize,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn event_service(_event: efi::Event) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn install_protocol_interface(
+            _handle: *mut efi::Handle,
+            _protocol: *mut efi::Guid,
+            _interface_type: u32,
+            _interface: *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn reinstall_protocol_interface(
+            _handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            _old_interface: *mut c_void,
+            _new_interface: *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn handle_protocol(
+            _handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            _interface: *mut *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn uninstall_protocol_interface(
+            _handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            _interface: *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn register_protocol_notify(
+            _protocol: *mut efi::Guid,
+            _event: efi::Event,
+            _registration: *mut *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn locate_handle(
+            _search_type: u32,
+            _protocol: *mut efi::Guid,
+            _search_key: *mut c_void,
+            _buffer_size: *mut usize,
+            _buffer: *mut efi::Handle,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn locate_device_path(
+            _protocol: *mut efi::Guid,
+            _device_path: *mut *mut efi::protocols::device_path::Protocol,
+            _device: *mut efi::Handle,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn install_configuration_table(_guid: *mut efi::Guid, _table: *mut c_void) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn load_image(
+            _boot_policy: efi::Boolean,
+            _parent_image_handle: efi::Handle,
+            _device_path: *mut efi::protocols::device_path::Protocol,
+            _source_buffer: *mut c_void,
+            _source_size: usize,
+            _image_handle: *mut efi::Handle,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn start_image(
+            _image_handle: efi::Handle,
+            _exit_data_size: *mut usize,
+            _exit_data: *mut *mut u16,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn exit(
+            _image_handle: efi::Handle,
+            _exit_status: efi::Status,
+            _exit_data_size: usize,
+            _exit_data: *mut u16,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn unload_image(_image_handle: efi::Handle) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn exit_boot_services(_image_handle: efi::Handle, _map_key: usize) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn get_next_monotonic_count(_count: *mut u64) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn stall(_microseconds: usize) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn set_watchdog_timer(
+            _timeout: usize,
+            _watchdog_code: u64,
+            _data_size: usize,
+            _watchdog_data: *mut u16,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn connect_controller(
+            _controller_handle: efi::Handle,
+            _driver_image_handle: *mut efi::Handle,
+            _remaining_device_path: *mut efi::protocols::device_path::Protocol,
+            _recursive: efi::Boolean,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn disconnect_controller(
+            _controller_handle: efi::Handle,
+            _driver_image_handle: efi::Handle,
+            _child_handle: efi::Handle,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn open_protocol(
+            _handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            _interface: *mut *mut c_void,
+            _agent_handle: efi::Handle,
+            _controller_handle: efi::Handle,
+            _attributes: u32,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn close_protocol(
+            _handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            _agent_handle: efi::Handle,
+            _controller_handle: efi::Handle,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn open_protocol_information(
+            _handle: efi::Handle,
+            _protocol: *mut efi::Guid,
+            _entry_buffer: *mut *mut efi::OpenProtocolInformationEntry,
+            _entry_count: *mut usize,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn protocols_per_handle(
+            _handle: efi::Handle,
+            _protocol_buffer: *mut *mut *mut efi::Guid,
+            _protocol_buffer_count: *mut usize,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn locate_handle_buffer(
+            _search_type: u32,
+            _protocol: *mut efi::Guid,
+            _search_key: *mut c_void,
+            _no_handles: *mut usize,
+            _buffer: *mut *mut efi::Handle,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn install_multiple_protocol_interfaces(
+            _handle: *mut efi::Handle,
+            _args: *mut c_void,
+            _more_args: *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn uninstall_multiple_protocol_interfaces(
+            _handle: efi::Handle,
+            _args: *mut c_void,
+            _more_args: *mut c_void,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn calculate_crc32(_data: *mut c_void, _data_size: usize, _crc32: *mut u32) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+        extern "efiapi" fn copy_mem(_destination: *mut c_void, _source: *mut c_void, _length: usize) {}
+        extern "efiapi" fn set_mem(_buffer: *mut c_void, _size: usize, _value: u8) {}
+        extern "efiapi" fn create_event_ex(
+            _event_type: u32,
+            _notify_tpl: efi::Tpl,
+            _notify_function: Option<efi::EventNotify>,
+            _notify_context: *const c_void,
+            _event_group: *const efi::Guid,
+            _event: *mut efi::Event,
+        ) -> efi::Status {
+            efi::Status::UNSUPPORTED
+        }
+
+        Box::leak(Box::new(efi::BootServices {
+            hdr: efi::TableHeader {
+                signature: efi::BOOT_SERVICES_SIGNATURE,
+                revision: efi::BOOT_SERVICES_REVISION,
+                header_size: core::mem::size_of::<efi::BootServices>() as u32,
+                crc32: 0,
+                reserved: 0,
+            },
+            raise_tpl,
+            restore_tpl,
+            allocate_pages,
+            free_pages,
+            get_memory_map,
+            allocate_pool,
+            free_pool,
+            create_event,
+            set_timer,
+            wait_for_event,
+            signal_event: event_service,
+            close_event: event_service,
+            check_event: event_service,
+            install_protocol_interface,
+            reinstall_protocol_interface,
+            uninstall_protocol_interface,
+            handle_protocol,
+            reserved: core::ptr::null_mut(),
+            register_protocol_notify,
+            locate_handle,
+            locate_device_path,
+            install_configuration_table,
+            load_image,
+            start_image,
+            exit,
+            unload_image,
+            exit_boot_services,
+            get_next_monotonic_count,
+            stall,
+            set_watchdog_timer,
+            connect_controller,
+            disconnect_controller,
+            open_protocol,
+            close_protocol,
+            open_protocol_information,
+            protocols_per_handle,
+            locate_handle_buffer,
+            locate_protocol: mock_locate_protocol,
+            install_multiple_protocol_interfaces,
+            uninstall_multiple_protocol_interfaces,
+            calculate_crc32,
+            copy_mem,
+            set_mem,
+            create_event_ex,
+        }))
+    }
+
+    #[test]
+    fn test_protocol_waits_until_installed() {
+        static SEEN: core::sync::atomic::AtomicU32 = core::sync::atomic::AtomicU32::new(0);
+
+        struct TestComponent;
+
+        #[component]
+        impl TestComponent {
+            fn entry_point(self, protocol: Protocol<TestProtocol>) -> Result<()> {
+                SEEN.store(protocol.value + 1, core::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let efi_bs = mock_efi_boot_services();
+
+        let mut storage = Storage::new();
+        assert!(!<Protocol<TestProtocol> as Param>::validate(&(), (&storage).into()));
+        storage.set_boot_services(StandardBootServices::new(efi_bs));
+
+        let mut component = TestComponent.into_component();
+        component.initialize(&mut storage);
+        assert_eq!(component.run(&mut storage), Ok(false));
+        assert_eq!(
+            component.metadata().unmet_params(),
+            [Requirement::Protocol("patina::component::params::tests::TestProtocol".into())]
+        );
+
+        let interface = Box::leak(Box::new(TestProtocol { value: 41 }));
+        INSTALLED_TEST_PROTOCOL.store(interface, core::sync::atomic::Ordering::SeqCst);
+        assert_eq!(component.run(&mut storage), Ok(true));
+        assert_eq!(SEEN.load(core::sync::atomic::Ordering::SeqCst), 42);
+    }
+
+    #[test]
+    fn test_params_report_what_they_wait_on() {
+        #[derive(crate::component::hob::FromHob, zerocopy_derive::FromBytes)]
+        #[hob = "12345678-1234-1234-1234-123456789012"]
+        #[repr(C)]
+        struct TestHob;
+
+        assert_eq!(
+            <crate::component::service::Service<dyn core::fmt::Debug> as Param>::requirement(),
+            Requirement::Service("dyn core::fmt::Debug".into())
+        );
+        assert_eq!(<Config<u32> as Param>::requirement(), Requirement::Config("u32".into()));
+        assert_eq!(<ConfigMut<u32> as Param>::requirement(), Requirement::Config("u32".into()));
+        assert_eq!(
+            <crate::component::hob::Hob<TestHob> as Param>::requirement(),
+            Requirement::Hob("patina::component::params::tests::test_params_report_what_they_wait_on::TestHob".into())
+        );
+        assert_eq!(
+            <Protocol<TestProtocol> as Param>::requirement(),
+            Requirement::Protocol("patina::component::params::tests::TestProtocol".into())
+        );
+        assert_eq!(
+            <StandardBootServices as Param>::requirement(),
+            Requirement::Other("patina::boot_services::StandardBootServices".into())
+        );
+    }
+
     #[test]
     fn test_runtime_services_fails_to_validate_when_null() {
         let mut storage = Storage::default(); // runtime_services is an empty pointer
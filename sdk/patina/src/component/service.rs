@@ -123,11 +123,15 @@ use core::{any::Any, cell::OnceCell, marker::PhantomData, ops::Deref};
 use crate::component::{
     metadata::MetaData,
     params::Param,
+    service::dispatch_report::Requirement,
     storage::{Storage, UnsafeStorageCell},
 };
 
+pub mod dispatch_report;
+pub mod firmware_volume;
 pub mod memory;
 pub mod perf_timer;
+pub mod runtime_table;
 
 pub use patina_macro::IntoService;
 
@@ -422,6 +426,10 @@ unsafe impl<T: ?Sized + 'static> Param for Service<T> {
         unsafe { storage.storage() }.get_raw_service(*state).is_some()
     }
 
+    fn requirement() -> Requirement {
+        Requirement::Service(core::any::type_name::<T>().into())
+    }
+
     fn init_state(storage: &mut Storage, _meta: &mut MetaData) -> Result<Self::State, Cow<'static, str>> {
         Ok(storage.register_service::<T>())
     }
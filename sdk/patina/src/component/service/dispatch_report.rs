@@ -0,0 +1,295 @@
+//! Dispatch Report Service Definitions.
+//!
+//! The core records the order in which Patina components and UEFI drivers are dispatched, and why the remaining ones
+//! were not: the params a component is waiting on, or the depex GUIDs a driver is waiting on. This module defines the
+//! [DispatchReport] built from those records, and the [DispatchReporter] service the core produces to query it.
+//!
+//! At ReadyToBoot the core also publishes the report as JSON in a configuration table named by
+//! [DISPATCH_REPORT_TABLE](crate::guids::DISPATCH_REPORT_TABLE), so that the reports of two boots can be diffed. The
+//! table starts with a [DispatchReportTableHeader] and is followed by the UTF-8 JSON text.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use patina::{
+//!     component::service::{Service, dispatch_report::{DispatchReporter, Requirement}},
+//!     error::Result,
+//! };
+//!
+//! fn component(reporter: Service<dyn DispatchReporter>) -> Result<()> {
+//!     for component in reporter.report().undispatched_components {
+//!         if component.unmet.iter().any(|requirement| matches!(requirement, Requirement::Hob(_))) {
+//!             log::warn!("{} is waiting on a HOB.", component.name);
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use r_efi::efi;
+
+use crate::{base::guid::Guid, error::EfiError};
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// Whether a [DispatchRecord] is for a Patina component or a UEFI driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchKind {
+    /// A Patina component, named by its type.
+    Component,
+    /// A UEFI driver, named by its FFS file GUID.
+    Driver,
+}
+
+/// One dispatch, in the order it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DispatchRecord {
+    /// What was dispatched.
+    pub kind: DispatchKind,
+    /// The component type name, or the driver file GUID.
+    pub name: String,
+    /// The performance counter value when the dispatch started, in ticks of [DispatchReport::perf_frequency].
+    pub timestamp: u64,
+    /// The status the entry point returned, or the load error of a driver that could not be loaded.
+    pub status: efi::Status,
+}
+
+/// A requirement that kept a component from being dispatched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    /// A service that was not produced, named by its type (for example `dyn my_crate::MyService`).
+    Service(String),
+    /// A config that was not available, or was locked while the component needed it mutably.
+    Config(String),
+    /// A HOB that was not in the HOB list.
+    Hob(String),
+    /// A UEFI protocol that was not installed, named by its interface type.
+    Protocol(String),
+    /// Any other param that was not available, or the reason the component could not be initialized.
+    Other(String),
+}
+
+impl Requirement {
+    fn kind(&self) -> &'static str {
+        match self {
+            Requirement::Service(_) => "service",
+            Requirement::Config(_) => "config",
+            Requirement::Hob(_) => "hob",
+            Requirement::Protocol(_) => "protocol",
+            Requirement::Other(_) => "other",
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Requirement::Service(name)
+            | Requirement::Config(name)
+            | Requirement::Hob(name)
+            | Requirement::Protocol(name)
+            | Requirement::Other(name) => name,
+        }
+    }
+}
+
+/// A component that was registered but not dispatched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndispatchedComponent {
+    /// The component type name.
+    pub name: String,
+    /// Every requirement that was not met on the last dispatch attempt.
+    pub unmet: Vec<Requirement>,
+}
+
+/// A UEFI driver that was discovered but not dispatched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndispatchedDriver {
+    /// The FFS file GUID of the driver.
+    pub file: efi::Guid,
+    /// The depex GUIDs that were not installed, or the driver a BEFORE or AFTER depex names.
+    pub unsatisfied: Vec<efi::Guid>,
+}
+
+/// The dispatch history of a boot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DispatchReport {
+    /// The frequency, in Hz, of the counter the timestamps are taken from.
+    pub perf_frequency: u64,
+    /// Every dispatch, in order.
+    pub dispatched: Vec<DispatchRecord>,
+    /// Components that were registered but not dispatched.
+    pub undispatched_components: Vec<UndispatchedComponent>,
+    /// Drivers that were discovered but not dispatched.
+    pub undispatched_drivers: Vec<UndispatchedDriver>,
+}
+
+impl DispatchReport {
+    /// Returns the report as a JSON object.
+    ///
+    /// GUIDs are formatted as `XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX` and statuses by name (for example `"Success"`
+    /// or `"NotFound"`), so that reports from two boots can be compared as text.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        let _ = write!(json, "{{\"perf_frequency\":{},\"dispatched\":[", self.perf_frequency);
+        for (i, record) in self.dispatched.iter().enumerate() {
+            let kind = match record.kind {
+                DispatchKind::Component => "component",
+                DispatchKind::Driver => "driver",
+            };
+            let status = match EfiError::status_to_result(record.status) {
+                Ok(()) => String::from("Success"),
+                Err(err) => format!("{err:?}"),
+            };
+            let _ = write!(
+                json,
+                "{}{{\"kind\":\"{kind}\",\"name\":{},\"timestamp\":{},\"status\":{}}}",
+                separator(i),
+                json_string(&record.name),
+                record.timestamp,
+                json_string(&status)
+            );
+        }
+        json.push_str("],\"undispatched_components\":[");
+        for (i, component) in self.undispatched_components.iter().enumerate() {
+            let _ = write!(json, "{}{{\"name\":{},\"unmet\":[", separator(i), json_string(&component.name));
+            for (j, requirement) in component.unmet.iter().enumerate() {
+                let _ = write!(
+                    json,
+                    "{}{{\"kind\":\"{}\",\"type\":{}}}",
+                    separator(j),
+                    requirement.kind(),
+                    json_string(requirement.name())
+                );
+            }
+            json.push_str("]}");
+        }
+        json.push_str("],\"undispatched_drivers\":[");
+        for (i, driver) in self.undispatched_drivers.iter().enumerate() {
+            let _ = write!(json, "{}{{\"file\":\"{}\",\"unsatisfied\":[", separator(i), Guid::from_ref(&driver.file));
+            for (j, guid) in driver.unsatisfied.iter().enumerate() {
+                let _ = write!(json, "{}\"{}\"", separator(j), Guid::from_ref(guid));
+            }
+            json.push_str("]}");
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+fn separator(index: usize) -> &'static str {
+    if index == 0 { "" } else { "," }
+}
+
+/// Quotes and escapes `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Header of the [DISPATCH_REPORT_TABLE](crate::guids::DISPATCH_REPORT_TABLE) configuration table.
+///
+/// Followed by `length` bytes of UTF-8 JSON, as returned by [DispatchReport::to_json].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DispatchReportTableHeader {
+    /// The version of the table, [DispatchReportTableHeader::VERSION].
+    pub version: u32,
+    /// The length of the JSON text that follows the header, in bytes.
+    pub length: u32,
+}
+
+impl DispatchReportTableHeader {
+    /// The current version of the table.
+    pub const VERSION: u32 = 1;
+}
+
+/// Access to the dispatch report of the current boot.
+///
+/// Produced by the core.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait DispatchReporter {
+    /// Returns the report as of the last completed dispatch pass.
+    fn report(&self) -> DispatchReport;
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn report_is_serialized_as_json() {
+        let guid =
+            efi::Guid::from_fields(0x52f1dbb3, 0x08f5, 0x4895, 0x99, 0x78, &[0x18, 0x59, 0xa4, 0x60, 0x07, 0xe5]);
+        let report = DispatchReport {
+            perf_frequency: 1000,
+            dispatched: vec![
+                DispatchRecord {
+                    kind: DispatchKind::Component,
+                    name: "a::B".into(),
+                    timestamp: 5,
+                    status: efi::Status::SUCCESS,
+                },
+                DispatchRecord {
+                    kind: DispatchKind::Driver,
+                    name: "\"quoted\"\n".into(),
+                    timestamp: 7,
+                    status: efi::Status::NOT_FOUND,
+                },
+            ],
+            undispatched_components: vec![UndispatchedComponent {
+                name: "a::C".into(),
+                unmet: vec![Requirement::Service("dyn a::S".into()), Requirement::Hob("a::H".into())],
+            }],
+            undispatched_drivers: vec![
+                UndispatchedDriver { file: guid, unsatisfied: vec![guid, guid] },
+                UndispatchedDriver { file: guid, unsatisfied: vec![] },
+            ],
+        };
+
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"{"perf_frequency":1000,"dispatched":["#,
+                r#"{"kind":"component","name":"a::B","timestamp":5,"status":"Success"},"#,
+                r#"{"kind":"driver","name":"\"quoted\"\n","timestamp":7,"status":"NotFound"}],"#,
+                r#""undispatched_components":[{"name":"a::C","unmet":["#,
+                r#"{"kind":"service","type":"dyn a::S"},{"kind":"hob","type":"a::H"}]}],"#,
+                r#""undispatched_drivers":["#,
+                r#"{"file":"52F1DBB3-08F5-4895-9978-1859A46007E5","unsatisfied":["#,
+                r#""52F1DBB3-08F5-4895-9978-1859A46007E5","52F1DBB3-08F5-4895-9978-1859A46007E5"]},"#,
+                r#"{"file":"52F1DBB3-08F5-4895-9978-1859A46007E5","unsatisfied":[]}]}"#,
+            )
+        );
+        assert_eq!(
+            DispatchReport::default().to_json(),
+            r#"{"perf_frequency":0,"dispatched":[],"undispatched_components":[],"undispatched_drivers":[]}"#
+        );
+        assert_eq!(json_string("\u{1}"), "\"\\u0001\"");
+    }
+}
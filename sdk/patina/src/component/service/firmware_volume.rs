@@ -0,0 +1,121 @@
+//! Firmware Volume Service Definitions.
+//!
+//! The core produces the Firmware Volume (FV) protocol for every firmware volume it dispatches from. This module
+//! defines the [FirmwareVolumes] service the core produces alongside it, so that components can query the same
+//! volume information without going through the protocol's `GetInfo` method.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use patina::{
+//!     component::service::{Service, firmware_volume::FirmwareVolumes},
+//!     error::Result,
+//! };
+//!
+//! fn component(volumes: Service<dyn FirmwareVolumes>) -> Result<()> {
+//!     for volume in volumes.volumes() {
+//!         log::info!("FV at {:#x}: {} of {} bytes free.", volume.base_address, volume.free_space, volume.size);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+
+use r_efi::efi;
+
+use crate::pi::fw_fs::{
+    fv::BlockMapEntry,
+    fvb::attributes::{EfiFvbAttributes2, raw::fvb2},
+};
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// Information about a firmware volume the core produces the FV protocol for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareVolumeInfo {
+    /// Physical address of the firmware volume.
+    pub base_address: u64,
+    /// Length of the firmware volume, in bytes.
+    pub size: u64,
+    /// Current attributes of the firmware volume.
+    pub attributes: EfiFvbAttributes2,
+    /// File system GUID from the firmware volume header.
+    pub file_system_guid: efi::Guid,
+    /// Name of the firmware volume from its extended header, if it has one.
+    pub fv_name: Option<efi::Guid>,
+    /// The extended header (`EFI_FIRMWARE_VOLUME_EXT_HEADER` and its entries), if the volume has one.
+    pub ext_header: Option<Vec<u8>>,
+    /// The block map of the firmware volume, without the terminating entry.
+    pub block_map: Vec<BlockMapEntry>,
+    /// Bytes available for new files at the end of the firmware volume.
+    pub free_space: u64,
+}
+
+impl FirmwareVolumeInfo {
+    /// Returns whether files can be written to the firmware volume.
+    pub fn is_writable(&self) -> bool {
+        self.attributes & fvb2::WRITE_STATUS != 0
+    }
+}
+
+/// Access to the firmware volumes installed by the core.
+///
+/// Produced by the core.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait FirmwareVolumes {
+    /// Returns the firmware volumes the core produces the FV protocol for, in installation order.
+    fn volumes(&self) -> Vec<FirmwareVolumeInfo>;
+
+    /// Returns the firmware volume whose extended header names it `fv_name`, if any.
+    fn volume(&self, fv_name: efi::Guid) -> Option<FirmwareVolumeInfo> {
+        self.volumes().into_iter().find(|volume| volume.fv_name == Some(fv_name))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    struct TestVolumes(Vec<FirmwareVolumeInfo>);
+
+    impl FirmwareVolumes for TestVolumes {
+        fn volumes(&self) -> Vec<FirmwareVolumeInfo> {
+            self.0.clone()
+        }
+    }
+
+    fn info(fv_name: Option<efi::Guid>, attributes: EfiFvbAttributes2) -> FirmwareVolumeInfo {
+        FirmwareVolumeInfo {
+            base_address: 0x1000,
+            size: 0x2000,
+            attributes,
+            file_system_guid: efi::Guid::from_fields(0, 0, 0, 0, 0, &[0; 6]),
+            fv_name,
+            ext_header: None,
+            block_map: vec![BlockMapEntry { num_blocks: 2, length: 0x1000 }],
+            free_space: 0x100,
+        }
+    }
+
+    #[test]
+    fn test_volume_finds_volume_by_name() {
+        let name = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6; 6]);
+        let volumes = TestVolumes(vec![info(None, fvb2::READ_STATUS), info(Some(name), fvb2::WRITE_STATUS)]);
+
+        let volume = volumes.volume(name).expect("named volume should be found");
+        assert_eq!(volume.fv_name, Some(name));
+        assert!(volume.is_writable());
+        assert!(!volumes.volumes()[0].is_writable());
+        assert!(volumes.volume(efi::Guid::from_fields(7, 8, 9, 10, 11, &[12; 6])).is_none());
+    }
+}
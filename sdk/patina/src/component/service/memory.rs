@@ -284,6 +284,25 @@ pub trait MemoryManager {
     fn get_page_attributes(&self, address: usize, page_count: usize) -> Result<(AccessType, CachingType), MemoryError>;
 }
 
+impl dyn MemoryManager {
+    /// Moves `value` to `EfiRuntimeServicesData` pages and leaks them.
+    ///
+    /// The OS does not reclaim runtime services memory after `ExitBootServices()`, so this is where state read by
+    /// runtime services belongs. The value is never dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MemoryError::NoAvailableMemory`] if the pages could not be allocated.
+    pub fn leak_in_runtime_memory<T>(&self, value: T) -> Result<&'static mut T, MemoryError> {
+        self.allocate_pages(
+            size_of::<T>().max(1).div_ceil(UEFI_PAGE_SIZE),
+            AllocationOptions::new().with_memory_type(EfiMemoryType::RuntimeServicesData),
+        )?
+        .leak_as(value)
+        .ok_or(MemoryError::NoAvailableMemory)
+    }
+}
+
 /// The `AllocationOptions` structure allows for the caller to  specify
 /// additional constraints on the allocation. This can be used to specify the type
 /// of memory to allocate, alignment requirements, and allocation strategy. Users
@@ -941,6 +960,24 @@ mod tests {
         assert!(service.get_page_attributes(0, 5).is_err());
     }
 
+    #[test]
+    fn test_leak_in_runtime_memory() {
+        let mut mock = MockMemoryManager::new();
+        mock.expect_allocate_pages()
+            .withf(|&page_count, options| {
+                page_count == 2 && options.memory_type() == EfiMemoryType::RuntimeServicesData
+            })
+            .returning(|page_count, options| StdMemoryManager::new().allocate_pages(page_count, options));
+        let memory_manager: &dyn MemoryManager = &mock;
+        let value = memory_manager.leak_in_runtime_memory([7u8; UEFI_PAGE_SIZE + 1]).unwrap();
+        assert!(value.iter().all(|&byte| byte == 7));
+
+        let mut mock = MockMemoryManager::new();
+        mock.expect_allocate_pages().returning(|_, _| Err(MemoryError::NoAvailableMemory));
+        let memory_manager: &dyn MemoryManager = &mock;
+        assert!(matches!(memory_manager.leak_in_runtime_memory(0u64), Err(MemoryError::NoAvailableMemory)));
+    }
+
     #[test]
     fn test_error_to_efi_error_conversion_not_changed() {
         // enumerate all the errors and ensure they convert to the expected EFI error codes.
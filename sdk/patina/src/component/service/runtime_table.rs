@@ -0,0 +1,146 @@
+//! Runtime Services Table Service Definitions.
+//!
+//! The core owns the UEFI Runtime Services table and fills every entry with a stub that returns
+//! `EFI_NOT_AVAILABLE_YET` until a producer provides a real implementation. This module defines the
+//! [RuntimeServicesTable] service that producers (such as a variable store or a real time clock component) use to
+//! publish their entries into that table.
+//!
+//! Entries are installed in groups that map to the architectural protocols of the PI specification, so that a single
+//! producer always owns a complete set of related entries. Installing a group also recalculates the table CRC32, so
+//! producers do not need to touch the system table directly.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use patina::{
+//!     component::service::{Service, runtime_table::{RuntimeServiceEntries, RuntimeServicesTable, MonotonicCounterEntries}},
+//!     error::Result,
+//! };
+//! use r_efi::efi;
+//!
+//! extern "efiapi" fn get_next_high_mono_count(count: *mut u32) -> efi::Status {
+//!     efi::Status::UNSUPPORTED
+//! }
+//!
+//! fn component(rt_table: Service<dyn RuntimeServicesTable>) -> Result<()> {
+//!     rt_table.install(RuntimeServiceEntries::MonotonicCounter(MonotonicCounterEntries { get_next_high_mono_count }))
+//! }
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use r_efi::efi;
+
+use crate::error::EfiError;
+
+#[cfg(any(test, feature = "mockall"))]
+use mockall::automock;
+
+/// Variable service entries of the runtime services table.
+///
+/// Corresponds to the entries produced alongside the Variable and Variable Write architectural protocols.
+#[derive(Debug, Clone, Copy)]
+pub struct VariableEntries {
+    /// `GetVariable()` implementation.
+    pub get_variable: efi::RuntimeGetVariable,
+    /// `GetNextVariableName()` implementation.
+    pub get_next_variable_name: efi::RuntimeGetNextVariableName,
+    /// `SetVariable()` implementation.
+    pub set_variable: efi::RuntimeSetVariable,
+    /// `QueryVariableInfo()` implementation.
+    pub query_variable_info: efi::RuntimeQueryVariableInfo,
+}
+
+/// Time service entries of the runtime services table.
+///
+/// Corresponds to the entries produced alongside the Real Time Clock architectural protocol.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeEntries {
+    /// `GetTime()` implementation.
+    pub get_time: efi::RuntimeGetTime,
+    /// `SetTime()` implementation.
+    pub set_time: efi::RuntimeSetTime,
+    /// `GetWakeupTime()` implementation.
+    pub get_wakeup_time: efi::RuntimeGetWakeupTime,
+    /// `SetWakeupTime()` implementation.
+    pub set_wakeup_time: efi::RuntimeSetWakeupTime,
+}
+
+/// Reset service entry of the runtime services table.
+///
+/// Corresponds to the entry produced alongside the Reset architectural protocol.
+#[derive(Debug, Clone, Copy)]
+pub struct ResetEntries {
+    /// `ResetSystem()` implementation.
+    pub reset_system: efi::RuntimeResetSystem,
+}
+
+/// Capsule service entries of the runtime services table.
+///
+/// Corresponds to the entries produced alongside the Capsule architectural protocol.
+#[derive(Debug, Clone, Copy)]
+pub struct CapsuleEntries {
+    /// `UpdateCapsule()` implementation.
+    pub update_capsule: efi::RuntimeUpdateCapsule,
+    /// `QueryCapsuleCapabilities()` implementation.
+    pub query_capsule_capabilities: efi::RuntimeQueryCapsuleCapabilities,
+}
+
+/// Monotonic counter service entry of the runtime services table.
+///
+/// Corresponds to the entry produced alongside the Monotonic Counter architectural protocol.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicCounterEntries {
+    /// `GetNextHighMonotonicCount()` implementation.
+    pub get_next_high_mono_count: efi::RuntimeGetNextHighMonoCount,
+}
+
+/// A group of runtime services table entries that are installed together.
+#[derive(Debug, Clone, Copy)]
+pub enum RuntimeServiceEntries {
+    /// Variable services.
+    Variable(VariableEntries),
+    /// Time services.
+    Time(TimeEntries),
+    /// Reset services.
+    Reset(ResetEntries),
+    /// Capsule services.
+    Capsule(CapsuleEntries),
+    /// Monotonic counter services.
+    MonotonicCounter(MonotonicCounterEntries),
+}
+
+impl RuntimeServiceEntries {
+    /// Returns a human readable name for the group, used for logging.
+    pub fn name(&self) -> &'static str {
+        match self {
+            RuntimeServiceEntries::Variable(_) => "Variable",
+            RuntimeServiceEntries::Time(_) => "Time",
+            RuntimeServiceEntries::Reset(_) => "Reset",
+            RuntimeServiceEntries::Capsule(_) => "Capsule",
+            RuntimeServiceEntries::MonotonicCounter(_) => "Monotonic Counter",
+        }
+    }
+}
+
+/// Provides write access to the entries of the UEFI Runtime Services table.
+///
+/// This service is produced by the core.
+#[cfg_attr(any(test, feature = "mockall"), automock)]
+pub trait RuntimeServicesTable {
+    /// Installs a group of entries into the runtime services table and recalculates the table checksum.
+    ///
+    /// Every function pointer provided must remain valid for the remainder of boot, including after
+    /// `ExitBootServices()`, so they should only reference code and data in runtime memory.
+    ///
+    /// # Errors
+    ///
+    /// - [EfiError::AlreadyStarted] if the group has already been installed by another producer.
+    /// - [EfiError::NotReady] if the runtime services table has not been created yet.
+    /// - [EfiError::WriteProtected] if the table has already been converted by `SetVirtualAddressMap()`.
+    fn install(&self, entries: RuntimeServiceEntries) -> Result<(), EfiError>;
+}
@@ -29,7 +29,14 @@ use super::{
     service::{IntoService, Service},
 };
 
-type HobParsers = BTreeMap<OwnedGuid, BTreeMap<TypeId, fn(&[u8], &mut Storage)>>;
+/// Identifies the HOBs a parser is registered for: GUIDed HOBs by their GUID, all other HOBs by their type.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum HobKey {
+    Guid(OwnedGuid),
+    Type(u16),
+}
+
+type HobParsers = BTreeMap<HobKey, BTreeMap<TypeId, fn(&[u8], &mut Storage)>>;
 
 /// A vector whose elements are sparsely populated.
 #[derive(Debug)]
@@ -374,7 +381,11 @@ impl Storage {
     }
 
     pub(crate) fn add_hob_parser<T: FromHob>(&mut self) {
-        self.hob_parsers.entry(T::HOB_GUID).or_default().insert(TypeId::of::<T>(), T::register);
+        let key = match T::HOB_TYPE {
+            crate::pi::hob::GUID_EXTENSION => HobKey::Guid(T::HOB_GUID),
+            hob_type => HobKey::Type(hob_type),
+        };
+        self.hob_parsers.entry(key).or_default().insert(TypeId::of::<T>(), T::register);
     }
 
     /// Registers a HOB with the storage and returns its global id.
@@ -418,7 +429,16 @@ impl Storage {
 
     /// Attempts to retrieve a HOB parser from the storage.
     pub fn get_hob_parsers(&self, guid: &OwnedGuid) -> Vec<fn(&[u8], &mut Storage)> {
-        self.hob_parsers.get(guid).map(|type_map| type_map.values().copied().collect()).unwrap_or_default()
+        self.parsers(&HobKey::Guid(guid.clone()))
+    }
+
+    /// Attempts to retrieve the parsers of a HOB type that is not GUIDed, such as `EFI_HOB_TYPE_UEFI_CAPSULE`.
+    pub fn get_hob_type_parsers(&self, hob_type: u16) -> Vec<fn(&[u8], &mut Storage)> {
+        self.parsers(&HobKey::Type(hob_type))
+    }
+
+    fn parsers(&self, key: &HobKey) -> Vec<fn(&[u8], &mut Storage)> {
+        self.hob_parsers.get(key).map(|type_map| type_map.values().copied().collect()).unwrap_or_default()
     }
 }
 
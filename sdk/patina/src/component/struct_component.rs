@@ -32,6 +32,15 @@ use crate::{
     error::Result,
 };
 use core::marker::PhantomData;
+use r_efi::efi;
+
+/// Event groups that cannot trigger a component.
+///
+/// They are signalled while ExitBootServices is running, when the memory map must no longer change. A component would
+/// be dispatched with boot services that allocate memory, so a component with one of these triggers fails to
+/// initialize instead.
+const UNSUPPORTED_TRIGGERS: [efi::Guid; 2] =
+    [efi::EVENT_GROUP_BEFORE_EXIT_BOOT_SERVICES, efi::EVENT_GROUP_EXIT_BOOT_SERVICES];
 
 /// A [Component] implementation for Structs who specify a function whose parameters implement [Param].
 pub struct StructComponent<Marker, Func>
@@ -60,6 +69,15 @@ where
             _marker: PhantomData,
         }
     }
+
+    /// Defers the component until the event group `event_group` has been signalled.
+    ///
+    /// This is set by `#[component(event = ...)]`. The ExitBootServices and BeforeExitBootServices event groups are
+    /// not supported, and a component triggered by either fails to initialize.
+    pub fn with_trigger(mut self, event_group: efi::Guid) -> Self {
+        self.metadata.set_trigger(event_group);
+        self
+    }
 }
 
 impl<Marker, In, Func> Component for StructComponent<Marker, Func>
@@ -79,6 +97,7 @@ where
 
         if let Err(bad_param) = Func::Param::try_validate(param_state, storage) {
             self.metadata.set_error_message(bad_param);
+            self.metadata.set_unmet_params(Func::Param::unmet_params(param_state, storage));
             return Ok(false);
         }
 
@@ -101,6 +120,14 @@ where
 
     /// One-time initialization of the Component. Should set [Access](super::metadata::Access) requirements.
     fn initialize(&mut self, _storage: &mut Storage) -> bool {
+        if let Some(trigger) = self.metadata.trigger()
+            && UNSUPPORTED_TRIGGERS.contains(&trigger)
+        {
+            self.metadata.set_error_message(
+                alloc::format!("event group {} cannot trigger a component", crate::Guid::from_ref(&trigger)).into(),
+            );
+            return false;
+        }
         match Func::Param::init_state(_storage, &mut self.metadata) {
             Ok(param_state) => {
                 self.param_state = Some(param_state);
@@ -121,6 +148,7 @@ mod tests {
     use crate::component::{
         IntoComponent, component,
         params::{Config, ConfigMut},
+        service::dispatch_report::Requirement,
     };
 
     use alloc::borrow::Cow;
@@ -162,6 +190,25 @@ mod tests {
         }
     }
 
+    pub trait TestService {}
+
+    #[allow(dead_code)]
+    pub struct TestStructManyUnmet {
+        pub x: i32,
+    }
+
+    #[component]
+    impl TestStructManyUnmet {
+        fn entry_point(
+            self,
+            _cfg: Config<i32>,
+            _service: crate::component::service::Service<dyn TestService>,
+            _cfg_mut: ConfigMut<u32>,
+        ) -> crate::error::Result<()> {
+            Ok(())
+        }
+    }
+
     #[allow(dead_code)]
     pub struct TestStructFail {
         pub x: i32,
@@ -212,6 +259,74 @@ mod tests {
         assert!(test_struct.run(&mut storage).is_err_and(|res| res == crate::error::EfiError::NotReady));
     }
 
+    #[test]
+    fn test_unmet_params_lists_every_unavailable_param() {
+        let mut storage = crate::component::storage::Storage::new();
+
+        let mut test_struct = TestStructManyUnmet { x: 5 }.into_component();
+        test_struct.initialize(&mut storage);
+        storage.lock_configs();
+        assert!(test_struct.run(&mut storage).is_ok_and(|res| !res));
+        assert_eq!(
+            test_struct.metadata().error_message(),
+            Some(Cow::from(
+                "patina::component::service::Service<dyn patina::component::struct_component::tests::TestService>"
+            ))
+        );
+        assert_eq!(
+            test_struct.metadata().unmet_params(),
+            [
+                Requirement::Service("dyn patina::component::struct_component::tests::TestService".into()),
+                Requirement::Config("u32".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exit_boot_services_triggers_are_rejected() {
+        struct TestComponent;
+
+        #[component(event = r_efi::efi::EVENT_GROUP_EXIT_BOOT_SERVICES)]
+        impl TestComponent {
+            fn entry_point(self) -> crate::error::Result<()> {
+                Ok(())
+            }
+        }
+
+        struct TestBeforeComponent;
+
+        #[component(event = r_efi::efi::EVENT_GROUP_BEFORE_EXIT_BOOT_SERVICES)]
+        impl TestBeforeComponent {
+            fn entry_point(self) -> crate::error::Result<()> {
+                Ok(())
+            }
+        }
+
+        struct TestReadyToBootComponent;
+
+        #[component(event = r_efi::efi::EVENT_GROUP_READY_TO_BOOT)]
+        impl TestReadyToBootComponent {
+            fn entry_point(self) -> crate::error::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut storage = crate::component::storage::Storage::new();
+
+        let mut component = TestComponent.into_component();
+        assert!(!component.initialize(&mut storage));
+        assert_eq!(
+            component.metadata().error_message(),
+            Some(Cow::from("event group 27ABF055-B1B8-4C26-8048-748F37BAA2DF cannot trigger a component"))
+        );
+
+        let mut component = TestBeforeComponent.into_component();
+        assert!(!component.initialize(&mut storage));
+
+        let mut component = TestReadyToBootComponent.into_component();
+        assert!(component.initialize(&mut storage));
+    }
+
     //Test structs that use generics and where clause
     struct GenericStruct<T>
     where
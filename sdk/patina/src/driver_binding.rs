@@ -236,6 +236,9 @@ impl<T: DriverBinding + 'static, U: BootServices + 'static> UefiDriverBinding<T,
     }
 
     /// Install the driver binding.
+    ///
+    /// If the driver binding handle is null, the protocol is installed on a new handle, which becomes the driver
+    /// binding handle, and also the image handle if that is null too.
     pub fn install(&mut self) -> Result<(), efi::Status> {
         let Self::Uninstalled(uefi_driver_binding) = self else {
             // Already installed.
@@ -243,7 +246,7 @@ impl<T: DriverBinding + 'static, U: BootServices + 'static> UefiDriverBinding<T,
         };
 
         // SAFETY: This is safe because _UefiDriverBinding interface is compliant to the expected interface of driver binding guid.
-        unsafe {
+        let handle = unsafe {
             uefi_driver_binding.boot_services.install_protocol_interface_unchecked(
                 Some(uefi_driver_binding.driver_binding_protocol.driver_binding_handle),
                 &efi::protocols::driver_binding::PROTOCOL_GUID,
@@ -252,6 +255,14 @@ impl<T: DriverBinding + 'static, U: BootServices + 'static> UefiDriverBinding<T,
             )
         }?;
 
+        let protocol = &mut uefi_driver_binding.driver_binding_protocol;
+        if protocol.driver_binding_handle.is_null() {
+            protocol.driver_binding_handle = handle;
+            if protocol.image_handle.is_null() {
+                protocol.image_handle = handle;
+            }
+        }
+
         let metadata = Box::metadata(uefi_driver_binding);
         match mem::replace(self, Self::Installed(metadata)) {
             UefiDriverBinding::Uninstalled(uefi_driver_binding) => _ = Box::leak(uefi_driver_binding),
@@ -285,6 +296,21 @@ impl<T: DriverBinding + 'static, U: BootServices + 'static> UefiDriverBinding<T,
         Ok(())
     }
 
+    /// Returns the handle the driver binding protocol is installed on, which drivers use as the agent handle when
+    /// they open protocols on the controllers they manage.
+    pub fn driver_binding_handle(&self) -> efi::Handle {
+        match self {
+            UefiDriverBinding::Uninstalled(uefi_driver_binding) => {
+                uefi_driver_binding.driver_binding_protocol.driver_binding_handle
+            }
+            UefiDriverBinding::Installed(ptr_metadata) => {
+                // SAFETY: The pointer behind this metadata has been leaked in install and is still valid.
+                let uefi_driver_binding = unsafe { &*(ptr_metadata.ptr_value as *const _UefiDriverBinding<T, U>) };
+                uefi_driver_binding.driver_binding_protocol.driver_binding_handle
+            }
+        }
+    }
+
     /// Returned weather or not the driver binding is installed.
     pub fn is_installed(&self) -> bool {
         match self {
@@ -393,6 +419,38 @@ mod tests {
         assert!(uefi_driver_binding.is_installed());
     }
 
+    #[test]
+    fn test_install_driver_binding_on_new_handle() {
+        const NEW_HANDLE: efi::Handle = 67890_usize as efi::Handle;
+
+        static mut BOOT_SERVICES_INIT: MaybeUninit<MockBootServices> = MaybeUninit::uninit();
+        // SAFETY: Test code - initializing static MaybeUninit with a mock boot services instance.
+        unsafe {
+            let mut mock_boot_services = MockBootServices::new();
+            mock_boot_services
+                .expect_install_protocol_interface_unchecked()
+                .once()
+                .withf_st(|handle, _, _| handle == &Some(ptr::null_mut()))
+                .return_const_st(Ok(NEW_HANDLE));
+
+            ptr::write(BOOT_SERVICES_INIT.as_mut_ptr(), mock_boot_services);
+        }
+        // SAFETY: Test code - BOOT_SERVICES_INIT was initialized in the unsafe block above.
+        static BOOT_SERVICES: &MockBootServices = unsafe { BOOT_SERVICES_INIT.assume_init_ref() };
+
+        let driver = MockDriverBinding::new();
+        let mut uefi_driver_binding = UefiDriverBinding::new(driver, ptr::null_mut(), BOOT_SERVICES);
+        assert!(uefi_driver_binding.driver_binding_handle().is_null());
+        uefi_driver_binding.install().unwrap();
+        assert_eq!(NEW_HANDLE, uefi_driver_binding.driver_binding_handle());
+
+        let UefiDriverBinding::Installed(ptr_metadata) = &uefi_driver_binding else { panic!("Not installed.") };
+        // SAFETY: Test code - the driver binding was leaked by install.
+        let interface =
+            unsafe { &*(ptr_metadata.ptr_value as *const _UefiDriverBinding<MockDriverBinding, MockBootServices>) };
+        assert_eq!(NEW_HANDLE, interface.driver_binding_protocol.image_handle);
+    }
+
     #[test]
     fn test_uninstall_driver_binding() {
         const TEST_HANDLE: efi::Handle = 12345_usize as efi::Handle;
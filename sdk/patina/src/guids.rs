@@ -21,6 +21,19 @@ use r_efi::efi;
 pub const CACHE_ATTRIBUTE_CHANGE_EVENT_GROUP: efi::Guid =
     efi::Guid::from_fields(0xb8e477c7, 0x26a9, 0x4b9a, 0xa7, 0xc9, &[0x5f, 0x8f, 0x1f, 0x3d, 0x9c, 0x7b]);
 
+/// Dispatch Report Table GUID
+///
+/// The GUID of the configuration table that the DXE Core installs at ReadyToBoot with the JSON dispatch report of
+/// the boot. See [DispatchReport](crate::component::service::dispatch_report::DispatchReport) for its layout.
+///
+/// (`52F1DBB3-08F5-4895-9978-1859A46007E5`)
+/// ```
+/// # use patina::{Guid, guids::DISPATCH_REPORT_TABLE};
+/// # assert_eq!("52F1DBB3-08F5-4895-9978-1859A46007E5", format!("{:?}", Guid::from_ref(&DISPATCH_REPORT_TABLE)));
+/// ```
+pub const DISPATCH_REPORT_TABLE: efi::Guid =
+    efi::Guid::from_fields(0x52f1dbb3, 0x08f5, 0x4895, 0x99, 0x78, &[0x18, 0x59, 0xa4, 0x60, 0x07, 0xe5]);
+
 /// DXE Core Module GUID
 ///
 /// The FFS file GUID for the DXE Core module. Interfaces that depend upon a module GUID such as the Memory Allocation
@@ -72,6 +72,12 @@ pub mod guid {
     /// GUID for Tiano decompression sections.
     pub const TIANO_DECOMPRESS_SECTION: efi::Guid =
         efi::Guid::from_fields(0xA31280AD, 0x481E, 0x41B6, 0x95, 0xE8, &[0x12, 0x7F, 0x4C, 0x98, 0x47, 0x79]);
+    /// GUID for sections signed with an RSA2048/SHA256 certificate block (`EFI_CERT_TYPE_RSA2048_SHA256_GUID`).
+    pub const RSA2048_SHA256_SECTION: efi::Guid =
+        efi::Guid::from_fields(0xA7717414, 0xC616, 0x4977, 0x94, 0x20, &[0x84, 0x47, 0x12, 0xA7, 0x35, 0xBF]);
+    /// GUID for sections signed with a PKCS7 `WIN_CERTIFICATE_UEFI_GUID` (`EFI_CERT_TYPE_PKCS7_GUID`).
+    pub const PKCS7_SECTION: efi::Guid =
+        efi::Guid::from_fields(0x4AAFD29D, 0x68DF, 0x49EE, 0x8A, 0xA9, &[0x34, 0x7D, 0x37, 0x56, 0x65, 0xA7]);
 }
 
 /// Defines an interface that can be implemented to provide extraction logic for encapsulation sections.
@@ -25,3 +25,8 @@ pub const EFI_FIRMWARE_FILE_SYSTEM3_GUID: efi::Guid =
 /// GUID for the file at the top of a firmware volume
 pub const EFI_FFS_VOLUME_TOP_FILE_GUID: efi::Guid =
     efi::Guid::from_fields(0x1ba0062e, 0xc779, 0x4582, 0x85, 0x66, &[0x33, 0x6a, 0xe8, 0xf7, 0x8f, 0x9]);
+
+// {FC510EE7-FFDC-11D4-BD41-0080C73C8881}
+/// GUID of the a priori file, which lists the DXE drivers of a firmware volume to dispatch first
+pub const EFI_APRIORI_GUID: efi::Guid =
+    efi::Guid::from_fields(0xfc510ee7, 0xffdc, 0x11d4, 0xbd, 0x41, &[0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81]);
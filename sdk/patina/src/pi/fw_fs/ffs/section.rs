@@ -67,6 +67,21 @@ pub mod raw_type {
     pub const FFS_MAX: u8 = 0xFF;
 }
 
+/// Authentication status bits reported by section extraction, as passed to the Security Architectural Protocol.
+/// Note: Typically called `EFI_AUTH_STATUS_*` in EDK II code.
+pub mod auth_status {
+    /// The platform has overridden the authentication of the section.
+    pub const PLATFORM_OVERRIDE: u32 = 0x01;
+    /// The section is signed.
+    pub const IMAGE_SIGNED: u32 = 0x02;
+    /// The signature of the section has not been verified.
+    pub const NOT_TESTED: u32 = 0x04;
+    /// The signature of the section failed verification.
+    pub const TEST_FAILED: u32 = 0x08;
+    /// Mask of all the authentication status bits.
+    pub const ALL: u32 = 0x0f;
+}
+
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 #[cfg_attr(test, derive(serde::Deserialize))]
@@ -171,6 +186,10 @@ pub mod header {
         pub attributes: u16,
         // Guid-specific header fields.
     }
+    /// GuidDefined attribute: the section content must be processed by the extractor for the GUID before use.
+    pub const GUIDED_SECTION_PROCESSING_REQUIRED: u16 = 0x01;
+    /// GuidDefined attribute: the section carries authentication data that the extractor verifies.
+    pub const GUIDED_SECTION_AUTH_STATUS_VALID: u16 = 0x02;
 
     /// EFI_VERSION_SECTION per PI spec 1.8A 3.2.5.15
     #[repr(C)]
@@ -10,6 +10,7 @@
 //!
 
 pub mod bds;
+pub mod capsule;
 pub mod communication;
 pub mod communication2;
 pub mod communication3;
@@ -17,9 +18,13 @@ pub mod cpu_arch;
 pub mod firmware_volume;
 pub mod firmware_volume_block;
 pub mod metronome;
+pub mod real_time_clock;
+pub mod reset;
 pub mod runtime;
 pub mod security;
 pub mod security2;
 pub mod status_code;
 pub mod timer;
+pub mod variable;
+pub mod variable_write;
 pub mod watchdog;
@@ -0,0 +1,22 @@
+//! Capsule Architectural Protocol
+//!
+//! Installed by the producer of the `UpdateCapsule()` and `QueryCapsuleCapabilities()` runtime services once they are
+//! available. The protocol has no interface; only its presence is meaningful.
+//!
+//! See <https://uefi.org/specs/PI/1.8A/V2_DXE_Architectural_Protocols.html#capsule-architectural-protocol>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use r_efi::efi;
+
+/// Capsule Architectural Protocol GUID
+///
+/// # Documentation
+/// UEFI Platform Initialization Specification, Release 1.8, Volume 2, Capsule Architectural Protocol
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x5053697e, 0x2cbc, 0x4819, 0x90, 0xd9, &[0x05, 0x80, 0xde, 0xee, 0x57, 0x54]);
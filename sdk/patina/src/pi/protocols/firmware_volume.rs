@@ -35,17 +35,59 @@ pub const PROTOCOL_GUID: Guid =
 /// Enumeration of write policies for firmware volume operations.
 pub type EfiFvWritePolicy = u32;
 
+/// Files may be left partially written if the write is interrupted.
+pub const EFI_FV_UNRELIABLE_WRITE: EfiFvWritePolicy = 0x00000000;
+/// Either all of the files are written, or none of them are.
+pub const EFI_FV_RELIABLE_WRITE: EfiFvWritePolicy = 0x00000001;
+
 #[repr(C)]
 /// Data structure for writing files to firmware volumes.
 ///
 /// Contains the metadata and content information needed to write a file
 /// to a firmware volume, including GUID, type, attributes, and data buffer.
 pub struct EfiFvWriteFileData {
-    name_guid: *mut Guid,
-    file_type: EfiFvFileType,
-    file_attributes: EfiFvFileAttributes,
-    buffer: *mut c_void,
-    buffer_size: u32,
+    /// Name of the file to write.
+    pub name_guid: *mut Guid,
+    /// FFS file type of the file to write.
+    pub file_type: EfiFvFileType,
+    /// Attributes of the file to write.
+    pub file_attributes: EfiFvFileAttributes,
+    /// File content; a zero-length buffer deletes the file.
+    pub buffer: *mut c_void,
+    /// Size of `buffer` in bytes.
+    pub buffer_size: u32,
+}
+
+/// Information type GUID for [EfiFirmwareVolumeInfo], as passed to [GetInfo].
+///
+/// The FV protocol also supports the `EFI_FILE_SYSTEM_INFO` information type
+/// (`r_efi::efi::protocols::file::SYSTEM_INFO_ID`).
+pub const FIRMWARE_VOLUME_INFO_ID: Guid =
+    Guid::from_fields(0x36d9c771, 0x4592, 0x4327, 0x85, 0x69, &[0x77, 0xa2, 0x14, 0xd8, 0xb0, 0xb2]);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+/// Information about a firmware volume, returned by [GetInfo] for [FIRMWARE_VOLUME_INFO_ID].
+///
+/// The structure is followed by `ext_header_size` bytes holding the volume's extended header
+/// (`EFI_FIRMWARE_VOLUME_EXT_HEADER` and its entries), if it has one. `size` covers both.
+pub struct EfiFirmwareVolumeInfo {
+    /// Size of this structure and the extended header that follows it, in bytes.
+    pub size: u64,
+    /// Physical address of the firmware volume.
+    pub base_address: u64,
+    /// Length of the firmware volume, in bytes.
+    pub fv_length: u64,
+    /// Current attributes of the firmware volume.
+    pub attributes: EfiFvAttributes,
+    /// Bytes available for new files at the end of the firmware volume.
+    pub free_space: u64,
+    /// File system GUID from the firmware volume header.
+    pub file_system_guid: Guid,
+    /// Name of the firmware volume from its extended header, or all zeroes if it has none.
+    pub fv_name: Guid,
+    /// Size of the extended header that follows this structure, or zero if the volume has none.
+    pub ext_header_size: u32,
 }
 
 /// Retrieves the current attributes and current settings of the firmware volume.
@@ -62,6 +62,9 @@ pub type Read = extern "efiapi" fn(*mut Protocol, Lba, usize, *mut usize, *mut c
 /// operation may be performed on all the blocks in the firmware volume.
 pub type Write = extern "efiapi" fn(*mut Protocol, Lba, usize, *mut usize, *mut c_void) -> Status;
 
+/// Terminates the variable argument list of [`EraseBlocks`].
+pub const EFI_LBA_LIST_TERMINATOR: Lba = 0xFFFF_FFFF_FFFF_FFFF;
+
 /// Erases and initializes specified firmware volume blocks.
 ///
 /// The variable argument list is a list of tuples that specify logical block addresses and
@@ -0,0 +1,22 @@
+//! Reset Architectural Protocol
+//!
+//! Installed by the producer of the `ResetSystem()` runtime service once it is available. The protocol has no
+//! interface; only its presence is meaningful.
+//!
+//! See <https://uefi.org/specs/PI/1.8A/V2_DXE_Architectural_Protocols.html#reset-architectural-protocol>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use r_efi::efi;
+
+/// Reset Architectural Protocol GUID
+///
+/// # Documentation
+/// UEFI Platform Initialization Specification, Release 1.8, Volume 2, Reset Architectural Protocol
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x27cfac88, 0x46cc, 0x11d4, 0x9a, 0x38, &[0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d]);
@@ -54,7 +54,7 @@ pub type EfiTimerNotify = extern "efiapi" fn(time: u64);
 ///   previously registered.
 /// * @retval - EFI_DEVICE_ERROR: The timer handler could not be registered.
 pub type EfiTimerRegisterHandler =
-    extern "efiapi" fn(this: *mut Protocol, notify_function: EfiTimerNotify) -> efi::Status;
+    extern "efiapi" fn(this: *mut Protocol, notify_function: Option<EfiTimerNotify>) -> efi::Status;
 
 /// This function adjusts the period of timer interrupts to the value specified
 /// by TimerPeriod.  If the timer period is updated, then the selected timer
@@ -0,0 +1,23 @@
+//! Variable Architectural Protocol
+//!
+//! Installed by the producer of the `GetVariable()`, `GetNextVariableName()`, `SetVariable()` and
+//! `QueryVariableInfo()` runtime services once they are available for read access. The protocol has no interface;
+//! only its presence is meaningful.
+//!
+//! See <https://uefi.org/specs/PI/1.8A/V2_DXE_Architectural_Protocols.html#variable-architectural-protocol>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use r_efi::efi;
+
+/// Variable Architectural Protocol GUID
+///
+/// # Documentation
+/// UEFI Platform Initialization Specification, Release 1.8, Section II-12.11.1
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x1e5668e2, 0x8481, 0x11d4, 0xbc, 0xf1, &[0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81]);
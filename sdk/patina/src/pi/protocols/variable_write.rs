@@ -0,0 +1,22 @@
+//! Variable Write Architectural Protocol
+//!
+//! Installed by the producer of the variable runtime services once `SetVariable()` can write non-volatile variables.
+//! The protocol has no interface; only its presence is meaningful.
+//!
+//! See <https://uefi.org/specs/PI/1.8A/V2_DXE_Architectural_Protocols.html#variable-write-architectural-protocol>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use r_efi::efi;
+
+/// Variable Write Architectural Protocol GUID
+///
+/// # Documentation
+/// UEFI Platform Initialization Specification, Release 1.8, Section II-12.12.1
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x6441f818, 0x6362, 0x4e44, 0xb5, 0x70, &[0x7d, 0xba, 0x31, 0xdd, 0x24, 0x53]);
@@ -26,11 +26,12 @@ pub const PROTOCOL_GUID: efi::Guid =
 /// Function type definition for watchdog timer notify.
 pub type WatchdogTimerNotify = extern "efiapi" fn(u64);
 
-/// Registers a handler that is to be invoked when the watchdog timer fires.
+/// Registers a handler that is to be invoked when the watchdog timer fires. A `None` (NULL) handler unregisters the
+/// current one.
 ///
 /// # Documentation
 /// UEFI Platform Initialization Specification, Release 1.8, Section II-12.14.2
-pub type RegisterHandler = extern "efiapi" fn(*const Protocol, WatchdogTimerNotify) -> efi::Status;
+pub type RegisterHandler = extern "efiapi" fn(*const Protocol, Option<WatchdogTimerNotify>) -> efi::Status;
 
 /// Sets the amount of time in the future to fire the watchdog timer.
 ///
@@ -15,6 +15,12 @@ extern crate alloc;
 /// Variable-services-specific structs and utilities
 pub mod variable_services;
 
+/// Authenticated variable descriptors and Secure Boot signature lists
+pub mod authenticated_variables;
+
+/// A list that never allocates, for state read by runtime services
+pub mod fixed_vec;
+
 #[cfg(any(test, feature = "mockall"))]
 use mockall::automock;
 
@@ -25,6 +31,21 @@ use spin::Once;
 use r_efi::efi;
 use variable_services::{GetVariableStatus, VariableInfo};
 
+/// The mapping the OS sets up with `SetVirtualAddressMap()`.
+///
+/// Passed to the code that holds physical addresses, such as the registers of a device, when the OS switches to
+/// virtual addressing. A closure over [RuntimeServices::convert_pointer] is the usual implementation.
+pub trait VirtualAddressMap {
+    /// Returns the virtual address of the physical `address`, or `None` if the OS did not map it.
+    fn convert(&self, address: usize) -> Option<usize>;
+}
+
+impl<F: Fn(usize) -> Option<usize>> VirtualAddressMap for F {
+    fn convert(&self, address: usize) -> Option<usize> {
+        self(address)
+    }
+}
+
 /// The UEFI spec runtime services.
 /// Wrapper around [`efi::RuntimeServices`]
 ///
@@ -137,6 +158,31 @@ pub trait RuntimeServices {
         unsafe { self.set_variable_unchecked(name_vec.as_mut_slice(), namespace, attributes, data.as_ref()) }
     }
 
+    /// Sets a UEFI variable with `EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS`.
+    ///
+    /// Prepends an `EFI_VARIABLE_AUTHENTICATION_2` descriptor built from `timestamp` and `signed_data` to `data` and
+    /// writes it through [set_variable](RuntimeServices::set_variable). `signed_data` is the DER encoded, detached
+    /// PKCS#7 `SignedData` over the message returned by [signed_message](authenticated_variables::signed_message)
+    /// for the same name, namespace, attributes, timestamp and data. An empty `data` deletes the variable.
+    ///
+    /// UEFI Spec Documentation: [8.2.2. Using the EFI_VARIABLE_AUTHENTICATION_2 descriptor](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#using-the-efi-variable-authentication-2-descriptor)
+    ///
+    fn set_authenticated_variable(
+        &self,
+        name: &[u16],
+        namespace: &efi::Guid,
+        attributes: u32,
+        timestamp: &efi::Time,
+        signed_data: &[u8],
+        data: &[u8],
+    ) -> Result<(), efi::Status> {
+        let descriptor = authenticated_variables::Authentication2 { timestamp: *timestamp, signed_data };
+        let mut buffer = Vec::with_capacity(descriptor.size() + data.len());
+        descriptor.write(&mut buffer);
+        buffer.extend_from_slice(data);
+        self.set_variable(name, namespace, attributes | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS, &buffer)
+    }
+
     /// Gets a UEFI variable.
     ///
     /// Returns a tuple of (data, attributes)
@@ -268,6 +314,28 @@ pub trait RuntimeServices {
     ///
     fn query_variable_info(&self, attributes: u32) -> Result<VariableInfo, efi::Status>;
 
+    /// Gets the current time.
+    ///
+    /// The default implementation returns `EFI_UNSUPPORTED`, for implementations that do not keep time.
+    ///
+    /// UEFI Spec Documentation: [8.3.1. EFI_RUNTIME_SERVICES.GetTime()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#gettime)
+    ///
+    fn get_time(&self) -> Result<efi::Time, efi::Status> {
+        Err(efi::Status::UNSUPPORTED)
+    }
+
+    /// Returns the virtual address that the physical `address` is mapped at after `SetVirtualAddressMap()`.
+    ///
+    /// Only valid in the notification function of an `EVT_SIGNAL_VIRTUAL_ADDRESS_CHANGE` event. The default
+    /// implementation returns `EFI_UNSUPPORTED`, for implementations without virtual addressing.
+    ///
+    /// UEFI Spec Documentation: [8.4.2. EFI_RUNTIME_SERVICES.ConvertPointer()](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#convertpointer)
+    ///
+    fn convert_pointer(&self, address: usize) -> Result<usize, efi::Status> {
+        let _ = address;
+        Err(efi::Status::UNSUPPORTED)
+    }
+
     /// Set's a UEFI variable
     ///
     /// # Safety
@@ -448,6 +516,32 @@ impl RuntimeServices for StandardRuntimeServices {
 
         if status.is_error() { Err(status) } else { Ok(var_info) }
     }
+
+    fn get_time(&self) -> Result<efi::Time, efi::Status> {
+        let get_time = self.efi_runtime_services().get_time;
+        if get_time as usize == 0 {
+            debug_assert!(false, "GetTime has not initialized in the Runtime Services Table.");
+            return Err(efi::Status::NOT_FOUND);
+        }
+
+        let mut time = efi::Time::default();
+        let status = get_time(ptr::addr_of_mut!(time), ptr::null_mut());
+
+        if status.is_error() { Err(status) } else { Ok(time) }
+    }
+
+    fn convert_pointer(&self, address: usize) -> Result<usize, efi::Status> {
+        let convert_pointer = self.efi_runtime_services().convert_pointer;
+        if convert_pointer as usize == 0 {
+            debug_assert!(false, "ConvertPointer has not initialized in the Runtime Services Table.");
+            return Err(efi::Status::NOT_FOUND);
+        }
+
+        let mut pointer = address as *mut c_void;
+        let status = convert_pointer(0, ptr::addr_of_mut!(pointer));
+
+        if status.is_error() { Err(status) } else { Ok(pointer as usize) }
+    }
 }
 
 #[cfg(test)]
@@ -491,11 +585,26 @@ pub(crate) mod test {
 
     pub const DUMMY_DATA: u32 = 0xDEADBEEF;
     pub const DUMMY_DATA_REPR_SIZE: usize = mem::size_of::<u32>();
+    pub const DUMMY_SIGNED_DATA: [u8; 4] = [0x30, 0x82, 0x01, 0x02];
 
     pub const DUMMY_MAXIMUM_VARIABLE_STORAGE_SIZE: u64 = 0x11111111_11111111;
     pub const DUMMY_REMAINING_VARIABLE_STORAGE_SIZE: u64 = 0x22222222_22222222;
     pub const DUMMY_MAXIMUM_VARIABLE_SIZE: u64 = 0x33333333_33333333;
 
+    pub const DUMMY_TIME: efi::Time = efi::Time {
+        year: 2024,
+        month: 6,
+        day: 15,
+        hour: 18,
+        minute: 30,
+        second: 5,
+        pad1: 0,
+        nanosecond: 0,
+        timezone: efi::UNSPECIFIED_TIMEZONE,
+        daylight: 0,
+        pad2: 0,
+    };
+
     #[derive(Debug)]
     pub struct DummyVariableType {
         pub value: u32,
@@ -601,6 +710,33 @@ pub(crate) mod test {
         efi::Status::SUCCESS
     }
 
+    /// Mocks SetVariable() from UEFI spec for a time based authenticated write
+    ///
+    /// Expects to be passed DUMMY_FIRST_NAME with an `EFI_VARIABLE_AUTHENTICATION_2` descriptor carrying
+    /// DUMMY_SIGNED_DATA in front of DUMMY_DATA.
+    ///
+    pub extern "efiapi" fn mock_efi_set_authenticated_variable(
+        name: *mut u16,
+        _namespace: *mut efi::Guid,
+        attributes: u32,
+        data_size: usize,
+        data: *mut c_void,
+    ) -> efi::Status {
+        // SAFETY: Test code - reading test function parameters to simulate UEFI variable behavior.
+        unsafe {
+            assert!(DUMMY_FIRST_NAME.iter().enumerate().all(|(i, &c)| *name.add(i) == c));
+            assert_eq!(attributes, DUMMY_ATTRIBUTES | efi::VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS);
+
+            let data = slice::from_raw_parts(data as *const u8, data_size);
+            let (descriptor, payload) = authenticated_variables::Authentication2::parse(data).unwrap();
+            assert_eq!(descriptor.signed_data, DUMMY_SIGNED_DATA);
+            assert_eq!(descriptor.timestamp.year, 2025);
+            assert_eq!(payload, DUMMY_DATA.to_ne_bytes());
+        }
+
+        efi::Status::SUCCESS
+    }
+
     /// Mocks GetNextVariableName() from UEFI spec
     ///
     /// Will mock a list of two variables:
@@ -699,6 +835,37 @@ pub(crate) mod test {
         efi::Status::SUCCESS
     }
 
+    /// Mocks GetTime() from UEFI spec
+    ///
+    /// Returns DUMMY_TIME.
+    ///
+    pub extern "efiapi" fn mock_efi_get_time(
+        time: *mut efi::Time,
+        _capabilities: *mut efi::TimeCapabilities,
+    ) -> efi::Status {
+        // SAFETY: Test code - writing test data to the output parameter.
+        unsafe { *time = DUMMY_TIME };
+
+        efi::Status::SUCCESS
+    }
+
+    /// Mocks ConvertPointer() from UEFI spec
+    ///
+    /// Moves addresses below 0x1000 up by 0x8000_0000, and rejects the others.
+    ///
+    pub extern "efiapi" fn mock_efi_convert_pointer(
+        _debug_disposition: usize,
+        address: *mut *mut c_void,
+    ) -> efi::Status {
+        // SAFETY: Test code - the wrapper passes a valid pointer.
+        let pointer = unsafe { &mut *address };
+        if *pointer as usize >= 0x1000 {
+            return efi::Status::NOT_FOUND;
+        }
+        *pointer = (*pointer as usize + 0x8000_0000) as *mut c_void;
+        efi::Status::SUCCESS
+    }
+
     #[test]
     fn test_debug_print_works_before_init() {
         let rs: StandardRuntimeServices = StandardRuntimeServices::new_uninit();
@@ -782,6 +949,23 @@ pub(crate) mod test {
         assert!(status.is_ok());
     }
 
+    #[test]
+    fn test_set_authenticated_variable() {
+        let rs = runtime_services!(set_variable = mock_efi_set_authenticated_variable);
+
+        let timestamp = efi::Time { year: 2025, month: 1, day: 1, ..Default::default() };
+        let status = rs.set_authenticated_variable(
+            &DUMMY_FIRST_NAME,
+            &DUMMY_FIRST_NAMESPACE,
+            DUMMY_ATTRIBUTES,
+            &timestamp,
+            &DUMMY_SIGNED_DATA,
+            &DUMMY_DATA.to_ne_bytes(),
+        );
+
+        assert!(status.is_ok());
+    }
+
     #[test]
     fn test_set_variable_non_terminated() {
         let rs = runtime_services!(set_variable = mock_efi_set_variable);
@@ -894,4 +1078,22 @@ pub(crate) mod test {
         assert!(status.is_err());
         assert_eq!(status.unwrap_err(), efi::Status::INVALID_PARAMETER);
     }
+
+    #[test]
+    fn test_get_time() {
+        let rs = runtime_services!(get_time = mock_efi_get_time);
+
+        let time = rs.get_time().unwrap();
+
+        assert_eq!((time.year, time.month, time.day), (2024, 6, 15));
+        assert_eq!((time.hour, time.minute, time.second), (18, 30, 5));
+    }
+
+    #[test]
+    fn test_convert_pointer() {
+        let rs = runtime_services!(convert_pointer = mock_efi_convert_pointer);
+
+        assert_eq!(rs.convert_pointer(0x234), Ok(0x8000_0234));
+        assert_eq!(rs.convert_pointer(0x2000), Err(efi::Status::NOT_FOUND));
+    }
 }
@@ -0,0 +1,459 @@
+//! Authenticated variable formats
+//!
+//! Definitions for writing variables with the `EFI_VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS` attribute and for
+//! the signature database formats used by the Secure Boot policy variables (`PK`, `KEK`, `db`, `dbx`, `dbt`, `dbr`).
+//!
+//! A time based authenticated write passes an `EFI_VARIABLE_AUTHENTICATION_2` descriptor in front of the variable
+//! data. The descriptor carries a timestamp and a detached PKCS#7 `SignedData` over the [signed_message] of the write.
+//!
+//! UEFI Spec Documentation:
+//! - [8.2.2. Using the EFI_VARIABLE_AUTHENTICATION_2 descriptor](https://uefi.org/specs/UEFI/2.10/08_Services_Runtime_Services.html#using-the-efi-variable-authentication-2-descriptor)
+//! - [32.4.1. Signature Database](https://uefi.org/specs/UEFI/2.10/32_Secure_Boot_and_Driver_Signing.html#signature-database)
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+use r_efi::efi;
+
+/// `EFI_GLOBAL_VARIABLE`, the namespace of the `PK` and `KEK` variables and the Secure Boot mode variables.
+pub const GLOBAL_VARIABLE_GUID: efi::Guid =
+    efi::Guid::from_fields(0x8be4df61, 0x93ca, 0x11d2, 0xaa, 0x0d, &[0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c]);
+
+/// `EFI_IMAGE_SECURITY_DATABASE_GUID`, the namespace of the `db`, `dbx`, `dbt` and `dbr` variables.
+pub const IMAGE_SECURITY_DATABASE_GUID: efi::Guid =
+    efi::Guid::from_fields(0xd719b2cb, 0x3d3a, 0x4596, 0xa3, 0xbc, &[0xda, 0xd0, 0x0e, 0x67, 0x65, 0x6f]);
+
+/// `EFI_CERT_TYPE_PKCS7_GUID`, the certificate type of an `EFI_VARIABLE_AUTHENTICATION_2` descriptor.
+pub const CERT_TYPE_PKCS7_GUID: efi::Guid =
+    efi::Guid::from_fields(0x4aafd29d, 0x68df, 0x49ee, 0x8a, 0xa9, &[0x34, 0x7d, 0x37, 0x56, 0x65, 0xa7]);
+
+/// `WIN_CERT_TYPE_EFI_GUID`
+pub const WIN_CERT_TYPE_EFI_GUID: u16 = 0x0ef1;
+
+/// `WIN_CERTIFICATE` revision 2.0.
+pub const WIN_CERT_REVISION: u16 = 0x0200;
+
+/// Signature types (`EFI_CERT_*_GUID`) used in `EFI_SIGNATURE_LIST` entries.
+pub mod signature_type {
+    use r_efi::efi;
+
+    /// `EFI_CERT_SHA1_GUID`: a SHA-1 digest.
+    pub const SHA1: efi::Guid =
+        efi::Guid::from_fields(0x826ca512, 0xcf10, 0x4ac9, 0xb1, 0x87, &[0xbe, 0x01, 0x49, 0x66, 0x31, 0xbd]);
+    /// `EFI_CERT_SHA256_GUID`: a SHA-256 digest.
+    pub const SHA256: efi::Guid =
+        efi::Guid::from_fields(0xc1c41626, 0x504c, 0x4092, 0xac, 0xa9, &[0x41, 0xf9, 0x36, 0x93, 0x43, 0x28]);
+    /// `EFI_CERT_SHA384_GUID`: a SHA-384 digest.
+    pub const SHA384: efi::Guid =
+        efi::Guid::from_fields(0xff3e5307, 0x9fd0, 0x48c9, 0x85, 0xf1, &[0x8a, 0xd5, 0x6c, 0x70, 0x1e, 0x01]);
+    /// `EFI_CERT_SHA512_GUID`: a SHA-512 digest.
+    pub const SHA512: efi::Guid =
+        efi::Guid::from_fields(0x093e0fae, 0xa6c4, 0x4f50, 0x9f, 0x1b, &[0xd4, 0x1e, 0x2b, 0x89, 0xc1, 0x9a]);
+    /// `EFI_CERT_RSA2048_GUID`: an RSA-2048 public key modulus.
+    pub const RSA2048: efi::Guid =
+        efi::Guid::from_fields(0x3c5766e8, 0x269c, 0x4e34, 0xaa, 0x14, &[0xed, 0x77, 0x6e, 0x85, 0xb3, 0xb6]);
+    /// `EFI_CERT_X509_GUID`: a DER encoded X.509 certificate.
+    pub const X509: efi::Guid =
+        efi::Guid::from_fields(0xa5c059a1, 0x94e4, 0x4aa7, 0x87, 0xb5, &[0xab, 0x15, 0x5c, 0x2b, 0xf0, 0x72]);
+    /// `EFI_CERT_X509_SHA256_GUID`: the SHA-256 digest of an X.509 TBSCertificate and a revocation time.
+    pub const X509_SHA256: efi::Guid =
+        efi::Guid::from_fields(0x3bd2a492, 0x96c0, 0x4079, 0xb4, 0x20, &[0xfc, 0xf9, 0x8e, 0xf1, 0x03, 0xed]);
+    /// `EFI_CERT_X509_SHA384_GUID`: the SHA-384 digest of an X.509 TBSCertificate and a revocation time.
+    pub const X509_SHA384: efi::Guid =
+        efi::Guid::from_fields(0x7076876e, 0x80c2, 0x4ee6, 0xaa, 0xd2, &[0x28, 0xb3, 0x49, 0xa6, 0x86, 0x5b]);
+    /// `EFI_CERT_X509_SHA512_GUID`: the SHA-512 digest of an X.509 TBSCertificate and a revocation time.
+    pub const X509_SHA512: efi::Guid =
+        efi::Guid::from_fields(0x446dbf63, 0x2502, 0x4cda, 0xbc, 0xfa, &[0x24, 0x65, 0xd2, 0xb0, 0xfe, 0x9d]);
+
+    /// Returns the size of the signature data (excluding the owner GUID) for signature types with a fixed size.
+    ///
+    /// Returns `None` for [X509], whose entries are variable sized, and for unknown types.
+    pub fn fixed_data_size(signature_type: &efi::Guid) -> Option<usize> {
+        // EFI_TIME is appended to the digest of the X509 hash types.
+        const TIME: usize = 16;
+        match *signature_type {
+            t if t == SHA1 => Some(20),
+            t if t == SHA256 => Some(32),
+            t if t == SHA384 => Some(48),
+            t if t == SHA512 => Some(64),
+            t if t == RSA2048 => Some(256),
+            t if t == X509_SHA256 => Some(32 + TIME),
+            t if t == X509_SHA384 => Some(48 + TIME),
+            t if t == X509_SHA512 => Some(64 + TIME),
+            _ => None,
+        }
+    }
+}
+
+/// Size of `EFI_TIME`.
+const TIME_SIZE: usize = size_of::<efi::Time>();
+/// Size of `WIN_CERTIFICATE_UEFI_GUID` without the certificate data.
+const WIN_CERTIFICATE_UEFI_GUID_SIZE: usize = 8 + 16;
+/// Size of `EFI_SIGNATURE_LIST` without the header and signatures.
+const SIGNATURE_LIST_SIZE: usize = 16 + 4 + 4 + 4;
+/// Size of the owner GUID that starts each `EFI_SIGNATURE_DATA`.
+const SIGNATURE_OWNER_SIZE: usize = 16;
+
+fn time_to_bytes(time: &efi::Time) -> [u8; TIME_SIZE] {
+    let mut bytes = [0u8; TIME_SIZE];
+    bytes[0..2].copy_from_slice(&time.year.to_le_bytes());
+    bytes[2] = time.month;
+    bytes[3] = time.day;
+    bytes[4] = time.hour;
+    bytes[5] = time.minute;
+    bytes[6] = time.second;
+    bytes[7] = time.pad1;
+    bytes[8..12].copy_from_slice(&time.nanosecond.to_le_bytes());
+    bytes[12..14].copy_from_slice(&time.timezone.to_le_bytes());
+    bytes[14] = time.daylight;
+    bytes[15] = time.pad2;
+    bytes
+}
+
+fn time_from_bytes(bytes: &[u8; TIME_SIZE]) -> efi::Time {
+    efi::Time {
+        year: u16::from_le_bytes([bytes[0], bytes[1]]),
+        month: bytes[2],
+        day: bytes[3],
+        hour: bytes[4],
+        minute: bytes[5],
+        second: bytes[6],
+        pad1: bytes[7],
+        nanosecond: u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+        timezone: i16::from_le_bytes([bytes[12], bytes[13]]),
+        daylight: bytes[14],
+        pad2: bytes[15],
+    }
+}
+
+/// A parsed `EFI_VARIABLE_AUTHENTICATION_2` descriptor.
+#[derive(Debug, Clone, Copy)]
+pub struct Authentication2<'a> {
+    /// Time of the write. Only the date and time fields may be set; the remaining fields must be zero.
+    pub timestamp: efi::Time,
+    /// DER encoded, detached PKCS#7 `SignedData` over the [signed_message] of the write.
+    pub signed_data: &'a [u8],
+}
+
+impl<'a> Authentication2<'a> {
+    /// Splits `data` into the descriptor and the variable payload that follows it.
+    ///
+    /// Returns `SECURITY_VIOLATION` if the descriptor is truncated, is not a PKCS#7 `WIN_CERTIFICATE_UEFI_GUID`, or
+    /// has a timestamp with any of the pad, nanosecond, time zone or daylight fields set.
+    pub fn parse(data: &'a [u8]) -> Result<(Self, &'a [u8]), efi::Status> {
+        let header_end = TIME_SIZE + WIN_CERTIFICATE_UEFI_GUID_SIZE;
+        if data.len() < header_end {
+            return Err(efi::Status::SECURITY_VIOLATION);
+        }
+        let timestamp = time_from_bytes(data[..TIME_SIZE].try_into().expect("Slice is TIME_SIZE bytes."));
+        let cert = &data[TIME_SIZE..];
+        let length = u32::from_le_bytes([cert[0], cert[1], cert[2], cert[3]]) as usize;
+        let revision = u16::from_le_bytes([cert[4], cert[5]]);
+        let certificate_type = u16::from_le_bytes([cert[6], cert[7]]);
+        let cert_type = efi::Guid::from_bytes(cert[8..24].try_into().expect("Slice is 16 bytes."));
+
+        if revision != WIN_CERT_REVISION
+            || certificate_type != WIN_CERT_TYPE_EFI_GUID
+            || cert_type != CERT_TYPE_PKCS7_GUID
+            || length <= WIN_CERTIFICATE_UEFI_GUID_SIZE
+            || length > cert.len()
+        {
+            return Err(efi::Status::SECURITY_VIOLATION);
+        }
+        if timestamp.pad1 != 0
+            || timestamp.nanosecond != 0
+            || timestamp.timezone != 0
+            || timestamp.daylight != 0
+            || timestamp.pad2 != 0
+        {
+            return Err(efi::Status::SECURITY_VIOLATION);
+        }
+
+        let signed_data = &cert[WIN_CERTIFICATE_UEFI_GUID_SIZE..length];
+        Ok((Self { timestamp, signed_data }, &cert[length..]))
+    }
+
+    /// Size of the serialized descriptor.
+    pub fn size(&self) -> usize {
+        TIME_SIZE + WIN_CERTIFICATE_UEFI_GUID_SIZE + self.signed_data.len()
+    }
+
+    /// Appends the serialized descriptor to `out`.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let length = (WIN_CERTIFICATE_UEFI_GUID_SIZE + self.signed_data.len()) as u32;
+        out.extend_from_slice(&time_to_bytes(&self.timestamp));
+        out.extend_from_slice(&length.to_le_bytes());
+        out.extend_from_slice(&WIN_CERT_REVISION.to_le_bytes());
+        out.extend_from_slice(&WIN_CERT_TYPE_EFI_GUID.to_le_bytes());
+        out.extend_from_slice(CERT_TYPE_PKCS7_GUID.as_bytes());
+        out.extend_from_slice(self.signed_data);
+    }
+}
+
+/// Size of the message signed for a time based authenticated write.
+///
+/// `name` is the null-terminated variable name.
+pub fn signed_message_size(name: &[u16], payload: &[u8]) -> usize {
+    name.len().saturating_sub(1) * 2 + 16 + 4 + TIME_SIZE + payload.len()
+}
+
+/// Serializes the message signed for a time based authenticated write into `buffer`, returning its size.
+///
+/// The message is the variable name (without the null terminator), vendor GUID, attributes, timestamp and payload.
+/// `name` is the null-terminated variable name and `attributes` are the attributes passed to `SetVariable()`.
+/// Returns `BUFFER_TOO_SMALL` if `buffer` is shorter than [signed_message_size].
+pub fn signed_message_into(
+    name: &[u16],
+    namespace: &efi::Guid,
+    attributes: u32,
+    timestamp: &efi::Time,
+    payload: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, efi::Status> {
+    let size = signed_message_size(name, payload);
+    let buffer = buffer.get_mut(..size).ok_or(efi::Status::BUFFER_TOO_SMALL)?;
+    let name = &name[..name.len().saturating_sub(1)];
+
+    let (name_bytes, rest) = buffer.split_at_mut(name.len() * 2);
+    for (chunk, c) in name_bytes.chunks_exact_mut(2).zip(name) {
+        chunk.copy_from_slice(&c.to_le_bytes());
+    }
+    rest[..16].copy_from_slice(namespace.as_bytes());
+    rest[16..20].copy_from_slice(&attributes.to_le_bytes());
+    rest[20..20 + TIME_SIZE].copy_from_slice(&time_to_bytes(timestamp));
+    rest[20 + TIME_SIZE..].copy_from_slice(payload);
+    Ok(size)
+}
+
+/// Returns the message signed for a time based authenticated write. See [signed_message_into].
+pub fn signed_message(
+    name: &[u16],
+    namespace: &efi::Guid,
+    attributes: u32,
+    timestamp: &efi::Time,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut message = alloc::vec![0u8; signed_message_size(name, payload)];
+    signed_message_into(name, namespace, attributes, timestamp, payload, &mut message)
+        .expect("Buffer was sized for the message.");
+    message
+}
+
+/// A single `EFI_SIGNATURE_DATA` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureData<'a> {
+    /// The agent that added the signature.
+    pub owner: efi::Guid,
+    /// The signature, whose format depends on the list's signature type.
+    pub data: &'a [u8],
+}
+
+/// A parsed `EFI_SIGNATURE_LIST`.
+#[derive(Debug, Clone, Copy)]
+pub struct SignatureList<'a> {
+    /// Type of every signature in the list (see [signature_type]).
+    pub signature_type: efi::Guid,
+    /// Signature type specific header.
+    pub header: &'a [u8],
+    signature_size: usize,
+    signatures: &'a [u8],
+}
+
+impl<'a> SignatureList<'a> {
+    /// Size of each `EFI_SIGNATURE_DATA` entry, including the owner GUID.
+    pub fn signature_size(&self) -> usize {
+        self.signature_size
+    }
+
+    /// Iterates over the signatures in the list.
+    pub fn signatures(&self) -> impl Iterator<Item = SignatureData<'a>> + use<'a> {
+        self.signatures.chunks_exact(self.signature_size).map(|entry| SignatureData {
+            owner: efi::Guid::from_bytes(entry[..SIGNATURE_OWNER_SIZE].try_into().expect("Slice is 16 bytes.")),
+            data: &entry[SIGNATURE_OWNER_SIZE..],
+        })
+    }
+
+    /// Appends a serialized list of `signatures` to `out`. Every signature must have the same size.
+    pub fn write(signature_type: &efi::Guid, signatures: &[SignatureData], out: &mut Vec<u8>) {
+        let signature_size = signatures.first().map_or(SIGNATURE_OWNER_SIZE, |s| SIGNATURE_OWNER_SIZE + s.data.len());
+        debug_assert!(signatures.iter().all(|s| SIGNATURE_OWNER_SIZE + s.data.len() == signature_size));
+        let list_size = SIGNATURE_LIST_SIZE + signature_size * signatures.len();
+
+        out.extend_from_slice(signature_type.as_bytes());
+        out.extend_from_slice(&(list_size as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(signature_size as u32).to_le_bytes());
+        for signature in signatures {
+            out.extend_from_slice(signature.owner.as_bytes());
+            out.extend_from_slice(signature.data);
+        }
+    }
+}
+
+/// Iterates over a sequence of `EFI_SIGNATURE_LIST` structures, as stored in the signature database variables.
+///
+/// Each item is an error (`INVALID_PARAMETER`) if the list is malformed: truncated, with a zero signature size, a
+/// size that is not a whole number of signatures, or a signature size that does not match a fixed size signature
+/// type. Iteration stops after the first error.
+pub fn signature_lists(data: &[u8]) -> impl Iterator<Item = Result<SignatureList<'_>, efi::Status>> {
+    let mut rest = data;
+    core::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let parsed = parse_signature_list(rest);
+        rest = match &parsed {
+            Ok((_, remainder)) => remainder,
+            Err(_) => &[],
+        };
+        Some(parsed.map(|(list, _)| list))
+    })
+}
+
+fn parse_signature_list(data: &[u8]) -> Result<(SignatureList<'_>, &[u8]), efi::Status> {
+    if data.len() < SIGNATURE_LIST_SIZE {
+        return Err(efi::Status::INVALID_PARAMETER);
+    }
+    let field = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().expect("Slice is 4 bytes."));
+    let signature_type = efi::Guid::from_bytes(data[..16].try_into().expect("Slice is 16 bytes."));
+    let list_size = field(16) as usize;
+    let header_size = field(20) as usize;
+    let signature_size = field(24) as usize;
+
+    let signatures_start = SIGNATURE_LIST_SIZE.checked_add(header_size).ok_or(efi::Status::INVALID_PARAMETER)?;
+    if list_size > data.len()
+        || list_size < signatures_start
+        || signature_size <= SIGNATURE_OWNER_SIZE
+        || !(list_size - signatures_start).is_multiple_of(signature_size)
+    {
+        return Err(efi::Status::INVALID_PARAMETER);
+    }
+    if let Some(size) = signature_type::fixed_data_size(&signature_type)
+        && (header_size != 0 || signature_size != SIGNATURE_OWNER_SIZE + size)
+    {
+        return Err(efi::Status::INVALID_PARAMETER);
+    }
+
+    let list = SignatureList {
+        signature_type,
+        header: &data[SIGNATURE_LIST_SIZE..signatures_start],
+        signature_size,
+        signatures: &data[signatures_start..list_size],
+    };
+    Ok((list, &data[list_size..]))
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+
+    const OWNER: efi::Guid = efi::Guid::from_fields(1, 2, 3, 4, 5, &[6, 7, 8, 9, 10, 11]);
+
+    fn timestamp() -> efi::Time {
+        efi::Time { year: 2025, month: 3, day: 14, hour: 15, minute: 9, second: 26, ..Default::default() }
+    }
+
+    #[test]
+    fn authentication_2_round_trip() {
+        let descriptor = Authentication2 { timestamp: timestamp(), signed_data: &[0x30, 0x82, 1, 2] };
+        let mut data = Vec::new();
+        descriptor.write(&mut data);
+        assert_eq!(data.len(), descriptor.size());
+        data.extend_from_slice(&[9, 9, 9]);
+
+        let (parsed, payload) = Authentication2::parse(&data).unwrap();
+        assert_eq!(parsed.signed_data, &[0x30, 0x82, 1, 2]);
+        assert_eq!(parsed.timestamp.year, 2025);
+        assert_eq!(parsed.timestamp.second, 26);
+        assert_eq!(payload, &[9, 9, 9]);
+    }
+
+    #[test]
+    fn authentication_2_rejects_malformed_descriptors() {
+        let mut data = Vec::new();
+        Authentication2 { timestamp: timestamp(), signed_data: &[1] }.write(&mut data);
+
+        assert_eq!(Authentication2::parse(&data[..30]).unwrap_err(), efi::Status::SECURITY_VIOLATION);
+
+        let mut bad_time = data.clone();
+        bad_time[8] = 1; // nanosecond
+        assert_eq!(Authentication2::parse(&bad_time).unwrap_err(), efi::Status::SECURITY_VIOLATION);
+
+        let mut bad_type = data.clone();
+        bad_type[TIME_SIZE + 8] ^= 0xff;
+        assert_eq!(Authentication2::parse(&bad_type).unwrap_err(), efi::Status::SECURITY_VIOLATION);
+
+        let mut bad_length = data.clone();
+        bad_length[TIME_SIZE..TIME_SIZE + 4].copy_from_slice(&0x100u32.to_le_bytes());
+        assert_eq!(Authentication2::parse(&bad_length).unwrap_err(), efi::Status::SECURITY_VIOLATION);
+    }
+
+    #[test]
+    fn signed_message_layout() {
+        let name = [b'd' as u16, b'b' as u16, 0];
+        let message = signed_message(&name, &IMAGE_SECURITY_DATABASE_GUID, 0x27, &timestamp(), &[0xaa]);
+        assert_eq!(message.len(), 4 + 16 + 4 + 16 + 1);
+        assert_eq!(&message[..4], &[b'd', 0, b'b', 0]);
+        assert_eq!(&message[4..20], IMAGE_SECURITY_DATABASE_GUID.as_bytes());
+        assert_eq!(&message[20..24], &0x27u32.to_le_bytes());
+        assert_eq!(&message[24..26], &2025u16.to_le_bytes());
+        assert_eq!(message[40], 0xaa);
+
+        let mut small = [0u8; 8];
+        assert_eq!(
+            signed_message_into(&name, &OWNER, 0, &timestamp(), &[], &mut small),
+            Err(efi::Status::BUFFER_TOO_SMALL)
+        );
+    }
+
+    #[test]
+    fn signature_list_round_trip() {
+        let mut data = Vec::new();
+        let hashes = [SignatureData { owner: OWNER, data: &[1; 32] }, SignatureData { owner: OWNER, data: &[2; 32] }];
+        SignatureList::write(&signature_type::SHA256, &hashes, &mut data);
+        SignatureList::write(&signature_type::X509, &[SignatureData { owner: OWNER, data: &[3; 100] }], &mut data);
+
+        let lists: Vec<_> = signature_lists(&data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(lists.len(), 2);
+        assert_eq!(lists[0].signature_type, signature_type::SHA256);
+        assert_eq!(lists[0].signatures().collect::<Vec<_>>(), hashes);
+        assert_eq!(lists[1].signature_size(), 116);
+        assert_eq!(lists[1].signatures().next().unwrap().data, &[3; 100]);
+    }
+
+    #[test]
+    fn signature_list_rejects_malformed_lists() {
+        let mut data = Vec::new();
+        SignatureList::write(&signature_type::SHA256, &[SignatureData { owner: OWNER, data: &[1; 32] }], &mut data);
+
+        let truncated = &data[..data.len() - 1];
+        assert!(signature_lists(truncated).next().unwrap().is_err());
+
+        // A SHA-256 list with 20 byte signatures.
+        let mut wrong_size = Vec::new();
+        SignatureList::write(
+            &signature_type::SHA256,
+            &[SignatureData { owner: OWNER, data: &[1; 20] }],
+            &mut wrong_size,
+        );
+        assert!(signature_lists(&wrong_size).next().unwrap().is_err());
+
+        // A list claiming a signature size that does not divide its payload.
+        let mut uneven = data.clone();
+        uneven[16..20].copy_from_slice(&((data.len() + 1) as u32).to_le_bytes());
+        uneven.push(0);
+        assert!(signature_lists(&uneven).next().unwrap().is_err());
+
+        assert_eq!(signature_lists(&[0u8; 10]).count(), 1);
+        assert_eq!(signature_lists(&[]).count(), 0);
+    }
+}
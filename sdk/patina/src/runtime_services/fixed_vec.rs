@@ -0,0 +1,123 @@
+//! Fixed-Capacity Vector
+//!
+//! State that runtime services read after `ExitBootServices()` cannot grow through the global allocator: its pool is
+//! boot services memory, which the OS reclaims. [FixedVec] keeps its elements inline, so a value that holds one can
+//! be placed in `EfiRuntimeServicesData` memory as a whole, for example with `leak_in_runtime_memory` of the
+//! [MemoryManager](crate::component::service::memory::MemoryManager) service.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use crate::error::EfiError;
+
+/// A list of at most `N` elements that never allocates.
+///
+/// Elements keep the order they were pushed in.
+#[derive(Debug)]
+pub struct FixedVec<T, const N: usize> {
+    entries: [Option<T>; N],
+    len: usize,
+}
+
+impl<T, const N: usize> FixedVec<T, N> {
+    /// Creates an empty list.
+    pub const fn new() -> Self {
+        Self { entries: [const { None }; N], len: 0 }
+    }
+
+    /// Returns the number of elements in the list.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the list holds no element.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the element at `index`, if there is one.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.entries[..self.len].get(index)?.as_ref()
+    }
+
+    /// Returns an iterator over the elements, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries[..self.len].iter().flatten()
+    }
+
+    /// Appends `value` to the list.
+    ///
+    /// ## Errors
+    ///
+    /// Returns [EfiError::OutOfResources] if the list already holds `N` elements.
+    pub fn push(&mut self, value: T) -> Result<(), EfiError> {
+        let slot = self.entries.get_mut(self.len).ok_or(EfiError::OutOfResources)?;
+        *slot = Some(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes and returns the element at `index`, shifting the ones after it down.
+    pub fn remove(&mut self, index: usize) -> Option<T> {
+        if index >= self.len {
+            return None;
+        }
+        let value = self.entries[index].take();
+        self.entries[index..self.len].rotate_left(1);
+        self.len -= 1;
+        value
+    }
+}
+
+impl<T, const N: usize> Default for FixedVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn elements<const N: usize>(list: &FixedVec<u32, N>) -> Vec<u32> {
+        list.iter().copied().collect()
+    }
+
+    #[test]
+    fn push_stops_at_capacity() {
+        let mut list = FixedVec::<u32, 3>::new();
+        assert!(list.is_empty());
+        for value in 1..=3 {
+            assert_eq!(list.push(value), Ok(()));
+        }
+        assert_eq!(list.push(4), Err(EfiError::OutOfResources));
+        assert_eq!(list.len(), 3);
+        assert_eq!(elements(&list), [1, 2, 3]);
+        assert_eq!(list.get(2), Some(&3));
+        assert_eq!(list.get(3), None);
+    }
+
+    #[test]
+    fn remove_keeps_the_order() {
+        let mut list = FixedVec::<u32, 4>::default();
+        for value in 1..=4 {
+            list.push(value).unwrap();
+        }
+        assert_eq!(list.remove(1), Some(2));
+        assert_eq!(elements(&list), [1, 3, 4]);
+        assert_eq!(list.remove(3), None);
+        assert_eq!(list.remove(2), Some(4));
+        assert_eq!(elements(&list), [1, 3]);
+
+        // Removed slots can be pushed to again.
+        list.push(5).unwrap();
+        list.push(6).unwrap();
+        assert_eq!(elements(&list), [1, 3, 5, 6]);
+        assert_eq!(list.push(7), Err(EfiError::OutOfResources));
+    }
+}
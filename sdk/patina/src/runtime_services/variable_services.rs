@@ -47,6 +47,18 @@ pub struct VariableIdentifier {
     namespace: efi::Guid,
 }
 
+impl VariableIdentifier {
+    /// The null-terminated UCS-2 name of the variable
+    pub fn name(&self) -> &[u16] {
+        &self.name
+    }
+
+    /// The vendor GUID the variable is stored under
+    pub fn namespace(&self) -> &efi::Guid {
+        &self.namespace
+    }
+}
+
 /// Provides a [`FallibleStreamingIterator`] over UEFI variable names
 ///
 /// Produces an EFI status on error.
@@ -11,6 +11,8 @@
 pub mod device_path;
 
 pub mod decompress;
+pub mod firmware_management;
+pub mod partition_info;
 pub mod performance_measurement;
 pub mod status_code;
 
@@ -201,6 +201,11 @@ impl DevicePath {
         self.buffer.len()
     }
 
+    /// Return the bytes of the device path, in the layout of `EFI_DEVICE_PATH_PROTOCOL`.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
     /// Return the number of nodes in the device path.
     pub fn node_count(&self) -> usize {
         self.iter().count()
@@ -494,6 +499,14 @@ mod tests {
         assert_eq!(22, device_path_buf.size());
     }
 
+    #[test]
+    fn test_device_path_as_bytes() {
+        let mut device_path_buf = DevicePathBuf::new_empty();
+        device_path_buf.append(Pci { function: 1, device: 2 });
+        device_path_buf.append(EndEntire);
+        assert_eq!(&[1, 1, 6, 0, 1, 2, 0x7F, 0xFF, 4, 0], device_path_buf.as_bytes());
+    }
+
     #[test]
     fn test_device_path_node_count() {
         let mut device_path_buf = DevicePathBuf::new_empty();
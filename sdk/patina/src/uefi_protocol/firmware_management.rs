@@ -0,0 +1,162 @@
+//! Firmware Management Protocol
+//!
+//! Installed by the drivers of updatable firmware devices, one instance per device, to describe the firmware images
+//! of the device and to update them. Firmware management (FMP) capsules are applied through it.
+//!
+//! See <https://uefi.org/specs/UEFI/2.10/23_Firmware_Update_and_Reporting.html#firmware-management-protocol>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use core::ffi::c_void;
+
+use r_efi::efi;
+
+use super::ProtocolInterface;
+
+/// The GUID of the Firmware Management Protocol.
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x86c77a67, 0x0b97, 0x4633, 0xa1, 0x87, &[0x49, 0x10, 0x4d, 0x06, 0x85, 0xc7]);
+
+/// The version of [ImageDescriptor] described by this module.
+pub const IMAGE_DESCRIPTOR_VERSION: u32 = 4;
+
+/// A firmware image of a device (`EFI_FIRMWARE_IMAGE_DESCRIPTOR`), as returned by [Protocol::get_image_info].
+///
+/// Fields added after version 1 are only valid if the descriptor version reported with them is high enough.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ImageDescriptor {
+    /// The index of the image, from 1, passed back to [Protocol::set_image].
+    pub image_index: u8,
+    /// The type of the image, matched against the `UpdateImageTypeId` of FMP capsule payloads.
+    pub image_type_id: efi::Guid,
+    /// A unique identifier of the image.
+    pub image_id: u64,
+    /// The null-terminated name of the image.
+    pub image_id_name: *mut efi::Char16,
+    /// The version of the image.
+    pub version: u32,
+    /// The null-terminated version of the image, as a string.
+    pub version_name: *mut efi::Char16,
+    /// The size of the image, in bytes.
+    pub size: usize,
+    /// The `IMAGE_ATTRIBUTE_*` bits the device supports.
+    pub attributes_supported: u64,
+    /// The `IMAGE_ATTRIBUTE_*` bits set for the image.
+    pub attributes_setting: u64,
+    /// The `IMAGE_COMPATIBILITY_*` bits of the image.
+    pub compatibilities: u64,
+    /// The lowest version the image may be updated to. Version 2 and later.
+    pub lowest_supported_image_version: u32,
+    /// The version of the last update attempt. Version 3 and later.
+    pub last_attempt_version: u32,
+    /// The `LAST_ATTEMPT_STATUS_*` of the last update attempt. Version 3 and later.
+    pub last_attempt_status: u32,
+    /// The instance of the device, or 0 if there is only one. Version 3 and later.
+    pub hardware_instance: u64,
+    /// The dependencies of the image. Version 4 and later.
+    pub dependencies: *mut c_void,
+}
+
+/// Reports the progress of [Protocol::set_image], from 1 to 100.
+pub type ProgressFn = extern "efiapi" fn(completion: usize) -> efi::Status;
+
+/// Returns the descriptors of the firmware images of the device.
+pub type GetImageInfo = extern "efiapi" fn(
+    this: *mut Protocol,
+    image_info_size: *mut usize,
+    image_info: *mut ImageDescriptor,
+    descriptor_version: *mut u32,
+    descriptor_count: *mut u8,
+    descriptor_size: *mut usize,
+    package_version: *mut u32,
+    package_version_name: *mut *mut efi::Char16,
+) -> efi::Status;
+
+/// Copies a firmware image out of the device.
+pub type GetImage =
+    extern "efiapi" fn(this: *mut Protocol, image_index: u8, image: *mut c_void, image_size: *mut usize) -> efi::Status;
+
+/// Updates a firmware image of the device.
+pub type SetImage = extern "efiapi" fn(
+    this: *mut Protocol,
+    image_index: u8,
+    image: *const c_void,
+    image_size: usize,
+    vendor_code: *const c_void,
+    progress: Option<ProgressFn>,
+    abort_reason: *mut *mut efi::Char16,
+) -> efi::Status;
+
+/// Checks whether an image can update a firmware image of the device.
+pub type CheckImage = extern "efiapi" fn(
+    this: *mut Protocol,
+    image_index: u8,
+    image: *const c_void,
+    image_size: usize,
+    image_updatable: *mut u32,
+) -> efi::Status;
+
+/// Returns the version of the firmware package of the device.
+pub type GetPackageInfo = extern "efiapi" fn(
+    this: *mut Protocol,
+    package_version: *mut u32,
+    package_version_name: *mut *mut efi::Char16,
+    package_version_name_max_len: *mut u32,
+    attributes_supported: *mut u64,
+    attributes_setting: *mut u64,
+) -> efi::Status;
+
+/// Updates the version of the firmware package of the device.
+pub type SetPackageInfo = extern "efiapi" fn(
+    this: *mut Protocol,
+    image: *const c_void,
+    image_size: usize,
+    vendor_code: *const c_void,
+    package_version: u32,
+    package_version_name: *const efi::Char16,
+) -> efi::Status;
+
+/// Rust definition of the UEFI Firmware Management Protocol (`EFI_FIRMWARE_MANAGEMENT_PROTOCOL`).
+#[repr(C)]
+pub struct Protocol {
+    /// Returns the descriptors of the firmware images of the device.
+    pub get_image_info: GetImageInfo,
+    /// Copies a firmware image out of the device.
+    pub get_image: GetImage,
+    /// Updates a firmware image of the device.
+    pub set_image: SetImage,
+    /// Checks whether an image can update a firmware image of the device.
+    pub check_image: CheckImage,
+    /// Returns the version of the firmware package of the device.
+    pub get_package_info: GetPackageInfo,
+    /// Updates the version of the firmware package of the device.
+    pub set_package_info: SetPackageInfo,
+}
+
+// Safety: Protocol matches the layout of EFI_FIRMWARE_MANAGEMENT_PROTOCOL, and PROTOCOL_GUID is the GUID the UEFI
+// specification defines for it.
+unsafe impl ProtocolInterface for Protocol {
+    const PROTOCOL_GUID: efi::Guid = PROTOCOL_GUID;
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use core::mem;
+
+    use super::*;
+
+    #[test]
+    fn test_image_descriptor_layout_matches_the_specification() {
+        assert_eq!(mem::offset_of!(ImageDescriptor, image_type_id), 4);
+        assert_eq!(mem::offset_of!(ImageDescriptor, image_id), 24);
+        assert_eq!(mem::offset_of!(ImageDescriptor, lowest_supported_image_version), 88);
+        assert_eq!(mem::offset_of!(ImageDescriptor, hardware_instance), 104);
+        assert_eq!(mem::size_of::<ImageDescriptor>(), 120);
+    }
+}
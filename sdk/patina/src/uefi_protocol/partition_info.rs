@@ -0,0 +1,169 @@
+//! Partition Information Protocol
+//!
+//! Installed by partition drivers on the handle of every partition they produce, next to its Block I/O protocol, to
+//! describe the partition table entry the partition comes from.
+//!
+//! See <https://uefi.org/specs/UEFI/2.10/13_Protocols_Media_Access.html#partition-information-protocol>
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use r_efi::efi;
+
+use super::ProtocolInterface;
+
+/// The GUID of the Partition Information Protocol.
+pub const PROTOCOL_GUID: efi::Guid =
+    efi::Guid::from_fields(0x8cf2f62c, 0xbc9b, 0x4821, 0x80, 0x8d, &[0xec, 0x9e, 0xc4, 0x21, 0xa1, 0xa0]);
+
+/// The revision of the protocol described by this module.
+pub const REVISION: u32 = 0x0001000;
+
+/// The partition does not come from an MBR or a GPT, for example an El Torito image.
+pub const TYPE_OTHER: u32 = 0x00;
+/// The partition comes from a legacy MBR, described by [PartitionInfo::mbr].
+pub const TYPE_MBR: u32 = 0x01;
+/// The partition comes from a GPT, described by [PartitionInfo::gpt].
+pub const TYPE_GPT: u32 = 0x02;
+
+/// A partition record of a legacy MBR (`MBR_PARTITION_RECORD`), as laid out on disk.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MbrPartitionRecord {
+    /// 0x80 for the partition legacy firmware boots from, 0 otherwise.
+    pub boot_indicator: u8,
+    /// The head of the first sector, in CHS addressing.
+    pub start_head: u8,
+    /// The sector of the first sector and the high bits of its cylinder, in CHS addressing.
+    pub start_sector: u8,
+    /// The low bits of the cylinder of the first sector, in CHS addressing.
+    pub start_track: u8,
+    /// The type of the partition, for example 0xEF for an EFI system partition.
+    pub os_indicator: u8,
+    /// The head of the last sector, in CHS addressing.
+    pub end_head: u8,
+    /// The sector of the last sector and the high bits of its cylinder, in CHS addressing.
+    pub end_sector: u8,
+    /// The low bits of the cylinder of the last sector, in CHS addressing.
+    pub end_track: u8,
+    /// The first LBA of the partition, little endian.
+    pub starting_lba: [u8; 4],
+    /// The number of blocks of the partition, little endian.
+    pub size_in_lba: [u8; 4],
+}
+
+/// A GPT partition entry (`EFI_PARTITION_ENTRY`), as laid out on disk.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GptPartitionEntry {
+    /// The type of the partition. Zero for an unused entry.
+    pub partition_type_guid: efi::Guid,
+    /// The GUID that uniquely identifies the partition.
+    pub unique_partition_guid: efi::Guid,
+    /// The first LBA of the partition.
+    pub starting_lba: u64,
+    /// The last LBA of the partition, inclusive.
+    pub ending_lba: u64,
+    /// The attribute bits of the partition.
+    pub attributes: u64,
+    /// The null-terminated UCS-2 name of the partition.
+    pub partition_name: [u16; 36],
+}
+
+/// The partition table entry of a partition, selected by the type of the [Protocol].
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union PartitionInfo {
+    /// The entry of a [TYPE_MBR] partition.
+    pub mbr: MbrPartitionRecord,
+    /// The entry of a [TYPE_GPT] partition.
+    pub gpt: GptPartitionEntry,
+}
+
+/// Rust definition of the UEFI Partition Information Protocol (`EFI_PARTITION_INFO_PROTOCOL`).
+///
+/// The specification declares the structure packed, but every field is naturally aligned, so `repr(C)` has the same
+/// layout.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Protocol {
+    /// Set to [REVISION].
+    pub revision: u32,
+    /// One of [TYPE_OTHER], [TYPE_MBR] or [TYPE_GPT].
+    pub r#type: u32,
+    /// 1 if the partition is an EFI system partition, 0 otherwise.
+    pub system: u8,
+    /// Reserved, must be zero.
+    pub reserved: [u8; 7],
+    /// The partition table entry of the partition. Zeroed for [TYPE_OTHER].
+    pub info: PartitionInfo,
+}
+
+// Safety: Protocol matches the layout of EFI_PARTITION_INFO_PROTOCOL, and PROTOCOL_GUID is the GUID the UEFI
+// specification defines for it.
+unsafe impl ProtocolInterface for Protocol {
+    const PROTOCOL_GUID: efi::Guid = PROTOCOL_GUID;
+}
+
+impl Protocol {
+    /// Creates the protocol for a partition of a legacy MBR.
+    pub const fn new_mbr(record: MbrPartitionRecord, system: bool) -> Self {
+        let mut info = PartitionInfo { gpt: GptPartitionEntry::ZERO };
+        info.mbr = record;
+        Self { revision: REVISION, r#type: TYPE_MBR, system: system as u8, reserved: [0; 7], info }
+    }
+
+    /// Creates the protocol for a partition of a GPT.
+    pub const fn new_gpt(entry: GptPartitionEntry, system: bool) -> Self {
+        Self {
+            revision: REVISION,
+            r#type: TYPE_GPT,
+            system: system as u8,
+            reserved: [0; 7],
+            info: PartitionInfo { gpt: entry },
+        }
+    }
+}
+
+impl GptPartitionEntry {
+    /// An unused partition entry.
+    pub const ZERO: Self = Self {
+        partition_type_guid: efi::Guid::from_bytes(&[0; 16]),
+        unique_partition_guid: efi::Guid::from_bytes(&[0; 16]),
+        starting_lba: 0,
+        ending_lba: 0,
+        attributes: 0,
+        partition_name: [0; 36],
+    };
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use core::mem;
+
+    use super::*;
+
+    #[test]
+    fn test_partition_info_layout_matches_the_specification() {
+        assert_eq!(mem::size_of::<MbrPartitionRecord>(), 16);
+        assert_eq!(mem::size_of::<GptPartitionEntry>(), 128);
+        assert_eq!(mem::size_of::<Protocol>(), 144);
+        assert_eq!(mem::offset_of!(Protocol, info), 16);
+    }
+
+    #[test]
+    fn test_mbr_partition_info_keeps_the_record() {
+        let record = MbrPartitionRecord { os_indicator: 0xEF, starting_lba: [1, 0, 0, 0], ..Default::default() };
+        let protocol = Protocol::new_mbr(record, true);
+        assert_eq!(protocol.r#type, TYPE_MBR);
+        assert_eq!(protocol.system, 1);
+        // SAFETY: new_mbr sets the mbr member of the union.
+        assert_eq!(unsafe { protocol.info.mbr }, record);
+        // SAFETY: The union is fully initialized, and the rest of the GPT entry stays zero.
+        assert_eq!(unsafe { protocol.info.gpt }.ending_lba, 0);
+    }
+}
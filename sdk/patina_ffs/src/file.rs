@@ -48,7 +48,7 @@ impl<'a> FileRef<'a> {
     ///
     /// Errors
     /// - [`FirmwareFileSystemError::InvalidHeader`]: malformed header or size.
-    /// - [`FirmwareFileSystemError::InvalidState`]: file state not DATA_VALID.
+    /// - [`FirmwareFileSystemError::InvalidState`]: file state not DATA_VALID or MARKED_FOR_UPDATE.
     /// - [`FirmwareFileSystemError::DataCorrupt`]: data checksum mismatch.
     ///
     /// ## Examples
@@ -109,22 +109,14 @@ impl<'a> FileRef<'a> {
         // Interpreting the state field requires knowledge of the EFI_FVB_ERASE_POLARITY from the FV header, which is not
         // available here unless the constructor API is modified to specify it. So it is inferred based on the state of
         // the reserved bits in the EFI_FFS_FILE_STATE which spec requires to be set to EFI_FVB_ERASE_POLARITY.
-        // This implementation does not support FV modification, so the only valid state is EFI_FILE_DATA_VALID.
-        let erase_polarity;
-        if (header.state & 0x80) == 0 {
-            //erase polarity = 0. Verify DATA_VALID is set, and no higher-order bits are set.
-            erase_polarity = false;
-            if header.state & 0xFC != file::raw::state::DATA_VALID {
-                //file is not in EFI_FILE_DATA_VALID state.
-                Err(FirmwareFileSystemError::InvalidState)?;
-            }
-        } else {
-            //erase polarity = 1. Verify DATA_VALID is clear, and no higher-order bits are clear.
-            erase_polarity = true;
-            if (!header.state) & 0xFC != file::raw::state::DATA_VALID {
-                //file is not in EFI_FILE_DATA_VALID state.
-                Err(FirmwareFileSystemError::InvalidState)?;
-            }
+        // The file data is valid in the EFI_FILE_DATA_VALID state, and remains valid once the file has been marked for
+        // update until the replacement file is written.
+        let erase_polarity = infer_erase_polarity(header.state);
+        if !matches!(
+            header_state(header.state, erase_polarity),
+            Some(file::State::DataValid | file::State::MarkedForUpdate)
+        ) {
+            Err(FirmwareFileSystemError::InvalidState)?;
         }
 
         // Verify the file header checksum.
@@ -141,7 +133,10 @@ impl<'a> FileRef<'a> {
                 Err(FirmwareFileSystemError::InvalidHeader)?;
             }
         } else {
-            let sum = buffer[content_offset..size].iter().fold(0u8, |sum, val| sum.wrapping_add(*val));
+            // The data checksum is chosen so that the data and the checksum sum to zero.
+            let sum = buffer[content_offset..size]
+                .iter()
+                .fold(header.integrity_check_file, |sum, val| sum.wrapping_add(*val));
             if sum != 0 {
                 Err(FirmwareFileSystemError::DataCorrupt)?;
             }
@@ -184,6 +179,12 @@ impl<'a> FileRef<'a> {
         self.erase_polarity
     }
 
+    /// The file state; either [`State::DataValid`](file::State::DataValid) or
+    /// [`State::MarkedForUpdate`](file::State::MarkedForUpdate).
+    pub fn state(&self) -> file::State {
+        header_state(self.header.state, self.erase_polarity).unwrap_or(file::State::DataValid)
+    }
+
     /// Decode the file's attributes into `EFI_FV_FILE_ATTRIBUTES` per PI spec.
     pub fn fv_attributes(&self) -> fv::file::EfiFvFileAttributes {
         let attributes = self.header.attributes;
@@ -276,6 +277,46 @@ impl<'a> FileRef<'a> {
     }
 }
 
+/// Decode the state of an FFS file from the raw `state` byte of its header.
+///
+/// File states are reached by programming state bits one at a time, so the state of a file is the most significant
+/// bit that has been programmed (PI spec 1.8A, V3, section 2.1.4.1.4). Bits are considered programmed when they differ
+/// from `erase_polarity`. Returns `None` if no state bit has been programmed, and
+/// [`State::HeaderInvalid`](file::State::HeaderInvalid) if a reserved bit has been programmed.
+///
+/// ## Examples
+///
+/// ```rust
+/// use patina::pi::fw_fs::ffs::file::{State, raw::state};
+/// use patina_ffs::file::header_state;
+///
+/// let valid = state::HEADER_CONSTRUCTION | state::HEADER_VALID | state::DATA_VALID;
+/// assert_eq!(header_state(!valid, true), Some(State::DataValid));
+/// assert_eq!(header_state(valid | state::DELETED, false), Some(State::Deleted));
+/// assert_eq!(header_state(0xff, true), None);
+/// ```
+pub fn header_state(state: u8, erase_polarity: bool) -> Option<file::State> {
+    let programmed = if erase_polarity { !state } else { state };
+    if programmed == 0 {
+        return None;
+    }
+    Some(match 0x80u8 >> programmed.leading_zeros() {
+        file::raw::state::HEADER_CONSTRUCTION => file::State::HeaderConstruction,
+        file::raw::state::HEADER_VALID => file::State::HeaderValid,
+        file::raw::state::DATA_VALID => file::State::DataValid,
+        file::raw::state::MARKED_FOR_UPDATE => file::State::MarkedForUpdate,
+        file::raw::state::DELETED => file::State::Deleted,
+        _ => file::State::HeaderInvalid,
+    })
+}
+
+/// Infer the erase polarity an FFS file state byte was written with.
+///
+/// The most significant state bit is reserved and must be left at the erase polarity of the volume.
+pub(crate) fn infer_erase_polarity(state: u8) -> bool {
+    state & 0x80 != 0
+}
+
 impl fmt::Debug for FileRef<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("FileRef")
@@ -299,12 +340,33 @@ pub struct File {
     attributes: u8,
     erase_polarity: bool,
     sections: Vec<Section>,
+    content: Option<Vec<u8>>,
 }
 
 impl File {
     /// Create a new, empty FFS file builder with the given name and type.
     pub fn new(name: efi::Guid, file_type_raw: u8) -> Self {
-        Self { name, file_type_raw, attributes: 0, erase_polarity: true, sections: Vec::new() }
+        Self { name, file_type_raw, attributes: 0, erase_polarity: true, sections: Vec::new(), content: None }
+    }
+
+    /// Create an FFS file builder whose content is the given bytes rather than a list of sections.
+    ///
+    /// Useful for files whose content is already serialized (e.g. data written through the FV protocol), and for
+    /// `EFI_FV_FILETYPE_RAW` files, whose content is not made of sections.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use patina::pi::fw_fs::ffs;
+    /// use patina_ffs::file::{File, FileRef};
+    /// use r_efi::efi;
+    ///
+    /// let file = File::new_with_content(efi::Guid::from_bytes(&[1u8; 16]), ffs::file::raw::r#type::RAW, b"hi".to_vec());
+    /// let bytes = file.serialize().unwrap();
+    /// assert_eq!(FileRef::new(&bytes).unwrap().content(), b"hi");
+    /// ```
+    pub fn new_with_content(name: efi::Guid, file_type_raw: u8, content: Vec<u8>) -> Self {
+        Self { name, file_type_raw, attributes: 0, erase_polarity: true, sections: Vec::new(), content: Some(content) }
     }
 
     /// Serialize the file into a valid FFS byte stream.
@@ -336,7 +398,7 @@ impl File {
     /// assert!(!bytes.is_empty());
     /// ```
     pub fn serialize(&self) -> Result<Vec<u8>, FirmwareFileSystemError> {
-        let mut content = Vec::new();
+        let mut content = self.content.clone().unwrap_or_default();
 
         let mut section_iter = self.sections.iter().peekable();
 
@@ -345,7 +407,7 @@ impl File {
             if section_iter.peek().is_some() {
                 //pad to next 4-byte aligned length, since sections start at 4-byte aligned offsets. No padding is added
                 //after the last section.
-                if content.len() % 4 != 0 {
+                if !content.len().is_multiple_of(4) {
                     let pad_length = 4 - (content.len() % 4);
                     //Per PI 1.8A volume 3 section 2.2.4, pad byte is always zero.
                     content.extend(iter::repeat_n(0u8, pad_length));
@@ -469,6 +531,39 @@ impl File {
         }
     }
 
+    /// Set the FFS attributes from `EFI_FV_FILE_ATTRIBUTES` per PI spec.
+    ///
+    /// The alignment is rounded up to the next alignment the FFS header can encode. Alignments larger than 16 MiB are
+    /// rejected with [`FirmwareFileSystemError::InvalidParameter`].
+    pub fn set_fv_attributes(
+        &mut self,
+        fv_attributes: fv::file::EfiFvFileAttributes,
+    ) -> Result<(), FirmwareFileSystemError> {
+        // encode alignment per Table 3.3 in PI spec 1.8 Part III (see FileRef::fv_attributes for the decode).
+        let (data_alignment, data_alignment_2) = match fv_attributes & fv::file::raw::attribute::ALIGNMENT {
+            0 => (0, false),
+            1..=4 => (1, false),
+            5..=7 => (2, false),
+            8..=9 => (3, false),
+            10 => (4, false),
+            11..=12 => (5, false),
+            13..=15 => (6, false),
+            16 => (7, false),
+            x @ 17..=24 => ((x - 17) as u8, true),
+            _ => Err(FirmwareFileSystemError::InvalidParameter)?,
+        };
+        self.attributes &=
+            !(attributes::raw::DATA_ALIGNMENT | attributes::raw::DATA_ALIGNMENT_2 | attributes::raw::FIXED);
+        self.attributes |= data_alignment << 3;
+        if data_alignment_2 {
+            self.attributes |= attributes::raw::DATA_ALIGNMENT_2;
+        }
+        if fv_attributes & fv::file::raw::attribute::FIXED != 0 {
+            self.attributes |= attributes::raw::FIXED;
+        }
+        Ok(())
+    }
+
     /// Returns `true` if the file has the data checksum attribute set.
     pub fn is_data_checksum(&self) -> bool {
         self.attributes & attributes::raw::CHECKSUM != 0
@@ -482,7 +577,7 @@ impl File {
             Ok(mem::size_of::<ffs::file::Header2>())
         } else {
             let mut section_iter = self.sections.iter().peekable();
-            let mut content_len = 0;
+            let mut content_len = self.content.as_ref().map_or(0, |content| content.len());
             while let Some(section) = &section_iter.next() {
                 let section_len = section.serialize()?.len();
                 content_len += section_len;
@@ -639,6 +734,7 @@ impl TryFrom<FileRef<'_>> for File {
             attributes: src.attributes_raw(),
             erase_polarity: src.erase_polarity(),
             sections: src.sections()?,
+            content: None,
         })
     }
 }
@@ -658,6 +754,7 @@ impl TryFrom<(FileRef<'_>, &dyn SectionExtractor)> for File {
             attributes: src.attributes_raw(),
             erase_polarity: src.erase_polarity(),
             sections,
+            content: None,
         })
     }
 }
@@ -34,6 +34,15 @@ pub trait SectionExtractor {
     /// Attempt to extract the content of `section` into a raw byte buffer that contains zero or
     /// more serialized sub-sections.
     fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError>;
+
+    /// Attempt to extract the content of `section` as with [`SectionExtractor::extract`], along with the
+    /// authentication status (see `ffs::section::auth_status`) that the extraction determined for it.
+    ///
+    /// The status is only applied to the sub-sections of GUID-defined sections with the
+    /// `GUIDED_SECTION_AUTH_STATUS_VALID` attribute. The default implementation reports no authentication status.
+    fn extract_with_authentication_status(&self, section: &Section) -> Result<(Vec<u8>, u32), FirmwareFileSystemError> {
+        self.extract(section).map(|data| (data, 0))
+    }
 }
 
 /// Produces a composed header and content buffer for a section.
@@ -247,6 +256,7 @@ pub struct Section {
     header: SectionHeader,
     data: SectionData,
     dirty: bool,
+    authentication_status: u32,
 }
 
 impl Section {
@@ -259,7 +269,12 @@ impl Section {
     pub fn new_from_header_with_data(header: SectionHeader, data: Vec<u8>) -> Result<Self, FirmwareFileSystemError> {
         //Pad sections need special handling due to having no section header.
         if let SectionHeader::Pad(_) = header {
-            Ok(Self { header, data: SectionData::Leaf(LeafSectionData { data }), dirty: false })
+            Ok(Self {
+                header,
+                data: SectionData::Leaf(LeafSectionData { data }),
+                dirty: false,
+                authentication_status: 0,
+            })
         } else {
             let mut buffer = header.serialize();
             buffer.extend(data);
@@ -267,6 +282,29 @@ impl Section {
         }
     }
 
+    /// Construct an encapsulation section that holds `sub_sections`.
+    ///
+    /// `header` must be a `Compression` or `GuidDefined` header. The section is dirty until it is composed with a
+    /// [`SectionComposer`] that produces its content from the sub-sections.
+    pub fn new_encapsulation(
+        header: SectionHeader,
+        sub_sections: Vec<Section>,
+    ) -> Result<Self, FirmwareFileSystemError> {
+        if !matches!(header, SectionHeader::Compression(_, _) | SectionHeader::GuidDefined(_, _, _)) {
+            Err(FirmwareFileSystemError::InvalidParameter)?;
+        }
+        Ok(Self {
+            header,
+            data: SectionData::Encapsulation(EncapsulationSectionData {
+                sub_sections,
+                data: Vec::new(),
+                extracted: true,
+            }),
+            dirty: true,
+            authentication_status: 0,
+        })
+    }
+
     /// Parse a serialized section from `buffer`.
     ///
     /// Validates the common and variant-specific headers, sets the content size accordingly, and
@@ -405,7 +443,7 @@ impl Section {
             _ => SectionData::Leaf(LeafSectionData { data: buffer[content_offset..section_size].to_vec() }),
         };
 
-        Ok(Section { header, data: section_data, dirty: false })
+        Ok(Section { header, data: section_data, dirty: false, authentication_status: 0 })
     }
 
     /// Borrow the logical header of this section.
@@ -413,6 +451,12 @@ impl Section {
         &self.header
     }
 
+    /// The authentication status (see `ffs::section::auth_status`) determined for this section by the extraction of
+    /// the encapsulation sections that contain it. Zero for sections that were not extracted.
+    pub fn authentication_status(&self) -> u32 {
+        self.authentication_status
+    }
+
     /// Whether this section is an encapsulation variant (i.e., capable of containing sub-sections).
     pub fn encapsulation(&self) -> bool {
         matches!(self.data, SectionData::Encapsulation(_))
@@ -463,7 +507,8 @@ impl Section {
 
     /// Compose this section (and any extracted children) using the provided composer.
     ///
-    /// On success, the section is marked clean and content is updated to the newly composed bytes.
+    /// On success, the section is marked clean and content is updated to the newly composed bytes. Encapsulation
+    /// sections that were never extracted have no sub-sections to compose from, so they keep their content.
     pub fn compose(&mut self, composer: &dyn SectionComposer) -> Result<(), FirmwareFileSystemError> {
         match &mut self.data {
             SectionData::Encapsulation(encapsulation) => {
@@ -476,7 +521,10 @@ impl Section {
 
         self.dirty = false;
 
-        let (header, content) = match self.data {
+        let (header, content) = match &self.data {
+            SectionData::Encapsulation(encapsulation) if !encapsulation.extracted => {
+                (self.header.clone(), encapsulation.data.clone())
+            }
             SectionData::Encapsulation(_) => composer.compose(self)?,
             SectionData::Leaf(_) => {
                 let content = self.try_content_as_slice()?.to_vec();
@@ -503,20 +551,33 @@ impl Section {
     ///
     /// If the extractor returns `Unsupported`, the method is a no-op. Otherwise, the returned
     /// bytes are parsed into immediate sub-sections and marked as extracted.
+    ///
+    /// Sub-sections inherit the authentication status of this section. For GUID-defined sections with the
+    /// `GUIDED_SECTION_AUTH_STATUS_VALID` attribute, the status reported by the extractor is added to it.
     pub fn extract(&mut self, extractor: &dyn SectionExtractor) -> Result<(), FirmwareFileSystemError> {
         if !matches!(&self.data, SectionData::Encapsulation(x) if !x.extracted) {
             return Ok(()); //nothing to do for non-encapsulation sections or already extracted encapsulation sections.
         }
 
-        let extracted_data = match extractor.extract(self) {
-            Err(FirmwareFileSystemError::Unsupported) => Vec::new(),
+        let (extracted_data, extracted_status) = match extractor.extract_with_authentication_status(self) {
+            Err(FirmwareFileSystemError::Unsupported) => (Vec::new(), 0),
             result => result?,
         };
 
+        let authentication_status = match &self.header {
+            SectionHeader::GuidDefined(guid_header, _, _)
+                if guid_header.attributes & section::header::GUIDED_SECTION_AUTH_STATUS_VALID != 0 =>
+            {
+                self.authentication_status | extracted_status
+            }
+            _ => self.authentication_status,
+        };
+
         let mut sections: Vec<Section> =
             SectionIterator::new(&extracted_data).collect::<Result<Vec<_>, FirmwareFileSystemError>>()?;
 
         for section in sections.iter_mut() {
+            section.authentication_status = authentication_status;
             section.extract(extractor)?;
         }
 
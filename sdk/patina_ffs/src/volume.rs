@@ -326,6 +326,37 @@ impl<'a> VolumeRef<'a> {
         self.fv_header.fv_length
     }
 
+    /// Byte offset from the start of the FV to the first file, following the header and extended header.
+    pub fn content_offset(&self) -> usize {
+        self.content_offset
+    }
+
+    /// Byte offset from the start of the FV to the free space that follows the last file.
+    ///
+    /// Deleted files still occupy space in the volume and are counted as used. Errors if a file in the volume cannot
+    /// be parsed.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use patina_ffs::volume::{Volume, VolumeRef};
+    /// use patina::pi::fw_fs::{fv::BlockMapEntry, fvb};
+    ///
+    /// let mut fv = Volume::new(vec![BlockMapEntry { num_blocks: 1, length: 4096 }]);
+    /// fv.set_attributes(fvb::attributes::raw::fvb2::ERASE_POLARITY);
+    /// fv.set_capacity(4096);
+    /// let bytes = fv.serialize().unwrap();
+    /// let fv_ref = VolumeRef::new(&bytes).unwrap();
+    /// assert_eq!(fv_ref.free_space_offset().unwrap(), fv_ref.content_offset());
+    /// ```
+    pub fn free_space_offset(&self) -> Result<usize, FirmwareFileSystemError> {
+        let mut files = FileRefIter::new(&self.data[self.content_offset..], self.erase_byte());
+        if let Some(err) = files.by_ref().find_map(|file| file.err()) {
+            return Err(err);
+        }
+        Ok((self.content_offset + files.next_offset).min(self.size() as usize))
+    }
+
     /// Iterate over contained FFS files as zero-copy [`FileRef`]s.
     ///
     /// PAD files are filtered out per PI spec. Parsing errors are surfaced as iterator items.
@@ -343,7 +374,8 @@ impl<'a> VolumeRef<'a> {
         self.fv_header.revision
     }
 
-    fn file_system_guid(&self) -> efi::Guid {
+    /// The file system GUID from the FV header (`FileSystemGuid`).
+    pub fn file_system_guid(&self) -> efi::Guid {
         self.fv_header.file_system_guid
     }
 }
@@ -385,41 +417,160 @@ impl<'a> Iterator for FileRefIter<'a> {
     type Item = Result<FileRef<'a>, FirmwareFileSystemError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.error {
-            return None;
-        }
-        if self.next_offset > self.data.len() {
-            return None;
-        }
-        if self.data[self.next_offset..].len() < mem::size_of::<file::Header>() {
-            return None;
-        }
-        if self.data[self.next_offset..self.next_offset + mem::size_of::<file::Header>()]
-            .iter()
-            .all(|&x| x == self.erase_byte)
-        {
-            return None;
-        }
-        let result = FileRef::new(&self.data[self.next_offset..]);
-        if let Ok(ref file) = result {
-            // per the PI spec, "Given a file F, the next file FvHeader is located at the next 8-byte aligned firmware volume
-            // offset following the last byte the file F"
-            match align_up(self.next_offset as u64 + file.size() as u64, 8) {
-                Ok(next_offset) => {
-                    self.next_offset = next_offset as usize;
+        loop {
+            if self.error {
+                return None;
+            }
+            if self.next_offset > self.data.len() {
+                return None;
+            }
+            if self.data[self.next_offset..].len() < mem::size_of::<file::Header>() {
+                return None;
+            }
+            if self.data[self.next_offset..self.next_offset + mem::size_of::<file::Header>()]
+                .iter()
+                .all(|&x| x == self.erase_byte)
+            {
+                return None;
+            }
+
+            // Files that were deleted, or whose write never completed, stay in the volume until it is reclaimed. Skip
+            // over them the way the PI spec describes: files with a valid header by their size, others by their header.
+            // SAFETY: the checks above ensure that a complete file header remains in the buffer.
+            let header = unsafe { ptr::read_unaligned(self.data[self.next_offset..].as_ptr() as *const file::Header) };
+            let skip = match crate::file::header_state(header.state, crate::file::infer_erase_polarity(header.state)) {
+                Some(file::State::HeaderValid | file::State::Deleted) => {
+                    match file_size(&self.data[self.next_offset..], &header) {
+                        Some(size) => Some(size),
+                        None => {
+                            self.error = true;
+                            return Some(Err(FirmwareFileSystemError::InvalidHeader));
+                        }
+                    }
                 }
-                Err(_) => {
-                    self.error = true;
-                    return Some(Err(FirmwareFileSystemError::DataCorrupt));
+                Some(file::State::HeaderConstruction | file::State::HeaderInvalid) => Some(file_header_size(&header)),
+                _ => None,
+            };
+            if let Some(skip) = skip {
+                if let Err(err) = self.advance(skip) {
+                    return Some(Err(err));
                 }
+                continue;
             }
-        } else {
-            self.error = true;
+
+            let result = FileRef::new(&self.data[self.next_offset..]);
+            if let Ok(ref file) = result {
+                if let Err(err) = self.advance(file.size()) {
+                    return Some(Err(err));
+                }
+                // A file marked for update is only superseded once its replacement, which always follows it, is valid.
+                if file.state() == file::State::MarkedForUpdate
+                    && FileRefIter::new(&self.data[self.next_offset..], self.erase_byte)
+                        .any(|x| x.is_ok_and(|x| x.name() == file.name() && x.state() == file::State::DataValid))
+                {
+                    continue;
+                }
+            } else {
+                self.error = true;
+            }
+            return Some(result);
         }
-        Some(result)
     }
 }
 
+impl FileRefIter<'_> {
+    // per the PI spec, "Given a file F, the next file FvHeader is located at the next 8-byte aligned firmware volume
+    // offset following the last byte the file F"
+    fn advance(&mut self, size: usize) -> Result<(), FirmwareFileSystemError> {
+        match align_up(self.next_offset as u64 + size as u64, 8) {
+            Ok(next_offset) => {
+                self.next_offset = next_offset as usize;
+                Ok(())
+            }
+            Err(_) => {
+                self.error = true;
+                Err(FirmwareFileSystemError::DataCorrupt)
+            }
+        }
+    }
+}
+
+// Size of the header of an FFS file; the extended header is used for large files.
+fn file_header_size(header: &file::Header) -> usize {
+    if header.attributes & ffs::attributes::raw::LARGE_FILE != 0 {
+        mem::size_of::<file::Header2>()
+    } else {
+        mem::size_of::<file::Header>()
+    }
+}
+
+// Size of an FFS file from its header, if the size is consistent with the header and fits in `data`.
+fn file_size(data: &[u8], header: &file::Header) -> Option<usize> {
+    let size = if header.attributes & ffs::attributes::raw::LARGE_FILE != 0 {
+        let extended_size = data.get(mem::size_of::<file::Header>()..mem::size_of::<file::Header2>())?;
+        u64::from_le_bytes(extended_size.try_into().ok()?).try_into().ok()?
+    } else {
+        u32::from_le_bytes([header.size[0], header.size[1], header.size[2], 0]) as usize
+    };
+    (size >= file_header_size(header) && size <= data.len()).then_some(size)
+}
+
+/// Build the PAD file that must precede `file` when it is placed at `offset` in a volume, if any.
+///
+/// The PAD file aligns the file content to the alignment specified in the file attributes. Returns `None` if the file
+/// content is already aligned at `offset`.
+///
+/// ## Examples
+///
+/// ```rust
+/// use patina::pi::fw_fs::{ffs, fv};
+/// use patina_ffs::{file::{File, FileRef}, volume::alignment_pad_file};
+/// use r_efi::efi;
+///
+/// let mut file = File::new_with_content(efi::Guid::from_bytes(&[1u8; 16]), ffs::file::raw::r#type::RAW, vec![0; 16]);
+/// file.set_fv_attributes(9).unwrap(); // 512-byte alignment
+/// let bytes = file.serialize().unwrap();
+/// let file_ref = FileRef::new(&bytes).unwrap();
+/// assert!(alignment_pad_file(512 - 24, &file_ref).unwrap().is_none());
+/// let pad = alignment_pad_file(0x48, &file_ref).unwrap().unwrap().serialize().unwrap();
+/// assert_eq!((0x48 + pad.len() + file_ref.content_offset()) % 512, 0);
+/// ```
+pub fn alignment_pad_file(offset: usize, file: &FileRef<'_>) -> Result<Option<File>, FirmwareFileSystemError> {
+    let required_content_alignment = file.fv_attributes() & fv::file::raw::attribute::ALIGNMENT;
+    let required_content_alignment: usize = 1 << required_content_alignment;
+
+    if (offset + file.content_offset()).is_multiple_of(required_content_alignment) {
+        return Ok(None);
+    }
+
+    //need to insert a pad file to ensure content is aligned to the required alignment specified in the
+    //file attributes.
+
+    //Per spec, max required_content_alignment is pad files is 16M (2^24). That means that pad file size
+    //will always be less than 16M so we can always use Header (instead of Header2) for pad header.
+    assert!(required_content_alignment < 0x1000000);
+
+    let pad_len_base = offset + mem::size_of::<ffs::file::Header>() + file.content_offset();
+    let rem = pad_len_base % required_content_alignment;
+    let pad_len = if rem == 0 { 0 } else { required_content_alignment - rem };
+
+    // check the padding math.
+    debug_assert_eq!(
+        (offset + mem::size_of::<ffs::file::Header>() + pad_len + file.content_offset()) % required_content_alignment,
+        0
+    );
+
+    let mut pad_file = File::new(efi::Guid::from_bytes(&[0xffu8; 16]), ffs::file::raw::r#type::FFS_PAD);
+    let pad_section = Section::new_from_header_with_data(
+        section::SectionHeader::Pad(pad_len.try_into().map_err(|_| FirmwareFileSystemError::InvalidHeader)?),
+        iter::repeat_n(0xffu8, pad_len).collect(),
+    )?;
+    pad_file.sections_mut().push(pad_section);
+    pad_file.set_erase_polarity(file.erase_polarity());
+
+    Ok(Some(pad_file))
+}
+
 enum Capacity {
     Unbounded,
     Size(usize),
@@ -458,6 +609,76 @@ impl Volume {
         self.files.iter()
     }
 
+    /// The FV attributes bitfield (`EFI_FVB_ATTRIBUTES_2`) written to the volume header.
+    pub fn attributes(&self) -> fvb::attributes::EfiFvbAttributes2 {
+        self.attributes
+    }
+
+    /// Set the FV attributes bitfield (`EFI_FVB_ATTRIBUTES_2`) written to the volume header.
+    ///
+    /// `EFI_FVB2_ERASE_POLARITY` also selects the byte used for padding and free space.
+    pub fn set_attributes(&mut self, attributes: fvb::attributes::EfiFvbAttributes2) {
+        self.attributes = attributes;
+    }
+
+    /// Set the file system GUID written to the volume header.
+    ///
+    /// Only `EFI_FIRMWARE_FILE_SYSTEM2_GUID` and `EFI_FIRMWARE_FILE_SYSTEM3_GUID` are accepted; FFSv2 volumes cannot
+    /// hold files of 16 MiB or more.
+    pub fn set_file_system_guid(&mut self, file_system_guid: efi::Guid) -> Result<(), FirmwareFileSystemError> {
+        if file_system_guid != ffs::guid::EFI_FIRMWARE_FILE_SYSTEM2_GUID
+            && file_system_guid != ffs::guid::EFI_FIRMWARE_FILE_SYSTEM3_GUID
+        {
+            Err(FirmwareFileSystemError::InvalidParameter)?;
+        }
+        self.file_system_guid = file_system_guid;
+        Ok(())
+    }
+
+    /// Attach an extended header naming the volume `fv_name`, followed by the already serialized `entries`.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust
+    /// use patina_ffs::volume::{Volume, VolumeRef};
+    /// use patina::pi::fw_fs::fv::BlockMapEntry;
+    /// use r_efi::efi;
+    ///
+    /// let fv_name = efi::Guid::from_bytes(&[7u8; 16]);
+    /// let mut fv = Volume::new(vec![BlockMapEntry { num_blocks: 1, length: 4096 }]);
+    /// fv.set_ext_header(fv_name, Vec::new()).unwrap();
+    /// let bytes = fv.serialize().unwrap();
+    /// assert_eq!(VolumeRef::new(&bytes).unwrap().fv_name(), Some(fv_name));
+    /// ```
+    pub fn set_ext_header(&mut self, fv_name: efi::Guid, entries: Vec<u8>) -> Result<(), FirmwareFileSystemError> {
+        let ext_header_size = (mem::size_of::<fv::ExtHeader>() + entries.len())
+            .try_into()
+            .map_err(|_| FirmwareFileSystemError::InvalidParameter)?;
+        self.ext_header = Some((fv::ExtHeader { fv_name, ext_header_size }, entries));
+        Ok(())
+    }
+
+    /// Pad the serialized volume with erased bytes up to `size`, leaving free space for files written later.
+    ///
+    /// Volumes whose content is larger than `size` are not truncated.
+    ///
+    /// ## Examples
+    ///
+    /// ```rust no_run
+    /// use patina_ffs::volume::{Volume, VolumeRef};
+    /// use patina::pi::fw_fs::{fv::BlockMapEntry, fvb};
+    ///
+    /// let mut fv = Volume::new(vec![BlockMapEntry { num_blocks: 4, length: 4096 }]);
+    /// fv.set_attributes(fvb::attributes::raw::fvb2::ERASE_POLARITY | fvb::attributes::raw::fvb2::WRITE_STATUS);
+    /// fv.set_capacity(4 * 4096);
+    /// let bytes = fv.serialize().unwrap();
+    /// assert_eq!(bytes.len(), 4 * 4096);
+    /// assert_eq!(VolumeRef::new(&bytes).unwrap().erase_byte(), 0xff);
+    /// ```
+    pub fn set_capacity(&mut self, size: usize) {
+        self.capacity = Capacity::Size(size);
+    }
+
     /// Mutable access to the list of FFS files contained in this FV.
     ///
     /// ## Examples
@@ -594,37 +815,7 @@ impl Volume {
             let file_ref = FileRef::new(file_buffer)?;
 
             //check if a pad file needs to be inserted to align the file content.
-            let required_content_alignment = file_ref.fv_attributes() & fv::file::raw::attribute::ALIGNMENT;
-            let required_content_alignment: usize = 1 << required_content_alignment;
-
-            if (fv_buffer.len() + file_ref.content_offset()) % required_content_alignment != 0 {
-                //need to insert a pad file to ensure content is aligned to the required alignment specified in the
-                //file attributes.
-
-                //Per spec, max required_content_alignment is pad files is 16M (2^24). That means that pad file size
-                //will always be less than 16M so we can always use Header (instead of Header2) for pad header.
-                assert!(required_content_alignment < 0x1000000);
-
-                let pad_len_base = fv_buffer.len() + mem::size_of::<ffs::file::Header>() + file_ref.content_offset();
-                let rem = pad_len_base % required_content_alignment;
-                let pad_len = if rem == 0 { 0 } else { required_content_alignment - rem };
-
-                // check the padding math.
-                debug_assert_eq!(
-                    (fv_buffer.len() + mem::size_of::<ffs::file::Header>() + pad_len + file_ref.content_offset())
-                        % required_content_alignment,
-                    0
-                );
-
-                let mut pad_file = File::new(efi::Guid::from_bytes(&[0xffu8; 16]), ffs::file::raw::r#type::FFS_PAD);
-                let pad_section = Section::new_from_header_with_data(
-                    section::SectionHeader::Pad(
-                        pad_len.try_into().map_err(|_| FirmwareFileSystemError::InvalidHeader)?,
-                    ),
-                    iter::repeat_n(0xffu8, pad_len).collect(),
-                )?;
-                pad_file.sections_mut().push(pad_section);
-
+            if let Some(pad_file) = alignment_pad_file(fv_buffer.len(), &file_ref)? {
                 fv_buffer.extend(pad_file.serialize()?);
             }
 
@@ -1,4 +1,4 @@
-//! Module for Brotli decompression.
+//! Module for Brotli decompression and compression.
 //!
 //! ## License
 //!
@@ -6,15 +6,37 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+#[cfg(feature = "brotli-compose")]
+use ::brotli::enc::{
+    BrotliAlloc, BrotliEncoderMaxCompressedSize,
+    encode::{BrotliEncoderOperation, BrotliEncoderStateStruct},
+};
 use alloc::{boxed::Box, vec, vec::Vec};
 use alloc_no_stdlib::{self, SliceWrapper, SliceWrapperMut, define_index_ops_mut};
 use brotli_decompressor::{BrotliDecompressStream, BrotliResult, BrotliState, HuffmanCode};
+use core::mem;
 use patina::pi::fw_fs;
 use patina_ffs::{
     FirmwareFileSystemError,
     section::{Section, SectionExtractor, SectionHeader},
 };
 
+#[cfg(feature = "brotli-compose")]
+use crate::{guid_defined_header, sub_sections_content};
+#[cfg(feature = "brotli-compose")]
+use patina_ffs::section::SectionComposer;
+
+/// Size of the Brotli section header: the decompressed size and the scratch size, both as `u64`.
+const BROTLI_HEADER_SIZE: usize = 16;
+/// Compression quality used by [BrotliSectionComposer]; the default of the EDK2 `BrotliCompress` tool.
+#[cfg(feature = "brotli-compose")]
+const BROTLI_QUALITY: i32 = 9;
+/// Window size (log2) used by [BrotliSectionComposer]; the default of the EDK2 `BrotliCompress` tool.
+#[cfg(feature = "brotli-compose")]
+const BROTLI_WINDOW_BITS: i32 = 22;
+/// Gap the EDK2 decompressor leaves after each allocation it makes from the scratch buffer.
+const SCRATCH_GAP_SIZE: usize = 0x1000;
+
 //Rebox and HeapAllocator exist to satisfy BrotliDecompress custom allocation requirements.
 //They essentially wrap Box for heap allocations.
 struct Rebox<T>(Box<[T]>);
@@ -40,16 +62,71 @@ impl<T> alloc_no_stdlib::SliceWrapperMut<T> for Rebox<T> {
 
 struct HeapAllocator<T: Clone> {
     pub default_value: T,
+    // Scratch space the EDK2 decompressor would need for the allocations made so far.
+    pub scratch_size: usize,
+}
+
+impl<T: Clone> HeapAllocator<T> {
+    fn new(default_value: T) -> Self {
+        Self { default_value, scratch_size: 0 }
+    }
 }
 
 impl<T: Clone> alloc_no_stdlib::Allocator<T> for HeapAllocator<T> {
     type AllocatedMemory = Rebox<T>;
     fn alloc_cell(self: &mut HeapAllocator<T>, len: usize) -> Rebox<T> {
+        self.scratch_size += len * mem::size_of::<T>() + SCRATCH_GAP_SIZE;
         Rebox(vec![self.default_value.clone(); len].into_boxed_slice())
     }
     fn free_cell(self: &mut HeapAllocator<T>, _data: Rebox<T>) {}
 }
 
+//EncoderAllocator wraps Box for the allocations of the Brotli encoder, which needs an allocator for each of the types
+//it works with.
+#[cfg(feature = "brotli-compose")]
+#[derive(Default)]
+struct EncoderAllocator;
+
+#[cfg(feature = "brotli-compose")]
+impl<T: Clone + Default> alloc_no_stdlib::Allocator<T> for EncoderAllocator {
+    type AllocatedMemory = Rebox<T>;
+    fn alloc_cell(&mut self, len: usize) -> Rebox<T> {
+        Rebox(vec![T::default(); len].into_boxed_slice())
+    }
+    fn free_cell(&mut self, _data: Rebox<T>) {}
+}
+
+#[cfg(feature = "brotli-compose")]
+impl BrotliAlloc for EncoderAllocator {}
+
+/// Decompresses a Brotli stream of `out_size` bytes, returning the data and the scratch size it requires.
+fn decompress(data: &[u8], out_size: usize) -> Result<(Vec<u8>, usize), FirmwareFileSystemError> {
+    let mut brotli_state = BrotliState::new(
+        HeapAllocator::<u8>::new(0),
+        HeapAllocator::<u32>::new(0),
+        HeapAllocator::<HuffmanCode>::new(Default::default()),
+    );
+    let mut out_data = vec![0u8; out_size];
+    let mut out_data_size = 0;
+    let result = BrotliDecompressStream(
+        &mut data.len(),
+        &mut 0,
+        data,
+        &mut out_data.len(),
+        &mut 0,
+        out_data.as_mut_slice(),
+        &mut out_data_size,
+        &mut brotli_state,
+    );
+
+    if !matches!(result, BrotliResult::ResultSuccess) {
+        return Err(FirmwareFileSystemError::DataCorrupt);
+    }
+    let scratch_size =
+        brotli_state.alloc_u8.scratch_size + brotli_state.alloc_u32.scratch_size + brotli_state.alloc_hc.scratch_size;
+    Ok((out_data, scratch_size))
+}
+
 /// Provides decompression for Brotli GUIDed sections.
 #[derive(Default, Clone, Copy)]
 pub struct BrotliSectionExtractor;
@@ -71,30 +148,71 @@ impl SectionExtractor for BrotliSectionExtractor {
             let out_size = u64::from_le_bytes(data[0..8].try_into().unwrap());
             let _scratch_size = u64::from_le_bytes(data[8..16].try_into().unwrap());
 
-            let mut brotli_state = BrotliState::new(
-                HeapAllocator::<u8> { default_value: 0 },
-                HeapAllocator::<u32> { default_value: 0 },
-                HeapAllocator::<HuffmanCode> { default_value: Default::default() },
-            );
-            let in_data = &data[16..];
-            let mut out_data = vec![0u8; out_size as usize];
-            let mut out_data_size = 0;
-            let result = BrotliDecompressStream(
-                &mut in_data.len(),
-                &mut 0,
-                &data[16..],
-                &mut out_data.len(),
-                &mut 0,
-                out_data.as_mut_slice(),
-                &mut out_data_size,
-                &mut brotli_state,
-            );
-
-            if matches!(result, BrotliResult::ResultSuccess) {
-                return Ok(out_data);
-            } else {
-                return Err(FirmwareFileSystemError::DataCorrupt);
+            let (out_data, _) = decompress(&data[BROTLI_HEADER_SIZE..], out_size as usize)?;
+            return Ok(out_data);
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+}
+
+/// Provides compression for Brotli GUIDed sections.
+///
+/// The compressed stream is preceded by the decompressed size and the scratch size the EDK2 decompressor needs,
+/// which is measured by decompressing the stream again as the EDK2 `BrotliCompress` tool does.
+#[cfg(feature = "brotli-compose")]
+#[derive(Default, Clone, Copy)]
+pub struct BrotliSectionComposer;
+
+#[cfg(feature = "brotli-compose")]
+impl BrotliSectionComposer {
+    /// Creates a new `BrotliSectionComposer` instance.
+    #[coverage(off)]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(feature = "brotli-compose")]
+impl SectionComposer for BrotliSectionComposer {
+    fn compose(&self, section: &Section) -> Result<(SectionHeader, Vec<u8>), FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == fw_fs::guid::BROTLI_SECTION
+        {
+            let content = sub_sections_content(section)?;
+
+            let mut encoder = BrotliEncoderStateStruct::new(EncoderAllocator);
+            encoder.params.quality = BROTLI_QUALITY;
+            encoder.params.lgwin = BROTLI_WINDOW_BITS;
+
+            let mut compressed = vec![0u8; BROTLI_HEADER_SIZE + BrotliEncoderMaxCompressedSize(content.len())];
+            let mut available_in = content.len();
+            let mut in_offset = 0;
+            let mut available_out = compressed.len() - BROTLI_HEADER_SIZE;
+            let mut out_offset = BROTLI_HEADER_SIZE;
+            while !encoder.is_finished() {
+                let result = encoder.compress_stream(
+                    BrotliEncoderOperation::BROTLI_OPERATION_FINISH,
+                    &mut available_in,
+                    &content,
+                    &mut in_offset,
+                    &mut available_out,
+                    &mut compressed,
+                    &mut out_offset,
+                    &mut None,
+                    &mut |_, _, _, _| (),
+                );
+                if !result || (available_out == 0 && !encoder.is_finished()) {
+                    Err(FirmwareFileSystemError::ComposeFailed)?;
+                }
             }
+            compressed.truncate(out_offset);
+
+            let (_, scratch_size) = decompress(&compressed[BROTLI_HEADER_SIZE..], content.len())
+                .map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+            compressed[0..8].copy_from_slice(&(content.len() as u64).to_le_bytes());
+            compressed[8..16].copy_from_slice(&(scratch_size as u64).to_le_bytes());
+
+            return Ok((guid_defined_header(guid_header, Vec::new(), compressed.len())?, compressed));
         }
         Err(FirmwareFileSystemError::Unsupported)
     }
@@ -120,4 +238,27 @@ mod tests {
         let result = result.unwrap();
         assert_eq!(result, b"Hello, World!");
     }
+
+    #[test]
+    #[cfg(feature = "brotli-compose")]
+    fn test_brotli_composer_round_trip() {
+        use crate::tests::{assert_round_trip, create_encapsulation_section};
+        use patina::pi::fw_fs::ffs::section::header::{GUIDED_SECTION_PROCESSING_REQUIRED, GuidDefined};
+
+        let header = GuidDefined {
+            section_definition_guid: fw_fs::guid::BROTLI_SECTION,
+            data_offset: 0,
+            attributes: GUIDED_SECTION_PROCESSING_REQUIRED,
+        };
+        let mut section = create_encapsulation_section(SectionHeader::GuidDefined(header, Vec::new(), 0));
+        assert_round_trip(section.clone(), &BrotliSectionComposer, &BrotliSectionExtractor);
+
+        section.compose(&BrotliSectionComposer).unwrap();
+        let content = section.try_content_as_slice().unwrap();
+        let out_size = u64::from_le_bytes(content[0..8].try_into().unwrap());
+        let scratch_size = u64::from_le_bytes(content[8..16].try_into().unwrap());
+        assert_eq!(out_size as usize, crate::sub_sections_content(&section).unwrap().len());
+        assert!(scratch_size > 0);
+        assert!(content.len() < out_size as usize / 4);
+    }
 }
@@ -1,4 +1,4 @@
-//! Module for a composite of brotli, uefi, and crc32 decompression.
+//! Module for a composite of brotli, uefi, and crc32 decompression, and of the matching compression.
 //!
 //! ## License
 //!
@@ -8,15 +8,21 @@
 //!
 use patina_ffs::{
     FirmwareFileSystemError,
-    section::{Section, SectionExtractor},
+    section::{Section, SectionComposer, SectionExtractor, SectionHeader},
 };
 
+#[cfg(feature = "brotli-compose")]
+use crate::BrotliSectionComposer;
 #[cfg(feature = "brotli")]
 use crate::BrotliSectionExtractor;
-#[cfg(feature = "crc32")]
-use crate::Crc32SectionExtractor;
+#[cfg(feature = "lzma-compose")]
+use crate::LzmaSectionComposer;
 #[cfg(feature = "lzma")]
 use crate::LzmaSectionExtractor;
+#[cfg(feature = "tiano")]
+use crate::TianoSectionComposer;
+#[cfg(feature = "crc32")]
+use crate::{Crc32SectionComposer, Crc32SectionExtractor};
 
 /// Provides a composite section extractor that combines all section extractors based on enabled feature flags.
 #[derive(Clone, Copy)]
@@ -82,6 +88,79 @@ impl SectionExtractor for CompositeSectionExtractor {
     }
 }
 
+/// Provides a composite section composer that combines all section composers based on enabled feature flags.
+#[derive(Clone, Copy)]
+pub struct CompositeSectionComposer {
+    #[cfg(feature = "brotli-compose")]
+    brotli: BrotliSectionComposer,
+    #[cfg(feature = "crc32")]
+    crc32: Crc32SectionComposer,
+    #[cfg(feature = "lzma-compose")]
+    lzma: LzmaSectionComposer,
+    #[cfg(feature = "tiano")]
+    tiano: TianoSectionComposer,
+}
+
+impl Default for CompositeSectionComposer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompositeSectionComposer {
+    /// Creates a new instance of the composite section composer.
+    pub const fn new() -> Self {
+        Self {
+            #[cfg(feature = "brotli-compose")]
+            brotli: BrotliSectionComposer {},
+            #[cfg(feature = "crc32")]
+            crc32: Crc32SectionComposer {},
+            #[cfg(feature = "lzma-compose")]
+            lzma: LzmaSectionComposer {},
+            #[cfg(feature = "tiano")]
+            tiano: TianoSectionComposer {},
+        }
+    }
+}
+
+impl SectionComposer for CompositeSectionComposer {
+    fn compose(&self, _section: &Section) -> Result<(SectionHeader, alloc::vec::Vec<u8>), FirmwareFileSystemError> {
+        #[cfg(feature = "brotli-compose")]
+        {
+            match self.brotli.compose(_section) {
+                Err(FirmwareFileSystemError::Unsupported) => (),
+                result => return result,
+            }
+        }
+
+        #[cfg(feature = "crc32")]
+        {
+            match self.crc32.compose(_section) {
+                Err(FirmwareFileSystemError::Unsupported) => (),
+                result => return result,
+            }
+        }
+
+        #[cfg(feature = "lzma-compose")]
+        {
+            match self.lzma.compose(_section) {
+                Err(FirmwareFileSystemError::Unsupported) => (),
+                result => return result,
+            }
+        }
+
+        #[cfg(feature = "tiano")]
+        {
+            match self.tiano.compose(_section) {
+                Err(FirmwareFileSystemError::Unsupported) => (),
+                result => return result,
+            }
+        }
+
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
@@ -136,4 +215,42 @@ mod tests {
 
         assert_eq!(result, b"Hello, World!");
     }
+
+    #[test]
+    #[cfg(all(feature = "brotli-compose", feature = "crc32", feature = "lzma-compose"))]
+    fn test_composite_round_trip() {
+        use crate::tests::{assert_round_trip, create_encapsulation_section};
+        use alloc::vec::Vec;
+        use patina::pi::fw_fs::{
+            ffs::section::header::{GUIDED_SECTION_PROCESSING_REQUIRED, GuidDefined},
+            guid::{BROTLI_SECTION, CRC32_SECTION, LZMA_SECTION},
+        };
+
+        for guid in [BROTLI_SECTION, CRC32_SECTION, LZMA_SECTION] {
+            let header = GuidDefined {
+                section_definition_guid: guid,
+                data_offset: 0,
+                attributes: GUIDED_SECTION_PROCESSING_REQUIRED,
+            };
+            let section = create_encapsulation_section(SectionHeader::GuidDefined(header, Vec::new(), 0));
+            assert_round_trip(section, &CompositeSectionComposer::default(), &CompositeSectionExtractor::default());
+        }
+    }
+
+    #[test]
+    fn test_composite_composer_unsupported() {
+        use crate::tests::create_encapsulation_section;
+        use alloc::vec::Vec;
+        use patina::pi::fw_fs::ffs::section::header::GuidDefined;
+        use r_efi::efi;
+
+        let header = GuidDefined {
+            section_definition_guid: efi::Guid::from_fields(0x12345678, 0x1234, 0x5678, 0x12, 0x34, &[0x56; 6]),
+            data_offset: 0,
+            attributes: 0,
+        };
+        let section = create_encapsulation_section(SectionHeader::GuidDefined(header, Vec::new(), 0));
+        let result = CompositeSectionComposer::default().compose(&section);
+        assert!(matches!(result, Err(FirmwareFileSystemError::Unsupported)));
+    }
 }
@@ -1,4 +1,4 @@
-//! Module for crc32 section decompression.
+//! Module for crc32 section decompression and composition.
 //!
 //! ## License
 //!
@@ -6,12 +6,15 @@
 //!
 //! SPDX-License-Identifier: Apache-2.0
 //!
+use alloc::vec::Vec;
 use patina::pi::fw_fs;
 use patina_ffs::{
     FirmwareFileSystemError,
-    section::{SectionExtractor, SectionHeader},
+    section::{Section, SectionComposer, SectionExtractor, SectionHeader},
 };
 
+use crate::{guid_defined_header, sub_sections_content};
+
 /// Provides extraction for CRC32 sections.
 #[derive(Default, Clone, Copy)]
 pub struct Crc32SectionExtractor;
@@ -45,6 +48,33 @@ impl SectionExtractor for Crc32SectionExtractor {
     }
 }
 
+/// Provides composition for CRC32 sections.
+///
+/// The sub-sections are stored as-is, with the CRC32 of the content as the GUID-specific header data.
+#[derive(Default, Clone, Copy)]
+pub struct Crc32SectionComposer;
+
+impl Crc32SectionComposer {
+    /// Creates a new `Crc32SectionComposer` instance.
+    #[coverage(off)]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl SectionComposer for Crc32SectionComposer {
+    fn compose(&self, section: &Section) -> Result<(SectionHeader, Vec<u8>), FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == fw_fs::guid::CRC32_SECTION
+        {
+            let content = sub_sections_content(section)?;
+            let crc32 = crc32fast::hash(&content).to_le_bytes().to_vec();
+            return Ok((guid_defined_header(guid_header, crc32, content.len())?, content));
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
@@ -113,4 +143,25 @@ mod tests {
 
         assert!(matches!(result, Err(FirmwareFileSystemError::Unsupported)));
     }
+
+    #[test]
+    fn test_crc32_composer_round_trip() {
+        use crate::tests::{assert_round_trip, create_encapsulation_section};
+        use patina::pi::fw_fs::ffs::section::header::GUIDED_SECTION_AUTH_STATUS_VALID;
+
+        let header = GuidDefined {
+            section_definition_guid: fw_fs::guid::CRC32_SECTION,
+            data_offset: 0,
+            attributes: GUIDED_SECTION_AUTH_STATUS_VALID,
+        };
+        let mut section = create_encapsulation_section(SectionHeader::GuidDefined(header, alloc::vec::Vec::new(), 0));
+        assert_round_trip(section.clone(), &Crc32SectionComposer, &Crc32SectionExtractor);
+
+        section.compose(&Crc32SectionComposer).unwrap();
+        let SectionHeader::GuidDefined(header, crc32, _) = section.header() else {
+            panic!("Expected GUID-defined header")
+        };
+        assert_eq!(crc32.as_slice(), crc32fast::hash(section.try_content_as_slice().unwrap()).to_le_bytes());
+        assert_eq!({ header.data_offset } as usize, core::mem::size_of::<GuidDefined>() + 4 + 4);
+    }
 }
@@ -1,6 +1,7 @@
 //! # Section Extractor Implementations
 //!
-//! This crate provides a set of Implementations for the `patina::pi::fw_fs::SectionExtractor` trait.
+//! This crate provides a set of Implementations for the `patina_ffs::section::SectionExtractor` trait, along with
+//! the matching `patina_ffs::section::SectionComposer` implementations used to build encapsulation sections.
 //!
 //! ## Features
 //!
@@ -8,10 +9,20 @@
 //! implementation of the `SectionExtractorLib` trait. The crate is configured in this manner to
 //! reduce compilation times, by only compiling the necessary implementations.
 //! - `brotli`: Enables the `SectionExtractorLibBrotli` implementation.
+//! - `brotli-compose`: Enables the `BrotliSectionComposer`, which pulls in the `brotli` encoder.
 //! - `crc32`: Enables the `Crc32SectionExtractor` implementation to validate CRC32 GUID-defined
-//!   sections and return the verified payload.
+//!   sections and return the verified payload, and the `Crc32SectionComposer` that produces them.
 //! - `lzma`: Enables the `LzmaSectionExtractor` implementation for GUID-defined LZMA compressed
 //!   sections.
+//! - `lzma-compose`: Enables the `LzmaSectionComposer`, which pulls in the `lzma-rust2` encoder.
+//! - `tiano`: Enables the `TianoSectionComposer` for standard compression sections and GUID-defined
+//!   Tiano compressed sections. The matching extractor is provided by the core.
+//!
+//! The composers are only needed by build tools, so `brotli-compose`, `lzma-compose` and `tiano` are not enabled by
+//! default and firmware builds do not compile the encoders.
+//!
+//! The `SignedSectionExtractor` verifies RSA2048/SHA256 and PKCS7 signed sections against a platform key set and
+//! is always available, as the cryptography it needs is provided by the platform through `SignedSectionCrypto`.
 //!
 //! ## License
 //!
@@ -25,34 +36,121 @@ extern crate alloc;
 
 #[cfg(feature = "brotli")]
 mod brotli;
+#[cfg(feature = "brotli-compose")]
+pub use brotli::BrotliSectionComposer;
 #[cfg(feature = "brotli")]
 pub use brotli::BrotliSectionExtractor;
 
 #[cfg(feature = "crc32")]
 mod crc32;
 #[cfg(feature = "crc32")]
-pub use crc32::Crc32SectionExtractor;
+pub use crc32::{Crc32SectionComposer, Crc32SectionExtractor};
 
 #[cfg(feature = "lzma")]
 mod lzma;
+#[cfg(feature = "lzma-compose")]
+pub use lzma::LzmaSectionComposer;
 #[cfg(feature = "lzma")]
 pub use lzma::LzmaSectionExtractor;
 
+#[cfg(feature = "tiano")]
+mod tiano;
+#[cfg(feature = "tiano")]
+pub use tiano::TianoSectionComposer;
+
 mod composite;
-pub use composite::CompositeSectionExtractor;
+pub use composite::{CompositeSectionComposer, CompositeSectionExtractor};
 
 mod null;
 pub use null::NullSectionExtractor;
 
+mod signed;
+pub use signed::{SignedSectionConfig, SignedSectionCrypto, SignedSectionExtractor};
+
+#[cfg(any(feature = "brotli-compose", feature = "crc32", feature = "lzma-compose", feature = "tiano"))]
+use alloc::vec::Vec;
+#[cfg(any(feature = "brotli-compose", feature = "crc32", feature = "lzma-compose", feature = "tiano"))]
+use patina::pi::fw_fs::ffs::section::header::GuidDefined;
+#[cfg(any(feature = "brotli-compose", feature = "crc32", feature = "lzma-compose", feature = "tiano"))]
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionHeader},
+};
+
+/// Serializes the sub-sections of an encapsulation section into the content a composer encodes.
+///
+/// Each sub-section starts on a 4-byte boundary relative to the start of the content, as the section iterator
+/// expects when the content is extracted again.
+#[cfg(any(feature = "brotli-compose", feature = "crc32", feature = "lzma-compose", feature = "tiano"))]
+pub(crate) fn sub_sections_content(section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+    let mut content = Vec::new();
+    for sub_section in section.sub_sections() {
+        content.resize(content.len().next_multiple_of(4), 0);
+        content.extend_from_slice(&sub_section.serialize()?);
+    }
+    Ok(content)
+}
+
+/// Builds the header of a composed GUID-defined section from the original `guid_header`.
+///
+/// `data_offset` is recomputed to cover `guid_data` and, for sections of 16MB or more, the extended size field.
+#[cfg(any(feature = "brotli-compose", feature = "crc32", feature = "lzma-compose", feature = "tiano"))]
+pub(crate) fn guid_defined_header(
+    guid_header: &GuidDefined,
+    guid_data: Vec<u8>,
+    content_size: usize,
+) -> Result<SectionHeader, FirmwareFileSystemError> {
+    let mut header = SectionHeader::GuidDefined(*guid_header, guid_data, 0);
+    header.set_content_size(content_size)?;
+    let data_offset = header.content_offset().try_into().map_err(|_| FirmwareFileSystemError::InvalidParameter)?;
+    if let SectionHeader::GuidDefined(guid_header, _, _) = &mut header {
+        guid_header.data_offset = data_offset;
+    }
+    Ok(header)
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
     use alloc::{vec, vec::Vec};
     use patina::pi::fw_fs::{
-        ffs::section::header::GuidDefined,
+        ffs::section::{header::GuidDefined, raw_type},
         guid::{BROTLI_SECTION, CRC32_SECTION, LZMA_SECTION},
     };
-    use patina_ffs::section::{Section, SectionHeader};
+    use patina_ffs::section::{Section, SectionComposer, SectionExtractor, SectionHeader};
+
+    /// Creates an encapsulation section with the given header around raw sections whose sizes are not multiples of
+    /// 4, so that composing it needs alignment padding between them.
+    pub(crate) fn create_encapsulation_section(header: SectionHeader) -> Section {
+        let sub_sections = [b"Hello, World!".to_vec(), vec![0x5a; 0x1001], (0..=255u8).cycle().take(0x2345).collect()]
+            .into_iter()
+            .map(|data| {
+                let header = SectionHeader::Standard(raw_type::RAW, data.len() as u32);
+                Section::new_from_header_with_data(header, data).expect("Failed to create test section")
+            })
+            .collect();
+        Section::new_encapsulation(header, sub_sections).expect("Failed to create encapsulation section")
+    }
+
+    /// Composes `section` and checks that extracting the serialized result restores its sub-sections.
+    pub(crate) fn assert_round_trip(
+        mut section: Section,
+        composer: &dyn SectionComposer,
+        extractor: &dyn SectionExtractor,
+    ) {
+        let expected: Vec<Vec<u8>> =
+            section.sub_sections().map(|sub_section| sub_section.serialize().unwrap()).collect();
+
+        section.compose(composer).expect("Failed to compose section");
+        let mut composed =
+            Section::new_from_buffer(&section.serialize().unwrap()).expect("Composed section should parse");
+        assert_eq!(composed.header().content_offset(), section.header().content_offset());
+        composed.extract(extractor).expect("Composed section should extract");
+
+        let actual: Vec<Vec<u8>> =
+            composed.sub_sections().map(|sub_section| sub_section.serialize().unwrap()).collect();
+        assert_eq!(actual, expected);
+    }
 
     /// Constructs a section with the specified GUID and payload, prepending
     /// the required 16-byte header (out_size + scratch_size) for Brotli sections.
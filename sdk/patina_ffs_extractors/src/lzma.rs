@@ -1,4 +1,4 @@
-//! Module for LZMA decompression.
+//! Module for LZMA decompression and compression.
 //!
 //! ## License
 //!
@@ -8,6 +8,8 @@
 //!
 use alloc::vec::Vec;
 use core::result::Result;
+#[cfg(feature = "lzma-compose")]
+use lzma_rust2::{LzmaOptions, LzmaWriter, Write};
 use patina_ffs::{
     FirmwareFileSystemError,
     section::{Section, SectionExtractor, SectionHeader},
@@ -16,11 +18,20 @@ use r_efi::efi;
 
 use patina_lzma_rs::io::Cursor;
 
+#[cfg(feature = "lzma-compose")]
+use crate::{guid_defined_header, sub_sections_content};
+#[cfg(feature = "lzma-compose")]
+use patina_ffs::section::SectionComposer;
+
 pub const LZMA_SECTION_GUID: efi::Guid =
     efi::Guid::from_fields(0xEE4E5898, 0x3914, 0x4259, 0x9D, 0x6E, &[0xDC, 0x7B, 0xD7, 0x94, 0x03, 0xCF]);
 
 pub const LZMA_UNKNOWN_UNPACKED_SIZE_MAGIC_VALUE: u64 = 0xFFFF_FFFF_FFFF_FFFF;
 
+/// Compression preset used by [LzmaSectionComposer]; the `xz` default.
+#[cfg(feature = "lzma-compose")]
+const LZMA_COMPRESSION_PRESET: u32 = 6;
+
 /// Provides decompression for LZMA GUIDed sections.
 #[derive(Default, Clone, Copy)]
 pub struct LzmaSectionExtractor;
@@ -59,6 +70,43 @@ impl SectionExtractor for LzmaSectionExtractor {
     }
 }
 
+/// Provides compression for LZMA GUIDed sections.
+///
+/// The content is stored as an `.lzma` stream with the unpacked size in its header, as produced by the EDK2
+/// `LzmaCompress` tool.
+#[cfg(feature = "lzma-compose")]
+#[derive(Default, Clone, Copy)]
+pub struct LzmaSectionComposer;
+
+#[cfg(feature = "lzma-compose")]
+impl LzmaSectionComposer {
+    /// Creates a new `LzmaSectionComposer` instance.
+    #[coverage(off)]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(feature = "lzma-compose")]
+impl SectionComposer for LzmaSectionComposer {
+    fn compose(&self, section: &Section) -> Result<(SectionHeader, Vec<u8>), FirmwareFileSystemError> {
+        if let SectionHeader::GuidDefined(guid_header, _, _) = section.header()
+            && guid_header.section_definition_guid == LZMA_SECTION_GUID
+        {
+            let content = sub_sections_content(section)?;
+
+            let options = LzmaOptions::with_preset(LZMA_COMPRESSION_PRESET);
+            let mut writer = LzmaWriter::new_use_header(Vec::new(), &options, Some(content.len() as u64))
+                .map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+            writer.write_all(&content).map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+            let compressed = writer.finish().map_err(|_| FirmwareFileSystemError::ComposeFailed)?;
+
+            return Ok((guid_defined_header(guid_header, Vec::new(), compressed.len())?, compressed));
+        }
+        Err(FirmwareFileSystemError::Unsupported)
+    }
+}
+
 #[cfg(test)]
 #[coverage(off)]
 mod tests {
@@ -135,4 +183,25 @@ mod tests {
 
         assert!(matches!(result, Err(FirmwareFileSystemError::Unsupported)));
     }
+
+    #[test]
+    #[cfg(feature = "lzma-compose")]
+    fn test_lzma_composer_round_trip() {
+        use crate::tests::{assert_round_trip, create_encapsulation_section};
+        use patina::pi::fw_fs::ffs::section::header::GUIDED_SECTION_PROCESSING_REQUIRED;
+
+        let header = GuidDefined {
+            section_definition_guid: LZMA_SECTION_GUID,
+            data_offset: 0,
+            attributes: GUIDED_SECTION_PROCESSING_REQUIRED,
+        };
+        let mut section = create_encapsulation_section(SectionHeader::GuidDefined(header, Vec::new(), 0));
+        assert_round_trip(section.clone(), &LzmaSectionComposer, &LzmaSectionExtractor);
+
+        section.compose(&LzmaSectionComposer).unwrap();
+        let content = section.try_content_as_slice().unwrap();
+        let unpacked_size = u64::from_le_bytes(content[5..13].try_into().unwrap());
+        assert_eq!(unpacked_size as usize, crate::sub_sections_content(&section).unwrap().len());
+        assert!(content.len() < unpacked_size as usize / 4);
+    }
 }
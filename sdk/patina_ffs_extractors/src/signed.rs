@@ -0,0 +1,433 @@
+//! Module for the verification of signed GUID-defined sections.
+//!
+//! Two signed section formats are supported:
+//! - `EFI_CERT_TYPE_RSA2048_SHA256_GUID` sections, whose GUID-specific header is an `EFI_CERT_BLOCK_RSA_2048_SHA256`
+//!   holding the RSA-2048 public key and the PKCS#1 v1.5 signature of the SHA-256 digest of the section content.
+//! - `EFI_CERT_TYPE_PKCS7_GUID` sections, whose GUID-specific header is a `WIN_CERTIFICATE_UEFI_GUID` holding a
+//!   detached PKCS#7 signature of the section content.
+//!
+//! As in the EDK II extraction libraries, the content of a signed section is returned whether or not its signature
+//! verifies. The outcome is reported in the authentication status, which the dispatcher passes to the Security
+//! Architectural Protocol so that platform policy decides what to do with the file.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::vec::Vec;
+use core::mem;
+use patina::{
+    pi::fw_fs::{
+        self,
+        ffs::section::{auth_status, header::GuidDefined},
+    },
+    runtime_services::authenticated_variables::{CERT_TYPE_PKCS7_GUID, WIN_CERT_REVISION, WIN_CERT_TYPE_EFI_GUID},
+};
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionExtractor, SectionHeader},
+};
+use r_efi::efi;
+
+use crate::NullSectionExtractor;
+
+/// `EFI_HASH_ALGORITHM_SHA256_GUID`, the only hash type accepted in an RSA2048/SHA256 certificate block.
+const HASH_ALGORITHM_SHA256_GUID: efi::Guid =
+    efi::Guid::from_fields(0x51aa59de, 0xfdf2, 0x4ea3, 0xbc, 0x63, &[0x87, 0x5f, 0xb7, 0x84, 0x2e, 0xe9]);
+
+/// Size of an RSA-2048 public key modulus and signature.
+const RSA2048_SIZE: usize = 256;
+
+/// Size of an `EFI_CERT_BLOCK_RSA_2048_SHA256`: the hash type GUID, the public key modulus and the signature.
+const RSA2048_SHA256_CERT_BLOCK_SIZE: usize = mem::size_of::<efi::Guid>() + 2 * RSA2048_SIZE;
+
+/// Size of a `WIN_CERTIFICATE_UEFI_GUID` header: `dwLength`, `wRevision`, `wCertificateType` and `CertType`.
+const WIN_CERTIFICATE_UEFI_GUID_SIZE: usize = 8 + mem::size_of::<efi::Guid>();
+
+/// Cryptographic operations needed to verify signed sections.
+///
+/// The extractor does not carry a crypto library of its own. Platforms provide an implementation backed by their
+/// crypto provider, and host tests can substitute one that uses test keys.
+pub trait SignedSectionCrypto {
+    /// Returns the SHA-256 digest of `data`.
+    fn sha256(&self, data: &[u8]) -> [u8; 32];
+
+    /// Verifies an RSASSA-PKCS1-v1_5 `signature` of the SHA-256 `digest` with the RSA-2048 public key whose
+    /// big-endian `modulus` is given, and the public exponent 0x10001.
+    fn rsa2048_sha256_verify(&self, modulus: &[u8; 256], digest: &[u8; 32], signature: &[u8; 256]) -> bool;
+
+    /// Verifies a detached, DER encoded PKCS#7 `SignedData` over `message`, whose signer must chain to the DER encoded
+    /// X.509 `trusted_certificate`.
+    fn pkcs7_verify(&self, signed_data: &[u8], trusted_certificate: &[u8], message: &[u8]) -> bool;
+}
+
+/// The platform public key set that signed sections are verified against.
+///
+/// A section whose signer is not in the key set fails verification, so an empty key set rejects every signed section.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SignedSectionConfig {
+    /// SHA-256 digests of the RSA-2048 public key moduli trusted for RSA2048/SHA256 sections.
+    pub rsa2048_sha256_public_keys: &'static [[u8; 32]],
+    /// DER encoded X.509 certificates trusted for PKCS7 sections.
+    pub pkcs7_trusted_certificates: &'static [&'static [u8]],
+}
+
+/// Provides extraction for signed sections, verifying them against a [SignedSectionConfig] key set.
+///
+/// Sections that are not signed are passed to the additional extractor `E`, so this extractor can wrap the
+/// [CompositeSectionExtractor](crate::CompositeSectionExtractor) of a platform.
+///
+/// ## Example
+///
+/// ```rust
+/// use patina_ffs_extractors::{CompositeSectionExtractor, SignedSectionConfig, SignedSectionCrypto, SignedSectionExtractor};
+///
+/// struct PlatformCrypto;
+///
+/// impl SignedSectionCrypto for PlatformCrypto {
+///     # fn sha256(&self, _data: &[u8]) -> [u8; 32] { [0; 32] }
+///     # fn rsa2048_sha256_verify(&self, _modulus: &[u8; 256], _digest: &[u8; 32], _signature: &[u8; 256]) -> bool { false }
+///     # fn pkcs7_verify(&self, _signed_data: &[u8], _trusted_certificate: &[u8], _message: &[u8]) -> bool { false }
+///     // ...
+/// }
+///
+/// const PUBLIC_KEYS: [[u8; 32]; 1] = [[0x5a; 32]];
+///
+/// static EXTRACTOR: SignedSectionExtractor<PlatformCrypto, CompositeSectionExtractor> = SignedSectionExtractor::new(
+///     PlatformCrypto,
+///     SignedSectionConfig { rsa2048_sha256_public_keys: &PUBLIC_KEYS, pkcs7_trusted_certificates: &[] },
+///     CompositeSectionExtractor::new(),
+/// );
+/// ```
+#[derive(Clone, Copy)]
+pub struct SignedSectionExtractor<C: SignedSectionCrypto, E: SectionExtractor = NullSectionExtractor> {
+    crypto: C,
+    config: SignedSectionConfig,
+    extractor: E,
+}
+
+impl<C: SignedSectionCrypto, E: SectionExtractor> SignedSectionExtractor<C, E> {
+    /// Creates a new `SignedSectionExtractor` verifying with `crypto` against `config`, and passing other sections
+    /// to `extractor`.
+    pub const fn new(crypto: C, config: SignedSectionConfig, extractor: E) -> Self {
+        Self { crypto, config, extractor }
+    }
+
+    /// Verifies an RSA2048/SHA256 section, given its certificate block and content.
+    fn verify_rsa2048_sha256(&self, cert_block: &[u8], content: &[u8]) -> Result<bool, FirmwareFileSystemError> {
+        if cert_block.len() < RSA2048_SHA256_CERT_BLOCK_SIZE {
+            Err(FirmwareFileSystemError::DataCorrupt)?;
+        }
+        let (hash_type, keys) = cert_block.split_at(mem::size_of::<efi::Guid>());
+        let (modulus, signature) = keys[..2 * RSA2048_SIZE].split_at(RSA2048_SIZE);
+        let modulus: &[u8; RSA2048_SIZE] = modulus.try_into().map_err(|_| FirmwareFileSystemError::DataCorrupt)?;
+        let signature: &[u8; RSA2048_SIZE] = signature.try_into().map_err(|_| FirmwareFileSystemError::DataCorrupt)?;
+
+        if hash_type != HASH_ALGORITHM_SHA256_GUID.as_bytes() {
+            log::error!("RSA2048/SHA256 section has an unsupported hash type.");
+            return Ok(false);
+        }
+        let key_digest = self.crypto.sha256(modulus);
+        if !self.config.rsa2048_sha256_public_keys.contains(&key_digest) {
+            log::error!("RSA2048/SHA256 section is signed with a key that is not in the platform key set.");
+            return Ok(false);
+        }
+        Ok(self.crypto.rsa2048_sha256_verify(modulus, &self.crypto.sha256(content), signature))
+    }
+
+    /// Verifies a PKCS7 section, given its `WIN_CERTIFICATE_UEFI_GUID` and content.
+    fn verify_pkcs7(&self, certificate: &[u8], content: &[u8]) -> Result<bool, FirmwareFileSystemError> {
+        if certificate.len() < WIN_CERTIFICATE_UEFI_GUID_SIZE {
+            Err(FirmwareFileSystemError::DataCorrupt)?;
+        }
+        let length = u32::from_le_bytes(certificate[0..4].try_into().unwrap()) as usize;
+        let revision = u16::from_le_bytes(certificate[4..6].try_into().unwrap());
+        let certificate_type = u16::from_le_bytes(certificate[6..8].try_into().unwrap());
+        if length < WIN_CERTIFICATE_UEFI_GUID_SIZE
+            || length > certificate.len()
+            || revision != WIN_CERT_REVISION
+            || certificate_type != WIN_CERT_TYPE_EFI_GUID
+            || certificate[8..WIN_CERTIFICATE_UEFI_GUID_SIZE] != *CERT_TYPE_PKCS7_GUID.as_bytes()
+        {
+            Err(FirmwareFileSystemError::DataCorrupt)?;
+        }
+
+        let signed_data = &certificate[WIN_CERTIFICATE_UEFI_GUID_SIZE..length];
+        let verified = self
+            .config
+            .pkcs7_trusted_certificates
+            .iter()
+            .any(|trusted_certificate| self.crypto.pkcs7_verify(signed_data, trusted_certificate, content));
+        if !verified {
+            log::error!("PKCS7 section is not signed by a certificate in the platform key set.");
+        }
+        Ok(verified)
+    }
+}
+
+impl<C: SignedSectionCrypto, E: SectionExtractor> SectionExtractor for SignedSectionExtractor<C, E> {
+    fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        self.extract_with_authentication_status(section).map(|(data, _)| data)
+    }
+
+    fn extract_with_authentication_status(&self, section: &Section) -> Result<(Vec<u8>, u32), FirmwareFileSystemError> {
+        let SectionHeader::GuidDefined(GuidDefined { section_definition_guid, .. }, guid_data, _) = section.header()
+        else {
+            return self.extractor.extract_with_authentication_status(section);
+        };
+
+        let content = section.try_content_as_slice()?;
+        let verified = match *section_definition_guid {
+            fw_fs::guid::RSA2048_SHA256_SECTION => self.verify_rsa2048_sha256(guid_data, content)?,
+            fw_fs::guid::PKCS7_SECTION => self.verify_pkcs7(guid_data, content)?,
+            _ => return self.extractor.extract_with_authentication_status(section),
+        };
+
+        let status =
+            if verified { auth_status::IMAGE_SIGNED } else { auth_status::IMAGE_SIGNED | auth_status::TEST_FAILED };
+        Ok((content.to_vec(), status))
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use patina::pi::fw_fs::ffs::section::{
+        header::{GUIDED_SECTION_AUTH_STATUS_VALID, GUIDED_SECTION_PROCESSING_REQUIRED},
+        raw_type,
+    };
+
+    const MODULUS: [u8; 256] = [0x5a; 256];
+    const CERTIFICATE: &[u8] = b"trusted certificate";
+
+    static PUBLIC_KEYS: [[u8; 32]; 1] = [digest(&MODULUS)];
+    static CERTIFICATES: [&[u8]; 1] = [CERTIFICATE];
+
+    const CONFIG: SignedSectionConfig =
+        SignedSectionConfig { rsa2048_sha256_public_keys: &PUBLIC_KEYS, pkcs7_trusted_certificates: &CERTIFICATES };
+
+    /// Stands in for SHA-256.
+    const fn digest(data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        let mut seed = 0;
+        while seed < 4 {
+            let mut hash = 0xcbf29ce484222325u64 ^ seed as u64;
+            let mut i = 0;
+            while i < data.len() {
+                hash = (hash ^ data[i] as u64).wrapping_mul(0x100000001b3);
+                i += 1;
+            }
+            let bytes = hash.to_le_bytes();
+            let mut j = 0;
+            while j < 8 {
+                out[seed * 8 + j] = bytes[j];
+                j += 1;
+            }
+            seed += 1;
+        }
+        out
+    }
+
+    /// Stands in for RSA and PKCS#7: signatures are digests of the key and the signed digest or message.
+    struct TestCrypto;
+
+    impl TestCrypto {
+        fn rsa_signature(modulus: &[u8], content: &[u8]) -> [u8; 256] {
+            let signature = digest(&[modulus, &digest(content)].concat());
+            signature.repeat(8).try_into().unwrap()
+        }
+
+        fn pkcs7_signature(certificate: &[u8], content: &[u8]) -> Vec<u8> {
+            [certificate, &digest(&[certificate, content].concat())].concat()
+        }
+    }
+
+    impl SignedSectionCrypto for TestCrypto {
+        fn sha256(&self, data: &[u8]) -> [u8; 32] {
+            digest(data)
+        }
+
+        fn rsa2048_sha256_verify(&self, modulus: &[u8; 256], digest_: &[u8; 32], signature: &[u8; 256]) -> bool {
+            *signature == *digest(&[modulus.as_slice(), digest_].concat()).repeat(8)
+        }
+
+        fn pkcs7_verify(&self, signed_data: &[u8], trusted_certificate: &[u8], message: &[u8]) -> bool {
+            signed_data == Self::pkcs7_signature(trusted_certificate, message)
+        }
+    }
+
+    fn extractor() -> SignedSectionExtractor<TestCrypto> {
+        SignedSectionExtractor::new(TestCrypto, CONFIG, NullSectionExtractor)
+    }
+
+    fn raw_section(data: &[u8]) -> Vec<u8> {
+        let header = SectionHeader::Standard(raw_type::RAW, data.len() as u32);
+        Section::new_from_header_with_data(header, data.to_vec()).unwrap().serialize().unwrap()
+    }
+
+    fn signed_section(guid: efi::Guid, attributes: u16, guid_data: Vec<u8>, content: &[u8]) -> Section {
+        let guid_header = GuidDefined {
+            section_definition_guid: guid,
+            data_offset: (mem::size_of::<GuidDefined>() + 4 + guid_data.len()) as u16,
+            attributes,
+        };
+        let header = SectionHeader::GuidDefined(guid_header, guid_data, content.len() as u32);
+        Section::new_from_header_with_data(header, content.to_vec()).expect("Failed to create test section")
+    }
+
+    fn rsa_cert_block(hash_type: efi::Guid, modulus: &[u8; 256], signature: &[u8; 256]) -> Vec<u8> {
+        [hash_type.as_bytes().as_slice(), modulus, signature].concat()
+    }
+
+    fn rsa_section(content: &[u8], signed_content: &[u8]) -> Section {
+        let signature = TestCrypto::rsa_signature(&MODULUS, signed_content);
+        signed_section(
+            fw_fs::guid::RSA2048_SHA256_SECTION,
+            GUIDED_SECTION_PROCESSING_REQUIRED | GUIDED_SECTION_AUTH_STATUS_VALID,
+            rsa_cert_block(HASH_ALGORITHM_SHA256_GUID, &MODULUS, &signature),
+            content,
+        )
+    }
+
+    fn win_certificate(signed_data: &[u8]) -> Vec<u8> {
+        let mut certificate = ((WIN_CERTIFICATE_UEFI_GUID_SIZE + signed_data.len()) as u32).to_le_bytes().to_vec();
+        certificate.extend_from_slice(&WIN_CERT_REVISION.to_le_bytes());
+        certificate.extend_from_slice(&WIN_CERT_TYPE_EFI_GUID.to_le_bytes());
+        certificate.extend_from_slice(CERT_TYPE_PKCS7_GUID.as_bytes());
+        certificate.extend_from_slice(signed_data);
+        certificate
+    }
+
+    fn pkcs7_section(certificate: &[u8], content: &[u8]) -> Section {
+        let signature = TestCrypto::pkcs7_signature(certificate, content);
+        signed_section(
+            fw_fs::guid::PKCS7_SECTION,
+            GUIDED_SECTION_PROCESSING_REQUIRED | GUIDED_SECTION_AUTH_STATUS_VALID,
+            win_certificate(&signature),
+            content,
+        )
+    }
+
+    #[test]
+    fn test_rsa2048_sha256_valid_signature() {
+        let content = raw_section(b"Hello, RSA!");
+        let section = rsa_section(&content, &content);
+
+        let (data, status) = extractor().extract_with_authentication_status(&section).unwrap();
+        assert_eq!(data, content);
+        assert_eq!(status, auth_status::IMAGE_SIGNED);
+    }
+
+    #[test]
+    fn test_rsa2048_sha256_failures_return_content() {
+        let content = raw_section(b"Hello, RSA!");
+        let failed = auth_status::IMAGE_SIGNED | auth_status::TEST_FAILED;
+
+        // Content that does not match the signature.
+        let section = rsa_section(&content, &raw_section(b"Hello, RSA?"));
+        assert_eq!(extractor().extract_with_authentication_status(&section).unwrap(), (content.clone(), failed));
+
+        // A key that is not in the platform key set.
+        let other_modulus = [0xa5; 256];
+        let signature = TestCrypto::rsa_signature(&other_modulus, &content);
+        let cert_block = rsa_cert_block(HASH_ALGORITHM_SHA256_GUID, &other_modulus, &signature);
+        let section = signed_section(fw_fs::guid::RSA2048_SHA256_SECTION, 0x03, cert_block, &content);
+        assert_eq!(extractor().extract_with_authentication_status(&section).unwrap().1, failed);
+
+        // A hash type other than SHA-256.
+        let signature = TestCrypto::rsa_signature(&MODULUS, &content);
+        let cert_block = rsa_cert_block(fw_fs::guid::CRC32_SECTION, &MODULUS, &signature);
+        let section = signed_section(fw_fs::guid::RSA2048_SHA256_SECTION, 0x03, cert_block, &content);
+        assert_eq!(extractor().extract_with_authentication_status(&section).unwrap().1, failed);
+
+        // An empty platform key set.
+        let section = rsa_section(&content, &content);
+        let extractor = SignedSectionExtractor::new(TestCrypto, SignedSectionConfig::default(), NullSectionExtractor);
+        assert_eq!(extractor.extract_with_authentication_status(&section).unwrap().1, failed);
+    }
+
+    #[test]
+    fn test_rsa2048_sha256_truncated_cert_block() {
+        let section = signed_section(fw_fs::guid::RSA2048_SHA256_SECTION, 0x03, vec![0; 64], b"data");
+        assert_eq!(extractor().extract(&section), Err(FirmwareFileSystemError::DataCorrupt));
+    }
+
+    #[test]
+    fn test_pkcs7_signature() {
+        let content = raw_section(b"Hello, PKCS7!");
+
+        let section = pkcs7_section(CERTIFICATE, &content);
+        let (data, status) = extractor().extract_with_authentication_status(&section).unwrap();
+        assert_eq!(data, content);
+        assert_eq!(status, auth_status::IMAGE_SIGNED);
+
+        let section = pkcs7_section(b"untrusted certificate", &content);
+        let (data, status) = extractor().extract_with_authentication_status(&section).unwrap();
+        assert_eq!(data, content);
+        assert_eq!(status, auth_status::IMAGE_SIGNED | auth_status::TEST_FAILED);
+    }
+
+    #[test]
+    fn test_pkcs7_malformed_certificate() {
+        let content = raw_section(b"Hello, PKCS7!");
+        let signature = TestCrypto::pkcs7_signature(CERTIFICATE, &content);
+
+        let mut bad_type = win_certificate(&signature);
+        bad_type[6] = 0x02; // WIN_CERT_TYPE_PKCS_SIGNED_DATA
+        let mut bad_length = win_certificate(&signature);
+        bad_length[0..4].copy_from_slice(&0x1000u32.to_le_bytes());
+
+        for certificate in [bad_type, bad_length, vec![0; 8]] {
+            let section = signed_section(fw_fs::guid::PKCS7_SECTION, 0x03, certificate, &content);
+            assert_eq!(extractor().extract(&section), Err(FirmwareFileSystemError::DataCorrupt));
+        }
+    }
+
+    #[test]
+    fn test_other_sections_use_additional_extractor() {
+        let section = signed_section(fw_fs::guid::LZMA_SECTION, 0x01, vec![], b"data");
+        assert_eq!(extractor().extract(&section), Err(FirmwareFileSystemError::Unsupported));
+
+        let section = Section::new_from_buffer(&raw_section(b"data")).unwrap();
+        assert_eq!(extractor().extract(&section), Err(FirmwareFileSystemError::Unsupported));
+    }
+
+    #[test]
+    fn test_authentication_status_is_inherited_by_sub_sections() {
+        // A section signed with an untrusted PKCS7 certificate, nested in a section with a valid RSA signature.
+        let inner = pkcs7_section(b"untrusted certificate", &raw_section(b"inner"));
+        let mut content = raw_section(b"outer");
+        content.resize(content.len().next_multiple_of(4), 0);
+        content.extend(inner.serialize().unwrap());
+        let mut section = rsa_section(&content, &content);
+
+        section.extract(&extractor()).unwrap();
+        let sub_sections: Vec<_> = section.sub_sections().collect();
+        assert_eq!(sub_sections.len(), 2);
+        assert_eq!(sub_sections[0].authentication_status(), auth_status::IMAGE_SIGNED);
+        assert_eq!(sub_sections[1].authentication_status(), auth_status::IMAGE_SIGNED);
+        let inner_raw = sub_sections[1].sub_sections().next().unwrap();
+        assert_eq!(inner_raw.try_content_as_slice().unwrap(), b"inner");
+        assert_eq!(inner_raw.authentication_status(), auth_status::IMAGE_SIGNED | auth_status::TEST_FAILED);
+    }
+
+    #[test]
+    fn test_authentication_status_requires_auth_status_valid() {
+        let content = raw_section(b"Hello, RSA!");
+        let signature = TestCrypto::rsa_signature(&MODULUS, &content);
+        let cert_block = rsa_cert_block(HASH_ALGORITHM_SHA256_GUID, &MODULUS, &signature);
+        let mut section = signed_section(
+            fw_fs::guid::RSA2048_SHA256_SECTION,
+            GUIDED_SECTION_PROCESSING_REQUIRED,
+            cert_block,
+            &content,
+        );
+
+        section.extract(&extractor()).unwrap();
+        assert_eq!(section.sub_sections().next().unwrap().authentication_status(), 0);
+    }
+}
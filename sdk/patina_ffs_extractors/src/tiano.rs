@@ -0,0 +1,553 @@
+//! Module for UEFI and Tiano compression.
+//!
+//! Both algorithms produce the LZ77 + Huffman bitstream described in the "Compression Algorithm Specification"
+//! chapter of the UEFI specification; Tiano compression only differs by using a larger sliding window. The
+//! bitstream is preceded by the compressed and original sizes, both as little-endian `u32`.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{collections::BinaryHeap, vec, vec::Vec};
+use core::cmp::Reverse;
+use patina::pi::fw_fs::{
+    self,
+    ffs::section::header::{Compression, NOT_COMPRESSED, STANDARD_COMPRESSION},
+};
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionComposer, SectionHeader},
+};
+
+use crate::{guid_defined_header, sub_sections_content};
+
+// Shortest and longest strings encoded as a position:length pair.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 256;
+
+// Size of the Char&Len set: 256 literals followed by the string lengths.
+const NC: usize = 0x100 + MAX_MATCH - MIN_MATCH + 1;
+const CBIT: usize = 9;
+
+// Size of the Extra set used to encode the Char&Len code lengths.
+const NT: usize = 19;
+const TBIT: usize = 5;
+// Index after which the Extra set code lengths carry a 2-bit count of zero lengths.
+const T_ZERO_RUN_INDEX: usize = 3;
+
+const MAX_CODE_LENGTH: usize = 16;
+const MAX_BLOCK_SYMBOLS: usize = u16::MAX as usize;
+
+// Bytes of zero padding after the bitstream, so the decoder can look ahead a full code length at the end.
+const PADDING_SIZE: usize = 2;
+
+const HASH_BITS: usize = 15;
+// Number of earlier occurrences compared when looking for the longest match.
+const MAX_CHAIN_LENGTH: usize = 256;
+
+/// Provides compression for standard compression sections and Tiano GUIDed sections.
+///
+/// Standard compression sections use UEFI compression, or store the sub-sections as-is when the header specifies
+/// `NOT_COMPRESSED`.
+#[derive(Default, Clone, Copy)]
+pub struct TianoSectionComposer;
+
+impl TianoSectionComposer {
+    /// Creates a new `TianoSectionComposer` instance.
+    #[coverage(off)]
+    pub const fn new() -> Self {
+        Self {}
+    }
+}
+
+impl SectionComposer for TianoSectionComposer {
+    fn compose(&self, section: &Section) -> Result<(SectionHeader, Vec<u8>), FirmwareFileSystemError> {
+        match section.header() {
+            SectionHeader::Compression(compression_header, _) => {
+                let compression_type = compression_header.compression_type;
+                let content = sub_sections_content(section)?;
+                let uncompressed_length =
+                    content.len().try_into().map_err(|_| FirmwareFileSystemError::InvalidParameter)?;
+                let content = match compression_type {
+                    NOT_COMPRESSED => content,
+                    STANDARD_COMPRESSION => compress(&content, Algorithm::Uefi)?,
+                    _ => Err(FirmwareFileSystemError::Unsupported)?,
+                };
+                let header = Compression { uncompressed_length, compression_type };
+                Ok((SectionHeader::Compression(header, content.len() as u32), content))
+            }
+            SectionHeader::GuidDefined(guid_header, _, _)
+                if guid_header.section_definition_guid == fw_fs::guid::TIANO_DECOMPRESS_SECTION =>
+            {
+                let content = compress(&sub_sections_content(section)?, Algorithm::Tiano)?;
+                Ok((guid_defined_header(guid_header, Vec::new(), content.len())?, content))
+            }
+            _ => Err(FirmwareFileSystemError::Unsupported),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Algorithm {
+    Uefi,
+    Tiano,
+}
+
+impl Algorithm {
+    /// Number of bits of the largest string position.
+    const fn window_bits(self) -> usize {
+        match self {
+            Algorithm::Uefi => 13,
+            Algorithm::Tiano => 19,
+        }
+    }
+
+    /// Width of the fields that encode the Position set code lengths.
+    const fn position_set_bits(self) -> usize {
+        match self {
+            Algorithm::Uefi => 4,
+            Algorithm::Tiano => 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Token {
+    Literal(u8),
+    // `position` is the distance back to the start of the string, minus one.
+    String { length: usize, position: usize },
+}
+
+/// Compresses `data`, returning the sizes header followed by the bitstream.
+fn compress(data: &[u8], algorithm: Algorithm) -> Result<Vec<u8>, FirmwareFileSystemError> {
+    let original_size: u32 = data.len().try_into().map_err(|_| FirmwareFileSystemError::InvalidParameter)?;
+
+    let mut writer = BitWriter::default();
+    for block in find_strings(data, algorithm.window_bits()).chunks(MAX_BLOCK_SYMBOLS) {
+        write_block(&mut writer, block, algorithm);
+    }
+    let bitstream = writer.finish();
+    let compressed_size: u32 = bitstream.len().try_into().map_err(|_| FirmwareFileSystemError::InvalidParameter)?;
+
+    let mut compressed = Vec::with_capacity(8 + bitstream.len());
+    compressed.extend_from_slice(&compressed_size.to_le_bytes());
+    compressed.extend_from_slice(&original_size.to_le_bytes());
+    compressed.extend_from_slice(&bitstream);
+    Ok(compressed)
+}
+
+/// Splits `data` into literals and strings repeated from the preceding `window_bits` window, greedily taking the
+/// longest match found through a hash chain of earlier positions.
+fn find_strings(data: &[u8], window_bits: usize) -> Vec<Token> {
+    let max_distance = (1 << window_bits) - 1;
+    let hash = |pos: usize| {
+        ((usize::from(data[pos]) << 10) ^ (usize::from(data[pos + 1]) << 5) ^ usize::from(data[pos + 2]))
+            & ((1 << HASH_BITS) - 1)
+    };
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; data.len()];
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let max_length = MAX_MATCH.min(data.len() - pos);
+        let (mut best_length, mut best_distance) = (0, 0);
+        if max_length >= MIN_MATCH {
+            let mut candidate = head[hash(pos)];
+            let mut chain_length = MAX_CHAIN_LENGTH;
+            while candidate != usize::MAX && pos - candidate <= max_distance && chain_length > 0 {
+                let length =
+                    data[candidate..].iter().zip(&data[pos..pos + max_length]).take_while(|(a, b)| a == b).count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, pos - candidate);
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain_length -= 1;
+            }
+        }
+
+        let advance = if best_length >= MIN_MATCH {
+            tokens.push(Token::String { length: best_length, position: best_distance - 1 });
+            best_length
+        } else {
+            tokens.push(Token::Literal(data[pos]));
+            1
+        };
+        let inserted_end = (pos + advance).min(data.len().saturating_sub(MIN_MATCH - 1)).max(pos);
+        for (inserted, previous) in (pos..inserted_end).zip(&mut previous[pos..inserted_end]) {
+            let hash = hash(inserted);
+            *previous = head[hash];
+            head[hash] = inserted;
+        }
+        pos += advance;
+    }
+    tokens
+}
+
+/// Symbol of the Position set for `position`: its length in bits.
+const fn position_symbol(position: usize) -> usize {
+    (usize::BITS - position.leading_zeros()) as usize
+}
+
+/// Writes one block: its symbol count, the three code length tables, and the Huffman coded tokens.
+fn write_block(writer: &mut BitWriter, tokens: &[Token], algorithm: Algorithm) {
+    let mut c_freq = [0u32; NC];
+    let mut p_freq = vec![0u32; algorithm.window_bits() + 1];
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => c_freq[usize::from(byte)] += 1,
+            Token::String { length, position } => {
+                c_freq[0x100 + length - MIN_MATCH] += 1;
+                p_freq[position_symbol(position)] += 1;
+            }
+        }
+    }
+
+    writer.put(16, tokens.len());
+
+    let c_len = code_lengths(&c_freq);
+    write_c_lengths(writer, &c_freq, &c_len);
+    let p_len = write_pt_lengths(writer, &p_freq, algorithm.position_set_bits(), None);
+
+    let c_codes = canonical_codes(&c_len);
+    let p_codes = canonical_codes(&p_len);
+    for token in tokens {
+        match *token {
+            Token::Literal(byte) => {
+                let symbol = usize::from(byte);
+                writer.put(c_len[symbol].into(), c_codes[symbol]);
+            }
+            Token::String { length, position } => {
+                let symbol = 0x100 + length - MIN_MATCH;
+                writer.put(c_len[symbol].into(), c_codes[symbol]);
+
+                let symbol = position_symbol(position);
+                writer.put(p_len[symbol].into(), p_codes[symbol]);
+                if symbol > 1 {
+                    writer.put(symbol - 1, position - (1 << (symbol - 1)));
+                }
+            }
+        }
+    }
+}
+
+/// Writes the Char&Len set code lengths, encoded with the Extra set, preceded by the Extra set code lengths.
+fn write_c_lengths(writer: &mut BitWriter, c_freq: &[u32], c_len: &[u8]) {
+    let count = used_length(c_len);
+    if count == 0 {
+        // A single symbol is stored directly, and costs no bits when coded. The Extra set is not used.
+        writer.put(TBIT, 0);
+        writer.put(TBIT, 0);
+        writer.put(CBIT, 0);
+        writer.put(CBIT, c_freq.iter().position(|&freq| freq != 0).unwrap_or(0));
+        return;
+    }
+
+    // Each entry is an Extra set symbol, followed by a field of the given width for zero runs.
+    let mut t_symbols: Vec<(usize, Option<(usize, usize)>)> = Vec::new();
+    let mut index = 0;
+    while index < count {
+        let length = c_len[index];
+        index += 1;
+        if length != 0 {
+            t_symbols.push((usize::from(length) + 2, None));
+            continue;
+        }
+        let mut zeros = 1;
+        while index < count && c_len[index] == 0 {
+            index += 1;
+            zeros += 1;
+        }
+        match zeros {
+            1..=2 => t_symbols.extend(core::iter::repeat_n((0, None), zeros)),
+            3..=18 => t_symbols.push((1, Some((4, zeros - 3)))),
+            19 => t_symbols.extend([(0, None), (1, Some((4, 15)))]),
+            _ => t_symbols.push((2, Some((CBIT, zeros - 20)))),
+        }
+    }
+
+    let mut t_freq = [0u32; NT];
+    for (symbol, _) in &t_symbols {
+        t_freq[*symbol] += 1;
+    }
+    let t_len = write_pt_lengths(writer, &t_freq, TBIT, Some(T_ZERO_RUN_INDEX));
+    let t_codes = canonical_codes(&t_len);
+
+    writer.put(CBIT, count);
+    for (symbol, zero_run) in t_symbols {
+        writer.put(t_len[symbol].into(), t_codes[symbol]);
+        if let Some((bits, value)) = zero_run {
+            writer.put(bits, value);
+        }
+    }
+}
+
+/// Writes the code lengths of the Extra or Position set into fields of `bits` width, returning the lengths.
+///
+/// If `zero_run_index` is set, a 2-bit count of zero lengths follows the length at that index.
+fn write_pt_lengths(writer: &mut BitWriter, freq: &[u32], bits: usize, zero_run_index: Option<usize>) -> Vec<u8> {
+    let lengths = code_lengths(freq);
+    let count = used_length(&lengths);
+    if count == 0 {
+        // A single symbol is stored directly, and costs no bits when coded.
+        writer.put(bits, 0);
+        writer.put(bits, freq.iter().position(|&freq| freq != 0).unwrap_or(0));
+        return lengths;
+    }
+
+    writer.put(bits, count);
+    let mut index = 0;
+    while index < count {
+        let length = usize::from(lengths[index]);
+        index += 1;
+        if length < 7 {
+            writer.put(3, length);
+        } else {
+            // Lengths of 7 and more are written as (length - 4) one bits followed by a zero bit.
+            writer.put(length - 3, (1 << (length - 3)) - 2);
+        }
+        if zero_run_index == Some(index) {
+            let mut zeros = 0;
+            while zeros < 3 && index < count && lengths[index] == 0 {
+                zeros += 1;
+                index += 1;
+            }
+            writer.put(2, zeros);
+        }
+    }
+    lengths
+}
+
+/// Number of code lengths up to and including the last non-zero one.
+fn used_length(lengths: &[u8]) -> usize {
+    lengths.iter().rposition(|&length| length != 0).map_or(0, |index| index + 1)
+}
+
+/// Computes Huffman code lengths of at most [MAX_CODE_LENGTH] bits for the symbol frequencies in `freq`.
+///
+/// All lengths are zero if fewer than two symbols are used, as such sets are stored without codes.
+fn code_lengths(freq: &[u32]) -> Vec<u8> {
+    let mut lengths = vec![0u8; freq.len()];
+    let mut symbols: Vec<usize> = (0..freq.len()).filter(|&symbol| freq[symbol] != 0).collect();
+    if symbols.len() < 2 {
+        return lengths;
+    }
+
+    // Build the Huffman tree. Leaves are nodes 0..symbols.len(); every parent is created after its children.
+    let mut parent = vec![usize::MAX; symbols.len()];
+    let mut heap: BinaryHeap<Reverse<(u64, usize)>> =
+        symbols.iter().enumerate().map(|(node, &symbol)| Reverse((u64::from(freq[symbol]), node))).collect();
+    while let (Some(Reverse((weight_a, a))), Some(Reverse((weight_b, b)))) = (heap.pop(), heap.pop()) {
+        let node = parent.len();
+        parent.push(usize::MAX);
+        parent[a] = node;
+        parent[b] = node;
+        heap.push(Reverse((weight_a + weight_b, node)));
+    }
+    let mut depth = vec![0usize; parent.len()];
+    for node in (0..parent.len()).rev() {
+        if parent[node] != usize::MAX {
+            depth[node] = depth[parent[node]] + 1;
+        }
+    }
+
+    let mut count = [0usize; MAX_CODE_LENGTH + 1];
+    for leaf_depth in &depth[..symbols.len()] {
+        count[(*leaf_depth).min(MAX_CODE_LENGTH)] += 1;
+    }
+    // Shortening the codes that are too long over-subscribes the code space. As the EDK2 compressor does, move codes
+    // from the shorter lengths down one level until the code is complete again.
+    let mut kraft_sum: usize = (1..=MAX_CODE_LENGTH).map(|length| count[length] << (MAX_CODE_LENGTH - length)).sum();
+    while kraft_sum > 1 << MAX_CODE_LENGTH {
+        count[MAX_CODE_LENGTH] -= 1;
+        if let Some(length) = (1..MAX_CODE_LENGTH).rev().find(|&length| count[length] != 0) {
+            count[length] -= 1;
+            count[length + 1] += 2;
+        }
+        kraft_sum -= 1;
+    }
+
+    // The most frequent symbols get the shortest codes.
+    symbols.sort_by_key(|&symbol| Reverse(freq[symbol]));
+    let mut symbols = symbols.into_iter();
+    for (length, count) in count.iter().enumerate().skip(1) {
+        for symbol in symbols.by_ref().take(*count) {
+            lengths[symbol] = length as u8;
+        }
+    }
+    lengths
+}
+
+/// Assigns canonical Huffman codes to `lengths`, in increasing symbol order within each length.
+fn canonical_codes(lengths: &[u8]) -> Vec<usize> {
+    let mut count = [0usize; MAX_CODE_LENGTH + 1];
+    for &length in lengths {
+        count[usize::from(length)] += 1;
+    }
+    let mut start = [0usize; MAX_CODE_LENGTH + 2];
+    for length in 1..=MAX_CODE_LENGTH {
+        start[length + 1] = start[length] + (count[length] << (MAX_CODE_LENGTH - length));
+    }
+
+    let mut codes = vec![0; lengths.len()];
+    for (symbol, &length) in lengths.iter().enumerate() {
+        let length = usize::from(length);
+        if length != 0 {
+            codes[symbol] = start[length] >> (MAX_CODE_LENGTH - length);
+            start[length] += 1 << (MAX_CODE_LENGTH - length);
+        }
+    }
+    codes
+}
+
+/// Writes values most significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bit_count: usize,
+}
+
+impl BitWriter {
+    /// Writes the low `bits` bits of `value`.
+    fn put(&mut self, bits: usize, value: usize) {
+        debug_assert!(bits <= 32 && value >> bits == 0);
+        self.buffer = (self.buffer << bits) | value as u64;
+        self.bit_count += bits;
+        while self.bit_count >= 8 {
+            self.bit_count -= 8;
+            self.bytes.push((self.buffer >> self.bit_count) as u8);
+        }
+    }
+
+    /// Flushes the partial byte and appends the padding.
+    fn finish(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.put(8 - self.bit_count, 0);
+        }
+        self.bytes.resize(self.bytes.len() + PADDING_SIZE, 0);
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_round_trip, create_encapsulation_section};
+    use mu_rust_helpers::uefi_decompress::{DecompressionAlgorithm, decompress_into_with_algo};
+    use patina::pi::fw_fs::ffs::section::header::{GUIDED_SECTION_PROCESSING_REQUIRED, GuidDefined};
+    use patina_ffs::section::SectionExtractor;
+
+    /// Extracts with the decompressor used by the core, as its `CoreExtractor` does.
+    struct UefiDecompressExtractor;
+
+    impl SectionExtractor for UefiDecompressExtractor {
+        fn extract(&self, section: &Section) -> Result<Vec<u8>, FirmwareFileSystemError> {
+            let (algorithm, data) = match section.header() {
+                SectionHeader::Compression(header, _) if header.compression_type == NOT_COMPRESSED => {
+                    return Ok(section.try_content_as_slice()?.to_vec());
+                }
+                SectionHeader::Compression(_, _) => (Algorithm::Uefi, section.try_content_as_slice()?),
+                SectionHeader::GuidDefined(_, _, _) => (Algorithm::Tiano, section.try_content_as_slice()?),
+                _ => Err(FirmwareFileSystemError::Unsupported)?,
+            };
+            decompress(data, algorithm)
+        }
+    }
+
+    fn decompress(data: &[u8], algorithm: Algorithm) -> Result<Vec<u8>, FirmwareFileSystemError> {
+        let algorithm = match algorithm {
+            Algorithm::Uefi => DecompressionAlgorithm::UefiDecompress,
+            Algorithm::Tiano => DecompressionAlgorithm::TianoDecompress,
+        };
+        let mut decompressed = vec![0u8; u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize];
+        decompress_into_with_algo(data, &mut decompressed, algorithm)
+            .map_err(|_| FirmwareFileSystemError::DataCorrupt)?;
+        Ok(decompressed)
+    }
+
+    fn pseudo_random(len: usize) -> Vec<u8> {
+        let mut state = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compress_round_trip() {
+        let text = b"The quick brown fox jumps over the lazy dog. ".repeat(200);
+        let random = pseudo_random(0x20000);
+        // Repeats further back than the UEFI window, which only Tiano compression can reach.
+        let far_repeat = [pseudo_random(0x10000), pseudo_random(0x10000)].concat();
+        let inputs: [&[u8]; 7] = [b"", b"a", b"abc", &[0u8; 0x30000], &text, &random, &far_repeat];
+
+        for algorithm in [Algorithm::Uefi, Algorithm::Tiano] {
+            for input in inputs {
+                let compressed = compress(input, algorithm).expect("compression should succeed");
+                assert_eq!(decompress(&compressed, algorithm).expect("decompression should succeed"), input);
+            }
+        }
+
+        assert!(compress(&text, Algorithm::Uefi).unwrap().len() < text.len() / 10);
+        let uefi = compress(&far_repeat, Algorithm::Uefi).unwrap();
+        assert!(compress(&far_repeat, Algorithm::Tiano).unwrap().len() < uefi.len() / 2 + 0x1000);
+    }
+
+    #[test]
+    fn test_code_lengths_are_limited() {
+        // Fibonacci frequencies produce the deepest possible Huffman tree.
+        let mut freq = vec![1u32, 1];
+        while freq.len() < 30 {
+            freq.push(freq[freq.len() - 1] + freq[freq.len() - 2]);
+        }
+
+        let lengths = code_lengths(&freq);
+        assert!(lengths.iter().all(|&length| (1..=16).contains(&length)));
+        let kraft_sum: usize = lengths.iter().map(|&length| 1 << (16 - length)).sum();
+        assert_eq!(kraft_sum, 1 << 16);
+        assert!(lengths.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_tiano_composer_round_trip() {
+        let compressed = Compression { uncompressed_length: 0, compression_type: STANDARD_COMPRESSION };
+        let stored = Compression { uncompressed_length: 0, compression_type: NOT_COMPRESSED };
+        let tiano = GuidDefined {
+            section_definition_guid: fw_fs::guid::TIANO_DECOMPRESS_SECTION,
+            data_offset: 0,
+            attributes: GUIDED_SECTION_PROCESSING_REQUIRED,
+        };
+
+        for header in [
+            SectionHeader::Compression(compressed, 0),
+            SectionHeader::Compression(stored, 0),
+            SectionHeader::GuidDefined(tiano, Vec::new(), 0),
+        ] {
+            assert_round_trip(create_encapsulation_section(header), &TianoSectionComposer, &UefiDecompressExtractor);
+        }
+    }
+
+    #[test]
+    fn test_tiano_composer_unsupported() {
+        let lzma = GuidDefined {
+            section_definition_guid: fw_fs::guid::LZMA_SECTION,
+            data_offset: 0,
+            attributes: GUIDED_SECTION_PROCESSING_REQUIRED,
+        };
+        let section = create_encapsulation_section(SectionHeader::GuidDefined(lzma, Vec::new(), 0));
+        assert!(matches!(TianoSectionComposer.compose(&section), Err(FirmwareFileSystemError::Unsupported)));
+
+        let unknown = Compression { uncompressed_length: 0, compression_type: 0x7f };
+        let section = create_encapsulation_section(SectionHeader::Compression(unknown, 0));
+        assert!(matches!(TianoSectionComposer.compose(&section), Err(FirmwareFileSystemError::Unsupported)));
+    }
+}
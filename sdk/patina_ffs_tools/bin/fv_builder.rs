@@ -0,0 +1,48 @@
+//! Executable for building firmware volumes from a manifest.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use clap::Parser;
+use patina_ffs_tools::{build_volume, manifest::Manifest};
+use std::{fs, io, path::PathBuf};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Path of the TOML or JSON manifest describing the firmware volume. JSON is expected for `.json` files.
+    manifest_path: PathBuf,
+    /// Path of the firmware volume image to write.
+    #[arg(short, long)]
+    output_path: PathBuf,
+    /// Directory that input paths of the manifest are relative to. Defaults to the directory of the manifest.
+    #[arg(short, long)]
+    input_dir: Option<PathBuf>,
+}
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+
+    let text = fs::read_to_string(&args.manifest_path)?;
+    let manifest = match args.manifest_path.extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("json") => Manifest::from_json(&text),
+        _ => Manifest::from_toml(&text),
+    }
+    .map_err(|e| {
+        eprintln!("Error reading {}: {e}", args.manifest_path.display());
+        io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+    })?;
+
+    let input_dir =
+        args.input_dir.unwrap_or_else(|| args.manifest_path.parent().map(PathBuf::from).unwrap_or_default());
+    let image =
+        build_volume(&manifest, |path| fs::read(input_dir.join(path)).map_err(|e| e.to_string())).map_err(|e| {
+            eprintln!("Error building firmware volume: {e}");
+            io::Error::other(e.to_string())
+        })?;
+
+    fs::write(&args.output_path, image)
+}
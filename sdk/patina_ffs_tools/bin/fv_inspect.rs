@@ -0,0 +1,125 @@
+//! Executable for inspecting, comparing and extracting firmware volumes.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+
+use clap::{Parser, Subcommand};
+use patina::pi::serializable::format_guid;
+use patina_ffs_tools::inspect::{self, VolumeInfo};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the file and section tree of the firmware volumes in an image.
+    Tree {
+        /// Path of the FV or flash image.
+        image_path: PathBuf,
+    },
+    /// Print the firmware volumes in an image as JSON.
+    Json {
+        /// Path of the FV or flash image.
+        image_path: PathBuf,
+        /// Optional path for the output file. If not specified, the output will be printed to stdout.
+        #[arg(short, long)]
+        output_path: Option<PathBuf>,
+    },
+    /// Extract the leaf sections of the files in an image, to `<output>/fv_<offset>/<file>/<index>_<type>.bin`.
+    Extract {
+        /// Path of the FV or flash image.
+        image_path: PathBuf,
+        /// Directory to extract the sections to.
+        #[arg(short, long)]
+        output_path: PathBuf,
+        /// Only extract the sections of the file with this name GUID.
+        #[arg(short, long)]
+        file: Option<String>,
+    },
+    /// Compare the firmware volumes of two images at file and section granularity.
+    Diff {
+        /// Path of the old FV or flash image.
+        old_path: PathBuf,
+        /// Path of the new FV or flash image.
+        new_path: PathBuf,
+    },
+}
+
+fn main() -> io::Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Command::Tree { image_path } => {
+            let volumes = read_volumes(&image_path)?.1;
+            let mut tree = String::new();
+            inspect::write_tree(&mut tree, &volumes).map_err(io::Error::other)?;
+            io::stdout().write_all(tree.as_bytes())
+        }
+        Command::Json { image_path, output_path } => {
+            let (image, volumes) = read_volumes(&image_path)?;
+            let json = serde_json::to_string_pretty(&inspect::serializable_volumes(&image, &volumes))
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            match output_path {
+                Some(path) => fs::write(path, json),
+                None => writeln!(io::stdout(), "{json}"),
+            }
+        }
+        Command::Extract { image_path, output_path, file } => {
+            let volumes = read_volumes(&image_path)?.1;
+            extract(&volumes, &output_path, file.as_deref())
+        }
+        Command::Diff { old_path, new_path } => {
+            let old = read_volumes(&old_path)?.1;
+            let new = read_volumes(&new_path)?.1;
+            let mut out = io::stdout();
+            for difference in inspect::diff(&old, &new) {
+                writeln!(out, "{difference}")?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn read_volumes(path: &Path) -> io::Result<(Vec<u8>, Vec<VolumeInfo>)> {
+    let image = fs::read(path)?;
+    let volumes = inspect::find_volumes(&image);
+    if volumes.is_empty() {
+        eprintln!("No firmware volumes found in {}", path.display());
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no firmware volumes found"));
+    }
+    Ok((image, volumes))
+}
+
+fn extract(volumes: &[VolumeInfo], output_path: &Path, file_name: Option<&str>) -> io::Result<()> {
+    for volume in volumes {
+        let volume_path = output_path.join(format!("fv_{:#x}", volume.offset));
+        for file in &volume.files {
+            let name = format_guid(file.name);
+            if file_name.is_some_and(|file_name| !file_name.eq_ignore_ascii_case(&name)) {
+                continue;
+            }
+            let file_path = volume_path.join(&name);
+            fs::create_dir_all(&file_path)?;
+            if file.sections.is_empty() {
+                fs::write(file_path.join("data.bin"), &file.data)?;
+            }
+            let leaves = file.sections.iter().flat_map(|section| section.leaves());
+            for (index, section) in leaves.enumerate() {
+                fs::write(file_path.join(format!("{index}_{}.bin", section.type_name())), &section.content)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,562 @@
+//! Builds firmware volume images from a [Manifest].
+//!
+//! The builder reads no files itself: the caller resolves every input `path` of the manifest to its bytes. The
+//! output only depends on the manifest and the input bytes, so building the same manifest twice produces the same
+//! image.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use patina::pi::fw_fs::{
+    ffs::{self, section::header},
+    fv::{self, BlockMapEntry},
+    fvb::attributes::raw::fvb2,
+    guid,
+};
+use patina_ffs::{
+    FirmwareFileSystemError,
+    file::File,
+    section::{Section, SectionHeader},
+    volume::Volume,
+};
+use patina_ffs_extractors::CompositeSectionComposer;
+use patina_internal_depex::text::{CompileError, SymbolTable, compile};
+use r_efi::efi;
+
+use crate::manifest::{
+    CompressionAlgorithm, DepexOpcode, DepexPhase, FileConfig, FileSystem, GuidedAlgorithm, Manifest, SectionConfig,
+};
+
+/// Errors that can occur while building a firmware volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The manifest could not be parsed.
+    Manifest(String),
+    /// A GUID is neither in registry format nor the name of an entry of the `guids` table.
+    UnknownGuid(String),
+    /// An input could not be read.
+    Input {
+        /// The input path, as written in the manifest.
+        path: String,
+        /// Why the input could not be read.
+        reason: String,
+    },
+    /// A file alignment is not a power of two or is larger than 16 MiB.
+    InvalidAlignment(u32),
+    /// A file has both a `path` and `sections`.
+    ConflictingContent(String),
+    /// A dependency expression section has both `opcodes` and an `expression`.
+    ConflictingDepex,
+    /// A dependency expression does not compile.
+    InvalidDepex(CompileError),
+    /// The files do not fit in the volume described by the block map.
+    VolumeTooSmall {
+        /// The size of the serialized volume, in bytes.
+        size: usize,
+        /// The total size of the block map, in bytes.
+        capacity: usize,
+    },
+    /// Composing or serializing the volume failed.
+    FirmwareFileSystem(FirmwareFileSystemError),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::Manifest(reason) => write!(f, "invalid manifest: {reason}"),
+            BuildError::UnknownGuid(name) => write!(f, "'{name}' is neither a GUID nor a name in the guids table"),
+            BuildError::Input { path, reason } => write!(f, "failed to read '{path}': {reason}"),
+            BuildError::InvalidAlignment(alignment) => write!(f, "invalid file alignment {alignment:#x}"),
+            BuildError::ConflictingContent(name) => write!(f, "file '{name}' has both a path and sections"),
+            BuildError::ConflictingDepex => write!(f, "depex section has both opcodes and an expression"),
+            BuildError::InvalidDepex(err) => write!(f, "invalid dependency expression: {err}"),
+            BuildError::VolumeTooSmall { size, capacity } => {
+                write!(f, "volume content of {size:#x} bytes does not fit in the {capacity:#x} byte block map")
+            }
+            BuildError::FirmwareFileSystem(err) => write!(f, "failed to build the volume: {err:?}"),
+        }
+    }
+}
+
+impl From<FirmwareFileSystemError> for BuildError {
+    fn from(err: FirmwareFileSystemError) -> Self {
+        BuildError::FirmwareFileSystem(err)
+    }
+}
+
+/// Builds the firmware volume described by `manifest`.
+///
+/// `read_input` returns the bytes of an input `path` of the manifest, or why it could not be read.
+///
+/// ## Examples
+///
+/// ```rust
+/// use patina_ffs::volume::VolumeRef;
+/// use patina_ffs_tools::{build_volume, manifest::Manifest};
+///
+/// let manifest = Manifest::from_toml(
+///     r#"
+///     [volume]
+///     block_map = [{ num_blocks = 1, length = 0x1000 }]
+///     attributes = 0x800
+///
+///     [[files]]
+///     name = "2c5a7d5e-9b1f-4f0e-8d3c-6a1b2c3d4e5f"
+///     type = "freeform"
+///     sections = [{ type = "raw", path = "data.bin" }]
+///     "#,
+/// )
+/// .unwrap();
+///
+/// let image = build_volume(&manifest, |_path| Ok(b"data".to_vec())).unwrap();
+/// assert_eq!(image.len(), 0x1000);
+/// assert_eq!(VolumeRef::new(&image).unwrap().files().count(), 1);
+/// ```
+pub fn build_volume<R>(manifest: &Manifest, mut read_input: R) -> Result<Vec<u8>, BuildError>
+where
+    R: FnMut(&str) -> Result<Vec<u8>, String>,
+{
+    let mut builder = Builder { manifest, read_input: &mut read_input };
+    builder.build()
+}
+
+struct Builder<'a> {
+    manifest: &'a Manifest,
+    read_input: &'a mut dyn FnMut(&str) -> Result<Vec<u8>, String>,
+}
+
+impl Builder<'_> {
+    fn build(&mut self) -> Result<Vec<u8>, BuildError> {
+        let config = &self.manifest.volume;
+        let block_map: Vec<BlockMapEntry> = config
+            .block_map
+            .iter()
+            .map(|entry| BlockMapEntry { num_blocks: entry.num_blocks, length: entry.length })
+            .collect();
+        let capacity = block_map.iter().map(|entry| entry.num_blocks as usize * entry.length as usize).sum::<usize>();
+
+        let mut volume = Volume::new(block_map);
+        volume.set_attributes(config.attributes);
+        volume.set_file_system_guid(match config.file_system {
+            FileSystem::Ffs2 => ffs::guid::EFI_FIRMWARE_FILE_SYSTEM2_GUID,
+            FileSystem::Ffs3 => ffs::guid::EFI_FIRMWARE_FILE_SYSTEM3_GUID,
+        })?;
+        if let Some(name) = &config.name {
+            volume.set_ext_header(self.guid(name)?, Vec::new())?;
+        }
+
+        let erase_polarity = config.attributes & fvb2::ERASE_POLARITY != 0;
+        for file_config in &self.manifest.files {
+            let mut file = self.file(file_config)?;
+            file.set_erase_polarity(erase_polarity);
+            volume.files_mut().push(file);
+        }
+
+        volume.compose(&CompositeSectionComposer::new())?;
+        volume.set_capacity(capacity);
+        let image = volume.serialize()?;
+        if image.len() > capacity {
+            Err(BuildError::VolumeTooSmall { size: image.len(), capacity })?;
+        }
+        Ok(image)
+    }
+
+    fn file(&mut self, config: &FileConfig) -> Result<File, BuildError> {
+        let name = self.guid(&config.name)?;
+        let file_type = config.file_type.raw();
+
+        let mut file = match &config.path {
+            Some(_) if !config.sections.is_empty() => Err(BuildError::ConflictingContent(config.name.clone()))?,
+            Some(path) => File::new_with_content(name, file_type, self.input(path)?),
+            None => File::new(name, file_type),
+        };
+        for section in &config.sections {
+            let section = self.section(section)?;
+            file.sections_mut().push(section);
+        }
+
+        let mut fv_attributes = match config.alignment {
+            None => 0,
+            Some(alignment) if alignment.is_power_of_two() && alignment <= 1 << 24 => alignment.trailing_zeros(),
+            Some(alignment) => Err(BuildError::InvalidAlignment(alignment))?,
+        };
+        if config.fixed {
+            fv_attributes |= fv::file::raw::attribute::FIXED;
+        }
+        file.set_fv_attributes(fv_attributes)?;
+        file.set_data_checksum(config.checksum);
+        Ok(file)
+    }
+
+    fn section(&mut self, config: &SectionConfig) -> Result<Section, BuildError> {
+        use ffs::section::raw_type;
+
+        let section = match config {
+            SectionConfig::Pe32 { path } => self.leaf_section(raw_type::PE32, path)?,
+            SectionConfig::Pic { path } => self.leaf_section(raw_type::PIC, path)?,
+            SectionConfig::Te { path } => self.leaf_section(raw_type::TE, path)?,
+            SectionConfig::Raw { path } => self.leaf_section(raw_type::RAW, path)?,
+            SectionConfig::Compatibility16 { path } => self.leaf_section(raw_type::COMPATIBILITY16, path)?,
+            SectionConfig::FirmwareVolumeImage { path } => self.leaf_section(raw_type::FIRMWARE_VOLUME_IMAGE, path)?,
+            SectionConfig::FreeformSubtypeGuid { guid, path } => {
+                let sub_type_guid = self.guid(guid)?;
+                let data = self.input(path)?;
+                let header = header::FreeformSubtypeGuid { sub_type_guid };
+                Section::new_from_header_with_data(
+                    SectionHeader::FreeFormSubtypeGuid(header, content_size(&data)?),
+                    data,
+                )?
+            }
+            SectionConfig::Ui { name } => {
+                let data = ucs2(name);
+                Section::new_from_header_with_data(
+                    SectionHeader::Standard(raw_type::USER_INTERFACE, content_size(&data)?),
+                    data,
+                )?
+            }
+            SectionConfig::Version { build_number, version } => {
+                let data = version.as_deref().map(ucs2).unwrap_or_default();
+                let header = header::Version { build_number: *build_number };
+                Section::new_from_header_with_data(SectionHeader::Version(header, content_size(&data)?), data)?
+            }
+            SectionConfig::Depex { phase, opcodes, expression } => {
+                let section_type = match phase {
+                    DepexPhase::Dxe => raw_type::DXE_DEPEX,
+                    DepexPhase::Pei => raw_type::PEI_DEPEX,
+                    DepexPhase::Mm => raw_type::MM_DEPEX,
+                };
+                let data = match expression {
+                    Some(_) if !opcodes.is_empty() => Err(BuildError::ConflictingDepex)?,
+                    Some(expression) => compile(expression, &self.symbols()).map_err(BuildError::InvalidDepex)?,
+                    None => self.depex(opcodes)?,
+                };
+                Section::new_from_header_with_data(SectionHeader::Standard(section_type, content_size(&data)?), data)?
+            }
+            SectionConfig::Compression { algorithm, sections } => {
+                let compression_type = match algorithm {
+                    CompressionAlgorithm::None => header::NOT_COMPRESSED,
+                    CompressionAlgorithm::Standard => header::STANDARD_COMPRESSION,
+                };
+                let header = header::Compression { uncompressed_length: 0, compression_type };
+                Section::new_encapsulation(SectionHeader::Compression(header, 0), self.sections(sections)?)?
+            }
+            SectionConfig::Guided { algorithm, sections } => {
+                let (section_definition_guid, attributes) = match algorithm {
+                    GuidedAlgorithm::Brotli => (guid::BROTLI_SECTION, header::GUIDED_SECTION_PROCESSING_REQUIRED),
+                    GuidedAlgorithm::Crc32 => (guid::CRC32_SECTION, header::GUIDED_SECTION_AUTH_STATUS_VALID),
+                    GuidedAlgorithm::Lzma => (guid::LZMA_SECTION, header::GUIDED_SECTION_PROCESSING_REQUIRED),
+                    GuidedAlgorithm::Tiano => {
+                        (guid::TIANO_DECOMPRESS_SECTION, header::GUIDED_SECTION_PROCESSING_REQUIRED)
+                    }
+                };
+                let header = header::GuidDefined { section_definition_guid, data_offset: 0, attributes };
+                Section::new_encapsulation(SectionHeader::GuidDefined(header, Vec::new(), 0), self.sections(sections)?)?
+            }
+        };
+        Ok(section)
+    }
+
+    fn sections(&mut self, configs: &[SectionConfig]) -> Result<Vec<Section>, BuildError> {
+        configs.iter().map(|config| self.section(config)).collect()
+    }
+
+    fn leaf_section(&mut self, section_type: u8, path: &str) -> Result<Section, BuildError> {
+        let data = self.input(path)?;
+        Ok(Section::new_from_header_with_data(SectionHeader::Standard(section_type, content_size(&data)?), data)?)
+    }
+
+    /// Encodes `opcodes` as a dependency expression, appending an `END` opcode when the expression lacks one.
+    fn depex(&self, opcodes: &[DepexOpcode]) -> Result<Vec<u8>, BuildError> {
+        let mut depex = Vec::new();
+        for opcode in opcodes {
+            let (op, guid) = match opcode {
+                DepexOpcode::Before(guid) => (0x00, Some(guid)),
+                DepexOpcode::After(guid) => (0x01, Some(guid)),
+                DepexOpcode::Push(guid) => (0x02, Some(guid)),
+                DepexOpcode::And => (0x03, None),
+                DepexOpcode::Or => (0x04, None),
+                DepexOpcode::Not => (0x05, None),
+                DepexOpcode::True => (0x06, None),
+                DepexOpcode::False => (0x07, None),
+                DepexOpcode::End => (0x08, None),
+                DepexOpcode::Sor => (0x09, None),
+            };
+            depex.push(op);
+            if let Some(guid) = guid {
+                depex.extend_from_slice(self.guid(guid)?.as_bytes());
+            }
+        }
+        if opcodes.last() != Some(&DepexOpcode::End) {
+            depex.push(0x08);
+        }
+        Ok(depex)
+    }
+
+    /// The entries of the manifest's `guids` table that hold a GUID in registry format.
+    fn symbols(&self) -> SymbolTable {
+        self.manifest
+            .guids
+            .iter()
+            .filter_map(|(name, value)| {
+                let uuid = uuid::Uuid::parse_str(value).ok()?;
+                Some((name.as_str(), efi::Guid::from_bytes(&uuid.to_bytes_le())))
+            })
+            .collect()
+    }
+
+    fn input(&mut self, path: &str) -> Result<Vec<u8>, BuildError> {
+        (self.read_input)(path).map_err(|reason| BuildError::Input { path: path.to_string(), reason })
+    }
+
+    /// Resolves a GUID in registry format, or the name of an entry of the manifest's `guids` table.
+    fn guid(&self, guid: &str) -> Result<efi::Guid, BuildError> {
+        let value = self.manifest.guids.get(guid).map(String::as_str).unwrap_or(guid);
+        uuid::Uuid::parse_str(value)
+            .map(|uuid| efi::Guid::from_bytes(&uuid.to_bytes_le()))
+            .map_err(|_| BuildError::UnknownGuid(guid.to_string()))
+    }
+}
+
+fn content_size(data: &[u8]) -> Result<u32, BuildError> {
+    Ok(data.len().try_into().map_err(|_| FirmwareFileSystemError::InvalidParameter)?)
+}
+
+/// Encodes `string` as a null-terminated UTF-16 string.
+fn ucs2(string: &str) -> Vec<u8> {
+    string.encode_utf16().chain(core::iter::once(0)).flat_map(u16::to_le_bytes).collect()
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use alloc::{format, vec};
+    use patina_ffs::volume::VolumeRef;
+    use patina_ffs_extractors::CompositeSectionExtractor;
+
+    const DRIVER_GUID: &str = "0d7c9c3c-3a3b-4c8e-9a4f-6c7e2b1d8f10";
+    const PROTOCOL_GUID: &str = "1e5668e2-8481-11d4-bcf1-0080c73c8881";
+    const FV_NAME: &str = "6d99e806-3d38-42c2-a095-5f4300bfd7dc";
+    const DATA_GUID: &str = "2c5a7d5e-9b1f-4f0e-8d3c-6a1b2c3d4e5f";
+
+    fn toml_manifest() -> String {
+        format!(
+            r#"
+            [guids]
+            gDriverGuid = "{DRIVER_GUID}"
+            gProtocolGuid = "{PROTOCOL_GUID}"
+
+            [volume]
+            name = "{FV_NAME}"
+            block_map = [{{ num_blocks = 0x10, length = 0x1000 }}]
+            attributes = 0x0004fe3f
+
+            [[files]]
+            name = "gDriverGuid"
+            type = "driver"
+            alignment = 0x1000
+            checksum = true
+            sections = [
+                {{ type = "depex", opcodes = [{{ push = "gProtocolGuid" }}, "not"] }},
+                {{ type = "guided", algorithm = "lzma", sections = [
+                    {{ type = "pe32", path = "Driver.efi" }},
+                    {{ type = "ui", name = "Driver" }},
+                    {{ type = "version", build_number = 7, version = "1.0" }},
+                ] }},
+            ]
+
+            [[files]]
+            name = "{DATA_GUID}"
+            type = "freeform"
+            sections = [
+                {{ type = "guided", algorithm = "crc32", sections = [{{ type = "raw", path = "data.bin" }}] }},
+                {{ type = "compression", algorithm = "none", sections = [{{ type = "raw", path = "data.bin" }}] }},
+                {{ type = "compression", algorithm = "standard", sections = [{{ type = "raw", path = "data.bin" }}] }},
+            ]
+
+            [[files]]
+            name = "gProtocolGuid"
+            type = "raw"
+            path = "data.bin"
+            "#
+        )
+    }
+
+    fn json_manifest() -> String {
+        format!(
+            r#"{{
+                "guids": {{ "gDriverGuid": "{DRIVER_GUID}", "gProtocolGuid": "{PROTOCOL_GUID}" }},
+                "volume": {{ "name": "{FV_NAME}", "block_map": [{{ "num_blocks": 16, "length": 4096 }}], "attributes": 327231 }},
+                "files": [
+                    {{ "name": "gDriverGuid", "type": "driver", "alignment": 4096, "checksum": true, "sections": [
+                        {{ "type": "depex", "opcodes": [{{ "push": "gProtocolGuid" }}, "not"] }},
+                        {{ "type": "guided", "algorithm": "lzma", "sections": [
+                            {{ "type": "pe32", "path": "Driver.efi" }},
+                            {{ "type": "ui", "name": "Driver" }},
+                            {{ "type": "version", "build_number": 7, "version": "1.0" }}
+                        ] }}
+                    ] }},
+                    {{ "name": "{DATA_GUID}", "type": "freeform", "sections": [
+                        {{ "type": "guided", "algorithm": "crc32", "sections": [{{ "type": "raw", "path": "data.bin" }}] }},
+                        {{ "type": "compression", "algorithm": "none", "sections": [{{ "type": "raw", "path": "data.bin" }}] }},
+                        {{ "type": "compression", "algorithm": "standard", "sections": [{{ "type": "raw", "path": "data.bin" }}] }}
+                    ] }},
+                    {{ "name": "gProtocolGuid", "type": "raw", "path": "data.bin" }}
+                ]
+            }}"#
+        )
+    }
+
+    fn read_input(path: &str) -> Result<Vec<u8>, String> {
+        match path {
+            "Driver.efi" => Ok((0..0x3000u32).map(|x| (x % 251) as u8).collect()),
+            "data.bin" => Ok(b"Hello, World!".repeat(100)),
+            _ => Err("not found".to_string()),
+        }
+    }
+
+    fn guid(guid: &str) -> efi::Guid {
+        efi::Guid::from_bytes(&uuid::Uuid::parse_str(guid).unwrap().to_bytes_le())
+    }
+
+    #[test]
+    fn test_toml_and_json_manifests_are_equivalent() {
+        assert_eq!(Manifest::from_toml(&toml_manifest()).unwrap(), Manifest::from_json(&json_manifest()).unwrap());
+    }
+
+    #[test]
+    fn test_build_volume() {
+        let manifest = Manifest::from_toml(&toml_manifest()).unwrap();
+        let image = build_volume(&manifest, read_input).unwrap();
+        assert_eq!(image.len(), 0x10000);
+
+        let volume = VolumeRef::new(&image).unwrap();
+        assert_eq!(volume.attributes(), 0x0004fe3f);
+        assert_eq!(volume.fv_name(), Some(guid(FV_NAME)));
+        assert_eq!(volume.file_system_guid(), ffs::guid::EFI_FIRMWARE_FILE_SYSTEM3_GUID);
+
+        let files = volume.files().collect::<Result<Vec<_>, _>>().unwrap();
+        let files: Vec<_> =
+            files.into_iter().filter(|file| file.file_type_raw() != ffs::file::raw::r#type::FFS_PAD).collect();
+        assert_eq!(files.len(), 3);
+
+        let driver = &files[0];
+        assert_eq!(driver.name(), guid(DRIVER_GUID));
+        assert_eq!(driver.file_type_raw(), ffs::file::raw::r#type::DRIVER);
+        assert_eq!(driver.fv_attributes() & fv::file::raw::attribute::ALIGNMENT, 12);
+        let sections = driver.sections_with_extractor(&CompositeSectionExtractor::new()).unwrap();
+        let section_types: Vec<_> = sections.iter().map(|section| section.section_type_raw()).collect();
+        use ffs::section::raw_type;
+        assert_eq!(
+            section_types,
+            vec![
+                raw_type::DXE_DEPEX,
+                raw_type::encapsulated::GUID_DEFINED,
+                raw_type::PE32,
+                raw_type::USER_INTERFACE,
+                raw_type::VERSION
+            ]
+        );
+        let mut depex = vec![0x02];
+        depex.extend_from_slice(guid(PROTOCOL_GUID).as_bytes());
+        depex.extend_from_slice(&[0x05, 0x08]);
+        assert_eq!(sections[0].try_content_as_slice().unwrap(), depex);
+        assert_eq!(sections[2].try_content_as_slice().unwrap(), read_input("Driver.efi").unwrap());
+        assert_eq!(sections[3].try_content_as_slice().unwrap(), ucs2("Driver"));
+        assert_eq!(sections[4].try_content_as_slice().unwrap(), ucs2("1.0"));
+
+        let data = &files[1];
+        let sections = data.sections_with_extractor(&CompositeSectionExtractor::new()).unwrap();
+        let section_types: Vec<_> = sections.iter().map(|section| section.section_type_raw()).collect();
+        assert_eq!(
+            section_types,
+            vec![
+                raw_type::encapsulated::GUID_DEFINED,
+                raw_type::RAW,
+                raw_type::encapsulated::COMPRESSION,
+                raw_type::encapsulated::COMPRESSION
+            ]
+        );
+        assert_eq!(sections[1].try_content_as_slice().unwrap(), read_input("data.bin").unwrap());
+
+        let raw = &files[2];
+        assert_eq!(raw.file_type_raw(), ffs::file::raw::r#type::RAW);
+        assert_eq!(raw.content(), read_input("data.bin").unwrap());
+    }
+
+    #[test]
+    fn test_build_volume_is_reproducible() {
+        let first = build_volume(&Manifest::from_toml(&toml_manifest()).unwrap(), read_input).unwrap();
+        let second = build_volume(&Manifest::from_json(&json_manifest()).unwrap(), read_input).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_build_volume_with_depex_expression() {
+        let opcodes = r#"opcodes = [{ push = "gProtocolGuid" }, "not"]"#;
+        assert!(toml_manifest().contains(opcodes));
+        let expression = toml_manifest().replace(opcodes, r#"expression = "NOT gProtocolGuid""#);
+
+        let first = build_volume(&Manifest::from_toml(&toml_manifest()).unwrap(), read_input).unwrap();
+        let second = build_volume(&Manifest::from_toml(&expression).unwrap(), read_input).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_build_volume_errors() {
+        let manifest = |files: &str| {
+            Manifest::from_toml(&format!(
+                "[volume]\nblock_map = [{{ num_blocks = 1, length = 0x1000 }}]\nattributes = 0x800\n{files}"
+            ))
+            .unwrap()
+        };
+
+        let unknown_guid = manifest("[[files]]\nname = \"gMissingGuid\"\ntype = \"raw\"");
+        assert_eq!(build_volume(&unknown_guid, read_input), Err(BuildError::UnknownGuid("gMissingGuid".into())));
+
+        let missing_input =
+            manifest(&format!("[[files]]\nname = \"{DATA_GUID}\"\ntype = \"raw\"\npath = \"missing.bin\""));
+        assert_eq!(
+            build_volume(&missing_input, read_input),
+            Err(BuildError::Input { path: "missing.bin".into(), reason: "not found".into() })
+        );
+
+        let bad_alignment = manifest(&format!("[[files]]\nname = \"{DATA_GUID}\"\ntype = \"raw\"\nalignment = 3"));
+        assert_eq!(build_volume(&bad_alignment, read_input), Err(BuildError::InvalidAlignment(3)));
+
+        let conflicting = manifest(&format!(
+            "[[files]]\nname = \"{DATA_GUID}\"\ntype = \"raw\"\npath = \"data.bin\"\nsections = [{{ type = \"ui\", name = \"x\" }}]"
+        ));
+        assert_eq!(build_volume(&conflicting, read_input), Err(BuildError::ConflictingContent(DATA_GUID.into())));
+
+        let too_large = manifest(&format!("[[files]]\nname = \"{DATA_GUID}\"\ntype = \"raw\"\npath = \"Driver.efi\""));
+        assert!(matches!(
+            build_volume(&too_large, read_input),
+            Err(BuildError::VolumeTooSmall { capacity: 0x1000, .. })
+        ));
+
+        let conflicting_depex = manifest(&format!(
+            "[[files]]\nname = \"{DATA_GUID}\"\ntype = \"driver\"\nsections = [{{ type = \"depex\", opcodes = [\"true\"], expression = \"TRUE\" }}]"
+        ));
+        assert_eq!(build_volume(&conflicting_depex, read_input), Err(BuildError::ConflictingDepex));
+
+        let invalid_depex = manifest(&format!(
+            "[[files]]\nname = \"{DATA_GUID}\"\ntype = \"driver\"\nsections = [{{ type = \"depex\", expression = \"TRUE AND gMissingGuid\" }}]"
+        ));
+        assert!(matches!(
+            build_volume(&invalid_depex, read_input),
+            Err(BuildError::InvalidDepex(CompileError { span, .. })) if span == (9..21)
+        ));
+
+        assert!(matches!(Manifest::from_toml("[volume]\nblock_map = []\nunknown = 1"), Err(BuildError::Manifest(_))));
+    }
+}
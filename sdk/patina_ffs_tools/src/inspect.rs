@@ -0,0 +1,754 @@
+//! Inspection of firmware volume images.
+//!
+//! [find_volumes] locates the firmware volumes of an FV or full flash image by scanning for the `_FVH` signature and
+//! parses each of them into a [VolumeInfo] tree. Encapsulation sections are extracted with the
+//! [CompositeSectionExtractor], and firmware volume image sections are parsed as nested volumes. The tree can be
+//! printed with [write_tree] and compared with [diff].
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, mem};
+
+use patina::pi::{
+    fw_fs::{
+        FirmwareVolume,
+        ffs::{self, file::raw::r#type, section::raw_type},
+        fv, guid,
+    },
+    serializable::{format_guid, serializable_fv::FirmwareVolumeSerDe},
+};
+use patina_ffs::{
+    FirmwareFileSystemError,
+    section::{Section, SectionHeader, SectionIterator},
+    volume::VolumeRef,
+};
+use patina_ffs_extractors::CompositeSectionExtractor;
+use patina_internal_depex::{
+    Depex, Opcode,
+    text::{SymbolTable, disassemble},
+};
+use r_efi::efi;
+
+use crate::manifest::FileType;
+
+/// Offset of the `_FVH` signature in the firmware volume header.
+const SIGNATURE_OFFSET: usize = mem::offset_of!(fv::Header, signature);
+
+/// A firmware volume found in an image.
+#[derive(Debug, Clone)]
+pub struct VolumeInfo {
+    /// Offset of the volume in the image it was found in.
+    pub offset: usize,
+    /// Length of the volume, in bytes.
+    pub size: usize,
+    /// The name of the volume from its extended header, if it has one.
+    pub name: Option<efi::Guid>,
+    /// The file system GUID of the volume.
+    pub file_system_guid: efi::Guid,
+    /// The `EFI_FVB_ATTRIBUTES_2` of the volume.
+    pub attributes: u32,
+    /// The files of the volume, without pad files.
+    pub files: Vec<FileInfo>,
+    /// The error that stopped parsing the files of the volume, if any.
+    pub error: Option<FirmwareFileSystemError>,
+}
+
+/// A file of a firmware volume.
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    /// The name GUID of the file.
+    pub name: efi::Guid,
+    /// The raw `EFI_FV_FILETYPE` of the file.
+    pub file_type: u8,
+    /// The raw FFS attributes of the file.
+    pub attributes: u8,
+    /// The size of the file, including its header.
+    pub size: usize,
+    /// The file data that follows the header.
+    pub data: Vec<u8>,
+    /// The sections of the file. Empty for files that are not made of sections.
+    pub sections: Vec<SectionInfo>,
+    /// The error that stopped parsing the sections of the file, if any.
+    pub error: Option<FirmwareFileSystemError>,
+}
+
+/// A section of a file.
+#[derive(Debug, Clone)]
+pub struct SectionInfo {
+    /// The header of the section.
+    pub header: SectionHeader,
+    /// The content of the section; encoded content for encapsulation sections.
+    pub content: Vec<u8>,
+    /// The extracted sub-sections of an encapsulation section.
+    pub sections: Vec<SectionInfo>,
+    /// The decoded expression of a dependency expression section.
+    pub depex: Option<Vec<Opcode>>,
+    /// The nested volume of a firmware volume image section.
+    pub volume: Option<VolumeInfo>,
+    /// The error that stopped extracting an encapsulation section, if any.
+    pub error: Option<FirmwareFileSystemError>,
+}
+
+impl SectionInfo {
+    /// The name of the section type, as written in a manifest.
+    pub fn type_name(&self) -> String {
+        section_type_name(self.header.section_type_raw())
+    }
+
+    /// Iterates over the leaf sections of this section, or the section itself if it is a leaf.
+    pub fn leaves(&self) -> impl Iterator<Item = &SectionInfo> {
+        let mut pending = alloc::vec![self];
+        core::iter::from_fn(move || {
+            while let Some(section) = pending.pop() {
+                if section.sections.is_empty() {
+                    return Some(section);
+                }
+                pending.extend(section.sections.iter().rev());
+            }
+            None
+        })
+    }
+}
+
+/// Finds and parses the firmware volumes of `image`, which is either a single volume or a flash image holding volumes.
+///
+/// Volume headers are searched for at every 8-byte boundary that does not fall in a volume found earlier.
+///
+/// ## Examples
+///
+/// ```rust
+/// use patina_ffs::volume::Volume;
+/// use patina::pi::fw_fs::fv::BlockMapEntry;
+/// use patina_ffs_tools::inspect::find_volumes;
+///
+/// let fv = Volume::new(vec![BlockMapEntry { num_blocks: 1, length: 0x1000 }]).serialize().unwrap();
+/// let mut flash = vec![0xffu8; 0x1000];
+/// flash.extend(&fv);
+///
+/// let volumes = find_volumes(&flash);
+/// assert_eq!(volumes.len(), 1);
+/// assert_eq!(volumes[0].offset, 0x1000);
+/// ```
+pub fn find_volumes(image: &[u8]) -> Vec<VolumeInfo> {
+    let mut volumes = Vec::new();
+    let mut offset = 0;
+    while offset + mem::size_of::<fv::Header>() <= image.len() {
+        if image[offset + SIGNATURE_OFFSET..].starts_with(b"_FVH")
+            && let Ok(volume) = parse_volume(offset, &image[offset..])
+        {
+            offset += volume.size.next_multiple_of(8);
+            volumes.push(volume);
+        } else {
+            offset += 8;
+        }
+    }
+    volumes
+}
+
+/// Parses the firmware volume at the start of `data`, recording `offset` as its location.
+pub fn parse_volume(offset: usize, data: &[u8]) -> Result<VolumeInfo, FirmwareFileSystemError> {
+    let size = VolumeRef::new(data)?.size() as usize;
+    let volume = VolumeRef::new(&data[..size])?;
+
+    let mut info = VolumeInfo {
+        offset,
+        size,
+        name: volume.fv_name(),
+        file_system_guid: volume.file_system_guid(),
+        attributes: volume.attributes(),
+        files: Vec::new(),
+        error: None,
+    };
+    for file in volume.files() {
+        let file = match file {
+            Ok(file) => file,
+            Err(err) => {
+                info.error = Some(err);
+                break;
+            }
+        };
+        let mut file_info = FileInfo {
+            name: file.name(),
+            file_type: file.file_type_raw(),
+            attributes: file.attributes_raw(),
+            size: file.size(),
+            data: file.content().to_vec(),
+            sections: Vec::new(),
+            error: None,
+        };
+        if has_sections(file_info.file_type) {
+            for section in SectionIterator::new(file.content()) {
+                match section {
+                    Ok(section) => file_info.sections.push(section_info(section)),
+                    Err(err) => {
+                        file_info.error = Some(err);
+                        break;
+                    }
+                }
+            }
+        }
+        info.files.push(file_info);
+    }
+    Ok(info)
+}
+
+/// Returns whether files of type `file_type` are made of sections.
+fn has_sections(file_type: u8) -> bool {
+    !matches!(file_type, r#type::RAW | r#type::FFS_PAD) && !(r#type::FFS_MIN..=r#type::FFS_MAX).contains(&file_type)
+}
+
+fn section_info(mut section: Section) -> SectionInfo {
+    let error = section.extract(&CompositeSectionExtractor::new()).err();
+    section_info_from(&section, error)
+}
+
+fn section_info_from(section: &Section, error: Option<FirmwareFileSystemError>) -> SectionInfo {
+    let content = section.try_content_as_slice().map(<[u8]>::to_vec).unwrap_or_default();
+    let depex = match section.section_type_raw() {
+        raw_type::DXE_DEPEX | raw_type::PEI_DEPEX | raw_type::MM_DEPEX => {
+            Some(Depex::from(content.as_slice()).opcodes().to_vec())
+        }
+        _ => None,
+    };
+    let volume = match section.section_type_raw() {
+        raw_type::FIRMWARE_VOLUME_IMAGE => parse_volume(0, &content).ok(),
+        _ => None,
+    };
+    SectionInfo {
+        header: section.header().clone(),
+        sections: section.sub_sections().map(|sub_section| section_info_from(sub_section, None)).collect(),
+        content,
+        depex,
+        volume,
+        error,
+    }
+}
+
+/// Converts the volumes found in `image` to the serializable representation used for JSON output.
+///
+/// The base address of each volume is its offset in `image`. Nested volumes are not included.
+pub fn serializable_volumes(image: &[u8], volumes: &[VolumeInfo]) -> Vec<FirmwareVolumeSerDe> {
+    volumes
+        .iter()
+        .filter_map(|volume| {
+            let fv = FirmwareVolume::new(&image[volume.offset..volume.offset + volume.size]).ok()?;
+            let mut serializable = FirmwareVolumeSerDe::from(fv);
+            serializable.fv_base_address = volume.offset as u64;
+            Some(serializable)
+        })
+        .collect()
+}
+
+/// Writes the file and section tree of `volumes` to `out`.
+pub fn write_tree(out: &mut dyn fmt::Write, volumes: &[VolumeInfo]) -> fmt::Result {
+    for volume in volumes {
+        write_volume(out, volume, 0)?;
+    }
+    Ok(())
+}
+
+fn write_volume(out: &mut dyn fmt::Write, volume: &VolumeInfo, depth: usize) -> fmt::Result {
+    let indent = depth * 2;
+    write!(out, "{:indent$}FV @ {:#x}, {:#x} bytes", "", volume.offset, volume.size)?;
+    if let Some(name) = volume.name {
+        write!(out, ", name {}", format_guid(name))?;
+    }
+    let file_system =
+        if volume.file_system_guid == ffs::guid::EFI_FIRMWARE_FILE_SYSTEM2_GUID { "ffs2" } else { "ffs3" };
+    writeln!(out, ", attributes {:#010x}, {file_system}", volume.attributes)?;
+
+    for file in &volume.files {
+        writeln!(
+            out,
+            "{:indent$}  File {} {}, {:#x} bytes, attributes {:#04x}",
+            "",
+            format_guid(file.name),
+            file_type_name(file.file_type),
+            file.size,
+            file.attributes
+        )?;
+        for section in &file.sections {
+            write_section(out, section, depth + 2)?;
+        }
+        if let Some(err) = file.error {
+            writeln!(out, "{:indent$}    error: {err:?}", "")?;
+        }
+    }
+    if let Some(err) = volume.error {
+        writeln!(out, "{:indent$}  error: {err:?}", "")?;
+    }
+    Ok(())
+}
+
+fn write_section(out: &mut dyn fmt::Write, section: &SectionInfo, depth: usize) -> fmt::Result {
+    let indent = depth * 2;
+    write!(out, "{:indent$}{}, {:#x} bytes", "", describe_header(&section.header), section.content.len())?;
+    match &section.header {
+        SectionHeader::Standard(raw_type::USER_INTERFACE, _) => write!(out, ": \"{}\"", ucs2(&section.content))?,
+        SectionHeader::Version(version, _) => {
+            let build_number = version.build_number;
+            write!(out, ": build {build_number} \"{}\"", ucs2(&section.content))?
+        }
+        _ => (),
+    }
+    if let Some(depex) = &section.depex {
+        match disassemble(depex, &SymbolTable::new()) {
+            Ok(text) => write!(out, ": {text}")?,
+            Err(_) => {
+                write!(out, ":")?;
+                for opcode in depex {
+                    write!(out, " {}", describe_opcode(opcode))?;
+                }
+            }
+        }
+    }
+    writeln!(out)?;
+
+    for sub_section in &section.sections {
+        write_section(out, sub_section, depth + 1)?;
+    }
+    if let Some(volume) = &section.volume {
+        write_volume(out, volume, depth + 1)?;
+    }
+    if let Some(err) = section.error {
+        writeln!(out, "{:indent$}  error: {err:?}", "")?;
+    }
+    Ok(())
+}
+
+/// Describes the type of a section, along with the encoding of encapsulation sections.
+fn describe_header(header: &SectionHeader) -> String {
+    match header {
+        SectionHeader::Compression(compression, _) => match compression.compression_type {
+            ffs::section::header::NOT_COMPRESSED => "compression (none)".to_string(),
+            ffs::section::header::STANDARD_COMPRESSION => "compression (standard)".to_string(),
+            other => format!("compression ({other:#04x})"),
+        },
+        SectionHeader::GuidDefined(guid_defined, _, _) => {
+            let algorithm = match guid_defined.section_definition_guid {
+                guid::BROTLI_SECTION => "brotli".to_string(),
+                guid::CRC32_SECTION => "crc32".to_string(),
+                guid::LZMA_SECTION => "lzma".to_string(),
+                guid::LZMA_F86_SECTION => "lzma_f86".to_string(),
+                guid::LZMA_PARALLEL_SECTION => "lzma_parallel".to_string(),
+                guid::TIANO_DECOMPRESS_SECTION => "tiano".to_string(),
+                other => format_guid(other),
+            };
+            format!("guided ({algorithm})")
+        }
+        SectionHeader::FreeFormSubtypeGuid(freeform, _) => {
+            format!("freeform_subtype_guid ({})", format_guid(freeform.sub_type_guid))
+        }
+        header => section_type_name(header.section_type_raw()),
+    }
+}
+
+/// The name of a file type, as written in a manifest.
+fn file_type_name(file_type: u8) -> String {
+    FileType::from_raw(file_type).map(|file_type| file_type.name().to_string()).unwrap_or(format!("{file_type:#04x}"))
+}
+
+/// The name of a section type, as written in a manifest.
+fn section_type_name(section_type: u8) -> String {
+    let name = match section_type {
+        raw_type::PE32 => "pe32",
+        raw_type::PIC => "pic",
+        raw_type::TE => "te",
+        raw_type::DXE_DEPEX => "dxe_depex",
+        raw_type::VERSION => "version",
+        raw_type::USER_INTERFACE => "ui",
+        raw_type::COMPATIBILITY16 => "compatibility16",
+        raw_type::FIRMWARE_VOLUME_IMAGE => "firmware_volume_image",
+        raw_type::FREEFORM_SUBTYPE_GUID => "freeform_subtype_guid",
+        raw_type::RAW => "raw",
+        raw_type::PEI_DEPEX => "pei_depex",
+        raw_type::MM_DEPEX => "mm_depex",
+        raw_type::encapsulated::COMPRESSION => "compression",
+        raw_type::encapsulated::GUID_DEFINED => "guided",
+        raw_type::encapsulated::DISPOSABLE => "disposable",
+        other => return format!("{other:#04x}"),
+    };
+    name.to_string()
+}
+
+fn describe_opcode(opcode: &Opcode) -> String {
+    match opcode {
+        Opcode::Before(guid) => format!("BEFORE {guid}"),
+        Opcode::After(guid) => format!("AFTER {guid}"),
+        Opcode::Push(guid, _) => format!("PUSH {guid}"),
+        Opcode::And => "AND".to_string(),
+        Opcode::Or => "OR".to_string(),
+        Opcode::Not => "NOT".to_string(),
+        Opcode::True => "TRUE".to_string(),
+        Opcode::False => "FALSE".to_string(),
+        Opcode::End => "END".to_string(),
+        Opcode::Sor => "SOR".to_string(),
+        Opcode::Unknown => "UNKNOWN".to_string(),
+        Opcode::Malformed { opcode, .. } => format!("MALFORMED({opcode:#04x})"),
+    }
+}
+
+/// Decodes a null-terminated UTF-16 string, replacing invalid code units.
+fn ucs2(data: &[u8]) -> String {
+    let units = data.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).take_while(|unit| *unit != 0);
+    char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+}
+
+/// How an element differs between two images.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// The element is only in the new image.
+    Added,
+    /// The element is only in the old image.
+    Removed,
+    /// The element is in both images, but differs as described.
+    Changed(String),
+}
+
+/// A difference between two images.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// The element that differs, e.g. `FV 6d99e806-.../File 0d7c9c3c-.../Section 1 (pe32)`.
+    pub path: String,
+    /// How the element differs.
+    pub change: Change,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.change {
+            Change::Added => write!(f, "+ {}", self.path),
+            Change::Removed => write!(f, "- {}", self.path),
+            Change::Changed(description) => write!(f, "~ {}: {description}", self.path),
+        }
+    }
+}
+
+/// Compares the volumes of two images at file and section granularity.
+///
+/// Volumes are matched by name, or by position for volumes without a name, and files are matched by name. Sections
+/// are compared in order, with encapsulation sections compared through their extracted sub-sections and firmware
+/// volume image sections compared as volumes.
+pub fn diff(old: &[VolumeInfo], new: &[VolumeInfo]) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    diff_volumes("", old, new, &mut differences);
+    differences
+}
+
+fn volume_path(prefix: &str, index: usize, volume: &VolumeInfo) -> String {
+    match volume.name {
+        Some(name) => format!("{prefix}FV {}", format_guid(name)),
+        None => format!("{prefix}FV #{index}"),
+    }
+}
+
+fn diff_volumes(prefix: &str, old: &[VolumeInfo], new: &[VolumeInfo], differences: &mut Vec<Difference>) {
+    let old_paths: Vec<_> = old.iter().enumerate().map(|(index, volume)| volume_path(prefix, index, volume)).collect();
+    let new_paths: Vec<_> = new.iter().enumerate().map(|(index, volume)| volume_path(prefix, index, volume)).collect();
+
+    for (old_volume, path) in old.iter().zip(&old_paths) {
+        match new_paths.iter().position(|new_path| new_path == path) {
+            Some(index) => diff_volume(path, old_volume, &new[index], differences),
+            None => differences.push(Difference { path: path.clone(), change: Change::Removed }),
+        }
+    }
+    for path in new_paths.iter().filter(|path| !old_paths.contains(path)) {
+        differences.push(Difference { path: path.clone(), change: Change::Added });
+    }
+}
+
+fn diff_volume(path: &str, old: &VolumeInfo, new: &VolumeInfo, differences: &mut Vec<Difference>) {
+    let mut changed = |description: String| {
+        differences.push(Difference { path: path.to_string(), change: Change::Changed(description) })
+    };
+    if old.size != new.size {
+        changed(format!("size {:#x} -> {:#x}", old.size, new.size));
+    }
+    if old.attributes != new.attributes {
+        changed(format!("attributes {:#010x} -> {:#010x}", old.attributes, new.attributes));
+    }
+    if old.file_system_guid != new.file_system_guid {
+        changed("file system changed".to_string());
+    }
+
+    let mut matched = Vec::new();
+    for old_file in &old.files {
+        let file_path = format!("{path}/File {}", format_guid(old_file.name));
+        let new_file =
+            new.files.iter().enumerate().find(|(index, file)| file.name == old_file.name && !matched.contains(index));
+        match new_file {
+            Some((index, new_file)) => {
+                matched.push(index);
+                diff_file(&file_path, old_file, new_file, differences);
+            }
+            None => differences.push(Difference { path: file_path, change: Change::Removed }),
+        }
+    }
+    for (_, new_file) in new.files.iter().enumerate().filter(|(index, _)| !matched.contains(index)) {
+        differences
+            .push(Difference { path: format!("{path}/File {}", format_guid(new_file.name)), change: Change::Added });
+    }
+}
+
+fn diff_file(path: &str, old: &FileInfo, new: &FileInfo, differences: &mut Vec<Difference>) {
+    let count = differences.len();
+    let mut changed = |description: String| {
+        differences.push(Difference { path: path.to_string(), change: Change::Changed(description) })
+    };
+    if old.file_type != new.file_type {
+        changed(format!("type {} -> {}", file_type_name(old.file_type), file_type_name(new.file_type)));
+    }
+    if old.attributes != new.attributes {
+        changed(format!("attributes {:#04x} -> {:#04x}", old.attributes, new.attributes));
+    }
+    if old.data == new.data {
+        return;
+    }
+    diff_sections(path, &old.sections, &new.sections, differences);
+    if differences.len() == count {
+        differences.push(Difference {
+            path: path.to_string(),
+            change: Change::Changed(format!("content differs ({:#x} -> {:#x} bytes)", old.data.len(), new.data.len())),
+        });
+    }
+}
+
+fn diff_sections(path: &str, old: &[SectionInfo], new: &[SectionInfo], differences: &mut Vec<Difference>) {
+    let section_path =
+        |index: usize, section: &SectionInfo| format!("{path}/Section {index} ({})", describe_header(&section.header));
+    for (index, (old_section, new_section)) in old.iter().zip(new).enumerate() {
+        diff_section(&section_path(index, old_section), old_section, new_section, differences);
+    }
+    for (index, section) in old.iter().enumerate().skip(new.len()) {
+        differences.push(Difference { path: section_path(index, section), change: Change::Removed });
+    }
+    for (index, section) in new.iter().enumerate().skip(old.len()) {
+        differences.push(Difference { path: section_path(index, section), change: Change::Added });
+    }
+}
+
+fn diff_section(path: &str, old: &SectionInfo, new: &SectionInfo, differences: &mut Vec<Difference>) {
+    let (old_header, new_header) = (describe_header(&old.header), describe_header(&new.header));
+    if old_header != new_header {
+        differences.push(Difference {
+            path: path.to_string(),
+            change: Change::Changed(format!("{old_header} -> {new_header}")),
+        });
+        return;
+    }
+    if let (SectionHeader::Version(old_version, _), SectionHeader::Version(new_version, _)) = (&old.header, &new.header)
+    {
+        let (old_build, new_build) = (old_version.build_number, new_version.build_number);
+        if old_build != new_build {
+            differences.push(Difference {
+                path: path.to_string(),
+                change: Change::Changed(format!("build number {old_build} -> {new_build}")),
+            });
+        }
+    }
+    if old.content == new.content {
+        return;
+    }
+
+    let count = differences.len();
+    let extracted = !old.sections.is_empty() || !new.sections.is_empty();
+    if let (Some(old_volume), Some(new_volume)) = (&old.volume, &new.volume) {
+        diff_volume(&format!("{path}/FV"), old_volume, new_volume, differences);
+    } else if extracted {
+        diff_sections(path, &old.sections, &new.sections, differences);
+    }
+    // Extracted encapsulation sections whose sub-sections are identical differ only in their encoding.
+    if differences.len() == count && !extracted {
+        differences.push(Difference {
+            path: path.to_string(),
+            change: Change::Changed(format!(
+                "content differs ({:#x} -> {:#x} bytes)",
+                old.content.len(),
+                new.content.len()
+            )),
+        });
+    }
+}
+
+#[cfg(test)]
+#[coverage(off)]
+mod tests {
+    use super::*;
+    use crate::{build_volume, manifest::Manifest};
+    use alloc::vec;
+
+    const DRIVER_GUID: &str = "0d7c9c3c-3a3b-4c8e-9a4f-6c7e2b1d8f10";
+    const PROTOCOL_GUID: &str = "1e5668e2-8481-11d4-bcf1-0080c73c8881";
+    const INNER_FV_NAME: &str = "6d99e806-3d38-42c2-a095-5f4300bfd7dc";
+    const OUTER_FV_NAME: &str = "4d4f7a3c-62a6-4a8e-9b8a-5f3c2e1d0b9a";
+    const DATA_GUID: &str = "2c5a7d5e-9b1f-4f0e-8d3c-6a1b2c3d4e5f";
+    const FV_FILE_GUID: &str = "8b9c4d2e-1f3a-4b5c-9d6e-7f8a9b0c1d2e";
+
+    fn inner_manifest(build_number: u16, extra_file: bool) -> Manifest {
+        let extra = if extra_file {
+            format!(
+                "[[files]]\nname = \"{DATA_GUID}\"\ntype = \"freeform\"\nsections = [{{ type = \"raw\", path = \"data.bin\" }}]"
+            )
+        } else {
+            String::new()
+        };
+        Manifest::from_toml(&format!(
+            r#"
+            [volume]
+            name = "{INNER_FV_NAME}"
+            block_map = [{{ num_blocks = 0x8, length = 0x1000 }}]
+            attributes = 0x0004fe3f
+
+            [[files]]
+            name = "{DRIVER_GUID}"
+            type = "driver"
+            sections = [
+                {{ type = "depex", opcodes = [{{ push = "{PROTOCOL_GUID}" }}, "not"] }},
+                {{ type = "guided", algorithm = "lzma", sections = [
+                    {{ type = "pe32", path = "Driver.efi" }},
+                    {{ type = "ui", name = "Driver" }},
+                    {{ type = "version", build_number = {build_number} }},
+                ] }},
+            ]
+
+            {extra}
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn outer_manifest() -> Manifest {
+        Manifest::from_toml(&format!(
+            r#"
+            [volume]
+            name = "{OUTER_FV_NAME}"
+            block_map = [{{ num_blocks = 0x10, length = 0x1000 }}]
+            attributes = 0x0004fe3f
+
+            [[files]]
+            name = "{FV_FILE_GUID}"
+            type = "firmware_volume_image"
+            sections = [{{ type = "firmware_volume_image", path = "inner.fv" }}]
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn flash_image(build_number: u16, extra_file: bool) -> Vec<u8> {
+        let inner = build_volume(&inner_manifest(build_number, extra_file), |path| match path {
+            "Driver.efi" => Ok((0..0x1000u32).map(|x| (x % 251) as u8).collect()),
+            "data.bin" => Ok(b"Hello, World!".repeat(10)),
+            _ => Err("not found".to_string()),
+        })
+        .unwrap();
+        let outer = build_volume(&outer_manifest(), |path| match path {
+            "inner.fv" => Ok(inner.clone()),
+            _ => Err("not found".to_string()),
+        })
+        .unwrap();
+
+        let mut flash = vec![0xffu8; 0x1000];
+        flash.extend(&inner);
+        flash.extend(&outer);
+        flash
+    }
+
+    fn guid(guid: &str) -> efi::Guid {
+        efi::Guid::from_bytes(&uuid::Uuid::parse_str(guid).unwrap().to_bytes_le())
+    }
+
+    #[test]
+    fn test_file_type_names_round_trip() {
+        for file_type in 0..=u8::MAX {
+            if let Some(known) = FileType::from_raw(file_type) {
+                assert_eq!(known.raw(), file_type);
+            }
+        }
+        assert_eq!(file_type_name(r#type::DRIVER), "driver");
+        assert_eq!(file_type_name(0xc0), "0xc0");
+    }
+
+    #[test]
+    fn test_find_volumes() {
+        let flash = flash_image(1, false);
+        let volumes = find_volumes(&flash);
+        assert_eq!(volumes.len(), 2);
+
+        let inner = &volumes[0];
+        assert_eq!((inner.offset, inner.size), (0x1000, 0x8000));
+        assert_eq!(inner.name, Some(guid(INNER_FV_NAME)));
+        assert!(inner.error.is_none());
+        assert_eq!(inner.files.len(), 1);
+
+        let driver = &inner.files[0];
+        assert_eq!(driver.name, guid(DRIVER_GUID));
+        assert_eq!(driver.file_type, r#type::DRIVER);
+        assert_eq!(driver.sections.len(), 2);
+        let depex = driver.sections[0].depex.as_ref().unwrap();
+        assert!(matches!(depex.as_slice(), [Opcode::Push(_, _), Opcode::Not, Opcode::End]));
+        let leaves: Vec<_> = driver.sections.iter().flat_map(SectionInfo::leaves).map(SectionInfo::type_name).collect();
+        assert_eq!(leaves, ["dxe_depex", "pe32", "ui", "version"]);
+
+        let outer = &volumes[1];
+        assert_eq!((outer.offset, outer.size), (0x9000, 0x10000));
+        let nested = outer.files[0].sections[0].volume.as_ref().unwrap();
+        assert_eq!(nested.name, Some(guid(INNER_FV_NAME)));
+        assert_eq!(nested.files.len(), 1);
+    }
+
+    #[test]
+    fn test_write_tree() {
+        let mut tree = String::new();
+        write_tree(&mut tree, &find_volumes(&flash_image(7, false))).unwrap();
+
+        assert!(tree.contains(&format!("FV @ 0x1000, 0x8000 bytes, name {INNER_FV_NAME}")));
+        assert!(tree.contains(&format!("File {DRIVER_GUID} driver")));
+        assert!(tree.contains(&format!("dxe_depex, 0x13 bytes: NOT {PROTOCOL_GUID}")));
+        assert!(tree.contains("guided (lzma)"));
+        assert!(tree.contains("ui, 0xe bytes: \"Driver\""));
+        assert!(tree.contains("version, 0x0 bytes: build 7 \"\""));
+        assert!(tree.contains(&format!("File {FV_FILE_GUID} firmware_volume_image")));
+        assert_eq!(tree.matches(&format!("name {INNER_FV_NAME}")).count(), 2);
+    }
+
+    #[test]
+    fn test_serializable_volumes() {
+        let flash = flash_image(1, false);
+        let volumes = find_volumes(&flash);
+        let serializable = serializable_volumes(&flash, &volumes);
+        assert_eq!(serializable.len(), 2);
+        assert_eq!(serializable[0].fv_base_address, 0x1000);
+        assert_eq!(serializable[1].fv_base_address, 0x9000);
+        assert!(serde_json::to_string(&serializable).is_ok());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = find_volumes(&flash_image(1, false));
+        assert!(diff(&old, &old).is_empty());
+
+        let new = find_volumes(&flash_image(2, true));
+        let differences: Vec<_> = diff(&old, &new).iter().map(|difference| difference.to_string()).collect();
+        let inner = format!("FV {INNER_FV_NAME}");
+        let driver = format!("File {DRIVER_GUID}/Section 1 (guided (lzma))/Section 2 (version)");
+        assert!(differences.contains(&format!("~ {inner}/{driver}: build number 1 -> 2")));
+        assert!(differences.contains(&format!("+ {inner}/File {DATA_GUID}")));
+        let nested = format!("FV {OUTER_FV_NAME}/File {FV_FILE_GUID}/Section 0 (firmware_volume_image)/FV");
+        assert!(differences.contains(&format!("~ {nested}/{driver}: build number 1 -> 2")));
+        assert!(differences.contains(&format!("+ {nested}/File {DATA_GUID}")));
+        assert_eq!(differences.len(), 4, "{differences:#?}");
+
+        let removed: Vec<_> = diff(&new, &old[..1]).iter().map(|difference| difference.to_string()).collect();
+        assert!(removed.contains(&format!("- FV {OUTER_FV_NAME}")));
+        assert!(removed.contains(&format!("- {inner}/File {DATA_GUID}")));
+    }
+}
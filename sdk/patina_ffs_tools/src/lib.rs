@@ -0,0 +1,59 @@
+//! # Firmware Volume Tools
+//!
+//! Host tools for producing and inspecting firmware volumes with `patina_ffs`, so that a platform can build its
+//! volumes without the EDK II `GenSec`, `GenFfs` and `GenFv` tools.
+//!
+//! The [manifest] module describes a firmware volume and its files in TOML or JSON, and [build_volume] turns a
+//! manifest into a volume image. Dependency expressions are given either as opcodes or in the EDK II text grammar. Building is reproducible: the same manifest and inputs always produce the same image.
+//!
+//! The [inspect] module finds the volumes of an FV or flash image, parses them into a file and section tree, and
+//! compares the trees of two images.
+//!
+//! ## Features
+//!
+//! - `std`: Builds the `fv_builder` binary, which builds the volume described by a manifest file:
+//!   `fv_builder MyVolume.toml -o MyVolume.Fv`. Input paths are relative to the directory of the manifest.
+//!   Also builds the `fv_inspect` binary, which prints the file and section tree of an image (`fv_inspect tree`),
+//!   prints its volumes as JSON (`fv_inspect json`), extracts its leaf sections to a directory (`fv_inspect extract`),
+//!   and compares two images (`fv_inspect diff`).
+//!
+//! ## Example Manifest
+//!
+//! ```toml
+//! [guids]
+//! gMyDriverGuid = "0d7c9c3c-3a3b-4c8e-9a4f-6c7e2b1d8f10"
+//! gEfiVariableArchProtocolGuid = "1e5668e2-8481-11d4-bcf1-0080c73c8881"
+//!
+//! [volume]
+//! name = "6d99e806-3d38-42c2-a095-5f4300bfd7dc"
+//! block_map = [{ num_blocks = 0x40, length = 0x1000 }]
+//! attributes = 0x0004fe3f
+//!
+//! [[files]]
+//! name = "gMyDriverGuid"
+//! type = "driver"
+//! alignment = 0x1000
+//! sections = [
+//!     { type = "depex", expression = "gEfiVariableArchProtocolGuid" },
+//!     { type = "guided", algorithm = "lzma", sections = [
+//!         { type = "pe32", path = "MyDriver.efi" },
+//!         { type = "ui", name = "MyDriver" },
+//!     ] },
+//! ]
+//! ```
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+#![feature(coverage_attribute)]
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+pub mod builder;
+pub mod inspect;
+pub mod manifest;
+
+pub use builder::{BuildError, build_volume};
@@ -0,0 +1,334 @@
+//! Firmware volume manifest definitions.
+//!
+//! A manifest describes the content of a firmware volume: its block map and attributes, and the files it holds along
+//! with their sections. Manifests are written in TOML or JSON; both formats deserialize into the same [Manifest].
+//!
+//! GUIDs are written either in registry format (`"8a8a6b39-1e6b-4fd7-9c7e-0d6b3c7b4a55"`) or as the name of an
+//! entry in the manifest's `guids` table, so that the same GUID can be referenced by name from several places.
+//!
+//! ## License
+//!
+//! Copyright (c) Microsoft Corporation.
+//!
+//! SPDX-License-Identifier: Apache-2.0
+//!
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use patina::pi::fw_fs::ffs::file::raw::r#type;
+use serde::Deserialize;
+
+use crate::BuildError;
+
+/// A firmware volume manifest.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Named GUIDs that the rest of the manifest can refer to by name.
+    #[serde(default)]
+    pub guids: BTreeMap<String, String>,
+    /// The volume header settings.
+    pub volume: VolumeConfig,
+    /// The files of the volume, in the order they are placed in it.
+    #[serde(default)]
+    pub files: Vec<FileConfig>,
+}
+
+impl Manifest {
+    /// Parses a manifest written in TOML.
+    pub fn from_toml(manifest: &str) -> Result<Self, BuildError> {
+        toml::from_str(manifest).map_err(|err| BuildError::Manifest(err.to_string()))
+    }
+
+    /// Parses a manifest written in JSON.
+    pub fn from_json(manifest: &str) -> Result<Self, BuildError> {
+        serde_json::from_str(manifest).map_err(|err| BuildError::Manifest(err.to_string()))
+    }
+}
+
+/// Volume header settings.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeConfig {
+    /// The block map of the volume. The volume is padded to the total size of the blocks.
+    pub block_map: Vec<BlockMapConfig>,
+    /// The `EFI_FVB_ATTRIBUTES_2` value of the volume header. `EFI_FVB2_ERASE_POLARITY` selects the erase byte.
+    #[serde(default)]
+    pub attributes: u32,
+    /// The file system of the volume.
+    #[serde(default)]
+    pub file_system: FileSystem,
+    /// The name of the volume. When set, an extended header carrying the name is added to the volume.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// A block map entry of the volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockMapConfig {
+    /// Number of blocks of this length.
+    pub num_blocks: u32,
+    /// Length of each block, in bytes.
+    pub length: u32,
+}
+
+/// The file system of a volume.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSystem {
+    /// `EFI_FIRMWARE_FILE_SYSTEM2_GUID`, which cannot hold files of 16 MiB or more.
+    Ffs2,
+    /// `EFI_FIRMWARE_FILE_SYSTEM3_GUID`.
+    #[default]
+    Ffs3,
+}
+
+/// A file of the volume.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// The name GUID of the file.
+    pub name: String,
+    /// The type of the file.
+    #[serde(rename = "type")]
+    pub file_type: FileType,
+    /// Required alignment of the file data, in bytes. Must be a power of two no larger than 16 MiB.
+    #[serde(default)]
+    pub alignment: Option<u32>,
+    /// Whether the file must stay at its offset in the volume (`FFS_ATTRIB_FIXED`).
+    #[serde(default)]
+    pub fixed: bool,
+    /// Whether to compute a checksum over the file data (`FFS_ATTRIB_CHECKSUM`).
+    #[serde(default)]
+    pub checksum: bool,
+    /// Input whose bytes are the file content, for files that are not made of sections (e.g. `raw` files).
+    #[serde(default)]
+    pub path: Option<String>,
+    /// The sections of the file. Mutually exclusive with `path`.
+    #[serde(default)]
+    pub sections: Vec<SectionConfig>,
+}
+
+/// The type of a file (`EFI_FV_FILETYPE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileType {
+    /// `EFI_FV_FILETYPE_RAW`.
+    Raw,
+    /// `EFI_FV_FILETYPE_FREEFORM`.
+    Freeform,
+    /// `EFI_FV_FILETYPE_SECURITY_CORE`.
+    SecurityCore,
+    /// `EFI_FV_FILETYPE_PEI_CORE`.
+    PeiCore,
+    /// `EFI_FV_FILETYPE_DXE_CORE`.
+    DxeCore,
+    /// `EFI_FV_FILETYPE_PEIM`.
+    Peim,
+    /// `EFI_FV_FILETYPE_DRIVER`.
+    Driver,
+    /// `EFI_FV_FILETYPE_COMBINED_PEIM_DRIVER`.
+    CombinedPeimDriver,
+    /// `EFI_FV_FILETYPE_APPLICATION`.
+    Application,
+    /// `EFI_FV_FILETYPE_MM`.
+    Mm,
+    /// `EFI_FV_FILETYPE_FIRMWARE_VOLUME_IMAGE`.
+    FirmwareVolumeImage,
+    /// `EFI_FV_FILETYPE_COMBINED_MM_DXE`.
+    CombinedMmDxe,
+    /// `EFI_FV_FILETYPE_MM_CORE`.
+    MmCore,
+    /// `EFI_FV_FILETYPE_MM_STANDALONE`.
+    MmStandalone,
+    /// `EFI_FV_FILETYPE_MM_CORE_STANDALONE`.
+    MmCoreStandalone,
+    /// `EFI_FV_FILETYPE_FFS_PAD`.
+    Pad,
+}
+
+impl FileType {
+    const TYPES: [(FileType, u8, &'static str); 16] = [
+        (FileType::Raw, r#type::RAW, "raw"),
+        (FileType::Freeform, r#type::FREEFORM, "freeform"),
+        (FileType::SecurityCore, r#type::SECURITY_CORE, "security_core"),
+        (FileType::PeiCore, r#type::PEI_CORE, "pei_core"),
+        (FileType::DxeCore, r#type::DXE_CORE, "dxe_core"),
+        (FileType::Peim, r#type::PEIM, "peim"),
+        (FileType::Driver, r#type::DRIVER, "driver"),
+        (FileType::CombinedPeimDriver, r#type::COMBINED_PEIM_DRIVER, "combined_peim_driver"),
+        (FileType::Application, r#type::APPLICATION, "application"),
+        (FileType::Mm, r#type::MM, "mm"),
+        (FileType::FirmwareVolumeImage, r#type::FIRMWARE_VOLUME_IMAGE, "firmware_volume_image"),
+        (FileType::CombinedMmDxe, r#type::COMBINED_MM_DXE, "combined_mm_dxe"),
+        (FileType::MmCore, r#type::MM_CORE, "mm_core"),
+        (FileType::MmStandalone, r#type::MM_STANDALONE, "mm_standalone"),
+        (FileType::MmCoreStandalone, r#type::MM_CORE_STANDALONE, "mm_core_standalone"),
+        (FileType::Pad, r#type::FFS_PAD, "pad"),
+    ];
+
+    /// The raw `EFI_FV_FILETYPE` value of the file type.
+    pub fn raw(self) -> u8 {
+        Self::TYPES.iter().find(|(file_type, _, _)| *file_type == self).map(|(_, raw, _)| *raw).unwrap()
+    }
+
+    /// The file type with the raw `EFI_FV_FILETYPE` value `raw`, if a manifest can describe it.
+    pub fn from_raw(raw: u8) -> Option<Self> {
+        Self::TYPES.iter().find(|(_, file_type, _)| *file_type == raw).map(|(file_type, _, _)| *file_type)
+    }
+
+    /// The name of the file type in a manifest.
+    pub fn name(self) -> &'static str {
+        Self::TYPES.iter().find(|(file_type, _, _)| *file_type == self).map(|(_, _, name)| *name).unwrap()
+    }
+}
+
+/// A section of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SectionConfig {
+    /// A PE32 image read from `path`.
+    Pe32 {
+        /// Input holding the section content.
+        path: String,
+    },
+    /// A position independent image read from `path`.
+    Pic {
+        /// Input holding the section content.
+        path: String,
+    },
+    /// A TE image read from `path`.
+    Te {
+        /// Input holding the section content.
+        path: String,
+    },
+    /// Raw data read from `path`.
+    Raw {
+        /// Input holding the section content.
+        path: String,
+    },
+    /// A 16-bit compatibility image read from `path`.
+    Compatibility16 {
+        /// Input holding the section content.
+        path: String,
+    },
+    /// A firmware volume image read from `path`.
+    FirmwareVolumeImage {
+        /// Input holding the section content.
+        path: String,
+    },
+    /// Data read from `path`, tagged with the `guid` of its format.
+    FreeformSubtypeGuid {
+        /// The subtype GUID of the section.
+        guid: String,
+        /// Input holding the section content.
+        path: String,
+    },
+    /// A user interface name, stored as a null-terminated UTF-16 string.
+    Ui {
+        /// The name of the file.
+        name: String,
+    },
+    /// A version section with a build number and optional version string.
+    Version {
+        /// The build number.
+        #[serde(default)]
+        build_number: u16,
+        /// The version string, stored as a null-terminated UTF-16 string.
+        #[serde(default)]
+        version: Option<String>,
+    },
+    /// A dependency expression.
+    Depex {
+        /// The phase the expression is evaluated in.
+        #[serde(default)]
+        phase: DepexPhase,
+        /// The opcodes of the expression. A trailing `end` is added when missing.
+        #[serde(default)]
+        opcodes: Vec<DepexOpcode>,
+        /// The expression in the EDK II text grammar, e.g. `gFooGuid AND NOT gBarGuid`, as an alternative to
+        /// `opcodes`. Names are resolved with the `guids` table.
+        expression: Option<String>,
+    },
+    /// A compression section holding `sections`.
+    Compression {
+        /// The compression applied to the sub-sections.
+        algorithm: CompressionAlgorithm,
+        /// The sub-sections of the section.
+        sections: Vec<SectionConfig>,
+    },
+    /// A GUID-defined section holding `sections`, encoded with `algorithm`.
+    Guided {
+        /// The encoding applied to the sub-sections.
+        algorithm: GuidedAlgorithm,
+        /// The sub-sections of the section.
+        sections: Vec<SectionConfig>,
+    },
+}
+
+/// The phase a dependency expression section is evaluated in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepexPhase {
+    /// `EFI_SECTION_DXE_DEPEX`.
+    #[default]
+    Dxe,
+    /// `EFI_SECTION_PEI_DEPEX`.
+    Pei,
+    /// `EFI_SECTION_MM_DEPEX`.
+    Mm,
+}
+
+/// A dependency expression opcode. Opcodes that take a GUID are written as a table, e.g. `{ push = "gFooGuid" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepexOpcode {
+    /// Schedule before the file with the given name GUID.
+    Before(String),
+    /// Schedule after the file with the given name GUID.
+    After(String),
+    /// Push whether the protocol with the given GUID is installed.
+    Push(String),
+    /// Logical and of the two top values.
+    And,
+    /// Logical or of the two top values.
+    Or,
+    /// Logical not of the top value.
+    Not,
+    /// Push true.
+    True,
+    /// Push false.
+    False,
+    /// End of the expression.
+    End,
+    /// Schedule on request.
+    Sor,
+}
+
+/// The compression of a compression section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionAlgorithm {
+    /// The sub-sections are stored as-is.
+    None,
+    /// The sub-sections are compressed with the UEFI compression algorithm.
+    Standard,
+}
+
+/// The encoding of a GUID-defined section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuidedAlgorithm {
+    /// Brotli compression.
+    Brotli,
+    /// Stored as-is with a CRC32 of the content.
+    Crc32,
+    /// LZMA compression.
+    Lzma,
+    /// Tiano compression.
+    Tiano,
+}
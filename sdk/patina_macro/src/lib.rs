@@ -251,6 +251,25 @@ pub fn smbios_record(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
 /// }
 /// ```
 ///
+/// ## Event Triggers
+///
+/// `event = path::to::EVENT_GROUP_GUID` defers the component until the event group is signalled, for example to run at
+/// ReadyToBoot. Once the event group has been signalled, the component is dispatched like any other as soon as its
+/// parameters are available.
+/// The ExitBootServices and BeforeExitBootServices event groups are not supported; a component naming either fails to
+/// initialize.
+///
+/// ```rust, ignore
+/// pub struct MyComponent;
+///
+/// #[component(event = patina::guids::EVENT_GROUP_END_OF_DXE)]
+/// impl MyComponent {
+///     fn entry_point(self, config: Config<u32>) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+/// ```
+///
 /// ## Validation Rules
 ///
 /// - Impl block must contain an `entry_point` method
@@ -9,7 +9,10 @@
 
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{FnArg, ImplItem, ItemFn, ItemImpl, Pat, Type, TypePath, parse2, spanned::Spanned};
+use syn::{
+    FnArg, ImplItem, ItemFn, ItemImpl, Meta, Pat, Token, Type, TypePath, parse::Parser, parse2, punctuated::Punctuated,
+    spanned::Spanned,
+};
 
 /// Validates component impl blocks with a unified `#[component]` attribute.
 ///
@@ -19,6 +22,9 @@ use syn::{FnArg, ImplItem, ItemFn, ItemImpl, Pat, Type, TypePath, parse2, spanne
 /// 3. Validate the entry_point parameters for conflicts
 /// 4. Generate the IntoComponent trait implementation
 ///
+/// The attribute accepts `event = path::to::EVENT_GROUP_GUID`, which defers the component until that event group is
+/// signalled.
+///
 /// ## Usage
 ///
 /// Apply to the impl block:
@@ -44,10 +50,21 @@ use syn::{FnArg, ImplItem, ItemFn, ItemImpl, Pat, Type, TypePath, parse2, spanne
 /// - Cannot have multiple `Commands` parameters or multiple service table parameters
 /// - Cannot have multiple `&mut Storage` parameters (only one mutable reference is allowed)
 /// - Cannot mix `&Storage` and `&mut Storage` parameters (a mutable reference must be exclusive)
-pub(crate) fn component_entry_point(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub(crate) fn component_entry_point(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let trigger = match parse_component_attr(attr) {
+        Ok(trigger) => trigger,
+        Err(error) => {
+            let error = error.to_compile_error();
+            return quote! {
+                #error
+                #item
+            };
+        }
+    };
+
     // Try to parse as an impl block
     if let Ok(impl_block) = parse2::<ItemImpl>(item.clone()) {
-        return validate_component_impl_block(impl_block);
+        return validate_component_impl_block(impl_block, trigger);
     }
 
     // If not an impl block, return error
@@ -57,8 +74,33 @@ pub(crate) fn component_entry_point(_attr: TokenStream, item: TokenStream) -> To
     }
 }
 
+/// Parses the `#[component(...)]` arguments into the builder calls to apply to the generated component.
+fn parse_component_attr(attr: TokenStream) -> syn::Result<TokenStream> {
+    let parser = Punctuated::<Meta, Token![,]>::parse_terminated;
+    let mut trigger = None;
+    for meta in parser.parse2(attr)? {
+        match meta {
+            // #[component(event = module_path_to_guid)]
+            Meta::NameValue(nv) if nv.path.is_ident("event") && trigger.is_none() => {
+                let value = &nv.value;
+                trigger = Some(quote!(.with_trigger(#value)));
+            }
+            Meta::NameValue(nv) if nv.path.is_ident("event") => {
+                return Err(syn::Error::new(nv.span(), "A component can only be triggered by a single event group."));
+            }
+            _ => {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "Unsupported #[component] argument. Expected `event = path::to::EVENT_GROUP_GUID`.",
+                ));
+            }
+        }
+    }
+    Ok(trigger.unwrap_or_default())
+}
+
 /// Validates a component impl block and generates the IntoComponent implementation.
-fn validate_component_impl_block(impl_block: ItemImpl) -> TokenStream {
+fn validate_component_impl_block(impl_block: ItemImpl, trigger: TokenStream) -> TokenStream {
     // Extract the type name from the impl block
     let type_path = match &*impl_block.self_ty {
         Type::Path(type_path) => type_path,
@@ -176,7 +218,7 @@ fn validate_component_impl_block(impl_block: ItemImpl) -> TokenStream {
                     patina::component::StructComponent::new(
                         #entry_point_fn,
                         self
-                    )
+                    )#trigger
                 )
             }
         }
@@ -575,6 +617,32 @@ mod tests {
         assert!(result.to_string().contains("entry_point"));
     }
 
+    #[test]
+    fn test_component_entry_point_event_trigger() {
+        let input = quote! {
+            impl MyComponent {
+                fn entry_point(self, config: Config<u32>) -> Result<()> {
+                    Ok(())
+                }
+            }
+        };
+
+        let result = component_entry_point(quote!(event = patina::guids::EVENT_GROUP_END_OF_DXE), input.clone());
+        assert!(!result.to_string().contains("compile_error"));
+        assert!(result.to_string().contains(". with_trigger (patina :: guids :: EVENT_GROUP_END_OF_DXE)"));
+
+        let result = component_entry_point(quote!(), input.clone());
+        assert!(!result.to_string().contains("with_trigger"));
+
+        let result = component_entry_point(quote!(timer = 100), input.clone());
+        assert!(result.to_string().contains("compile_error"));
+        assert!(result.to_string().contains("Unsupported #[component] argument"));
+
+        let result = component_entry_point(quote!(event = A, event = B), input);
+        assert!(result.to_string().contains("compile_error"));
+        assert!(result.to_string().contains("single event group"));
+    }
+
     #[test]
     fn test_component_entry_point_validates_params() {
         let input = quote! {